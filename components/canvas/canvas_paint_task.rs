@@ -5,17 +5,27 @@
 use azure::azure::AzFloat;
 use azure::azure_hl::{DrawTarget, SurfaceFormat, BackendType, StrokeOptions, DrawOptions, Pattern};
 use azure::azure_hl::{ColorPattern, PathBuilder, JoinStyle, CapStyle, DrawSurfaceOptions, Filter};
+use azure::azure_hl::CompositionOp;
 use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
+use gfx::ScaledFontExtensionMethods;
 use gfx::color;
+use gfx::font::{ShapingFlags, ShapingOptions, SpecifiedFontStyle};
+use gfx::font_cache_task::FontCacheTask;
+use gfx::font_context::FontContext;
+use gfx::text::glyph::CharIndex;
+use gfx::text::text_run::TextRun;
+use util::geometry::Au;
+use util::range::Range;
 use util::task::spawn_named;
 use util::vec::byte_swap;
 
 use cssparser::RGBA;
 use std::borrow::ToOwned;
 use std::ops::Add;
+use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender};
 
 #[derive(Clone)]
@@ -26,10 +36,30 @@ pub enum CanvasMsg {
     BeginPath,
     ClosePath,
     Fill,
+    Stroke,
+    Clip,
     MoveTo(Point2D<f32>),
+    LineTo(Point2D<f32>),
+    Rect(Rect<f32>),
+    QuadraticCurveTo(Point2D<f32>, Point2D<f32>),
     BezierCurveTo(Point2D<f32>, Point2D<f32>, Point2D<f32>),
+    Arc(Point2D<f32>, f32, f32, f32, bool),
+    DrawImage(Vec<u8>, Size2D<i32>, Rect<f32>, Rect<f32>),
+    FillText(String, Point2D<f32>, Option<f32>),
+    MeasureText(String, Sender<f32>),
+    SaveContext,
+    RestoreContext,
     SetFillStyle(FillOrStrokeStyle),
     SetStrokeStyle(FillOrStrokeStyle),
+    SetLineWidth(f32),
+    SetLineCap(LineCapStyle),
+    SetLineJoin(LineJoinStyle),
+    SetMiterLimit(f32),
+    SetGlobalAlpha(f32),
+    SetGlobalComposition(CompositionStyle),
+    SetFont(Arc<SpecifiedFontStyle>),
+    SetTextAlign(TextAlign),
+    SetTextBaseline(TextBaseline),
     SetTransform(Matrix2D<f32>),
     Recreate(Size2D<i32>),
     SendPixelContents(Sender<Vec<u8>>),
@@ -38,35 +68,79 @@ pub enum CanvasMsg {
     Close,
 }
 
-pub struct CanvasPaintTask<'a> {
+/// The drawing state that is pushed and popped by `save()` and `restore()`.
+/// http://www.whatwg.org/html/#the-canvas-state
+#[derive(Clone)]
+struct CanvasPaintState {
+    fill_style: FillOrStrokeStyle,
+    stroke_style: FillOrStrokeStyle,
+    line_width: f32,
+    line_cap: LineCapStyle,
+    line_join: LineJoinStyle,
+    miter_limit: f32,
+    global_alpha: f32,
+    global_composition: CompositionStyle,
+    /// The current 2D transform matrix.
+    transform: Matrix2D<f32>,
+    font: Option<Arc<SpecifiedFontStyle>>,
+    text_align: TextAlign,
+    text_baseline: TextBaseline,
+    /// The number of clips pushed onto the draw target, including those of the saved states.
+    clip_count: usize,
+}
+
+impl CanvasPaintState {
+    fn new() -> CanvasPaintState {
+        let black = RGBA {
+            red: 0.0,
+            green: 0.0,
+            blue: 0.0,
+            alpha: 1.0,
+        };
+        CanvasPaintState {
+            fill_style: FillOrStrokeStyle::Color(black),
+            stroke_style: FillOrStrokeStyle::Color(black),
+            line_width: 1.0,
+            line_cap: LineCapStyle::Butt,
+            line_join: LineJoinStyle::Miter,
+            miter_limit: 10.0,
+            global_alpha: 1.0,
+            global_composition: CompositionStyle::SrcOver,
+            transform: Matrix2D::identity(),
+            font: None,
+            text_align: TextAlign::Start,
+            text_baseline: TextBaseline::Alphabetic,
+            clip_count: 0,
+        }
+    }
+}
+
+pub struct CanvasPaintTask {
     drawtarget: DrawTarget,
-    fill_style: Pattern,
-    stroke_style: Pattern,
-    stroke_opts: StrokeOptions<'a>,
     /// TODO(pcwalton): Support multiple paths.
     path_builder: PathBuilder,
-    /// The current 2D transform matrix.
-    transform: Matrix2D<f32>,
+    state: CanvasPaintState,
+    saved_states: Vec<CanvasPaintState>,
+    font_context: FontContext,
 }
 
-impl<'a> CanvasPaintTask<'a> {
-    fn new(size: Size2D<i32>) -> CanvasPaintTask<'a> {
+impl CanvasPaintTask {
+    fn new(size: Size2D<i32>, font_cache_task: FontCacheTask) -> CanvasPaintTask {
         let draw_target = CanvasPaintTask::create(size);
         let path_builder = draw_target.create_path_builder();
         CanvasPaintTask {
             drawtarget: draw_target,
-            fill_style: Pattern::Color(ColorPattern::new(color::black())),
-            stroke_style: Pattern::Color(ColorPattern::new(color::black())),
-            stroke_opts: StrokeOptions::new(1.0, JoinStyle::MiterOrBevel, CapStyle::Butt, 1.0, &[]),
             path_builder: path_builder,
-            transform: Matrix2D::identity(),
+            state: CanvasPaintState::new(),
+            saved_states: vec!(),
+            font_context: FontContext::new(font_cache_task),
         }
     }
 
-    pub fn start(size: Size2D<i32>) -> Sender<CanvasMsg> {
+    pub fn start(size: Size2D<i32>, font_cache_task: FontCacheTask) -> Sender<CanvasMsg> {
         let (chan, port) = channel::<CanvasMsg>();
         spawn_named("CanvasTask".to_owned(), move || {
-            let mut painter = CanvasPaintTask::new(size);
+            let mut painter = CanvasPaintTask::new(size, font_cache_task);
 
             loop {
                 match port.recv().unwrap() {
@@ -76,12 +150,40 @@ impl<'a> CanvasPaintTask<'a> {
                     CanvasMsg::BeginPath => painter.begin_path(),
                     CanvasMsg::ClosePath => painter.close_path(),
                     CanvasMsg::Fill => painter.fill(),
+                    CanvasMsg::Stroke => painter.stroke(),
+                    CanvasMsg::Clip => painter.clip(),
                     CanvasMsg::MoveTo(ref point) => painter.move_to(point),
+                    CanvasMsg::LineTo(ref point) => painter.line_to(point),
+                    CanvasMsg::Rect(ref rect) => painter.rect(rect),
+                    CanvasMsg::QuadraticCurveTo(ref cp, ref pt) => {
+                        painter.quadratic_curve_to(cp, pt)
+                    }
                     CanvasMsg::BezierCurveTo(ref cp1, ref cp2, ref pt) => {
                         painter.bezier_curve_to(cp1, cp2, pt)
                     }
-                    CanvasMsg::SetFillStyle(style) => painter.set_fill_style(style),
-                    CanvasMsg::SetStrokeStyle(style) => painter.set_stroke_style(style),
+                    CanvasMsg::Arc(ref center, radius, start, end, ccw) => {
+                        painter.arc(center, radius, start, end, ccw)
+                    }
+                    CanvasMsg::DrawImage(imagedata, image_size, dest_rect, source_rect) => {
+                        painter.draw_image(imagedata, image_size, dest_rect, source_rect)
+                    }
+                    CanvasMsg::FillText(text, ref point, max_width) => {
+                        painter.fill_text(text, point, max_width)
+                    }
+                    CanvasMsg::MeasureText(text, chan) => painter.measure_text(text, chan),
+                    CanvasMsg::SaveContext => painter.save_context_state(),
+                    CanvasMsg::RestoreContext => painter.restore_context_state(),
+                    CanvasMsg::SetFillStyle(style) => painter.state.fill_style = style,
+                    CanvasMsg::SetStrokeStyle(style) => painter.state.stroke_style = style,
+                    CanvasMsg::SetLineWidth(width) => painter.state.line_width = width,
+                    CanvasMsg::SetLineCap(cap) => painter.state.line_cap = cap,
+                    CanvasMsg::SetLineJoin(join) => painter.state.line_join = join,
+                    CanvasMsg::SetMiterLimit(limit) => painter.state.miter_limit = limit,
+                    CanvasMsg::SetGlobalAlpha(alpha) => painter.state.global_alpha = alpha,
+                    CanvasMsg::SetGlobalComposition(op) => painter.state.global_composition = op,
+                    CanvasMsg::SetFont(font) => painter.state.font = Some(font),
+                    CanvasMsg::SetTextAlign(align) => painter.state.text_align = align,
+                    CanvasMsg::SetTextBaseline(baseline) => painter.state.text_baseline = baseline,
                     CanvasMsg::SetTransform(ref matrix) => painter.set_transform(matrix),
                     CanvasMsg::Recreate(size) => painter.recreate(size),
                     CanvasMsg::SendPixelContents(chan) => painter.send_pixel_contents(chan),
@@ -95,9 +197,25 @@ impl<'a> CanvasPaintTask<'a> {
         chan
     }
 
+    /// The options that apply `globalAlpha` and `globalCompositeOperation` to a drawing.
+    fn draw_options(&self) -> DrawOptions {
+        let mut draw_options = DrawOptions::new(self.state.global_alpha, 0);
+        draw_options.set_composition_op(self.state.global_composition.to_azure_composition_op());
+        draw_options
+    }
+
+    fn stroke_options(&self) -> StrokeOptions {
+        StrokeOptions::new(self.state.line_width,
+                           self.state.line_join.to_azure_style(),
+                           self.state.line_cap.to_azure_style(),
+                           self.state.miter_limit,
+                           &[])
+    }
+
     fn fill_rect(&self, rect: &Rect<f32>) {
-        let drawopts = DrawOptions::new(1.0, 0);
-        self.drawtarget.fill_rect(rect, self.fill_style.to_pattern_ref(), Some(&drawopts));
+        let draw_options = self.draw_options();
+        let pattern = self.state.fill_style.to_azure_pattern();
+        self.drawtarget.fill_rect(rect, pattern.to_pattern_ref(), Some(&draw_options));
     }
 
     fn clear_rect(&self, rect: &Rect<f32>) {
@@ -105,10 +223,10 @@ impl<'a> CanvasPaintTask<'a> {
     }
 
     fn stroke_rect(&self, rect: &Rect<f32>) {
-        let drawopts = DrawOptions::new(1.0, 0);
-        match self.stroke_style {
+        let draw_options = self.draw_options();
+        match self.state.stroke_style.to_azure_pattern() {
             Pattern::Color(ref color) => {
-                self.drawtarget.stroke_rect(rect, color, &self.stroke_opts, &drawopts)
+                self.drawtarget.stroke_rect(rect, color, &self.stroke_options(), &draw_options)
             }
             _ => {
                 // TODO(pcwalton)
@@ -125,8 +243,8 @@ impl<'a> CanvasPaintTask<'a> {
     }
 
     fn fill(&self) {
-        let draw_options = DrawOptions::new(1.0, 0);
-        match self.fill_style {
+        let draw_options = self.draw_options();
+        match self.state.fill_style.to_azure_pattern() {
             Pattern::Color(ref color) => {
                 self.drawtarget.fill(&self.path_builder.finish(), color, &draw_options);
             }
@@ -136,10 +254,46 @@ impl<'a> CanvasPaintTask<'a> {
         };
     }
 
+    fn stroke(&self) {
+        let draw_options = self.draw_options();
+        match self.state.stroke_style.to_azure_pattern() {
+            Pattern::Color(ref color) => {
+                self.drawtarget.stroke(&self.path_builder.finish(), color,
+                                       &self.stroke_options(), &draw_options);
+            }
+            _ => {}
+        };
+    }
+
+    fn clip(&mut self) {
+        self.drawtarget.push_clip(&self.path_builder.finish());
+        self.state.clip_count += 1;
+    }
+
     fn move_to(&self, point: &Point2D<AzFloat>) {
         self.path_builder.move_to(*point)
     }
 
+    fn line_to(&self, point: &Point2D<AzFloat>) {
+        self.path_builder.line_to(*point)
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-rect
+    fn rect(&self, rect: &Rect<AzFloat>) {
+        self.path_builder.move_to(rect.origin);
+        self.path_builder.line_to(Point2D(rect.max_x(), rect.origin.y));
+        self.path_builder.line_to(Point2D(rect.max_x(), rect.max_y()));
+        self.path_builder.line_to(Point2D(rect.origin.x, rect.max_y()));
+        self.path_builder.close();
+        self.path_builder.move_to(rect.origin);
+    }
+
+    fn quadratic_curve_to(&self,
+                          cp: &Point2D<AzFloat>,
+                          endpoint: &Point2D<AzFloat>) {
+        self.path_builder.quadratic_curve_to(cp, endpoint)
+    }
+
     fn bezier_curve_to(&self,
                        cp1: &Point2D<AzFloat>,
                        cp2: &Point2D<AzFloat>,
@@ -147,16 +301,123 @@ impl<'a> CanvasPaintTask<'a> {
         self.path_builder.bezier_curve_to(cp1, cp2, endpoint)
     }
 
-    fn set_fill_style(&mut self, style: FillOrStrokeStyle) {
-        self.fill_style = style.to_azure_pattern()
+    fn arc(&self,
+           center: &Point2D<AzFloat>,
+           radius: AzFloat,
+           start_angle: AzFloat,
+           end_angle: AzFloat,
+           ccw: bool) {
+        self.path_builder.arc(*center, radius, start_angle, end_angle, ccw)
+    }
+
+    /// Draws the `source_rect` part of an image with B8G8R8A8 pixels into `dest_rect`.
+    fn draw_image(&self, imagedata: Vec<u8>, image_size: Size2D<i32>,
+                  dest_rect: Rect<f32>, source_rect: Rect<f32>) {
+        if image_size.width <= 0 || image_size.height <= 0 {
+            return
+        }
+
+        let source_surface = self.drawtarget.create_source_surface_from_data(imagedata.as_slice(),
+            image_size, image_size.width * 4, SurfaceFormat::B8G8R8A8);
+        let draw_surface_options = DrawSurfaceOptions::new(Filter::Linear, true);
+        self.drawtarget.draw_surface(source_surface, dest_rect, source_rect,
+                                     draw_surface_options, self.draw_options());
     }
 
-    fn set_stroke_style(&mut self, style: FillOrStrokeStyle) {
-        self.stroke_style = style.to_azure_pattern()
+    /// Shapes `text` with the current font, if one has been set.
+    fn create_text_run(&mut self, text: String) -> Option<TextRun> {
+        let font = match self.state.font {
+            Some(ref font) => font.clone(),
+            None => return None,
+        };
+        let options = ShapingOptions {
+            letter_spacing: None,
+            word_spacing: Au(0),
+            flags: ShapingFlags::empty(),
+        };
+        let font_group = self.font_context.get_layout_font_group_for_style(font);
+        Some(font_group.create_textrun(text, &options))
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-filltext
+    fn fill_text(&mut self, text: String, point: &Point2D<f32>, max_width: Option<f32>) {
+        let run = match self.create_text_run(text) {
+            Some(run) => run,
+            None => return,
+        };
+        let range = Range::new(CharIndex(0), run.char_len());
+        let advance = run.advance_for_range(&range).to_subpx() as f32;
+
+        // Step 5: squash the text horizontally if it does not fit in `max_width`.
+        let scale = match max_width {
+            Some(max_width) if max_width <= 0.0 => return,
+            Some(max_width) if advance > max_width => max_width / advance,
+            _ => 1.0,
+        };
+
+        let x = match self.state.text_align {
+            TextAlign::Start | TextAlign::Left => 0.0,
+            TextAlign::Center => -advance / 2.0,
+            TextAlign::End | TextAlign::Right => -advance,
+        };
+        let ascent = run.ascent().to_subpx() as f32;
+        let descent = run.descent().to_subpx() as f32;
+        let y = match self.state.text_baseline {
+            TextBaseline::Top | TextBaseline::Hanging => ascent,
+            TextBaseline::Middle => (ascent - descent) / 2.0,
+            TextBaseline::Alphabetic => 0.0,
+            TextBaseline::Ideographic | TextBaseline::Bottom => -descent,
+        };
+
+        let color = match self.state.fill_style {
+            FillOrStrokeStyle::Color(ref color) => {
+                color::new(color.red, color.green, color.blue,
+                           color.alpha * self.state.global_alpha)
+            }
+        };
+
+        let transform = self.state.transform.translate(point.x, point.y).scale(scale, 1.0);
+        self.drawtarget.set_transform(&transform);
+        let paint_font = self.font_context.get_paint_font_from_template(&run.font_template,
+                                                                        run.actual_pt_size);
+        paint_font.borrow().draw_text_into_context(&self.drawtarget,
+                                                   &run,
+                                                   &range,
+                                                   Point2D(Au::from_frac_px(x as f64),
+                                                           Au::from_frac_px(y as f64)),
+                                                   color,
+                                                   true);
+        self.drawtarget.set_transform(&self.state.transform);
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-measuretext
+    fn measure_text(&mut self, text: String, chan: Sender<f32>) {
+        let width = match self.create_text_run(text) {
+            Some(run) => {
+                let range = Range::new(CharIndex(0), run.char_len());
+                run.advance_for_range(&range).to_subpx() as f32
+            }
+            None => 0.0,
+        };
+        chan.send(width).unwrap();
+    }
+
+    fn save_context_state(&mut self) {
+        self.saved_states.push(self.state.clone());
+    }
+
+    fn restore_context_state(&mut self) {
+        if let Some(state) = self.saved_states.pop() {
+            for _ in range(state.clip_count, self.state.clip_count) {
+                self.drawtarget.pop_clip();
+            }
+            self.state = state;
+            self.drawtarget.set_transform(&self.state.transform);
+        }
     }
 
     fn set_transform(&mut self, transform: &Matrix2D<f32>) {
-        self.transform = *transform;
+        self.state.transform = *transform;
         self.drawtarget.set_transform(transform)
     }
 
@@ -164,8 +425,12 @@ impl<'a> CanvasPaintTask<'a> {
         DrawTarget::new(BackendType::Skia, size, SurfaceFormat::B8G8R8A8)
     }
 
+    /// Resizes the canvas, which also resets the drawing state.
     fn recreate(&mut self, size: Size2D<i32>) {
         self.drawtarget = CanvasPaintTask::create(size);
+        self.path_builder = self.drawtarget.create_path_builder();
+        self.state = CanvasPaintState::new();
+        self.saved_states.clear();
     }
 
     fn send_pixel_contents(&mut self, chan: Sender<Vec<u8>>) {
@@ -290,3 +555,268 @@ impl FillOrStrokeStyle {
     }
 }
 
+// http://www.whatwg.org/html/#dom-context-2d-linecap
+#[derive(Copy, Clone, PartialEq)]
+pub enum LineCapStyle {
+    Butt,
+    Round,
+    Square,
+}
+
+impl LineCapStyle {
+    fn to_azure_style(&self) -> CapStyle {
+        match *self {
+            LineCapStyle::Butt => CapStyle::Butt,
+            LineCapStyle::Round => CapStyle::Round,
+            LineCapStyle::Square => CapStyle::Square,
+        }
+    }
+
+    pub fn from_str(string: &str) -> Option<LineCapStyle> {
+        match string {
+            "butt" => Some(LineCapStyle::Butt),
+            "round" => Some(LineCapStyle::Round),
+            "square" => Some(LineCapStyle::Square),
+            _ => None
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            LineCapStyle::Butt => "butt",
+            LineCapStyle::Round => "round",
+            LineCapStyle::Square => "square",
+        }
+    }
+}
+
+// http://www.whatwg.org/html/#dom-context-2d-linejoin
+#[derive(Copy, Clone, PartialEq)]
+pub enum LineJoinStyle {
+    Round,
+    Bevel,
+    Miter,
+}
+
+impl LineJoinStyle {
+    fn to_azure_style(&self) -> JoinStyle {
+        match *self {
+            LineJoinStyle::Round => JoinStyle::Round,
+            LineJoinStyle::Bevel => JoinStyle::Bevel,
+            LineJoinStyle::Miter => JoinStyle::MiterOrBevel,
+        }
+    }
+
+    pub fn from_str(string: &str) -> Option<LineJoinStyle> {
+        match string {
+            "round" => Some(LineJoinStyle::Round),
+            "bevel" => Some(LineJoinStyle::Bevel),
+            "miter" => Some(LineJoinStyle::Miter),
+            _ => None
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            LineJoinStyle::Round => "round",
+            LineJoinStyle::Bevel => "bevel",
+            LineJoinStyle::Miter => "miter",
+        }
+    }
+}
+
+// http://www.whatwg.org/html/#dom-context-2d-globalcompositeoperation
+#[derive(Copy, Clone, PartialEq)]
+pub enum CompositionStyle {
+    SrcOver,
+    SrcIn,
+    SrcOut,
+    SrcAtop,
+    DestOver,
+    DestIn,
+    DestOut,
+    DestAtop,
+    Copy,
+    Lighter,
+    Xor,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl CompositionStyle {
+    fn to_azure_composition_op(&self) -> CompositionOp {
+        match *self {
+            CompositionStyle::SrcOver => CompositionOp::Over,
+            CompositionStyle::SrcIn => CompositionOp::In,
+            CompositionStyle::SrcOut => CompositionOp::Out,
+            CompositionStyle::SrcAtop => CompositionOp::Atop,
+            CompositionStyle::DestOver => CompositionOp::DestOver,
+            CompositionStyle::DestIn => CompositionOp::DestIn,
+            CompositionStyle::DestOut => CompositionOp::DestOut,
+            CompositionStyle::DestAtop => CompositionOp::DestAtop,
+            CompositionStyle::Copy => CompositionOp::Source,
+            CompositionStyle::Lighter => CompositionOp::Add,
+            CompositionStyle::Xor => CompositionOp::Xor,
+            CompositionStyle::Multiply => CompositionOp::Multiply,
+            CompositionStyle::Screen => CompositionOp::Screen,
+            CompositionStyle::Overlay => CompositionOp::Overlay,
+            CompositionStyle::Darken => CompositionOp::Darken,
+            CompositionStyle::Lighten => CompositionOp::Lighten,
+            CompositionStyle::ColorDodge => CompositionOp::ColorDodge,
+            CompositionStyle::ColorBurn => CompositionOp::ColorBurn,
+            CompositionStyle::HardLight => CompositionOp::HardLight,
+            CompositionStyle::SoftLight => CompositionOp::SoftLight,
+            CompositionStyle::Difference => CompositionOp::Difference,
+            CompositionStyle::Exclusion => CompositionOp::Exclusion,
+            CompositionStyle::Hue => CompositionOp::Hue,
+            CompositionStyle::Saturation => CompositionOp::Saturation,
+            CompositionStyle::Color => CompositionOp::Color,
+            CompositionStyle::Luminosity => CompositionOp::Luminosity,
+        }
+    }
+
+    pub fn from_str(string: &str) -> Option<CompositionStyle> {
+        match string {
+            "source-over" => Some(CompositionStyle::SrcOver),
+            "source-in" => Some(CompositionStyle::SrcIn),
+            "source-out" => Some(CompositionStyle::SrcOut),
+            "source-atop" => Some(CompositionStyle::SrcAtop),
+            "destination-over" => Some(CompositionStyle::DestOver),
+            "destination-in" => Some(CompositionStyle::DestIn),
+            "destination-out" => Some(CompositionStyle::DestOut),
+            "destination-atop" => Some(CompositionStyle::DestAtop),
+            "copy" => Some(CompositionStyle::Copy),
+            "lighter" => Some(CompositionStyle::Lighter),
+            "xor" => Some(CompositionStyle::Xor),
+            "multiply" => Some(CompositionStyle::Multiply),
+            "screen" => Some(CompositionStyle::Screen),
+            "overlay" => Some(CompositionStyle::Overlay),
+            "darken" => Some(CompositionStyle::Darken),
+            "lighten" => Some(CompositionStyle::Lighten),
+            "color-dodge" => Some(CompositionStyle::ColorDodge),
+            "color-burn" => Some(CompositionStyle::ColorBurn),
+            "hard-light" => Some(CompositionStyle::HardLight),
+            "soft-light" => Some(CompositionStyle::SoftLight),
+            "difference" => Some(CompositionStyle::Difference),
+            "exclusion" => Some(CompositionStyle::Exclusion),
+            "hue" => Some(CompositionStyle::Hue),
+            "saturation" => Some(CompositionStyle::Saturation),
+            "color" => Some(CompositionStyle::Color),
+            "luminosity" => Some(CompositionStyle::Luminosity),
+            _ => None
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            CompositionStyle::SrcOver => "source-over",
+            CompositionStyle::SrcIn => "source-in",
+            CompositionStyle::SrcOut => "source-out",
+            CompositionStyle::SrcAtop => "source-atop",
+            CompositionStyle::DestOver => "destination-over",
+            CompositionStyle::DestIn => "destination-in",
+            CompositionStyle::DestOut => "destination-out",
+            CompositionStyle::DestAtop => "destination-atop",
+            CompositionStyle::Copy => "copy",
+            CompositionStyle::Lighter => "lighter",
+            CompositionStyle::Xor => "xor",
+            CompositionStyle::Multiply => "multiply",
+            CompositionStyle::Screen => "screen",
+            CompositionStyle::Overlay => "overlay",
+            CompositionStyle::Darken => "darken",
+            CompositionStyle::Lighten => "lighten",
+            CompositionStyle::ColorDodge => "color-dodge",
+            CompositionStyle::ColorBurn => "color-burn",
+            CompositionStyle::HardLight => "hard-light",
+            CompositionStyle::SoftLight => "soft-light",
+            CompositionStyle::Difference => "difference",
+            CompositionStyle::Exclusion => "exclusion",
+            CompositionStyle::Hue => "hue",
+            CompositionStyle::Saturation => "saturation",
+            CompositionStyle::Color => "color",
+            CompositionStyle::Luminosity => "luminosity",
+        }
+    }
+}
+
+// http://www.whatwg.org/html/#dom-context-2d-textalign
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextAlign {
+    Start,
+    End,
+    Left,
+    Right,
+    Center,
+}
+
+impl TextAlign {
+    pub fn from_str(string: &str) -> Option<TextAlign> {
+        match string {
+            "start" => Some(TextAlign::Start),
+            "end" => Some(TextAlign::End),
+            "left" => Some(TextAlign::Left),
+            "right" => Some(TextAlign::Right),
+            "center" => Some(TextAlign::Center),
+            _ => None
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            TextAlign::Start => "start",
+            TextAlign::End => "end",
+            TextAlign::Left => "left",
+            TextAlign::Right => "right",
+            TextAlign::Center => "center",
+        }
+    }
+}
+
+// http://www.whatwg.org/html/#dom-context-2d-textbaseline
+#[derive(Copy, Clone, PartialEq)]
+pub enum TextBaseline {
+    Top,
+    Hanging,
+    Middle,
+    Alphabetic,
+    Ideographic,
+    Bottom,
+}
+
+impl TextBaseline {
+    pub fn from_str(string: &str) -> Option<TextBaseline> {
+        match string {
+            "top" => Some(TextBaseline::Top),
+            "hanging" => Some(TextBaseline::Hanging),
+            "middle" => Some(TextBaseline::Middle),
+            "alphabetic" => Some(TextBaseline::Alphabetic),
+            "ideographic" => Some(TextBaseline::Ideographic),
+            "bottom" => Some(TextBaseline::Bottom),
+            _ => None
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            TextBaseline::Top => "top",
+            TextBaseline::Hanging => "hanging",
+            TextBaseline::Middle => "middle",
+            TextBaseline::Alphabetic => "alphabetic",
+            TextBaseline::Ideographic => "ideographic",
+            TextBaseline::Bottom => "bottom",
+        }
+    }
+}
@@ -0,0 +1,89 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Serialization of the canvas bitmap for `toDataURL()` and `toBlob()`.
+
+use geom::size::Size2D;
+use image::ColorType;
+use image::jpeg::JPEGEncoder;
+use image::png::PNGEncoder;
+
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::num::Float;
+
+/// The quality used for JPEG images when no valid quality is requested.
+const DEFAULT_JPEG_QUALITY: u8 = 92;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    /// A JPEG image with a quality between 0 and 100.
+    Jpeg(u8),
+}
+
+impl ImageFormat {
+    /// Returns the format for the `type` and `quality` arguments of `toDataURL()`. Types
+    /// that are not supported fall back to PNG.
+    /// http://www.whatwg.org/html/#a-serialisation-of-the-bitmap-as-a-file
+    pub fn from_mime_type(mime_type: &str, quality: Option<f64>) -> ImageFormat {
+        match mime_type.to_ascii_lowercase().as_slice() {
+            "image/jpeg" => {
+                let quality = match quality {
+                    Some(quality) if quality >= 0.0 && quality <= 1.0 => {
+                        (quality * 100.0).round() as u8
+                    }
+                    _ => DEFAULT_JPEG_QUALITY,
+                };
+                ImageFormat::Jpeg(quality)
+            }
+            _ => ImageFormat::Png,
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match *self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg(_) => "image/jpeg",
+        }
+    }
+}
+
+/// Encodes a bitmap of premultiplied B8G8R8A8 pixels, or returns `None` if the encoder
+/// fails.
+pub fn encode_image(pixels: &[u8], size: Size2D<i32>, format: ImageFormat) -> Option<Vec<u8>> {
+    let (width, height) = (size.width as u32, size.height as u32);
+    let mut encoded = vec!();
+    let result = match format {
+        ImageFormat::Png => {
+            let rgba: Vec<u8> = pixels.chunks(4).flat_map(|bgra| {
+                let alpha = bgra[3];
+                vec![unpremultiply(bgra[2], alpha),
+                     unpremultiply(bgra[1], alpha),
+                     unpremultiply(bgra[0], alpha),
+                     alpha].into_iter()
+            }).collect();
+            PNGEncoder::new(&mut encoded).encode(rgba.as_slice(), width, height,
+                                                 ColorType::RGBA(8))
+        }
+        ImageFormat::Jpeg(quality) => {
+            // JPEG has no alpha channel, so the bitmap is composited onto black, which is
+            // just the premultiplied color.
+            let rgb: Vec<u8> = pixels.chunks(4).flat_map(|bgra| {
+                vec![bgra[2], bgra[1], bgra[0]].into_iter()
+            }).collect();
+            JPEGEncoder::new_with_quality(&mut encoded, quality).encode(rgb.as_slice(),
+                                                                        width, height,
+                                                                        ColorType::RGB(8))
+        }
+    };
+    result.ok().map(|_| encoded)
+}
+
+fn unpremultiply(color: u8, alpha: u8) -> u8 {
+    if alpha == 0 {
+        return 0
+    }
+    cmp::min((color as u32) * 255 / (alpha as u32), 255) as u8
+}
@@ -11,7 +11,9 @@ extern crate azure;
 extern crate cssparser;
 extern crate geom;
 extern crate gfx;
+extern crate image;
 extern crate util;
 
 
 pub mod canvas_paint_task;
+pub mod image_encoder;
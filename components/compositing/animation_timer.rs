@@ -0,0 +1,116 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A timer thread that wakes the compositor up once per frame while some page has
+//! `requestAnimationFrame` callbacks pending.
+
+use compositor_task::{CompositorProxy, Msg};
+
+use msg::compositor_msg::AnimationState;
+use msg::constellation_msg::{ConstellationChan, PipelineId};
+use msg::constellation_msg::Msg as ConstellationMsg;
+
+use std::collections::HashSet;
+use std::old_io::timer;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread::Builder;
+use std::time::duration::Duration;
+use time;
+
+/// The duration of a frame in nanoseconds, for a refresh rate of 60 Hz.
+static FRAME_DURATION: i64 = 16_666_666;
+
+pub struct AnimationTimerProxy {
+    sender: Sender<ToAnimationTimerMsg>,
+    /// The pipelines that have animation frame callbacks pending.
+    animating_pipelines: HashSet<PipelineId>,
+    /// Whether an `AnimationFrame` message is on its way.
+    frame_scheduled: bool,
+}
+
+pub struct AnimationTimer {
+    compositor_proxy: Box<CompositorProxy>,
+    receiver: Receiver<ToAnimationTimerMsg>,
+}
+
+enum ToAnimationTimerMsg {
+    ExitMsg,
+    ScheduleFrameMsg(u64),
+}
+
+impl AnimationTimerProxy {
+    pub fn new(compositor_proxy: Box<CompositorProxy+Send>) -> AnimationTimerProxy {
+        let (to_animation_timer_sender, to_animation_timer_receiver) = channel();
+        Builder::new().spawn(move || {
+            let mut animation_timer = AnimationTimer {
+                compositor_proxy: compositor_proxy,
+                receiver: to_animation_timer_receiver,
+            };
+            animation_timer.run();
+        });
+        AnimationTimerProxy {
+            sender: to_animation_timer_sender,
+            animating_pipelines: HashSet::new(),
+            frame_scheduled: false,
+        }
+    }
+
+    pub fn set_animation_state(&mut self, pipeline_id: PipelineId, state: AnimationState) {
+        match state {
+            AnimationState::AnimationsPresent => {
+                self.animating_pipelines.insert(pipeline_id);
+            }
+            AnimationState::NoAnimationsPresent => {
+                self.animating_pipelines.remove(&pipeline_id);
+            }
+        }
+        self.schedule_frame_if_necessary();
+    }
+
+    pub fn remove_pipeline(&mut self, pipeline_id: PipelineId) {
+        self.animating_pipelines.remove(&pipeline_id);
+    }
+
+    /// Handles an `AnimationFrame` message by asking the constellation to tick the animating
+    /// pipelines, and schedules the next frame.
+    pub fn tick(&mut self, constellation_chan: &ConstellationChan) {
+        self.frame_scheduled = false;
+        if self.animating_pipelines.is_empty() {
+            return;
+        }
+        let pipeline_ids = self.animating_pipelines.iter().map(|&id| id).collect();
+        let ConstellationChan(ref chan) = *constellation_chan;
+        chan.send(ConstellationMsg::TickAnimations(pipeline_ids)).unwrap();
+        self.schedule_frame_if_necessary();
+    }
+
+    fn schedule_frame_if_necessary(&mut self) {
+        if self.frame_scheduled || self.animating_pipelines.is_empty() {
+            return;
+        }
+        self.frame_scheduled = true;
+        let timestamp = time::precise_time_ns();
+        self.sender.send(ToAnimationTimerMsg::ScheduleFrameMsg(timestamp)).unwrap()
+    }
+
+    pub fn shutdown(&mut self) {
+        self.sender.send(ToAnimationTimerMsg::ExitMsg).unwrap()
+    }
+}
+
+impl AnimationTimer {
+    pub fn run(&mut self) {
+        loop {
+            match self.receiver.recv() {
+                Ok(ToAnimationTimerMsg::ScheduleFrameMsg(timestamp)) => {
+                    let target = timestamp as i64 + FRAME_DURATION;
+                    let delta = target - (time::precise_time_ns() as i64);
+                    timer::sleep(Duration::nanoseconds(delta));
+                    self.compositor_proxy.send(Msg::AnimationFrame);
+                }
+                Ok(ToAnimationTimerMsg::ExitMsg) | Err(_) => break,
+            }
+        }
+    }
+}
@@ -2,12 +2,14 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use animation_timer::AnimationTimerProxy;
 use compositor_layer::{CompositorData, CompositorLayer, WantsScrollEventsFlag};
 use compositor_task::{CompositorEventListener, CompositorProxy, CompositorReceiver};
 use compositor_task::{CompositorTask, LayerProperties, Msg};
 use constellation::{FrameId, SendableFrameTree};
 use pipeline::CompositionPipeline;
 use scrolling::ScrollingTimerProxy;
+use touch::{TouchHandler, TouchAction};
 use windowing;
 use windowing::{MouseWindowEvent, WindowEvent, WindowMethods, WindowNavigateMsg};
 
@@ -28,13 +30,13 @@ use layers::scene::Scene;
 use png;
 use gleam::gl::types::{GLint, GLsizei};
 use gleam::gl;
-use script_traits::{ConstellationControlMsg, ScriptControlChan};
+use script_traits::{CompositorEvent, ConstellationControlMsg, ScriptControlChan};
 use msg::compositor_msg::{Epoch, LayerId};
 use msg::compositor_msg::{ReadyState, PaintState, ScrollPolicy};
 use msg::constellation_msg::{ConstellationChan, NavigationDirection};
 use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::{Key, KeyModifiers, KeyState, LoadData};
-use msg::constellation_msg::{PipelineId, WindowSizeData};
+use msg::constellation_msg::{PipelineId, TouchEventType, TouchId, WindowSizeData};
 use util::geometry::{PagePx, ScreenPx, ViewportPx};
 use util::memory::MemoryProfilerChan;
 use util::opts;
@@ -87,6 +89,9 @@ pub struct IOCompositor<Window: WindowMethods> {
     /// A handle to the scrolling timer.
     scrolling_timer: ScrollingTimerProxy,
 
+    /// A handle to the animation timer.
+    animation_timer: AnimationTimerProxy,
+
     /// Tracks whether we should composite this frame.
     composition_request: CompositionRequest,
 
@@ -128,6 +133,9 @@ pub struct IOCompositor<Window: WindowMethods> {
 
     /// Pending scroll events.
     pending_scroll_events: Vec<ScrollEvent>,
+
+    /// Recognizes pan, pinch zoom and tap gestures from the touch events script did not consume.
+    touch_handler: TouchHandler,
 }
 
 pub struct ScrollEvent {
@@ -163,6 +171,10 @@ struct PipelineDetails {
 
     /// The status of this pipeline's PaintTask.
     paint_state: PaintState,
+
+    /// Whether this pipeline's ScriptTask has touch event listeners, in which case touch
+    /// events go through it before the compositor runs its gestures.
+    has_touch_event_listeners: bool,
 }
 
 impl PipelineDetails {
@@ -171,6 +183,7 @@ impl PipelineDetails {
             pipeline: None,
             ready_state: ReadyState::Blank,
             paint_state: PaintState::Painting,
+            has_touch_event_listeners: false,
         }
     }
 }
@@ -189,6 +202,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         // display list. This is only here because we don't have that logic in the painter yet.
         let window_size = window.framebuffer_size();
         let hidpi_factor = window.hidpi_factor();
+        let animation_timer = AnimationTimerProxy::new(sender.clone_compositor_proxy());
         IOCompositor {
             window: window,
             port: receiver,
@@ -202,8 +216,10 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             window_size: window_size,
             hidpi_factor: hidpi_factor,
             scrolling_timer: ScrollingTimerProxy::new(sender),
+            animation_timer: animation_timer,
             composition_request: CompositionRequest::NoCompositingNecessary,
             pending_scroll_events: Vec::new(),
+            touch_handler: TouchHandler::new(),
             shutdown_state: ShutdownState::NotShuttingDown,
             page_zoom: ScaleFactor(1.0),
             viewport_zoom: ScaleFactor(1.0),
@@ -364,6 +380,16 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 }
             }
 
+            (Msg::HasTouchEventListeners(pipeline_id), ShutdownState::NotShuttingDown) => {
+                self.get_or_create_pipeline_details(pipeline_id).has_touch_event_listeners = true;
+            }
+
+            (Msg::TouchEvent(event_type, identifier, point, prevented),
+             ShutdownState::NotShuttingDown) => {
+                let point = TypedPoint2D::from_untyped(&point);
+                self.on_touch_event_processed(event_type, identifier, point, prevented);
+            }
+
             (Msg::SetCursor(cursor), ShutdownState::NotShuttingDown) => {
                 self.window.set_cursor(cursor)
             }
@@ -372,6 +398,16 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 if self.pipeline_details.remove(&pipeline_id).is_none() {
                     panic!("Saw PaintTaskExited message from an unknown pipeline!");
                 }
+                self.animation_timer.remove_pipeline(pipeline_id);
+            }
+
+            (Msg::ChangeRunningAnimationsState(pipeline_id, animation_state),
+             ShutdownState::NotShuttingDown) => {
+                self.animation_timer.set_animation_state(pipeline_id, animation_state);
+            }
+
+            (Msg::AnimationFrame, ShutdownState::NotShuttingDown) => {
+                self.animation_timer.tick(&self.constellation_chan);
             }
 
             // When we are shutting_down, we need to avoid performing operations
@@ -704,6 +740,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 }
 
                 self.start_scrolling_timer_if_necessary();
+                self.send_viewport_rects_for_all_layers();
             }
             None => {}
         }
@@ -780,6 +817,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             if self.send_buffer_requests_for_all_layers() {
                 self.start_scrolling_timer_if_necessary();
             }
+            self.send_viewport_rects_for_all_layers();
         } else {
             self.fragment_point = Some(point);
         }
@@ -813,6 +851,10 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.on_mouse_window_move_event_class(cursor);
             }
 
+            WindowEvent::Touch(event_type, identifier, point) => {
+                self.on_touch_window_event(event_type, identifier, point);
+            }
+
             WindowEvent::Scroll(delta, cursor) => {
                 self.on_scroll_window_event(delta, cursor);
             }
@@ -896,6 +938,61 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         }
     }
 
+    fn on_touch_window_event(&mut self,
+                             event_type: TouchEventType,
+                             identifier: TouchId,
+                             point: TypedPoint2D<DevicePixel, f32>) {
+        let result = match self.find_topmost_layer_at_point(point / self.scene.scale) {
+            Some(result) => result,
+            None => {
+                self.on_touch_event_processed(event_type, identifier, point, false);
+                return;
+            }
+        };
+
+        // Pages without touch event listeners can't prevent the gestures, so there is no need
+        // to wait for their script task.
+        let has_listeners = match self.pipeline_details.get(&result.layer.get_pipeline_id()) {
+            Some(details) => details.has_touch_event_listeners,
+            None => false,
+        };
+        if has_listeners {
+            result.layer.send_touch_event(self, event_type, identifier, result.point, point);
+        } else {
+            self.on_touch_event_processed(event_type, identifier, point, false);
+        }
+    }
+
+    /// Runs the default gestures for a touch event once script has dispatched it.
+    fn on_touch_event_processed(&mut self,
+                                event_type: TouchEventType,
+                                identifier: TouchId,
+                                point: TypedPoint2D<DevicePixel, f32>,
+                                prevented: bool) {
+        let action = match event_type {
+            TouchEventType::Down => self.touch_handler.on_touch_down(identifier, point, prevented),
+            TouchEventType::Move => self.touch_handler.on_touch_move(identifier, point, prevented),
+            TouchEventType::Up => self.touch_handler.on_touch_up(identifier, point, prevented),
+            TouchEventType::Cancel => self.touch_handler.on_touch_cancel(identifier),
+        };
+
+        match action {
+            TouchAction::NoAction => {}
+            TouchAction::Scroll(delta, point) => {
+                let cursor = TypedPoint2D(point.x.get() as i32, point.y.get() as i32);
+                self.on_scroll_window_event(delta, cursor);
+            }
+            TouchAction::Zoom(magnification) => {
+                self.on_pinch_zoom_window_event(magnification);
+            }
+            TouchAction::Click(point) => {
+                self.on_mouse_window_event_class(MouseWindowEvent::MouseDown(0, point));
+                self.on_mouse_window_event_class(MouseWindowEvent::MouseUp(0, point));
+                self.on_mouse_window_event_class(MouseWindowEvent::Click(0, point));
+            }
+        }
+    }
+
     fn on_scroll_window_event(&mut self,
                               delta: TypedPoint2D<DevicePixel, f32>,
                               cursor: TypedPoint2D<DevicePixel, i32>) {
@@ -913,6 +1010,11 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             let delta = scroll_event.delta / self.scene.scale;
             let cursor = scroll_event.cursor.as_f32() / self.scene.scale;
 
+            match self.find_topmost_layer_at_point(cursor) {
+                Some(result) => result.layer.send_wheel_event(self, delta, result.point),
+                None => {},
+            }
+
             match self.scene.root {
                 Some(ref mut layer) => {
                     layer.handle_scroll_event(delta, cursor);
@@ -1052,6 +1154,10 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             let pipeline = self.get_pipeline(layer.get_pipeline_id());
             let ScriptControlChan(ref chan) = pipeline.script_chan;
             chan.send(ConstellationControlMsg::Viewport(pipeline.id.clone(), layer_rect)).unwrap();
+
+            // Keep the scroll position that script sees in sync with the layer.
+            let event = CompositorEvent::ScrollEvent(layer_rect.origin);
+            chan.send(ConstellationControlMsg::SendEvent(pipeline.id.clone(), event)).unwrap();
         }
 
         for kid in layer.children().iter() {
@@ -1402,6 +1508,7 @@ impl<Window> CompositorEventListener for IOCompositor<Window> where Window: Wind
         memory_profiler_chan.send(memory::MemoryProfilerMsg::Exit).unwrap();
 
         self.scrolling_timer.shutdown();
+        self.animation_timer.shutdown();
     }
 
     fn pinch_zoom_level(&self) -> f32 {
@@ -14,12 +14,13 @@ use geom::size::TypedSize2D;
 use geom::rect::Rect;
 use gfx::paint_task::Msg as PaintMsg;
 use layers::color::Color;
-use layers::geometry::LayerPixel;
+use layers::geometry::{DevicePixel, LayerPixel};
 use layers::layers::{Layer, LayerBufferSet};
 use script_traits::CompositorEvent::{ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent};
+use script_traits::CompositorEvent::{TouchEvent, WheelEvent};
 use script_traits::{ScriptControlChan, ConstellationControlMsg};
 use msg::compositor_msg::{Epoch, LayerId, ScrollPolicy};
-use msg::constellation_msg::PipelineId;
+use msg::constellation_msg::{PipelineId, TouchEventType, TouchId};
 use std::num::Float;
 use std::rc::Rc;
 
@@ -118,6 +119,20 @@ pub trait CompositorLayer {
                                      cursor: TypedPoint2D<LayerPixel, f32>)
                                      where Window: WindowMethods;
 
+    fn send_wheel_event<Window>(&self,
+                                compositor: &IOCompositor<Window>,
+                                delta: TypedPoint2D<LayerPixel, f32>,
+                                cursor: TypedPoint2D<LayerPixel, f32>)
+                                where Window: WindowMethods;
+
+    fn send_touch_event<Window>(&self,
+                                compositor: &IOCompositor<Window>,
+                                event_type: TouchEventType,
+                                identifier: TouchId,
+                                point: TypedPoint2D<LayerPixel, f32>,
+                                window_point: TypedPoint2D<DevicePixel, f32>)
+                                where Window: WindowMethods;
+
     fn clamp_scroll_offset_and_scroll_layer(&self,
                                             new_offset: TypedPoint2D<LayerPixel, f32>)
                                             -> ScrollEventResult;
@@ -360,6 +375,33 @@ impl CompositorLayer for Layer<CompositorData> {
         let _ = chan.send(ConstellationControlMsg::SendEvent(pipeline.id.clone(), message));
     }
 
+    fn send_wheel_event<Window>(&self,
+                                compositor: &IOCompositor<Window>,
+                                delta: TypedPoint2D<LayerPixel, f32>,
+                                cursor: TypedPoint2D<LayerPixel, f32>)
+                                where Window: WindowMethods {
+        // Scrolling down moves the content up, which is a negative scroll delta for the
+        // compositor but a positive one for wheel events.
+        let message = WheelEvent(-delta.to_untyped(), cursor.to_untyped());
+        let pipeline = compositor.get_pipeline(self.get_pipeline_id());
+        let ScriptControlChan(ref chan) = pipeline.script_chan;
+        let _ = chan.send(ConstellationControlMsg::SendEvent(pipeline.id.clone(), message));
+    }
+
+    fn send_touch_event<Window>(&self,
+                                compositor: &IOCompositor<Window>,
+                                event_type: TouchEventType,
+                                identifier: TouchId,
+                                point: TypedPoint2D<LayerPixel, f32>,
+                                window_point: TypedPoint2D<DevicePixel, f32>)
+                                where Window: WindowMethods {
+        let message = TouchEvent(event_type, identifier, point.to_untyped(),
+                                 window_point.to_untyped());
+        let pipeline = compositor.get_pipeline(self.get_pipeline_id());
+        let ScriptControlChan(ref chan) = pipeline.script_chan;
+        let _ = chan.send(ConstellationControlMsg::SendEvent(pipeline.id.clone(), message));
+    }
+
     fn scroll_layer_and_all_child_layers(&self, new_offset: TypedPoint2D<LayerPixel, f32>)
                                          -> bool {
         let mut result = false;
@@ -18,10 +18,10 @@ use geom::size::Size2D;
 use layers::platform::surface::{NativeCompositingGraphicsContext, NativeGraphicsMetadata};
 use layers::layers::LayerBufferSet;
 use pipeline::CompositionPipeline;
-use msg::compositor_msg::{Epoch, LayerId, LayerMetadata, ReadyState};
+use msg::compositor_msg::{AnimationState, Epoch, LayerId, LayerMetadata, ReadyState};
 use msg::compositor_msg::{PaintListener, PaintState, ScriptListener, ScrollPolicy};
 use msg::constellation_msg::{ConstellationChan, LoadData, PipelineId};
-use msg::constellation_msg::{Key, KeyState, KeyModifiers};
+use msg::constellation_msg::{Key, KeyState, KeyModifiers, TouchEventType, TouchId};
 use util::cursor::Cursor;
 use util::geometry::PagePx;
 use util::memory::MemoryProfilerChan;
@@ -93,6 +93,19 @@ impl ScriptListener for Box<CompositorProxy+'static+Send> {
     fn send_key_event(&mut self, key: Key, state: KeyState, modifiers: KeyModifiers) {
         self.send(Msg::KeyEvent(key, state, modifiers));
     }
+
+    fn set_has_touch_event_listeners(&mut self, pipeline_id: PipelineId) {
+        self.send(Msg::HasTouchEventListeners(pipeline_id));
+    }
+
+    fn send_touch_event(&mut self, event_type: TouchEventType, identifier: TouchId,
+                        point: Point2D<f32>, prevented: bool) {
+        self.send(Msg::TouchEvent(event_type, identifier, point, prevented));
+    }
+
+    fn set_animation_state(&mut self, pipeline_id: PipelineId, animation_state: AnimationState) {
+        self.send(Msg::ChangeRunningAnimationsState(pipeline_id, animation_state));
+    }
 }
 
 /// Information about each layer that the compositor keeps.
@@ -217,10 +230,21 @@ pub enum Msg {
     ScrollTimeout(u64),
     /// Sends an unconsumed key event back to the compositor.
     KeyEvent(Key, KeyState, KeyModifiers),
+    /// Informs the compositor that the given pipeline has touch event listeners.
+    HasTouchEventListeners(PipelineId),
+    /// Sends a touch event back to the compositor after script has dispatched it, with the
+    /// position of the touch point in the window and whether its default action was prevented.
+    TouchEvent(TouchEventType, TouchId, Point2D<f32>, bool),
     /// Changes the cursor.
     SetCursor(Cursor),
     /// Informs the compositor that the paint task for the given pipeline has exited.
     PaintTaskExited(PipelineId),
+    /// Alerts the compositor that the given pipeline has or no longer has animation frame
+    /// callbacks pending.
+    ChangeRunningAnimationsState(PipelineId, AnimationState),
+    /// Indicates that it is time to tick the pipelines with animation frame callbacks pending.
+    /// (See the `animation_timer` module.)
+    AnimationFrame,
 }
 
 impl Debug for Msg {
@@ -245,8 +269,12 @@ impl Debug for Msg {
             Msg::LoadComplete => write!(f, "LoadComplete"),
             Msg::ScrollTimeout(..) => write!(f, "ScrollTimeout"),
             Msg::KeyEvent(..) => write!(f, "KeyEvent"),
+            Msg::HasTouchEventListeners(..) => write!(f, "HasTouchEventListeners"),
+            Msg::TouchEvent(..) => write!(f, "TouchEvent"),
             Msg::SetCursor(..) => write!(f, "SetCursor"),
             Msg::PaintTaskExited(..) => write!(f, "PaintTaskExited"),
+            Msg::ChangeRunningAnimationsState(..) => write!(f, "ChangeRunningAnimationsState"),
+            Msg::AnimationFrame => write!(f, "AnimationFrame"),
         }
     }
 }
@@ -287,7 +315,8 @@ impl CompositorTask {
                     as Box<CompositorEventListener>
             }
             None => {
-                box headless::NullCompositor::create(receiver,
+                box headless::NullCompositor::create(sender,
+                                                     receiver,
                                                      constellation_chan.clone(),
                                                      time_profiler_chan,
                                                      memory_profiler_chan)
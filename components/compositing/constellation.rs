@@ -15,13 +15,14 @@ use layout_traits::LayoutTaskFactory;
 use libc;
 use script_traits::{CompositorEvent, ConstellationControlMsg};
 use script_traits::{ScriptControlChan, ScriptTaskFactory};
-use msg::compositor_msg::LayerId;
+use msg::compositor_msg::{AnimationState, LayerId};
 use msg::constellation_msg::{self, ConstellationChan, Failure};
 use msg::constellation_msg::{IFrameSandboxState, NavigationDirection};
 use msg::constellation_msg::{Key, KeyState, KeyModifiers};
-use msg::constellation_msg::{LoadData, NavigationType};
+use msg::constellation_msg::{HistoryState, LoadData, NavigationType};
 use msg::constellation_msg::{PipelineExitType, PipelineId};
-use msg::constellation_msg::{SubpageId, WindowSizeData};
+use msg::constellation_msg::{StorageType, SubpageId, TopLevelBrowsingContextId};
+use msg::constellation_msg::WindowSizeData;
 use msg::constellation_msg::Msg as ConstellationMsg;
 use net::image_cache_task::{ImageCacheTask, ImageCacheTaskClient};
 use net::resource_task::ResourceTask;
@@ -88,8 +89,14 @@ pub struct Constellation<LTF, STF> {
     pub time_profiler_chan: TimeProfilerChan,
 
     pub window_size: WindowSizeData,
+
+    /// The number of animation frames that have been ticked, used to throttle hidden frames.
+    animation_frame_count: u64,
 }
 
+/// Hidden frames only run their animation frame callbacks once every this many frames.
+static HIDDEN_FRAME_TICK_INTERVAL: u64 = 60;
+
 /// A unique ID used to identify a frame.
 #[derive(Copy)]
 pub struct FrameId(u32);
@@ -123,6 +130,26 @@ impl FrameTree {
     fn add_child(&self, new_child: ChildFrameTree) {
         self.children.borrow_mut().push(new_child);
     }
+
+    /// Returns whether the frame of the given pipeline is visible, or `None` if the pipeline is
+    /// not in this frame tree. A frame is hidden if it or one of its ancestors has an empty rect.
+    fn is_visible(&self, id: PipelineId) -> Option<bool> {
+        if self.pipeline.borrow().id == id {
+            return Some(true);
+        }
+        for child in self.children.borrow().iter() {
+            match child.frame_tree.is_visible(id) {
+                Some(visible) => {
+                    let has_area = child.rect.as_ref().map_or(false, |rect| {
+                        rect.size.width.get() > 0.0 && rect.size.height.get() > 0.0
+                    });
+                    return Some(visible && has_area);
+                }
+                None => {}
+            }
+        }
+        None
+    }
 }
 
 #[derive(Clone)]
@@ -257,13 +284,48 @@ struct FrameChange {
     pub after: Rc<FrameTree>,
     /// The kind of navigation that is occurring.
     pub navigation_type: NavigationType,
+    /// The session history changes requested by the new document while it was loading, which
+    /// are applied once it is displayed.
+    pub history_changes: Vec<HistoryChange>,
+}
+
+/// A change to the session history requested by a document.
+enum HistoryChange {
+    /// Adds an entry for the document, after `pushState` or a fragment navigation.
+    Push(PipelineId, Url, Option<HistoryState>),
+    /// Replaces the URL and state of the current entry, after `replaceState`.
+    Replace(PipelineId, Url, Option<HistoryState>),
+}
+
+/// An entry in the session history.
+/// http://www.whatwg.org/html/#session-history-entry
+struct SessionHistoryEntry {
+    /// The frame tree that is displayed for this entry.
+    frame_tree: Rc<FrameTree>,
+    /// The pipeline whose document this entry belongs to.
+    pipeline_id: PipelineId,
+    /// The URL of this entry, or `None` if it is the URL its document was loaded from.
+    url: Option<Url>,
+    /// The state object of this entry, as added by `pushState` or `replaceState`.
+    state: Option<HistoryState>,
+}
+
+impl SessionHistoryEntry {
+    fn new(frame_tree: Rc<FrameTree>, pipeline_id: PipelineId) -> SessionHistoryEntry {
+        SessionHistoryEntry {
+            frame_tree: frame_tree,
+            pipeline_id: pipeline_id,
+            url: None,
+            state: None,
+        }
+    }
 }
 
-/// Stores the Id's of the pipelines previous and next in the browser's history
+/// Stores the session history entries previous and next to the current one.
 struct NavigationContext {
-    previous: Vec<Rc<FrameTree>>,
-    next: Vec<Rc<FrameTree>>,
-    current: Option<Rc<FrameTree>>,
+    previous: Vec<SessionHistoryEntry>,
+    next: Vec<SessionHistoryEntry>,
+    current: Option<SessionHistoryEntry>,
 }
 
 impl NavigationContext {
@@ -275,46 +337,64 @@ impl NavigationContext {
         }
     }
 
-    /* Note that the following two methods can fail. They should only be called  *
-     * when it is known that there exists either a previous page or a next page. */
+    /// The number of entries in the session history.
+    fn len(&self) -> usize {
+        self.previous.len() + self.current.iter().count() + self.next.len()
+    }
 
-    fn back(&mut self, compositor_proxy: &mut CompositorProxy) -> Rc<FrameTree> {
-        self.next.push(self.current.take().unwrap());
-        let prev = self.previous.pop().unwrap();
-        self.set_current(prev.clone(), compositor_proxy);
-        prev
+    /// The index of the current entry, and the number of entries.
+    fn position(&self) -> (u32, u32) {
+        (self.previous.len() as u32, self.len() as u32)
     }
 
-    fn forward(&mut self, compositor_proxy: &mut CompositorProxy) -> Rc<FrameTree> {
-        self.previous.push(self.current.take().unwrap());
-        let next = self.next.pop().unwrap();
-        self.set_current(next.clone(), compositor_proxy);
-        next
+    /// What `position` will be once a document that is being loaded gets its entry.
+    fn position_after_load(&self) -> (u32, u32) {
+        let index = (self.previous.len() + self.current.iter().count()) as u32;
+        (index, index + 1)
     }
 
-    /// Loads a new set of page frames, returning all evicted frame trees
-    fn load(&mut self, frame_tree: Rc<FrameTree>, compositor_proxy: &mut CompositorProxy)
+    /// Moves `delta` entries back (if negative) or forward through the session history.
+    /// This can fail; it should only be called when it is known that enough entries exist.
+    fn traverse(&mut self, delta: i32, compositor_proxy: &mut CompositorProxy) {
+        let mut current = self.current.take().unwrap();
+        if delta < 0 {
+            for _ in range(0, -delta) {
+                self.next.push(current);
+                current = self.previous.pop().unwrap();
+            }
+        } else {
+            for _ in range(0, delta) {
+                self.previous.push(current);
+                current = self.next.pop().unwrap();
+            }
+        }
+        self.set_current(current, compositor_proxy);
+    }
+
+    /// Adds a new entry after the current one, returning the frame trees of all evicted
+    /// entries.
+    fn load(&mut self, entry: SessionHistoryEntry, compositor_proxy: &mut CompositorProxy)
             -> Vec<Rc<FrameTree>> {
-        debug!("navigating to {:?}", frame_tree.pipeline.borrow().id);
+        debug!("navigating to {:?}", entry.frame_tree.pipeline.borrow().id);
         let evicted = replace(&mut self.next, vec!());
         match self.current.take() {
             Some(current) => self.previous.push(current),
             None => (),
         }
-        self.set_current(frame_tree, compositor_proxy);
-        evicted
+        self.set_current(entry, compositor_proxy);
+        evicted.into_iter().map(|entry| entry.frame_tree).collect()
     }
 
     /// Returns the frame trees whose keys are pipeline_id.
     fn find_all(&mut self, pipeline_id: PipelineId) -> Vec<Rc<FrameTree>> {
-        let from_current = self.current.iter().filter_map(|frame_tree| {
-            frame_tree.find(pipeline_id)
+        let from_current = self.current.iter().filter_map(|entry| {
+            entry.frame_tree.find(pipeline_id)
         });
-        let from_next = self.next.iter().filter_map(|frame_tree| {
-            frame_tree.find(pipeline_id)
+        let from_next = self.next.iter().filter_map(|entry| {
+            entry.frame_tree.find(pipeline_id)
         });
-        let from_prev = self.previous.iter().filter_map(|frame_tree| {
-            frame_tree.find(pipeline_id)
+        let from_prev = self.previous.iter().filter_map(|entry| {
+            entry.frame_tree.find(pipeline_id)
         });
         from_prev.chain(from_current).chain(from_next).collect()
     }
@@ -325,18 +405,19 @@ impl NavigationContext {
         let from_prev = self.previous.iter();
 
         let mut all_contained = from_prev.chain(from_current).chain(from_next);
-        all_contained.any(|frame_tree| {
-            frame_tree.contains(pipeline_id)
+        all_contained.any(|entry| {
+            entry.frame_tree.contains(pipeline_id)
         })
     }
 
-    /// Always use this method to set the currently-displayed frame. It correctly informs the
+    /// Always use this method to set the current entry. It correctly informs the
     /// compositor of the new URLs.
-    fn set_current(&mut self, new_frame: Rc<FrameTree>, compositor_proxy: &mut CompositorProxy) {
-        self.current = Some(new_frame.clone());
+    fn set_current(&mut self, new_entry: SessionHistoryEntry,
+                   compositor_proxy: &mut CompositorProxy) {
         compositor_proxy.send(CompositorMsg::ChangePageLoadData(
-            new_frame.id,
-            new_frame.pipeline.borrow().load_data.clone()));
+            new_entry.frame_tree.id,
+            new_entry.frame_tree.pipeline.borrow().load_data.clone()));
+        self.current = Some(new_entry);
     }
 }
 
@@ -373,6 +454,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                     initial_viewport: opts::get().initial_window_size.as_f32() * ScaleFactor(1.0),
                     device_pixel_ratio: ScaleFactor(1.0),
                 },
+                animation_frame_count: 0,
             };
             constellation.run();
         });
@@ -388,15 +470,20 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         }
     }
 
-    /// Helper function for creating a pipeline
+    /// Helper function for creating a pipeline. The top-level browsing context, and with it
+    /// sessionStorage, is shared by the pipelines of a frame tree and survives navigations.
+    /// The document learns its position in the session history before it starts loading.
     fn new_pipeline(&mut self,
                     id: PipelineId,
                     parent: Option<(PipelineId, SubpageId)>,
+                    top_level_browsing_context_id: TopLevelBrowsingContextId,
                     script_pipeline: Option<Rc<Pipeline>>,
-                    load_data: LoadData)
+                    load_data: LoadData,
+                    session_history_position: (u32, u32))
                     -> Rc<Pipeline> {
         let pipe = Pipeline::create::<LTF, STF>(id,
                                                 parent,
+                                                top_level_browsing_context_id,
                                                 self.chan.clone(),
                                                 self.compositor_proxy.clone_compositor_proxy(),
                                                 self.devtools_chan.clone(),
@@ -408,6 +495,8 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                                                 self.window_size,
                                                 script_pipeline,
                                                 load_data.clone());
+        let (index, length) = session_history_position;
+        pipe.session_history_changed(index, length);
         pipe.load();
         Rc::new(pipe)
     }
@@ -430,8 +519,8 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
     /// Convenience function for getting the currently active frame tree.
     /// The currently active frame tree should always be the current painter
-    fn current_frame<'a>(&'a self) -> &'a Option<Rc<FrameTree>> {
-        &self.navigation_context.current
+    fn current_frame<'a>(&'a self) -> Option<&'a Rc<FrameTree>> {
+        self.navigation_context.current.as_ref().map(|entry| &entry.frame_tree)
     }
 
     /// Returns both the navigation context and pending frame trees whose keys are pipeline_id.
@@ -509,6 +598,28 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 debug!("constellation got get-pipeline-title message");
                 self.handle_get_pipeline_title_msg(pipeline_id);
             }
+            ConstellationMsg::BroadcastStorageEvent(pipeline_id, storage_type, url, key, old_value,
+                                                    new_value) => {
+                debug!("constellation got broadcast storage event message");
+                self.handle_broadcast_storage_event(pipeline_id, storage_type, url, key, old_value,
+                                                    new_value);
+            }
+            ConstellationMsg::PushHistoryState(pipeline_id, url, state) => {
+                debug!("constellation got push history state message");
+                self.handle_history_change(HistoryChange::Push(pipeline_id, url, state));
+            }
+            ConstellationMsg::ReplaceHistoryState(pipeline_id, url, state) => {
+                debug!("constellation got replace history state message");
+                self.handle_history_change(HistoryChange::Replace(pipeline_id, url, state));
+            }
+            ConstellationMsg::TraverseHistory(pipeline_id, delta) => {
+                debug!("constellation got traverse history message");
+                self.handle_traverse_history_msg(pipeline_id, delta);
+            }
+            ConstellationMsg::TickAnimations(pipeline_ids) => {
+                debug!("constellation got tick animations message");
+                self.handle_tick_animations_msg(pipeline_ids);
+            }
         }
         true
     }
@@ -569,8 +680,11 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
         let new_id = self.get_next_pipeline_id();
         let new_frame_id = self.get_next_frame_id();
-        let pipeline = self.new_pipeline(new_id, parent, None,
-                                         LoadData::new(Url::parse("about:failure").unwrap()));
+        let position = self.navigation_context.position_after_load();
+        let pipeline = self.new_pipeline(new_id, parent,
+                                         old_pipeline.top_level_browsing_context_id, None,
+                                         LoadData::new(Url::parse("about:failure").unwrap()),
+                                         position);
 
         self.browse(Some(pipeline_id),
                     Rc::new(FrameTree::new(new_frame_id, pipeline.clone(), None)),
@@ -590,13 +704,16 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             before: before,
             after: after,
             navigation_type: navigation_type,
+            history_changes: vec!(),
         });
     }
 
     fn handle_init_load(&mut self, url: Url) {
         let next_pipeline_id = self.get_next_pipeline_id();
         let next_frame_id = self.get_next_frame_id();
-        let pipeline = self.new_pipeline(next_pipeline_id, None, None, LoadData::new(url));
+        let position = self.navigation_context.position_after_load();
+        let pipeline = self.new_pipeline(next_pipeline_id, None, TopLevelBrowsingContextId::new(),
+                                         None, LoadData::new(url), position);
         self.browse(None,
                     Rc::new(FrameTree::new(next_frame_id, pipeline.clone(), None)),
                     NavigationType::Load);
@@ -620,9 +737,9 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
         {
             // If the subframe is in the current frame tree, the compositor needs the new size
-            for current_frame in self.navigation_context.current.iter() {
+            for current_entry in self.navigation_context.current.iter() {
                 debug!("Constellation: Sending size for frame in current frame tree.");
-                let source_frame = current_frame.find(pipeline_id);
+                let source_frame = current_entry.frame_tree.find(pipeline_id);
                 for source_frame in source_frame.iter() {
                     let mut children = source_frame.children.borrow_mut();
                     match children.iter_mut().find(|child| subpage_eq(child)) {
@@ -779,11 +896,14 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         };
 
         let new_frame_pipeline_id = self.get_next_pipeline_id();
+        let position = self.navigation_context.position();
         let pipeline = self.new_pipeline(
             new_frame_pipeline_id,
             Some((containing_page_pipeline_id, new_subpage_id)),
+            source_pipeline.top_level_browsing_context_id,
             script_pipeline,
-            LoadData::new(url)
+            LoadData::new(url),
+            position
         );
 
         let rect = self.pending_sizes.remove(&(containing_page_pipeline_id, new_subpage_id));
@@ -804,7 +924,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         let url = load_data.url.to_string();
         debug!("Constellation: received message to load {:?}", url);
         // Make sure no pending page would be overridden.
-        let source_frame = self.current_frame().as_ref().unwrap().find(source_id).expect(
+        let source_frame = self.current_frame().unwrap().find(source_id).expect(
             "Constellation: received a LoadUrl message from a pipeline_id associated
             with a pipeline not in the active frame tree. This should be
             impossible.");
@@ -813,7 +933,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             let old_id = frame_change.before.expect("Constellation: Received load msg
                 from pipeline, but there is no currently active page. This should
                 be impossible.");
-            let changing_frame = self.current_frame().as_ref().unwrap().find(old_id).expect("Constellation:
+            let changing_frame = self.current_frame().unwrap().find(old_id).expect("Constellation:
                 Pending change has non-active source pipeline. This should be
                 impossible.");
             if changing_frame.contains(source_id) || source_frame.contains(old_id) {
@@ -828,7 +948,12 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         let parent_id = source_frame.pipeline.borrow().parent;
         let next_pipeline_id = self.get_next_pipeline_id();
         let next_frame_id = self.get_next_frame_id();
-        let pipeline = self.new_pipeline(next_pipeline_id, parent_id, None, load_data);
+        let top_level_browsing_context_id =
+            source_frame.pipeline.borrow().top_level_browsing_context_id;
+        let position = self.navigation_context.position_after_load();
+        let pipeline = self.new_pipeline(next_pipeline_id, parent_id,
+                                         top_level_browsing_context_id, None, load_data,
+                                         position);
         self.browse(Some(source_id),
                     Rc::new(FrameTree::new(next_frame_id,
                                            pipeline.clone(),
@@ -841,46 +966,153 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
     fn handle_navigate_msg(&mut self, direction: constellation_msg::NavigationDirection) {
         debug!("received message to navigate {:?}", direction);
+        match direction {
+            NavigationDirection::Forward => self.traverse_history(1),
+            NavigationDirection::Back => self.traverse_history(-1),
+        }
+    }
 
+    fn handle_traverse_history_msg(&mut self, source_id: PipelineId, delta: i32) {
+        debug!("received message from {:?} to traverse the history by {:?}", source_id, delta);
+        if !self.pipeline_is_in_current_frame(source_id) {
+            return;
+        }
+        self.traverse_history(delta);
+    }
+
+    /// Moves `delta` entries back (if negative) or forward through the session history.
+    /// http://www.whatwg.org/html/#traverse-the-history
+    fn traverse_history(&mut self, delta: i32) {
         // TODO(tkuehn): what is the "critical point" beyond which pending frames
         // should not be cleared? Currently, the behavior is that forward/back
         // navigation always has navigation priority, and after that new page loading is
         // first come, first served.
-        let destination_frame = match direction {
-            NavigationDirection::Forward => {
-                if self.navigation_context.next.is_empty() {
-                    debug!("no next page to navigate to");
-                    return;
-                } else {
-                    let old = self.current_frame().as_ref().unwrap();
-                    for frame in old.iter() {
-                        frame.pipeline.borrow().revoke_paint_permission();
-                        frame.pipeline.borrow().freeze();
-                    }
-                }
-                self.navigation_context.forward(&mut *self.compositor_proxy)
+        let available = if delta < 0 {
+            self.navigation_context.previous.len()
+        } else {
+            self.navigation_context.next.len()
+        };
+        if delta == 0 || (delta.abs() as usize) > available {
+            debug!("no page to navigate to");
+            return;
+        }
+
+        let old_frame_tree = self.current_frame().unwrap().clone();
+        self.navigation_context.traverse(delta, &mut *self.compositor_proxy);
+
+        let (destination_frame, pipeline_id, url, state) = {
+            let entry = self.navigation_context.current.as_ref().unwrap();
+            (entry.frame_tree.clone(), entry.pipeline_id, entry.url.clone(), entry.state.clone())
+        };
+
+        // Entries that share a frame tree belong to documents that stay loaded.
+        if &*destination_frame as *const FrameTree != &*old_frame_tree as *const FrameTree {
+            for frame in old_frame_tree.iter() {
+                frame.pipeline.borrow().revoke_paint_permission();
+                frame.pipeline.borrow().freeze();
             }
-            NavigationDirection::Back => {
-                if self.navigation_context.previous.is_empty() {
-                    debug!("no previous page to navigate to");
-                    return;
-                } else {
-                    let old = self.current_frame().as_ref().unwrap();
-                    for frame in old.iter() {
-                        frame.pipeline.borrow().revoke_paint_permission();
-                        frame.pipeline.borrow().freeze();
-                    }
-                }
-                self.navigation_context.back(&mut *self.compositor_proxy)
+            for frame in destination_frame.iter() {
+                frame.pipeline.borrow().load();
+                frame.pipeline.borrow().thaw();
             }
+            self.send_frame_tree_and_grant_paint_permission(destination_frame);
+        }
+
+        self.send_session_history_position(None);
+        match self.pipelines.get(&pipeline_id) {
+            Some(pipeline) => pipeline.traverse_history(url, state),
+            None => (),
+        }
+    }
+
+    fn handle_history_change(&mut self, change: HistoryChange) {
+        let pipeline_id = match change {
+            HistoryChange::Push(pipeline_id, _, _) |
+            HistoryChange::Replace(pipeline_id, _, _) => pipeline_id,
         };
 
-        for frame in destination_frame.iter() {
-            frame.pipeline.borrow().load();
-            frame.pipeline.borrow().thaw();
+        // A document that is still loading has no session history entry yet.
+        match self.pending_frames.iter_mut().find(|frame_change| {
+            frame_change.after.contains(pipeline_id)
+        }) {
+            Some(frame_change) => {
+                frame_change.history_changes.push(change);
+                return;
+            }
+            None => (),
+        }
+
+        if !self.pipeline_is_in_current_frame(pipeline_id) {
+            debug!("ignoring session history change from inactive pipeline {:?}", pipeline_id);
+            return;
+        }
+
+        match change {
+            HistoryChange::Push(pipeline_id, url, state) => {
+                let frame_tree = self.current_frame().unwrap().clone();
+                let entry = SessionHistoryEntry {
+                    frame_tree: frame_tree,
+                    pipeline_id: pipeline_id,
+                    url: Some(url),
+                    state: state,
+                };
+                let evicted_frames = self.navigation_context.load(entry,
+                                                                  &mut *self.compositor_proxy);
+                self.handle_evicted_frames(evicted_frames);
+                // The document that pushed the entry already accounted for it.
+                self.send_session_history_position(Some(pipeline_id));
+            }
+            HistoryChange::Replace(pipeline_id, url, state) => {
+                let entry = self.navigation_context.current.as_mut().unwrap();
+                entry.pipeline_id = pipeline_id;
+                entry.url = Some(url);
+                entry.state = state;
+            }
+        }
+    }
+
+    /// Tells the documents of the current frame tree where the current entry is in the session
+    /// history, and how many entries there are, except for `except`.
+    fn send_session_history_position(&self, except: Option<PipelineId>) {
+        let (index, length) = self.navigation_context.position();
+        for frame_tree in self.current_frame().iter() {
+            for frame in frame_tree.iter() {
+                let pipeline = frame.pipeline.borrow();
+                if Some(pipeline.id) != except {
+                    pipeline.session_history_changed(index, length);
+                }
+            }
         }
-        self.send_frame_tree_and_grant_paint_permission(destination_frame);
+    }
 
+    fn handle_tick_animations_msg(&mut self, pipeline_ids: Vec<PipelineId>) {
+        self.animation_frame_count += 1;
+        let throttled = self.animation_frame_count % HIDDEN_FRAME_TICK_INTERVAL != 0;
+        for pipeline_id in pipeline_ids.into_iter() {
+            let visible = match self.current_frame().and_then(|frame| frame.is_visible(pipeline_id)) {
+                Some(visible) => visible,
+                None => {
+                    // Pipelines that are not in the current frame tree are frozen, so the
+                    // compositor must stop ticking them; they tell it again when thawed.
+                    // Pipelines that are still loading will be in the frame tree soon.
+                    let pending = self.pending_frames.iter().any(|frame_change| {
+                        frame_change.after.contains(pipeline_id)
+                    });
+                    if !pending {
+                        self.compositor_proxy.send(CompositorMsg::ChangeRunningAnimationsState(
+                            pipeline_id, AnimationState::NoAnimationsPresent));
+                    }
+                    continue
+                }
+            };
+            if !visible && throttled {
+                continue;
+            }
+            match self.pipelines.get(&pipeline_id) {
+                Some(pipeline) => pipeline.tick_animations(),
+                None => {}
+            }
+        }
     }
 
     fn pipeline_is_in_current_frame(&self, pipeline_id: PipelineId) -> bool {
@@ -889,8 +1121,8 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
     }
 
     fn handle_key_msg(&self, key: Key, state: KeyState, mods: KeyModifiers) {
-        match *self.current_frame() {
-            Some(ref frame) => {
+        match self.current_frame() {
+            Some(frame) => {
                 let ScriptControlChan(ref chan) = frame.pipeline.borrow().script_chan;
                 chan.send(ConstellationControlMsg::SendEvent(
                     frame.pipeline.borrow().id,
@@ -912,6 +1144,27 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         }
     }
 
+    /// Forwards a storage area change to every other pipeline in the current frame tree. The
+    /// script task only fires the `storage` event in documents with the same origin.
+    fn handle_broadcast_storage_event(&self, source: PipelineId, storage_type: StorageType,
+                                      url: Url, key: Option<String>, old_value: Option<String>,
+                                      new_value: Option<String>) {
+        let current_frame = match self.current_frame() {
+            Some(frame) => frame,
+            None => return,
+        };
+        for frame in current_frame.iter() {
+            let pipeline = frame.pipeline.borrow();
+            if pipeline.id == source {
+                continue
+            }
+            let ScriptControlChan(ref chan) = pipeline.script_chan;
+            chan.send(ConstellationControlMsg::DispatchStorageEvent(
+                pipeline.id, storage_type, url.clone(), key.clone(), old_value.clone(),
+                new_value.clone())).unwrap();
+        }
+    }
+
     fn handle_painter_ready_msg(&mut self, pipeline_id: PipelineId) {
         debug!("Painter {:?} ready to send paint msg", pipeline_id);
         // This message could originate from a pipeline in the navigation context or
@@ -939,7 +1192,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 // Create the next frame tree that will be given to the compositor
                 let next_frame_tree = if to_add.parent.borrow().is_some() {
                     // NOTE: work around borrowchk issues
-                    self.current_frame().as_ref().unwrap().clone()
+                    self.current_frame().unwrap().clone()
                 } else {
                     to_add.clone()
                 };
@@ -948,7 +1201,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 match frame_change.before {
                     Some(revoke_id) if self.current_frame().is_some() => {
                         debug!("Constellation: revoking permission from {:?}", revoke_id);
-                        let current_frame = self.current_frame().as_ref().unwrap();
+                        let current_frame = self.current_frame().unwrap();
 
                         let to_revoke = current_frame.find(revoke_id).expect(
                             "Constellation: pending frame change refers to an old \
@@ -993,7 +1246,12 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
                 self.send_frame_tree_and_grant_paint_permission(next_frame_tree.clone());
                 self.handle_evicted_frames_for_load_navigation(next_frame_tree,
+                                                               pipeline_id,
                                                                frame_change.navigation_type);
+                for change in frame_change.history_changes.into_iter() {
+                    self.handle_history_change(change);
+                }
+                self.send_session_history_position(None);
             },
             None => (),
         }
@@ -1009,9 +1267,9 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             let _ = chan.send(ConstellationControlMsg::Resize(pipeline.id, new_size));
             already_seen.insert(pipeline.id);
         }
-        for frame_tree in self.navigation_context.previous.iter()
+        for entry in self.navigation_context.previous.iter()
             .chain(self.navigation_context.next.iter()) {
-            let pipeline = &*frame_tree.pipeline.borrow();
+            let pipeline = &*entry.frame_tree.pipeline.borrow();
             if !already_seen.contains(&pipeline.id) {
                 debug!("constellation sending resize message to inactive frame");
                 let ScriptControlChan(ref chan) = pipeline.script_chan;
@@ -1062,13 +1320,15 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
     fn handle_evicted_frames_for_load_navigation(&mut self,
                                                  frame_tree: Rc<FrameTree>,
+                                                 pipeline_id: PipelineId,
                                                  navigation_type: NavigationType) {
         // Don't call navigation_context.load() on a Navigate type (or None, as in the case of
         // parsed iframes that finish loading).
         match navigation_type {
             NavigationType::Load => {
                 debug!("Evicting frames for NavigationType::Load");
-                let evicted_frames = self.navigation_context.load(frame_tree,
+                let entry = SessionHistoryEntry::new(frame_tree, pipeline_id);
+                let evicted_frames = self.navigation_context.load(entry,
                                                                   &mut *self.compositor_proxy);
                 self.handle_evicted_frames(evicted_frames);
             }
@@ -1116,8 +1376,8 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
     fn create_compositor_layer_for_iframe_if_necessary(&mut self, pipeline_id: PipelineId) {
         let current_frame_tree = match self.current_frame() {
-            &Some(ref tree) => tree.clone(),
-            &None => return,
+            Some(tree) => tree.clone(),
+            None => return,
         };
 
         let pair = self.find_child_parent_pair_in_frame_tree(current_frame_tree,
@@ -2,7 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use compositor_task::{CompositorEventListener, CompositorReceiver, Msg};
+use animation_timer::AnimationTimerProxy;
+use compositor_task::{CompositorEventListener, CompositorProxy, CompositorReceiver, Msg};
 use windowing::WindowEvent;
 
 use geom::scale_factor::ScaleFactor;
@@ -27,10 +28,13 @@ pub struct NullCompositor {
     time_profiler_chan: TimeProfilerChan,
     /// A channel to the memory profiler.
     memory_profiler_chan: MemoryProfilerChan,
+    /// A handle to the animation timer.
+    animation_timer: AnimationTimerProxy,
 }
 
 impl NullCompositor {
-    fn new(port: Box<CompositorReceiver>,
+    fn new(sender: Box<CompositorProxy+Send>,
+           port: Box<CompositorReceiver>,
            constellation_chan: ConstellationChan,
            time_profiler_chan: TimeProfilerChan,
            memory_profiler_chan: MemoryProfilerChan)
@@ -40,15 +44,18 @@ impl NullCompositor {
             constellation_chan: constellation_chan,
             time_profiler_chan: time_profiler_chan,
             memory_profiler_chan: memory_profiler_chan,
+            animation_timer: AnimationTimerProxy::new(sender),
         }
     }
 
-    pub fn create(port: Box<CompositorReceiver>,
+    pub fn create(sender: Box<CompositorProxy+Send>,
+                  port: Box<CompositorReceiver>,
                   constellation_chan: ConstellationChan,
                   time_profiler_chan: TimeProfilerChan,
                   memory_profiler_chan: MemoryProfilerChan)
                   -> NullCompositor {
-        let compositor = NullCompositor::new(port,
+        let compositor = NullCompositor::new(sender,
+                                             port,
                                              constellation_chan,
                                              time_profiler_chan,
                                              memory_profiler_chan);
@@ -98,6 +105,15 @@ impl CompositorEventListener for NullCompositor {
                 response_channel.send(()).unwrap();
             }
 
+            // Animation frame callbacks are run even though nothing is drawn.
+            Msg::ChangeRunningAnimationsState(pipeline_id, animation_state) => {
+                self.animation_timer.set_animation_state(pipeline_id, animation_state);
+            }
+
+            Msg::AnimationFrame => {
+                self.animation_timer.tick(&self.constellation_chan);
+            }
+
             // Explicitly list ignored messages so that when we add a new one,
             // we'll notice and think about whether it needs a response, like
             // SetFrameTree.
@@ -115,6 +131,8 @@ impl CompositorEventListener for NullCompositor {
             Msg::ChangePageTitle(..) |
             Msg::ChangePageLoadData(..) |
             Msg::KeyEvent(..) |
+            Msg::HasTouchEventListeners(..) |
+            Msg::TouchEvent(..) |
             Msg::SetCursor(..) => {}
             Msg::PaintTaskExited(..) => {}
         }
@@ -128,6 +146,8 @@ impl CompositorEventListener for NullCompositor {
         // another task from finishing (i.e. SetIds)
         while self.port.try_recv_compositor_msg().is_some() {}
 
+        self.animation_timer.shutdown();
+
         self.time_profiler_chan.send(time::TimeProfilerMsg::Exit);
         self.memory_profiler_chan.send(memory::MemoryProfilerMsg::Exit);
     }
@@ -43,7 +43,9 @@ pub use constellation::Constellation;
 pub mod compositor_task;
 
 mod compositor_layer;
+mod animation_timer;
 mod scrolling;
+mod touch;
 
 mod compositor;
 mod headless;
@@ -12,18 +12,22 @@ use gfx::paint_task::Msg as PaintMsg;
 use gfx::paint_task::{PaintChan, PaintTask};
 use gfx::font_cache_task::FontCacheTask;
 use msg::constellation_msg::{ConstellationChan, Failure, PipelineId, SubpageId};
-use msg::constellation_msg::{LoadData, WindowSizeData, PipelineExitType};
+use msg::constellation_msg::{HistoryState, LoadData, WindowSizeData, PipelineExitType};
+use msg::constellation_msg::TopLevelBrowsingContextId;
 use net::image_cache_task::ImageCacheTask;
 use net::resource_task::ResourceTask;
 use net::storage_task::StorageTask;
 use util::time::TimeProfilerChan;
 use std::rc::Rc;
 use std::sync::mpsc::{Receiver, channel};
+use url::Url;
 
 /// A uniquely-identifiable pipeline of script task, layout task, and paint task.
 pub struct Pipeline {
     pub id: PipelineId,
     pub parent: Option<(PipelineId, SubpageId)>,
+    /// The top-level browsing context this pipeline's frame belongs to.
+    pub top_level_browsing_context_id: TopLevelBrowsingContextId,
     pub script_chan: ScriptControlChan,
     pub layout_chan: LayoutControlChan,
     pub paint_chan: PaintChan,
@@ -49,6 +53,7 @@ impl Pipeline {
     /// If script_pipeline is not None, then subpage_id must also be not None.
     pub fn create<LTF,STF>(id: PipelineId,
                            parent: Option<(PipelineId, SubpageId)>,
+                           top_level_browsing_context_id: TopLevelBrowsingContextId,
                            constellation_chan: ConstellationChan,
                            compositor_proxy: Box<CompositorProxy+'static+Send>,
                            devtools_chan: Option<DevtoolsControlChan>,
@@ -78,6 +83,7 @@ impl Pipeline {
                 let (script_chan, script_port) = channel();
                 ScriptTaskFactory::create(None::<&mut STF>,
                                           id,
+                                          top_level_browsing_context_id,
                                           compositor_proxy.clone_compositor_proxy(),
                                           &layout_pair,
                                           ScriptControlChan(script_chan.clone()),
@@ -130,6 +136,7 @@ impl Pipeline {
 
         Pipeline::new(id,
                       parent,
+                      top_level_browsing_context_id,
                       script_chan,
                       LayoutControlChan(pipeline_chan),
                       paint_chan,
@@ -140,6 +147,7 @@ impl Pipeline {
 
     pub fn new(id: PipelineId,
                parent: Option<(PipelineId, SubpageId)>,
+               top_level_browsing_context_id: TopLevelBrowsingContextId,
                script_chan: ScriptControlChan,
                layout_chan: LayoutControlChan,
                paint_chan: PaintChan,
@@ -150,6 +158,7 @@ impl Pipeline {
         Pipeline {
             id: id,
             parent: parent,
+            top_level_browsing_context_id: top_level_browsing_context_id,
             script_chan: script_chan,
             layout_chan: layout_chan,
             paint_chan: paint_chan,
@@ -201,6 +210,23 @@ impl Pipeline {
         let _ = script_channel.send(ConstellationControlMsg::Thaw(self.id)).unwrap();
     }
 
+    pub fn traverse_history(&self, url: Option<Url>, state: Option<HistoryState>) {
+        let ScriptControlChan(ref script_channel) = self.script_chan;
+        let _ = script_channel.send(
+            ConstellationControlMsg::HistoryTraversal(self.id, url, state));
+    }
+
+    pub fn session_history_changed(&self, index: u32, length: u32) {
+        let ScriptControlChan(ref script_channel) = self.script_chan;
+        let _ = script_channel.send(
+            ConstellationControlMsg::SessionHistoryChanged(self.id, index, length));
+    }
+
+    pub fn tick_animations(&self) {
+        let ScriptControlChan(ref script_channel) = self.script_chan;
+        let _ = script_channel.send(ConstellationControlMsg::TickAllAnimations(self.id));
+    }
+
     pub fn force_exit(&self) {
         let ScriptControlChan(ref script_channel) = self.script_chan;
         let _ = script_channel.send(
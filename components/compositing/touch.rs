@@ -0,0 +1,191 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Recognizes the default gestures of touch input: taps, panning and pinch zoom. Touch points
+//! are fed in after script has dispatched them, so that a page that prevents the default action
+//! of a touch event keeps the gesture from happening.
+
+use geom::point::TypedPoint2D;
+use layers::geometry::DevicePixel;
+use msg::constellation_msg::TouchId;
+use std::num::Float;
+
+/// The distance in device pixels a touch point has to move before a tap becomes a pan.
+static TOUCH_PAN_MIN_SCREEN_PX: f32 = 20.0;
+
+pub struct TouchHandler {
+    state: TouchState,
+    active_touch_points: Vec<TouchPoint>,
+}
+
+#[derive(Clone, Copy)]
+struct TouchPoint {
+    id: TouchId,
+    point: TypedPoint2D<DevicePixel, f32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TouchState {
+    /// Nothing is touching the surface.
+    Nothing,
+    /// A single touch point is down and has not moved far enough to start panning. Releasing
+    /// it is a tap.
+    Touching,
+    /// A single touch point is moving.
+    Panning,
+    /// Two touch points are moving.
+    Pinching,
+    /// More than two touch points are down. No gestures are recognized until enough of them
+    /// are released.
+    MultiTouch,
+    /// Script prevented the default action of an event in this touch sequence. No gestures are
+    /// recognized until all touch points are released.
+    DefaultPrevented,
+}
+
+/// What the compositor should do in response to a touch event.
+pub enum TouchAction {
+    NoAction,
+    /// Scroll by the given delta, as a wheel would at the given position.
+    Scroll(TypedPoint2D<DevicePixel, f32>, TypedPoint2D<DevicePixel, f32>),
+    /// Zoom by the given magnification.
+    Zoom(f32),
+    /// Click at the given position.
+    Click(TypedPoint2D<DevicePixel, f32>),
+}
+
+impl TouchHandler {
+    pub fn new() -> TouchHandler {
+        TouchHandler {
+            state: TouchState::Nothing,
+            active_touch_points: Vec::new(),
+        }
+    }
+
+    pub fn on_touch_down(&mut self, id: TouchId, point: TypedPoint2D<DevicePixel, f32>,
+                         prevented: bool) -> TouchAction {
+        self.active_touch_points.push(TouchPoint { id: id, point: point });
+
+        self.state = if prevented {
+            TouchState::DefaultPrevented
+        } else {
+            match self.state {
+                TouchState::Nothing => TouchState::Touching,
+                TouchState::Touching | TouchState::Panning => TouchState::Pinching,
+                TouchState::Pinching | TouchState::MultiTouch => TouchState::MultiTouch,
+                TouchState::DefaultPrevented => TouchState::DefaultPrevented,
+            }
+        };
+        TouchAction::NoAction
+    }
+
+    pub fn on_touch_move(&mut self, id: TouchId, point: TypedPoint2D<DevicePixel, f32>,
+                         prevented: bool) -> TouchAction {
+        let index = match self.active_touch_points.iter().position(|t| t.id == id) {
+            Some(index) => index,
+            None => {
+                warn!("Got a touchmove event for an unknown touch point");
+                return TouchAction::NoAction;
+            }
+        };
+
+        if prevented {
+            self.state = TouchState::DefaultPrevented;
+        }
+
+        let old_point = self.active_touch_points[index].point;
+        let action = match self.state {
+            TouchState::Touching => {
+                let delta = point - old_point;
+                if delta.x.get().abs() <= TOUCH_PAN_MIN_SCREEN_PX &&
+                   delta.y.get().abs() <= TOUCH_PAN_MIN_SCREEN_PX {
+                    // Keep the point where the touch started, so that slow movements still
+                    // add up to a pan.
+                    return TouchAction::NoAction;
+                }
+                self.state = TouchState::Panning;
+                TouchAction::Scroll(delta, point)
+            }
+            TouchState::Panning => TouchAction::Scroll(point - old_point, point),
+            TouchState::Pinching => {
+                let old_distance = self.pinch_distance();
+                self.active_touch_points[index].point = point;
+                let new_distance = self.pinch_distance();
+                if old_distance > 0.0 && new_distance > 0.0 {
+                    TouchAction::Zoom(new_distance / old_distance)
+                } else {
+                    TouchAction::NoAction
+                }
+            }
+            TouchState::Nothing | TouchState::MultiTouch | TouchState::DefaultPrevented => {
+                TouchAction::NoAction
+            }
+        };
+        self.active_touch_points[index].point = point;
+        action
+    }
+
+    pub fn on_touch_up(&mut self, id: TouchId, point: TypedPoint2D<DevicePixel, f32>,
+                       prevented: bool) -> TouchAction {
+        if !self.remove_touch_point(id) {
+            warn!("Got a touchend event for an unknown touch point");
+            return TouchAction::NoAction;
+        }
+
+        // A tap whose touchend was cancelled does not click.
+        if prevented {
+            self.state = TouchState::DefaultPrevented;
+        }
+
+        let action = match self.state {
+            TouchState::Touching => TouchAction::Click(point),
+            _ => TouchAction::NoAction,
+        };
+        self.update_state_after_release();
+        action
+    }
+
+    pub fn on_touch_cancel(&mut self, id: TouchId) -> TouchAction {
+        if !self.remove_touch_point(id) {
+            warn!("Got a touchcancel event for an unknown touch point");
+            return TouchAction::NoAction;
+        }
+
+        // A cancelled tap does not click.
+        if self.state == TouchState::Touching {
+            self.state = TouchState::Panning;
+        }
+        self.update_state_after_release();
+        TouchAction::NoAction
+    }
+
+    fn remove_touch_point(&mut self, id: TouchId) -> bool {
+        match self.active_touch_points.iter().position(|t| t.id == id) {
+            Some(index) => {
+                self.active_touch_points.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update_state_after_release(&mut self) {
+        let remaining = self.active_touch_points.len();
+        self.state = match (self.state, remaining) {
+            (_, 0) => TouchState::Nothing,
+            (TouchState::DefaultPrevented, _) => TouchState::DefaultPrevented,
+            // Lifting one finger of a pinch keeps panning with the other one.
+            (_, 1) => TouchState::Panning,
+            (_, 2) => TouchState::Pinching,
+            (_, _) => TouchState::MultiTouch,
+        };
+    }
+
+    /// The distance between the first two touch points.
+    fn pinch_distance(&self) -> f32 {
+        debug_assert!(self.active_touch_points.len() >= 2);
+        let delta = self.active_touch_points[1].point - self.active_touch_points[0].point;
+        (delta.x.get() * delta.x.get() + delta.y.get() * delta.y.get()).sqrt()
+    }
+}
@@ -59,7 +59,7 @@ extern crate freetype;
 #[cfg(target_os="macos")] extern crate core_graphics;
 #[cfg(target_os="macos")] extern crate core_text;
 
-pub use paint_context::PaintContext;
+pub use paint_context::{PaintContext, ScaledFontExtensionMethods};
 
 // Private painting modules
 mod paint_context;
@@ -835,8 +835,8 @@ impl<'a> PaintContext<'a> {
             .get_paint_font_from_template(&text.text_run.font_template,
                                            text.text_run.actual_pt_size)
             .borrow()
-            .draw_text_into_context(self,
-                                    &*text.text_run,
+            .draw_text_into_context(&self.draw_target,
+                                    &**text.text_run,
                                     &text.range,
                                     baseline_origin,
                                     text.text_color,
@@ -1126,10 +1126,11 @@ impl ToRadiiPx for BorderRadii<Au> {
     }
 }
 
-trait ScaledFontExtensionMethods {
+pub trait ScaledFontExtensionMethods {
+    /// Draws the glyphs of `run` in `range` onto `target`, starting at `baseline_origin`.
     fn draw_text_into_context(&self,
-                              rctx: &PaintContext,
-                              run: &Box<TextRun>,
+                              target: &DrawTarget,
+                              run: &TextRun,
                               range: &Range<CharIndex>,
                               baseline_origin: Point2D<Au>,
                               color: Color,
@@ -1138,13 +1139,12 @@ trait ScaledFontExtensionMethods {
 
 impl ScaledFontExtensionMethods for ScaledFont {
     fn draw_text_into_context(&self,
-                              rctx: &PaintContext,
-                              run: &Box<TextRun>,
+                              target: &DrawTarget,
+                              run: &TextRun,
                               range: &Range<CharIndex>,
                               baseline_origin: Point2D<Au>,
                               color: Color,
                               antialias: bool) {
-        let target = rctx.get_draw_target();
         let pattern = ColorPattern::new(color);
         let azure_pattern = pattern.azure_color_pattern;
         assert!(!azure_pattern.is_null());
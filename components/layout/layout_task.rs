@@ -38,7 +38,7 @@ use script::dom::bindings::js::LayoutJS;
 use script::dom::node::{LayoutDataRef, Node, NodeTypeId};
 use script::dom::element::ElementTypeId;
 use script::dom::htmlelement::HTMLElementTypeId;
-use script::layout_interface::{ContentBoxResponse, ContentBoxesResponse};
+use script::layout_interface::{ComputedStyleResponse, ContentBoxResponse, ContentBoxesResponse};
 use script::layout_interface::ReflowQueryType;
 use script::layout_interface::{HitTestResponse, LayoutChan, LayoutRPC};
 use script::layout_interface::{MouseOverResponse, Msg};
@@ -50,7 +50,8 @@ use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::{ConstellationChan, Failure, PipelineExitType, PipelineId};
 use net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use net::local_image_cache::{ImageResponder, LocalImageCache};
-use net::resource_task::{ResourceTask, load_bytes_iter};
+use net::mixed_content;
+use net::resource_task::{LoadContext, ResourceTask, load_subresource_bytes_iter};
 use servo_util::cursor::Cursor;
 use servo_util::geometry::Au;
 use servo_util::logical_geometry::LogicalPoint;
@@ -67,8 +68,11 @@ use std::ops::{Deref, DerefMut};
 use std::sync::mpsc::{channel, Sender, Receiver, Select};
 use std::mem;
 use std::ptr;
+use selectors::parser::PseudoElement;
 use style::selector_matching::Stylist;
 use style::computed_values::{filter, mix_blend_mode};
+use style::properties::ComputedValues;
+use style::font_face::Source;
 use style::stylesheets::{Origin, Stylesheet, iter_font_face_rules};
 use style::node::TNode;
 use style::media_queries::{MediaType, Device};
@@ -108,6 +112,9 @@ pub struct LayoutTaskData {
 
     /// A queued response for the content boxes of a node.
     pub content_boxes_response: Vec<Rect<Au>>,
+
+    /// A queued response for the computed style of a node, and the used size of its content box.
+    pub computed_style_response: (Option<Arc<ComputedValues>>, Option<Size2D<Au>>),
 }
 
 /// Information needed by the layout task.
@@ -290,6 +297,7 @@ impl LayoutTask {
                     generation: 0,
                     content_box_response: Rect::zero(),
                     content_boxes_response: Vec::new(),
+                    computed_style_response: (None, None),
               })),
         }
     }
@@ -397,13 +405,21 @@ impl LayoutTask {
                                                                                  LayoutTaskData>>)
                                  -> bool {
         match request {
-            Msg::AddStylesheet(sheet) => self.handle_add_stylesheet(sheet, possibly_locked_rw_data),
-            Msg::LoadStylesheet(url) => self.handle_load_stylesheet(url, possibly_locked_rw_data),
+            Msg::AddStylesheet(sheet, document_url, console) => {
+                self.handle_add_stylesheet(sheet, &document_url, console.as_ref(),
+                                           possibly_locked_rw_data)
+            }
+            Msg::LoadStylesheet(url, document_url, console) => {
+                self.handle_load_stylesheet(url, &document_url, console, possibly_locked_rw_data)
+            }
             Msg::SetQuirksMode => self.handle_set_quirks_mode(possibly_locked_rw_data),
             Msg::GetRPC(response_chan) => {
                 response_chan.send(box LayoutRPCImpl(self.rw_data.clone()) as
                                    Box<LayoutRPC + Send>).unwrap();
             },
+            Msg::GetFontCacheTask(response_chan) => {
+                response_chan.send(self.font_cache_task.clone()).unwrap();
+            },
             Msg::Reflow(data) => {
                 profile(TimeProfilerCategory::LayoutPerform,
                         self.profiler_metadata(&*data),
@@ -479,12 +495,18 @@ impl LayoutTask {
 
     fn handle_load_stylesheet<'a>(&'a self,
                                   url: Url,
+                                  document_url: &Url,
+                                  console: Option<Sender<String>>,
                                   possibly_locked_rw_data:
                                     &mut Option<MutexGuard<'a, LayoutTaskData>>) {
         // TODO: Get the actual value. http://dev.w3.org/csswg/css-syntax/#environment-encoding
         let environment_encoding = UTF_8 as EncodingRef;
 
-        let (metadata, iter) = load_bytes_iter(&self.resource_task, url);
+        // The resource task checks the targets of redirects for mixed content.
+        let (metadata, iter) = load_subresource_bytes_iter(&self.resource_task, url,
+                                                           LoadContext::Style,
+                                                           document_url.clone(),
+                                                           console.clone());
         let protocol_encoding_label = metadata.charset.as_ref().map(|s| s.as_slice());
         let final_url = metadata.final_url;
 
@@ -493,17 +515,26 @@ impl LayoutTask {
                                                 protocol_encoding_label,
                                                 Some(environment_encoding),
                                                 Origin::Author);
-        self.handle_add_stylesheet(sheet, possibly_locked_rw_data);
+        self.handle_add_stylesheet(sheet, document_url, console.as_ref(),
+                                   possibly_locked_rw_data);
     }
 
     fn handle_add_stylesheet<'a>(&'a self,
                                  sheet: Stylesheet,
+                                 document_url: &Url,
+                                 console: Option<&Sender<String>>,
                                  possibly_locked_rw_data:
                                     &mut Option<MutexGuard<'a, LayoutTaskData>>) {
         // Find all font-face rules and notify the font cache of them.
         // GWTODO: Need to handle unloading web fonts (when we handle unloading stylesheets!)
         let mut rw_data = self.lock_rw_data(possibly_locked_rw_data);
         iter_font_face_rules(&sheet, &rw_data.stylist.device, &|&:family, src| {
+            if let Source::Url(ref url_source) = *src {
+                if !mixed_content::allow_subresource_load(document_url, &url_source.url,
+                                                          LoadContext::Font, console) {
+                    return;
+                }
+            }
             self.font_cache_task.add_web_font(family.to_owned(), (*src).clone());
         });
         rw_data.stylist.add_stylesheet(sheet);
@@ -627,6 +658,40 @@ impl LayoutTask {
         rw_data.content_boxes_response = iterator.rects;
     }
 
+    fn process_computed_style_request<'a>(&'a self,
+                                          requested_node: TrustedNodeAddress,
+                                          pseudo_element: &Option<PseudoElement>,
+                                          layout_root: &mut FlowRef,
+                                          rw_data: &mut RWGuard<'a>) {
+        let mut node: LayoutJS<Node> = unsafe {
+            LayoutJS::from_trusted_node_address(requested_node)
+        };
+        let node: &mut LayoutNode = unsafe {
+            mem::transmute(&mut node)
+        };
+
+        let style = {
+            let layout_data_ref = node.borrow_layout_data();
+            layout_data_ref.as_ref().and_then(|layout_data| {
+                match *pseudo_element {
+                    None => layout_data.shared_data.style.clone(),
+                    Some(PseudoElement::Before) => layout_data.data.before_style.clone(),
+                    Some(PseudoElement::After) => layout_data.data.after_style.clone(),
+                }
+            })
+        };
+
+        let used_size = style.as_ref().and_then(|style| {
+            let requested_node: OpaqueNode = OpaqueNodeMethods::from_script_node(requested_node);
+            let mut iterator = ContentBoxSizeFragmentBorderBoxIterator::new(requested_node,
+                                                                            style.clone());
+            sequential::iterate_through_flow_tree_fragment_border_boxes(layout_root,
+                                                                       &mut iterator);
+            iterator.rect.map(|rect| rect.size)
+        });
+        rw_data.computed_style_response = (style, used_size);
+    }
+
     fn build_display_list_for_reflow<'a>(&'a self,
                                          data: &Reflow,
                                          node: &mut LayoutNode,
@@ -875,6 +940,12 @@ impl LayoutTask {
             ReflowQueryType::ContentBoxesQuery(node) => {
                 self.process_content_boxes_request(node, &mut layout_root, &mut rw_data)
             }
+            ReflowQueryType::ComputedStyleQuery(node, ref pseudo_element) => {
+                self.process_computed_style_request(node,
+                                                    pseudo_element,
+                                                    &mut layout_root,
+                                                    &mut rw_data)
+            }
             ReflowQueryType::NoQuery => {}
         }
 
@@ -978,6 +1049,14 @@ impl LayoutRPC for LayoutRPCImpl {
         ContentBoxesResponse(rw_data.content_boxes_response.clone())
     }
 
+    /// Requests the computed style of a node, as in the `getComputedStyle()` call.
+    fn computed_style(&self) -> ComputedStyleResponse {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock().unwrap();
+        let (ref style, used_size) = rw_data.computed_style_response;
+        ComputedStyleResponse(style.clone(), used_size)
+    }
+
     /// Requests the node containing the point of interest.
     fn hit_test(&self, _: TrustedNodeAddress, point: Point2D<f32>) -> Result<HitTestResponse, ()> {
         let point = Point2D(Au::from_frac_px(point.x as f64), Au::from_frac_px(point.y as f64));
@@ -1091,3 +1170,43 @@ impl FragmentBorderBoxIterator for CollectingFragmentBorderBoxIterator {
         self.node_address == fragment.node
     }
 }
+
+/// Computes the used size of the content box of the boxes generated by a node or one of its
+/// pseudo-elements, which are told apart from the node's other fragments by their style.
+struct ContentBoxSizeFragmentBorderBoxIterator {
+    node_address: OpaqueNode,
+    style: Arc<ComputedValues>,
+    rect: Option<Rect<Au>>,
+}
+
+impl ContentBoxSizeFragmentBorderBoxIterator {
+    fn new(node_address: OpaqueNode, style: Arc<ComputedValues>)
+           -> ContentBoxSizeFragmentBorderBoxIterator {
+        ContentBoxSizeFragmentBorderBoxIterator {
+            node_address: node_address,
+            style: style,
+            rect: None,
+        }
+    }
+}
+
+impl FragmentBorderBoxIterator for ContentBoxSizeFragmentBorderBoxIterator {
+    fn process(&mut self, fragment: &Fragment, border_box: &Rect<Au>) {
+        let border_padding = fragment.border_padding.to_physical(fragment.style.writing_mode);
+        let content_box = Rect(Point2D(border_box.origin.x + border_padding.left,
+                                       border_box.origin.y + border_padding.top),
+                               fragment.content_box().size.to_physical(fragment.style.writing_mode));
+        self.rect = Some(match self.rect {
+            Some(rect) => rect.union(&content_box),
+            None => content_box,
+        });
+    }
+
+    fn should_process(&mut self, fragment: &Fragment) -> bool {
+        // Text fragments belong to the text node or, for generated content, to an inline box
+        // whose width and height don't apply.
+        self.node_address == fragment.node &&
+            &*fragment.style as *const ComputedValues == &*self.style as *const ComputedValues &&
+            !fragment.is_scanned_text_fragment()
+    }
+}
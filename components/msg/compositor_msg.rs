@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use azure::azure_hl::Color;
-use constellation_msg::{Key, KeyState, KeyModifiers};
+use constellation_msg::{Key, KeyState, KeyModifiers, TouchEventType, TouchId};
 use geom::point::Point2D;
 use geom::rect::Rect;
 use layers::platform::surface::NativeGraphicsMetadata;
@@ -32,6 +32,15 @@ pub enum ReadyState {
     FinishedLoading,
 }
 
+/// Whether a pipeline has `requestAnimationFrame` callbacks waiting for the next frame.
+#[derive(PartialEq, Clone, Debug, Copy)]
+pub enum AnimationState {
+    /// Informs the compositor that the pipeline needs to be ticked every frame.
+    AnimationsPresent,
+    /// Informs the compositor that the pipeline no longer needs to be ticked.
+    NoAnimationsPresent,
+}
+
 /// A newtype struct for denoting the age of messages; prevents race conditions.
 #[derive(PartialEq, Eq, Debug, Copy)]
 pub struct Epoch(pub uint);
@@ -115,7 +124,18 @@ pub trait ScriptListener {
                              point: Point2D<f32>);
     /// Informs the compositor that the title of the page with the given pipeline ID has changed.
     fn set_title(&mut self, pipeline_id: PipelineId, new_title: Option<String>);
+    /// Informs the compositor whether the page with the given pipeline ID has animation frame
+    /// callbacks pending.
+    fn set_animation_state(&mut self, pipeline_id: PipelineId, animation_state: AnimationState);
     fn close(&mut self);
     fn dup(&mut self) -> Box<ScriptListener+'static>;
     fn send_key_event(&mut self, key: Key, state: KeyState, modifiers: KeyModifiers);
+    /// Informs the compositor that the page with the given pipeline ID has touch event
+    /// listeners, so that touch events are sent to it before the default gestures run.
+    fn set_has_touch_event_listeners(&mut self, pipeline_id: PipelineId);
+    /// Reports a touch event back to the compositor once script has dispatched it, so that
+    /// the compositor can run its default gestures unless it was prevented. `point` is the
+    /// position of the touch point in the window, as sent by the compositor.
+    fn send_touch_event(&mut self, event_type: TouchEventType, identifier: TouchId,
+                        point: Point2D<f32>, prevented: bool);
 }
@@ -13,6 +13,8 @@ use hyper::method::Method;
 use layers::geometry::DevicePixel;
 use util::cursor::Cursor;
 use util::geometry::{PagePx, ViewportPx};
+use util::str::DOMString;
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
 use std::sync::mpsc::{channel, Sender, Receiver};
 use url::Url;
 
@@ -59,6 +61,20 @@ pub enum KeyState {
     Repeated,
 }
 
+/// The phase of a touch point, as reported by the windowing system.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TouchEventType {
+    Down,
+    Move,
+    Up,
+    Cancel,
+}
+
+/// An opaque identifier for a touch point, unique among the points currently touching the
+/// surface.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TouchId(pub i32);
+
 //N.B. Based on the glutin key enum
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Key {
@@ -212,8 +228,25 @@ pub enum Msg {
     GetPipelineTitle(PipelineId),
     /// Requests that the constellation inform the compositor of the a cursor change.
     SetCursor(Cursor),
+    /// Notifies the other pipelines that the given pipeline changed a storage area:
+    /// the storage type, the document URL, and the key, old value and new value of the change.
+    BroadcastStorageEvent(PipelineId, StorageType, Url, Option<DOMString>, Option<DOMString>,
+                          Option<DOMString>),
+    /// Adds a session history entry for the given pipeline's document, with the given URL and
+    /// serialized state, after `pushState` or a fragment navigation.
+    PushHistoryState(PipelineId, Url, Option<HistoryState>),
+    /// Replaces the URL and serialized state of the current session history entry.
+    ReplaceHistoryState(PipelineId, Url, Option<HistoryState>),
+    /// Traverses the session history by the given number of entries, on behalf of the given
+    /// pipeline.
+    TraverseHistory(PipelineId, i32),
+    /// Requests that the given pipelines run their animation frame callbacks.
+    TickAnimations(Vec<PipelineId>),
 }
 
+/// The state object of a session history entry, serialized with `JS_WriteStructuredClone`.
+pub type HistoryState = Vec<u64>;
+
 /// Similar to net::resource_task::LoadData
 /// can be passed to LoadUrl to load a page with GET/POST
 /// parameters or headers
@@ -255,6 +288,33 @@ pub struct PipelineId(pub uint);
 #[derive(Clone, PartialEq, Eq, Copy, Hash, Debug)]
 pub struct SubpageId(pub uint);
 
+static mut NEXT_TOP_LEVEL_BROWSING_CONTEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Identifies a top-level browsing context across navigations. Every pipeline of the frame
+/// tree, including those of iframes, carries the ID of the top-level browsing context it
+/// belongs to.
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug)]
+pub struct TopLevelBrowsingContextId(pub usize);
+
+impl TopLevelBrowsingContextId {
+    /// Returns an ID that is unique within this process.
+    pub fn new() -> TopLevelBrowsingContextId {
+        TopLevelBrowsingContextId(unsafe {
+            NEXT_TOP_LEVEL_BROWSING_CONTEXT_ID.fetch_add(1, Ordering::SeqCst)
+        })
+    }
+}
+
+/// The storage area a storage message refers to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum StorageType {
+    /// `window.sessionStorage`, kept in memory only and scoped to the given top-level
+    /// browsing context.
+    Session(TopLevelBrowsingContextId),
+    /// `window.localStorage`, persisted to disk when a storage directory is configured.
+    Local,
+}
+
 // The type of pipeline exit. During complete shutdowns, pipelines do not have to
 // release resources automatically released on process termination.
 #[derive(Copy)]
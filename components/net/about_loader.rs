@@ -2,24 +2,40 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use http_cache::{CacheEntryInfo, HttpCache};
+use image_cache_task::{ImageCacheTask, Msg};
 use resource_task::{TargetedLoadResponse, Metadata, LoadData, start_sending, ResponseSenders};
-use resource_task::ProgressMsg::Done;
+use resource_task::ProgressMsg::{Payload, Done};
 use file_loader;
 
 use url::Url;
 use hyper::http::RawStatus;
+use util::memory;
+use util::opts;
 use util::resource_files::resources_dir_path;
+use util::task::spawn_named;
 
-use std::borrow::IntoCow;
+use std::borrow::{IntoCow, ToOwned};
 use std::old_io::fs::PathExtensions;
-use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Sender, channel};
+use std::thunk::Invoke;
 
-pub fn factory(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
+pub fn factory(http_cache: Arc<Mutex<HttpCache>>, image_cache: Option<ImageCacheTask>)
+               -> Box<Invoke<(LoadData, Sender<TargetedLoadResponse>)> + Send> {
+    box move |(load_data, start_chan)| {
+        load(load_data, start_chan, http_cache, image_cache)
+    }
+}
+
+fn load(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>,
+        http_cache: Arc<Mutex<HttpCache>>, image_cache: Option<ImageCacheTask>) {
     let senders = ResponseSenders {
         immediate_consumer: start_chan.clone(),
         eventual_consumer: load_data.consumer.clone(),
     };
-    match load_data.url.non_relative_scheme_data().unwrap() {
+    let page = load_data.url.non_relative_scheme_data().unwrap().to_owned();
+    match page.as_slice() {
         "blank" => {
             let chan = start_sending(senders, Metadata {
                 final_url: load_data.url,
@@ -38,6 +54,21 @@ pub fn factory(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>
             assert!(path.exists());
             load_data.url = Url::from_file_path(&path).unwrap();
         }
+        "memory" => {
+            send_generated_page(load_data.url, senders, memory_page);
+            return
+        }
+        "cache" => {
+            send_generated_page(load_data.url, senders, move || {
+                let http_entries = http_cache.lock().unwrap().entries();
+                cache_page(http_entries, image_cache)
+            });
+            return
+        }
+        "config" => {
+            send_generated_page(load_data.url, senders, config_page);
+            return
+        }
         _ => {
             start_sending(senders, Metadata::default(load_data.url))
                 .send(Done(Err("Unknown about: URL.".to_string()))).unwrap();
@@ -46,3 +77,124 @@ pub fn factory(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>
     };
     file_loader::factory(load_data, start_chan)
 }
+
+/// Builds a page and sends it. This happens on its own task, since collecting the cache
+/// contents waits on the image cache task.
+fn send_generated_page<F>(url: Url, senders: ResponseSenders, generate: F)
+                          where F: FnOnce() -> String + Send {
+    spawn_named("about_loader".to_owned(), move || {
+        let html = generate();
+        let chan = start_sending(senders, Metadata {
+            final_url: url,
+            content_type: Some(("text".to_string(), "html".to_string())),
+            charset: Some("utf-8".to_string()),
+            headers: None,
+            status: Some(RawStatus(200, "OK".into_cow())),
+        });
+        let _ = chan.send(Payload(html.into_bytes()));
+        let _ = chan.send(Done(Ok(())));
+    });
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Builds a page with a table for each of `sections`, given as a heading, the column names and
+/// the rows. Cell contents are escaped.
+fn html_page(title: &str, sections: Vec<(&str, Vec<&str>, Vec<Vec<String>>)>) -> String {
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                            <title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n", title);
+    for (heading, columns, rows) in sections.into_iter() {
+        html.push_str(format!("<h2>{}</h2>\n<table>\n<tr>", heading).as_slice());
+        for column in columns.iter() {
+            html.push_str(format!("<th>{}</th>", column).as_slice());
+        }
+        html.push_str("</tr>\n");
+        for row in rows.iter() {
+            html.push_str("<tr>");
+            for cell in row.iter() {
+                html.push_str(format!("<td>{}</td>", escape_html(cell.as_slice())).as_slice());
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// about:memory, the measurements the memory profiler prints.
+fn memory_page() -> String {
+    let rows: Vec<Vec<String>> = memory::get_memory_report().into_iter().map(|(category, nbytes)| {
+        let size = match nbytes {
+            Some(nbytes) => format!("{:.2}", (nbytes as f64) / (1024f64 * 1024f64)),
+            None => "???".to_owned(),
+        };
+        vec!(category, size)
+    }).collect();
+    html_page("about:memory", vec!(("Memory usage", vec!("Category", "Size (MiB)"), rows)))
+}
+
+/// about:cache, the contents of the HTTP cache and the image cache.
+fn cache_page(http_entries: Vec<CacheEntryInfo>, image_cache: Option<ImageCacheTask>) -> String {
+    let http_rows: Vec<Vec<String>> = http_entries.into_iter().map(|entry| {
+        vec!(entry.url,
+             entry.status.to_string(),
+             entry.size.to_string(),
+             (if entry.fresh { "fresh" } else { "stale" }).to_owned())
+    }).collect();
+
+    let image_entries = match image_cache {
+        Some(image_cache) => {
+            let (entries_chan, entries_port) = channel();
+            image_cache.send(Msg::GetEntries(entries_chan));
+            entries_port.recv().unwrap_or(vec!())
+        }
+        None => vec!(),
+    };
+    let image_rows: Vec<Vec<String>> = image_entries.into_iter().map(|entry| {
+        vec!(entry.url.serialize(), entry.state.to_owned())
+    }).collect();
+
+    html_page("about:cache", vec!(
+        ("HTTP cache", vec!("URL", "Status", "Size (bytes)", "Freshness"), http_rows),
+        ("Image cache", vec!("URL", "State"), image_rows),
+    ))
+}
+
+/// about:config, the options this instance was started with.
+fn config_page() -> String {
+    let rows: Vec<Vec<String>> = opts::get().config_entries().into_iter().map(|(name, value)| {
+        vec!(name.to_owned(), value)
+    }).collect();
+    html_page("about:config", vec!(("Options", vec!("Name", "Value"), rows)))
+}
+
+#[test]
+fn test_escape_html() {
+    assert_eq!(escape_html("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;".to_owned());
+}
+
+#[test]
+fn test_about_config_lists_options() {
+    use resource_task::{new_resource_task, load_whole_resource, ControlMsg, ProtocolRegistry};
+
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+    let url = Url::parse("about:config").unwrap();
+    let (_, body) = load_whole_resource(&resource_task, url).unwrap();
+    let body = String::from_utf8(body).unwrap();
+    assert!(body.contains("<td>max_connections_per_host</td><td>6</td>"));
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
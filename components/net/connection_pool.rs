@@ -0,0 +1,323 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A pool of keep-alive HTTP connections, shared by all HTTP loads of a resource task.
+//!
+//! Loads check a connection out through a `PooledConnector`. Once a response has been read
+//! completely on a connection that the server is willing to keep open, the loader marks it as
+//! reusable and it goes back to the pool when hyper drops it. At most `max_per_host`
+//! connections to a host are in use at a time; further loads to that host wait in a queue.
+
+use proxy::{AbsoluteFormWriter, ProxyConnector};
+
+use hyper::net::{HttpStream, NetworkConnector, NetworkStream};
+use time;
+
+use std::collections::HashMap;
+use std::old_io::IoResult;
+use std::old_io::net::ip::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How long an idle connection is kept before we assume the server has closed it.
+const IDLE_TIMEOUT_NS: u64 = 15 * 1000 * 1000 * 1000;
+
+/// Identifies the server a connection is made to.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct HostKey {
+    scheme: String,
+    host: String,
+    port: u16,
+}
+
+/// Counters describing the activity of a connection pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConnectionPoolMetrics {
+    /// Connections opened to a server.
+    pub connections_opened: usize,
+    /// Requests sent on a kept-alive connection instead of a new one.
+    pub connections_reused: usize,
+    /// Requests that had to wait because their host was at the connection limit.
+    pub requests_queued: usize,
+    /// Connections currently in use by a load.
+    pub active_connections: usize,
+    /// Connections currently kept alive, waiting for another request.
+    pub idle_connections: usize,
+}
+
+struct IdleConnection {
+    stream: HttpStream,
+    /// When the connection was returned to the pool, from `time::precise_time_ns`.
+    since: u64,
+}
+
+struct PoolState {
+    max_per_host: usize,
+    idle: HashMap<HostKey, Vec<IdleConnection>>,
+    active: HashMap<HostKey, usize>,
+    metrics: ConnectionPoolMetrics,
+}
+
+impl PoolState {
+    fn active_count(&self, key: &HostKey) -> usize {
+        self.active.get(key).map_or(0, |count| *count)
+    }
+
+    fn set_active_count(&mut self, key: &HostKey, count: usize) {
+        if count == 0 {
+            self.active.remove(key);
+        } else {
+            self.active.insert(key.clone(), count);
+        }
+    }
+
+    fn take_idle(&mut self, key: &HostKey) -> Option<HttpStream> {
+        let now = time::precise_time_ns();
+        let connections = match self.idle.get_mut(key) {
+            Some(connections) => connections,
+            None => return None,
+        };
+        connections.retain(|connection| now - connection.since < IDLE_TIMEOUT_NS);
+        connections.pop().map(|connection| connection.stream)
+    }
+}
+
+/// A handle to a connection pool. Clones share the same pool.
+#[derive(Clone)]
+pub struct ConnectionPool {
+    state: Arc<(Mutex<PoolState>, Condvar)>,
+}
+
+impl ConnectionPool {
+    pub fn new(max_per_host: usize) -> ConnectionPool {
+        ConnectionPool {
+            state: Arc::new((Mutex::new(PoolState {
+                max_per_host: max_per_host,
+                idle: HashMap::new(),
+                active: HashMap::new(),
+                metrics: Default::default(),
+            }), Condvar::new())),
+        }
+    }
+
+    pub fn metrics(&self) -> ConnectionPoolMetrics {
+        let state = self.state.0.lock().unwrap();
+        let mut metrics = state.metrics;
+        metrics.active_connections = state.active.values().fold(0, |sum, count| sum + *count);
+        metrics.idle_connections = state.idle.values().fold(0, |sum, idle| sum + idle.len());
+        metrics
+    }
+
+    /// Takes an idle connection to `key`, or reserves a slot for a new connection if `None`
+    /// is returned. Blocks while the host is at the connection limit. If `fresh` is set, the
+    /// idle connections to the host are discarded instead of reused.
+    fn checkout(&self, key: &HostKey, fresh: bool) -> Option<HttpStream> {
+        let &(ref lock, ref condvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if fresh {
+            state.idle.remove(key);
+        }
+
+        let mut queued = false;
+        loop {
+            let active = state.active_count(key);
+            if let Some(stream) = state.take_idle(key) {
+                state.set_active_count(key, active + 1);
+                state.metrics.connections_reused += 1;
+                return Some(stream);
+            }
+            if active < state.max_per_host {
+                state.set_active_count(key, active + 1);
+                return None;
+            }
+            if !queued {
+                state.metrics.requests_queued += 1;
+                queued = true;
+            }
+            state = condvar.wait(state).unwrap();
+        }
+    }
+
+    fn record_opened(&self) {
+        self.state.0.lock().unwrap().metrics.connections_opened += 1;
+    }
+
+    /// Gives back the slot taken by `checkout`, keeping `stream` for reuse if there is one.
+    fn release(&self, key: &HostKey, stream: Option<HttpStream>) {
+        let &(ref lock, ref condvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        let active = state.active_count(key);
+        state.set_active_count(key, active - 1);
+        if let Some(stream) = stream {
+            let connection = IdleConnection {
+                stream: stream,
+                since: time::precise_time_ns(),
+            };
+            if state.idle.contains_key(key) {
+                state.idle.get_mut(key).unwrap().push(connection);
+            } else {
+                state.idle.insert(key.clone(), vec!(connection));
+            }
+        }
+        condvar.notify_all();
+    }
+}
+
+/// Returns a checked-out connection to the pool once every handle to it is gone.
+struct ConnectionGuard {
+    pool: ConnectionPool,
+    key: HostKey,
+    stream: Mutex<Option<HttpStream>>,
+    reusable: AtomicBool,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let stream = if self.reusable.load(Ordering::SeqCst) {
+            self.stream.lock().unwrap().take()
+        } else {
+            None
+        };
+        self.pool.release(&self.key, stream);
+    }
+}
+
+/// A connection checked out of the pool.
+#[derive(Clone)]
+pub struct PooledStream {
+    stream: HttpStream,
+    /// Puts the whole URL in the request line if the connection is to a forward proxy.
+    request_line: AbsoluteFormWriter,
+    guard: Arc<ConnectionGuard>,
+}
+
+impl Reader for PooledStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Writer for PooledStream {
+    fn write_all(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.request_line.write_all(&mut self.stream, buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.stream.flush()
+    }
+}
+
+impl NetworkStream for PooledStream {
+    fn peer_name(&mut self) -> IoResult<SocketAddr> {
+        self.stream.peer_name()
+    }
+}
+
+/// A hyper connector that takes its connections from a `ConnectionPool`.
+pub struct PooledConnector<'v> {
+    pool: ConnectionPool,
+    connector: ProxyConnector<'v>,
+    /// Whether to open a new connection even if an idle one is available.
+    fresh: bool,
+    /// The connection handed out by the last `connect`, and whether it was an idle one.
+    last: Option<(Arc<ConnectionGuard>, bool)>,
+}
+
+impl<'v> PooledConnector<'v> {
+    pub fn new(pool: ConnectionPool, connector: ProxyConnector<'v>) -> PooledConnector<'v> {
+        PooledConnector {
+            pool: pool,
+            connector: connector,
+            fresh: false,
+            last: None,
+        }
+    }
+
+    /// Whether the last connection handed out had already been used for another request.
+    /// Such a connection may have been closed by the server in the meantime.
+    pub fn reused_connection(&self) -> bool {
+        self.last.as_ref().map_or(false, |&(_, reused)| reused)
+    }
+
+    /// Makes the next `connect` open a new connection.
+    pub fn retry_with_new_connection(&mut self) {
+        self.fresh = true;
+        self.last = None;
+    }
+
+    /// Lets the last connection handed out go back to the pool once the response is dropped.
+    /// Must only be called after the whole response body has been read.
+    pub fn keep_alive(&self) {
+        if let Some((ref guard, _)) = self.last {
+            guard.reusable.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl<'v> NetworkConnector<PooledStream> for PooledConnector<'v> {
+    fn connect(&mut self, host: &str, port: u16, scheme: &str) -> IoResult<PooledStream> {
+        let key = HostKey {
+            scheme: scheme.to_owned(),
+            host: host.to_owned(),
+            port: port,
+        };
+        let (stream, reused) = match self.pool.checkout(&key, self.fresh) {
+            Some(stream) => (stream, true),
+            None => match self.connector.connect(host, port, scheme) {
+                Ok(stream) => {
+                    self.pool.record_opened();
+                    (stream, false)
+                }
+                Err(e) => {
+                    self.pool.release(&key, None);
+                    return Err(e);
+                }
+            },
+        };
+        let guard = Arc::new(ConnectionGuard {
+            pool: self.pool.clone(),
+            key: key,
+            stream: Mutex::new(Some(stream.clone())),
+            reusable: AtomicBool::new(false),
+        });
+        self.last = Some((guard.clone(), reused));
+        Ok(PooledStream {
+            stream: stream,
+            request_line: AbsoluteFormWriter::new(self.connector.forward_proxy_origin(host, port, scheme)),
+            guard: guard,
+        })
+    }
+}
+
+#[test]
+fn test_checkout_respects_per_host_limit() {
+    use std::borrow::ToOwned;
+    use std::sync::mpsc::channel;
+    use std::thread::Thread;
+
+    let pool = ConnectionPool::new(1);
+    let key = HostKey {
+        scheme: "http".to_owned(),
+        host: "example.com".to_owned(),
+        port: 80,
+    };
+    assert!(pool.checkout(&key, false).is_none());
+    assert_eq!(pool.metrics().active_connections, 1);
+
+    let (done_chan, done_port) = channel();
+    let (waiting_pool, waiting_key) = (pool.clone(), key.clone());
+    let _guard = Thread::scoped(move || {
+        assert!(waiting_pool.checkout(&waiting_key, false).is_none());
+        done_chan.send(()).unwrap();
+        waiting_pool.release(&waiting_key, None);
+    });
+
+    // The second checkout has to wait until the first connection is released.
+    while pool.metrics().requests_queued == 0 {
+        Thread::yield_now();
+    }
+    assert!(done_port.try_recv().is_err());
+    pool.release(&key, None);
+    done_port.recv().unwrap();
+}
@@ -7,7 +7,13 @@
 
 use url::Url;
 use cookie::Cookie;
+use cookie_rs;
+use http_cache::write_atomically;
+use rustc_serialize::json;
 use std::cmp::Ordering;
+use std::old_io::File;
+use std::old_io::fs::PathExtensions;
+use time::{at, now, Timespec};
 
 /// The creator of a given cookie
 #[derive(PartialEq, Copy)]
@@ -19,13 +25,63 @@ pub enum CookieSource {
 }
 
 pub struct CookieStorage {
-    cookies: Vec<Cookie>
+    cookies: Vec<Cookie>,
+    /// The file in which persistent cookies are kept across runs, if any.
+    jar_path: Option<Path>,
 }
 
 impl CookieStorage {
     pub fn new() -> CookieStorage {
         CookieStorage {
-            cookies: Vec::new()
+            cookies: Vec::new(),
+            jar_path: None,
+        }
+    }
+
+    /// Creates a cookie storage backed by the cookie jar at `path`, loading any unexpired
+    /// persistent cookies stored there by a previous run.
+    pub fn new_with_jar(path: Path) -> CookieStorage {
+        let cookies = if path.exists() {
+            match File::open(&path).read_to_string() {
+                Ok(contents) => {
+                    match json::decode::<Vec<StoredCookie>>(contents.as_slice()) {
+                        Ok(stored) => {
+                            stored.into_iter().filter_map(|c| c.into_cookie()).collect()
+                        }
+                        Err(_) => {
+                            warn!("cookie_storage: ignoring corrupt cookie jar {}", path.display());
+                            vec!()
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("cookie_storage: couldn't read cookie jar {}: {}", path.display(), e);
+                    vec!()
+                }
+            }
+        } else {
+            vec!()
+        };
+
+        CookieStorage {
+            cookies: cookies,
+            jar_path: Some(path),
+        }
+    }
+
+    /// Writes the persistent, unexpired cookies to the cookie jar, if there is one.
+    fn save(&self) {
+        let path = match self.jar_path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let current_time = now().to_timespec();
+        let stored: Vec<StoredCookie> = self.cookies.iter()
+            .filter(|c| c.persistent && c.expiry_time.to_timespec() > current_time)
+            .map(StoredCookie::from_cookie)
+            .collect();
+        if let Err(e) = write_atomically(path, json::encode(&stored).unwrap().as_slice()) {
+            warn!("cookie_storage: couldn't write cookie jar {}: {}", path.display(), e);
         }
     }
 
@@ -43,6 +99,9 @@ impl CookieStorage {
 
             // http://tools.ietf.org/html/rfc6265#section-5.3 step 11.2
             if !c.cookie.httponly || source == CookieSource::HTTP {
+                if c.persistent {
+                    self.save();
+                }
                 Ok(Some(c))
             } else {
                 // Undo the removal.
@@ -73,7 +132,11 @@ impl CookieStorage {
         }
 
         // Step 12
+        let persistent = cookie.persistent;
         self.cookies.push(cookie);
+        if persistent {
+            self.save();
+        }
     }
 
     fn cookie_comparator(a: &Cookie, b: &Cookie) -> Ordering {
@@ -124,6 +187,66 @@ impl CookieStorage {
     }
 }
 
+/// The representation of a persistent cookie in the cookie jar.
+#[derive(RustcEncodable, RustcDecodable)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    path: String,
+    secure: bool,
+    httponly: bool,
+    host_only: bool,
+    creation_time: i64,
+    last_access: i64,
+    expiry_time: i64,
+}
+
+impl StoredCookie {
+    fn from_cookie(cookie: &Cookie) -> StoredCookie {
+        StoredCookie {
+            name: cookie.cookie.name.clone(),
+            value: cookie.cookie.value.clone(),
+            domain: cookie.cookie.domain.clone().unwrap_or_default(),
+            path: cookie.cookie.path.clone().unwrap_or_default(),
+            secure: cookie.cookie.secure,
+            httponly: cookie.cookie.httponly,
+            host_only: cookie.host_only,
+            creation_time: cookie.creation_time.to_timespec().sec,
+            last_access: cookie.last_access.to_timespec().sec,
+            expiry_time: cookie.expiry_time.to_timespec().sec,
+        }
+    }
+
+    /// Rebuilds the cookie, running it through the same checks as a freshly received cookie.
+    /// Returns `None` for expired or invalid cookies.
+    fn into_cookie(self) -> Option<Cookie> {
+        let expiry_time = Timespec::new(self.expiry_time, 0);
+        if expiry_time <= now().to_timespec() {
+            return None;
+        }
+
+        let scheme = if self.secure { "https" } else { "http" };
+        let request = match Url::parse(format!("{}://{}{}", scheme, self.domain, self.path).as_slice()) {
+            Ok(url) => url,
+            Err(_) => return None,
+        };
+
+        let mut bare_cookie = cookie_rs::Cookie::new(self.name, self.value);
+        bare_cookie.domain = if self.host_only { None } else { Some(self.domain) };
+        bare_cookie.path = Some(self.path);
+        bare_cookie.secure = self.secure;
+        bare_cookie.httponly = self.httponly;
+        bare_cookie.expires = Some(at(expiry_time));
+
+        Cookie::new_wrapped(bare_cookie, &request, CookieSource::HTTP).map(|mut cookie| {
+            cookie.creation_time = at(Timespec::new(self.creation_time, 0));
+            cookie.last_access = at(Timespec::new(self.last_access, 0));
+            cookie
+        })
+    }
+}
+
 #[test]
 fn test_sort_order() {
     use cookie_rs;
@@ -141,3 +264,46 @@ fn test_sort_order() {
     assert!(CookieStorage::cookie_comparator(&a_prime, &a) == Ordering::Greater);
     assert!(CookieStorage::cookie_comparator(&a, &a) == Ordering::Equal);
 }
+
+#[cfg(test)]
+fn cookie_from_header(header: &str, url: &Url) -> Cookie {
+    Cookie::new_wrapped(cookie_rs::Cookie::parse(header).unwrap(), url, CookieSource::HTTP).unwrap()
+}
+
+#[test]
+fn test_cookie_jar_round_trip() {
+    use std::old_io::TempDir;
+
+    let dir = TempDir::new("cookie_jar").unwrap();
+    let jar = dir.path().join("cookies.json");
+    let url = &Url::parse("http://example.com/foo/bar").unwrap();
+    {
+        let mut storage = CookieStorage::new_with_jar(jar.clone());
+        storage.push(cookie_from_header("session=1", url), CookieSource::HTTP);
+        storage.push(cookie_from_header("persistent=2; Max-Age=3600", url), CookieSource::HTTP);
+        storage.push(cookie_from_header("domain=3; Max-Age=3600; Domain=example.com; Secure",
+                                        url), CookieSource::HTTP);
+    }
+
+    let mut storage = CookieStorage::new_with_jar(jar.clone());
+    assert_eq!(storage.cookies_for_url(url, CookieSource::HTTP), Some("persistent=2".to_owned()));
+    let sub_url = &Url::parse("https://www.example.com/foo/bar").unwrap();
+    assert_eq!(storage.cookies_for_url(sub_url, CookieSource::HTTP), Some("domain=3".to_owned()));
+}
+
+#[test]
+fn test_cookie_jar_drops_expired_cookies() {
+    use std::old_io::TempDir;
+    use std::time::Duration;
+
+    let dir = TempDir::new("cookie_jar").unwrap();
+    let jar = dir.path().join("cookies.json");
+    let url = &Url::parse("http://example.com/").unwrap();
+    let mut expired = StoredCookie::from_cookie(&cookie_from_header("old=1; Max-Age=3600", url));
+    expired.expiry_time = (now().to_timespec() - Duration::seconds(1)).sec;
+    let valid = StoredCookie::from_cookie(&cookie_from_header("new=1; Max-Age=3600", url));
+    write_atomically(&jar, json::encode(&vec![expired, valid]).unwrap().as_slice()).unwrap();
+
+    let mut storage = CookieStorage::new_with_jar(jar);
+    assert_eq!(storage.cookies_for_url(url, CookieSource::HTTP), Some("new=1".to_owned()));
+}
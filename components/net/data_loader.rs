@@ -8,7 +8,7 @@ use resource_task::ProgressMsg::{Payload, Done};
 use rustc_serialize::base64::FromBase64;
 
 use hyper::mime::Mime;
-use url::{percent_decode, SchemeData};
+use url::{percent_decode, SchemeData, Url};
 
 use std::sync::mpsc::Sender;
 
@@ -31,13 +31,36 @@ fn load(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
         eventual_consumer: load_data.consumer,
     };
 
+    match decode(&url) {
+        Ok((content_type, bytes)) => {
+            metadata.set_content_type(content_type.as_ref());
+            let progress_chan = start_sending(senders, metadata);
+            progress_chan.send(Payload(bytes)).unwrap();
+            progress_chan.send(Done(Ok(()))).unwrap();
+        }
+        Err(DecodeError::InvalidDataUri) => {
+            start_sending(senders, metadata).send(Done(Err("invalid data uri".to_string()))).unwrap();
+        }
+        Err(DecodeError::NonBase64DataUri) => {
+            start_sending(senders, metadata).send(Done(Err("non-base64 data uri".to_string()))).unwrap();
+        }
+    }
+}
+
+pub enum DecodeError {
+    InvalidDataUri,
+    NonBase64DataUri,
+}
+
+/// Splits a `data:` URL into its content type, if it has a valid one, and its decoded data.
+pub fn decode(url: &Url) -> Result<(Option<Mime>, Vec<u8>), DecodeError> {
     // Split out content type and data.
     let mut scheme_data = match url.scheme_data {
-        SchemeData::NonRelative(scheme_data) => scheme_data,
+        SchemeData::NonRelative(ref scheme_data) => scheme_data.clone(),
         _ => panic!("Expected a non-relative scheme URL.")
     };
     match url.query {
-        Some(query) => {
+        Some(ref query) => {
             scheme_data.push_str("?");
             scheme_data.push_str(query.as_slice());
         },
@@ -45,8 +68,7 @@ fn load(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
     }
     let parts: Vec<&str> = scheme_data.as_slice().splitn(1, ',').collect();
     if parts.len() != 2 {
-        start_sending(senders, metadata).send(Done(Err("invalid data uri".to_string()))).unwrap();
-        return;
+        return Err(DecodeError::InvalidDataUri);
     }
 
     // ";base64" must come at the end of the content type, per RFC 2397.
@@ -61,9 +83,7 @@ fn load(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
     // Parse the content type using rust-http.
     // FIXME: this can go into an infinite loop! (rust-http #25)
     let content_type: Option<Mime> = ct_str.parse().ok();
-    metadata.set_content_type(content_type.as_ref());
 
-    let progress_chan = start_sending(senders, metadata);
     let bytes = percent_decode(parts[1].as_bytes());
 
     if is_base64 {
@@ -71,17 +91,11 @@ fn load(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
         // but Acid 3 apparently depends on spaces being ignored.
         let bytes = bytes.into_iter().filter(|&b| b != ' ' as u8).collect::<Vec<u8>>();
         match bytes.as_slice().from_base64() {
-            Err(..) => {
-                progress_chan.send(Done(Err("non-base64 data uri".to_string()))).unwrap();
-            }
-            Ok(data) => {
-                progress_chan.send(Payload(data)).unwrap();
-                progress_chan.send(Done(Ok(()))).unwrap();
-            }
+            Err(..) => Err(DecodeError::NonBase64DataUri),
+            Ok(data) => Ok((content_type, data)),
         }
     } else {
-        progress_chan.send(Payload(bytes)).unwrap();
-        progress_chan.send(Done(Ok(()))).unwrap();
+        Ok((content_type, bytes))
     }
 }
 
@@ -91,7 +105,6 @@ fn assert_parse(url:          &'static str,
                 charset:      Option<String>,
                 data:         Option<Vec<u8>>) {
     use std::sync::mpsc::channel;
-    use url::Url;
     use sniffer_task;
 
     let (start_chan, start_port) = channel();
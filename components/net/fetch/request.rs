@@ -2,13 +2,29 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use url::Url;
+use url::{Url, UrlParser};
 use hyper::method::Method;
 use hyper::mime::{Mime, TopLevel, SubLevel, Attr, Value};
-use hyper::header::Headers;
+use hyper::header::{AccessControlAllowHeaders, AccessControlAllowMethods, AccessControlMaxAge};
+use hyper::header::{AccessControlRequestHeaders, AccessControlRequestMethod};
+use hyper::header::{Authorization, Basic, Headers, HeaderView, Location};
 use hyper::header::ContentType;
-use fetch::cors_cache::CORSCache;
-use fetch::response::Response;
+use hyper::status::StatusClass;
+use data_loader;
+use fetch::cors_cache::{CORSCache, CacheRequestDetails};
+use fetch::response::{Response, ResponseBody, ResponseType, TerminationReason};
+use mixed_content;
+use resource_task::{ControlMsg, LoadContext, LoadData, ProgressMsg, ResourceTask};
+#[cfg(test)]
+use resource_task::{new_resource_task, spawn_test_server, ProtocolRegistry};
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::num::FromPrimitive;
+use std::old_io::File;
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// The number of redirects after which a fetch fails.
+const MAX_REDIRECTS: uint = 20;
 
 /// A [request context](http://fetch.spec.whatwg.org/#concept-request-context)
 #[derive(Copy)]
@@ -37,7 +53,7 @@ pub enum Referer {
 }
 
 /// A [request mode](http://fetch.spec.whatwg.org/#concept-request-mode)
-#[derive(Copy)]
+#[derive(Copy, PartialEq)]
 pub enum RequestMode {
     SameOrigin,
     NoCORS,
@@ -45,8 +61,16 @@ pub enum RequestMode {
     ForcedPreflightMode
 }
 
+/// A [request redirect mode](http://fetch.spec.whatwg.org/#concept-request-redirect-mode)
+#[derive(Copy, PartialEq)]
+pub enum RedirectMode {
+    Follow,
+    Error,
+    Manual
+}
+
 /// Request [credentials mode](http://fetch.spec.whatwg.org/#concept-request-credentials-mode)
-#[derive(Copy)]
+#[derive(Copy, PartialEq)]
 pub enum CredentialsMode {
     Omit,
     CredentialsSameOrigin,
@@ -54,7 +78,7 @@ pub enum CredentialsMode {
 }
 
 /// [Response tainting](http://fetch.spec.whatwg.org/#concept-request-response-tainting)
-#[derive(Copy)]
+#[derive(Copy, PartialEq)]
 pub enum ResponseTainting {
     Basic,
     CORSTainting,
@@ -74,6 +98,7 @@ pub struct Request {
     pub skip_service_worker: bool,
     pub context: Context,
     pub context_frame_type: ContextFrameType,
+    /// `None` is an opaque origin, which is never the same as another origin.
     pub origin: Option<Url>,
     pub force_origin_header: bool,
     pub same_origin_data: bool,
@@ -83,14 +108,22 @@ pub struct Request {
     pub mode: RequestMode,
     pub credentials_mode: CredentialsMode,
     pub use_url_credentials: bool,
-    pub manual_redirect: bool,
+    pub redirect_mode: RedirectMode,
     pub redirect_count: uint,
     pub response_tainting: ResponseTainting,
-    pub cache: Option<Box<CORSCache+'static>>
+    pub cache: Option<Box<CORSCache+Send>>,
+    /// The URL of the document or worker that made the request, for mixed content checks.
+    pub client_url: Option<Url>,
+    /// Where to report messages about the request, for the console of the client.
+    pub console: Option<Sender<String>>,
+    /// The resource task, which makes the network requests.
+    resource_task: ResourceTask,
+    /// The body of the last network response, which `receive_body` reads.
+    body_port: Option<Receiver<ProgressMsg>>
 }
 
 impl Request {
-    pub fn new(url: Url, context: Context) -> Request {
+    pub fn new(url: Url, context: Context, resource_task: ResourceTask) -> Request {
          Request {
             method: Method::Get,
             url: url,
@@ -110,10 +143,116 @@ impl Request {
             mode: RequestMode::NoCORS,
             credentials_mode: CredentialsMode::Omit,
             use_url_credentials: false,
-            manual_redirect: false,
+            redirect_mode: RedirectMode::Follow,
             redirect_count: 0,
             response_tainting: ResponseTainting::Basic,
-            cache: None
+            cache: None,
+            client_url: None,
+            console: None,
+            resource_task: resource_task,
+            body_port: None
+        }
+    }
+
+
+    /// [Fetch](http://fetch.spec.whatwg.org#concept-fetch), with the response filtered
+    /// according to the response tainting of the request.
+    pub fn fetch(&mut self, cors_flag: bool) -> Response {
+        let mut response = self.fetch_response_head(cors_flag);
+        self.receive_body(&mut response, |_| true);
+        response
+    }
+
+    /// Like `fetch`, but returns as soon as the headers of the response have arrived. The body
+    /// is then read with `receive_body`.
+    pub fn fetch_response_head(&mut self, cors_flag: bool) -> Response {
+        let response = self.main_fetch(cors_flag);
+        if response.is_network_error() {
+            self.body_port = None;
+            return response;
+        }
+        match self.response_tainting {
+            ResponseTainting::Basic => response.to_filtered(ResponseType::Basic),
+            ResponseTainting::CORSTainting => response.to_filtered(ResponseType::CORS),
+            ResponseTainting::Opaque => response.to_filtered(ResponseType::Opaque)
+        }
+    }
+
+    /// Reads the body of a response returned by `fetch_response_head`, passing each chunk to
+    /// `process_chunk` as it arrives. Reading stops when `process_chunk` returns `false`. If
+    /// the body isn't read to the end, the termination reason of the response is set.
+    pub fn receive_body<F>(&mut self, response: &mut Response, mut process_chunk: F)
+                           where F: FnMut(&[u8]) -> bool {
+        let body_port = match self.body_port.take() {
+            Some(body_port) => body_port,
+            None => {
+                // The body came with the response, as for `data` URLs.
+                let aborted = match response.body {
+                    ResponseBody::Done(ref body) => !process_chunk(body.as_slice()),
+                    _ => false
+                };
+                if aborted {
+                    response.termination_reason = Some(TerminationReason::EndUserAbort);
+                }
+                return;
+            }
+        };
+        let mut body = vec![];
+        loop {
+            match body_port.recv() {
+                Ok(ProgressMsg::Payload(chunk)) => {
+                    if !process_chunk(chunk.as_slice()) {
+                        response.termination_reason = Some(TerminationReason::EndUserAbort);
+                        return;
+                    }
+                    body.push_all(chunk.as_slice());
+                },
+                Ok(ProgressMsg::Done(Ok(()))) => break,
+                Ok(ProgressMsg::Done(Err(_))) | Err(_) => {
+                    response.termination_reason = Some(TerminationReason::Fatal);
+                    return;
+                }
+            }
+        }
+        if let Some(ref mut internal_response) = response.internal_response {
+            internal_response.body = ResponseBody::Done(body.clone());
+        }
+        if response.response_type != ResponseType::Opaque {
+            response.body = ResponseBody::Done(body);
+        }
+    }
+
+    /// The steps of [fetch](http://fetch.spec.whatwg.org#concept-fetch) that decide how the
+    /// request is fetched. The response isn't filtered yet.
+    fn main_fetch(&mut self, cors_flag: bool) -> Response {
+        // TODO: Content Security Policy
+        if let Some(ref client_url) = self.client_url {
+            if !mixed_content::allow_subresource_load(client_url, &self.url, load_context(self.context),
+                                                      self.console.as_ref()) {
+                return Response::network_error();
+            }
+        }
+        let same_origin = self.origin.as_ref().map_or(false, |origin| is_same_origin(origin, &self.url));
+        let scheme = self.url.scheme.clone();
+        if (same_origin && !cors_flag) ||
+           (scheme.as_slice() == "data" && self.same_origin_data) ||
+           scheme.as_slice() == "about" {
+            self.basic_fetch()
+        } else if self.mode == RequestMode::SameOrigin {
+            Response::network_error()
+        } else if self.mode == RequestMode::NoCORS {
+            self.response_tainting = ResponseTainting::Opaque;
+            self.basic_fetch()
+        } else if !is_http_scheme(scheme.as_slice()) {
+            Response::network_error()
+        } else if self.mode == RequestMode::ForcedPreflightMode ||
+                  (self.unsafe_request && (!is_simple_method(&self.method) ||
+                                           self.headers.iter().any(|h| !is_simple_header(&h)))) {
+            self.response_tainting = ResponseTainting::CORSTainting;
+            self.http_fetch(true, true, false)
+        } else {
+            self.response_tainting = ResponseTainting::CORSTainting;
+            self.http_fetch(true, false, false)
         }
     }
 
@@ -123,9 +262,11 @@ impl Request {
             "about" => match self.url.non_relative_scheme_data() {
                 Some(s) if s.as_slice() == "blank" => {
                     let mut response = Response::new();
+                    response.url = Some(self.url.clone());
                     response.headers.set(ContentType(Mime(
                         TopLevel::Text, SubLevel::Html,
                         vec![(Attr::Charset, Value::Utf8)])));
+                    response.body = ResponseBody::Done(vec![]);
                     response
                 },
                 _ => Response::network_error()
@@ -133,26 +274,588 @@ impl Request {
             "http" | "https" => {
                 self.http_fetch(false, false, false)
             },
-            "blob" | "data" | "file" | "ftp" => {
-                // XXXManishearth handle these
-                panic!("Unimplemented scheme for Fetch")
+            "data" => {
+                if self.method != Method::Get {
+                    return Response::network_error();
+                }
+                match data_loader::decode(&self.url) {
+                    Ok((content_type, bytes)) => {
+                        let content_type = content_type.unwrap_or_else(|| {
+                            Mime(TopLevel::Text, SubLevel::Plain,
+                                 vec![(Attr::Charset, Value::Ext("US-ASCII".to_owned()))])
+                        });
+                        let mut response = Response::new();
+                        response.url = Some(self.url.clone());
+                        response.headers.set(ContentType(content_type));
+                        response.body = ResponseBody::Done(bytes);
+                        response
+                    },
+                    Err(_) => Response::network_error()
+                }
             },
-
+            "file" => {
+                if self.method != Method::Get {
+                    return Response::network_error();
+                }
+                let bytes = self.url.to_file_path().ok().and_then(|path| {
+                    File::open(&path).read_to_end().ok()
+                });
+                match bytes {
+                    Some(bytes) => {
+                        let mut response = Response::new();
+                        response.url = Some(self.url.clone());
+                        response.body = ResponseBody::Done(bytes);
+                        response
+                    },
+                    None => Response::network_error()
+                }
+            },
+            // `blob` URLs aren't supported, as nothing creates them yet: there is no
+            // `URL.createObjectURL`. There is no FTP loader either.
             _ => Response::network_error()
         }
     }
 
-    // [HTTP fetch](http://fetch.spec.whatwg.org#http-fetch)
-    pub fn http_fetch(&mut self, _cors_flag: bool, cors_preflight_flag: bool, _authentication_fetch_flag: bool) -> Response {
-        let response = Response::new();
+    /// [HTTP fetch](http://fetch.spec.whatwg.org#http-fetch)
+    pub fn http_fetch(&mut self, cors_flag: bool, cors_preflight_flag: bool, authentication_fetch_flag: bool) -> Response {
         // TODO: Service worker fetch
         // Step 3
         // Substep 1
         self.skip_service_worker = true;
         // Substep 2
-        if cors_preflight_flag {
-            // XXXManishearth stuff goes here
+        if cors_preflight_flag && self.needs_preflight() {
+            let preflight_result = self.preflight_fetch();
+            if preflight_result.is_network_error() {
+                return preflight_result;
+            }
+        }
+        // Substep 3
+        let credentials_flag = match self.credentials_mode {
+            CredentialsMode::Include => true,
+            CredentialsMode::CredentialsSameOrigin => self.response_tainting == ResponseTainting::Basic,
+            CredentialsMode::Omit => false
+        };
+        // Substep 4
+        let response = self.http_network_or_cache_fetch(credentials_flag, authentication_fetch_flag);
+        if response.is_network_error() {
+            return response;
+        }
+        // Substep 5
+        if cors_flag && !self.cors_check(&response) {
+            return Response::network_error();
+        }
+        // Step 4
+        match response.status.map(|status| status as u16) {
+            Some(301) | Some(302) | Some(303) | Some(307) | Some(308) => {
+                // The body of the redirect isn't needed.
+                self.body_port = None;
+                self.http_redirect_fetch(cors_flag, response)
+            },
+            // TODO: 401 and 407 authentication
+            _ => response
+        }
+    }
+
+    /// The redirect steps of [HTTP fetch](http://fetch.spec.whatwg.org#http-fetch)
+    fn http_redirect_fetch(&mut self, cors_flag: bool, response: Response) -> Response {
+        match self.redirect_mode {
+            RedirectMode::Error => return Response::network_error(),
+            RedirectMode::Manual => return response,
+            RedirectMode::Follow => {}
+        }
+        // Substep 1, 2
+        let location = match response.headers.get::<Location>() {
+            Some(&Location(ref location)) => location.clone(),
+            None => return response
+        };
+        // Substep 3, 4
+        let location_url = match UrlParser::new().base_url(&self.url).parse(location.as_slice()) {
+            Ok(url) => url,
+            Err(_) => return Response::network_error()
+        };
+        // Substep 5
+        if self.redirect_count == MAX_REDIRECTS {
+            return Response::network_error();
+        }
+        // Substep 6
+        self.redirect_count += 1;
+        // Substep 7
+        self.same_origin_data = true;
+        // Substep 8
+        if cors_flag && has_credentials(&location_url) {
+            return Response::network_error();
+        }
+        // Substep 9
+        if cors_flag && !is_same_origin(&self.url, &location_url) {
+            self.origin = None;
+        }
+        // Substep 10
+        let status = response.status.map(|status| status as u16);
+        if (self.method == Method::Post && (status == Some(301) || status == Some(302))) ||
+           (status == Some(303) && self.method != Method::Head) {
+            self.method = Method::Get;
+            self.body = None;
+        }
+        // Substep 11
+        self.url = location_url;
+        // Substep 12
+        self.main_fetch(cors_flag)
+    }
+
+    /// Whether the CORS preflight cache lacks the method or any of the headers of the request,
+    /// which then needs a [CORS preflight fetch](http://fetch.spec.whatwg.org#cors-preflight-fetch).
+    fn needs_preflight(&mut self) -> bool {
+        let method_needs_preflight = !is_simple_method(&self.method) ||
+                                     self.mode == RequestMode::ForcedPreflightMode;
+        let header_names = non_simple_header_names(&self.headers);
+        let origin = match self.origin {
+            Some(ref origin) => origin.clone(),
+            None => return method_needs_preflight || !header_names.is_empty()
+        };
+        let url = self.url.clone();
+        let method = self.method.clone();
+        let credentials = self.credentials_mode == CredentialsMode::Include;
+        let details = || CacheRequestDetails {
+            origin: origin.clone(),
+            destination: url.clone(),
+            credentials: credentials
+        };
+        match self.cache {
+            Some(ref mut cache) => {
+                (method_needs_preflight && !cache.match_method(details(), method)) ||
+                header_names.iter().any(|name| !cache.match_header(details(), name.as_slice()))
+            },
+            None => method_needs_preflight || !header_names.is_empty()
+        }
+    }
+
+    /// [CORS preflight fetch](http://fetch.spec.whatwg.org#cors-preflight-fetch)
+    fn preflight_fetch(&mut self) -> Response {
+        // Step 1
+        let mut headers = Headers::new();
+        headers.set_raw("Origin".to_owned(), vec![self.serialized_origin().into_bytes()]);
+        // Step 2
+        headers.set(AccessControlRequestMethod(self.method.clone()));
+        // Step 3, 4, 5
+        let header_names = non_simple_header_names(&self.headers);
+        if !header_names.is_empty() {
+            headers.set(AccessControlRequestHeaders(header_names.clone()));
+        }
+        // Step 6
+        let (response, _) = http_network_fetch(&self.resource_task, &self.url, &Method::Options,
+                                               &headers, None, false);
+        // Step 7
+        let success = response.status.map_or(false, |status| status.class() == StatusClass::Success);
+        if response.is_network_error() || !success || !self.cors_check(&response) {
+            return Response::network_error();
+        }
+        // Substep 1
+        let mut methods = match response.headers.get() {
+            Some(&AccessControlAllowMethods(ref methods)) => methods.clone(),
+            None => vec![]
+        };
+        // Substep 2
+        let allowed_headers = match response.headers.get() {
+            Some(&AccessControlAllowHeaders(ref names)) => names.clone(),
+            None => vec![]
+        };
+        // Substep 4
+        if methods.is_empty() && self.mode == RequestMode::ForcedPreflightMode {
+            methods = vec![self.method.clone()];
+        }
+        // Substep 5
+        if !is_simple_method(&self.method) && !methods.iter().any(|method| *method == self.method) {
+            return Response::network_error();
+        }
+        // Substep 6
+        let header_allowed = |name: &String| {
+            allowed_headers.iter().any(|allowed| allowed.as_slice().eq_ignore_ascii_case(name.as_slice()))
+        };
+        if !header_names.iter().all(|name| header_allowed(name)) {
+            return Response::network_error();
+        }
+        // Substep 7, 8
+        let max_age = match response.headers.get() {
+            Some(&AccessControlMaxAge(max_age)) => max_age as uint,
+            None => 0
+        };
+        // Substep 9: TODO impose a limit on max-age
+        // Substep 10, 11, 12
+        let origin = match self.origin {
+            Some(ref origin) => origin.clone(),
+            None => return response
+        };
+        let url = self.url.clone();
+        let credentials = self.credentials_mode == CredentialsMode::Include;
+        let details = || CacheRequestDetails {
+            origin: origin.clone(),
+            destination: url.clone(),
+            credentials: credentials
+        };
+        if let Some(ref mut cache) = self.cache {
+            for method in methods.into_iter() {
+                cache.match_method_and_update(details(), method, max_age);
+            }
+            for name in allowed_headers.iter() {
+                cache.match_header_and_update(details(), name.as_slice(), max_age);
+            }
         }
         response
     }
+
+    /// [HTTP network or cache fetch](http://fetch.spec.whatwg.org#http-network-or-cache-fetch)
+    fn http_network_or_cache_fetch(&mut self, credentials_flag: bool, _authentication_fetch_flag: bool) -> Response {
+        // Step 1
+        let mut headers = self.headers.clone();
+        // Step 2: TODO use the HTTP cache
+        // Step 3
+        if let Referer::RefererUrl(ref referer) = self.referer {
+            headers.set_raw("Referer".to_owned(), vec![referer.serialize().into_bytes()]);
+        }
+        // Step 4
+        if self.force_origin_header || self.response_tainting == ResponseTainting::CORSTainting {
+            headers.set_raw("Origin".to_owned(), vec![self.serialized_origin().into_bytes()]);
+        }
+        // Step 5: cookies are added by the HTTP loader when the credentials flag is set.
+        if credentials_flag {
+            if self.use_url_credentials && !headers.has::<Authorization<Basic>>() && has_credentials(&self.url) {
+                headers.set(Authorization(Basic {
+                    username: self.url.username().unwrap_or("").to_owned(),
+                    password: self.url.password().map(|password| password.to_owned())
+                }));
+            }
+        }
+        // Step 6
+        let (response, body_port) = http_network_fetch(&self.resource_task, &self.url, &self.method,
+                                                       &headers, self.body.clone(), credentials_flag);
+        self.body_port = body_port;
+        response
+    }
+
+    /// [CORS check](http://fetch.spec.whatwg.org#concept-cors-check)
+    fn cors_check(&self, response: &Response) -> bool {
+        // Step 1, 2
+        // The raw value is used, since hyper's header can't represent `null`.
+        let origin = match response.headers.get_raw("access-control-allow-origin") {
+            Some(values) if values.len() == 1 => String::from_utf8_lossy(values[0].as_slice()).trim().to_owned(),
+            _ => return false
+        };
+        // Step 3
+        if self.credentials_mode != CredentialsMode::Include && origin.as_slice() == "*" {
+            return true;
+        }
+        // Step 4, 5
+        if origin != self.serialized_origin() {
+            return false;
+        }
+        // Step 6
+        if self.credentials_mode != CredentialsMode::Include {
+            return true;
+        }
+        // Step 7, 8
+        match response.headers.get_raw("access-control-allow-credentials") {
+            Some(values) if values.len() == 1 => String::from_utf8_lossy(values[0].as_slice()).trim() == "true",
+            _ => false
+        }
+    }
+
+    /// The [ASCII serialization](https://html.spec.whatwg.org/#ascii-serialisation-of-an-origin)
+    /// of the origin of the request.
+    fn serialized_origin(&self) -> String {
+        match self.origin {
+            Some(ref origin) => serialize_origin(origin),
+            None => "null".to_owned()
+        }
+    }
+}
+
+/// [HTTP network fetch](http://fetch.spec.whatwg.org#http-network-fetch), through the HTTP
+/// loader of the resource task, which takes care of HSTS, proxies, connection reuse, the HTTP
+/// cache, content codings and, when `credentials_flag` is set, cookies. Returns the response
+/// as soon as its headers have arrived, with the port its body arrives on.
+fn http_network_fetch(resource_task: &ResourceTask, url: &Url, method: &Method, headers: &Headers,
+                      body: Option<Vec<u8>>, credentials_flag: bool)
+                      -> (Response, Option<Receiver<ProgressMsg>>) {
+    let (start_chan, start_port) = channel();
+    let mut load_data = LoadData::new(url.clone(), start_chan);
+    load_data.method = method.clone();
+    load_data.headers = headers.clone();
+    load_data.data = body;
+    load_data.follow_redirects = false;
+    load_data.credentials = credentials_flag;
+    if resource_task.send(ControlMsg::Load(load_data)).is_err() {
+        return (Response::network_error(), None);
+    }
+    let load_response = match start_port.recv() {
+        Ok(load_response) => load_response,
+        Err(_) => return (Response::network_error(), None)
+    };
+
+    let metadata = load_response.metadata;
+    let status = match metadata.status {
+        Some(ref status) => status,
+        None => return (Response::network_error(), None)
+    };
+    let mut response = Response::new();
+    response.url = Some(metadata.final_url.clone());
+    response.status = match FromPrimitive::from_u16(status.0) {
+        Some(status) => Some(status),
+        None => return (Response::network_error(), None)
+    };
+    response.status_message = status.1.to_string();
+    response.headers = metadata.headers.clone().unwrap_or_else(Headers::new);
+    response.body = ResponseBody::Receiving(vec![]);
+    (response, Some(load_response.progress_port))
+}
+
+/// The kind of load a request of the given context is, for mixed content checks.
+fn load_context(context: Context) -> LoadContext {
+    match context {
+        Context::Audio | Context::Track | Context::Video => LoadContext::AudioVideo,
+        Context::Favicon | Context::Image | Context::ImageSet => LoadContext::Image,
+        Context::Font => LoadContext::Font,
+        Context::Frame | Context::IFrame | Context::Hyperlink | Context::Location |
+        Context::Form => LoadContext::Browsing,
+        Context::Script | Context::ServiceWorker | Context::SharedWorker | Context::Worker |
+        Context::Import | Context::XSLT => LoadContext::Script,
+        Context::Style => LoadContext::Style,
+        _ => LoadContext::Connect
+    }
+}
+
+/// http://fetch.spec.whatwg.org#concept-http-scheme
+fn is_http_scheme(scheme: &str) -> bool {
+    scheme == "http" || scheme == "https"
+}
+
+/// http://fetch.spec.whatwg.org#simple-method
+fn is_simple_method(m: &Method) -> bool {
+    match *m {
+        Method::Get | Method::Head | Method::Post => true,
+        _ => false
+    }
+}
+
+/// http://fetch.spec.whatwg.org#simple-header
+fn is_simple_header(h: &HeaderView) -> bool {
+    match h.name().to_ascii_lowercase().as_slice() {
+        "accept" | "accept-language" | "content-language" => true,
+        "content-type" => match h.value() {
+            Some(&ContentType(Mime(TopLevel::Text, SubLevel::Plain, _))) |
+            Some(&ContentType(Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, _))) |
+            Some(&ContentType(Mime(TopLevel::Multipart, SubLevel::FormData, _))) => true,
+            _ => false
+        },
+        _ => false
+    }
+}
+
+/// The sorted, lowercased names of the headers that aren't simple headers.
+fn non_simple_header_names(headers: &Headers) -> Vec<String> {
+    let mut names: Vec<String> = headers.iter().filter(|h| !is_simple_header(h))
+                                        .map(|h| h.name().to_ascii_lowercase()).collect();
+    names.sort();
+    names
+}
+
+/// Whether `a` and `b` have the same origin: the same scheme, host and port.
+/// http://tools.ietf.org/html/rfc6454#section-5
+pub fn is_same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme == b.scheme && a.host() == b.host() && a.port_or_default() == b.port_or_default()
+}
+
+fn serialize_origin(url: &Url) -> String {
+    let host = url.serialize_host().unwrap_or(String::new());
+    match url.port() {
+        Some(port) => format!("{}://{}:{}", url.scheme, host, port),
+        None => format!("{}://{}", url.scheme, host)
+    }
+}
+
+fn has_credentials(url: &Url) -> bool {
+    url.username().map_or(false, |username| !username.is_empty()) || url.password().is_some()
+}
+
+#[cfg(test)]
+fn body_of(response: &Response) -> Vec<u8> {
+    match response.body {
+        ResponseBody::Done(ref body) => body.clone(),
+        _ => panic!("expected a complete body")
+    }
+}
+
+#[cfg(test)]
+fn test_server_url(port: u16, path: &str) -> Url {
+    Url::parse(format!("http://127.0.0.1:{}{}", port, path).as_slice()).unwrap()
+}
+
+#[test]
+fn test_fetch_data_url() {
+    let mut request = Request::new(Url::parse("data:text/html,<p>fetched").unwrap(), Context::Fetch,
+                                   new_resource_task(None, ProtocolRegistry::new()));
+    request.origin = Some(Url::parse("http://servo.org/").unwrap());
+    request.same_origin_data = true;
+    let response = request.fetch(false);
+    assert!(response.response_type == ResponseType::Basic);
+    assert_eq!(body_of(&response), b"<p>fetched".to_vec());
+    match response.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Text, SubLevel::Html, _))) => {},
+        _ => panic!("expected a text/html content type")
+    }
+}
+
+#[test]
+fn test_fetch_follows_redirect() {
+    let (port, requests) = spawn_test_server(vec![
+        b"HTTP/1.1 302 Found\r\nLocation: /final\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\ndone".to_vec(),
+    ]);
+    let mut request = Request::new(test_server_url(port, "/start"), Context::Fetch,
+                                   new_resource_task(None, ProtocolRegistry::new()));
+    request.origin = Some(test_server_url(port, "/"));
+    let response = request.fetch(false);
+    assert!(requests.recv().unwrap().starts_with("GET /start "));
+    assert!(requests.recv().unwrap().starts_with("GET /final "));
+    assert_eq!(request.redirect_count, 1);
+    assert_eq!(response.url, Some(test_server_url(port, "/final")));
+    assert_eq!(body_of(&response), b"done".to_vec());
+}
+
+#[test]
+fn test_fetch_redirect_error_mode() {
+    let (port, _requests) = spawn_test_server(vec![
+        b"HTTP/1.1 301 Moved Permanently\r\nLocation: /final\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+    ]);
+    let mut request = Request::new(test_server_url(port, "/start"), Context::Fetch,
+                                   new_resource_task(None, ProtocolRegistry::new()));
+    request.origin = Some(test_server_url(port, "/"));
+    request.redirect_mode = RedirectMode::Error;
+    assert!(request.fetch(false).is_network_error());
+}
+
+#[test]
+fn test_fetch_no_cors_response_is_opaque() {
+    let (port, _requests) = spawn_test_server(vec![
+        b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecret".to_vec(),
+    ]);
+    let mut request = Request::new(test_server_url(port, "/"), Context::Image,
+                                   new_resource_task(None, ProtocolRegistry::new()));
+    request.origin = Some(Url::parse("http://servo.org/").unwrap());
+    let response = request.fetch(false);
+    assert!(response.response_type == ResponseType::Opaque);
+    assert!(response.status.is_none());
+    assert!(response.internal_response.is_some());
+}
+
+#[test]
+fn test_fetch_cors_check() {
+    let (port, requests) = spawn_test_server(vec![
+        b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\nAccess-Control-Allow-Origin: http://servo.org\r\n\
+          Content-Type: text/plain\r\nX-Secret: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+    ]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+    let mut request = Request::new(test_server_url(port, "/"), Context::XMLHttpRequest,
+                                   resource_task.clone());
+    request.origin = Some(Url::parse("http://servo.org/").unwrap());
+    request.mode = RequestMode::CORSMode;
+    assert!(request.fetch(false).is_network_error());
+    assert!(requests.recv().unwrap().contains("Origin: http://servo.org\r\n"));
+
+    let mut request = Request::new(test_server_url(port, "/"), Context::XMLHttpRequest, resource_task);
+    request.origin = Some(Url::parse("http://servo.org/").unwrap());
+    request.mode = RequestMode::CORSMode;
+    let response = request.fetch(false);
+    assert!(response.response_type == ResponseType::CORS);
+    assert!(response.headers.has::<ContentType>());
+    assert!(!response.headers.has_raw("x-secret"));
+}
+
+#[test]
+fn test_fetch_cors_preflight_is_cached() {
+    use fetch::cors_cache::BasicCORSCache;
+
+    let (port, requests) = spawn_test_server(vec![
+        b"HTTP/1.1 200 OK\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: PUT\r\n\
+          Access-Control-Max-Age: 60\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+    ]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+    let new_request = |cache: Option<Box<CORSCache+Send>>| {
+        let mut request = Request::new(test_server_url(port, "/"), Context::XMLHttpRequest,
+                                       resource_task.clone());
+        request.origin = Some(Url::parse("http://servo.org/").unwrap());
+        request.mode = RequestMode::CORSMode;
+        request.method = Method::Put;
+        request.unsafe_request = true;
+        request.cache = cache;
+        request
+    };
+
+    let mut request = new_request(Some(box BasicCORSCache::new() as Box<CORSCache+Send>));
+    assert!(!request.fetch(false).is_network_error());
+    let preflight = requests.recv().unwrap();
+    assert!(preflight.starts_with("OPTIONS / "));
+    assert!(preflight.contains("Access-Control-Request-Method: PUT\r\n"));
+    assert!(requests.recv().unwrap().starts_with("PUT / "));
+
+    let mut request = new_request(request.cache.take());
+    assert!(!request.fetch(false).is_network_error());
+    assert!(requests.recv().unwrap().starts_with("PUT / "));
+}
+
+#[test]
+fn test_fetch_body_is_received_in_chunks() {
+    let (port, _requests) = spawn_test_server(vec![
+        b"HTTP/1.1 200 OK\r\nStatus-Test: 1\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbody".to_vec(),
+    ]);
+    let mut request = Request::new(test_server_url(port, "/"), Context::Fetch,
+                                   new_resource_task(None, ProtocolRegistry::new()));
+    request.origin = Some(test_server_url(port, "/"));
+    let mut response = request.fetch_response_head(false);
+    assert!(response.headers.has_raw("status-test"));
+    assert_eq!(response.status_message.as_slice(), "OK");
+    let mut received = vec![];
+    request.receive_body(&mut response, |chunk| { received.push_all(chunk); true });
+    assert_eq!(received, b"body".to_vec());
+    assert_eq!(body_of(&response), b"body".to_vec());
+    assert!(response.termination_reason.is_none());
+}
+
+#[test]
+fn test_fetch_sends_cookies_only_with_credentials() {
+    let (port, requests) = spawn_test_server(vec![
+        b"HTTP/1.1 200 OK\r\nSet-Cookie: id=1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+    ]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+    let new_request = |credentials_mode: CredentialsMode| {
+        let mut request = Request::new(test_server_url(port, "/"), Context::Fetch, resource_task.clone());
+        request.origin = Some(test_server_url(port, "/"));
+        request.credentials_mode = credentials_mode;
+        request
+    };
+
+    assert!(!new_request(CredentialsMode::Include).fetch(false).is_network_error());
+    requests.recv().unwrap();
+    assert!(!new_request(CredentialsMode::Omit).fetch(false).is_network_error());
+    assert!(!requests.recv().unwrap().contains("Cookie:"));
+    assert!(!new_request(CredentialsMode::CredentialsSameOrigin).fetch(false).is_network_error());
+    assert!(requests.recv().unwrap().contains("Cookie: id=1\r\n"));
+}
+
+#[test]
+fn test_fetch_blocks_mixed_content() {
+    let (port, requests) = spawn_test_server(vec![
+        b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+    ]);
+    let mut request = Request::new(test_server_url(port, "/"), Context::XMLHttpRequest,
+                                   new_resource_task(None, ProtocolRegistry::new()));
+    request.origin = Some(Url::parse("https://servo.org/").unwrap());
+    request.client_url = Some(Url::parse("https://servo.org/").unwrap());
+    request.mode = RequestMode::CORSMode;
+    assert!(request.fetch(false).is_network_error());
+    assert!(requests.try_recv().is_err());
 }
@@ -6,6 +6,7 @@ use url::Url;
 use hyper::status::StatusCode;
 use hyper::header::Headers;
 use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
 use std::sync::mpsc::Receiver;
 
 /// [Response type](http://fetch.spec.whatwg.org/#concept-response-type)
@@ -57,6 +58,8 @@ pub struct Response {
     pub url: Option<Url>,
     /// `None` can be considered a StatusCode of `0`.
     pub status: Option<StatusCode>,
+    /// [Status message](http://fetch.spec.whatwg.org/#concept-response-status-message)
+    pub status_message: String,
     pub headers: Headers,
     pub body: ResponseBody,
     /// [Internal response](http://fetch.spec.whatwg.org/#concept-internal-response), only used if the Response is a filtered response
@@ -70,6 +73,7 @@ impl Response {
             termination_reason: None,
             url: None,
             status: Some(StatusCode::Ok),
+            status_message: "OK".to_owned(),
             headers: Headers::new(),
             body: ResponseBody::Empty,
             internal_response: None
@@ -82,6 +86,7 @@ impl Response {
             termination_reason: None,
             url: None,
             status: None,
+            status_message: String::new(),
             headers: Headers::new(),
             body: ResponseBody::Empty,
             internal_response: None
@@ -119,12 +124,14 @@ impl Response {
                 response.response_type = filter_type;
             },
             ResponseType::CORS => {
+                // http://fetch.spec.whatwg.org/#concept-filtered-response-cors
+                let exposed_headers = exposed_header_names(&old_headers);
                 let headers = old_headers.iter().filter(|header| {
-                    match header.name().to_ascii_lowercase().as_slice() {
+                    let name = header.name().to_ascii_lowercase();
+                    match name.as_slice() {
                         "cache-control" | "content-language" |
-                        "content-type" | "expires" | "last-modified" | "Pragma" => false,
-                        // XXXManishearth handle Access-Control-Expose-Headers
-                        _ => true
+                        "content-type" | "expires" | "last-modified" | "pragma" => true,
+                        _ => exposed_headers.iter().any(|exposed| *exposed == name)
                     }
                 }).collect();
                 response.headers = headers;
@@ -133,9 +140,24 @@ impl Response {
             ResponseType::Opaque => {
                 response.headers = Headers::new();
                 response.status = None;
+                response.status_message = String::new();
                 response.body = ResponseBody::Empty;
+                response.response_type = filter_type;
             }
         }
         response
     }
 }
+
+/// The lowercased names listed in the `Access-Control-Expose-Headers` headers of `headers`.
+fn exposed_header_names(headers: &Headers) -> Vec<String> {
+    match headers.get_raw("access-control-expose-headers") {
+        Some(values) => values.iter().flat_map(|value| {
+            String::from_utf8_lossy(value.as_slice()).split(',')
+                .map(|name| name.trim().to_ascii_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect::<Vec<String>>().into_iter()
+        }).collect(),
+        None => vec!()
+    }
+}
@@ -0,0 +1,268 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! HTTP Strict Transport Security, as specified in http://tools.ietf.org/html/rfc6797
+//!
+//! Hosts that have asked to only be reached over HTTPS are remembered, and later `http:`
+//! requests to them are upgraded. The list starts out with the hosts preloaded from
+//! `resources/hsts_preload.json`.
+
+use hyper::header::Headers;
+use rustc_serialize::json;
+use time;
+use url::Url;
+use util::resource_files::read_resource_file;
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::old_io::net::ip::IpAddr;
+use std::str;
+
+#[derive(RustcDecodable, RustcEncodable, Clone)]
+pub struct HstsEntry {
+    pub host: String,
+    pub include_subdomains: bool,
+    /// How long the entry is valid for, in seconds. Preloaded entries have no limit.
+    pub max_age: Option<u64>,
+    /// When the entry was added, in seconds since the epoch.
+    pub timestamp: Option<u64>,
+}
+
+impl HstsEntry {
+    /// Returns `None` for IP addresses, which HSTS doesn't apply to.
+    pub fn new(host: String, include_subdomains: bool, max_age: Option<u64>) -> Option<HstsEntry> {
+        if host.parse::<IpAddr>().is_ok() {
+            return None;
+        }
+        Some(HstsEntry {
+            host: host.to_ascii_lowercase(),
+            include_subdomains: include_subdomains,
+            max_age: max_age,
+            timestamp: Some(time::get_time().sec as u64),
+        })
+    }
+
+    fn is_expired(&self) -> bool {
+        match (self.max_age, self.timestamp) {
+            (Some(max_age), Some(timestamp)) => {
+                // The clock may have gone backwards since the entry was added.
+                (time::get_time().sec as u64).saturating_sub(timestamp) >= max_age
+            }
+            _ => false,
+        }
+    }
+
+    fn matches_domain(&self, host: &str) -> bool {
+        !self.is_expired() && self.host.as_slice() == host
+    }
+
+    fn matches_subdomain(&self, host: &str) -> bool {
+        !self.is_expired() && host.ends_with(format!(".{}", self.host).as_slice())
+    }
+}
+
+#[derive(RustcDecodable, RustcEncodable, Clone)]
+pub struct HstsList {
+    pub entries: Vec<HstsEntry>,
+}
+
+impl HstsList {
+    pub fn new() -> HstsList {
+        HstsList {
+            entries: vec!(),
+        }
+    }
+
+    /// Parses a list such as `resources/hsts_preload.json`.
+    pub fn from_preload(preload_content: &str) -> Option<HstsList> {
+        json::decode(preload_content).ok()
+    }
+
+    /// Whether requests to `host` must be made over HTTPS.
+    pub fn is_host_secure(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.entries.iter().any(|entry| {
+            entry.matches_domain(host.as_slice()) ||
+                (entry.include_subdomains && entry.matches_subdomain(host.as_slice()))
+        })
+    }
+
+    /// Returns `url` upgraded to HTTPS if its host is in the list.
+    pub fn upgrade_url(&self, url: Url) -> Url {
+        let secure = url.scheme.as_slice() == "http" &&
+                     url.domain().map_or(false, |host| self.is_host_secure(host));
+        if secure {
+            info!("upgrading {} to HTTPS for HSTS", url.serialize());
+            secure_url(&url)
+        } else {
+            url
+        }
+    }
+
+    /// Adds `entry`, replacing any entry for the same host. An entry with a `max-age` of zero
+    /// removes the host from the list instead.
+    pub fn push(&mut self, entry: HstsEntry) {
+        self.entries.retain(|existing| existing.host != entry.host);
+        if entry.max_age != Some(0) {
+            self.entries.push(entry);
+        }
+    }
+
+    /// Takes note of the `Strict-Transport-Security` header of a response from `url`. The
+    /// header is ignored on responses that weren't received over HTTPS.
+    pub fn update_from_response(&mut self, url: &Url, headers: &Headers) {
+        if url.scheme.as_slice() != "https" {
+            return;
+        }
+        let host = match url.domain() {
+            Some(host) => host.to_owned(),
+            None => return,
+        };
+        // Only the first header counts.
+        let value = match headers.get_raw("strict-transport-security") {
+            Some(values) if !values.is_empty() => values[0].clone(),
+            _ => return,
+        };
+        let value = match str::from_utf8(value.as_slice()) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        if let Some((max_age, include_subdomains)) = parse_sts_header(value) {
+            if let Some(entry) = HstsEntry::new(host, include_subdomains, Some(max_age)) {
+                self.push(entry);
+            }
+        }
+    }
+}
+
+/// Parses a `Strict-Transport-Security` header into its `max-age` and whether it has
+/// `includeSubDomains`. Returns `None` if the header is invalid.
+fn parse_sts_header(value: &str) -> Option<(u64, bool)> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+    for directive in value.split(';') {
+        let directive = directive.trim();
+        if directive.is_empty() {
+            continue;
+        }
+        let mut parts = directive.splitn(1, '=');
+        let name = parts.next().unwrap().trim().to_ascii_lowercase();
+        let value = parts.next().map(|value| value.trim().trim_matches('"'));
+        match (name.as_slice(), value) {
+            ("max-age", Some(value)) => {
+                if max_age.is_some() {
+                    return None;
+                }
+                max_age = match value.parse() {
+                    Ok(max_age) => Some(max_age),
+                    Err(_) => return None,
+                };
+            }
+            ("includesubdomains", None) => include_subdomains = true,
+            _ => {}
+        }
+    }
+    max_age.map(|max_age| (max_age, include_subdomains))
+}
+
+/// Reads the preloaded list from `resources/hsts_preload.json`.
+pub fn preload_hsts_domains() -> HstsList {
+    read_resource_file(&["hsts_preload.json"]).ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|content| HstsList::from_preload(content.as_slice()))
+        .unwrap_or_else(|| HstsList::new())
+}
+
+/// Returns `url` with its scheme changed to `https`, and the default port changed to match.
+pub fn secure_url(url: &Url) -> Url {
+    if url.scheme.as_slice() != "http" {
+        return url.clone();
+    }
+    let mut secure_url = url.clone();
+    secure_url.scheme = "https".to_owned();
+    if let Some(data) = secure_url.relative_scheme_data_mut() {
+        if data.port == Some(80) {
+            data.port = None;
+        }
+        data.default_port = Some(443);
+    }
+    secure_url
+}
+
+#[test]
+fn test_parse_sts_header() {
+    assert_eq!(parse_sts_header("max-age=31536000"), Some((31536000, false)));
+    assert_eq!(parse_sts_header("max-age=\"600\"; includeSubDomains"), Some((600, true)));
+    assert_eq!(parse_sts_header("INCLUDESUBDOMAINS; Max-Age=0"), Some((0, true)));
+    assert_eq!(parse_sts_header("includeSubDomains"), None);
+    assert_eq!(parse_sts_header("max-age=1; max-age=2"), None);
+    assert_eq!(parse_sts_header("max-age=forever"), None);
+}
+
+#[test]
+fn test_hsts_list_matches_subdomains_only_when_included() {
+    let mut list = HstsList::new();
+    list.push(HstsEntry::new("example.com".to_owned(), false, None).unwrap());
+    list.push(HstsEntry::new("servo.org".to_owned(), true, None).unwrap());
+
+    assert!(list.is_host_secure("example.com"));
+    assert!(!list.is_host_secure("www.example.com"));
+    assert!(list.is_host_secure("Servo.org"));
+    assert!(list.is_host_secure("doc.servo.org"));
+    assert!(!list.is_host_secure("notservo.org"));
+}
+
+#[test]
+fn test_hsts_entry_is_not_created_for_ip_address() {
+    assert!(HstsEntry::new("127.0.0.1".to_owned(), false, None).is_none());
+}
+
+#[test]
+fn test_hsts_entry_from_the_future_is_not_expired() {
+    let mut entry = HstsEntry::new("servo.org".to_owned(), false, Some(600)).unwrap();
+    entry.timestamp = Some(time::get_time().sec as u64 + 3600);
+    assert!(!entry.is_expired());
+    entry.timestamp = Some(0);
+    assert!(entry.is_expired());
+}
+
+#[test]
+fn test_update_from_response() {
+    let mut headers = Headers::new();
+    headers.set_raw("Strict-Transport-Security".to_owned(), vec!(b"max-age=600".to_vec()));
+    let mut list = HstsList::new();
+
+    list.update_from_response(&Url::parse("http://servo.org/").unwrap(), &headers);
+    assert!(!list.is_host_secure("servo.org"), "the header only counts over HTTPS");
+
+    list.update_from_response(&Url::parse("https://servo.org/").unwrap(), &headers);
+    assert!(list.is_host_secure("servo.org"));
+
+    headers.set_raw("Strict-Transport-Security".to_owned(), vec!(b"max-age=0".to_vec()));
+    list.update_from_response(&Url::parse("https://servo.org/").unwrap(), &headers);
+    assert!(!list.is_host_secure("servo.org"));
+}
+
+#[test]
+fn test_upgrade_url() {
+    let mut list = HstsList::new();
+    list.push(HstsEntry::new("servo.org".to_owned(), false, None).unwrap());
+    let url = list.upgrade_url(Url::parse("http://servo.org/").unwrap());
+    assert_eq!(url.serialize(), "https://servo.org/".to_owned());
+    let url = list.upgrade_url(Url::parse("http://example.com/").unwrap());
+    assert_eq!(url.serialize(), "http://example.com/".to_owned());
+}
+
+#[test]
+fn test_secure_url() {
+    let url = secure_url(&Url::parse("http://servo.org:80/index.html?q").unwrap());
+    assert_eq!(url.serialize(), "https://servo.org/index.html?q".to_owned());
+    let url = secure_url(&Url::parse("http://servo.org:8000/").unwrap());
+    assert_eq!(url.serialize(), "https://servo.org:8000/".to_owned());
+}
+
+#[test]
+fn test_preload_list_parses() {
+    assert!(!preload_hsts_domains().entries.is_empty());
+}
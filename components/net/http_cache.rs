@@ -0,0 +1,722 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A private HTTP cache, as specified in http://tools.ietf.org/html/rfc7234
+//!
+//! Responses are kept in memory, keyed by URL, with one entry per set of selecting request
+//! headers named by `Vary`. If a cache directory is configured, entries are also written
+//! through to disk so that they survive across runs. The bodies held in memory are capped; the
+//! least recently used entries are evicted first, and are read back from disk if they are
+//! needed again.
+
+use hyper::header::Headers;
+use hyper::http::RawStatus;
+use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use rustc_serialize::json;
+use time::{self, Timespec};
+use url::Url;
+
+use std::ascii::AsciiExt;
+use std::borrow::{IntoCow, ToOwned};
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::hash::{hash, SipHasher};
+use std::iter::AdditiveIterator;
+use std::old_io::{File, IoResult};
+use std::old_io::fs::{self, PathExtensions};
+
+/// A response stored in the cache.
+#[derive(Clone)]
+pub struct CachedResponse {
+    /// The status line of the stored response.
+    pub status: RawStatus,
+    /// The headers of the stored response, updated on revalidation.
+    pub headers: Headers,
+    /// The (decoded) response body.
+    pub body: Vec<u8>,
+    /// The values of the request headers named by `Vary` when this response was stored.
+    vary: Vec<(String, Option<String>)>,
+    /// When the request that produced this response was sent.
+    request_time: Timespec,
+    /// When this response was received.
+    response_time: Timespec,
+}
+
+/// The result of looking up a request in the cache.
+pub enum CacheLookup {
+    /// No usable stored response; the request must go to the network unchanged.
+    Miss,
+    /// A stored response that may be used without contacting the server.
+    Fresh(CachedResponse),
+    /// A stored response that must be revalidated before use. The request headers have
+    /// already been extended with the appropriate conditional headers.
+    Stale(CachedResponse),
+}
+
+/// A summary of a cache entry, for diagnostics.
+pub struct CacheEntryInfo {
+    pub url: String,
+    pub status: u16,
+    pub size: usize,
+    pub fresh: bool,
+}
+
+/// The default limit on the size of the response bodies kept in memory, in bytes.
+pub const DEFAULT_MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+pub struct HttpCache {
+    /// Stored responses, keyed by URL.
+    entries: HashMap<String, Vec<CachedResponse>>,
+    /// The keys of `entries`, from least to most recently used.
+    recently_used: Vec<String>,
+    /// The total size of the response bodies in `entries`.
+    memory_size: usize,
+    /// The size `memory_size` is kept under by evicting entries.
+    memory_limit: usize,
+    /// The directory used for persistent storage, if any.
+    disk_path: Option<Path>,
+}
+
+impl HttpCache {
+    pub fn new(disk_path: Option<Path>) -> HttpCache {
+        HttpCache::with_memory_limit(disk_path, DEFAULT_MEMORY_LIMIT)
+    }
+
+    pub fn with_memory_limit(disk_path: Option<Path>, memory_limit: usize) -> HttpCache {
+        if let Some(ref path) = disk_path {
+            if !path.is_dir() {
+                if let Err(e) = fs::mkdir_recursive(path, ::std::old_io::USER_RWX) {
+                    warn!("http_cache: couldn't create cache directory {}: {}", path.display(), e);
+                }
+            }
+        }
+        HttpCache {
+            entries: HashMap::new(),
+            recently_used: vec!(),
+            memory_size: 0,
+            memory_limit: memory_limit,
+            disk_path: disk_path,
+        }
+    }
+
+    /// http://tools.ietf.org/html/rfc7234#section-4
+    ///
+    /// Looks up a stored response for a GET request to `url`. If the stored response is stale
+    /// but carries validators, the conditional headers are added to `request_headers`.
+    pub fn lookup(&mut self, url: &Url, request_headers: &mut Headers) -> CacheLookup {
+        let request_directives = cache_directives(request_headers);
+        if request_directives.iter().any(|d| d.0 == "no-store") {
+            return CacheLookup::Miss;
+        }
+
+        let key = url.serialize();
+        if !self.entries.contains_key(&key) {
+            let stored = self.read_from_disk(&key);
+            if stored.is_empty() {
+                return CacheLookup::Miss;
+            }
+            self.memory_size += body_size(&stored);
+            self.entries.insert(key.clone(), stored);
+        }
+        self.mark_used(&key);
+        self.evict_if_necessary();
+
+        // http://tools.ietf.org/html/rfc7234#section-4.1
+        let response = match self.entries.get(&key).and_then(|responses| {
+            responses.iter().find(|r| vary_matches(r, request_headers))
+        }) {
+            Some(response) => response.clone(),
+            None => return CacheLookup::Miss,
+        };
+
+        let response_directives = cache_directives(&response.headers);
+        let must_revalidate =
+            response_directives.iter().any(|d| d.0 == "no-cache") ||
+            request_directives.iter().any(|d| d.0 == "no-cache") ||
+            request_headers.get_raw("pragma").map_or(false, |values| {
+                values.iter().any(|v| v.as_slice() == b"no-cache")
+            });
+
+        let age = response.current_age();
+        let mut lifetime = response.freshness_lifetime();
+        if let Some(max_age) = directive_seconds(&request_directives, "max-age") {
+            lifetime = min(lifetime, max_age);
+        }
+        if !must_revalidate && age < lifetime {
+            debug!("http_cache: fresh hit for {}", key);
+            return CacheLookup::Fresh(response);
+        }
+
+        // http://tools.ietf.org/html/rfc7234#section-4.3.1
+        let etag = header_string(&response.headers, "etag");
+        let last_modified = header_string(&response.headers, "last-modified");
+        if etag.is_none() && last_modified.is_none() {
+            return CacheLookup::Miss;
+        }
+        if let Some(etag) = etag {
+            request_headers.set_raw("If-None-Match".to_owned(), vec![etag.into_bytes()]);
+        }
+        if let Some(last_modified) = last_modified {
+            request_headers.set_raw("If-Modified-Since".to_owned(),
+                                    vec![last_modified.into_bytes()]);
+        }
+        debug!("http_cache: revalidating stale entry for {}", key);
+        CacheLookup::Stale(response)
+    }
+
+    /// http://tools.ietf.org/html/rfc7234#section-3
+    ///
+    /// Stores a response to a GET request, if it is allowed to be cached.
+    pub fn store(&mut self, url: &Url, request_headers: &Headers, request_time: Timespec,
+                 status: RawStatus, response_headers: Headers, body: Vec<u8>) {
+        if !is_storable(request_headers, &status, &response_headers) {
+            return;
+        }
+
+        let vary = match selecting_headers(&response_headers) {
+            Some(names) => names.into_iter().map(|name| {
+                let value = header_string(request_headers, name.as_slice());
+                (name, value)
+            }).collect(),
+            // `Vary: *` always fails to match, so there is no point keeping the response.
+            None => return,
+        };
+
+        if body.len() > self.memory_limit {
+            debug!("http_cache: not storing {} bytes for {}", body.len(), url.serialize());
+            return;
+        }
+
+        let response = CachedResponse {
+            status: status,
+            headers: response_headers,
+            body: body,
+            vary: vary,
+            request_time: request_time,
+            response_time: time::get_time(),
+        };
+
+        let key = url.serialize();
+        {
+            let responses = match self.entries.entry(key.clone()) {
+                Occupied(entry) => entry.into_mut(),
+                Vacant(entry) => entry.insert(vec!()),
+            };
+            let old_size = body_size(responses);
+            responses.retain(|r| r.vary != response.vary);
+            responses.push(response);
+            self.memory_size = self.memory_size - old_size + body_size(responses);
+        }
+        self.write_to_disk(&key);
+        self.mark_used(&key);
+        self.evict_if_necessary();
+    }
+
+    /// http://tools.ietf.org/html/rfc7234#section-4.3.4
+    ///
+    /// Updates a stored response with the headers of a `304 Not Modified` response and returns
+    /// the response that should be used to satisfy the request.
+    pub fn refresh(&mut self, url: &Url, request_headers: &Headers, request_time: Timespec,
+                   not_modified_headers: &Headers) -> Option<CachedResponse> {
+        let key = url.serialize();
+        let refreshed = match self.entries.get_mut(&key) {
+            Some(responses) => {
+                match responses.iter_mut().find(|r| vary_matches(&**r, request_headers)) {
+                    Some(response) => {
+                        for header in not_modified_headers.iter() {
+                            let name = header.name().to_owned();
+                            // The body of the stored response is unchanged, so the framing
+                            // headers must be too.
+                            if name.as_slice().eq_ignore_ascii_case("content-length") {
+                                continue;
+                            }
+                            response.headers.set_raw(name, vec![header.value_string().into_bytes()]);
+                        }
+                        response.request_time = request_time;
+                        response.response_time = time::get_time();
+                        Some(response.clone())
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+        if refreshed.is_some() {
+            self.write_to_disk(&key);
+        }
+        refreshed
+    }
+
+    /// http://tools.ietf.org/html/rfc7234#section-4.4
+    ///
+    /// Removes all stored responses for `url`, after an unsafe request has succeeded.
+    pub fn invalidate(&mut self, url: &Url) {
+        let key = url.serialize();
+        self.remove_from_memory(&key);
+        if let Some(path) = self.path_for_key(&key) {
+            let _ = fs::unlink(&path);
+        }
+    }
+
+    /// Summarizes the responses currently held in memory.
+    pub fn entries(&self) -> Vec<CacheEntryInfo> {
+        let mut entries = vec!();
+        for (url, responses) in self.entries.iter() {
+            for response in responses.iter() {
+                let RawStatus(code, _) = response.status;
+                entries.push(CacheEntryInfo {
+                    url: url.clone(),
+                    status: code,
+                    size: response.body.len(),
+                    fresh: response.current_age() < response.freshness_lifetime(),
+                });
+            }
+        }
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        entries
+    }
+
+    /// Moves `key` to the most recently used end of the eviction order.
+    fn mark_used(&mut self, key: &String) {
+        self.recently_used.retain(|used| used != key);
+        self.recently_used.push(key.clone());
+    }
+
+    /// Drops the least recently used entries from memory until the bodies fit in the memory
+    /// limit. Entries that were written to disk stay there.
+    fn evict_if_necessary(&mut self) {
+        while self.memory_size > self.memory_limit && !self.recently_used.is_empty() {
+            let key = self.recently_used.remove(0);
+            debug!("http_cache: evicting {} from memory", key);
+            self.remove_from_memory(&key);
+        }
+    }
+
+    fn remove_from_memory(&mut self, key: &String) {
+        if let Some(responses) = self.entries.remove(key) {
+            self.memory_size -= body_size(&responses);
+        }
+        self.recently_used.retain(|used| used != key);
+    }
+
+    fn path_for_key(&self, key: &String) -> Option<Path> {
+        self.disk_path.as_ref().map(|dir| {
+            dir.join(format!("{:016x}.json", hash::<_, SipHasher>(key)))
+        })
+    }
+
+    fn read_from_disk(&self, key: &String) -> Vec<CachedResponse> {
+        let path = match self.path_for_key(key) {
+            Some(ref path) if path.exists() => path.clone(),
+            _ => return vec!(),
+        };
+        let contents = match File::open(&path).read_to_string() {
+            Ok(contents) => contents,
+            Err(_) => return vec!(),
+        };
+        let stored: DiskEntry = match json::decode(contents.as_slice()) {
+            Ok(stored) => stored,
+            Err(_) => {
+                warn!("http_cache: discarding corrupt cache file {}", path.display());
+                return vec!();
+            }
+        };
+        // Guard against hash collisions.
+        if stored.url != *key {
+            return vec!();
+        }
+        stored.responses.into_iter().filter_map(|r| r.into_cached_response()).collect()
+    }
+
+    fn write_to_disk(&self, key: &String) {
+        let path = match self.path_for_key(key) {
+            Some(path) => path,
+            None => return,
+        };
+        let responses = match self.entries.get(key) {
+            Some(responses) => responses,
+            None => return,
+        };
+        let entry = DiskEntry {
+            url: key.clone(),
+            responses: responses.iter().map(DiskResponse::from_cached_response).collect(),
+        };
+        if let Err(e) = write_atomically(&path, json::encode(&entry).unwrap().as_slice()) {
+            warn!("http_cache: couldn't write {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl CachedResponse {
+    /// http://tools.ietf.org/html/rfc7234#section-4.2.1
+    fn freshness_lifetime(&self) -> i64 {
+        let directives = cache_directives(&self.headers);
+        if let Some(max_age) = directive_seconds(&directives, "max-age") {
+            return max_age;
+        }
+
+        let date = header_date(&self.headers, "date").unwrap_or(self.response_time);
+        if let Some(expires) = header_string(&self.headers, "expires") {
+            // An invalid date, such as "0", means that the response is already expired.
+            return match parse_http_date(expires.as_slice()) {
+                Some(expires) => (expires - date).num_seconds(),
+                None => 0,
+            };
+        }
+
+        // http://tools.ietf.org/html/rfc7234#section-4.2.2
+        if directives.iter().any(|d| d.0 == "public") || is_heuristically_cacheable(&self.status) {
+            if let Some(last_modified) = header_date(&self.headers, "last-modified") {
+                return (date - last_modified).num_seconds() / 10;
+            }
+        }
+        0
+    }
+
+    /// http://tools.ietf.org/html/rfc7234#section-4.2.3
+    fn current_age(&self) -> i64 {
+        let date = header_date(&self.headers, "date").unwrap_or(self.response_time);
+        let age_value = header_string(&self.headers, "age")
+            .and_then(|age| age.as_slice().trim().parse().ok())
+            .unwrap_or(0i64);
+        let apparent_age = max((self.response_time - date).num_seconds(), 0);
+        let response_delay = (self.response_time - self.request_time).num_seconds();
+        let corrected_initial_age = max(apparent_age, age_value + response_delay);
+        let resident_time = (time::get_time() - self.response_time).num_seconds();
+        corrected_initial_age + resident_time
+    }
+}
+
+fn body_size(responses: &[CachedResponse]) -> usize {
+    responses.iter().map(|response| response.body.len()).sum()
+}
+
+/// http://tools.ietf.org/html/rfc7234#section-3
+fn is_storable(request_headers: &Headers, status: &RawStatus, response_headers: &Headers)
+               -> bool {
+    // We don't support combining partial content. Redirections aren't stored either, since
+    // the loader hands a response from the cache to the consumer without following it.
+    if status.0 == 206 || (status.0 >= 300 && status.0 < 400) {
+        return false;
+    }
+    if cache_directives(request_headers).iter().any(|d| d.0 == "no-store") {
+        return false;
+    }
+    let directives = cache_directives(response_headers);
+    if directives.iter().any(|d| d.0 == "no-store") {
+        return false;
+    }
+    let explicitly_cacheable = directives.iter().any(|d| d.0 == "max-age" || d.0 == "public") ||
+                               response_headers.has_raw("expires");
+    // Without validators or an explicit lifetime a stale response can never be reused.
+    explicitly_cacheable ||
+        is_heuristically_cacheable(status) && (response_headers.has_raw("etag") ||
+                                               response_headers.has_raw("last-modified"))
+}
+
+/// http://tools.ietf.org/html/rfc7231#section-6.1
+///
+/// Redirections are deliberately left out, since they are never stored.
+fn is_heuristically_cacheable(status: &RawStatus) -> bool {
+    let RawStatus(code, _) = *status;
+    match code {
+        200 | 203 | 204 | 404 | 405 | 410 | 414 | 501 => true,
+        _ => false,
+    }
+}
+
+/// Parses the `Cache-Control` header into a list of directive names and optional values.
+fn cache_directives(headers: &Headers) -> Vec<(String, Option<String>)> {
+    let mut directives = vec!();
+    if let Some(values) = headers.get_raw("cache-control") {
+        for value in values.iter() {
+            let value = String::from_utf8_lossy(value.as_slice());
+            for directive in value.split(',') {
+                let mut parts = directive.splitn(1, '=');
+                let name = parts.next().unwrap().trim().to_ascii_lowercase();
+                if name.is_empty() {
+                    continue;
+                }
+                let argument = parts.next().map(|arg| arg.trim().trim_matches('"').to_owned());
+                directives.push((name, argument));
+            }
+        }
+    }
+    directives
+}
+
+fn directive_seconds(directives: &[(String, Option<String>)], name: &str) -> Option<i64> {
+    directives.iter()
+              .find(|d| d.0 == name)
+              .and_then(|d| d.1.as_ref())
+              .and_then(|seconds| seconds.as_slice().parse().ok())
+}
+
+/// Returns the lowercased names of the request headers that select this response, or `None`
+/// if the response can never be reused (`Vary: *`).
+fn selecting_headers(response_headers: &Headers) -> Option<Vec<String>> {
+    let mut names = vec!();
+    if let Some(values) = response_headers.get_raw("vary") {
+        for value in values.iter() {
+            for name in String::from_utf8_lossy(value.as_slice()).split(',') {
+                let name = name.trim().to_ascii_lowercase();
+                if name == "*" {
+                    return None;
+                }
+                if !name.is_empty() {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    Some(names)
+}
+
+/// http://tools.ietf.org/html/rfc7234#section-4.1
+fn vary_matches(response: &CachedResponse, request_headers: &Headers) -> bool {
+    response.vary.iter().all(|&(ref name, ref value)| {
+        header_string(request_headers, name.as_slice()) == *value
+    })
+}
+
+/// Returns the value of a header as a string, joining multiple values with commas.
+fn header_string(headers: &Headers, name: &str) -> Option<String> {
+    headers.get_raw(name).map(|values| {
+        let values: Vec<String> = values.iter()
+                                        .map(|v| String::from_utf8_lossy(v.as_slice()).into_owned())
+                                        .collect();
+        values.connect(",")
+    })
+}
+
+fn header_date(headers: &Headers, name: &str) -> Option<Timespec> {
+    header_string(headers, name).and_then(|value| parse_http_date(value.as_slice()))
+}
+
+/// http://tools.ietf.org/html/rfc7231#section-7.1.1.1
+pub fn parse_http_date(value: &str) -> Option<Timespec> {
+    let value = value.trim();
+    // IMF-fixdate, then the obsolete RFC 850 and asctime formats.
+    for format in ["%a, %d %b %Y %T GMT", "%A, %d-%b-%y %T GMT", "%a %b %e %T %Y"].iter() {
+        if let Ok(tm) = time::strptime(value, *format) {
+            return Some(tm.to_timespec());
+        }
+    }
+    None
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into place, so that a
+/// crash never leaves a partially written file behind.
+pub fn write_atomically(path: &Path, contents: &str) -> IoResult<()> {
+    let mut temp_path = path.clone();
+    temp_path.set_extension("tmp");
+    {
+        let mut file = try!(File::create(&temp_path));
+        try!(file.write_str(contents));
+        try!(file.fsync());
+    }
+    fs::rename(&temp_path, path)
+}
+
+/// The on-disk representation of the responses stored for a URL.
+#[derive(RustcEncodable, RustcDecodable)]
+struct DiskEntry {
+    url: String,
+    responses: Vec<DiskResponse>,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct DiskResponse {
+    status_code: u16,
+    status_text: String,
+    headers: Vec<(String, String)>,
+    body: String,
+    vary: Vec<(String, Option<String>)>,
+    request_time: i64,
+    response_time: i64,
+}
+
+impl DiskResponse {
+    fn from_cached_response(response: &CachedResponse) -> DiskResponse {
+        let RawStatus(code, ref text) = response.status;
+        DiskResponse {
+            status_code: code,
+            status_text: text.as_slice().to_owned(),
+            headers: response.headers.iter()
+                                     .map(|h| (h.name().to_owned(), h.value_string()))
+                                     .collect(),
+            body: response.body.as_slice().to_base64(STANDARD),
+            vary: response.vary.clone(),
+            request_time: response.request_time.sec,
+            response_time: response.response_time.sec,
+        }
+    }
+
+    fn into_cached_response(self) -> Option<CachedResponse> {
+        let body = match self.body.as_slice().from_base64() {
+            Ok(body) => body,
+            Err(_) => return None,
+        };
+        let mut headers = Headers::new();
+        for (name, value) in self.headers.into_iter() {
+            headers.set_raw(name, vec![value.into_bytes()]);
+        }
+        Some(CachedResponse {
+            status: RawStatus(self.status_code, self.status_text.into_cow()),
+            headers: headers,
+            body: body,
+            vary: self.vary,
+            request_time: Timespec::new(self.request_time, 0),
+            response_time: Timespec::new(self.response_time, 0),
+        })
+    }
+}
+
+#[cfg(test)]
+fn response_with_headers(raw_headers: &[(&str, &str)]) -> Headers {
+    let mut headers = Headers::new();
+    for &(name, value) in raw_headers.iter() {
+        headers.set_raw(name.to_owned(), vec![value.as_bytes().to_vec()]);
+    }
+    headers
+}
+
+#[test]
+fn test_parse_http_date() {
+    let expected = Some(Timespec::new(784111777, 0));
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+    assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+    assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+    assert_eq!(parse_http_date("0"), None);
+}
+
+#[test]
+fn test_fresh_and_stale_lookup() {
+    let url = Url::parse("http://example.com/fresh").unwrap();
+    let mut cache = HttpCache::new(None);
+    let headers = response_with_headers(&[("Cache-Control", "max-age=3600")]);
+    cache.store(&url, &Headers::new(), time::get_time(),
+                RawStatus(200, "OK".into_cow()), headers, b"hello".to_vec());
+    match cache.lookup(&url, &mut Headers::new()) {
+        CacheLookup::Fresh(response) => assert_eq!(response.body, b"hello".to_vec()),
+        _ => panic!("expected a fresh response"),
+    }
+
+    let url = Url::parse("http://example.com/stale").unwrap();
+    let headers = response_with_headers(&[("Cache-Control", "no-cache"), ("ETag", "\"v1\"")]);
+    cache.store(&url, &Headers::new(), time::get_time(),
+                RawStatus(200, "OK".into_cow()), headers, b"hello".to_vec());
+    let mut request_headers = Headers::new();
+    match cache.lookup(&url, &mut request_headers) {
+        CacheLookup::Stale(..) => {}
+        _ => panic!("expected a stale response"),
+    }
+    assert_eq!(header_string(&request_headers, "if-none-match"), Some("\"v1\"".to_owned()));
+}
+
+#[test]
+fn test_no_store_and_vary() {
+    let url = Url::parse("http://example.com/").unwrap();
+    let mut cache = HttpCache::new(None);
+    let headers = response_with_headers(&[("Cache-Control", "no-store, max-age=3600")]);
+    cache.store(&url, &Headers::new(), time::get_time(),
+                RawStatus(200, "OK".into_cow()), headers, vec!());
+    match cache.lookup(&url, &mut Headers::new()) {
+        CacheLookup::Miss => {}
+        _ => panic!("no-store responses must not be cached"),
+    }
+
+    let headers = response_with_headers(&[("Cache-Control", "max-age=3600"),
+                                          ("Vary", "Accept-Language")]);
+    let english = response_with_headers(&[("Accept-Language", "en")]);
+    cache.store(&url, &english, time::get_time(),
+                RawStatus(200, "OK".into_cow()), headers, b"hello".to_vec());
+    match cache.lookup(&url, &mut english.clone()) {
+        CacheLookup::Fresh(..) => {}
+        _ => panic!("expected a fresh response for a matching request"),
+    }
+    let mut french = response_with_headers(&[("Accept-Language", "fr")]);
+    match cache.lookup(&url, &mut french) {
+        CacheLookup::Miss => {}
+        _ => panic!("expected a miss for a request with different selecting headers"),
+    }
+}
+
+#[test]
+fn test_expires_and_heuristic_lifetime() {
+    let response = CachedResponse {
+        status: RawStatus(200, "OK".into_cow()),
+        headers: response_with_headers(&[("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                                         ("Expires", "Sun, 06 Nov 1994 09:49:37 GMT")]),
+        body: vec!(),
+        vary: vec!(),
+        request_time: time::get_time(),
+        response_time: time::get_time(),
+    };
+    assert_eq!(response.freshness_lifetime(), 3600);
+
+    let response = CachedResponse {
+        headers: response_with_headers(&[("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                                         ("Last-Modified", "Sun, 06 Nov 1994 07:49:37 GMT")]),
+        .. response
+    };
+    assert_eq!(response.freshness_lifetime(), 360);
+}
+
+#[test]
+fn test_disk_store_round_trip() {
+    use std::old_io::TempDir;
+
+    let dir = TempDir::new("http_cache").unwrap();
+    let url = Url::parse("http://example.com/persistent").unwrap();
+    {
+        let mut cache = HttpCache::new(Some(dir.path().clone()));
+        let headers = response_with_headers(&[("Cache-Control", "max-age=3600")]);
+        cache.store(&url, &Headers::new(), time::get_time(),
+                    RawStatus(200, "OK".into_cow()), headers, b"persisted".to_vec());
+    }
+    let mut cache = HttpCache::new(Some(dir.path().clone()));
+    match cache.lookup(&url, &mut Headers::new()) {
+        CacheLookup::Fresh(response) => assert_eq!(response.body, b"persisted".to_vec()),
+        _ => panic!("expected the response to be read back from disk"),
+    }
+}
+
+#[test]
+fn test_memory_limit_evicts_least_recently_used() {
+    let mut cache = HttpCache::with_memory_limit(None, 10);
+    let first = Url::parse("http://example.com/first").unwrap();
+    let second = Url::parse("http://example.com/second").unwrap();
+    let third = Url::parse("http://example.com/third").unwrap();
+    let headers = response_with_headers(&[("Cache-Control", "max-age=3600")]);
+    for url in [&first, &second].iter() {
+        cache.store(*url, &Headers::new(), time::get_time(),
+                    RawStatus(200, "OK".into_cow()), headers.clone(), b"abcd".to_vec());
+    }
+    // Using the first entry makes the second one the least recently used.
+    match cache.lookup(&first, &mut Headers::new()) {
+        CacheLookup::Fresh(..) => {}
+        _ => panic!("expected a fresh response"),
+    }
+    cache.store(&third, &Headers::new(), time::get_time(),
+                RawStatus(200, "OK".into_cow()), headers.clone(), b"abcd".to_vec());
+    assert_eq!(cache.memory_size, 8);
+    match cache.lookup(&second, &mut Headers::new()) {
+        CacheLookup::Miss => {}
+        _ => panic!("expected the least recently used entry to be evicted"),
+    }
+    match cache.lookup(&first, &mut Headers::new()) {
+        CacheLookup::Fresh(..) => {}
+        _ => panic!("expected the recently used entry to be kept"),
+    }
+
+    // A body that can never fit is not stored at all.
+    cache.store(&second, &Headers::new(), time::get_time(),
+                RawStatus(200, "OK".into_cow()), headers, b"abcdefghijk".to_vec());
+    match cache.lookup(&second, &mut Headers::new()) {
+        CacheLookup::Miss => {}
+        _ => panic!("expected an oversized response not to be cached"),
+    }
+}
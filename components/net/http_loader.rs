@@ -2,38 +2,62 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use connection_pool::{ConnectionPool, PooledConnector};
 use cookie_storage::CookieSource;
+use hsts::HstsList;
+use http_cache::{CacheLookup, CachedResponse, HttpCache};
+use mixed_content;
+use proxy::{ProxyConfig, ProxyConnector};
 use resource_task::{Metadata, TargetedLoadResponse, LoadData, start_sending_opt, ResponseSenders};
 use resource_task::ControlMsg;
+use resource_task::ProgressMsg;
 use resource_task::ProgressMsg::{Payload, Done};
 
 use log;
 use std::collections::HashSet;
 use file_loader;
-use hyper::client::Request;
-use hyper::header::{ContentLength, ContentType, Host, Location};
-use hyper::HttpError;
+use hyper::client::{Request, Response};
+use flate2::reader::{GzDecoder, ZlibDecoder};
+use hyper::header::{ContentLength, ContentType, Headers, Host, Location};
+use hyper::{HttpError, HttpResult};
 use hyper::method::Method;
 use hyper::net::HttpConnector;
 use hyper::status::{StatusCode, StatusClass};
+use hyper::version::HttpVersion;
+use std::ascii::AsciiExt;
 use std::error::Error;
 use openssl::ssl::{SslContext, SslVerifyMode};
 use std::old_io::{IoError, IoErrorKind, Reader};
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{Sender, channel};
 use std::thunk::Invoke;
+use time;
 use util::task::spawn_named;
 use util::resource_files::resources_dir_path;
 use url::{Url, UrlParser};
 
 use std::borrow::ToOwned;
 
-pub fn factory(cookies_chan: Sender<ControlMsg>)
+pub fn factory(cookies_chan: Sender<ControlMsg>, http_cache: Arc<Mutex<HttpCache>>,
+               connection_pool: ConnectionPool, proxies: ProxyConfig,
+               hsts_list: Arc<Mutex<HstsList>>)
                -> Box<Invoke<(LoadData, Sender<TargetedLoadResponse>)> + Send> {
     box move |(load_data, start_chan)| {
-        spawn_named("http_loader".to_owned(), move || load(load_data, start_chan, cookies_chan))
+        spawn_named("http_loader".to_owned(), move || {
+            load(load_data, start_chan, cookies_chan, http_cache, connection_pool, proxies,
+                 hsts_list)
+        })
     }
 }
 
+/// Verifies server certificates against the bundled `resources/certs`.
+pub fn verifier(ssl: &mut SslContext) {
+    ssl.set_verify(SslVerifyMode::SslVerifyPeer, None);
+    let mut certs = resources_dir_path();
+    certs.push("certs");
+    ssl.set_CA_file(&certs);
+}
+
 fn send_error(url: Url, err: String, senders: ResponseSenders) {
     let mut metadata = Metadata::default(url);
     metadata.status = None;
@@ -44,7 +68,156 @@ fn send_error(url: Url, err: String, senders: ResponseSenders) {
     };
 }
 
-fn load(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>, cookies_chan: Sender<ControlMsg>) {
+/// Satisfies a request with a response from the HTTP cache.
+fn send_cached_response(url: Url, cached: CachedResponse, senders: ResponseSenders) {
+    let mut metadata = Metadata::default(url);
+    metadata.set_content_type(match cached.headers.get() {
+        Some(&ContentType(ref mime)) => Some(mime),
+        None => None
+    });
+    metadata.headers = Some(cached.headers);
+    metadata.status = Some(cached.status);
+
+    if let Ok(progress_chan) = start_sending_opt(senders, metadata) {
+        let _ = progress_chan.send(Payload(cached.body));
+        let _ = progress_chan.send(Done(Ok(())));
+    }
+}
+
+/// http://tools.ietf.org/html/rfc7231#section-3.1.2.2
+enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+/// Determines how the response body is encoded, or `None` if we don't support the encoding.
+fn content_encoding(headers: &Headers) -> Option<ContentEncoding> {
+    let values = match headers.get_raw("content-encoding") {
+        Some(values) => values,
+        None => return Some(ContentEncoding::Identity),
+    };
+    let value = values.iter()
+                      .map(|v| String::from_utf8_lossy(v.as_slice()).trim().to_ascii_lowercase())
+                      .filter(|v| !v.is_empty() && v.as_slice() != "identity")
+                      .collect::<Vec<String>>();
+    match value.as_slice() {
+        [] => Some(ContentEncoding::Identity),
+        [ref encoding] if encoding.as_slice() == "gzip" || encoding.as_slice() == "x-gzip" => {
+            Some(ContentEncoding::Gzip)
+        }
+        [ref encoding] if encoding.as_slice() == "deflate" => Some(ContentEncoding::Deflate),
+        _ => None,
+    }
+}
+
+/// Whether a response to a request with the given method can have a body. Responses without
+/// one must not be decoded, even if they carry a `Content-Encoding` header.
+/// http://tools.ietf.org/html/rfc7230#section-3.3.3
+fn response_has_body(method: &Method, status: &StatusCode, headers: &Headers) -> bool {
+    if *method == Method::Head || status.class() == StatusClass::Informational {
+        return false;
+    }
+    match *status {
+        StatusCode::NoContent | StatusCode::NotModified => return false,
+        _ => {}
+    }
+    match headers.get::<ContentLength>() {
+        Some(&ContentLength(0)) => false,
+        _ => true,
+    }
+}
+
+/// Streams the body from `reader` to the consumer, keeping a copy in `body` if requested.
+/// Returns `Ok(false)` if the consumer stopped listening before the end of the body.
+fn send_data<R: Reader>(reader: &mut R, progress_chan: &Sender<ProgressMsg>,
+                        body: &mut Option<Vec<u8>>) -> Result<bool, String> {
+    loop {
+        let mut buf = Vec::with_capacity(1024);
+
+        unsafe { buf.set_len(1024); }
+        match reader.read(buf.as_mut_slice()) {
+            Ok(len) => {
+                unsafe { buf.set_len(len); }
+                if let Some(ref mut body) = *body {
+                    body.push_all(buf.as_slice());
+                }
+                if progress_chan.send(Payload(buf)).is_err() {
+                    // The send errors when the receiver is out of scope,
+                    // which will happen if the fetch has timed out (or has been aborted)
+                    // so we don't need to continue with the loading of the file here.
+                    return Ok(false);
+                }
+            }
+            Err(ref e) if e.kind == IoErrorKind::EndOfFile => return Ok(true),
+            Err(e) => return Err(e.desc.to_string()),
+        }
+    }
+}
+
+/// Whether the server will keep the connection open once the body of `response` has been read.
+fn is_keep_alive(response: &Response) -> bool {
+    let header_has = |name: &str, token: &str| {
+        response.headers.get_raw(name).map_or(false, |values| {
+            values.iter().any(|value| {
+                String::from_utf8_lossy(value.as_slice()).split(',').any(|v| {
+                    v.trim().eq_ignore_ascii_case(token)
+                })
+            })
+        })
+    };
+
+    // Without a length or chunked encoding, the body ends when the connection is closed.
+    if !response.headers.has::<ContentLength>() && !header_has("transfer-encoding", "chunked") {
+        return false;
+    }
+    if header_has("connection", "close") {
+        return false;
+    }
+    match response.version {
+        HttpVersion::Http11 => true,
+        HttpVersion::Http10 => header_has("connection", "keep-alive"),
+        _ => false,
+    }
+}
+
+/// Sends a request through `connector` and waits for the response headers.
+fn send_request(connector: &mut PooledConnector, method: &Method, url: &Url, headers: &Headers,
+                body: Option<&[u8]>) -> HttpResult<Response> {
+    let mut req = try!(Request::with_connector(method.clone(), url.clone(), connector));
+
+    // Preserve the `host` header set automatically by Request.
+    let host = req.headers().get::<Host>().unwrap().clone();
+    *req.headers_mut() = headers.clone();
+    req.headers_mut().set(host);
+
+    match body {
+        Some(data) => req.headers_mut().set(ContentLength(data.len() as u64)),
+        None => match *method {
+            Method::Get | Method::Head => (),
+            _ => req.headers_mut().set(ContentLength(0))
+        }
+    }
+
+    if log_enabled!(log::INFO) {
+        info!("{}", method);
+        for header in req.headers().iter() {
+            info!(" - {}", header);
+        }
+        info!("{:?}", body);
+    }
+
+    let mut writer = try!(req.start());
+    if let Some(data) = body {
+        try!(writer.write_all(data).map_err(HttpError::HttpIoError));
+    }
+    writer.send()
+}
+
+fn load(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>,
+        cookies_chan: Sender<ControlMsg>, http_cache: Arc<Mutex<HttpCache>>,
+        connection_pool: ConnectionPool, proxies: ProxyConfig,
+        hsts_list: Arc<Mutex<HstsList>>) {
     // FIXME: At the time of writing this FIXME, servo didn't have any central
     //        location for configuration. If you're reading this and such a
     //        repository DOES exist, please update this constant to use it.
@@ -67,6 +240,15 @@ fn load(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>, cooki
             return;
         }
 
+        // The resource task has already checked the original URL.
+        if iters > 1 {
+            url = hsts_list.lock().unwrap().upgrade_url(url);
+            if !mixed_content::allow_load(&load_data, &url) {
+                send_error(url, "blocked as mixed content".to_string(), senders);
+                return;
+            }
+        }
+
         match url.scheme.as_slice() {
             "http" | "https" => {}
             _ => {
@@ -78,111 +260,82 @@ fn load(mut load_data: LoadData, start_chan: Sender<TargetedLoadResponse>, cooki
 
         info!("requesting {}", url.serialize());
 
-        fn verifier(ssl: &mut SslContext) {
-            ssl.set_verify(SslVerifyMode::SslVerifyPeer, None);
-            let mut certs = resources_dir_path();
-            certs.push("certs");
-            ssl.set_CA_file(&certs);
-        };
-
         let ssl_err_string = "[UnknownError { library: \"SSL routines\", \
 function: \"SSL3_GET_SERVER_CERTIFICATE\", \
 reason: \"certificate verify failed\" }]";
 
-        let mut connector = HttpConnector(Some(box verifier as Box<FnMut(&mut SslContext)>));
-        let mut req = match Request::with_connector(load_data.method.clone(), url.clone(), &mut connector) {
-            Ok(req) => req,
-            Err(HttpError::HttpIoError(IoError {kind: IoErrorKind::OtherIoError,
-                                                desc: "Error in OpenSSL",
-                                                detail: Some(ref det)})) if det.as_slice() == ssl_err_string => {
-                let mut image = resources_dir_path();
-                image.push("badcert.html");
-                let load_data = LoadData::new(Url::from_file_path(&image).unwrap(), senders.eventual_consumer);
-                file_loader::factory(load_data, senders.immediate_consumer);
-                return;
-            },
-            Err(e) => {
-                println!("{:?}", e);
-                send_error(url, e.description().to_string(), senders);
-                return;
-            }
-        };
-
-        // Preserve the `host` header set automatically by Request.
-        let host = req.headers().get::<Host>().unwrap().clone();
-
         // Avoid automatically preserving request headers when redirects occur.
         // See https://bugzilla.mozilla.org/show_bug.cgi?id=401564 and
         // https://bugzilla.mozilla.org/show_bug.cgi?id=216828 .
         // Only preserve ones which have been explicitly marked as such.
-        if iters == 1 {
+        let mut request_headers = if iters == 1 {
             let mut combined_headers = load_data.headers.clone();
             combined_headers.extend(load_data.preserved_headers.iter());
-            *req.headers_mut() = combined_headers;
+            combined_headers
         } else {
-            *req.headers_mut() = load_data.preserved_headers.clone();
-        }
-
-        req.headers_mut().set(host);
+            load_data.preserved_headers.clone()
+        };
 
-        let (tx, rx) = channel();
-        cookies_chan.send(ControlMsg::GetCookiesForUrl(url.clone(), tx, CookieSource::HTTP)).unwrap();
-        if let Some(cookie_list) = rx.recv().unwrap() {
-            let mut v = Vec::new();
-            v.push(cookie_list.into_bytes());
-            req.headers_mut().set_raw("Cookie".to_owned(), v);
+        if load_data.credentials {
+            let (tx, rx) = channel();
+            cookies_chan.send(ControlMsg::GetCookiesForUrl(url.clone(), tx, CookieSource::HTTP)).unwrap();
+            if let Some(cookie_list) = rx.recv().unwrap() {
+                let mut v = Vec::new();
+                v.push(cookie_list.into_bytes());
+                request_headers.set_raw("Cookie".to_owned(), v);
+            }
         }
 
-        // FIXME(seanmonstar): use AcceptEncoding from Hyper once available
-        //if !req.headers.has::<AcceptEncoding>() {
-            // We currently don't support HTTP Compression (FIXME #2587)
-            req.headers_mut().set_raw("Accept-Encoding".to_owned(), vec![b"identity".to_vec()]);
-        //}
-        if log_enabled!(log::INFO) {
-            info!("{}", load_data.method);
-            for header in req.headers().iter() {
-                info!(" - {}", header);
-            }
-            info!("{:?}", load_data.data);
+        if !request_headers.has_raw("accept-encoding") {
+            request_headers.set_raw("Accept-Encoding".to_owned(), vec![b"gzip, deflate".to_vec()]);
         }
 
-        // Avoid automatically sending request body if a redirect has occurred.
-        let writer = match load_data.data {
-            Some(ref data) if iters == 1 => {
-                req.headers_mut().set(ContentLength(data.len() as u64));
-                let mut writer = match req.start() {
-                    Ok(w) => w,
-                    Err(e) => {
-                        send_error(url, e.description().to_string(), senders);
-                        return;
-                    }
-                };
-                match writer.write_all(&*data) {
-                    Err(e) => {
-                        send_error(url, e.desc.to_string(), senders);
-                        return;
-                    }
-                    _ => {}
-                };
-                writer
-            },
-            _ => {
-                match load_data.method {
-                    Method::Get | Method::Head => (),
-                    _ => req.headers_mut().set(ContentLength(0))
-                }
-                match req.start() {
-                    Ok(w) => w,
-                    Err(e) => {
-                        send_error(url, e.description().to_string(), senders);
-                        return;
-                    }
+        // Only GET requests are answered from the cache; a stale response is kept around
+        // so that a `304 Not Modified` can be satisfied from it.
+        let request_time = time::get_time();
+        let revalidating = if load_data.method == Method::Get {
+            match http_cache.lock().unwrap().lookup(&url, &mut request_headers) {
+                CacheLookup::Fresh(cached) => {
+                    send_cached_response(url, cached, senders);
+                    return;
                 }
+                CacheLookup::Stale(..) => true,
+                CacheLookup::Miss => false,
             }
+        } else {
+            false
+        };
+
+        let connector = HttpConnector(Some(box verifier as Box<FnMut(&mut SslContext)>));
+        let mut connector = PooledConnector::new(connection_pool.clone(),
+                                                 ProxyConnector::new(proxies.clone(), connector));
+
+        // Avoid automatically sending request body if a redirect has occurred.
+        let body = match load_data.data {
+            Some(ref data) if iters == 1 => Some(data.as_slice()),
+            _ => None,
         };
-        let mut response = match writer.send() {
+
+        let mut result = send_request(&mut connector, &load_data.method, &url, &request_headers, body);
+        if result.is_err() && connector.reused_connection() {
+            // The server may have closed the idle connection since it was last used.
+            connector.retry_with_new_connection();
+            result = send_request(&mut connector, &load_data.method, &url, &request_headers, body);
+        }
+
+        let mut response = match result {
             Ok(r) => r,
+            Err(HttpError::HttpIoError(IoError {kind: IoErrorKind::OtherIoError,
+                                                desc: "Error in OpenSSL",
+                                                detail: Some(ref det)})) if det.as_slice() == ssl_err_string => {
+                let mut image = resources_dir_path();
+                image.push("badcert.html");
+                let load_data = LoadData::new(Url::from_file_path(&image).unwrap(), senders.eventual_consumer);
+                file_loader::factory(load_data, senders.immediate_consumer);
+                return;
+            },
             Err(e) => {
+                println!("{:?}", e);
                 send_error(url, e.description().to_string(), senders);
                 return;
             }
@@ -196,33 +349,40 @@ reason: \"certificate verify failed\" }]";
             }
         }
 
-        if let Some(cookies) = response.headers.get_raw("set-cookie") {
-            for cookie in cookies.iter() {
-                if let Ok(cookies) = String::from_utf8(cookie.clone()) {
-                    cookies_chan.send(ControlMsg::SetCookiesForUrl(url.clone(),
-                                                                   cookies,
-                                                                   CookieSource::HTTP)).unwrap();
+        hsts_list.lock().unwrap().update_from_response(&url, &response.headers);
+
+        if load_data.credentials {
+            if let Some(cookies) = response.headers.get_raw("set-cookie") {
+                for cookie in cookies.iter() {
+                    if let Ok(cookies) = String::from_utf8(cookie.clone()) {
+                        cookies_chan.send(ControlMsg::SetCookiesForUrl(url.clone(),
+                                                                       cookies,
+                                                                       CookieSource::HTTP)).unwrap();
+                    }
                 }
             }
         }
 
-        if response.status.class() == StatusClass::Redirection {
+        if revalidating && response.status == StatusCode::NotModified {
+            let refreshed = http_cache.lock().unwrap().refresh(&url, &request_headers,
+                                                               request_time, &response.headers);
+            if let Some(cached) = refreshed {
+                send_cached_response(url, cached, senders);
+                return;
+            }
+        }
+
+        // http://tools.ietf.org/html/rfc7234#section-4.4
+        match load_data.method {
+            Method::Get | Method::Head | Method::Options | Method::Trace => {}
+            _ => if response.status.class() == StatusClass::Success {
+                http_cache.lock().unwrap().invalidate(&url);
+            }
+        }
+
+        if load_data.follow_redirects && response.status.class() == StatusClass::Redirection {
             match response.headers.get::<Location>() {
                 Some(&Location(ref new_url)) => {
-                    // CORS (http://fetch.spec.whatwg.org/#http-fetch, status section, point 9, 10)
-                    match load_data.cors {
-                        Some(ref c) => {
-                            if c.preflight {
-                                // The preflight lied
-                                send_error(url, "Preflight fetch inconsistent with main fetch".to_string(), senders);
-                                return;
-                            } else {
-                                // XXXManishearth There are some CORS-related steps here,
-                                // but they don't seem necessary until credentials are implemented
-                            }
-                        }
-                        _ => {}
-                    }
                     let new_url = match UrlParser::new().base_url(&url).parse(new_url.as_slice()) {
                         Ok(u) => u,
                         Err(e) => {
@@ -253,7 +413,7 @@ reason: \"certificate verify failed\" }]";
             }
         }
 
-        let mut metadata = Metadata::default(url);
+        let mut metadata = Metadata::default(url.clone());
         metadata.set_content_type(match response.headers.get() {
             Some(&ContentType(ref mime)) => Some(mime),
             None => None
@@ -261,28 +421,61 @@ reason: \"certificate verify failed\" }]";
         metadata.headers = Some(response.headers.clone());
         metadata.status = Some(response.status_raw().clone());
 
+        // Keep a copy of the body if the response might be stored in the cache.
+        let mut cached_body = if load_data.method == Method::Get { Some(vec!()) } else { None };
+        let status = response.status_raw().clone();
+        let headers = response.headers.clone();
+
         let progress_chan = match start_sending_opt(senders, metadata) {
             Ok(p) => p,
             _ => return
         };
-        loop {
-            let mut buf = Vec::with_capacity(1024);
-
-            unsafe { buf.set_len(1024); }
-            match response.read(buf.as_mut_slice()) {
-                Ok(len) => {
-                    unsafe { buf.set_len(len); }
-                    if progress_chan.send(Payload(buf)).is_err() {
-                        // The send errors when the receiver is out of scope,
-                        // which will happen if the fetch has timed out (or has been aborted)
-                        // so we don't need to continue with the loading of the file here.
-                        return;
-                    }
+
+        // The body is decoded as it streams in, but the metadata keeps the original
+        // `Content-Encoding` header.
+        let encoding = if response_has_body(&load_data.method, &response.status, &headers) {
+            content_encoding(&headers)
+        } else {
+            Some(ContentEncoding::Identity)
+        };
+        let result = match encoding {
+            Some(ContentEncoding::Identity) => send_data(&mut response, &progress_chan, &mut cached_body),
+            Some(ContentEncoding::Gzip) => {
+                match GzDecoder::new(&mut response) {
+                    Ok(mut decoder) => send_data(&mut decoder, &progress_chan, &mut cached_body),
+                    Err(e) => Err(e.desc.to_string()),
                 }
-                Err(_) => {
-                    let _ = progress_chan.send(Done(Ok(())));
-                    break;
+            }
+            Some(ContentEncoding::Deflate) => {
+                send_data(&mut ZlibDecoder::new(&mut response), &progress_chan, &mut cached_body)
+            }
+            None => Err("unsupported content encoding".to_string()),
+        };
+
+        // The connection can only carry another request once this response has been read
+        // to the end. It goes back to the pool when the response is dropped, which has to
+        // happen before the consumer hears that the load is done.
+        if let Ok(true) = result {
+            if load_data.method != Method::Head && is_keep_alive(&response) &&
+                    response.read_to_end().is_ok() {
+                connector.keep_alive();
+            }
+        }
+        drop(response);
+        drop(connector);
+
+        match result {
+            Ok(true) => {
+                if let Some(body) = cached_body {
+                    http_cache.lock().unwrap().store(&url, &request_headers, request_time,
+                                                     status, headers, body);
                 }
+                let _ = progress_chan.send(Done(Ok(())));
+            }
+            // The consumer went away, so there's nobody left to tell.
+            Ok(false) => {}
+            Err(e) => {
+                let _ = progress_chan.send(Done(Err(e)));
             }
         }
 
@@ -36,8 +36,9 @@ pub enum Msg {
     /// Clients must wait for a response before shutting down the ResourceTask
     Exit(Sender<()>),
 
-    /// Used by the prefetch tasks to post back image binaries
-    StorePrefetchedImageData(Url, Result<Vec<u8>, ()>),
+    /// Used by the prefetch tasks to post back image binaries, along with the URL they were
+    /// loaded from after redirects
+    StorePrefetchedImageData(Url, Result<(Vec<u8>, Url), ()>),
 
     /// Used by the decoder tasks to post decoded images back to the cache
     StoreImage(Url, Option<Arc<Box<Image>>>),
@@ -47,6 +48,19 @@ pub enum Msg {
 
     /// For testing
     WaitForStorePrefetched(Sender<()>),
+
+    /// Report the images in the cache and how far along each one is
+    GetEntries(Sender<Vec<ImageCacheEntryInfo>>),
+
+    /// Request the URL an image was loaded from after redirects, or `None` if it has not
+    /// been loaded yet
+    GetFinalUrl(Url, Sender<Option<Url>>),
+}
+
+/// A summary of an image in the cache, for diagnostics.
+pub struct ImageCacheEntryInfo {
+    pub url: Url,
+    pub state: &'static str,
 }
 
 #[derive(Clone)]
@@ -79,12 +93,18 @@ impl ImageCacheTask {
         let (chan, port) = channel();
         let chan_clone = chan.clone();
 
+        // Let the resource task list our contents on about:cache.
+        let _ = resource_task.send(resource_task::ControlMsg::SetImageCache(ImageCacheTask {
+            chan: chan.clone(),
+        }));
+
         spawn_named("ImageCacheTask".to_owned(), move || {
             let mut cache = ImageCache {
                 resource_task: resource_task,
                 port: port,
                 chan: chan_clone,
                 state_map: HashMap::new(),
+                final_url_map: HashMap::new(),
                 wait_map: HashMap::new(),
                 need_exit: None,
                 task_pool: task_pool,
@@ -137,6 +157,8 @@ struct ImageCache {
     chan: Sender<Msg>,
     /// The state of processing an image for a URL
     state_map: HashMap<Url, ImageState>,
+    /// The URL each loaded image was fetched from after redirects
+    final_url_map: HashMap<Url, Url>,
     /// List of clients waiting on a WaitForImage response
     wait_map: HashMap<Url, Arc<Mutex<Vec<Sender<ImageResponseMsg>>>>>,
     need_exit: Option<Sender<()>>,
@@ -193,6 +215,12 @@ impl ImageCache {
                 }
                 Msg::WaitForStore(chan) => store_chan = Some(chan),
                 Msg::WaitForStorePrefetched(chan) => store_prefetched_chan = Some(chan),
+                Msg::GetEntries(response) => {
+                    let _ = response.send(self.entries());
+                }
+                Msg::GetFinalUrl(url, response) => {
+                    let _ = response.send(self.final_url_map.get(&url).map(|url| url.clone()));
+                }
                 Msg::Exit(response) => {
                     assert!(self.need_exit.is_none());
                     self.need_exit = Some(response);
@@ -239,6 +267,24 @@ impl ImageCache {
         self.state_map.insert(url, state);
     }
 
+    fn entries(&self) -> Vec<ImageCacheEntryInfo> {
+        let mut entries: Vec<ImageCacheEntryInfo> = self.state_map.iter().map(|(url, state)| {
+            ImageCacheEntryInfo {
+                url: url.clone(),
+                state: match *state {
+                    ImageState::Init => "init",
+                    ImageState::Prefetching(..) => "prefetching",
+                    ImageState::Prefetched(..) => "prefetched",
+                    ImageState::Decoding => "decoding",
+                    ImageState::Decoded(..) => "decoded",
+                    ImageState::Failed => "failed",
+                },
+            }
+        }).collect();
+        entries.sort_by(|a, b| a.url.serialize().cmp(&b.url.serialize()));
+        entries
+    }
+
     fn prefetch(&mut self, url: Url) {
         match self.get_state(&url) {
             ImageState::Init => {
@@ -265,11 +311,12 @@ impl ImageCache {
         }
     }
 
-    fn store_prefetched_image_data(&mut self, url: Url, data: Result<Vec<u8>, ()>) {
+    fn store_prefetched_image_data(&mut self, url: Url, data: Result<(Vec<u8>, Url), ()>) {
         match self.get_state(&url) {
           ImageState::Prefetching(next_step) => {
             match data {
-              Ok(data) => {
+              Ok((data, final_url)) => {
+                self.final_url_map.insert(url.clone(), final_url);
                 self.set_state(url.clone(), ImageState::Prefetched(data));
                 match next_step {
                   AfterPrefetch::DoDecode => self.decode(url),
@@ -446,20 +493,22 @@ impl ImageCacheTask {
     }
 }
 
-fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<Vec<u8>, ()> {
+fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<(Vec<u8>, Url), ()> {
     let (response_chan, response_port) = channel();
     resource_task.send(resource_task::ControlMsg::Load(LoadData::new(url, response_chan))).unwrap();
 
     let mut image_data = vec!();
 
-    let progress_port = response_port.recv().unwrap().progress_port;
+    let response = response_port.recv().unwrap();
+    let final_url = response.metadata.final_url;
+    let progress_port = response.progress_port;
     loop {
         match progress_port.recv().unwrap() {
             Payload(data) => {
                 image_data.push_all(data.as_slice());
             }
             Done(Ok(..)) => {
-                return Ok(image_data);
+                return Ok((image_data, final_url));
             }
             Done(Err(..)) => {
                 return Err(());
@@ -694,6 +743,30 @@ mod tests {
         mock_resource_task.send(resource_task::ControlMsg::Exit);
     }
 
+    #[test]
+    fn should_return_final_url_of_redirected_image() {
+        let mock_resource_task = mock_resource_task(box SendTestImage);
+
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4), profiler());
+        let url = Url::parse("file:///").unwrap();
+
+        let (response_chan, response_port) = channel();
+        image_cache_task.send(Msg::GetFinalUrl(url.clone(), response_chan));
+        assert!(response_port.recv().unwrap().is_none());
+
+        let join_port = image_cache_task.wait_for_store_prefetched();
+        image_cache_task.send(Prefetch(url.clone()));
+        join_port.recv().unwrap();
+
+        // The mock resource task reports every load as redirected to file:///fake.
+        let (response_chan, response_port) = channel();
+        image_cache_task.send(Msg::GetFinalUrl(url, response_chan));
+        assert!(response_port.recv().unwrap() == Some(Url::parse("file:///fake").unwrap()));
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::ControlMsg::Exit);
+    }
+
     #[test]
     fn should_return_decoded_image_data_for_multiple_requests() {
         let mock_resource_task = mock_resource_task(box SendTestImage);
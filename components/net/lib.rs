@@ -17,11 +17,13 @@
 
 extern crate "cookie" as cookie_rs;
 extern crate collections;
+extern crate flate2;
 extern crate geom;
 extern crate hyper;
 extern crate png;
 #[macro_use]
 extern crate log;
+extern crate msg;
 extern crate openssl;
 extern crate "rustc-serialize" as rustc_serialize;
 extern crate util;
@@ -40,16 +42,23 @@ pub mod image {
 }
 
 pub mod about_loader;
+pub mod connection_pool;
 pub mod file_loader;
+pub mod hsts;
+pub mod http_cache;
 pub mod http_loader;
 pub mod data_loader;
 pub mod cookie;
 pub mod cookie_storage;
 pub mod image_cache_task;
 pub mod local_image_cache;
+pub mod mime_classifier;
+pub mod mixed_content;
+pub mod proxy;
 pub mod pub_domains;
 pub mod resource_task;
 pub mod storage_task;
+pub mod websocket_loader;
 mod sniffer_task;
 
 /// An implementation of the [Fetch spec](http://fetch.spec.whatwg.org/)
@@ -0,0 +1,389 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An implementation of the [MIME Sniffing spec](http://mimesniff.spec.whatwg.org/)
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::cmp::min;
+
+/// The number of bytes of a resource header that sniffing looks at.
+/// http://mimesniff.spec.whatwg.org/#reading-the-resource-header
+pub const MAX_RESOURCE_HEADER_LENGTH: usize = 1445;
+
+pub type MimeType = (String, String);
+
+/// A byte pattern with a mask, and the set of leading bytes that may be skipped before it.
+/// http://mimesniff.spec.whatwg.org/#matching-a-mime-type-pattern
+struct ByteMatcher {
+    pattern: &'static [u8],
+    mask: &'static [u8],
+    leading_ignore: &'static [u8],
+    content_type: (&'static str, &'static str),
+}
+
+impl ByteMatcher {
+    fn matches(&self, data: &[u8]) -> bool {
+        assert!(self.pattern.len() == self.mask.len());
+        let start = data.iter().position(|b| !self.leading_ignore.contains(b))
+                               .unwrap_or(data.len());
+        let data = &data[start..];
+        if data.len() < self.pattern.len() {
+            return false;
+        }
+        self.pattern.iter().zip(self.mask.iter()).zip(data.iter()).all(|((&p, &m), &d)| {
+            d & m == p
+        })
+    }
+
+    fn classify(&self, data: &[u8]) -> Option<MimeType> {
+        if self.matches(data) {
+            Some((self.content_type.0.to_owned(), self.content_type.1.to_owned()))
+        } else {
+            None
+        }
+    }
+}
+
+const WHITESPACE: &'static [u8] = b"\t\n\x0C\r ";
+
+/// http://mimesniff.spec.whatwg.org/#matching-an-image-type-pattern
+static IMAGE_MATCHERS: [ByteMatcher; 8] = [
+    ByteMatcher { pattern: b"\x00\x00\x01\x00", mask: b"\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "x-icon") },
+    ByteMatcher { pattern: b"\x00\x00\x02\x00", mask: b"\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "x-icon") },
+    ByteMatcher { pattern: b"BM", mask: b"\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "bmp") },
+    ByteMatcher { pattern: b"GIF87a", mask: b"\xFF\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "gif") },
+    ByteMatcher { pattern: b"GIF89a", mask: b"\xFF\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "gif") },
+    ByteMatcher { pattern: b"RIFF\x00\x00\x00\x00WEBPVP",
+                  mask: b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "webp") },
+    ByteMatcher { pattern: b"\x89PNG\r\n\x1A\n", mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "png") },
+    ByteMatcher { pattern: b"\xFF\xD8\xFF", mask: b"\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("image", "jpeg") },
+];
+
+/// http://mimesniff.spec.whatwg.org/#matching-an-audio-or-video-type-pattern
+static AUDIO_VIDEO_MATCHERS: [ByteMatcher; 6] = [
+    ByteMatcher { pattern: b"FORM\x00\x00\x00\x00AIFF",
+                  mask: b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("audio", "aiff") },
+    ByteMatcher { pattern: b"ID3", mask: b"\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("audio", "mpeg") },
+    ByteMatcher { pattern: b"OggS\x00", mask: b"\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("application", "ogg") },
+    ByteMatcher { pattern: b"MThd\x00\x00\x00\x06", mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("audio", "midi") },
+    ByteMatcher { pattern: b"RIFF\x00\x00\x00\x00AVI ",
+                  mask: b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("video", "avi") },
+    ByteMatcher { pattern: b"RIFF\x00\x00\x00\x00WAVE",
+                  mask: b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("audio", "wave") },
+];
+
+/// http://mimesniff.spec.whatwg.org/#matching-an-archive-type-pattern
+static ARCHIVE_MATCHERS: [ByteMatcher; 3] = [
+    ByteMatcher { pattern: b"\x1F\x8B\x08", mask: b"\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("application", "x-gzip") },
+    ByteMatcher { pattern: b"PK\x03\x04", mask: b"\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("application", "zip") },
+    ByteMatcher { pattern: b"Rar \x1A\x07\x00", mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("application", "x-rar-compressed") },
+];
+
+/// The patterns from http://mimesniff.spec.whatwg.org/#identifying-a-resource-with-an-unknown-mime-type
+/// that may only be used when scriptable types may be sniffed, other than the HTML tags.
+static SCRIPTABLE_MATCHERS: [ByteMatcher; 2] = [
+    ByteMatcher { pattern: b"<?xml", mask: b"\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: WHITESPACE, content_type: ("text", "xml") },
+    ByteMatcher { pattern: b"%PDF-", mask: b"\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("application", "pdf") },
+];
+
+/// The remaining patterns from
+/// http://mimesniff.spec.whatwg.org/#identifying-a-resource-with-an-unknown-mime-type
+static PLAIN_MATCHERS: [ByteMatcher; 4] = [
+    ByteMatcher { pattern: b"%!PS-Adobe-", mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("application", "postscript") },
+    ByteMatcher { pattern: b"\xFE\xFF", mask: b"\xFF\xFF",
+                  leading_ignore: b"", content_type: ("text", "plain") },
+    ByteMatcher { pattern: b"\xFF\xFE", mask: b"\xFF\xFF",
+                  leading_ignore: b"", content_type: ("text", "plain") },
+    ByteMatcher { pattern: b"\xEF\xBB\xBF", mask: b"\xFF\xFF\xFF",
+                  leading_ignore: b"", content_type: ("text", "plain") },
+];
+
+/// The tags that identify an HTML document, each of which must be followed by a
+/// tag-terminating byte.
+static HTML_TAGS: [&'static [u8]; 17] = [
+    b"<!DOCTYPE HTML", b"<HTML", b"<HEAD", b"<SCRIPT", b"<IFRAME", b"<H1", b"<DIV", b"<FONT",
+    b"<TABLE", b"<A", b"<STYLE", b"<TITLE", b"<B", b"<BODY", b"<BR", b"<P", b"<!--",
+];
+
+/// http://mimesniff.spec.whatwg.org/#mime-type-sniffing-algorithm
+pub fn classify(supplied_type: &Option<MimeType>, no_sniff: bool, check_for_apache_bug: bool,
+                data: &[u8]) -> Option<MimeType> {
+    let data = &data[..min(data.len(), MAX_RESOURCE_HEADER_LENGTH)];
+
+    // Step 1
+    let supplied = match *supplied_type {
+        Some((ref type_, ref subtype)) if !is_unknown_type(type_.as_slice(), subtype.as_slice()) => {
+            (type_.to_ascii_lowercase(), subtype.to_ascii_lowercase())
+        }
+        _ => return Some(sniff_unknown_type(!no_sniff, data)),
+    };
+
+    // Step 2
+    if no_sniff {
+        return supplied_type.clone();
+    }
+
+    // Step 3
+    if check_for_apache_bug {
+        return Some(sniff_text_or_binary(data));
+    }
+
+    // Step 4
+    if is_xml_type(&supplied) {
+        return supplied_type.clone();
+    }
+
+    // Step 5
+    if supplied.0 == "text" && supplied.1 == "html" {
+        return supplied_type.clone();
+    }
+
+    // Step 6
+    if supplied.0 == "image" {
+        if let Some(sniffed) = IMAGE_MATCHERS.iter().filter_map(|m| m.classify(data)).next() {
+            return Some(sniffed);
+        }
+    }
+
+    // Step 7
+    if supplied.0 == "audio" || supplied.0 == "video" ||
+       (supplied.0 == "application" && supplied.1 == "ogg") {
+        if let Some(sniffed) = sniff_audio_or_video(data) {
+            return Some(sniffed);
+        }
+    }
+
+    // Step 8
+    supplied_type.clone()
+}
+
+/// http://mimesniff.spec.whatwg.org/#unknown-mime-type
+fn is_unknown_type(type_: &str, subtype: &str) -> bool {
+    (type_.eq_ignore_ascii_case("unknown") && subtype.eq_ignore_ascii_case("unknown")) ||
+    (type_.eq_ignore_ascii_case("application") && subtype.eq_ignore_ascii_case("unknown")) ||
+    (type_ == "*" && subtype == "*")
+}
+
+/// http://mimesniff.spec.whatwg.org/#xml-mime-type
+fn is_xml_type(&(ref type_, ref subtype): &MimeType) -> bool {
+    subtype.ends_with("+xml") ||
+    (subtype.as_slice() == "xml" && (type_.as_slice() == "text" || type_.as_slice() == "application"))
+}
+
+/// http://mimesniff.spec.whatwg.org/#identifying-a-resource-with-an-unknown-mime-type
+fn sniff_unknown_type(sniff_scriptable: bool, data: &[u8]) -> MimeType {
+    if sniff_scriptable {
+        if let Some(sniffed) = sniff_html(data) {
+            return sniffed;
+        }
+        if let Some(sniffed) = SCRIPTABLE_MATCHERS.iter().filter_map(|m| m.classify(data)).next() {
+            return sniffed;
+        }
+    }
+
+    PLAIN_MATCHERS.iter().filter_map(|m| m.classify(data)).next()
+        .or_else(|| IMAGE_MATCHERS.iter().filter_map(|m| m.classify(data)).next())
+        .or_else(|| sniff_audio_or_video(data))
+        .or_else(|| ARCHIVE_MATCHERS.iter().filter_map(|m| m.classify(data)).next())
+        .unwrap_or_else(|| {
+            if data.iter().any(|&b| is_binary_data_byte(b)) {
+                ("application".to_owned(), "octet-stream".to_owned())
+            } else {
+                ("text".to_owned(), "plain".to_owned())
+            }
+        })
+}
+
+/// http://mimesniff.spec.whatwg.org/#rules-for-text-or-binary
+fn sniff_text_or_binary(data: &[u8]) -> MimeType {
+    let is_text_bom = data.starts_with(b"\xFE\xFF") ||
+                      data.starts_with(b"\xFF\xFE") ||
+                      data.starts_with(b"\xEF\xBB\xBF");
+    if is_text_bom || !data.iter().any(|&b| is_binary_data_byte(b)) {
+        return ("text".to_owned(), "plain".to_owned());
+    }
+    sniff_unknown_type(false, data)
+}
+
+/// http://mimesniff.spec.whatwg.org/#binary-data-byte
+fn is_binary_data_byte(byte: u8) -> bool {
+    match byte {
+        0x00...0x08 | 0x0B | 0x0E...0x1A | 0x1C...0x1F => true,
+        _ => false,
+    }
+}
+
+/// Matches the case-insensitive HTML tag patterns, skipping leading whitespace.
+fn sniff_html(data: &[u8]) -> Option<MimeType> {
+    let start = match data.iter().position(|b| !WHITESPACE.contains(b)) {
+        Some(start) => start,
+        None => return None,
+    };
+    let data = &data[start..];
+    for tag in HTML_TAGS.iter() {
+        if data.len() <= tag.len() {
+            continue;
+        }
+        let matches = tag.iter().zip(data.iter()).all(|(&t, &d)| t == d.to_ascii_uppercase());
+        // http://mimesniff.spec.whatwg.org/#tag-terminating-byte
+        let terminated = data[tag.len()] == b' ' || data[tag.len()] == b'>';
+        if matches && terminated {
+            return Some(("text".to_owned(), "html".to_owned()));
+        }
+    }
+    None
+}
+
+/// http://mimesniff.spec.whatwg.org/#matching-an-audio-or-video-type-pattern
+fn sniff_audio_or_video(data: &[u8]) -> Option<MimeType> {
+    AUDIO_VIDEO_MATCHERS.iter().filter_map(|m| m.classify(data)).next()
+        .or_else(|| if is_mp4(data) { Some(("video".to_owned(), "mp4".to_owned())) } else { None })
+        .or_else(|| if is_webm(data) { Some(("video".to_owned(), "webm".to_owned())) } else { None })
+}
+
+/// http://mimesniff.spec.whatwg.org/#signature-for-mp4
+fn is_mp4(data: &[u8]) -> bool {
+    if data.len() < 12 {
+        return false;
+    }
+    let box_size = ((data[0] as usize) << 24) | ((data[1] as usize) << 16) |
+                   ((data[2] as usize) << 8) | (data[3] as usize);
+    if data.len() < box_size || box_size % 4 != 0 || box_size < 12 {
+        return false;
+    }
+    if &data[4..8] != b"ftyp" {
+        return false;
+    }
+    if &data[8..11] == b"mp4" {
+        return true;
+    }
+    let mut offset = 16;
+    while offset + 3 <= box_size {
+        if &data[offset..offset + 3] == b"mp4" {
+            return true;
+        }
+        offset += 4;
+    }
+    false
+}
+
+/// http://mimesniff.spec.whatwg.org/#signature-for-webm
+fn is_webm(data: &[u8]) -> bool {
+    if !data.starts_with(b"\x1A\x45\xDF\xA3") {
+        return false;
+    }
+    // Look for the DocType element with a value of "webm" in the EBML header.
+    let limit = min(data.len(), 38);
+    let mut offset = 4;
+    while offset + 1 < limit {
+        if data[offset] == 0x42 && data[offset + 1] == 0x82 {
+            let rest = &data[offset + 2..];
+            // Skip the variable-length size of the DocType element.
+            let size_length = rest.first().map_or(0, |&b| b.leading_zeros() as usize + 1);
+            return rest.len() >= size_length + 4 && &rest[size_length..size_length + 4] == b"webm";
+        }
+        offset += 1;
+    }
+    false
+}
+
+/// http://mimesniff.spec.whatwg.org/#check-for-apache-bug-flag
+pub fn is_apache_bug_content_type(raw_content_type: &[u8]) -> bool {
+    match raw_content_type {
+        b"text/plain" |
+        b"text/plain; charset=ISO-8859-1" |
+        b"text/plain; charset=iso-8859-1" |
+        b"text/plain; charset=UTF-8" => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+fn mime(type_: &str, subtype: &str) -> Option<MimeType> {
+    Some((type_.to_owned(), subtype.to_owned()))
+}
+
+#[test]
+fn test_sniff_unknown_html_and_xml() {
+    assert_eq!(classify(&None, false, false, b"  <!DOCTYPE html>\n<html>"), mime("text", "html"));
+    assert_eq!(classify(&None, false, false, b"<p>hello</p>"), mime("text", "html"));
+    assert_eq!(classify(&None, false, false, b"<!-- comment -->"), mime("text", "html"));
+    assert_eq!(classify(&None, false, false, b"<?xml version=\"1.0\"?>"), mime("text", "xml"));
+    assert_eq!(classify(&None, false, false, b"<pre>"), mime("text", "plain"));
+}
+
+#[test]
+fn test_sniff_unknown_without_scriptable() {
+    assert_eq!(classify(&None, true, false, b"<html>"), mime("text", "plain"));
+    assert_eq!(classify(&None, true, false, b"%PDF-1.4"), mime("text", "plain"));
+}
+
+#[test]
+fn test_sniff_unknown_binary() {
+    assert_eq!(classify(&None, false, false, b"\x89PNG\r\n\x1A\n\x00\x00"), mime("image", "png"));
+    assert_eq!(classify(&None, false, false, b"PK\x03\x04\x00"), mime("application", "zip"));
+    assert_eq!(classify(&None, false, false, b"\x00\x01\x02"), mime("application", "octet-stream"));
+    assert_eq!(classify(&None, false, false, b"plain text"), mime("text", "plain"));
+    assert_eq!(classify(&mime("application", "unknown"), false, false, b"GIF89a"), mime("image", "gif"));
+}
+
+#[test]
+fn test_sniff_image_with_wrong_type() {
+    assert_eq!(classify(&mime("image", "png"), false, false, b"\xFF\xD8\xFF\xE0"),
+               mime("image", "jpeg"));
+    assert_eq!(classify(&mime("image", "png"), false, false, b"not an image"),
+               mime("image", "png"));
+}
+
+#[test]
+fn test_sniff_audio_video() {
+    assert_eq!(classify(&mime("audio", "basic"), false, false, b"ID3\x03"), mime("audio", "mpeg"));
+    assert_eq!(classify(&mime("video", "x-unknown"), false, false,
+                        b"\x00\x00\x00\x14ftypisom\x00\x00\x00\x00mp41"),
+               mime("video", "mp4"));
+}
+
+#[test]
+fn test_supplied_type_is_kept() {
+    assert_eq!(classify(&mime("text", "html"), false, false, b"\x89PNG\r\n\x1A\n"),
+               mime("text", "html"));
+    assert_eq!(classify(&mime("application", "xhtml+xml"), false, false, b"<html>"),
+               mime("application", "xhtml+xml"));
+}
+
+#[test]
+fn test_nosniff() {
+    assert_eq!(classify(&mime("image", "png"), true, false, b"\xFF\xD8\xFF\xE0"),
+               mime("image", "png"));
+}
+
+#[test]
+fn test_apache_bug() {
+    assert!(is_apache_bug_content_type(b"text/plain; charset=UTF-8"));
+    assert!(!is_apache_bug_content_type(b"text/plain;charset=UTF-8"));
+    assert_eq!(classify(&mime("text", "plain"), false, true, b"hello"), mime("text", "plain"));
+    assert_eq!(classify(&mime("text", "plain"), false, true, b"\x89PNG\r\n\x1A\n\x00"),
+               mime("image", "png"));
+}
@@ -0,0 +1,120 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Mixed content checks, as specified in http://www.w3.org/TR/mixed-content/
+//!
+//! A secure document loading a resource over an insecure connection is mixed content. Such
+//! loads are blocked, except for images, audio and video, which are only reported.
+
+use resource_task::{LoadContext, LoadData};
+
+use std::sync::mpsc::Sender;
+use url::Url;
+
+/// http://www.w3.org/TR/mixed-content/#categories
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MixedContentCategory {
+    /// Resources that can take over the document, such as scripts. These are blocked.
+    Blockable,
+    /// Resources that are only displayed, such as images. These are loaded with a warning.
+    OptionallyBlockable,
+}
+
+impl MixedContentCategory {
+    pub fn for_context(context: LoadContext) -> MixedContentCategory {
+        match context {
+            LoadContext::Image | LoadContext::AudioVideo => MixedContentCategory::OptionallyBlockable,
+            LoadContext::Browsing | LoadContext::Script | LoadContext::Style |
+            LoadContext::Font | LoadContext::Connect => MixedContentCategory::Blockable,
+        }
+    }
+}
+
+/// Whether loading `url` from a document at `document_url` is mixed content.
+pub fn is_mixed_content(document_url: &Url, url: &Url) -> bool {
+    let secure_document = match document_url.scheme.as_slice() {
+        "https" | "wss" => true,
+        _ => false,
+    };
+    let insecure_resource = match url.scheme.as_slice() {
+        "http" | "ws" | "ftp" => true,
+        _ => false,
+    };
+    secure_document && insecure_resource
+}
+
+/// Decides whether `load_data` may fetch `url`, which is either its URL or the target of a
+/// redirect, and reports the decision to the console of the requesting document. Returns
+/// `false` if the load must be blocked.
+pub fn allow_load(load_data: &LoadData, url: &Url) -> bool {
+    match load_data.document_url {
+        Some(ref document_url) => {
+            allow_subresource_load(document_url, url, load_data.context, load_data.console.as_ref())
+        }
+        None => true,
+    }
+}
+
+/// Decides whether the document at `document_url` may load `url` as a resource of the given
+/// kind, for loads that do not go through the resource task with a `LoadData` of their own,
+/// such as images, stylesheets, fonts and frames. Returns `false` if the load must be blocked.
+pub fn allow_subresource_load(document_url: &Url, url: &Url, context: LoadContext,
+                              console: Option<&Sender<String>>) -> bool {
+    if !is_mixed_content(document_url, url) {
+        return true;
+    }
+
+    let (allowed, message) = match MixedContentCategory::for_context(context) {
+        MixedContentCategory::Blockable => {
+            (false, format!("Blocked loading mixed active content \"{}\" in the secure document \"{}\".",
+                            url.serialize(), document_url.serialize()))
+        }
+        MixedContentCategory::OptionallyBlockable => {
+            (true, format!("Loading mixed display content \"{}\" in the secure document \"{}\".",
+                           url.serialize(), document_url.serialize()))
+        }
+    };
+    warn!("{}", message);
+    if let Some(console) = console {
+        let _ = console.send(message);
+    }
+    allowed
+}
+
+#[test]
+fn test_is_mixed_content() {
+    let secure = Url::parse("https://servo.org/").unwrap();
+    let insecure = Url::parse("http://servo.org/").unwrap();
+
+    assert!(is_mixed_content(&secure, &Url::parse("http://example.com/a.js").unwrap()));
+    assert!(is_mixed_content(&secure, &Url::parse("ws://example.com/").unwrap()));
+    assert!(!is_mixed_content(&secure, &Url::parse("https://example.com/a.js").unwrap()));
+    assert!(!is_mixed_content(&secure, &Url::parse("data:text/plain,a").unwrap()));
+    assert!(!is_mixed_content(&insecure, &Url::parse("http://example.com/a.js").unwrap()));
+}
+
+#[test]
+fn test_allow_load_blocks_only_blockable_content() {
+    use std::sync::mpsc::channel;
+
+    let (consumer, _) = channel();
+    let (console, messages) = channel();
+    let url = Url::parse("http://example.com/resource").unwrap();
+    let mut load_data = LoadData::new(url.clone(), consumer);
+    load_data.document_url = Some(Url::parse("https://servo.org/").unwrap());
+    load_data.console = Some(console);
+
+    load_data.context = LoadContext::Script;
+    assert!(!allow_load(&load_data, &url));
+    assert!(messages.recv().unwrap().starts_with("Blocked loading mixed active content"));
+
+    load_data.context = LoadContext::Image;
+    assert!(allow_load(&load_data, &url));
+    assert!(messages.recv().unwrap().starts_with("Loading mixed display content"));
+
+    load_data.document_url = None;
+    load_data.context = LoadContext::Script;
+    assert!(allow_load(&load_data, &url));
+    assert!(messages.try_recv().is_err());
+}
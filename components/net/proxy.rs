@@ -0,0 +1,392 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Proxy configuration, and a connector that reaches servers through a proxy.
+//!
+//! Plain HTTP requests are sent to an HTTP proxy as they are, with the whole URL in the request
+//! line. HTTPS requests go through a tunnel opened with `CONNECT`. A SOCKS5 proxy opens a
+//! tunnel for both.
+
+use hyper::net::{HttpConnector, HttpStream, NetworkConnector};
+use openssl::ssl::{SslContext, SslMethod, SslStream};
+use openssl::ssl::error::SslError;
+use url::{Url, UrlParser, SchemeType, whatwg_scheme_type_mapper};
+use util::opts;
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::env;
+use std::mem;
+use std::old_io::{IoError, IoErrorKind, IoResult};
+use std::old_io::net::tcp::TcpStream;
+
+/// The longest response head we accept from a proxy in reply to `CONNECT`.
+const MAX_CONNECT_RESPONSE_LENGTH: usize = 8 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProxyScheme {
+    Http,
+    Socks5,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Proxy {
+    pub scheme: ProxyScheme,
+    pub host: String,
+    pub port: u16,
+}
+
+fn proxy_scheme_type_mapper(scheme: &str) -> SchemeType {
+    match scheme {
+        "socks5" | "socks5h" => SchemeType::Relative(1080),
+        _ => whatwg_scheme_type_mapper(scheme),
+    }
+}
+
+impl Proxy {
+    /// Parses a proxy URL such as `http://proxy:3128` or `socks5://proxy:1080`. A bare
+    /// `host:port` is taken to be an HTTP proxy.
+    pub fn parse(input: &str) -> Option<Proxy> {
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+        let input = if input.contains("://") {
+            input.to_owned()
+        } else {
+            format!("http://{}", input)
+        };
+        let url = match UrlParser::new().scheme_type_mapper(proxy_scheme_type_mapper)
+                                        .parse(input.as_slice()) {
+            Ok(url) => url,
+            Err(_) => return None,
+        };
+        let scheme = match url.scheme.as_slice() {
+            "http" => ProxyScheme::Http,
+            "socks5" | "socks5h" => ProxyScheme::Socks5,
+            _ => return None,
+        };
+        match (url.serialize_host(), url.port_or_default()) {
+            (Some(host), Some(port)) => Some(Proxy {
+                scheme: scheme,
+                host: host,
+                port: port,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Which proxy, if any, to use for each request.
+#[derive(Clone, Default)]
+pub struct ProxyConfig {
+    pub http: Option<Proxy>,
+    pub https: Option<Proxy>,
+    /// Hosts to reach directly. An entry matches the host itself and its subdomains, and `*`
+    /// matches every host.
+    pub no_proxy: Vec<String>,
+}
+
+/// Reads an environment variable, accepting its uppercase spelling too.
+fn env_var(name: &str) -> Option<String> {
+    env::var_string(name)
+        .or_else(|_| env::var_string(name.to_ascii_uppercase().as_slice()))
+        .ok()
+}
+
+impl ProxyConfig {
+    /// Reads the proxy configuration from the command line, falling back to the `http_proxy`,
+    /// `https_proxy` and `no_proxy` environment variables.
+    pub fn from_opts() -> ProxyConfig {
+        let opts = opts::get();
+        let (http, https) = match opts.proxy {
+            Some(ref proxy) => {
+                let proxy = Proxy::parse(proxy.as_slice());
+                (proxy.clone(), proxy)
+            }
+            None => {
+                (env_var("http_proxy").and_then(|proxy| Proxy::parse(proxy.as_slice())),
+                 env_var("https_proxy").and_then(|proxy| Proxy::parse(proxy.as_slice())))
+            }
+        };
+        let no_proxy = match opts.no_proxy {
+            Some(ref no_proxy) => no_proxy.clone(),
+            None => env_var("no_proxy").unwrap_or(String::new()),
+        };
+        ProxyConfig {
+            http: http,
+            https: https,
+            no_proxy: ProxyConfig::parse_no_proxy(no_proxy.as_slice()),
+        }
+    }
+
+    /// Parses a comma-separated `no_proxy` list such as `localhost,.example.com`.
+    pub fn parse_no_proxy(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(|entry| entry.trim().trim_left_matches('.').to_ascii_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .collect()
+    }
+
+    /// Returns the proxy to reach `host` through for a request with the given scheme.
+    pub fn proxy_for(&self, scheme: &str, host: &str) -> Option<&Proxy> {
+        let proxy = match scheme {
+            "http" => self.http.as_ref(),
+            "https" => self.https.as_ref(),
+            _ => None,
+        };
+        if proxy.is_some() && self.bypasses(host) {
+            return None;
+        }
+        proxy
+    }
+
+    fn bypasses(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.no_proxy.iter().any(|entry| {
+            entry.as_slice() == "*" || host == *entry ||
+                host.ends_with(format!(".{}", entry).as_slice())
+        })
+    }
+}
+
+fn proxy_error(desc: &'static str, detail: Option<String>) -> IoError {
+    IoError {
+        kind: IoErrorKind::OtherIoError,
+        desc: desc,
+        detail: detail,
+    }
+}
+
+/// Converts an SSL error the same way hyper does, so that the loader recognises certificate
+/// errors on tunnelled connections too.
+fn lift_ssl_error(ssl: SslError) -> IoError {
+    match ssl {
+        SslError::StreamError(err) => err,
+        SslError::SslSessionClosed => IoError {
+            kind: IoErrorKind::EndOfFile,
+            desc: "SSL Connection Closed",
+            detail: None,
+        },
+        SslError::OpenSslErrors(errs) => proxy_error("Error in OpenSSL", Some(format!("{:?}", errs))),
+    }
+}
+
+/// Asks an HTTP proxy to open a tunnel to `host:port` with `CONNECT`.
+fn open_connect_tunnel(stream: &mut TcpStream, host: &str, port: u16) -> IoResult<()> {
+    try!(write!(stream, "CONNECT {0}:{1} HTTP/1.1\r\nHost: {0}:{1}\r\n\r\n", host, port));
+    try!(stream.flush());
+
+    // Read the response head a byte at a time, so that nothing sent through the tunnel
+    // afterwards is consumed.
+    let mut head = vec!();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > MAX_CONNECT_RESPONSE_LENGTH {
+            return Err(proxy_error("proxy sent an invalid response to CONNECT", None));
+        }
+        head.push(try!(stream.read_byte()));
+    }
+    let head = String::from_utf8_lossy(head.as_slice()).into_owned();
+    let status_line = head.lines().next().unwrap_or("").to_owned();
+    match status_line.split(' ').nth(1) {
+        Some(status) if status.starts_with("2") => Ok(()),
+        _ => Err(proxy_error("proxy refused to open a tunnel", Some(status_line))),
+    }
+}
+
+/// Asks a SOCKS5 proxy to connect to `host:port`, as described in RFC 1928. The host name is
+/// resolved by the proxy.
+fn open_socks5_tunnel(stream: &mut TcpStream, host: &str, port: u16) -> IoResult<()> {
+    if host.len() > 255 {
+        return Err(proxy_error("host name too long for SOCKS5", None));
+    }
+
+    // Version 5, offering only the "no authentication required" method.
+    try!(stream.write_all(&[5, 1, 0]));
+    let reply = try!(stream.read_exact(2));
+    if reply[0] != 5 || reply[1] != 0 {
+        return Err(proxy_error("SOCKS5 proxy requires authentication", None));
+    }
+
+    // CONNECT to a domain name.
+    let mut request = vec!(5, 1, 0, 3, host.len() as u8);
+    request.push_all(host.as_bytes());
+    request.push((port >> 8) as u8);
+    request.push(port as u8);
+    try!(stream.write_all(request.as_slice()));
+
+    let reply = try!(stream.read_exact(4));
+    if reply[0] != 5 || reply[1] != 0 {
+        return Err(proxy_error("SOCKS5 proxy refused the connection",
+                               Some(format!("reply code {}", reply[1]))));
+    }
+    // Skip the address the proxy bound, and its port.
+    let address_length = match reply[3] {
+        1 => 4,
+        3 => try!(stream.read_byte()) as usize,
+        4 => 16,
+        _ => return Err(proxy_error("SOCKS5 proxy sent an invalid reply", None)),
+    };
+    try!(stream.read_exact(address_length + 2));
+    Ok(())
+}
+
+/// A hyper connector that goes through the configured proxy, if any.
+pub struct ProxyConnector<'v> {
+    config: ProxyConfig,
+    /// Opens direct connections. Its verifier also checks the certificates of servers reached
+    /// through a tunnel.
+    connector: HttpConnector<'v>,
+}
+
+impl<'v> ProxyConnector<'v> {
+    pub fn new(config: ProxyConfig, connector: HttpConnector<'v>) -> ProxyConnector<'v> {
+        ProxyConnector {
+            config: config,
+            connector: connector,
+        }
+    }
+
+    /// If requests to `host` are sent to an HTTP proxy as they are, returns the origin to put
+    /// in front of the path in their request line.
+    pub fn forward_proxy_origin(&self, host: &str, port: u16, scheme: &str) -> Option<String> {
+        match self.config.proxy_for(scheme, host) {
+            Some(proxy) if proxy.scheme == ProxyScheme::Http && scheme == "http" => {
+                Some(if port == 80 {
+                    format!("http://{}", host)
+                } else {
+                    format!("http://{}:{}", host, port)
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn wrap_ssl(&mut self, stream: TcpStream) -> IoResult<HttpStream> {
+        let mut context = try!(SslContext::new(SslMethod::Sslv23).map_err(lift_ssl_error));
+        if let Some(ref mut verifier) = self.connector.0 {
+            (*verifier)(&mut context);
+        }
+        let stream = try!(SslStream::new(&context, stream).map_err(lift_ssl_error));
+        Ok(HttpStream::Https(stream))
+    }
+}
+
+impl<'v> NetworkConnector<HttpStream> for ProxyConnector<'v> {
+    fn connect(&mut self, host: &str, port: u16, scheme: &str) -> IoResult<HttpStream> {
+        let proxy = match self.config.proxy_for(scheme, host) {
+            Some(proxy) => proxy.clone(),
+            None => return self.connector.connect(host, port, scheme),
+        };
+        debug!("connecting to {}:{} through proxy {:?}", host, port, proxy);
+
+        let mut stream = try!(TcpStream::connect((proxy.host.as_slice(), proxy.port)));
+        match (proxy.scheme, scheme) {
+            (ProxyScheme::Http, "http") => return Ok(HttpStream::Http(stream)),
+            (ProxyScheme::Http, _) => try!(open_connect_tunnel(&mut stream, host, port)),
+            (ProxyScheme::Socks5, _) => try!(open_socks5_tunnel(&mut stream, host, port)),
+        }
+        match scheme {
+            "http" => Ok(HttpStream::Http(stream)),
+            "https" => self.wrap_ssl(stream),
+            _ => Err(IoError {
+                kind: IoErrorKind::InvalidInput,
+                desc: "Invalid scheme for Http",
+                detail: None,
+            }),
+        }
+    }
+}
+
+/// Rewrites the request line of a request sent to an HTTP proxy so that it names the whole
+/// URL (`GET http://host/path HTTP/1.1`) rather than only the path.
+#[derive(Clone)]
+pub struct AbsoluteFormWriter {
+    /// The origin to insert, until the request line has been written.
+    origin: Option<String>,
+    /// The start of the request line, buffered until it is complete.
+    line: Vec<u8>,
+}
+
+impl AbsoluteFormWriter {
+    pub fn new(origin: Option<String>) -> AbsoluteFormWriter {
+        AbsoluteFormWriter {
+            origin: origin,
+            line: vec!(),
+        }
+    }
+
+    pub fn write_all<W: Writer>(&mut self, writer: &mut W, buf: &[u8]) -> IoResult<()> {
+        if self.origin.is_none() {
+            return writer.write_all(buf);
+        }
+        self.line.push_all(buf);
+        if !self.line.windows(2).any(|pair| pair[0] == b'\r' && pair[1] == b'\n') {
+            return Ok(());
+        }
+
+        let origin = self.origin.take().unwrap();
+        let line = mem::replace(&mut self.line, vec!());
+        match line.iter().position(|&byte| byte == b' ') {
+            Some(space) => {
+                try!(writer.write_all(&line[..space + 1]));
+                try!(writer.write_all(origin.as_bytes()));
+                writer.write_all(&line[space + 1..])
+            }
+            None => writer.write_all(line.as_slice()),
+        }
+    }
+}
+
+#[test]
+fn test_parse_proxy() {
+    assert_eq!(Proxy::parse("http://proxy.example.com:3128"), Some(Proxy {
+        scheme: ProxyScheme::Http,
+        host: "proxy.example.com".to_owned(),
+        port: 3128,
+    }));
+    assert_eq!(Proxy::parse("proxy.example.com:3128").unwrap().scheme, ProxyScheme::Http);
+    assert_eq!(Proxy::parse("http://proxy.example.com").unwrap().port, 80);
+    assert_eq!(Proxy::parse("socks5://127.0.0.1").unwrap(), Proxy {
+        scheme: ProxyScheme::Socks5,
+        host: "127.0.0.1".to_owned(),
+        port: 1080,
+    });
+    assert_eq!(Proxy::parse("ftp://proxy.example.com"), None);
+    assert_eq!(Proxy::parse(""), None);
+}
+
+#[test]
+fn test_no_proxy() {
+    let proxy = Proxy::parse("http://proxy.example.com:3128");
+    let config = ProxyConfig {
+        http: proxy.clone(),
+        https: None,
+        no_proxy: ProxyConfig::parse_no_proxy(" localhost, .internal.example.com,"),
+    };
+    assert_eq!(config.proxy_for("http", "servo.org"), proxy.as_ref());
+    assert_eq!(config.proxy_for("https", "servo.org"), None);
+    assert_eq!(config.proxy_for("http", "localhost"), None);
+    assert_eq!(config.proxy_for("http", "internal.example.com"), None);
+    assert_eq!(config.proxy_for("http", "www.Internal.Example.com"), None);
+    assert_eq!(config.proxy_for("http", "notinternal.example.com"), proxy.as_ref());
+
+    let config = ProxyConfig {
+        http: proxy,
+        https: None,
+        no_proxy: ProxyConfig::parse_no_proxy("*"),
+    };
+    assert_eq!(config.proxy_for("http", "servo.org"), None);
+}
+
+#[test]
+fn test_absolute_form_writer() {
+    let mut writer = AbsoluteFormWriter::new(Some("http://servo.org".to_owned()));
+    let mut out = vec!();
+    writer.write_all(&mut out, b"GET /index").unwrap();
+    assert!(out.is_empty());
+    writer.write_all(&mut out, b".html HTTP/1.1\r\nHost: servo.org\r\n").unwrap();
+    writer.write_all(&mut out, b"\r\n").unwrap();
+    assert_eq!(out, b"GET http://servo.org/index.html HTTP/1.1\r\nHost: servo.org\r\n\r\n".to_vec());
+}
@@ -5,14 +5,23 @@
 //! A task that takes a URL and streams back the binary data.
 
 use about_loader;
+use connection_pool::{ConnectionPool, ConnectionPoolMetrics};
 use data_loader;
 use file_loader;
+use hsts::{HstsList, preload_hsts_domains};
+use http_cache::HttpCache;
 use http_loader;
+use image_cache_task::ImageCacheTask;
+use mixed_content;
+use proxy::ProxyConfig;
 use sniffer_task;
 use sniffer_task::SnifferTask;
+use websocket_loader;
+use websocket_loader::{WebSocketConnectData, WebSocketDomAction, WebSocketNetworkEvent};
 use cookie_storage::{CookieStorage, CookieSource};
 use cookie;
 
+use util::opts;
 use util::task::spawn_named;
 
 use hyper::header::UserAgent;
@@ -22,11 +31,13 @@ use hyper::method::Method;
 use hyper::mime::{Mime, Attr};
 use url::Url;
 
+use std::ascii::AsciiExt;
 use std::borrow::{ToOwned, IntoCow};
 use std::collections::HashMap;
 use std::env;
 use std::mem;
 use std::old_io::{BufferedReader, File};
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thunk::Invoke;
 
@@ -34,6 +45,8 @@ use std::thunk::Invoke;
 use std::old_io::{Listener, Acceptor, TimedOut};
 #[cfg(test)]
 use std::old_io::net::tcp::TcpListener;
+#[cfg(test)]
+use proxy::{Proxy, ProxyScheme};
 
 static mut HOST_TABLE: Option<*mut HashMap<String, String>> = None;
 
@@ -58,9 +71,30 @@ pub enum ControlMsg {
     SetCookiesForUrl(Url, String, CookieSource),
     /// Retrieve the stored cookies for a given URL
     GetCookiesForUrl(Url, Sender<Option<String>>, CookieSource),
+    /// Retrieve the activity counters of the HTTP connection pool
+    GetConnectionPoolMetrics(Sender<ConnectionPoolMetrics>),
+    /// Set the image cache whose contents about:cache lists
+    SetImageCache(ImageCacheTask),
+    /// Open a WebSocket connection, reporting its events to the sender and taking the
+    /// messages to send from the receiver
+    WebSocketConnect(WebSocketConnectData, Sender<WebSocketNetworkEvent>, Receiver<WebSocketDomAction>),
     Exit
 }
 
+/// The kind of resource being loaded, which decides how mixed content is treated.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoadContext {
+    /// A document, in a window or a frame.
+    Browsing,
+    Image,
+    AudioVideo,
+    Script,
+    Style,
+    Font,
+    /// A connection opened by script, such as with `XMLHttpRequest`.
+    Connect,
+}
+
 #[derive(Clone)]
 pub struct LoadData {
     pub url: Url,
@@ -70,8 +104,18 @@ pub struct LoadData {
     /// Headers that will apply to the initial request and any redirects
     pub preserved_headers: Headers,
     pub data: Option<Vec<u8>>,
-    pub cors: Option<ResourceCORSData>,
+    /// Whether redirects are followed. When they aren't, a redirect response is handed to
+    /// the consumer like any other, as the fetch algorithm handles redirects itself.
+    pub follow_redirects: bool,
+    /// Whether cookies are sent with the request and stored from the response
+    pub credentials: bool,
     pub consumer: Sender<LoadResponse>,
+    pub context: LoadContext,
+    /// The URL of the document that requested this load, if any, for mixed content checks
+    pub document_url: Option<Url>,
+    /// Where to report messages about this load, such as mixed content being blocked, for
+    /// the console of the requesting document
+    pub console: Option<Sender<String>>,
 }
 
 impl LoadData {
@@ -82,20 +126,16 @@ impl LoadData {
             headers: Headers::new(),
             preserved_headers: Headers::new(),
             data: None,
-            cors: None,
+            follow_redirects: true,
+            credentials: true,
             consumer: consumer,
+            context: LoadContext::Browsing,
+            document_url: None,
+            console: None,
         }
     }
 }
 
-#[derive(Clone)]
-pub struct ResourceCORSData {
-    /// CORS Preflight flag
-    pub preflight: bool,
-    /// Origin of CORS Request
-    pub origin: Url
-}
-
 /// Metadata about a loaded resource, such as is obtained from HTTP headers.
 #[derive(Clone)]
 pub struct Metadata {
@@ -202,6 +242,24 @@ pub fn load_whole_resource(resource_task: &ResourceTask, url: Url)
         -> Result<(Metadata, Vec<u8>), String> {
     let (start_chan, start_port) = channel();
     resource_task.send(ControlMsg::Load(LoadData::new(url, start_chan))).unwrap();
+    receive_whole_resource(start_port)
+}
+
+/// Like `load_whole_resource`, for a resource used by the document at `document_url`.
+pub fn load_whole_subresource(resource_task: &ResourceTask, url: Url, context: LoadContext,
+                              document_url: Url, console: Option<Sender<String>>)
+        -> Result<(Metadata, Vec<u8>), String> {
+    let (start_chan, start_port) = channel();
+    let mut load_data = LoadData::new(url, start_chan);
+    load_data.context = context;
+    load_data.document_url = Some(document_url);
+    load_data.console = console;
+    resource_task.send(ControlMsg::Load(load_data)).unwrap();
+    receive_whole_resource(start_port)
+}
+
+fn receive_whole_resource(start_port: Receiver<LoadResponse>)
+        -> Result<(Metadata, Vec<u8>), String> {
     let response = start_port.recv().unwrap();
 
     let mut buf = vec!();
@@ -217,13 +275,58 @@ pub fn load_whole_resource(resource_task: &ResourceTask, url: Url)
 /// Handle to a resource task
 pub type ResourceTask = Sender<ControlMsg>;
 
+/// A loader for a URL scheme that is not built into the resource task, such as an embedder's
+/// `app:` scheme for bundled assets.
+pub trait ProtocolHandler {
+    /// Starts loading `load_data`. Like the built-in loaders, the handler must answer through
+    /// `start_sending` with `start_chan` as the immediate consumer, and should do any blocking
+    /// work on its own task, since it is invoked on the resource task.
+    fn load(&self, load_data: LoadData, start_chan: Sender<TargetedLoadResponse>);
+}
+
+impl ProtocolHandler for fn(LoadData, Sender<TargetedLoadResponse>) {
+    fn load(&self, load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
+        (*self)(load_data, start_chan)
+    }
+}
+
+/// The protocol handlers registered by an embedder, keyed by URL scheme.
+pub struct ProtocolRegistry {
+    handlers: HashMap<String, Box<ProtocolHandler + Send>>,
+}
+
+impl ProtocolRegistry {
+    pub fn new() -> ProtocolRegistry {
+        ProtocolRegistry {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers `handler` for URLs with the given scheme, returning the handler it replaces.
+    /// Registered handlers take precedence over the built-in loaders.
+    pub fn register(&mut self, scheme: &str, handler: Box<ProtocolHandler + Send>)
+                    -> Option<Box<ProtocolHandler + Send>> {
+        self.handlers.insert(scheme.to_ascii_lowercase(), handler)
+    }
+
+    fn get<'a>(&'a self, scheme: &str) -> Option<&'a (ProtocolHandler + Send)> {
+        self.handlers.get(scheme).map(|handler| &**handler)
+    }
+}
+
 /// Create a ResourceTask
-pub fn new_resource_task(user_agent: Option<String>) -> ResourceTask {
+pub fn new_resource_task(user_agent: Option<String>, protocols: ProtocolRegistry) -> ResourceTask {
+    start_resource_task(user_agent, protocols, ProxyConfig::from_opts())
+}
+
+fn start_resource_task(user_agent: Option<String>, protocols: ProtocolRegistry,
+                       proxies: ProxyConfig) -> ResourceTask {
     let (setup_chan, setup_port) = channel();
     let sniffer_task = sniffer_task::new_sniffer_task();
     let setup_chan_clone = setup_chan.clone();
     spawn_named("ResourceManager".to_owned(), move || {
-        ResourceManager::new(setup_port, user_agent, sniffer_task, setup_chan_clone).start();
+        ResourceManager::new(setup_port, user_agent, sniffer_task, setup_chan_clone,
+                             protocols, proxies).start();
     });
     setup_chan
 }
@@ -257,17 +360,40 @@ struct ResourceManager {
     sniffer_task: SnifferTask,
     cookie_storage: CookieStorage,
     resource_task: Sender<ControlMsg>,
+    /// The HTTP cache, shared by all HTTP loads.
+    http_cache: Arc<Mutex<HttpCache>>,
+    /// Keep-alive connections, shared by all HTTP loads.
+    connection_pool: ConnectionPool,
+    /// The proxies HTTP loads go through.
+    proxies: ProxyConfig,
+    /// The image cache, for about:cache.
+    image_cache: Option<ImageCacheTask>,
+    /// The hosts whose `http:` URLs are upgraded to `https:`.
+    hsts_list: Arc<Mutex<HstsList>>,
+    /// Loaders for schemes registered by the embedder.
+    protocols: ProtocolRegistry,
 }
 
 impl ResourceManager {
     fn new(from_client: Receiver<ControlMsg>, user_agent: Option<String>, sniffer_task: SnifferTask,
-           resource_task: Sender<ControlMsg>) -> ResourceManager {
+           resource_task: Sender<ControlMsg>, protocols: ProtocolRegistry,
+           proxies: ProxyConfig) -> ResourceManager {
         ResourceManager {
             from_client: from_client,
             user_agent: user_agent,
             sniffer_task: sniffer_task,
-            cookie_storage: CookieStorage::new(),
+            cookie_storage: match opts::get().cookie_jar_path {
+                Some(ref path) => CookieStorage::new_with_jar(Path::new(path)),
+                None => CookieStorage::new(),
+            },
             resource_task: resource_task,
+            http_cache: Arc::new(Mutex::new(HttpCache::new(
+                opts::get().http_cache_path.as_ref().map(|path| Path::new(path))))),
+            connection_pool: ConnectionPool::new(opts::get().max_connections_per_host),
+            proxies: proxies,
+            image_cache: None,
+            hsts_list: Arc::new(Mutex::new(preload_hsts_domains())),
+            protocols: protocols,
         }
     }
 }
@@ -293,6 +419,17 @@ impl ResourceManager {
               ControlMsg::GetCookiesForUrl(url, consumer, source) => {
                 consumer.send(self.cookie_storage.cookies_for_url(&url, source)).unwrap();
               }
+              ControlMsg::GetConnectionPoolMetrics(consumer) => {
+                consumer.send(self.connection_pool.metrics()).unwrap();
+              }
+              ControlMsg::SetImageCache(image_cache) => {
+                self.image_cache = Some(image_cache);
+              }
+              ControlMsg::WebSocketConnect(connect_data, event_sender, action_receiver) => {
+                websocket_loader::connect(connect_data, event_sender, action_receiver,
+                                          self.resource_task.clone(), self.proxies.clone(),
+                                          self.user_agent.clone());
+              }
               ControlMsg::Exit => {
                 break
               }
@@ -313,6 +450,21 @@ impl ResourceManager {
             eventual_consumer: load_data.consumer.clone(),
         };
 
+        // Redirects are checked again by the HTTP loader.
+        load_data.url = self.hsts_list.lock().unwrap().upgrade_url(load_data.url);
+        if !mixed_content::allow_load(&load_data, &load_data.url) {
+            start_sending(senders, Metadata::default(load_data.url))
+                .send(ProgressMsg::Done(Err("blocked as mixed content".to_string()))).unwrap();
+            return
+        }
+
+        if let Some(handler) = self.protocols.get(load_data.url.scheme.as_slice()) {
+            debug!("resource_task: loading url with a registered handler: {}",
+                   load_data.url.serialize());
+            handler.load(load_data, self.sniffer_task.clone());
+            return
+        }
+
         fn from_factory(factory: fn(LoadData, Sender<TargetedLoadResponse>))
                         -> Box<Invoke<(LoadData, Sender<TargetedLoadResponse>)> + Send> {
             box move |&:(load_data, start_chan)| {
@@ -322,9 +474,11 @@ impl ResourceManager {
 
         let loader = match load_data.url.scheme.as_slice() {
             "file" => from_factory(file_loader::factory),
-            "http" | "https" => http_loader::factory(self.resource_task.clone()),
+            "http" | "https" => http_loader::factory(self.resource_task.clone(), self.http_cache.clone(),
+                                                     self.connection_pool.clone(), self.proxies.clone(),
+                                                     self.hsts_list.clone()),
             "data" => from_factory(data_loader::factory),
-            "about" => from_factory(about_loader::factory),
+            "about" => about_loader::factory(self.http_cache.clone(), self.image_cache.clone()),
             _ => {
                 debug!("resource_task: no loader for scheme {}", load_data.url.scheme);
                 start_sending(senders, Metadata::default(load_data.url))
@@ -348,6 +502,22 @@ pub fn load_bytes_iter(resource_task: &ResourceTask, url: Url) -> (Metadata, Pro
     (response.metadata, iter)
 }
 
+/// Like `load_bytes_iter`, for a resource used by the document at `document_url`.
+pub fn load_subresource_bytes_iter(resource_task: &ResourceTask, url: Url, context: LoadContext,
+                                   document_url: Url, console: Option<Sender<String>>)
+                                   -> (Metadata, ProgressMsgPortIterator) {
+    let (input_chan, input_port) = channel();
+    let mut load_data = LoadData::new(url, input_chan);
+    load_data.context = context;
+    load_data.document_url = Some(document_url);
+    load_data.console = console;
+    resource_task.send(ControlMsg::Load(load_data)).unwrap();
+
+    let response = input_port.recv().unwrap();
+    let iter = ProgressMsgPortIterator { progress_port: response.progress_port };
+    (response.metadata, iter)
+}
+
 /// Iterator that reads chunks of bytes from a ProgressMsg port
 pub struct ProgressMsgPortIterator {
     progress_port: Receiver<ProgressMsg>
@@ -370,13 +540,13 @@ impl Iterator for ProgressMsgPortIterator {
 
 #[test]
 fn test_exit() {
-    let resource_task = new_resource_task(None);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
     resource_task.send(ControlMsg::Exit);
 }
 
 #[test]
 fn test_bad_scheme() {
-    let resource_task = new_resource_task(None);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
     let (start_chan, start) = channel();
     let url = Url::parse("bogus://whatever").unwrap();
     resource_task.send(ControlMsg::Load(LoadData::new(url, start_chan)));
@@ -388,6 +558,42 @@ fn test_bad_scheme() {
     resource_task.send(ControlMsg::Exit);
 }
 
+#[cfg(test)]
+fn app_loader(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
+    let senders = ResponseSenders {
+        immediate_consumer: start_chan,
+        eventual_consumer: load_data.consumer,
+    };
+    let mut metadata = Metadata::default(load_data.url.clone());
+    metadata.set_content_type(Some(&"text/plain".parse().unwrap()));
+    let progress_chan = start_sending(senders, metadata);
+    progress_chan.send(ProgressMsg::Payload(load_data.url.serialize().into_bytes())).unwrap();
+    progress_chan.send(ProgressMsg::Done(Ok(()))).unwrap();
+}
+
+#[test]
+fn test_registered_protocol_handler() {
+    let mut protocols = ProtocolRegistry::new();
+    assert!(protocols.register("APP", box (app_loader as fn(LoadData, Sender<TargetedLoadResponse>)))
+                     .is_none());
+    let resource_task = new_resource_task(None, protocols);
+
+    let url = Url::parse("app://bundle/assets/index.html").unwrap();
+    let (metadata, body) = load_whole_resource(&resource_task, url).unwrap();
+    assert_eq!(metadata.content_type, Some(("text".to_owned(), "plain".to_owned())));
+    assert_eq!(body, b"app://bundle/assets/index.html".to_vec());
+
+    let (start_chan, start) = channel();
+    let url = Url::parse("unregistered://whatever").unwrap();
+    resource_task.send(ControlMsg::Load(LoadData::new(url, start_chan))).unwrap();
+    let response = start.recv().unwrap();
+    match response.progress_port.recv().unwrap() {
+        ProgressMsg::Done(result) => assert!(result.is_err()),
+        _ => panic!("expected a network error for an unregistered scheme"),
+    }
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
 #[test]
 fn test_parse_hostfile() {
     let mock_host_file_content = "127.0.0.1 foo.bar.com\n127.0.0.2 servo.test.server";
@@ -413,7 +619,7 @@ fn test_replace_hosts() {
     let mut acceptor = listener.listen().unwrap();
 
     //Start the resource task and make a request to our TCP server
-    let resource_task = new_resource_task(None);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
     let (start_chan, _) = channel();
     let mut raw_url: String = "http://foo.bar.com:".to_string();
     raw_url = raw_url + port.to_string().as_slice();
@@ -429,3 +635,392 @@ fn test_replace_hosts() {
     resource_task.send(ControlMsg::Exit);
     drop(acceptor);
 }
+
+/// Starts a local HTTP server that answers each connection with the next of `responses`, and
+/// returns its port along with a port on which the raw requests it received are sent.
+#[cfg(test)]
+pub fn spawn_test_server(responses: Vec<Vec<u8>>) -> (u16, Receiver<String>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.socket_name().unwrap().port;
+    let mut acceptor = listener.listen().unwrap();
+    let (request_chan, request_port) = channel();
+    spawn_named("TestHttpServer".to_owned(), move || {
+        for response in responses.into_iter() {
+            let mut stream = match acceptor.accept() {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+            let mut request = String::new();
+            {
+                let mut reader = BufferedReader::new(stream.clone());
+                loop {
+                    match reader.read_line() {
+                        Ok(ref line) if line.as_slice() != "\r\n" => request.push_str(line.as_slice()),
+                        _ => break,
+                    }
+                }
+            }
+            if request_chan.send(request).is_err() {
+                return;
+            }
+            stream.write_all(response.as_slice()).unwrap();
+            drop(stream);
+        }
+    });
+    (port, request_port)
+}
+
+#[cfg(test)]
+fn load_test_url(resource_task: &ResourceTask, port: u16, path: &str) -> (Metadata, Vec<u8>) {
+    let url = Url::parse(format!("http://127.0.0.1:{}{}", port, path).as_slice()).unwrap();
+    load_whole_resource(resource_task, url).unwrap()
+}
+
+#[test]
+fn test_http_cache_fresh_response_is_reused() {
+    let response = "HTTP/1.1 200 OK\r\nCache-Control: max-age=3600\r\n\
+                    Content-Length: 5\r\nConnection: close\r\n\r\nhello".as_bytes().to_vec();
+    let (port, requests) = spawn_test_server(vec![response]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    let (_, body) = load_test_url(&resource_task, port, "/fresh");
+    assert_eq!(body, b"hello".to_vec());
+    requests.recv().unwrap();
+
+    let (_, body) = load_test_url(&resource_task, port, "/fresh");
+    assert_eq!(body, b"hello".to_vec());
+    assert!(requests.try_recv().is_err(), "the second load should not hit the network");
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_http_cache_revalidates_with_etag() {
+    let first = "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\n\
+                 Content-Length: 5\r\nConnection: close\r\n\r\nhello".as_bytes().to_vec();
+    let second = "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\
+                  Connection: close\r\n\r\n".as_bytes().to_vec();
+    let (port, requests) = spawn_test_server(vec![first, second]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    let (_, body) = load_test_url(&resource_task, port, "/etag");
+    assert_eq!(body, b"hello".to_vec());
+    assert!(!requests.recv().unwrap().contains("If-None-Match"));
+
+    let (metadata, body) = load_test_url(&resource_task, port, "/etag");
+    assert_eq!(body, b"hello".to_vec());
+    assert_eq!(metadata.status.unwrap().0, 200);
+    assert!(requests.recv().unwrap().contains("If-None-Match: \"v1\""));
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_http_cache_revalidates_with_last_modified() {
+    let first = "HTTP/1.1 200 OK\r\nLast-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\n\
+                 Cache-Control: max-age=0\r\nContent-Length: 5\r\n\
+                 Connection: close\r\n\r\nhello".as_bytes().to_vec();
+    let second = "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".as_bytes().to_vec();
+    let (port, requests) = spawn_test_server(vec![first, second]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    load_test_url(&resource_task, port, "/modified");
+    requests.recv().unwrap();
+
+    let (_, body) = load_test_url(&resource_task, port, "/modified");
+    assert_eq!(body, b"hello".to_vec());
+    assert!(requests.recv().unwrap()
+                    .contains("If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT"));
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[cfg(test)]
+fn encoded_response(encoding: &str, body: &[u8]) -> Vec<u8> {
+    use flate2::Compression;
+    use flate2::writer::{GzEncoder, ZlibEncoder};
+
+    let encoded = match encoding {
+        "gzip" => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::Default);
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }
+        "deflate" => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }
+        _ => panic!("unknown encoding"),
+    };
+    let mut response = format!("HTTP/1.1 200 OK\r\nContent-Encoding: {}\r\n\
+                                Content-Length: {}\r\nConnection: close\r\n\r\n",
+                               encoding, encoded.len()).into_bytes();
+    response.push_all(encoded.as_slice());
+    response
+}
+
+#[test]
+fn test_gzip_content_encoding_is_decoded() {
+    let (port, requests) = spawn_test_server(vec![encoded_response("gzip", b"hello gzip")]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    let (metadata, body) = load_test_url(&resource_task, port, "/gzip");
+    assert_eq!(body, b"hello gzip".to_vec());
+    assert!(requests.recv().unwrap().contains("Accept-Encoding: gzip, deflate"));
+    let headers = metadata.headers.unwrap();
+    assert_eq!(headers.get_raw("content-encoding").unwrap(), [b"gzip".to_vec()].as_slice());
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_content_encoding_of_empty_responses_is_ignored() {
+    let no_content = "HTTP/1.1 204 No Content\r\nContent-Encoding: gzip\r\n\
+                      Connection: close\r\n\r\n".as_bytes().to_vec();
+    let empty = "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 0\r\n\
+                 Connection: close\r\n\r\n".as_bytes().to_vec();
+    let (port, _requests) = spawn_test_server(vec![no_content, empty]);
+    let resource_task = new_resource_task(None);
+
+    let url = Url::parse(format!("http://127.0.0.1:{}/no-content", port).as_slice()).unwrap();
+    let (metadata, body) = load_whole_resource(&resource_task, url).unwrap();
+    assert_eq!(metadata.status.unwrap().0, 204);
+    assert!(body.is_empty());
+
+    let url = Url::parse(format!("http://127.0.0.1:{}/empty", port).as_slice()).unwrap();
+    let (_, body) = load_whole_resource(&resource_task, url).unwrap();
+    assert!(body.is_empty());
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_deflate_content_encoding_is_decoded() {
+    let (port, _requests) = spawn_test_server(vec![encoded_response("deflate", b"hello deflate")]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    let (_, body) = load_test_url(&resource_task, port, "/deflate");
+    assert_eq!(body, b"hello deflate".to_vec());
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+/// Starts a local HTTP server that keeps connections open and answers every request on them
+/// with `response`. Returns its port along with a port on which a message is sent for each
+/// accepted connection.
+#[cfg(test)]
+fn spawn_keep_alive_test_server(response: Vec<u8>) -> (u16, Receiver<()>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.socket_name().unwrap().port;
+    let mut acceptor = listener.listen().unwrap();
+    let (connection_chan, connection_port) = channel();
+    spawn_named("TestKeepAliveServer".to_owned(), move || {
+        for stream in acceptor.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+            if connection_chan.send(()).is_err() {
+                return;
+            }
+            let response = response.clone();
+            spawn_named("TestKeepAliveConnection".to_owned(), move || {
+                let mut reader = BufferedReader::new(stream.clone());
+                loop {
+                    loop {
+                        match reader.read_line() {
+                            Ok(ref line) if line.as_slice() != "\r\n" => {}
+                            Ok(_) => break,
+                            Err(_) => return,
+                        }
+                    }
+                    if stream.write_all(response.as_slice()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (port, connection_port)
+}
+
+#[cfg(test)]
+fn get_connection_pool_metrics(resource_task: &ResourceTask) -> ConnectionPoolMetrics {
+    let (metrics_chan, metrics_port) = channel();
+    resource_task.send(ControlMsg::GetConnectionPoolMetrics(metrics_chan)).unwrap();
+    metrics_port.recv().unwrap()
+}
+
+#[test]
+fn test_keep_alive_connection_is_reused() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".as_bytes().to_vec();
+    let (port, connections) = spawn_keep_alive_test_server(response);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    for _ in range(0u, 3) {
+        let (_, body) = load_test_url(&resource_task, port, "/keep-alive");
+        assert_eq!(body, b"hello".to_vec());
+    }
+
+    connections.recv().unwrap();
+    assert!(connections.try_recv().is_err(), "all loads should share one connection");
+    let metrics = get_connection_pool_metrics(&resource_task);
+    assert_eq!(metrics.connections_opened, 1);
+    assert_eq!(metrics.connections_reused, 2);
+    assert_eq!(metrics.active_connections, 0);
+    assert_eq!(metrics.idle_connections, 1);
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_connection_close_is_not_reused() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\
+                    Connection: close\r\n\r\nhello".as_bytes().to_vec();
+    let (port, _requests) = spawn_test_server(vec![response.clone(), response]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    load_test_url(&resource_task, port, "/close");
+    load_test_url(&resource_task, port, "/close");
+
+    let metrics = get_connection_pool_metrics(&resource_task);
+    assert_eq!(metrics.connections_opened, 2);
+    assert_eq!(metrics.connections_reused, 0);
+    assert_eq!(metrics.idle_connections, 0);
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[cfg(test)]
+fn new_proxied_resource_task(scheme: ProxyScheme, port: u16) -> ResourceTask {
+    let proxy = Some(Proxy {
+        scheme: scheme,
+        host: "127.0.0.1".to_owned(),
+        port: port,
+    });
+    let proxies = ProxyConfig {
+        http: proxy.clone(),
+        https: proxy,
+        no_proxy: vec!(),
+    };
+    start_resource_task(None, ProtocolRegistry::new(), proxies)
+}
+
+#[test]
+fn test_http_load_through_forward_proxy() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\
+                    Connection: close\r\n\r\nproxied".as_bytes().to_vec();
+    let (port, requests) = spawn_test_server(vec![response]);
+    let resource_task = new_proxied_resource_task(ProxyScheme::Http, port);
+
+    let url = Url::parse("http://servo.invalid:8000/forward").unwrap();
+    let (_, body) = load_whole_resource(&resource_task, url).unwrap();
+    assert_eq!(body, b"proxied".to_vec());
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("GET http://servo.invalid:8000/forward HTTP/1.1\r\n"));
+    assert!(request.contains("Host: servo.invalid:8000"));
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_https_load_opens_connect_tunnel() {
+    // The stand-in accepts the tunnel but closes it, so the TLS handshake fails.
+    let response = "HTTP/1.1 200 Connection established\r\n\r\n".as_bytes().to_vec();
+    let (port, requests) = spawn_test_server(vec![response]);
+    let resource_task = new_proxied_resource_task(ProxyScheme::Http, port);
+
+    let url = Url::parse("https://servo.invalid/tunnel").unwrap();
+    assert!(load_whole_resource(&resource_task, url).is_err());
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("CONNECT servo.invalid:443 HTTP/1.1\r\n"));
+    assert!(request.contains("Host: servo.invalid:443"));
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+/// Starts a SOCKS5 proxy stand-in that accepts one connection and answers the HTTP request
+/// sent through it with `response`. The host and port the client asked for are sent on the
+/// returned receiver.
+#[cfg(test)]
+fn spawn_socks5_test_server(response: Vec<u8>) -> (u16, Receiver<(String, u16)>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.socket_name().unwrap().port;
+    let mut acceptor = listener.listen().unwrap();
+    let (target_chan, target_port) = channel();
+    spawn_named("TestSocks5Server".to_owned(), move || {
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.read_exact(3).unwrap(), vec!(5, 1, 0));
+        stream.write_all(&[5, 0]).unwrap();
+
+        let request = stream.read_exact(5).unwrap();
+        assert_eq!(request.slice_to(4), [5, 1, 0, 3].as_slice());
+        let host = stream.read_exact(request[4] as usize).unwrap();
+        let target_port = stream.read_exact(2).unwrap();
+        target_chan.send((String::from_utf8(host).unwrap(),
+                          (target_port[0] as u16) << 8 | target_port[1] as u16)).unwrap();
+        stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).unwrap();
+
+        let mut reader = BufferedReader::new(stream.clone());
+        loop {
+            match reader.read_line() {
+                Ok(ref line) if line.as_slice() != "\r\n" => {}
+                _ => break,
+            }
+        }
+        stream.write_all(response.as_slice()).unwrap();
+    });
+    (port, target_port)
+}
+
+#[test]
+fn test_http_load_through_socks5_proxy() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\
+                    Connection: close\r\n\r\nsocks".as_bytes().to_vec();
+    let (port, targets) = spawn_socks5_test_server(response);
+    let resource_task = new_proxied_resource_task(ProxyScheme::Socks5, port);
+
+    let url = Url::parse("http://servo.invalid:8000/socks").unwrap();
+    let (_, body) = load_whole_resource(&resource_task, url).unwrap();
+    assert_eq!(body, b"socks".to_vec());
+    assert_eq!(targets.recv().unwrap(), ("servo.invalid".to_owned(), 8000));
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_mixed_content_script_is_blocked() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".as_bytes().to_vec();
+    let (port, requests) = spawn_test_server(vec![response]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    let url = Url::parse(format!("http://127.0.0.1:{}/script.js", port).as_slice()).unwrap();
+    let document_url = Url::parse("https://servo.org/").unwrap();
+    let (console, messages) = channel();
+    let result = load_whole_subresource(&resource_task, url, LoadContext::Script, document_url,
+                                        Some(console));
+    assert_eq!(result.err(), Some("blocked as mixed content".to_string()));
+    assert!(messages.recv().unwrap().starts_with("Blocked loading mixed active content"));
+    assert!(requests.try_recv().is_err());
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_mixed_content_image_is_loaded_with_a_warning() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nimage".as_bytes().to_vec();
+    let (port, requests) = spawn_test_server(vec![response]);
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+
+    let url = Url::parse(format!("http://127.0.0.1:{}/image.png", port).as_slice()).unwrap();
+    let document_url = Url::parse("https://servo.org/").unwrap();
+    let (console, messages) = channel();
+    let result = load_whole_subresource(&resource_task, url, LoadContext::Image, document_url,
+                                        Some(console));
+    assert_eq!(result.map(|(_, body)| body), Ok(b"image".to_vec()));
+    assert!(messages.recv().unwrap().starts_with("Loading mixed display content"));
+    assert!(requests.try_recv().is_ok());
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
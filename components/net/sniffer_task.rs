@@ -3,9 +3,16 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 //! A task that sniffs data
+use mime_classifier;
+use mime_classifier::MAX_RESOURCE_HEADER_LENGTH;
+use resource_task::{LoadResponse, ProgressMsg, TargetedLoadResponse};
+use resource_task::ProgressMsg::{Payload, Done};
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread::Builder;
-use resource_task::{TargetedLoadResponse};
+use util::task::spawn_named;
 
 pub type SnifferTask = Sender<TargetedLoadResponse>;
 
@@ -35,10 +42,146 @@ impl SnifferManager {
         loop {
             match self.data_receiver.recv() {
                 Ok(snif_data) => {
-                    let _ = snif_data.consumer.send(snif_data.load_response);
+                    // Sniffing has to wait for the start of the body, which mustn't hold up
+                    // any other loads.
+                    spawn_named("Sniffer".to_owned(), move || sniff(snif_data));
                 }
                 Err(_) => break,
             }
         }
     }
 }
+
+/// http://mimesniff.spec.whatwg.org/#reading-the-resource-header
+///
+/// Buffers the body until it is as long as a resource header or the load has finished, so the
+/// bytes the classifier sees don't depend on how the body was split into chunks. Returns the
+/// buffered messages and whether the load has finished.
+fn read_resource_header(progress_port: &Receiver<ProgressMsg>) -> (Vec<ProgressMsg>, bool) {
+    let mut buffered = vec!();
+    let mut length = 0;
+    while length < MAX_RESOURCE_HEADER_LENGTH {
+        match progress_port.recv() {
+            Ok(Payload(data)) => {
+                length += data.len();
+                buffered.push(Payload(data));
+            }
+            Ok(done) => {
+                buffered.push(done);
+                return (buffered, true);
+            }
+            // The loader went away without telling us whether it succeeded.
+            Err(_) => return (buffered, true),
+        }
+    }
+    (buffered, false)
+}
+
+/// Determines the computed type of a response and hands it on to its consumer.
+fn sniff(snif_data: TargetedLoadResponse) {
+    let TargetedLoadResponse {
+        load_response: LoadResponse { mut metadata, progress_port },
+        consumer,
+    } = snif_data;
+
+    let (buffered, finished) = read_resource_header(&progress_port);
+
+    let failed = buffered.iter().any(|msg| match *msg {
+        Done(Err(_)) => true,
+        _ => false,
+    });
+    // A data URL carries its own media type, and one without defaults to
+    // text/plain;charset=US-ASCII (RFC 2397), so it is never sniffed.
+    let is_data_url = metadata.final_url.scheme.as_slice() == "data";
+    if !failed && !is_data_url {
+        let mut resource_header = vec!();
+        for msg in buffered.iter() {
+            if let Payload(ref data) = *msg {
+                resource_header.push_all(data.as_slice());
+            }
+        }
+
+        // http://mimesniff.spec.whatwg.org/#supplied-mime-type-detection-algorithm
+        let (no_sniff, check_for_apache_bug) = match metadata.headers {
+            Some(ref headers) => {
+                let no_sniff = headers.get_raw("x-content-type-options").map_or(false, |values| {
+                    values.iter().any(|v| {
+                        String::from_utf8_lossy(v.as_slice()).trim().eq_ignore_ascii_case("nosniff")
+                    })
+                });
+                let check_for_apache_bug = headers.get_raw("content-type").map_or(false, |values| {
+                    values.len() == 1 && mime_classifier::is_apache_bug_content_type(values[0].as_slice())
+                });
+                (no_sniff, check_for_apache_bug)
+            }
+            None => (false, false),
+        };
+
+        let computed_type = mime_classifier::classify(&metadata.content_type, no_sniff,
+                                                      check_for_apache_bug,
+                                                      resource_header.as_slice());
+        debug!("sniffer: {} computed type {:?} (supplied {:?})",
+               metadata.final_url.serialize(), computed_type, metadata.content_type);
+        metadata.content_type = computed_type;
+    }
+
+    let (progress_chan, new_progress_port) = channel();
+    let load_response = LoadResponse {
+        metadata: metadata,
+        progress_port: new_progress_port,
+    };
+    if consumer.send(load_response).is_err() {
+        return;
+    }
+    for msg in buffered.into_iter() {
+        if progress_chan.send(msg).is_err() {
+            return;
+        }
+    }
+    if !finished {
+        for msg in progress_port.iter() {
+            if progress_chan.send(msg).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn sniff_test_response(headers: Option<::hyper::header::Headers>,
+                       content_type: Option<(String, String)>,
+                       body: &[u8]) -> LoadResponse {
+    use resource_task::{Metadata, ResponseSenders, start_sending};
+    use url::Url;
+
+    let (consumer, consumer_port) = channel();
+    let senders = ResponseSenders {
+        immediate_consumer: new_sniffer_task(),
+        eventual_consumer: consumer,
+    };
+    let mut metadata = Metadata::default(Url::parse("http://example.com/").unwrap());
+    metadata.content_type = content_type;
+    metadata.headers = headers;
+    let progress_chan = start_sending(senders, metadata);
+    progress_chan.send(Payload(body.to_vec())).unwrap();
+    progress_chan.send(Done(Ok(()))).unwrap();
+    consumer_port.recv().unwrap()
+}
+
+#[test]
+fn test_sniffer_updates_content_type() {
+    let response = sniff_test_response(None, None, b"<!DOCTYPE html><title>hi</title>");
+    assert_eq!(response.metadata.content_type, Some(("text".to_owned(), "html".to_owned())));
+    assert_eq!(response.progress_port.recv().unwrap(),
+               Payload(b"<!DOCTYPE html><title>hi</title>".to_vec()));
+    assert_eq!(response.progress_port.recv().unwrap(), Done(Ok(())));
+}
+
+#[test]
+fn test_sniffer_respects_nosniff() {
+    let mut headers = ::hyper::header::Headers::new();
+    headers.set_raw("X-Content-Type-Options".to_owned(), vec![b"nosniff".to_vec()]);
+    let png = Some(("image".to_owned(), "png".to_owned()));
+    let response = sniff_test_response(Some(headers), png.clone(), b"\xFF\xD8\xFF\xE0");
+    assert_eq!(response.metadata.content_type, png);
+}
@@ -2,35 +2,50 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use http_cache::write_atomically;
+
+use msg::constellation_msg::{StorageType, TopLevelBrowsingContextId};
+use rustc_serialize::json;
 use std::borrow::ToOwned;
 use std::collections::HashMap;
 use std::collections::BTreeMap;
+use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::hash::{hash, SipHasher};
+use std::old_io::File;
+use std::old_io::fs::{self, PathExtensions};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use url::Url;
 
+use util::opts;
 use util::str::DOMString;
 use util::task::spawn_named;
 
+/// The maximum number of bytes of keys and values that a single origin may store in
+/// each storage area.
+pub const QUOTA_SIZE_LIMIT: usize = 5 * 1024 * 1024;
+
 /// Request operations on the storage data associated with a particular url
 pub enum StorageTaskMsg {
     /// gets the number of key/value pairs present in the associated storage data
-    Length(Sender<u32>, Url),
+    Length(Sender<u32>, Url, StorageType),
 
     /// gets the name of the key at the specified index in the associated storage data
-    Key(Sender<Option<DOMString>>, Url, u32),
+    Key(Sender<Option<DOMString>>, Url, StorageType, u32),
 
     /// gets the value associated with the given key in the associated storage data
-    GetItem(Sender<Option<DOMString>>, Url, DOMString),
+    GetItem(Sender<Option<DOMString>>, Url, StorageType, DOMString),
 
-    /// sets the value of the given key in the associated storage data
-    /// TODO throw QuotaExceededError in case of error
-    SetItem(Sender<bool>, Url, DOMString, DOMString),
+    /// sets the value of the given key in the associated storage data; replies with whether
+    /// the value changed and the previous value, or with an error if the origin's quota
+    /// would be exceeded
+    SetItem(Sender<Result<(bool, Option<DOMString>), ()>>, Url, StorageType, DOMString, DOMString),
 
-    /// removes the key/value pair for the given key in the associated storage data
-    RemoveItem(Sender<bool>, Url, DOMString),
+    /// removes the key/value pair for the given key in the associated storage data; replies
+    /// with the removed value, if any
+    RemoveItem(Sender<Option<DOMString>>, Url, StorageType, DOMString),
 
     /// clears the associated storage data by removing all the key/value pairs
-    Clear(Sender<bool>, Url),
+    Clear(Sender<bool>, Url, StorageType),
 
     /// shut down this task
     Exit
@@ -46,24 +61,49 @@ pub trait StorageTaskFactory {
 impl StorageTaskFactory for StorageTask {
     /// Create a StorageTask
     fn new() -> StorageTask {
-        let (chan, port) = channel();
-        spawn_named("StorageManager".to_owned(), move || {
-            StorageManager::new(port).start();
-        });
-        chan
+        let local_storage_path = opts::get().local_storage_path.as_ref().map(|path| Path::new(path));
+        new_storage_task(local_storage_path)
     }
 }
 
+fn new_storage_task(local_storage_path: Option<Path>) -> StorageTask {
+    let (chan, port) = channel();
+    spawn_named("StorageManager".to_owned(), move || {
+        StorageManager::new(port, local_storage_path).start();
+    });
+    chan
+}
+
+/// The on-disk representation of one origin's localStorage.
+#[derive(RustcEncodable, RustcDecodable)]
+struct StoredOrigin {
+    origin: String,
+    items: BTreeMap<DOMString, DOMString>,
+}
+
 struct StorageManager {
     port: Receiver<StorageTaskMsg>,
-    data: HashMap<String, BTreeMap<DOMString, DOMString>>,
+    /// The sessionStorage of each top-level browsing context, keyed by origin.
+    session_data: HashMap<TopLevelBrowsingContextId, HashMap<String, BTreeMap<DOMString, DOMString>>>,
+    local_data: HashMap<String, BTreeMap<DOMString, DOMString>>,
+    /// The directory in which localStorage is persisted, if any.
+    local_storage_path: Option<Path>,
 }
 
 impl StorageManager {
-    fn new(port: Receiver<StorageTaskMsg>) -> StorageManager {
+    fn new(port: Receiver<StorageTaskMsg>, local_storage_path: Option<Path>) -> StorageManager {
+        if let Some(ref path) = local_storage_path {
+            if !path.is_dir() {
+                if let Err(e) = fs::mkdir_recursive(path, ::std::old_io::USER_RWX) {
+                    warn!("storage: couldn't create storage directory {}: {}", path.display(), e);
+                }
+            }
+        }
         StorageManager {
             port: port,
-            data: HashMap::new(),
+            session_data: HashMap::new(),
+            local_data: HashMap::new(),
+            local_storage_path: local_storage_path,
         }
     }
 }
@@ -72,23 +112,23 @@ impl StorageManager {
     fn start(&mut self) {
         loop {
             match self.port.recv().unwrap() {
-                StorageTaskMsg::Length(sender, url) => {
-                    self.length(sender, url)
+                StorageTaskMsg::Length(sender, url, storage_type) => {
+                    self.length(sender, url, storage_type)
                 }
-                StorageTaskMsg::Key(sender, url, index) => {
-                    self.key(sender, url, index)
+                StorageTaskMsg::Key(sender, url, storage_type, index) => {
+                    self.key(sender, url, storage_type, index)
                 }
-                StorageTaskMsg::SetItem(sender, url, name, value) => {
-                    self.set_item(sender, url, name, value)
+                StorageTaskMsg::SetItem(sender, url, storage_type, name, value) => {
+                    self.set_item(sender, url, storage_type, name, value)
                 }
-                StorageTaskMsg::GetItem(sender, url, name) => {
-                    self.get_item(sender, url, name)
+                StorageTaskMsg::GetItem(sender, url, storage_type, name) => {
+                    self.get_item(sender, url, storage_type, name)
                 }
-                StorageTaskMsg::RemoveItem(sender, url, name) => {
-                    self.remove_item(sender, url, name)
+                StorageTaskMsg::RemoveItem(sender, url, storage_type, name) => {
+                    self.remove_item(sender, url, storage_type, name)
                 }
-                StorageTaskMsg::Clear(sender, url) => {
-                    self.clear(sender, url)
+                StorageTaskMsg::Clear(sender, url, storage_type) => {
+                    self.clear(sender, url, storage_type)
                 }
                 StorageTaskMsg::Exit => {
                     break
@@ -97,59 +137,161 @@ impl StorageManager {
         }
     }
 
-    fn length(&self, sender: Sender<u32>, url: Url) {
+    fn length(&mut self, sender: Sender<u32>, url: Url, storage_type: StorageType) {
         let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get(&origin).map_or(0u, |entry| entry.len()) as u32).unwrap();
+        let data = self.select_data(storage_type, &origin);
+        sender.send(data.get(&origin).map_or(0u, |entry| entry.len()) as u32).unwrap();
     }
 
-    fn key(&self, sender: Sender<Option<DOMString>>, url: Url, index: u32) {
+    fn key(&mut self, sender: Sender<Option<DOMString>>, url: Url, storage_type: StorageType,
+           index: u32) {
         let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get(&origin)
+        let data = self.select_data(storage_type, &origin);
+        sender.send(data.get(&origin)
                     .and_then(|entry| entry.keys().nth(index as uint))
                     .map(|key| key.clone())).unwrap();
     }
 
-    fn set_item(&mut self, sender: Sender<bool>, url: Url, name: DOMString, value: DOMString) {
+    fn set_item(&mut self, sender: Sender<Result<(bool, Option<DOMString>), ()>>, url: Url,
+                storage_type: StorageType, name: DOMString, value: DOMString) {
         let origin = self.get_origin_as_string(url);
-        if !self.data.contains_key(&origin) {
-            self.data.insert(origin.clone(), BTreeMap::new());
-        }
+        let result = {
+            let data = self.select_data(storage_type, &origin);
+            if !data.contains_key(&origin) {
+                data.insert(origin.clone(), BTreeMap::new());
+            }
 
-        let updated = self.data.get_mut(&origin).map(|entry| {
-            if entry.get(&origin).map_or(true, |item| item.as_slice() != value.as_slice()) {
-                entry.insert(name.clone(), value.clone());
-                true
+            let entry = data.get_mut(&origin).unwrap();
+            let old_value = entry.get(&name).map(|item| item.clone());
+            if old_value.as_ref().map_or(false, |item| item.as_slice() == value.as_slice()) {
+                Ok((false, old_value))
             } else {
-                false
+                let old_size = entry.iter().fold(0, |size, (k, v)| size + k.len() + v.len());
+                let new_size = old_size + name.len() + value.len() -
+                    old_value.as_ref().map_or(0, |item| name.len() + item.len());
+                if new_size > QUOTA_SIZE_LIMIT {
+                    Err(())
+                } else {
+                    entry.insert(name, value);
+                    Ok((true, old_value))
+                }
             }
-        }).unwrap();
+        };
 
-        sender.send(updated).unwrap();
+        if let Ok((true, _)) = result {
+            self.save(storage_type, &origin);
+        }
+        sender.send(result).unwrap();
     }
 
-    fn get_item(&self, sender: Sender<Option<DOMString>>, url: Url, name: DOMString) {
+    fn get_item(&mut self, sender: Sender<Option<DOMString>>, url: Url, storage_type: StorageType,
+                name: DOMString) {
         let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get(&origin)
+        let data = self.select_data(storage_type, &origin);
+        sender.send(data.get(&origin)
                     .and_then(|entry| entry.get(&name))
                     .map(|value| value.to_string())).unwrap();
     }
 
-    fn remove_item(&mut self, sender: Sender<bool>, url: Url, name: DOMString) {
+    fn remove_item(&mut self, sender: Sender<Option<DOMString>>, url: Url,
+                   storage_type: StorageType, name: DOMString) {
         let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get_mut(&origin)
-                    .map_or(false, |entry| entry.remove(&name).is_some())).unwrap();
+        let old_value = self.select_data(storage_type, &origin).get_mut(&origin)
+                            .and_then(|entry| entry.remove(&name));
+        if old_value.is_some() {
+            self.save(storage_type, &origin);
+        }
+        sender.send(old_value).unwrap();
     }
 
-    fn clear(&mut self, sender: Sender<bool>, url: Url) {
+    fn clear(&mut self, sender: Sender<bool>, url: Url, storage_type: StorageType) {
         let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get_mut(&origin)
-                    .map_or(false, |entry| {
-                        if !entry.is_empty() {
-                            entry.clear();
-                            true
-                        } else {
-                            false
-                        }})).unwrap();
+        let cleared = self.select_data(storage_type, &origin).get_mut(&origin)
+                          .map_or(false, |entry| {
+                              if !entry.is_empty() {
+                                  entry.clear();
+                                  true
+                              } else {
+                                  false
+                              }});
+        if cleared {
+            self.save(storage_type, &origin);
+        }
+        sender.send(cleared).unwrap();
+    }
+
+    /// Returns the storage area for `storage_type`, loading `origin`'s persisted
+    /// localStorage first if it hasn't been read yet.
+    fn select_data(&mut self, storage_type: StorageType, origin: &String)
+                   -> &mut HashMap<String, BTreeMap<DOMString, DOMString>> {
+        match storage_type {
+            StorageType::Session(browsing_context) => {
+                match self.session_data.entry(browsing_context) {
+                    Occupied(entry) => entry.into_mut(),
+                    Vacant(entry) => entry.insert(HashMap::new()),
+                }
+            }
+            StorageType::Local => {
+                if !self.local_data.contains_key(origin) {
+                    if let Some(items) = self.read_from_disk(origin) {
+                        self.local_data.insert(origin.clone(), items);
+                    }
+                }
+                &mut self.local_data
+            }
+        }
+    }
+
+    fn path_for_origin(&self, origin: &String) -> Option<Path> {
+        self.local_storage_path.as_ref().map(|dir| {
+            dir.join(format!("{:016x}.json", hash::<_, SipHasher>(origin)))
+        })
+    }
+
+    fn read_from_disk(&self, origin: &String) -> Option<BTreeMap<DOMString, DOMString>> {
+        let path = match self.path_for_origin(origin) {
+            Some(ref path) if path.exists() => path.clone(),
+            _ => return None,
+        };
+        let contents = match File::open(&path).read_to_string() {
+            Ok(contents) => contents,
+            Err(_) => return None,
+        };
+        match json::decode::<StoredOrigin>(contents.as_slice()) {
+            // Guard against hash collisions.
+            Ok(stored) => if stored.origin == *origin { Some(stored.items) } else { None },
+            Err(_) => {
+                warn!("storage: discarding corrupt storage file {}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Writes `origin`'s localStorage through to disk. Session storage is never persisted.
+    fn save(&self, storage_type: StorageType, origin: &String) {
+        if storage_type != StorageType::Local {
+            return
+        }
+        let path = match self.path_for_origin(origin) {
+            Some(path) => path,
+            None => return,
+        };
+        let items = self.local_data.get(origin).map_or(BTreeMap::new(), |items| items.clone());
+        if items.is_empty() {
+            if path.exists() {
+                if let Err(e) = fs::unlink(&path) {
+                    warn!("storage: couldn't remove {}: {}", path.display(), e);
+                }
+            }
+            return
+        }
+        let stored = StoredOrigin {
+            origin: origin.clone(),
+            items: items,
+        };
+        if let Err(e) = write_atomically(&path, json::encode(&stored).unwrap().as_slice()) {
+            warn!("storage: couldn't write {}: {}", path.display(), e);
+        }
     }
 
     fn get_origin_as_string(&self, url: Url) -> String {
@@ -165,3 +307,92 @@ impl StorageManager {
         origin
     }
 }
+
+#[cfg(test)]
+fn set_item(storage_task: &StorageTask, url: &Url, storage_type: StorageType, name: &str,
+            value: &str) -> Result<(bool, Option<DOMString>), ()> {
+    let (sender, receiver) = channel();
+    storage_task.send(StorageTaskMsg::SetItem(sender, url.clone(), storage_type,
+                                              name.to_owned(), value.to_owned())).unwrap();
+    receiver.recv().unwrap()
+}
+
+#[cfg(test)]
+fn get_item(storage_task: &StorageTask, url: &Url, storage_type: StorageType, name: &str)
+            -> Option<DOMString> {
+    let (sender, receiver) = channel();
+    storage_task.send(StorageTaskMsg::GetItem(sender, url.clone(), storage_type,
+                                              name.to_owned())).unwrap();
+    receiver.recv().unwrap()
+}
+
+#[test]
+fn test_session_and_local_storage_are_separate() {
+    let storage_task = new_storage_task(None);
+    let url = Url::parse("http://example.com/foo").unwrap();
+    let session = StorageType::Session(TopLevelBrowsingContextId(0));
+
+    assert_eq!(set_item(&storage_task, &url, session, "key", "session"),
+               Ok((true, None)));
+    assert_eq!(get_item(&storage_task, &url, session, "key"),
+               Some("session".to_owned()));
+    assert_eq!(get_item(&storage_task, &url, StorageType::Local, "key"), None);
+
+    assert_eq!(set_item(&storage_task, &url, session, "key", "session"),
+               Ok((false, Some("session".to_owned()))));
+    assert_eq!(set_item(&storage_task, &url, session, "key", "other"),
+               Ok((true, Some("session".to_owned()))));
+    storage_task.send(StorageTaskMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_session_storage_is_scoped_to_top_level_browsing_context() {
+    let storage_task = new_storage_task(None);
+    let url = Url::parse("http://example.com/foo").unwrap();
+    let first = StorageType::Session(TopLevelBrowsingContextId(0));
+    let second = StorageType::Session(TopLevelBrowsingContextId(1));
+
+    assert_eq!(set_item(&storage_task, &url, first, "key", "first"), Ok((true, None)));
+    assert_eq!(get_item(&storage_task, &url, second, "key"), None);
+    assert_eq!(set_item(&storage_task, &url, second, "key", "second"), Ok((true, None)));
+    assert_eq!(get_item(&storage_task, &url, first, "key"), Some("first".to_owned()));
+    storage_task.send(StorageTaskMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_quota_is_enforced_per_origin() {
+    let storage_task = new_storage_task(None);
+    let url = Url::parse("http://example.com/").unwrap();
+    let other_url = Url::parse("http://example.org/").unwrap();
+    let big_value = ::std::iter::repeat('a').take(QUOTA_SIZE_LIMIT - 3).collect::<String>();
+
+    assert!(set_item(&storage_task, &url, StorageType::Local, "big", big_value.as_slice()).is_ok());
+    assert_eq!(set_item(&storage_task, &url, StorageType::Local, "more", "data"), Err(()));
+    assert_eq!(get_item(&storage_task, &url, StorageType::Local, "more"), None);
+    // Replacing an existing value only counts the difference.
+    assert!(set_item(&storage_task, &url, StorageType::Local, "big", "small").is_ok());
+    assert!(set_item(&storage_task, &url, StorageType::Local, "more", "data").is_ok());
+    assert!(set_item(&storage_task, &other_url, StorageType::Local, "big",
+                     big_value.as_slice()).is_ok());
+    storage_task.send(StorageTaskMsg::Exit).unwrap();
+}
+
+#[test]
+fn test_local_storage_is_persisted() {
+    use std::old_io::TempDir;
+
+    let dir = TempDir::new("local_storage").unwrap();
+    let url = Url::parse("http://example.com:8000/foo").unwrap();
+    let session = StorageType::Session(TopLevelBrowsingContextId(0));
+    {
+        let storage_task = new_storage_task(Some(dir.path().clone()));
+        assert!(set_item(&storage_task, &url, StorageType::Local, "key", "value").is_ok());
+        assert!(set_item(&storage_task, &url, session, "session", "value").is_ok());
+        storage_task.send(StorageTaskMsg::Exit).unwrap();
+    }
+
+    let storage_task = new_storage_task(Some(dir.path().clone()));
+    assert_eq!(get_item(&storage_task, &url, StorageType::Local, "key"), Some("value".to_owned()));
+    assert_eq!(get_item(&storage_task, &url, session, "session"), None);
+    storage_task.send(StorageTaskMsg::Exit).unwrap();
+}
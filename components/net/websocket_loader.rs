@@ -0,0 +1,657 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! WebSocket connections, as specified in http://tools.ietf.org/html/rfc6455
+//!
+//! A connection task performs the opening handshake, and then both reads frames from the server,
+//! reporting messages to the DOM, and writes the messages the DOM sends. All the I/O on a stream
+//! happens on that one task, since a TLS stream can't be read and written from two tasks at once.
+
+use cookie_storage::CookieSource;
+use http_loader::verifier;
+use proxy::{ProxyConfig, ProxyConnector};
+use resource_task::ControlMsg;
+
+use hyper::net::{HttpConnector, HttpStream, NetworkConnector};
+use openssl::crypto::hash::{hash, HashType};
+use openssl::crypto::rand::rand_bytes;
+use openssl::ssl::SslContext;
+use rustc_serialize::base64::{ToBase64, STANDARD};
+use url::Url;
+use util::task::spawn_named;
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::cmp::min;
+use std::old_io::{IoError, IoErrorKind, IoResult};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+
+/// Appended to the key of the client to compute the accept value of the server.
+static WEBSOCKET_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The longest handshake response head that is read.
+static MAX_HANDSHAKE_RESPONSE_LENGTH: uint = 16384;
+
+/// The largest message that is accepted from a server.
+static MAX_MESSAGE_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// How long a connection waits for a frame from the server before it goes back to sending the
+/// messages the DOM queued in the meantime, in milliseconds.
+static POLL_INTERVAL_MS: u64 = 10;
+
+/// What the DOM needs to give to open a connection.
+pub struct WebSocketConnectData {
+    pub url: Url,
+    /// The serialized origin of the document opening the connection.
+    pub origin: String,
+    /// The subprotocols the server can choose from.
+    pub protocols: Vec<String>,
+}
+
+/// A request from the DOM to an open connection.
+pub enum WebSocketDomAction {
+    SendText(String),
+    SendBinary(Vec<u8>),
+    /// Starts the closing handshake, with an optional status code and a reason.
+    Close(Option<u16>, String),
+}
+
+/// Something that happened on a connection, reported to the DOM.
+pub enum WebSocketNetworkEvent {
+    /// The opening handshake succeeded, and the server picked this subprotocol, if any.
+    ConnectionEstablished(Option<String>),
+    TextMessage(String),
+    BinaryMessage(Vec<u8>),
+    /// This many bytes of the data the DOM sent were written to the network.
+    DataSent(u64),
+    /// The connection failed, and was closed without completing the closing handshake.
+    Fail,
+    /// The connection was closed, with the status code, the reason, and whether the closing
+    /// handshake completed.
+    Close(u16, String, bool),
+}
+
+/// http://tools.ietf.org/html/rfc6455#section-5.2
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_u8(opcode: u8) -> Option<Opcode> {
+        match opcode {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        self.to_u8() & 0x8 != 0
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: Opcode,
+    payload: Vec<u8>,
+}
+
+fn protocol_error(desc: &'static str) -> IoError {
+    IoError {
+        kind: IoErrorKind::OtherIoError,
+        desc: desc,
+        detail: None,
+    }
+}
+
+/// Writes `payload` as a single, final frame. Frames sent by a client are masked.
+fn write_frame<W: Writer>(writer: &mut W, opcode: Opcode, payload: &[u8]) -> IoResult<()> {
+    let mut frame = vec!(0x80 | opcode.to_u8());
+    let len = payload.len() as u64;
+    if len < 126 {
+        frame.push(0x80 | len as u8);
+    } else if len <= 0xFFFF {
+        frame.push(0x80 | 126);
+        frame.push((len >> 8) as u8);
+        frame.push(len as u8);
+    } else {
+        frame.push(0x80 | 127);
+        for i in range(0u, 8).rev() {
+            frame.push((len >> (8 * i)) as u8);
+        }
+    }
+    let mask = rand_bytes(4);
+    frame.push_all(mask.as_slice());
+    frame.extend(payload.iter().enumerate().map(|(i, &byte)| byte ^ mask[i % 4]));
+    try!(writer.write_all(frame.as_slice()));
+    writer.flush()
+}
+
+/// Reads a frame. Frames sent by a server aren't masked.
+fn read_frame<R: Reader>(reader: &mut R) -> IoResult<Frame> {
+    let first_byte = try!(reader.read_byte());
+    read_frame_after(first_byte, reader)
+}
+
+/// Reads the rest of a frame whose first byte was already read.
+fn read_frame_after<R: Reader>(first_byte: u8, reader: &mut R) -> IoResult<Frame> {
+    if first_byte & 0x70 != 0 {
+        return Err(protocol_error("reserved bits are set, but no extension was negotiated"));
+    }
+    let fin = first_byte & 0x80 != 0;
+    let opcode = match Opcode::from_u8(first_byte & 0xF) {
+        Some(opcode) => opcode,
+        None => return Err(protocol_error("unknown opcode")),
+    };
+    let second_byte = try!(reader.read_byte());
+    if second_byte & 0x80 != 0 {
+        return Err(protocol_error("frames from the server must not be masked"));
+    }
+    let len = match second_byte & 0x7F {
+        126 => try!(reader.read_be_u16()) as u64,
+        127 => try!(reader.read_be_u64()),
+        len => len as u64,
+    };
+    if opcode.is_control() && (len > 125 || !fin) {
+        return Err(protocol_error("invalid control frame"));
+    }
+    if len > MAX_MESSAGE_LENGTH {
+        return Err(protocol_error("frame too large"));
+    }
+    let payload = try!(reader.read_exact(len as uint));
+    Ok(Frame {
+        fin: fin,
+        opcode: opcode,
+        payload: payload,
+    })
+}
+
+/// The payload of a close frame, as described in RFC 6455 section 5.5.1.
+fn close_payload(code: Option<u16>, reason: &str) -> Vec<u8> {
+    match code {
+        Some(code) => {
+            let mut payload = vec!((code >> 8) as u8, code as u8);
+            payload.push_all(reason.as_bytes());
+            payload
+        }
+        None => vec!(),
+    }
+}
+
+/// The URL that cookies for a connection to `url` are read from and stored for.
+pub fn http_url(url: &Url) -> Url {
+    let mut http_url = url.clone();
+    http_url.scheme = match url.scheme.as_slice() {
+        "wss" => "https".to_owned(),
+        _ => "http".to_owned(),
+    };
+    http_url
+}
+
+/// Connects to the server and performs the opening handshake of RFC 6455 section 4.1. Returns
+/// the stream and the subprotocol the server picked.
+fn handshake(connect_data: &WebSocketConnectData, cookies: Option<String>,
+             user_agent: Option<String>, proxies: ProxyConfig,
+             cookies_chan: &Sender<ControlMsg>) -> IoResult<(HttpStream, Option<String>)> {
+    let url = &connect_data.url;
+    let scheme = match url.scheme.as_slice() {
+        "ws" => "http",
+        "wss" => "https",
+        _ => return Err(protocol_error("not a WebSocket URL")),
+    };
+    let host = match url.domain() {
+        Some(host) => host.to_owned(),
+        None => match url.serialize_host() {
+            Some(host) => host,
+            None => return Err(protocol_error("URL has no host")),
+        },
+    };
+    let port = url.port_or_default().unwrap_or(if scheme == "https" { 443 } else { 80 });
+
+    let connector = HttpConnector(Some(box verifier as Box<FnMut(&mut SslContext)>));
+    let mut connector = ProxyConnector::new(proxies, connector);
+    let mut stream = try!(connector.connect(host.as_slice(), port, scheme));
+
+    let mut resource = connector.forward_proxy_origin(host.as_slice(), port, scheme)
+                                .unwrap_or(String::new());
+    resource.push_str(url.serialize_path().unwrap_or("/".to_owned()).as_slice());
+    if let Some(ref query) = url.query {
+        resource.push('?');
+        resource.push_str(query.as_slice());
+    }
+    let host_header = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.clone(),
+    };
+    let key = rand_bytes(16).to_base64(STANDARD);
+
+    let mut request = format!("GET {} HTTP/1.1\r\n\
+                               Host: {}\r\n\
+                               Upgrade: websocket\r\n\
+                               Connection: Upgrade\r\n\
+                               Sec-WebSocket-Key: {}\r\n\
+                               Sec-WebSocket-Version: 13\r\n\
+                               Origin: {}\r\n",
+                              resource, host_header, key, connect_data.origin);
+    if !connect_data.protocols.is_empty() {
+        request.push_str(format!("Sec-WebSocket-Protocol: {}\r\n",
+                                 connect_data.protocols.connect(", ")).as_slice());
+    }
+    if let Some(cookies) = cookies {
+        request.push_str(format!("Cookie: {}\r\n", cookies).as_slice());
+    }
+    if let Some(user_agent) = user_agent {
+        request.push_str(format!("User-Agent: {}\r\n", user_agent).as_slice());
+    }
+    request.push_str("\r\n");
+    try!(stream.write_all(request.as_bytes()));
+    try!(stream.flush());
+
+    // Read the response head a byte at a time, so that no frame is consumed.
+    let mut head = vec!();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > MAX_HANDSHAKE_RESPONSE_LENGTH {
+            return Err(protocol_error("handshake response too long"));
+        }
+        head.push(try!(stream.read_byte()));
+    }
+    let head = String::from_utf8_lossy(head.as_slice()).into_owned();
+    let mut lines = head.as_slice().split_str("\r\n");
+    match lines.next().and_then(|status_line| status_line.split(' ').nth(1)) {
+        Some("101") => {}
+        _ => return Err(protocol_error("server didn't switch protocols")),
+    }
+    let headers: Vec<(String, String)> = lines.filter_map(|line| {
+        let mut parts = line.splitn(1, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => Some((name.trim().to_ascii_lowercase(),
+                                               value.trim().to_owned())),
+            _ => None,
+        }
+    }).collect();
+
+    // http://tools.ietf.org/html/rfc6455#section-4.1, the checks of the server's handshake.
+    let upgrade = find_header(headers.as_slice(), "upgrade");
+    if !upgrade.map_or(false, |value| value.eq_ignore_ascii_case("websocket")) {
+        return Err(protocol_error("missing Upgrade: websocket"));
+    }
+    if !find_header(headers.as_slice(), "connection").map_or(false, |value| {
+        value.split(',').any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+    }) {
+        return Err(protocol_error("missing Connection: Upgrade"));
+    }
+    let mut accept_input = key.clone();
+    accept_input.push_str(WEBSOCKET_GUID);
+    let accept = hash(HashType::SHA1, accept_input.as_bytes()).to_base64(STANDARD);
+    if find_header(headers.as_slice(), "sec-websocket-accept") != Some(accept.as_slice()) {
+        return Err(protocol_error("invalid Sec-WebSocket-Accept"));
+    }
+    if find_header(headers.as_slice(), "sec-websocket-extensions").is_some() {
+        return Err(protocol_error("server accepted an extension that wasn't offered"));
+    }
+    let protocol = match find_header(headers.as_slice(), "sec-websocket-protocol") {
+        Some(protocol) => {
+            if !connect_data.protocols.iter().any(|offered| offered.as_slice() == protocol) {
+                return Err(protocol_error("server picked a subprotocol that wasn't offered"));
+            }
+            Some(protocol.to_owned())
+        }
+        None => None,
+    };
+
+    for &(ref name, ref value) in headers.iter() {
+        if name.as_slice() == "set-cookie" {
+            let _ = cookies_chan.send(ControlMsg::SetCookiesForUrl(http_url(url), value.clone(),
+                                                                   CookieSource::HTTP));
+        }
+    }
+
+    Ok((stream, protocol))
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|&&(ref header_name, _)| header_name.as_slice() == name)
+                  .map(|&(_, ref value)| value.as_slice())
+}
+
+/// Sets how long a read from `stream` may block, in milliseconds.
+fn set_read_timeout(stream: &mut HttpStream, timeout_ms: Option<u64>) {
+    match *stream {
+        HttpStream::Http(ref mut stream) => stream.set_read_timeout(timeout_ms),
+        HttpStream::Https(ref mut stream) => stream.get_mut().set_read_timeout(timeout_ms),
+    }
+}
+
+/// An open connection, owned by its connection task.
+struct Connection {
+    stream: HttpStream,
+    event_sender: Sender<WebSocketNetworkEvent>,
+    /// Whether a close frame was sent, after which no other frame may be.
+    close_sent: bool,
+}
+
+impl Connection {
+    fn send(&mut self, opcode: Opcode, payload: &[u8]) -> IoResult<()> {
+        if self.close_sent {
+            return Ok(());
+        }
+        if opcode == Opcode::Close {
+            self.close_sent = true;
+        }
+        write_frame(&mut self.stream, opcode, payload)
+    }
+
+    /// Sends what the DOM asked for, and tells it how much of its buffered data left.
+    fn handle_action(&mut self, action: WebSocketDomAction) -> IoResult<()> {
+        let sent = match action {
+            WebSocketDomAction::SendText(text) => {
+                try!(self.send(Opcode::Text, text.as_bytes()));
+                text.len()
+            }
+            WebSocketDomAction::SendBinary(data) => {
+                try!(self.send(Opcode::Binary, data.as_slice()));
+                data.len()
+            }
+            WebSocketDomAction::Close(code, reason) => {
+                try!(self.send(Opcode::Close, close_payload(code, reason.as_slice()).as_slice()));
+                0
+            }
+        };
+        if sent > 0 {
+            let _ = self.event_sender.send(WebSocketNetworkEvent::DataSent(sent as u64));
+        }
+        Ok(())
+    }
+
+    /// Waits at most `POLL_INTERVAL_MS` for a frame to start. Once it has, the rest of the frame
+    /// is read without a timeout, so that a timeout never leaves a frame half read.
+    fn poll_frame(&mut self) -> IoResult<Option<Frame>> {
+        set_read_timeout(&mut self.stream, Some(POLL_INTERVAL_MS));
+        let first_byte = match self.stream.read_byte() {
+            Ok(byte) => byte,
+            Err(ref e) if e.kind == IoErrorKind::TimedOut => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        set_read_timeout(&mut self.stream, None);
+        read_frame_after(first_byte, &mut self.stream).map(Some)
+    }
+
+    /// Fails the connection, as described in RFC 6455 section 7.1.7.
+    fn fail(&mut self, code: u16) {
+        let _ = self.send(Opcode::Close, close_payload(Some(code), "").as_slice());
+        let _ = self.event_sender.send(WebSocketNetworkEvent::Fail);
+    }
+
+    /// Reports that the connection was closed without a close frame. 1006 is "closed
+    /// abnormally".
+    fn closed_abnormally(&mut self) {
+        let _ = self.event_sender.send(WebSocketNetworkEvent::Close(1006, String::new(), false));
+    }
+}
+
+/// Opens a connection to `connect_data.url`. Its events are sent to `event_sender` and it is
+/// driven through `action_receiver`; it closes when the DOM drops its sender.
+pub fn connect(connect_data: WebSocketConnectData,
+               event_sender: Sender<WebSocketNetworkEvent>,
+               action_receiver: Receiver<WebSocketDomAction>,
+               cookies_chan: Sender<ControlMsg>,
+               proxies: ProxyConfig,
+               user_agent: Option<String>) {
+    spawn_named("WebSocket connection".to_owned(), move || {
+        let (cookies_sender, cookies_receiver) = channel();
+        cookies_chan.send(ControlMsg::GetCookiesForUrl(http_url(&connect_data.url), cookies_sender,
+                                                       CookieSource::HTTP)).unwrap();
+        let cookies = cookies_receiver.recv().unwrap();
+
+        let (stream, protocol) = match handshake(&connect_data, cookies, user_agent, proxies,
+                                                 &cookies_chan) {
+            Ok(connection) => connection,
+            Err(e) => {
+                debug!("WebSocket handshake with {} failed: {:?}", connect_data.url.serialize(), e);
+                let _ = event_sender.send(WebSocketNetworkEvent::Fail);
+                return;
+            }
+        };
+        if event_sender.send(WebSocketNetworkEvent::ConnectionEstablished(protocol)).is_err() {
+            return;
+        }
+
+        let mut connection = Connection {
+            stream: stream,
+            event_sender: event_sender,
+            close_sent: false,
+        };
+        run_connection(&mut connection, action_receiver);
+    });
+}
+
+/// Sends the messages of the DOM and reads frames, reporting the messages they make up, until
+/// the connection is closed.
+fn run_connection(connection: &mut Connection, action_receiver: Receiver<WebSocketDomAction>) {
+    // The opcode and data of a fragmented message, until its final frame arrives.
+    let mut message: Option<(Opcode, Vec<u8>)> = None;
+    loop {
+        loop {
+            match action_receiver.try_recv() {
+                Ok(action) => {
+                    if let Err(e) = connection.handle_action(action) {
+                        debug!("WebSocket connection failed: {:?}", e);
+                        return connection.closed_abnormally();
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // The WebSocket object is gone: 1001 is "going away".
+                    let payload = close_payload(Some(1001), "");
+                    let _ = connection.send(Opcode::Close, payload.as_slice());
+                    return;
+                }
+            }
+        }
+
+        let frame = match connection.poll_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => continue,
+            Err(ref e) if e.kind == IoErrorKind::EndOfFile => {
+                return connection.closed_abnormally();
+            }
+            Err(e) => {
+                debug!("WebSocket connection failed: {:?}", e);
+                return connection.fail(1002);
+            }
+        };
+
+        let complete = match frame.opcode {
+            Opcode::Text | Opcode::Binary => {
+                if message.is_some() {
+                    return connection.fail(1002);
+                }
+                if frame.fin {
+                    Some((frame.opcode, frame.payload))
+                } else {
+                    message = Some((frame.opcode, frame.payload));
+                    None
+                }
+            }
+            Opcode::Continuation => {
+                let (opcode, mut data) = match message.take() {
+                    Some(message) => message,
+                    None => return connection.fail(1002),
+                };
+                if (data.len() + frame.payload.len()) as u64 > MAX_MESSAGE_LENGTH {
+                    return connection.fail(1009);
+                }
+                data.push_all(frame.payload.as_slice());
+                if frame.fin {
+                    Some((opcode, data))
+                } else {
+                    message = Some((opcode, data));
+                    None
+                }
+            }
+            Opcode::Ping => {
+                let _ = connection.send(Opcode::Pong, frame.payload.as_slice());
+                None
+            }
+            Opcode::Pong => None,
+            Opcode::Close => {
+                // 1005 is "no status code was given".
+                let (code, reason) = if frame.payload.len() >= 2 {
+                    let code = ((frame.payload[0] as u16) << 8) | frame.payload[1] as u16;
+                    (code, String::from_utf8_lossy(&frame.payload[2..]).into_owned())
+                } else {
+                    (1005, String::new())
+                };
+                // Echo the status code if we didn't start the closing handshake.
+                let echoed = &frame.payload[..min(2, frame.payload.len())];
+                let _ = connection.send(Opcode::Close, echoed);
+                let _ = connection.event_sender.send(WebSocketNetworkEvent::Close(code, reason,
+                                                                                  true));
+                return;
+            }
+        };
+
+        let event = match complete {
+            Some((Opcode::Text, data)) => match String::from_utf8(data) {
+                Ok(text) => WebSocketNetworkEvent::TextMessage(text),
+                // 1007 is "inconsistent data".
+                Err(_) => return connection.fail(1007),
+            },
+            Some((_, data)) => WebSocketNetworkEvent::BinaryMessage(data),
+            None => continue,
+        };
+        if connection.event_sender.send(event).is_err() {
+            return;
+        }
+    }
+}
+
+/// Starts a server that accepts one WebSocket connection, answers the handshake, and then echoes
+/// every text frame it receives until it receives a close frame, which it answers.
+#[cfg(test)]
+fn spawn_echo_server() -> u16 {
+    use std::old_io::{Listener, Acceptor};
+    use std::old_io::net::tcp::TcpListener;
+
+    let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.socket_name().unwrap().port;
+    let mut acceptor = listener.listen().unwrap();
+    spawn_named("WebSocketEchoServer".to_owned(), move || {
+        let mut stream = acceptor.accept().unwrap();
+        let mut head = vec!();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_byte().unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        let key = head.as_slice().split_str("\r\n").find(|line| line.starts_with("Sec-WebSocket-Key: "))
+                      .unwrap()["Sec-WebSocket-Key: ".len()..].to_owned();
+        let accept = hash(HashType::SHA1, (key + WEBSOCKET_GUID).as_bytes()).to_base64(STANDARD);
+        write!(&mut stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                             Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept).unwrap();
+
+        loop {
+            let header = stream.read_exact(2).unwrap();
+            let opcode = header[0] & 0xF;
+            let len = (header[1] & 0x7F) as uint;
+            let mask = stream.read_exact(4).unwrap();
+            let payload: Vec<u8> = stream.read_exact(len).unwrap().iter().enumerate()
+                                         .map(|(i, &byte)| byte ^ mask[i % 4]).collect();
+            stream.write_all(&[0x80 | opcode, len as u8]).unwrap();
+            stream.write_all(payload.as_slice()).unwrap();
+            if opcode == 0x8 {
+                return;
+            }
+        }
+    });
+    port
+}
+
+#[test]
+fn test_frame_round_trip() {
+    use std::old_io::{MemReader, MemWriter};
+
+    let mut writer = MemWriter::new();
+    write_frame(&mut writer, Opcode::Binary, &[1, 2, 3]).unwrap();
+    let frame = writer.into_inner();
+    assert_eq!(frame[0], 0x82);
+    assert_eq!(frame[1], 0x80 | 3);
+
+    // Unmask the frame, as a server would, so that it can be read back.
+    let mask = frame[2..6].to_vec();
+    let mut unmasked = vec!(frame[0], frame[1] & 0x7F);
+    unmasked.extend(frame.into_iter().skip(6).enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+    let frame = read_frame(&mut MemReader::new(unmasked)).unwrap();
+    assert!(frame.fin);
+    assert_eq!(frame.opcode, Opcode::Binary);
+    assert_eq!(frame.payload, vec!(1, 2, 3));
+}
+
+#[test]
+fn test_read_frame_rejects_masked_frames() {
+    use std::old_io::MemReader;
+
+    assert!(read_frame(&mut MemReader::new(vec!(0x81, 0x81, 0, 0, 0, 0, 0x61))).is_err());
+}
+
+#[test]
+fn test_echo_server() {
+    use resource_task::{new_resource_task, ProtocolRegistry};
+
+    let port = spawn_echo_server();
+    let resource_task = new_resource_task(None, ProtocolRegistry::new());
+    let (event_sender, event_receiver) = channel();
+    let (action_sender, action_receiver) = channel();
+    let connect_data = WebSocketConnectData {
+        url: Url::parse(format!("ws://127.0.0.1:{}/echo", port).as_slice()).unwrap(),
+        origin: "http://servo.org".to_owned(),
+        protocols: vec!(),
+    };
+    resource_task.send(ControlMsg::WebSocketConnect(connect_data, event_sender, action_receiver)).unwrap();
+
+    match event_receiver.recv().unwrap() {
+        WebSocketNetworkEvent::ConnectionEstablished(None) => {}
+        _ => panic!("expected the connection to open"),
+    }
+    action_sender.send(WebSocketDomAction::SendText("hello".to_owned())).unwrap();
+    match event_receiver.recv().unwrap() {
+        WebSocketNetworkEvent::DataSent(length) => assert_eq!(length, 5),
+        _ => panic!("expected the message to be sent"),
+    }
+    match event_receiver.recv().unwrap() {
+        WebSocketNetworkEvent::TextMessage(text) => assert_eq!(text.as_slice(), "hello"),
+        _ => panic!("expected the message to be echoed"),
+    }
+    action_sender.send(WebSocketDomAction::Close(Some(1000), "done".to_owned())).unwrap();
+    match event_receiver.recv().unwrap() {
+        WebSocketNetworkEvent::Close(code, reason, clean) => {
+            assert_eq!(code, 1000);
+            assert_eq!(reason.as_slice(), "done");
+            assert!(clean);
+        }
+        _ => panic!("expected the connection to close"),
+    }
+
+    resource_task.send(ControlMsg::Exit).unwrap();
+}
@@ -11,6 +11,7 @@ use dom::bindings::js::{JS, JSRef, Temporary};
 use dom::bindings::js::{OptionalRootedRootable, RootedReference};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
 use dom::element::{Element, AttributeHandlers};
+use dom::mutationobserver::{Mutation, queue_mutation_record};
 use dom::node::Node;
 use dom::window::Window;
 use dom::virtualmethods::vtable_for;
@@ -215,6 +216,12 @@ impl<'a> AttrHelpers<'a> for JSRef<'a, Attr> {
         let node: JSRef<Node> = NodeCast::from_ref(owner);
         let namespace_is_null = self.namespace == ns!("");
 
+        let old_value = match set_type {
+            AttrSettingType::FirstSetAttr => None,
+            AttrSettingType::ReplacedAttr => Some(self.Value()),
+        };
+        queue_mutation_record(node, Mutation::Attribute(self.local_name(), &self.namespace, old_value));
+
         match set_type {
             AttrSettingType::ReplacedAttr if namespace_is_null =>
                 vtable_for(&node).before_remove_attr(self),
@@ -54,6 +54,12 @@ pub enum Error {
     DataClone,
     /// NoModificationAllowedError DOMException
     NoModificationAllowedError,
+    /// QuotaExceededError DOMException
+    QuotaExceeded,
+    /// InvalidNodeTypeError DOMException
+    InvalidNodeType,
+    /// WrongDocumentError DOMException
+    WrongDocument,
 
     /// TypeError JavaScript Error
     TypeError(DOMString),
@@ -89,6 +95,9 @@ pub fn throw_dom_exception(cx: *mut JSContext, global: GlobalRef,
         Error::Timeout => DOMErrorName::TimeoutError,
         Error::DataClone => DOMErrorName::DataCloneError,
         Error::NoModificationAllowedError => DOMErrorName::NoModificationAllowedError,
+        Error::QuotaExceeded => DOMErrorName::QuotaExceededError,
+        Error::InvalidNodeType => DOMErrorName::InvalidNodeTypeError,
+        Error::WrongDocument => DOMErrorName::WrongDocumentError,
         Error::TypeError(message) => {
             throw_type_error(cx, &message);
             return;
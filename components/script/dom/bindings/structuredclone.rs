@@ -17,6 +17,7 @@ use js::jsval::{JSVal, UndefinedValue};
 
 use libc::size_t;
 use std::ptr;
+use std::slice;
 
 /// A buffer for a structured clone.
 pub struct StructuredCloneData {
@@ -57,6 +58,28 @@ impl StructuredCloneData {
         }
         message
     }
+
+    /// Copies the buffer into a vector, which can be sent to other tasks and read any number
+    /// of times with `read_vec`.
+    pub fn to_vec(&self) -> Vec<u64> {
+        unsafe {
+            slice::from_raw_parts(self.data as *const u64, self.nbytes as usize / 8).to_vec()
+        }
+    }
+
+    /// Reads a structured clone from a buffer returned by `to_vec`.
+    ///
+    /// Panics if `JS_ReadStructuredClone` fails.
+    pub fn read_vec(data: &[u64], global: GlobalRef) -> JSVal {
+        let mut message = UndefinedValue();
+        unsafe {
+            assert!(JS_ReadStructuredClone(
+                global.get_cx(), data.as_ptr(), (data.len() * 8) as size_t,
+                JS_STRUCTURED_CLONE_VERSION, &mut message,
+                ptr::null(), ptr::null_mut()) != 0);
+        }
+        message
+    }
 }
 
 unsafe impl Send for StructuredCloneData {}
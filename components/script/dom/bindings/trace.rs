@@ -34,8 +34,11 @@ use dom::bindings::refcounted::Trusted;
 use dom::bindings::utils::{Reflectable, Reflector, WindowProxyHandler};
 use script_task::ScriptChan;
 
+use canvas::canvas_paint_task::{LineCapStyle, LineJoinStyle, CompositionStyle};
+use canvas::canvas_paint_task::{TextAlign, TextBaseline};
 use cssparser::RGBA;
 use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use html5ever::tree_builder::QuirksMode;
 use hyper::header::Headers;
@@ -46,8 +49,11 @@ use js::rust::Cx;
 use layout_interface::{LayoutRPC, LayoutChan};
 use libc;
 use msg::constellation_msg::{PipelineId, SubpageId, WindowSizeData};
+use msg::constellation_msg::{StorageType, TopLevelBrowsingContextId};
+use gfx::font_cache_task::FontCacheTask;
 use net::image_cache_task::ImageCacheTask;
 use script_traits::ScriptControlChan;
+use selectors::parser::PseudoElement;
 use script_traits::UntrustedNodeAddress;
 use msg::compositor_msg::ScriptListener;
 use msg::constellation_msg::ConstellationChan;
@@ -60,6 +66,8 @@ use std::ffi::CString;
 use std::hash::{Hash, Hasher};
 use std::old_io::timer::Timer;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::{Receiver, Sender};
 use string_cache::{Atom, Namespace};
 use style::properties::PropertyDeclarationBlock;
@@ -126,6 +134,12 @@ impl<T: JSTraceable> JSTraceable for Rc<T> {
     }
 }
 
+impl<T: JSTraceable> JSTraceable for Arc<T> {
+    fn trace(&self, trc: *mut JSTracer) {
+        (**self).trace(trc)
+    }
+}
+
 impl<T: JSTraceable> JSTraceable for Box<T> {
     fn trace(&self, trc: *mut JSTracer) {
         (**self).trace(trc)
@@ -204,19 +218,22 @@ impl<A: JSTraceable, B: JSTraceable> JSTraceable for (A, B) {
 }
 
 
-no_jsmanaged_fields!(bool, f32, f64, String, Url);
+no_jsmanaged_fields!(bool, f32, f64, String, Url, AtomicBool);
 no_jsmanaged_fields!(usize, u8, u16, u32, u64);
 no_jsmanaged_fields!(isize, i8, i16, i32, i64);
 no_jsmanaged_fields!(Sender<T>);
 no_jsmanaged_fields!(Receiver<T>);
+no_jsmanaged_fields!(Point2D<T>);
 no_jsmanaged_fields!(Rect<T>);
 no_jsmanaged_fields!(ImageCacheTask, ScriptControlChan);
+no_jsmanaged_fields!(FontCacheTask);
 no_jsmanaged_fields!(Atom, Namespace, Timer);
 no_jsmanaged_fields!(Trusted<T>);
 no_jsmanaged_fields!(PropertyDeclarationBlock);
+no_jsmanaged_fields!(PseudoElement);
 // These three are interdependent, if you plan to put jsmanaged data
 // in one of these make sure it is propagated properly to containing structs
-no_jsmanaged_fields!(SubpageId, WindowSizeData, PipelineId);
+no_jsmanaged_fields!(SubpageId, WindowSizeData, PipelineId, TopLevelBrowsingContextId);
 no_jsmanaged_fields!(QuirksMode);
 no_jsmanaged_fields!(Cx);
 no_jsmanaged_fields!(Headers, Method);
@@ -227,6 +244,8 @@ no_jsmanaged_fields!(UntrustedNodeAddress);
 no_jsmanaged_fields!(LengthOrPercentageOrAuto);
 no_jsmanaged_fields!(RGBA);
 no_jsmanaged_fields!(Matrix2D<T>);
+no_jsmanaged_fields!(LineCapStyle, LineJoinStyle, CompositionStyle, TextAlign, TextBaseline);
+no_jsmanaged_fields!(StorageType);
 
 impl JSTraceable for Box<ScriptChan+Send> {
     #[inline]
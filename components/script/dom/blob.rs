@@ -76,6 +76,16 @@ impl Blob {
     }
 }
 
+pub trait BlobHelpers {
+    fn get_bytes(self) -> Vec<u8>;
+}
+
+impl<'a> BlobHelpers for JSRef<'a, Blob> {
+    fn get_bytes(self) -> Vec<u8> {
+        self.bytes.clone().unwrap_or(vec!())
+    }
+}
+
 impl<'a> BlobMethods for JSRef<'a, Blob> {
     fn Size(self) -> u64{
         match self.bytes {
@@ -2,18 +2,22 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::CanvasRenderingContext2DBinding;
 use dom::bindings::codegen::Bindings::CanvasRenderingContext2DBinding::CanvasRenderingContext2DMethods;
 use dom::bindings::codegen::Bindings::CanvasRenderingContext2DBinding::CanvasWindingRule;
 use dom::bindings::codegen::Bindings::ImageDataBinding::ImageDataMethods;
+use dom::bindings::codegen::UnionTypes::HTMLImageElementOrHTMLCanvasElement;
 use dom::bindings::codegen::UnionTypes::StringOrCanvasGradientOrCanvasPattern;
-use dom::bindings::error::Error::IndexSize;
-use dom::bindings::error::Fallible;
+use dom::bindings::error::Error::{IndexSize, InvalidState, Security};
+use dom::bindings::error::{ErrorResult, Fallible};
 use dom::bindings::global::{GlobalRef, GlobalField};
 use dom::bindings::js::{JS, JSRef, LayoutJS, Temporary};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
 use dom::htmlcanvaselement::{HTMLCanvasElement, HTMLCanvasElementHelpers};
+use dom::htmlimageelement::{HTMLImageElement, HTMLImageElementHelpers};
 use dom::imagedata::{ImageData, ImageDataHelpers};
+use dom::textmetrics::TextMetrics;
 
 use cssparser::Color as CSSColor;
 use cssparser::{Parser, RGBA, ToCss};
@@ -21,42 +25,103 @@ use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
+use gfx::font::SpecifiedFontStyle;
+use net::fetch::request::is_same_origin;
+use net::image::base::Image;
+use net::image_cache_task::{self, ImageResponseMsg};
+use png::PixelsByColorType;
+use style::computed_values::{font_stretch, font_style, font_variant, font_weight};
+use style::parser::ParserContext;
+use style::properties::longhands::font_family;
+use style::properties::longhands::font_weight::SpecifiedValue as SpecifiedFontWeight;
+use style::properties::shorthands::font;
+use style::stylesheets::Origin;
+use style::values::specified;
+use url::Url;
+use util::geometry::Au;
+use util::str::DOMString;
 
 use canvas::canvas_paint_task::{CanvasMsg, CanvasPaintTask, FillOrStrokeStyle};
+use canvas::canvas_paint_task::{LineCapStyle, LineJoinStyle, CompositionStyle};
+use canvas::canvas_paint_task::{TextAlign, TextBaseline};
 
-use std::cell::Cell;
+use std::borrow::ToOwned;
 use std::num::{Float, ToPrimitive};
+use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender};
 
+/// The font used until the `font` attribute is set.
+const DEFAULT_FONT: &'static str = "10px sans-serif";
+
+/// The part of the drawing state that is reflected by attributes of the context, and
+/// so is mirrored here rather than queried from the paint task.
+/// http://www.whatwg.org/html/#the-canvas-state
+#[jstraceable]
+#[derive(Clone)]
+struct CanvasContextState {
+    stroke_color: RGBA,
+    fill_color: RGBA,
+    line_width: f64,
+    line_cap: LineCapStyle,
+    line_join: LineJoinStyle,
+    miter_limit: f64,
+    global_alpha: f64,
+    global_composition: CompositionStyle,
+    transform: Matrix2D<f32>,
+    font: DOMString,
+    text_align: TextAlign,
+    text_baseline: TextBaseline,
+}
+
+impl CanvasContextState {
+    fn new() -> CanvasContextState {
+        let black = RGBA {
+            red: 0.0,
+            green: 0.0,
+            blue: 0.0,
+            alpha: 1.0,
+        };
+        CanvasContextState {
+            stroke_color: black,
+            fill_color: black,
+            line_width: 1.0,
+            line_cap: LineCapStyle::Butt,
+            line_join: LineJoinStyle::Miter,
+            miter_limit: 10.0,
+            global_alpha: 1.0,
+            global_composition: CompositionStyle::SrcOver,
+            transform: Matrix2D::identity(),
+            font: DEFAULT_FONT.to_owned(),
+            text_align: TextAlign::Start,
+            text_baseline: TextBaseline::Alphabetic,
+        }
+    }
+}
+
 #[dom_struct]
 pub struct CanvasRenderingContext2D {
     reflector_: Reflector,
     global: GlobalField,
     renderer: Sender<CanvasMsg>,
     canvas: JS<HTMLCanvasElement>,
-    stroke_color: Cell<RGBA>,
-    fill_color: Cell<RGBA>,
-    transform: Cell<Matrix2D<f32>>,
+    state: DOMRefCell<CanvasContextState>,
+    saved_states: DOMRefCell<Vec<CanvasContextState>>,
 }
 
 impl CanvasRenderingContext2D {
     fn new_inherited(global: GlobalRef, canvas: JSRef<HTMLCanvasElement>, size: Size2D<i32>)
                      -> CanvasRenderingContext2D {
-        let black = RGBA {
-            red: 0.0,
-            green: 0.0,
-            blue: 0.0,
-            alpha: 1.0,
-        };
-        CanvasRenderingContext2D {
+        let font_cache_task = global.as_window().page().font_cache_task.clone();
+        let context = CanvasRenderingContext2D {
             reflector_: Reflector::new(),
             global: GlobalField::from_rooted(&global),
-            renderer: CanvasPaintTask::start(size),
+            renderer: CanvasPaintTask::start(size, font_cache_task),
             canvas: JS::from_rooted(canvas),
-            stroke_color: Cell::new(black),
-            fill_color: Cell::new(black),
-            transform: Cell::new(Matrix2D::identity()),
-        }
+            state: DOMRefCell::new(CanvasContextState::new()),
+            saved_states: DOMRefCell::new(vec!()),
+        };
+        context.set_default_font();
+        context
     }
 
     pub fn new(global: GlobalRef, canvas: JSRef<HTMLCanvasElement>, size: Size2D<i32>)
@@ -65,12 +130,153 @@ impl CanvasRenderingContext2D {
                            global, CanvasRenderingContext2DBinding::Wrap)
     }
 
+    /// Resizes the canvas bitmap, which also resets the drawing state.
     pub fn recreate(&self, size: Size2D<i32>) {
         self.renderer.send(CanvasMsg::Recreate(size)).unwrap();
+        *self.state.borrow_mut() = CanvasContextState::new();
+        self.saved_states.borrow_mut().clear();
+        self.set_default_font();
+    }
+
+    /// Returns the B8G8R8A8 pixels of the canvas bitmap.
+    pub fn get_pixel_contents(&self) -> Vec<u8> {
+        let (sender, receiver) = channel::<Vec<u8>>();
+        self.renderer.send(CanvasMsg::SendPixelContents(sender)).unwrap();
+        receiver.recv().unwrap()
     }
 
     fn update_transform(&self) {
-        self.renderer.send(CanvasMsg::SetTransform(self.transform.get())).unwrap()
+        self.renderer.send(CanvasMsg::SetTransform(self.state.borrow().transform)).unwrap()
+    }
+
+    fn set_transform(&self, transform: Matrix2D<f32>) {
+        self.state.borrow_mut().transform = transform;
+        self.update_transform()
+    }
+
+    fn set_default_font(&self) {
+        let style = parse_font(DEFAULT_FONT, &Url::parse("about:blank").unwrap()).unwrap();
+        self.renderer.send(CanvasMsg::SetFont(Arc::new(style))).unwrap();
+    }
+
+    /// Returns the B8G8R8A8 pixels and the size of an image source, or `None` if the image
+    /// is not completely available yet.
+    fn fetch_image_data(&self, image: &HTMLImageElementOrHTMLCanvasElement)
+                        -> Fallible<Option<(Vec<u8>, Size2D<i32>)>> {
+        match *image {
+            HTMLImageElementOrHTMLCanvasElement::eHTMLImageElement(ref image) => {
+                let image = image.root();
+                let url = match image.r().get_url() {
+                    Some(url) => url,
+                    None => return Ok(None),
+                };
+                match self.fetch_image_from_cache(url) {
+                    Some((data, size, final_url)) => {
+                        // Redirects may have taken the image to another origin.
+                        if !self.is_same_origin(&final_url) {
+                            self.canvas.root().r().taint();
+                        }
+                        Ok(Some((data, size)))
+                    }
+                    None => Ok(None),
+                }
+            }
+            HTMLImageElementOrHTMLCanvasElement::eHTMLCanvasElement(ref canvas) => {
+                let canvas = canvas.root();
+                // Step 2: drawing a canvas with no pixels is an error.
+                let size = canvas.r().get_size();
+                if size.width == 0 || size.height == 0 {
+                    return Err(InvalidState)
+                }
+                if !canvas.r().is_origin_clean() {
+                    self.canvas.root().r().taint();
+                }
+                let data = match canvas.r().fetch_all_data() {
+                    Some(data) => data,
+                    None => {
+                        // A canvas without a context is transparent black.
+                        let len = (size.width * size.height * 4) as usize;
+                        range(0, len).map(|_| 0u8).collect()
+                    }
+                };
+                Ok(Some((data, size)))
+            }
+        }
+    }
+
+    /// Whether an image from `url` can be drawn without tainting the canvas.
+    fn is_same_origin(&self, url: &Url) -> bool {
+        if url.scheme.as_slice() == "data" {
+            return true
+        }
+        let origin = self.global.root().r().get_url();
+        is_same_origin(url, &origin)
+    }
+
+    /// Returns the pixels and the size of the image at `url`, along with the URL it was
+    /// loaded from after redirects.
+    fn fetch_image_from_cache(&self, url: Url) -> Option<(Vec<u8>, Size2D<i32>, Url)> {
+        let global = self.global.root();
+        let global = global.r();
+        let window = global.as_window();
+        let image_cache = window.image_cache_task();
+        let (sender, receiver) = channel();
+        image_cache.send(image_cache_task::Msg::Decode(url.clone()));
+        image_cache.send(image_cache_task::Msg::GetImage(url.clone(), sender));
+        match receiver.recv().unwrap() {
+            ImageResponseMsg::ImageReady(image) => {
+                let (sender, receiver) = channel();
+                image_cache.send(image_cache_task::Msg::GetFinalUrl(url.clone(), sender));
+                let final_url = receiver.recv().unwrap().unwrap_or(url);
+                Some((image_to_bgra(&**image), Size2D(image.width as i32, image.height as i32),
+                      final_url))
+            }
+            ImageResponseMsg::ImageNotReady | ImageResponseMsg::ImageFailed => None,
+        }
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-drawimage
+    fn draw_image(&self, image: HTMLImageElementOrHTMLCanvasElement,
+                  source_rect: Option<Rect<f64>>, dest_origin: Point2D<f64>,
+                  dest_size: Option<Size2D<f64>>) -> ErrorResult {
+        let (data, size) = match try!(self.fetch_image_data(&image)) {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+
+        // Step 1: the source defaults to the whole image and the destination to the
+        // size of the source.
+        let source_rect = source_rect.unwrap_or(Rect(Point2D(0.0, 0.0),
+                                                     Size2D(size.width as f64,
+                                                            size.height as f64)));
+        let dest_rect = Rect(dest_origin, dest_size.unwrap_or(source_rect.size));
+        if [source_rect.origin.x, source_rect.origin.y,
+            source_rect.size.width, source_rect.size.height,
+            dest_rect.origin.x, dest_rect.origin.y,
+            dest_rect.size.width, dest_rect.size.height].iter().any(|v| !v.is_finite()) {
+            return Ok(())
+        }
+
+        // Step 4: nothing is drawn for an empty source or destination.
+        if source_rect.size.width == 0.0 || source_rect.size.height == 0.0 ||
+           dest_rect.size.width == 0.0 || dest_rect.size.height == 0.0 {
+            return Ok(())
+        }
+
+        // Step 3: the parts of the source rectangle outside of the image are not drawn.
+        let image_rect = Rect(Point2D(0.0, 0.0), Size2D(size.width as f64, size.height as f64));
+        let (source_rect, dest_rect) =
+            match clip_source_rect(normalize_rect(source_rect), normalize_rect(dest_rect),
+                                   image_rect) {
+                Some(rects) => rects,
+                None => return Ok(()),
+            };
+
+        self.renderer.send(CanvasMsg::DrawImage(data, size,
+                                                to_f32_rect(dest_rect),
+                                                to_f32_rect(source_rect)))
+                     .unwrap();
+        Ok(())
     }
 }
 
@@ -89,34 +295,86 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
         Temporary::new(self.canvas)
     }
 
+    // http://www.whatwg.org/html/#dom-context-2d-save
+    fn Save(self) {
+        let state = self.state.borrow().clone();
+        self.saved_states.borrow_mut().push(state);
+        self.renderer.send(CanvasMsg::SaveContext).unwrap();
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-restore
+    fn Restore(self) {
+        match self.saved_states.borrow_mut().pop() {
+            Some(state) => {
+                *self.state.borrow_mut() = state;
+                self.renderer.send(CanvasMsg::RestoreContext).unwrap();
+            }
+            None => {}
+        }
+    }
+
     fn Scale(self, x: f64, y: f64) {
-        self.transform.set(self.transform.get().scale(x as f32, y as f32));
-        self.update_transform()
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.scale(x as f32, y as f32))
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-rotate
+    fn Rotate(self, angle: f64) {
+        let (sin, cos) = (angle.sin() as f32, angle.cos() as f32);
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.mul(&Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0)))
     }
 
     fn Translate(self, x: f64, y: f64) {
-        self.transform.set(self.transform.get().translate(x as f32, y as f32));
-        self.update_transform()
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.translate(x as f32, y as f32))
     }
 
     fn Transform(self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
-        self.transform.set(self.transform.get().mul(&Matrix2D::new(a as f32,
-                                                                   b as f32,
-                                                                   c as f32,
-                                                                   d as f32,
-                                                                   e as f32,
-                                                                   f as f32)));
-        self.update_transform()
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.mul(&Matrix2D::new(a as f32,
+                                                        b as f32,
+                                                        c as f32,
+                                                        d as f32,
+                                                        e as f32,
+                                                        f as f32)))
     }
 
     fn SetTransform(self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
-        self.transform.set(Matrix2D::new(a as f32,
+        self.set_transform(Matrix2D::new(a as f32,
                                          b as f32,
                                          c as f32,
                                          d as f32,
                                          e as f32,
-                                         f as f32));
-        self.update_transform()
+                                         f as f32))
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-globalalpha
+    fn GlobalAlpha(self) -> f64 {
+        self.state.borrow().global_alpha
+    }
+
+    fn SetGlobalAlpha(self, alpha: f64) {
+        if !alpha.is_finite() || alpha < 0.0 || alpha > 1.0 {
+            return
+        }
+        self.state.borrow_mut().global_alpha = alpha;
+        self.renderer.send(CanvasMsg::SetGlobalAlpha(alpha as f32)).unwrap()
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-globalcompositeoperation
+    fn GlobalCompositeOperation(self) -> DOMString {
+        self.state.borrow().global_composition.to_str().to_owned()
+    }
+
+    fn SetGlobalCompositeOperation(self, op: DOMString) {
+        match CompositionStyle::from_str(op.as_slice()) {
+            Some(op) => {
+                self.state.borrow_mut().global_composition = op;
+                self.renderer.send(CanvasMsg::SetGlobalComposition(op)).unwrap()
+            }
+            None => {}
+        }
     }
 
     fn FillRect(self, x: f64, y: f64, width: f64, height: f64) {
@@ -146,22 +404,96 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
         self.renderer.send(CanvasMsg::Fill).unwrap();
     }
 
+    // http://www.whatwg.org/html/#dom-context-2d-stroke
+    fn Stroke(self) {
+        self.renderer.send(CanvasMsg::Stroke).unwrap();
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-clip
+    fn Clip(self, _: CanvasWindingRule) {
+        self.renderer.send(CanvasMsg::Clip).unwrap();
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-filltext
+    fn FillText(self, text: DOMString, x: f64, y: f64, max_width: Option<f64>) {
+        if !x.is_finite() || !y.is_finite() || max_width.map_or(false, |w| !w.is_finite()) {
+            return
+        }
+        self.renderer.send(CanvasMsg::FillText(text, Point2D(x as f32, y as f32),
+                                               max_width.map(|w| w as f32))).unwrap();
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-measuretext
+    fn MeasureText(self, text: DOMString) -> Temporary<TextMetrics> {
+        let (sender, receiver) = channel::<f32>();
+        self.renderer.send(CanvasMsg::MeasureText(text, sender)).unwrap();
+        let width = receiver.recv().unwrap();
+        TextMetrics::new(self.global.root().r(), width as f64)
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-drawimage
+    fn DrawImage(self, image: HTMLImageElementOrHTMLCanvasElement,
+                 dx: f64, dy: f64) -> ErrorResult {
+        self.draw_image(image, None, Point2D(dx, dy), None)
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-drawimage
+    fn DrawImage_(self, image: HTMLImageElementOrHTMLCanvasElement,
+                  dx: f64, dy: f64, dw: f64, dh: f64) -> ErrorResult {
+        self.draw_image(image, None, Point2D(dx, dy), Some(Size2D(dw, dh)))
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-drawimage
+    fn DrawImage__(self, image: HTMLImageElementOrHTMLCanvasElement,
+                   sx: f64, sy: f64, sw: f64, sh: f64,
+                   dx: f64, dy: f64, dw: f64, dh: f64) -> ErrorResult {
+        self.draw_image(image, Some(Rect(Point2D(sx, sy), Size2D(sw, sh))),
+                        Point2D(dx, dy), Some(Size2D(dw, dh)))
+    }
+
     fn MoveTo(self, x: f64, y: f64) {
         self.renderer.send(CanvasMsg::MoveTo(Point2D(x as f32, y as f32))).unwrap();
     }
 
+    // http://www.whatwg.org/html/#dom-context-2d-lineto
+    fn LineTo(self, x: f64, y: f64) {
+        self.renderer.send(CanvasMsg::LineTo(Point2D(x as f32, y as f32))).unwrap();
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-rect
+    fn Rect(self, x: f64, y: f64, width: f64, height: f64) {
+        let rect = Rect(Point2D(x as f32, y as f32), Size2D(width as f32, height as f32));
+        self.renderer.send(CanvasMsg::Rect(rect)).unwrap();
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-quadraticcurveto
+    fn QuadraticCurveTo(self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        self.renderer.send(CanvasMsg::QuadraticCurveTo(Point2D(cpx as f32, cpy as f32),
+                                                       Point2D(x as f32, y as f32))).unwrap();
+    }
+
     fn BezierCurveTo(self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
         self.renderer.send(CanvasMsg::BezierCurveTo(Point2D(cp1x as f32, cp1y as f32),
                                                     Point2D(cp2x as f32, cp2y as f32),
                                                     Point2D(x as f32, y as f32))).unwrap();
     }
 
+    // http://www.whatwg.org/html/#dom-context-2d-arc
+    fn Arc(self, x: f64, y: f64, radius: f64, start: f64, end: f64, ccw: bool) -> ErrorResult {
+        if radius < 0.0 {
+            return Err(IndexSize)
+        }
+        self.renderer.send(CanvasMsg::Arc(Point2D(x as f32, y as f32), radius as f32,
+                                          start as f32, end as f32, ccw)).unwrap();
+        Ok(())
+    }
+
     fn StrokeStyle(self) -> StringOrCanvasGradientOrCanvasPattern {
         // FIXME(pcwalton, #4761): This is not spec-compliant. See:
         //
         // https://html.spec.whatwg.org/multipage/scripting.html#serialisation-of-a-colour
         let mut result = String::new();
-        self.stroke_color.get().to_css(&mut result).unwrap();
+        self.state.borrow().stroke_color.to_css(&mut result).unwrap();
         StringOrCanvasGradientOrCanvasPattern::eString(result)
     }
 
@@ -170,7 +502,7 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
             StringOrCanvasGradientOrCanvasPattern::eString(string) => {
                 match parse_color(string.as_slice()) {
                     Ok(rgba) => {
-                        self.stroke_color.set(rgba);
+                        self.state.borrow_mut().stroke_color = rgba;
                         self.renderer
                             .send(CanvasMsg::SetStrokeStyle(FillOrStrokeStyle::Color(rgba)))
                             .unwrap();
@@ -189,7 +521,7 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
         //
         // https://html.spec.whatwg.org/multipage/scripting.html#serialisation-of-a-colour
         let mut result = String::new();
-        self.stroke_color.get().to_css(&mut result).unwrap();
+        self.state.borrow().fill_color.to_css(&mut result).unwrap();
         StringOrCanvasGradientOrCanvasPattern::eString(result)
     }
 
@@ -198,7 +530,7 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
             StringOrCanvasGradientOrCanvasPattern::eString(string) => {
                 match parse_color(string.as_slice()) {
                     Ok(rgba) => {
-                        self.fill_color.set(rgba);
+                        self.state.borrow_mut().fill_color = rgba;
                         self.renderer
                             .send(CanvasMsg::SetFillStyle(FillOrStrokeStyle::Color(rgba)))
                             .unwrap()
@@ -210,6 +542,108 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
         }
     }
 
+    // http://www.whatwg.org/html/#dom-context-2d-linewidth
+    fn LineWidth(self) -> f64 {
+        self.state.borrow().line_width
+    }
+
+    fn SetLineWidth(self, width: f64) {
+        if !width.is_finite() || width <= 0.0 {
+            return
+        }
+        self.state.borrow_mut().line_width = width;
+        self.renderer.send(CanvasMsg::SetLineWidth(width as f32)).unwrap()
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-linecap
+    fn LineCap(self) -> DOMString {
+        self.state.borrow().line_cap.to_str().to_owned()
+    }
+
+    fn SetLineCap(self, cap: DOMString) {
+        match LineCapStyle::from_str(cap.as_slice()) {
+            Some(cap) => {
+                self.state.borrow_mut().line_cap = cap;
+                self.renderer.send(CanvasMsg::SetLineCap(cap)).unwrap()
+            }
+            None => {}
+        }
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-linejoin
+    fn LineJoin(self) -> DOMString {
+        self.state.borrow().line_join.to_str().to_owned()
+    }
+
+    fn SetLineJoin(self, join: DOMString) {
+        match LineJoinStyle::from_str(join.as_slice()) {
+            Some(join) => {
+                self.state.borrow_mut().line_join = join;
+                self.renderer.send(CanvasMsg::SetLineJoin(join)).unwrap()
+            }
+            None => {}
+        }
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-miterlimit
+    fn MiterLimit(self) -> f64 {
+        self.state.borrow().miter_limit
+    }
+
+    fn SetMiterLimit(self, limit: f64) {
+        if !limit.is_finite() || limit <= 0.0 {
+            return
+        }
+        self.state.borrow_mut().miter_limit = limit;
+        self.renderer.send(CanvasMsg::SetMiterLimit(limit as f32)).unwrap()
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-font
+    fn Font(self) -> DOMString {
+        self.state.borrow().font.clone()
+    }
+
+    fn SetFont(self, value: DOMString) {
+        let url = self.global.root().r().get_url();
+        match parse_font(value.as_slice(), &url) {
+            Some(style) => {
+                self.state.borrow_mut().font = value;
+                self.renderer.send(CanvasMsg::SetFont(Arc::new(style))).unwrap()
+            }
+            None => {}
+        }
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-textalign
+    fn TextAlign(self) -> DOMString {
+        self.state.borrow().text_align.to_str().to_owned()
+    }
+
+    fn SetTextAlign(self, align: DOMString) {
+        match TextAlign::from_str(align.as_slice()) {
+            Some(align) => {
+                self.state.borrow_mut().text_align = align;
+                self.renderer.send(CanvasMsg::SetTextAlign(align)).unwrap()
+            }
+            None => {}
+        }
+    }
+
+    // http://www.whatwg.org/html/#dom-context-2d-textbaseline
+    fn TextBaseline(self) -> DOMString {
+        self.state.borrow().text_baseline.to_str().to_owned()
+    }
+
+    fn SetTextBaseline(self, baseline: DOMString) {
+        match TextBaseline::from_str(baseline.as_slice()) {
+            Some(baseline) => {
+                self.state.borrow_mut().text_baseline = baseline;
+                self.renderer.send(CanvasMsg::SetTextBaseline(baseline)).unwrap()
+            }
+            None => {}
+        }
+    }
+
     fn CreateImageData(self, sw: f64, sh: f64) -> Fallible<Temporary<ImageData>> {
         if sw == 0.0 || sh == 0.0 {
             return Err(IndexSize)
@@ -227,6 +661,10 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
             return Err(IndexSize)
         }
 
+        if !self.canvas.root().r().is_origin_clean() {
+            return Err(Security)
+        }
+
         let (sender, receiver) = channel::<Vec<u8>>();
         let dest_rect = Rect(Point2D(sx.to_i32().unwrap(), sy.to_i32().unwrap()), Size2D(sw.to_i32().unwrap(), sh.to_i32().unwrap()));
         let canvas_size = self.canvas.root().r().get_size();
@@ -271,3 +709,110 @@ pub fn parse_color(string: &str) -> Result<RGBA,()> {
     }
 }
 
+
+/// Parses the value of the `font` attribute into the style used to shape text. Relative
+/// sizes and weights are resolved against the default `10px sans-serif` font.
+/// http://www.whatwg.org/html/#dom-context-2d-font
+fn parse_font(string: &str, base_url: &Url) -> Option<SpecifiedFontStyle> {
+    let context = ParserContext::new(Origin::Author, base_url);
+    let mut input = Parser::new(string);
+    let longhands = match font::parse(&context, &mut input) {
+        Ok(longhands) => longhands,
+        Err(()) => return None,
+    };
+    if !input.is_exhausted() {
+        return None
+    }
+
+    let default_size = Au::from_px(10);
+    let font_size = match longhands.font_size {
+        Some(ref size) => match size.0 {
+            specified::Length::Au(size) => size,
+            specified::Length::Em(em) | specified::Length::Rem(em) => default_size.scale_by(em),
+            specified::Length::Ex(ex) => default_size.scale_by(ex * 0.5),
+            specified::Length::ServoCharacterWidth(_) => default_size,
+        },
+        None => default_size,
+    };
+    let font_weight = match longhands.font_weight {
+        Some(SpecifiedFontWeight::Weight100) |
+        Some(SpecifiedFontWeight::Lighter) => font_weight::T::Weight100,
+        Some(SpecifiedFontWeight::Weight200) => font_weight::T::Weight200,
+        Some(SpecifiedFontWeight::Weight300) => font_weight::T::Weight300,
+        Some(SpecifiedFontWeight::Weight400) | None => font_weight::T::Weight400,
+        Some(SpecifiedFontWeight::Weight500) => font_weight::T::Weight500,
+        Some(SpecifiedFontWeight::Weight600) => font_weight::T::Weight600,
+        Some(SpecifiedFontWeight::Weight700) |
+        Some(SpecifiedFontWeight::Bolder) => font_weight::T::Weight700,
+        Some(SpecifiedFontWeight::Weight800) => font_weight::T::Weight800,
+        Some(SpecifiedFontWeight::Weight900) => font_weight::T::Weight900,
+    };
+
+    Some(SpecifiedFontStyle {
+        font_family: longhands.font_family.unwrap_or_else(font_family::get_initial_value),
+        font_style: longhands.font_style.unwrap_or(font_style::T::normal),
+        font_variant: longhands.font_variant.unwrap_or(font_variant::T::normal),
+        font_weight: font_weight,
+        font_size: font_size,
+        font_stretch: font_stretch::T::normal,
+    })
+}
+
+/// Converts the pixels of a decoded image to premultiplied B8G8R8A8.
+fn image_to_bgra(image: &Image) -> Vec<u8> {
+    match image.pixels {
+        // The image cache has already swapped and premultiplied these.
+        PixelsByColorType::RGBA8(ref pixels) => pixels.clone(),
+        // ...and swapped these.
+        PixelsByColorType::RGB8(ref pixels) => {
+            pixels.as_slice().chunks(3).flat_map(|bgr| {
+                vec![bgr[0], bgr[1], bgr[2], 255].into_iter()
+            }).collect()
+        }
+        PixelsByColorType::K8(ref pixels) => {
+            pixels.iter().flat_map(|&k| vec![k, k, k, 255].into_iter()).collect()
+        }
+        PixelsByColorType::KA8(ref pixels) => {
+            pixels.as_slice().chunks(2).flat_map(|ka| {
+                let k = ((ka[0] as u32) * (ka[1] as u32) / 255) as u8;
+                vec![k, k, k, ka[1]].into_iter()
+            }).collect()
+        }
+    }
+}
+
+fn normalize_rect(rect: Rect<f64>) -> Rect<f64> {
+    let (x, width) = if rect.size.width < 0.0 {
+        (rect.origin.x + rect.size.width, -rect.size.width)
+    } else {
+        (rect.origin.x, rect.size.width)
+    };
+    let (y, height) = if rect.size.height < 0.0 {
+        (rect.origin.y + rect.size.height, -rect.size.height)
+    } else {
+        (rect.origin.y, rect.size.height)
+    };
+    Rect(Point2D(x, y), Size2D(width, height))
+}
+
+/// Clips `source_rect` to `image_rect`, and `dest_rect` in the same proportion, or returns
+/// `None` if they don't intersect.
+fn clip_source_rect(source_rect: Rect<f64>, dest_rect: Rect<f64>, image_rect: Rect<f64>)
+                    -> Option<(Rect<f64>, Rect<f64>)> {
+    let clipped_source = match source_rect.intersection(&image_rect) {
+        Some(rect) => rect,
+        None => return None,
+    };
+    let scale_x = dest_rect.size.width / source_rect.size.width;
+    let scale_y = dest_rect.size.height / source_rect.size.height;
+    let clipped_dest =
+        Rect(Point2D(dest_rect.origin.x + (clipped_source.origin.x - source_rect.origin.x) * scale_x,
+                     dest_rect.origin.y + (clipped_source.origin.y - source_rect.origin.y) * scale_y),
+             Size2D(clipped_source.size.width * scale_x, clipped_source.size.height * scale_y));
+    Some((clipped_source, clipped_dest))
+}
+
+fn to_f32_rect(rect: Rect<f64>) -> Rect<f32> {
+    Rect(Point2D(rect.origin.x as f32, rect.origin.y as f32),
+         Size2D(rect.size.width as f32, rect.size.height as f32))
+}
@@ -12,7 +12,9 @@ use dom::bindings::error::Error::IndexSize;
 use dom::bindings::js::JSRef;
 use dom::document::Document;
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
+use dom::mutationobserver::{Mutation, queue_mutation_record};
 use dom::node::{Node, NodeHelpers, NodeTypeId};
+use dom::range::RangeHelpers;
 
 use util::str::DOMString;
 
@@ -72,8 +74,8 @@ impl<'a> CharacterDataMethods for JSRef<'a, CharacterData> {
     }
 
     fn SetData(self, arg: DOMString) -> ErrorResult {
-        *self.data.borrow_mut() = arg;
-        Ok(())
+        let length = self.Length();
+        self.ReplaceData(0, length, arg)
     }
 
     fn Length(self) -> u32 {
@@ -85,8 +87,8 @@ impl<'a> CharacterDataMethods for JSRef<'a, CharacterData> {
     }
 
     fn AppendData(self, arg: DOMString) -> ErrorResult {
-        self.data.borrow_mut().push_str(arg.as_slice());
-        Ok(())
+        let length = self.Length();
+        self.ReplaceData(length, 0, arg)
     }
 
     fn InsertData(self, offset: u32, arg: DOMString) -> ErrorResult {
@@ -107,11 +109,20 @@ impl<'a> CharacterDataMethods for JSRef<'a, CharacterData> {
         } else {
             count
         };
+        let arg_length = arg.chars().count() as u32;
+
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        queue_mutation_record(node, Mutation::CharacterData(self.data.borrow().clone()));
+
         let mut data = self.data.borrow().slice_chars(0, offset as usize).to_owned();
         data.push_str(arg.as_slice());
         data.push_str(&self.data.borrow().slice_chars((offset + count) as usize, length as usize));
         *self.data.borrow_mut() = data;
-        // FIXME: Once we have `Range`, we should implement step7 to step11
+
+        // Step 7-10.
+        for range in node.ranges().into_iter() {
+            range.root().r().update_for_replace_data(node, offset, count, arg_length);
+        }
         Ok(())
     }
 
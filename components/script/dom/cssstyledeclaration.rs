@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::Bindings::CSSStyleDeclarationBinding::{self, CSSStyleDeclarationMethods};
-use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast};
+use dom::bindings::codegen::InheritTypes::NodeCast;
 use dom::bindings::error::Error;
 use dom::bindings::error::ErrorResult;
 use dom::bindings::error::Fallible;
@@ -12,13 +12,18 @@ use dom::bindings::js::{JS, JSRef, OptionalRootedRootable, Temporary};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
 use dom::document::DocumentHelpers;
 use dom::element::{Element, ElementHelpers, StylePriority};
-use dom::htmlelement::HTMLElement;
-use dom::node::{window_from_node, document_from_node, NodeDamage, Node};
+use dom::node::{window_from_node, document_from_node, NodeDamage, Node, NodeHelpers};
 use dom::window::Window;
+use layout_interface::ComputedStyleResponse;
+use util::geometry::Au;
 use util::str::DOMString;
+
+use cssparser::ToCss;
+use geom::size::Size2D;
+use selectors::parser::PseudoElement;
 use string_cache::Atom;
 use style::properties::{is_supported_property, longhands_from_shorthand, parse_style_attribute};
-use style::properties::PropertyDeclaration;
+use style::properties::{ComputedValues, PropertyDeclaration};
 
 use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
@@ -26,7 +31,10 @@ use std::borrow::ToOwned;
 #[dom_struct]
 pub struct CSSStyleDeclaration {
     reflector_: Reflector,
-    owner: JS<HTMLElement>,
+    owner: JS<Element>,
+    /// The pseudo-element whose style a computed style declaration describes, if any.
+    pseudo: Option<PseudoElement>,
+    /// Whether this is a read-only computed style declaration.
     readonly: bool,
 }
 
@@ -43,7 +51,8 @@ macro_rules! css_properties(
                 self.GetPropertyValue($cssprop.to_owned())
             }
             fn $setter(self, value: DOMString) {
-                self.SetPropertyValue($cssprop.to_owned(), value).unwrap();
+                // Setting a property of a read-only declaration is a no-op.
+                let _ = self.SetPropertyValue($cssprop.to_owned(), value);
             }
         )*
     );
@@ -62,17 +71,108 @@ fn serialize_value(declaration: &PropertyDeclaration) -> DOMString {
     declaration.value()
 }
 
+/// The longhands exposed by a computed style declaration.
+static COMPUTED_PROPERTIES: &'static [&'static str] = &[
+    "background-color",
+    "border-bottom-color", "border-bottom-style", "border-bottom-width",
+    "border-left-color", "border-left-style", "border-left-width",
+    "border-right-color", "border-right-style", "border-right-width",
+    "border-top-color", "border-top-style", "border-top-width",
+    "bottom", "box-sizing", "clear", "color", "content", "direction", "display", "float",
+    "font-size", "font-style", "font-weight", "height", "left",
+    "margin-bottom", "margin-left", "margin-right", "margin-top",
+    "max-height", "max-width", "min-height", "min-width", "opacity", "overflow",
+    "padding-bottom", "padding-left", "padding-right", "padding-top",
+    "position", "right", "text-align", "top", "visibility", "white-space", "width", "z-index",
+];
+
+/// Serializes the resolved value of `property`. `used_size` is the used size of the content
+/// box, if the element or pseudo-element generated any boxes.
+/// http://dev.w3.org/csswg/cssom/#resolved-values
+fn serialize_resolved_value(style: &ComputedValues, used_size: Option<Size2D<Au>>,
+                            property: &str) -> Option<DOMString> {
+    let box_style = style.get_box();
+    let border = style.get_border();
+    let margin = style.get_margin();
+    let padding = style.get_padding();
+    let offsets = style.get_positionoffsets();
+    let value = match property {
+        "background-color" =>
+            style.resolve_color(style.get_background().background_color).to_css_string(),
+        "border-bottom-color" => style.resolve_color(border.border_bottom_color).to_css_string(),
+        "border-left-color" => style.resolve_color(border.border_left_color).to_css_string(),
+        "border-right-color" => style.resolve_color(border.border_right_color).to_css_string(),
+        "border-top-color" => style.resolve_color(border.border_top_color).to_css_string(),
+        "border-bottom-style" => border.border_bottom_style.to_css_string(),
+        "border-left-style" => border.border_left_style.to_css_string(),
+        "border-right-style" => border.border_right_style.to_css_string(),
+        "border-top-style" => border.border_top_style.to_css_string(),
+        "border-bottom-width" => border.border_bottom_width.to_css_string(),
+        "border-left-width" => border.border_left_width.to_css_string(),
+        "border-right-width" => border.border_right_width.to_css_string(),
+        "border-top-width" => border.border_top_width.to_css_string(),
+        "bottom" => offsets.bottom.to_css_string(),
+        "box-sizing" => box_style.box_sizing.to_css_string(),
+        "clear" => box_style.clear.to_css_string(),
+        "color" => style.get_color().color.to_css_string(),
+        "content" => box_style.content.to_css_string(),
+        "direction" => style.get_inheritedbox().direction.to_css_string(),
+        "display" => box_style.display.to_css_string(),
+        "float" => box_style.float.to_css_string(),
+        "font-size" => style.get_font().font_size.to_css_string(),
+        "font-style" => style.get_font().font_style.to_css_string(),
+        "font-weight" => style.get_font().font_weight.to_css_string(),
+        // The used value, if the property applies to the element.
+        "height" => match used_size {
+            Some(size) => size.height.to_css_string(),
+            None => box_style.height.to_css_string(),
+        },
+        "left" => offsets.left.to_css_string(),
+        "margin-bottom" => margin.margin_bottom.to_css_string(),
+        "margin-left" => margin.margin_left.to_css_string(),
+        "margin-right" => margin.margin_right.to_css_string(),
+        "margin-top" => margin.margin_top.to_css_string(),
+        "max-height" => box_style.max_height.to_css_string(),
+        "max-width" => box_style.max_width.to_css_string(),
+        "min-height" => box_style.min_height.to_css_string(),
+        "min-width" => box_style.min_width.to_css_string(),
+        "opacity" => format!("{}", style.get_effects().opacity),
+        "overflow" => box_style.overflow.to_css_string(),
+        "padding-bottom" => padding.padding_bottom.to_css_string(),
+        "padding-left" => padding.padding_left.to_css_string(),
+        "padding-right" => padding.padding_right.to_css_string(),
+        "padding-top" => padding.padding_top.to_css_string(),
+        "position" => box_style.position.to_css_string(),
+        "right" => offsets.right.to_css_string(),
+        "text-align" => style.get_inheritedtext().text_align.to_css_string(),
+        "top" => offsets.top.to_css_string(),
+        "visibility" => style.get_inheritedbox().visibility.to_css_string(),
+        "white-space" => style.get_inheritedtext().white_space.to_css_string(),
+        // The used value, if the property applies to the element.
+        "width" => match used_size {
+            Some(size) => size.width.to_css_string(),
+            None => box_style.width.to_css_string(),
+        },
+        "z-index" => box_style.z_index.to_css_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
 impl CSSStyleDeclaration {
-    pub fn new_inherited(owner: JSRef<HTMLElement>, modification_access: CSSModificationAccess) -> CSSStyleDeclaration {
+    pub fn new_inherited(owner: JSRef<Element>, pseudo: Option<PseudoElement>,
+                         modification_access: CSSModificationAccess) -> CSSStyleDeclaration {
         CSSStyleDeclaration {
             reflector_: Reflector::new(),
             owner: JS::from_rooted(owner),
+            pseudo: pseudo,
             readonly: modification_access == CSSModificationAccess::Readonly,
         }
     }
 
-    pub fn new(global: JSRef<Window>, owner: JSRef<HTMLElement>, modification_access: CSSModificationAccess) -> Temporary<CSSStyleDeclaration> {
-        reflect_dom_object(box CSSStyleDeclaration::new_inherited(owner, modification_access),
+    pub fn new(global: JSRef<Window>, owner: JSRef<Element>, pseudo: Option<PseudoElement>,
+               modification_access: CSSModificationAccess) -> Temporary<CSSStyleDeclaration> {
+        reflect_dom_object(box CSSStyleDeclaration::new_inherited(owner, pseudo, modification_access),
                            GlobalRef::Window(global),
                            CSSStyleDeclarationBinding::Wrap)
     }
@@ -81,27 +181,45 @@ impl CSSStyleDeclaration {
 trait PrivateCSSStyleDeclarationHelpers {
     fn get_declaration(self, property: &Atom) -> Option<PropertyDeclaration>;
     fn get_important_declaration(self, property: &Atom) -> Option<PropertyDeclaration>;
+    fn get_computed_value(self, property: &Atom) -> DOMString;
 }
 
 impl<'a> PrivateCSSStyleDeclarationHelpers for JSRef<'a, CSSStyleDeclaration> {
     fn get_declaration(self, property: &Atom) -> Option<PropertyDeclaration> {
         let owner = self.owner.root();
-        let element: JSRef<Element> = ElementCast::from_ref(owner.r());
-        element.get_inline_style_declaration(property).map(|decl| decl.clone())
+        owner.r().get_inline_style_declaration(property).map(|decl| decl.clone())
     }
 
     fn get_important_declaration(self, property: &Atom) -> Option<PropertyDeclaration> {
         let owner = self.owner.root();
-        let element: JSRef<Element> = ElementCast::from_ref(owner.r());
-        element.get_important_inline_style_declaration(property).map(|decl| decl.clone())
+        owner.r().get_important_inline_style_declaration(property).map(|decl| decl.clone())
+    }
+
+    /// Asks layout for the style of the owner, or of its pseudo-element, and serializes the
+    /// resolved value of `property`.
+    fn get_computed_value(self, property: &Atom) -> DOMString {
+        let owner = self.owner.root();
+        let node: JSRef<Node> = NodeCast::from_ref(owner.r());
+        if !node.is_in_doc() {
+            return "".to_owned();
+        }
+
+        let window = window_from_node(owner.r()).root();
+        let ComputedStyleResponse(style, used_size) =
+            window.r().page().computed_style_query(node.to_trusted_node_address(), self.pseudo);
+        style.and_then(|style| serialize_resolved_value(&*style, used_size, property.as_slice()))
+             .unwrap_or("".to_owned())
     }
 }
 
 impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
     fn Length(self) -> u32 {
+        if self.readonly {
+            return COMPUTED_PROPERTIES.len() as u32;
+        }
+
         let owner = self.owner.root();
-        let elem: JSRef<Element> = ElementCast::from_ref(owner.r());
-        let len = match *elem.style_attribute().borrow() {
+        let len = match *owner.r().style_attribute().borrow() {
             Some(ref declarations) => declarations.normal.len() + declarations.important.len(),
             None => 0
         };
@@ -110,9 +228,12 @@ impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
 
     fn Item(self, index: u32) -> DOMString {
         let index = index as usize;
+        if self.readonly {
+            return COMPUTED_PROPERTIES.get(index).map_or("".to_owned(), |&name| name.to_owned());
+        }
+
         let owner = self.owner.root();
-        let elem: JSRef<Element> = ElementCast::from_ref(owner.r());
-        let style_attribute = elem.style_attribute().borrow();
+        let style_attribute = owner.r().style_attribute().borrow();
         let result = style_attribute.as_ref().and_then(|declarations| {
             if index > declarations.normal.len() {
                 declarations.important
@@ -133,6 +254,10 @@ impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
         // Step 1
         let property = Atom::from_slice(property.as_slice().to_ascii_lowercase().as_slice());
 
+        if self.readonly {
+            return self.get_computed_value(&property);
+        }
+
         // Step 2
         let longhand_properties = longhands_from_shorthand(property.as_slice());
         if let Some(longhand_properties) = longhand_properties {
@@ -232,7 +357,7 @@ impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
         }
 
         let owner = self.owner.root();
-        let element: JSRef<Element> = ElementCast::from_ref(owner.r());
+        let element = owner.r();
 
         // Step 8
         for decl in decl_block.normal.iter() {
@@ -274,7 +399,7 @@ impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
         let page = window.page();
         let decl_block = parse_style_attribute(property.as_slice(),
                                                &page.get_url());
-        let element: JSRef<Element> = ElementCast::from_ref(owner.r());
+        let element = owner.r();
 
         // Step 5
         for decl in decl_block.normal.iter() {
@@ -319,7 +444,7 @@ impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
             None => {
                 // Step 5
                 let owner = self.owner.root();
-                let elem: JSRef<Element> = ElementCast::from_ref(owner.r());
+                let elem = owner.r();
                 elem.remove_inline_style_property(property)
             }
         }
@@ -18,8 +18,9 @@ use dom::bindings::utils::Reflectable;
 use dom::errorevent::ErrorEvent;
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
 use dom::messageevent::MessageEvent;
+use dom::messageport::{MessagePort, MessagePortHelpers};
 use dom::worker::{TrustedWorkerAddress, WorkerMessageHandler, WorkerEventHandler, Worker};
-use dom::workerglobalscope::{WorkerGlobalScope, WorkerGlobalScopeHelpers};
+use dom::workerglobalscope::{WorkerGlobalScope, WorkerGlobalScopeHelpers, WorkerRuntime};
 use dom::workerglobalscope::WorkerGlobalScopeTypeId;
 use script_task::{ScriptTask, ScriptChan, ScriptMsg, TimerSource};
 use script_task::ScriptMsg::WorkerDispatchErrorEvent;
@@ -34,7 +35,10 @@ use js::jsapi::JSContext;
 use js::jsval::JSVal;
 use js::rust::Cx;
 
+use std::borrow::ToOwned;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::{Sender, Receiver};
 use url::Url;
 
@@ -104,11 +108,13 @@ impl DedicatedWorkerGlobalScope {
                          resource_task: ResourceTask,
                          parent_sender: Box<ScriptChan+Send>,
                          own_sender: Sender<(TrustedWorkerAddress, ScriptMsg)>,
-                         receiver: Receiver<(TrustedWorkerAddress, ScriptMsg)>)
+                         receiver: Receiver<(TrustedWorkerAddress, ScriptMsg)>,
+                         closing: Arc<AtomicBool>)
                          -> DedicatedWorkerGlobalScope {
         DedicatedWorkerGlobalScope {
             workerglobalscope: WorkerGlobalScope::new_inherited(
-                WorkerGlobalScopeTypeId::DedicatedGlobalScope, worker_url, cx, resource_task),
+                WorkerGlobalScopeTypeId::DedicatedGlobalScope, worker_url, cx, resource_task,
+                closing),
             receiver: receiver,
             own_sender: own_sender,
             parent_sender: parent_sender,
@@ -121,11 +127,12 @@ impl DedicatedWorkerGlobalScope {
                resource_task: ResourceTask,
                parent_sender: Box<ScriptChan+Send>,
                own_sender: Sender<(TrustedWorkerAddress, ScriptMsg)>,
-               receiver: Receiver<(TrustedWorkerAddress, ScriptMsg)>)
+               receiver: Receiver<(TrustedWorkerAddress, ScriptMsg)>,
+               closing: Arc<AtomicBool>)
                -> Temporary<DedicatedWorkerGlobalScope> {
         let scope = box DedicatedWorkerGlobalScope::new_inherited(
             worker_url, cx.clone(), resource_task, parent_sender,
-            own_sender, receiver);
+            own_sender, receiver, closing);
         DedicatedWorkerGlobalScopeBinding::Wrap(cx.ptr, scope)
     }
 }
@@ -136,7 +143,9 @@ impl DedicatedWorkerGlobalScope {
                             resource_task: ResourceTask,
                             parent_sender: Box<ScriptChan+Send>,
                             own_sender: Sender<(TrustedWorkerAddress, ScriptMsg)>,
-                            receiver: Receiver<(TrustedWorkerAddress, ScriptMsg)>) {
+                            receiver: Receiver<(TrustedWorkerAddress, ScriptMsg)>,
+                            closing: Arc<AtomicBool>,
+                            runtime: WorkerRuntime) {
         spawn_named(format!("WebWorker for {}", worker_url.serialize()), move || {
             task_state::initialize(SCRIPT | IN_WORKER);
 
@@ -155,12 +164,15 @@ impl DedicatedWorkerGlobalScope {
                 }
             };
 
-            let (_js_runtime, js_context) = ScriptTask::new_rt_and_cx();
+            let (js_runtime, js_context) = ScriptTask::new_rt_and_cx();
             let global = DedicatedWorkerGlobalScope::new(
                 worker_url, js_context.clone(), resource_task,
-                parent_sender, own_sender, receiver).root();
+                parent_sender, own_sender, receiver, closing.clone()).root();
+            let _attached = runtime.attach(js_runtime.ptr, js_context.ptr, closing);
 
-            {
+            // The worker may have been terminated before its runtime could be interrupted.
+            let scope: JSRef<WorkerGlobalScope> = WorkerGlobalScopeCast::from_ref(global.r());
+            if !scope.is_closing() {
                 let _ar = AutoWorkerReset::new(global.r(), worker);
 
                 match js_context.evaluate_script(
@@ -170,9 +182,13 @@ impl DedicatedWorkerGlobalScope {
                 }
             }
 
-            loop {
+            while !scope.is_closing() {
                 match global.r().receiver.recv() {
                     Ok((linked_worker, msg)) => {
+                        // Tasks that were queued before the worker was closed are discarded.
+                        if scope.is_closing() {
+                            break;
+                        }
                         let _ar = AutoWorkerReset::new(global.r(), linked_worker);
                         global.r().handle_event(msg);
                     }
@@ -204,11 +220,15 @@ trait PrivateDedicatedWorkerGlobalScopeHelpers {
 impl<'a> PrivateDedicatedWorkerGlobalScopeHelpers for JSRef<'a, DedicatedWorkerGlobalScope> {
     fn handle_event(self, msg: ScriptMsg) {
         match msg {
-            ScriptMsg::DOMMessage(data) => {
+            ScriptMsg::DOMMessage(data, ports) => {
                 let scope: JSRef<WorkerGlobalScope> = WorkerGlobalScopeCast::from_ref(self);
                 let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
                 let message = data.read(GlobalRef::Worker(scope));
-                MessageEvent::dispatch_jsval(target, GlobalRef::Worker(scope), message);
+                let ports = ports.into_iter().map(|port| {
+                    MessagePort::new(GlobalRef::Worker(scope), port)
+                }).collect();
+                MessageEvent::dispatch_with_ports(target, GlobalRef::Worker(scope),
+                                                  "message".to_owned(), message, ports);
             },
             ScriptMsg::RunnableMsg(runnable) => {
                 runnable.handler()
@@ -240,11 +260,14 @@ impl<'a> PrivateDedicatedWorkerGlobalScopeHelpers for JSRef<'a, DedicatedWorkerG
 }
 
 impl<'a> DedicatedWorkerGlobalScopeMethods for JSRef<'a, DedicatedWorkerGlobalScope> {
-    fn PostMessage(self, cx: *mut JSContext, message: JSVal) -> ErrorResult {
+    // http://www.whatwg.org/html/#dom-dedicatedworkerglobalscope-postmessage
+    fn PostMessage(self, cx: *mut JSContext, message: JSVal, transfer: JSVal) -> ErrorResult {
+        let ports = try!(MessagePort::transfer_list(cx, transfer));
         let data = try!(StructuredCloneData::write(cx, message));
+        let ports = ports.into_iter().map(|port| port.root().r().transfer()).collect();
         let worker = self.worker.borrow().as_ref().unwrap().clone();
         self.parent_sender.send(ScriptMsg::RunnableMsg(
-            box WorkerMessageHandler::new(worker, data))).unwrap();
+            box WorkerMessageHandler::new(worker, data, ports))).unwrap();
         Ok(())
     }
 
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::attr::{Attr, AttrHelpers, AttrValue};
+use dom::bindings::callback::ExceptionHandling::Report;
 use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::DocumentBinding;
 use dom::bindings::codegen::Bindings::DocumentBinding::{DocumentMethods, DocumentReadyState};
@@ -11,7 +12,10 @@ use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
 use dom::bindings::codegen::Bindings::EventTargetBinding::EventTargetMethods;
 use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use dom::bindings::codegen::Bindings::NodeFilterBinding::NodeFilter;
-use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use dom::bindings::codegen::Bindings::PerformanceBinding::PerformanceMethods;
+use dom::bindings::codegen::Bindings::TouchBinding::TouchMethods;
+use dom::bindings::codegen::Bindings::WheelEventBinding::WheelEventConstants;
+use dom::bindings::codegen::Bindings::WindowBinding::{FrameRequestCallback, WindowMethods};
 use dom::bindings::codegen::InheritTypes::{DocumentDerived, EventCast, HTMLElementCast};
 use dom::bindings::codegen::InheritTypes::{HTMLHeadElementCast, TextCast, ElementCast};
 use dom::bindings::codegen::InheritTypes::{DocumentTypeCast, HTMLHtmlElementCast, NodeCast};
@@ -25,7 +29,7 @@ use dom::bindings::error::Error::{NotSupported, InvalidCharacter, Security};
 use dom::bindings::error::Error::{HierarchyRequest, NamespaceError};
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{MutNullableJS, JS, JSRef, LayoutJS, Temporary, TemporaryPushable};
-use dom::bindings::js::{OptionalRootable, RootedReference};
+use dom::bindings::js::{OptionalRootable, Root, RootedReference};
 use dom::bindings::refcounted::Trusted;
 use dom::bindings::utils::reflect_dom_object;
 use dom::bindings::utils::xml_name_type;
@@ -35,7 +39,8 @@ use dom::customevent::CustomEvent;
 use dom::documentfragment::DocumentFragment;
 use dom::documenttype::DocumentType;
 use dom::domimplementation::DOMImplementation;
-use dom::element::{Element, ElementCreator, AttributeHandlers, get_attribute_parts};
+use dom::focusevent::FocusEvent;
+use dom::element::{Element, ElementCreator, ElementHelpers, AttributeHandlers, get_attribute_parts};
 use dom::element::{ElementTypeId, ActivationElementHelpers};
 use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
 use dom::eventtarget::{EventTarget, EventTargetTypeId, EventTargetHelpers};
@@ -55,13 +60,18 @@ use dom::nodelist::NodeList;
 use dom::text::Text;
 use dom::processinginstruction::ProcessingInstruction;
 use dom::range::Range;
+use dom::touch::Touch;
+use dom::touchevent::TouchEvent;
+use dom::touchlist::TouchList;
 use dom::treewalker::TreeWalker;
 use dom::uievent::UIEvent;
+use dom::wheelevent::WheelEvent;
 use dom::window::{Window, WindowHelpers};
 
-use msg::compositor_msg::ScriptListener;
+use msg::compositor_msg::{AnimationState, ScriptListener};
 use msg::constellation_msg::{Key, KeyState, KeyModifiers};
 use msg::constellation_msg::{SUPER, ALT, SHIFT, CONTROL};
+use msg::constellation_msg::{TouchEventType, TouchId};
 use net::resource_task::ControlMsg::{SetCookiesForUrl, GetCookiesForUrl};
 use net::cookie_storage::CookieSource::NonHTTP;
 use script_task::Runnable;
@@ -104,7 +114,7 @@ pub struct Document {
     last_modified: DOMRefCell<Option<DOMString>>,
     encoding_name: DOMRefCell<DOMString>,
     is_html_document: bool,
-    url: Url,
+    url: DOMRefCell<Url>,
     quirks_mode: Cell<QuirksMode>,
     images: MutNullableJS<HTMLCollection>,
     embeds: MutNullableJS<HTMLCollection>,
@@ -120,6 +130,17 @@ pub struct Document {
     focused: MutNullableJS<Element>,
     /// The script element that is currently executing.
     current_script: MutNullableJS<HTMLScriptElement>,
+    /// http://w3c.github.io/animation-timing/#dfn-animation-frame-request-callback-identifier
+    animation_frame_ident: Cell<i32>,
+    /// http://w3c.github.io/animation-timing/#dfn-animation-frame-request-callback-list
+    /// A callback is replaced with `None` when its cancelled flag is set.
+    animation_frame_list: DOMRefCell<Vec<(i32, Option<FrameRequestCallback>)>>,
+    /// Whether the animation frame callbacks are being run.
+    running_animation_callbacks: Cell<bool>,
+    /// Whether the document is frozen, in which case the compositor must not tick it.
+    animation_frames_suspended: Cell<bool>,
+    /// The touch points that are currently touching the surface, in the order they started.
+    active_touch_points: DOMRefCell<Vec<JS<Touch>>>,
 }
 
 impl DocumentDerived for EventTarget {
@@ -191,6 +212,7 @@ pub trait DocumentHelpers<'a> {
     fn is_html_document(self) -> bool;
     fn is_fully_active(self) -> bool;
     fn url(self) -> Url;
+    fn set_url(self, url: Url);
     fn quirks_mode(self) -> QuirksMode;
     fn set_quirks_mode(self, mode: QuirksMode);
     fn set_last_modified(self, value: DOMString);
@@ -206,15 +228,26 @@ pub trait DocumentHelpers<'a> {
     fn begin_focus_transaction(self);
     fn request_focus(self, elem: JSRef<Element>);
     fn commit_focus_transaction(self);
+    fn unfocus_if_focused(self, elem: JSRef<Element>);
     fn send_title_to_compositor(self);
     fn dirty_all_nodes(self);
     fn handle_click_event(self, js_runtime: *mut JSRuntime, _button: uint, point: Point2D<f32>);
+    fn handle_wheel_event(self, js_runtime: *mut JSRuntime, delta: Point2D<f32>, point: Point2D<f32>);
+    fn handle_scroll_event(self, position: Point2D<f32>);
+    fn fire_scroll_event(self);
+    fn handle_touch_event(self, js_runtime: *mut JSRuntime, event_type: TouchEventType,
+                          identifier: TouchId, point: Point2D<f32>) -> bool;
     fn dispatch_key_event(self, key: Key, state: KeyState,
         modifiers: KeyModifiers, compositor: &mut Box<ScriptListener+'static>);
     /// Return need force reflow or not
     fn handle_mouse_move_event(self, js_runtime: *mut JSRuntime, point: Point2D<f32>,
                                prev_mouse_over_targets: &mut Vec<JS<Node>>) -> bool;
     fn set_current_script(self, script: Option<JSRef<HTMLScriptElement>>);
+    fn request_animation_frame(self, callback: FrameRequestCallback) -> i32;
+    fn cancel_animation_frame(self, ident: i32);
+    fn run_the_animation_frame_callbacks(self);
+    fn suspend_animation_frames(self);
+    fn resume_animation_frames(self);
 }
 
 impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
@@ -250,7 +283,13 @@ impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
 
     // http://dom.spec.whatwg.org/#dom-document-url
     fn url(self) -> Url {
-        self.url.clone()
+        self.url.borrow().clone()
+    }
+
+    /// Changes the URL of this document, after `pushState`, `replaceState` or a fragment
+    /// navigation.
+    fn set_url(self, url: Url) {
+        *self.url.borrow_mut() = url;
     }
 
     fn quirks_mode(self) -> QuirksMode {
@@ -408,9 +447,35 @@ impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
 
     /// Reassign the focus context to the element that last requested focus during this
     /// transaction, or none if no elements requested it.
+    // https://html.spec.whatwg.org/multipage/#focus-update-steps
     fn commit_focus_transaction(self) {
-        //TODO: dispatch blur, focus, focusout, and focusin events
-        self.focused.assign(self.possibly_focused.get());
+        let old_focused = self.focused.get().root();
+        let new_focused = self.possibly_focused.get().root();
+        if old_focused.r() == new_focused.r() {
+            return;
+        }
+
+        // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#events-focusevent-event-order
+        if let Some(old_focused) = old_focused.r() {
+            self.fire_focus_event("blur", old_focused, new_focused.r(), false);
+            self.fire_focus_event("focusout", old_focused, new_focused.r(), true);
+        }
+
+        self.focused.assign(new_focused.r());
+
+        if let Some(new_focused) = new_focused.r() {
+            self.fire_focus_event("focus", new_focused, old_focused.r(), false);
+            self.fire_focus_event("focusin", new_focused, old_focused.r(), true);
+        }
+    }
+
+    /// Moves the focus back to the viewport if the given element, which stopped being a
+    /// focusable area, has it. No events are fired.
+    // https://html.spec.whatwg.org/multipage/#focus-fixup-rule
+    fn unfocus_if_focused(self, elem: JSRef<Element>) {
+        if self.focused.get().root().r() == Some(elem) {
+            self.focused.clear();
+        }
     }
 
     /// Sends this document's title to the compositor.
@@ -465,6 +530,12 @@ impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
                 let window = frame.window.root();
                 let doc = window.r().Document().root();
                 doc.r().begin_focus_transaction();
+                let focusable = node.inclusive_ancestors()
+                                    .filter_map(ElementCast::to_ref)
+                                    .find(|element| element.is_focusable_area());
+                if let Some(focusable) = focusable {
+                    doc.r().request_focus(focusable);
+                }
 
                 // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#event-type-click
                 let x = point.x as i32;
@@ -562,6 +633,155 @@ impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
         needs_reflow
     }
 
+    fn handle_wheel_event(self, js_runtime: *mut JSRuntime, delta: Point2D<f32>, point: Point2D<f32>) {
+        let window = self.window.root();
+        let window = window.r();
+        let node = match window.page().hit_test(&point) {
+            Some(node_address) => node::from_untrusted_node_address(js_runtime, node_address),
+            None => return,
+        }.root();
+
+        let target = match node.r().inclusive_ancestors().filter_map(ElementCast::to_ref).next() {
+            Some(element) => element,
+            None => return,
+        };
+
+        // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#event-type-wheel
+        // The compositor has already scrolled the page, so cancelling the event has no effect.
+        let x = point.x.to_i32().unwrap_or(0);
+        let y = point.y.to_i32().unwrap_or(0);
+        let event = WheelEvent::new(window,
+                                    "wheel".to_owned(),
+                                    true,
+                                    true,
+                                    Some(window),
+                                    0i32,
+                                    x, y, x, y,
+                                    false, false, false, false,
+                                    0i16,
+                                    None,
+                                    delta.x as f64, delta.y as f64, 0.0,
+                                    WheelEventConstants::DOM_DELTA_PIXEL).root();
+        let event: JSRef<Event> = EventCast::from_ref(event.r());
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(target);
+        event.fire(target);
+    }
+
+    /// Records the scroll position the compositor scrolled the page to, and fires a `scroll`
+    /// event if it changed.
+    fn handle_scroll_event(self, position: Point2D<f32>) {
+        let window = self.window.root();
+        let offset = Point2D(position.x as f64, position.y as f64);
+        if window.r().set_scroll_offset(offset) {
+            self.fire_scroll_event();
+        }
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#scrolling-events
+    fn fire_scroll_event(self) {
+        let window = self.window.root();
+        let event = Event::new(GlobalRef::Window(window.r()), "scroll".to_owned(),
+                               EventBubbles::Bubbles,
+                               EventCancelable::NotCancelable).root();
+        // The window is not part of the event path of the document, so dispatch the event
+        // there with the document as its target, as for `load`.
+        let wintarget: JSRef<EventTarget> = EventTargetCast::from_ref(window.r());
+        let doctarget: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        event.r().set_trusted(true);
+        let _ = wintarget.dispatch_event_with_target(doctarget, event.r());
+    }
+
+    /// Dispatches a touch event for the touch point with the given identifier, at the given
+    /// position in the viewport. Returns whether the default action of the event was prevented.
+    fn handle_touch_event(self, js_runtime: *mut JSRuntime, event_type: TouchEventType,
+                          identifier: TouchId, point: Point2D<f32>) -> bool {
+        let window = self.window.root();
+        let window = window.r();
+        let TouchId(identifier) = identifier;
+
+        // http://www.w3.org/TR/touch-events/#widl-Touch-target
+        // A touch point keeps the target it started on, even if it moves out of it.
+        let target: Root<EventTarget> = match event_type {
+            TouchEventType::Down => {
+                let node = match window.page().hit_test(&point) {
+                    Some(node_address) => node::from_untrusted_node_address(js_runtime, node_address),
+                    None => return false,
+                }.root();
+                let el = match node.r().inclusive_ancestors().filter_map(ElementCast::to_ref).next() {
+                    Some(el) => el,
+                    None => return false,
+                };
+                let target: JSRef<EventTarget> = EventTargetCast::from_ref(el);
+                Temporary::from_rooted(target).root()
+            }
+            TouchEventType::Move | TouchEventType::Up | TouchEventType::Cancel => {
+                let touch_points = self.active_touch_points.borrow();
+                let touch = touch_points.iter()
+                                        .map(|touch| touch.root())
+                                        .find(|touch| touch.r().Identifier() == identifier);
+                match touch {
+                    Some(touch) => touch.r().Target().root(),
+                    None => return false,
+                }
+            }
+        };
+
+        let x = point.x.to_i32().unwrap_or(0);
+        let y = point.y.to_i32().unwrap_or(0);
+        let touch = Touch::new(window, identifier, target.r(), x, y, x, y, x, y).root();
+
+        {
+            let mut touch_points = self.active_touch_points.borrow_mut();
+            let index = touch_points.iter()
+                                    .position(|t| t.root().r().Identifier() == identifier);
+            match (event_type, index) {
+                (TouchEventType::Down, None) => touch_points.push(JS::from_rooted(touch.r())),
+                (TouchEventType::Down, Some(index)) |
+                (TouchEventType::Move, Some(index)) => {
+                    touch_points[index] = JS::from_rooted(touch.r());
+                }
+                (TouchEventType::Up, Some(index)) |
+                (TouchEventType::Cancel, Some(index)) => {
+                    touch_points.remove(index);
+                }
+                (_, None) => {}
+            }
+        }
+
+        // http://www.w3.org/TR/touch-events/#list-of-touchevent-types
+        let (type_, cancelable) = match event_type {
+            TouchEventType::Down => ("touchstart", true),
+            TouchEventType::Move => ("touchmove", true),
+            TouchEventType::Up => ("touchend", true),
+            TouchEventType::Cancel => ("touchcancel", false),
+        };
+
+        let active: Vec<Root<Touch>> = self.active_touch_points.borrow().iter()
+                                           .map(|touch| touch.root())
+                                           .collect();
+        let touches: Vec<JSRef<Touch>> = active.iter().map(|touch| touch.r()).collect();
+        let target_touches: Vec<JSRef<Touch>> = touches.iter().map(|touch| *touch).filter(|touch| {
+            touch.Target().root().r() == target.r()
+        }).collect();
+        let touches = TouchList::new(window, touches.as_slice()).root();
+        let target_touches = TouchList::new(window, target_touches.as_slice()).root();
+        let changed_touches = TouchList::new(window, &[touch.r()]).root();
+
+        let event = TouchEvent::new(window,
+                                    type_.to_owned(),
+                                    true,
+                                    cancelable,
+                                    Some(window),
+                                    0i32,
+                                    false, false, false, false,
+                                    touches.r(),
+                                    target_touches.r(),
+                                    changed_touches.r()).root();
+        let event: JSRef<Event> = EventCast::from_ref(event.r());
+        event.fire(target.r());
+        event.DefaultPrevented()
+    }
+
     /// The entry point for all key processing for web content
     fn dispatch_key_event(self, key: Key,
                           state: KeyState,
@@ -620,6 +840,11 @@ impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
             compositor.send_key_event(key, state, modifiers);
         }
 
+        // https://html.spec.whatwg.org/multipage/#sequential-focus-navigation
+        if key == Key::Tab && state != KeyState::Released && !prevented {
+            self.focus_next_element(shift);
+        }
+
         // This behavior is unspecced
         // We are supposed to dispatch synthetic click activation for Space and/or Return,
         // however *when* we do it is up to us
@@ -643,6 +868,84 @@ impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
     fn set_current_script(self, script: Option<JSRef<HTMLScriptElement>>) {
         self.current_script.assign(script);
     }
+
+    // http://w3c.github.io/animation-timing/#dom-windowanimationtiming-requestanimationframe
+    fn request_animation_frame(self, callback: FrameRequestCallback) -> i32 {
+        let ident = self.animation_frame_ident.get() + 1;
+        self.animation_frame_ident.set(ident);
+        let mut list = self.animation_frame_list.borrow_mut();
+        list.push((ident, Some(callback)));
+
+        // The compositor ticks this page every frame until the list is empty again.
+        if list.len() == 1 && !self.animation_frames_suspended.get() {
+            self.set_animation_state(AnimationState::AnimationsPresent);
+        }
+        ident
+    }
+
+    // http://w3c.github.io/animation-timing/#dom-windowanimationtiming-cancelanimationframe
+    fn cancel_animation_frame(self, ident: i32) {
+        let mut list = self.animation_frame_list.borrow_mut();
+        if self.running_animation_callbacks.get() {
+            // The callback may be part of the batch being run, which must then skip it.
+            for entry in list.iter_mut() {
+                if entry.0 == ident {
+                    entry.1 = None;
+                }
+            }
+            return;
+        }
+        let had_callbacks = !list.is_empty();
+        list.retain(|&(callback_ident, _)| callback_ident != ident);
+        if had_callbacks && list.is_empty() {
+            self.set_animation_state(AnimationState::NoAnimationsPresent);
+        }
+    }
+
+    // http://w3c.github.io/animation-timing/#dfn-invoke-callbacks-algorithm
+    fn run_the_animation_frame_callbacks(self) {
+        // Callbacks requested while this batch runs are left for the next frame.
+        let count = self.animation_frame_list.borrow().len();
+        if count == 0 {
+            return;
+        }
+
+        let window = self.window.root();
+        let performance = window.r().Performance().root();
+        let now = performance.r().Now();
+        self.running_animation_callbacks.set(true);
+        for index in range(0, count) {
+            let callback = self.animation_frame_list.borrow_mut()[index].1.take();
+            match callback {
+                Some(callback) => {
+                    let _ = callback.Call_(window.r(), now, Report);
+                }
+                None => (),
+            }
+        }
+        self.running_animation_callbacks.set(false);
+
+        let mut list = self.animation_frame_list.borrow_mut();
+        list.retain(|&(_, ref callback)| callback.is_some());
+        if list.is_empty() {
+            self.set_animation_state(AnimationState::NoAnimationsPresent);
+        }
+    }
+
+    /// Stops the compositor from ticking this document while it is frozen.
+    fn suspend_animation_frames(self) {
+        self.animation_frames_suspended.set(true);
+        if !self.animation_frame_list.borrow().is_empty() {
+            self.set_animation_state(AnimationState::NoAnimationsPresent);
+        }
+    }
+
+    fn resume_animation_frames(self) {
+        self.animation_frames_suspended.set(false);
+        if !self.animation_frame_list.borrow().is_empty() {
+            self.set_animation_state(AnimationState::AnimationsPresent);
+        }
+    }
 }
 
 #[derive(PartialEq)]
@@ -693,7 +996,7 @@ impl Document {
                 }
             },
             last_modified: DOMRefCell::new(None),
-            url: url,
+            url: DOMRefCell::new(url),
             // http://dom.spec.whatwg.org/#concept-document-quirks
             quirks_mode: Cell::new(NoQuirks),
             // http://dom.spec.whatwg.org/#concept-document-encoding
@@ -710,6 +1013,11 @@ impl Document {
             possibly_focused: Default::default(),
             focused: Default::default(),
             current_script: Default::default(),
+            animation_frame_ident: Cell::new(0),
+            animation_frame_list: DOMRefCell::new(vec!()),
+            running_animation_callbacks: Cell::new(false),
+            animation_frames_suspended: Cell::new(false),
+            active_touch_points: DOMRefCell::new(vec!()),
         }
     }
 
@@ -739,6 +1047,10 @@ impl Document {
 trait PrivateDocumentHelpers {
     fn createNodeList<F: Fn(JSRef<Node>) -> bool>(self, callback: F) -> Temporary<NodeList>;
     fn get_html_element(self) -> Option<Temporary<HTMLHtmlElement>>;
+    fn set_animation_state(self, state: AnimationState);
+    fn fire_focus_event(self, type_: &str, target: JSRef<Element>,
+                        related_target: Option<JSRef<Element>>, bubbles: bool);
+    fn focus_next_element(self, backward: bool);
 }
 
 impl<'a> PrivateDocumentHelpers for JSRef<'a, Document> {
@@ -762,6 +1074,67 @@ impl<'a> PrivateDocumentHelpers for JSRef<'a, Document> {
             .and_then(HTMLHtmlElementCast::to_ref)
             .map(Temporary::from_rooted)
     }
+
+    fn set_animation_state(self, state: AnimationState) {
+        let window = self.window.root();
+        let window = window.r();
+        window.compositor().set_animation_state(window.page().id, state);
+    }
+
+    fn fire_focus_event(self, type_: &str, target: JSRef<Element>,
+                        related_target: Option<JSRef<Element>>, bubbles: bool) {
+        let window = self.window.root();
+        let related_target = related_target.map(|element| EventTargetCast::from_ref(element));
+        let event = FocusEvent::new(window.r(), type_.to_owned(), bubbles, false,
+                                    Some(window.r()), 0i32, related_target).root();
+        let event: JSRef<Event> = EventCast::from_ref(event.r());
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(target);
+        event.fire(target);
+    }
+
+    /// Moves the focus to the next element in the sequential focus navigation order, or to
+    /// the previous one if `backward` is set. The navigation wraps around at either end.
+    fn focus_next_element(self, backward: bool) {
+        let root = match self.GetDocumentElement().root() {
+            Some(root) => root,
+            None => return,
+        };
+        let root: JSRef<Node> = NodeCast::from_ref(root.r());
+
+        // Elements with a positive tabindex come first, in increasing tabindex order, then
+        // the other focusable elements in tree order. A negative tabindex means that the
+        // element is not reachable with sequential navigation.
+        let mut candidates: Vec<(i32, JSRef<Element>)> =
+            root.traverse_preorder()
+                .filter_map(ElementCast::to_ref)
+                .filter(|element| element.is_focusable_area())
+                .filter_map(|element| match element.tab_index() {
+                    Some(index) if index < 0 => None,
+                    Some(index) => Some((index, element)),
+                    None => Some((0, element)),
+                })
+                .collect();
+        candidates.sort_by(|&(a, _), &(b, _)| (a == 0, a).cmp(&(b == 0, b)));
+        if candidates.is_empty() {
+            return;
+        }
+
+        let focused = self.get_focused_element().root();
+        let position = focused.r().and_then(|focused| {
+            candidates.iter().position(|&(_, element)| element == focused)
+        });
+        let count = candidates.len();
+        let next = match (position, backward) {
+            (Some(index), false) => (index + 1) % count,
+            (Some(index), true) => (index + count - 1) % count,
+            (None, false) => 0,
+            (None, true) => count - 1,
+        };
+
+        self.begin_focus_transaction();
+        self.request_focus(candidates[next].1);
+        self.commit_focus_transaction();
+    }
 }
 
 trait PrivateClickEventHelpers {
@@ -1012,6 +1385,8 @@ impl<'a> DocumentMethods for JSRef<'a, Document> {
                 UIEvent::new_uninitialized(window.r()))),
             "mouseevents" | "mouseevent" => Ok(EventCast::from_temporary(
                 MouseEvent::new_uninitialized(window.r()))),
+            "focusevent" => Ok(EventCast::from_temporary(
+                FocusEvent::new_uninitialized(window.r()))),
             "customevent" => Ok(EventCast::from_temporary(
                 CustomEvent::new_uninitialized(GlobalRef::Window(window.r())))),
             "htmlevents" | "events" | "event" => Ok(Event::new_uninitialized(
@@ -1020,10 +1395,24 @@ impl<'a> DocumentMethods for JSRef<'a, Document> {
                 KeyboardEvent::new_uninitialized(window.r()))),
             "messageevent" => Ok(EventCast::from_temporary(
                 MessageEvent::new_uninitialized(GlobalRef::Window(window.r())))),
+            "touchevent" => Ok(EventCast::from_temporary(
+                TouchEvent::new_uninitialized(window.r()))),
             _ => Err(NotSupported)
         }
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-document-activeelement
+    fn GetActiveElement(self) -> Option<Temporary<Element>> {
+        // TODO: Step 2.
+        match self.get_focused_element() {
+            Some(element) => Some(element),
+            None => match self.GetBody() {
+                Some(body) => Some(ElementCast::from_temporary(body)),
+                None => self.GetDocumentElement(),
+            }
+        }
+    }
+
     // http://www.whatwg.org/html/#dom-document-lastmodified
     fn LastModified(self) -> DOMString {
         match *self.last_modified.borrow() {
@@ -1034,7 +1423,24 @@ impl<'a> DocumentMethods for JSRef<'a, Document> {
 
     // http://dom.spec.whatwg.org/#dom-document-createrange
     fn CreateRange(self) -> Temporary<Range> {
-        Range::new(self)
+        Range::new_with_doc(self)
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Document-createTouch-Touch-WindowProxy-view-EventTarget-target-long-identifier-long-pageX-long-pageY-long-screenX-long-screenY
+    fn CreateTouch(self, _view: JSRef<Window>, target: JSRef<EventTarget>, identifier: i32,
+                   page_x: i32, page_y: i32, screen_x: i32, screen_y: i32) -> Temporary<Touch> {
+        let window = self.window.root();
+        let client_x = page_x - window.r().ScrollX() as i32;
+        let client_y = page_y - window.r().ScrollY() as i32;
+        Touch::new(window.r(), identifier, target, screen_x, screen_y,
+                   client_x, client_y, page_x, page_y)
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Document-createTouchList-TouchList-Touch-touches
+    fn CreateTouchList(self, touches: Vec<Root<Touch>>) -> Temporary<TouchList> {
+        let window = self.window.root();
+        let touches: Vec<JSRef<Touch>> = touches.iter().map(|touch| touch.r()).collect();
+        TouchList::new(window.r(), touches.as_slice())
     }
 
     // http://dom.spec.whatwg.org/#dom-document-createtreewalker
@@ -5,7 +5,7 @@
 use dom::bindings::codegen::Bindings::DocumentBinding::DocumentReadyState;
 use dom::bindings::codegen::Bindings::DOMParserBinding;
 use dom::bindings::codegen::Bindings::DOMParserBinding::DOMParserMethods;
-use dom::bindings::codegen::Bindings::DOMParserBinding::SupportedType::{Text_html, Text_xml};
+use dom::bindings::codegen::Bindings::DOMParserBinding::SupportedType::Text_html;
 use dom::bindings::error::Fallible;
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JS, JSRef, Temporary};
@@ -14,6 +14,7 @@ use dom::document::{Document, DocumentHelpers, IsHTMLDocument};
 use dom::document::DocumentSource;
 use dom::window::Window;
 use parse::html::{HTMLInput, parse_html};
+use parse::xml::{XMLInput, XMLScripting, parse_xml};
 use util::str::DOMString;
 
 use std::borrow::ToOwned;
@@ -61,12 +62,14 @@ impl<'a> DOMParserMethods for JSRef<'a, DOMParser> {
                 document.r().set_ready_state(DocumentReadyState::Complete);
                 Ok(Temporary::from_rooted(document.r()))
             }
-            Text_xml => {
-                //FIXME: this should probably be FromParser when we actually parse the string (#3756).
-                Ok(Document::new(window.r(), Some(url.clone()),
-                                 IsHTMLDocument::NonHTMLDocument,
-                                 Some(content_type),
-                                 DocumentSource::NotFromParser))
+            _ => {
+                let document = Document::new(window.r(), Some(url.clone()),
+                                             IsHTMLDocument::NonHTMLDocument,
+                                             Some(content_type),
+                                             DocumentSource::FromParser).root();
+                parse_xml(document.r(), XMLInput::InputString(s), &url, XMLScripting::Disabled);
+                document.r().set_ready_state(DocumentReadyState::Complete);
+                Ok(Temporary::from_rooted(document.r()))
             }
         }
     }
@@ -10,11 +10,14 @@ use dom::attr::AttrValue;
 use dom::namednodemap::NamedNodeMap;
 use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::AttrBinding::AttrMethods;
+use dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use dom::bindings::codegen::Bindings::ElementBinding;
 use dom::bindings::codegen::Bindings::ElementBinding::ElementMethods;
 use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
 use dom::bindings::codegen::Bindings::HTMLInputElementBinding::HTMLInputElementMethods;
 use dom::bindings::codegen::Bindings::NamedNodeMapBinding::NamedNodeMapMethods;
+use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
+use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::codegen::InheritTypes::{ElementCast, ElementDerived, EventTargetCast};
 use dom::bindings::codegen::InheritTypes::{HTMLBodyElementDerived, HTMLInputElementCast};
 use dom::bindings::codegen::InheritTypes::{HTMLInputElementDerived, HTMLTableElementCast};
@@ -24,14 +27,16 @@ use dom::bindings::codegen::InheritTypes::{HTMLTableSectionElementDerived, NodeC
 use dom::bindings::codegen::InheritTypes::HTMLAnchorElementCast;
 use dom::bindings::error::{ErrorResult, Fallible};
 use dom::bindings::error::Error::{NamespaceError, InvalidCharacter, Syntax};
+use dom::bindings::error::Error::{NoModificationAllowedError, NotSupported};
 use dom::bindings::js::{MutNullableJS, JS, JSRef, LayoutJS, Temporary, TemporaryPushable};
-use dom::bindings::js::{OptionalRootable, Root};
+use dom::bindings::js::{OptionalRootable, Root, RootedReference};
 use dom::bindings::utils::xml_name_type;
 use dom::bindings::utils::XMLName::{QName, Name, InvalidXMLName};
 use dom::create::create_element;
 use dom::domrect::DOMRect;
 use dom::domrectlist::DOMRectList;
 use dom::document::{Document, DocumentHelpers, LayoutDocumentHelpers};
+use dom::documentfragment::DocumentFragment;
 use dom::domtokenlist::DOMTokenList;
 use dom::event::{Event, EventHelpers};
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
@@ -46,11 +51,14 @@ use dom::htmltablecellelement::{HTMLTableCellElement, HTMLTableCellElementHelper
 use dom::htmltablerowelement::{HTMLTableRowElement, HTMLTableRowElementHelpers};
 use dom::htmltablesectionelement::{HTMLTableSectionElement, HTMLTableSectionElementHelpers};
 use dom::htmltextareaelement::{HTMLTextAreaElement, RawLayoutHTMLTextAreaElementHelpers};
+use dom::mutationobserver::{Mutation, queue_mutation_record};
 use dom::node::{CLICK_IN_PROGRESS, LayoutNodeHelpers, Node, NodeHelpers, NodeTypeId};
 use dom::node::{NodeIterator, document_from_node, NodeDamage};
 use dom::node::{window_from_node};
 use dom::nodelist::NodeList;
 use dom::virtualmethods::{VirtualMethods, vtable_for};
+use dom::window::WindowHelpers;
+use parse::html::parse_html_fragment;
 use devtools_traits::AttrInfo;
 use style::legacy::{SimpleColorAttribute, UnsignedIntegerAttribute, IntegerAttribute, LengthAttribute};
 use selectors::matching::matches;
@@ -58,7 +66,7 @@ use style::properties::{PropertyDeclarationBlock, PropertyDeclaration, parse_sty
 use selectors::parser::parse_author_origin_selector_list_from_str;
 use style;
 use util::namespace;
-use util::str::{DOMString, LengthOrPercentageOrAuto};
+use util::str::{DOMString, LengthOrPercentageOrAuto, parse_integer};
 
 use html5ever::tree_builder::{NoQuirks, LimitedQuirks, Quirks};
 
@@ -414,6 +422,11 @@ pub trait ElementHelpers<'a> {
     fn update_inline_style(self, property_decl: PropertyDeclaration, style_priority: StylePriority);
     fn get_inline_style_declaration(self, property: &Atom) -> Option<PropertyDeclaration>;
     fn get_important_inline_style_declaration(self, property: &Atom) -> Option<PropertyDeclaration>;
+    fn parse_fragment(self, markup: DOMString) -> Fallible<Temporary<DocumentFragment>>;
+    fn tab_index(self) -> Option<i32>;
+    fn is_focusable_by_default(self) -> bool;
+    fn is_focusable_area(self) -> bool;
+    fn scrolls_viewport(self) -> bool;
 }
 
 impl<'a> ElementHelpers<'a> for JSRef<'a, Element> {
@@ -559,6 +572,79 @@ impl<'a> ElementHelpers<'a> for JSRef<'a, Element> {
                         .map(|decl| decl.clone())
         })
     }
+
+    // http://domparsing.spec.whatwg.org/#concept-parse-fragment
+    fn parse_fragment(self, markup: DOMString) -> Fallible<Temporary<DocumentFragment>> {
+        // Step 1-2.
+        let context_document = document_from_node(self).root();
+        if !context_document.r().is_html_document() {
+            // FIXME: the XML fragment parsing algorithm is not implemented.
+            return Err(NotSupported);
+        }
+        let new_children = parse_html_fragment(self, markup);
+
+        // Step 3.
+        let fragment = DocumentFragment::new(context_document.r()).root();
+
+        // Step 4.
+        let fragment_node: JSRef<Node> = NodeCast::from_ref(fragment.r());
+        for child in new_children.into_iter() {
+            let child = child.root();
+            try!(fragment_node.AppendChild(child.r()));
+        }
+
+        // Step 5.
+        Ok(Temporary::from_rooted(fragment.r()))
+    }
+
+    /// Returns the value of the `tabindex` attribute, if it is a valid integer.
+    // https://html.spec.whatwg.org/multipage/#attr-tabindex
+    fn tab_index(self) -> Option<i32> {
+        self.get_attribute(ns!(""), &atom!("tabindex")).root().and_then(|attr| {
+            parse_integer(attr.r().value().as_slice().chars())
+        })
+    }
+
+    /// Whether this element is focusable without a `tabindex` attribute.
+    fn is_focusable_by_default(self) -> bool {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        match node.type_id() {
+            NodeTypeId::Element(ElementTypeId::HTMLElement(HTMLElementTypeId::HTMLAnchorElement)) =>
+                self.has_attribute(&atom!("href")),
+            NodeTypeId::Element(ElementTypeId::HTMLElement(HTMLElementTypeId::HTMLInputElement)) =>
+                !self.get_string_attribute(&atom!("type")).as_slice().eq_ignore_ascii_case("hidden"),
+            NodeTypeId::Element(ElementTypeId::HTMLElement(HTMLElementTypeId::HTMLButtonElement)) |
+            NodeTypeId::Element(ElementTypeId::HTMLElement(HTMLElementTypeId::HTMLSelectElement)) |
+            NodeTypeId::Element(ElementTypeId::HTMLElement(HTMLElementTypeId::HTMLTextAreaElement)) =>
+                true,
+            _ => false,
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/#focusable-area
+    fn is_focusable_area(self) -> bool {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        if !node.is_in_doc() || node.get_disabled_state() {
+            return false;
+        }
+        self.tab_index().is_some() || self.is_focusable_by_default()
+    }
+
+    /// Whether the scroll position of this element is the one of the viewport, which is
+    /// the case of the root element, or of the body element in quirks mode.
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrolltop step 4-6
+    fn scrolls_viewport(self) -> bool {
+        let document = document_from_node(self).root();
+        let is_root = document.r().GetDocumentElement().root().map_or(false, |root| root.r() == self);
+        let is_body = document.r().GetBody().root().map_or(false, |body| {
+            let body: JSRef<Element> = ElementCast::from_ref(body.r());
+            body == self
+        });
+        match document.r().quirks_mode() {
+            NoQuirks | LimitedQuirks => is_root,
+            Quirks => is_body,
+        }
+    }
 }
 
 pub trait AttributeHandlers {
@@ -710,14 +796,18 @@ impl<'a> AttributeHandlers for JSRef<'a, Element> {
         match idx {
             None => (),
             Some(idx) => {
+                let node: JSRef<Node> = NodeCast::from_ref(self);
+                let attr = (*self.attrs.borrow())[idx].root();
+                queue_mutation_record(node, Mutation::Attribute(attr.r().local_name(),
+                                                                attr.r().namespace(),
+                                                                Some(attr.r().Value())));
+
                 if namespace == ns!("") {
-                    let attr = (*self.attrs.borrow())[idx].root();
-                    vtable_for(&NodeCast::from_ref(self)).before_remove_attr(attr.r());
+                    vtable_for(&node).before_remove_attr(attr.r());
                 }
 
                 self.attrs.borrow_mut().remove(idx);
 
-                let node: JSRef<Node> = NodeCast::from_ref(self);
                 if node.is_in_doc() {
                     let document = document_from_node(self).root();
                     if local_name == atom!("style") {
@@ -1087,15 +1177,152 @@ impl<'a> ElementMethods for JSRef<'a, Element> {
             rect.origin.x + rect.size.width)
     }
 
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrolltop
+    fn ScrollTop(self) -> f64 {
+        if !self.scrolls_viewport() {
+            // FIXME: scrolling boxes other than the viewport are not supported.
+            return 0.0;
+        }
+        let window = window_from_node(self).root();
+        window.r().ScrollY()
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrolltop
+    fn SetScrollTop(self, y: f64) {
+        if !self.scrolls_viewport() {
+            return;
+        }
+        let window = window_from_node(self).root();
+        window.r().scroll(window.r().ScrollX(), y);
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrollleft
+    fn ScrollLeft(self) -> f64 {
+        if !self.scrolls_viewport() {
+            return 0.0;
+        }
+        let window = window_from_node(self).root();
+        window.r().ScrollX()
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrollleft
+    fn SetScrollLeft(self, x: f64) {
+        if !self.scrolls_viewport() {
+            return;
+        }
+        let window = window_from_node(self).root();
+        window.r().scroll(x, window.r().ScrollY());
+    }
+
     fn GetInnerHTML(self) -> Fallible<DOMString> {
         //XXX TODO: XML case
         Ok(serialize(&mut NodeIterator::new(NodeCast::from_ref(self), false, false)))
     }
 
+    // http://domparsing.spec.whatwg.org/#dom-element-innerhtml
+    fn SetInnerHTML(self, value: DOMString) -> ErrorResult {
+        // Step 1.
+        let fragment = try!(self.parse_fragment(value)).root();
+
+        // Step 2-3.
+        let fragment: JSRef<Node> = NodeCast::from_ref(fragment.r());
+        Node::replace_all(Some(fragment), NodeCast::from_ref(self));
+        Ok(())
+    }
+
     fn GetOuterHTML(self) -> Fallible<DOMString> {
         Ok(serialize(&mut NodeIterator::new(NodeCast::from_ref(self), true, false)))
     }
 
+    // http://domparsing.spec.whatwg.org/#dom-element-outerhtml
+    fn SetOuterHTML(self, value: DOMString) -> ErrorResult {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+
+        // Step 1.
+        let parent = match node.parent_node().root() {
+            // Step 2.
+            None => return Ok(()),
+            Some(parent) => parent,
+        };
+
+        let context = match parent.r().type_id() {
+            // Step 3.
+            NodeTypeId::Document => return Err(NoModificationAllowedError),
+
+            // Step 4.
+            NodeTypeId::DocumentFragment => {
+                let document = document_from_node(self).root();
+                document.r().CreateElement("body".to_owned()).unwrap()
+            }
+            _ => Temporary::from_rooted(ElementCast::to_ref(parent.r()).unwrap()),
+        }.root();
+
+        // Step 5.
+        let fragment = try!(context.r().parse_fragment(value)).root();
+
+        // Step 6.
+        let fragment: JSRef<Node> = NodeCast::from_ref(fragment.r());
+        try!(parent.r().ReplaceChild(fragment, node));
+        Ok(())
+    }
+
+    // http://domparsing.spec.whatwg.org/#dom-element-insertadjacenthtml
+    fn InsertAdjacentHTML(self, position: DOMString, text: DOMString) -> ErrorResult {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        let position = position.as_slice().to_ascii_lowercase();
+
+        // Step 1.
+        let context = match position.as_slice() {
+            "beforebegin" | "afterend" => {
+                match node.parent_node().root() {
+                    Some(ref parent) if parent.r().type_id() != NodeTypeId::Document => {
+                        Temporary::from_rooted(parent.r())
+                    }
+                    _ => return Err(NoModificationAllowedError),
+                }
+            }
+            "afterbegin" | "beforeend" => Temporary::from_rooted(node),
+            _ => return Err(Syntax),
+        }.root();
+
+        // Step 2.
+        let context = match ElementCast::to_ref(context.r()) {
+            Some(element) if !(element.html_element_in_html_document() &&
+                               element.local_name().as_slice() == "html") => {
+                Temporary::from_rooted(element)
+            }
+            _ => {
+                let document = document_from_node(self).root();
+                document.r().CreateElement("body".to_owned()).unwrap()
+            }
+        }.root();
+
+        // Step 3.
+        let fragment = try!(context.r().parse_fragment(text)).root();
+        let fragment: JSRef<Node> = NodeCast::from_ref(fragment.r());
+
+        // Step 4.
+        match position.as_slice() {
+            "beforebegin" => {
+                let parent = node.parent_node().root().unwrap();
+                try!(parent.r().InsertBefore(fragment, Some(node)));
+            }
+            "afterbegin" => {
+                let first_child = node.first_child().root();
+                try!(node.InsertBefore(fragment, first_child.r()));
+            }
+            "beforeend" => {
+                try!(node.AppendChild(fragment));
+            }
+            _ => {
+                let parent = node.parent_node().root().unwrap();
+                let next_sibling = node.next_sibling().root();
+                try!(parent.r().InsertBefore(fragment, next_sibling.r()));
+            }
+        }
+        Ok(())
+    }
+
     // http://dom.spec.whatwg.org/#dom-parentnode-children
     fn Children(self) -> Temporary<HTMLCollection> {
         let window = window_from_node(self).root();
@@ -1303,9 +1530,11 @@ impl<'a> VirtualMethods for JSRef<'a, Element> {
 
         if !tree_in_doc { return; }
 
+        let doc = document_from_node(*self).root();
+        doc.r().unfocus_if_focused(*self);
+
         match self.get_attribute(ns!(""), &atom!("id")).root() {
             Some(attr) => {
-                let doc = document_from_node(*self).root();
                 let value = attr.r().Value();
                 if !value.is_empty() {
                     let value = Atom::from_slice(value.as_slice());
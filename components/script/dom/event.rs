@@ -31,13 +31,20 @@ pub enum EventPhase {
 #[derive(PartialEq)]
 #[jstraceable]
 pub enum EventTypeId {
+    CloseEvent,
     CustomEvent,
+    FocusEvent,
+    HashChangeEvent,
     HTMLEvent,
     KeyboardEvent,
     MessageEvent,
     MouseEvent,
+    PopStateEvent,
     ProgressEvent,
+    StorageEvent,
+    TouchEvent,
     UIEvent,
+    WheelEvent,
     ErrorEvent
 }
 
@@ -9,6 +9,7 @@ use dom::bindings::codegen::Bindings::EventListenerBinding::EventListener;
 use dom::bindings::codegen::Bindings::EventTargetBinding::EventTargetMethods;
 use dom::bindings::error::{Fallible, report_pending_exception};
 use dom::bindings::error::Error::InvalidState;
+use dom::bindings::global::{GlobalRef, global_object_for_js_object};
 use dom::bindings::js::JSRef;
 use dom::bindings::utils::{Reflectable, Reflector};
 use dom::event::{Event, EventHelpers};
@@ -43,7 +44,9 @@ pub enum ListenerPhase {
 #[derive(Copy, PartialEq)]
 #[jstraceable]
 pub enum EventTargetTypeId {
+    MessagePort,
     Node(NodeTypeId),
+    SharedWorker,
     WebSocket,
     Window,
     Worker,
@@ -111,6 +114,21 @@ impl EventTarget {
     }
 }
 
+/// Tells the compositor when a page starts listening for touch events, since until then it
+/// runs its gestures without waiting for script.
+fn note_event_listener(target: JSRef<EventTarget>, ty: &str) {
+    if !ty.starts_with("touch") {
+        return;
+    }
+    let global = global_object_for_js_object(target.reflector().get_jsobject()).root();
+    match global.r() {
+        GlobalRef::Window(window) => {
+            window.compositor().set_has_touch_event_listeners(window.page().id);
+        }
+        GlobalRef::Worker(_) => {}
+    }
+}
+
 pub trait EventTargetHelpers {
     fn dispatch_event_with_target(self,
                                   target: JSRef<EventTarget>,
@@ -147,6 +165,10 @@ impl<'a> EventTargetHelpers for JSRef<'a, EventTarget> {
     fn set_inline_event_listener(self,
                                  ty: DOMString,
                                  listener: Option<EventListener>) {
+        if listener.is_some() {
+            note_event_listener(self, ty.as_slice());
+        }
+
         let mut handlers = self.handlers.borrow_mut();
         let entries = match handlers.entry(ty) {
             Occupied(entry) => entry.into_mut(),
@@ -256,6 +278,8 @@ impl<'a> EventTargetMethods for JSRef<'a, EventTarget> {
                         capture: bool) {
         match listener {
             Some(listener) => {
+                note_event_listener(self, ty.as_slice());
+
                 let mut handlers = self.handlers.borrow_mut();
                 let entry = match handlers.entry(ty) {
                     Occupied(entry) => entry.into_mut(),
@@ -3,11 +3,12 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::Bindings::FileBinding;
+use dom::bindings::codegen::Bindings::BlobBinding::BlobMethods;
 use dom::bindings::codegen::Bindings::FileBinding::FileMethods;
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JSRef, Temporary};
 use dom::bindings::utils::reflect_dom_object;
-use dom::blob::{Blob, BlobTypeId};
+use dom::blob::{Blob, BlobHelpers, BlobTypeId};
 use util::str::DOMString;
 
 #[dom_struct]
@@ -18,14 +19,12 @@ pub struct File {
 
 impl File {
     fn new_inherited(global: GlobalRef, type_: BlobTypeId,
-                     _file_bits: JSRef<Blob>, name: DOMString) -> File {
+                     file_bits: JSRef<Blob>, name: DOMString) -> File {
         File {
-            //TODO: get type from the underlying filesystem instead of "".to_string()
-            blob: Blob::new_inherited(global, type_, None, ""),
+            blob: Blob::new_inherited(global, type_, Some(file_bits.get_bytes()),
+                                      file_bits.Type().as_slice()),
             name: name,
         }
-        // XXXManishearth Once Blob is able to store data
-        // the relevant subfields of file_bits should be copied over
     }
 
     pub fn new(global: GlobalRef, file_bits: JSRef<Blob>, name: DOMString) -> Temporary<File> {
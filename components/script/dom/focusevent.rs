@@ -0,0 +1,78 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::FocusEventBinding;
+use dom::bindings::codegen::Bindings::FocusEventBinding::FocusEventMethods;
+use dom::bindings::codegen::Bindings::UIEventBinding::UIEventMethods;
+use dom::bindings::codegen::InheritTypes::{UIEventCast, FocusEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{MutNullableJS, JSRef, RootedReference, Temporary};
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventTypeId};
+use dom::eventtarget::EventTarget;
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use util::str::DOMString;
+use std::default::Default;
+
+#[dom_struct]
+pub struct FocusEvent {
+    uievent: UIEvent,
+    related_target: MutNullableJS<EventTarget>,
+}
+
+impl FocusEventDerived for Event {
+    fn is_focusevent(&self) -> bool {
+        *self.type_id() == EventTypeId::FocusEvent
+    }
+}
+
+impl FocusEvent {
+    fn new_inherited() -> FocusEvent {
+        FocusEvent {
+            uievent: UIEvent::new_inherited(EventTypeId::FocusEvent),
+            related_target: Default::default(),
+        }
+    }
+
+    pub fn new_uninitialized(window: JSRef<Window>) -> Temporary<FocusEvent> {
+        reflect_dom_object(box FocusEvent::new_inherited(),
+                           GlobalRef::Window(window),
+                           FocusEventBinding::Wrap)
+    }
+
+    pub fn new(window: JSRef<Window>,
+               type_: DOMString,
+               can_bubble: bool,
+               cancelable: bool,
+               view: Option<JSRef<Window>>,
+               detail: i32,
+               related_target: Option<JSRef<EventTarget>>) -> Temporary<FocusEvent> {
+        let ev = FocusEvent::new_uninitialized(window).root();
+        let uievent: JSRef<UIEvent> = UIEventCast::from_ref(ev.r());
+        uievent.InitUIEvent(type_, can_bubble, cancelable, view, detail);
+        ev.r().related_target.assign(related_target);
+        Temporary::from_rooted(ev.r())
+    }
+
+    pub fn Constructor(global: GlobalRef,
+                       type_: DOMString,
+                       init: &FocusEventBinding::FocusEventInit) -> Fallible<Temporary<FocusEvent>> {
+        let event = FocusEvent::new(global.as_window(), type_,
+                                    init.parent.parent.bubbles,
+                                    init.parent.parent.cancelable,
+                                    init.parent.view.r(),
+                                    init.parent.detail,
+                                    init.relatedTarget.r());
+        Ok(event)
+    }
+}
+
+impl<'a> FocusEventMethods for JSRef<'a, FocusEvent> {
+    // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#widl-FocusEvent-relatedTarget
+    fn GetRelatedTarget(self) -> Option<Temporary<EventTarget>> {
+        self.related_target.get()
+    }
+}
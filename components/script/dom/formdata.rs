@@ -3,23 +3,26 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::BlobBinding::BlobMethods;
+use dom::bindings::codegen::Bindings::FileBinding::FileMethods;
 use dom::bindings::codegen::Bindings::FormDataBinding;
 use dom::bindings::codegen::Bindings::FormDataBinding::FormDataMethods;
-use dom::bindings::codegen::InheritTypes::FileCast;
+use dom::bindings::codegen::InheritTypes::{BlobCast, FileCast};
 use dom::bindings::codegen::UnionTypes::FileOrString;
 use dom::bindings::codegen::UnionTypes::FileOrString::{eFile, eString};
 use dom::bindings::error::{Fallible};
 use dom::bindings::global::{GlobalRef, GlobalField};
 use dom::bindings::js::{JS, JSRef, Temporary, Unrooted};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
-use dom::blob::Blob;
+use dom::blob::{Blob, BlobHelpers};
 use dom::file::File;
-use dom::htmlformelement::HTMLFormElement;
+use dom::htmlformelement::{HTMLFormElement, HTMLFormElementHelpers};
+use dom::htmlformelement;
+use dom::htmlformelement::FormDatumValue;
 use util::str::DOMString;
 
 use std::borrow::ToOwned;
-use std::collections::HashMap;
-use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::mem;
 
 #[derive(Clone)]
 #[jstraceable]
@@ -32,7 +35,8 @@ pub enum FormDatum {
 #[dom_struct]
 pub struct FormData {
     reflector_: Reflector,
-    data: DOMRefCell<HashMap<DOMString, Vec<FormDatum>>>,
+    /// The entries, in insertion order.
+    data: DOMRefCell<Vec<(DOMString, FormDatum)>>,
     global: GlobalField,
     form: Option<JS<HTMLFormElement>>
 }
@@ -41,7 +45,7 @@ impl FormData {
     fn new_inherited(form: Option<JSRef<HTMLFormElement>>, global: GlobalRef) -> FormData {
         FormData {
             reflector_: Reflector::new(),
-            data: DOMRefCell::new(HashMap::new()),
+            data: DOMRefCell::new(vec!()),
             global: GlobalField::from_rooted(&global),
             form: form.map(|f| JS::from_rooted(f)),
         }
@@ -52,8 +56,13 @@ impl FormData {
                            global, FormDataBinding::Wrap)
     }
 
+    // https://xhr.spec.whatwg.org/#dom-formdata
     pub fn Constructor(global: GlobalRef, form: Option<JSRef<HTMLFormElement>>) -> Fallible<Temporary<FormData>> {
-        Ok(FormData::new(form, global))
+        let form_data = FormData::new(form, global).root();
+        if let Some(form) = form {
+            form_data.r().append_form_data_set(form);
+        }
+        Ok(Temporary::from_rooted(form_data.r()))
     }
 }
 
@@ -61,61 +70,118 @@ impl<'a> FormDataMethods for JSRef<'a, FormData> {
     #[allow(unrooted_must_root)]
     fn Append(self, name: DOMString, value: JSRef<Blob>, filename: Option<DOMString>) {
         let file = FormDatum::FileData(JS::from_rooted(self.get_file_from_blob(value, filename)));
-        let mut data = self.data.borrow_mut();
-        match data.entry(name) {
-            Occupied(entry) => entry.into_mut().push(file),
-            Vacant(entry) => {
-                entry.insert(vec!(file));
-            }
-        }
+        self.data.borrow_mut().push((name, file));
     }
 
     fn Append_(self, name: DOMString, value: DOMString) {
-        let mut data = self.data.borrow_mut();
-        match data.entry(name) {
-            Occupied(entry) => entry.into_mut().push(FormDatum::StringData(value)),
-            Vacant  (entry) => { entry.insert(vec!(FormDatum::StringData(value))); },
-        }
+        self.data.borrow_mut().push((name, FormDatum::StringData(value)));
     }
 
     fn Delete(self, name: DOMString) {
-        self.data.borrow_mut().remove(&name);
+        self.data.borrow_mut().retain(|&(ref entry_name, _)| *entry_name != name);
     }
 
     #[allow(unsafe_blocks)]
     fn Get(self, name: DOMString) -> Option<FileOrString> {
-        if self.data.borrow().contains_key(&name) {
-            match (*self.data.borrow())[name][0].clone() {
-                FormDatum::StringData(ref s) => Some(eString(s.clone())),
-                FormDatum::FileData(ref f) => {
-                    Some(eFile(Unrooted::from_js(*f)))
-                }
+        let data = self.data.borrow();
+        data.iter().find(|&&(ref entry_name, _)| *entry_name == name).map(|&(_, ref datum)| {
+            match *datum {
+                FormDatum::StringData(ref s) => eString(s.clone()),
+                FormDatum::FileData(ref f) => eFile(Unrooted::from_js(*f)),
             }
-        } else {
-            None
-        }
+        })
     }
 
     fn Has(self, name: DOMString) -> bool {
-        self.data.borrow().contains_key(&name)
+        self.data.borrow().iter().any(|&(ref entry_name, _)| *entry_name == name)
     }
+
     #[allow(unrooted_must_root)]
     fn Set(self, name: DOMString, value: JSRef<Blob>, filename: Option<DOMString>) {
         let file = FormDatum::FileData(JS::from_rooted(self.get_file_from_blob(value, filename)));
-        self.data.borrow_mut().insert(name, vec!(file));
+        self.set(name, file);
     }
 
     fn Set_(self, name: DOMString, value: DOMString) {
-        self.data.borrow_mut().insert(name, vec!(FormDatum::StringData(value)));
+        self.set(name, FormDatum::StringData(value));
     }
 }
 
-trait PrivateFormDataHelpers{
-  fn get_file_from_blob(&self, value: JSRef<Blob>, filename: Option<DOMString>) -> Temporary<File>;
+pub trait FormDataHelpers {
+    fn form_data_set(self) -> Vec<htmlformelement::FormDatum>;
 }
 
-impl PrivateFormDataHelpers for FormData {
-    fn get_file_from_blob(&self, value: JSRef<Blob>, filename: Option<DOMString>) -> Temporary<File> {
+impl<'a> FormDataHelpers for JSRef<'a, FormData> {
+    /// The entries as a form data set, for encoding as a request body.
+    fn form_data_set(self) -> Vec<htmlformelement::FormDatum> {
+        self.data.borrow().iter().map(|&(ref name, ref datum)| {
+            match *datum {
+                FormDatum::StringData(ref value) => htmlformelement::FormDatum {
+                    ty: "text".to_owned(),
+                    name: name.clone(),
+                    value: FormDatumValue::String(value.clone()),
+                },
+                FormDatum::FileData(ref file) => {
+                    let file = file.root();
+                    let blob: JSRef<Blob> = BlobCast::from_ref(file.r());
+                    htmlformelement::FormDatum {
+                        ty: "file".to_owned(),
+                        name: name.clone(),
+                        value: FormDatumValue::File {
+                            name: file.r().Name(),
+                            type_: blob.Type(),
+                            bytes: blob.get_bytes(),
+                        },
+                    }
+                }
+            }
+        }).collect()
+    }
+}
+
+trait PrivateFormDataHelpers {
+    fn set(self, name: DOMString, datum: FormDatum);
+    fn append_form_data_set(self, form: JSRef<HTMLFormElement>);
+    fn get_file_from_blob(self, value: JSRef<Blob>, filename: Option<DOMString>) -> Temporary<File>;
+}
+
+impl<'a> PrivateFormDataHelpers for JSRef<'a, FormData> {
+    // https://xhr.spec.whatwg.org/#dom-formdata-set
+    #[allow(unrooted_must_root)]
+    fn set(self, name: DOMString, datum: FormDatum) {
+        let mut data = self.data.borrow_mut();
+        let entries = mem::replace(&mut *data, vec!());
+        let mut datum = Some(datum);
+        for (entry_name, entry) in entries.into_iter() {
+            if entry_name != name {
+                data.push((entry_name, entry));
+            } else if let Some(datum) = datum.take() {
+                data.push((entry_name, datum));
+            }
+        }
+        if let Some(datum) = datum {
+            data.push((name, datum));
+        }
+    }
+
+    /// Appends the form data set of `form`.
+    #[allow(unrooted_must_root)]
+    fn append_form_data_set(self, form: JSRef<HTMLFormElement>) {
+        let global = self.global.root();
+        for datum in form.get_form_dataset(None).into_iter() {
+            let value = match datum.value {
+                FormDatumValue::String(value) => FormDatum::StringData(value),
+                FormDatumValue::File { name, type_, bytes } => {
+                    let blob = Blob::new(global.r(), Some(bytes), type_.as_slice()).root();
+                    let file = File::new(global.r(), blob.r(), name).root();
+                    FormDatum::FileData(JS::from_rooted(file.r()))
+                }
+            };
+            self.data.borrow_mut().push((datum.name, value));
+        }
+    }
+
+    fn get_file_from_blob(self, value: JSRef<Blob>, filename: Option<DOMString>) -> Temporary<File> {
         let global = self.global.root();
         let f: Option<JSRef<File>> = FileCast::to_ref(value);
         let name = filename.unwrap_or(f.map(|inner| inner.name().clone()).unwrap_or("blob".to_owned()));
@@ -0,0 +1,68 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use dom::bindings::codegen::Bindings::HashChangeEventBinding;
+use dom::bindings::codegen::Bindings::HashChangeEventBinding::HashChangeEventMethods;
+use dom::bindings::codegen::InheritTypes::{EventCast, HashChangeEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventTypeId};
+use util::str::DOMString;
+
+// http://www.whatwg.org/html/#the-hashchangeevent-interface
+#[dom_struct]
+pub struct HashChangeEvent {
+    event: Event,
+    old_url: DOMString,
+    new_url: DOMString,
+}
+
+impl HashChangeEventDerived for Event {
+    fn is_hashchangeevent(&self) -> bool {
+        *self.type_id() == EventTypeId::HashChangeEvent
+    }
+}
+
+impl HashChangeEvent {
+    fn new_inherited(old_url: DOMString, new_url: DOMString) -> HashChangeEvent {
+        HashChangeEvent {
+            event: Event::new_inherited(EventTypeId::HashChangeEvent),
+            old_url: old_url,
+            new_url: new_url,
+        }
+    }
+
+    pub fn new(global: GlobalRef, type_: DOMString,
+               can_bubble: bool, cancelable: bool,
+               old_url: DOMString, new_url: DOMString) -> Temporary<HashChangeEvent> {
+        let ev = reflect_dom_object(box HashChangeEvent::new_inherited(old_url, new_url),
+                                    global,
+                                    HashChangeEventBinding::Wrap).root();
+        let event: JSRef<Event> = EventCast::from_ref(ev.r());
+        event.InitEvent(type_, can_bubble, cancelable);
+        Temporary::from_rooted(ev.r())
+    }
+
+    pub fn Constructor(global: GlobalRef,
+                       type_: DOMString,
+                       init: &HashChangeEventBinding::HashChangeEventInit)
+                       -> Fallible<Temporary<HashChangeEvent>> {
+        let ev = HashChangeEvent::new(global, type_, init.parent.bubbles, init.parent.cancelable,
+                                      init.oldURL.clone(), init.newURL.clone());
+        Ok(ev)
+    }
+}
+
+impl<'a> HashChangeEventMethods for JSRef<'a, HashChangeEvent> {
+    fn OldURL(self) -> DOMString {
+        self.old_url.clone()
+    }
+
+    fn NewURL(self) -> DOMString {
+        self.new_url.clone()
+    }
+}
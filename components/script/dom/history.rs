@@ -0,0 +1,227 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::HistoryBinding;
+use dom::bindings::codegen::Bindings::HistoryBinding::HistoryMethods;
+use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use dom::bindings::codegen::InheritTypes::{EventCast, EventTargetCast};
+use dom::bindings::error::ErrorResult;
+use dom::bindings::error::Error::Security;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::structuredclone::StructuredCloneData;
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::document::DocumentHelpers;
+use dom::event::{Event, EventHelpers};
+use dom::eventtarget::EventTarget;
+use dom::hashchangeevent::HashChangeEvent;
+use dom::popstateevent::PopStateEvent;
+use dom::window::Window;
+use script_task::ScriptMsg;
+use msg::constellation_msg::{ConstellationChan, HistoryState, LoadData};
+use msg::constellation_msg::Msg as ConstellationMsg;
+use net::fetch::request::is_same_origin;
+use util::str::DOMString;
+
+use js::jsapi::JSContext;
+use js::jsval::{JSVal, NullValue};
+use url::{Url, UrlParser};
+
+use std::borrow::ToOwned;
+use std::cell::Cell;
+
+// http://www.whatwg.org/html/#the-history-interface
+#[dom_struct]
+pub struct History {
+    reflector_: Reflector,
+    window: JS<Window>,
+    /// The state object of the current session history entry.
+    state: Cell<JSVal>,
+    /// The serialized form of `state`, as stored in the constellation.
+    serialized_state: DOMRefCell<Option<HistoryState>>,
+}
+
+impl History {
+    fn new_inherited(window: JSRef<Window>) -> History {
+        History {
+            reflector_: Reflector::new(),
+            window: JS::from_rooted(window),
+            state: Cell::new(NullValue()),
+            serialized_state: DOMRefCell::new(None),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>) -> Temporary<History> {
+        reflect_dom_object(box History::new_inherited(window),
+                           GlobalRef::Window(window),
+                           HistoryBinding::Wrap)
+    }
+}
+
+pub trait HistoryHelpers {
+    fn traverse(self, url: Url, state: Option<HistoryState>);
+    fn clear_state(self);
+}
+
+impl<'a> HistoryHelpers for JSRef<'a, History> {
+    /// Makes the session history entry with the given URL and serialized state current,
+    /// firing `popstate` and `hashchange` as appropriate.
+    /// http://www.whatwg.org/html/#traverse-the-history
+    fn traverse(self, url: Url, state: Option<HistoryState>) {
+        let window = self.window.root();
+        let document = window.r().Document().root();
+        let old_url = document.r().url();
+        if old_url == url && *self.serialized_state.borrow() == state {
+            return;
+        }
+
+        // Step 5-6.
+        document.r().set_url(url.clone());
+        let state_changed = *self.serialized_state.borrow() != state;
+        let value = match state {
+            Some(ref data) => StructuredCloneData::read_vec(data.as_slice(),
+                                                            GlobalRef::Window(window.r())),
+            None => NullValue(),
+        };
+        self.state.set(value);
+        *self.serialized_state.borrow_mut() = state;
+
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(window.r());
+
+        // Step 11.
+        if state_changed {
+            let event = PopStateEvent::new(GlobalRef::Window(window.r()),
+                                           "popstate".to_owned(), false, false,
+                                           value).root();
+            let event: JSRef<Event> = EventCast::from_ref(event.r());
+            event.fire(target);
+        }
+
+        // Step 12.
+        if old_url.fragment != url.fragment {
+            fire_hashchange(window.r(), &old_url, &url);
+        }
+    }
+
+    /// Resets the state object, for a new session history entry created by a fragment
+    /// navigation.
+    fn clear_state(self) {
+        self.state.set(NullValue());
+        *self.serialized_state.borrow_mut() = None;
+    }
+}
+
+trait PrivateHistoryHelpers {
+    fn add_state(self, cx: *mut JSContext, data: JSVal, url: Option<DOMString>,
+                 replace: bool) -> ErrorResult;
+}
+
+impl<'a> PrivateHistoryHelpers for JSRef<'a, History> {
+    // http://www.whatwg.org/html/#dom-history-pushstate
+    fn add_state(self, cx: *mut JSContext, data: JSVal, url: Option<DOMString>,
+                 replace: bool) -> ErrorResult {
+        let window = self.window.root();
+        let document = window.r().Document().root();
+
+        // Step 2.
+        let serialized = try!(StructuredCloneData::write(cx, data)).to_vec();
+
+        // Step 3.
+        let document_url = document.r().url();
+        let new_url = match url {
+            Some(url) => {
+                let new_url = match UrlParser::new().base_url(&document_url).parse(url.as_slice()) {
+                    Ok(new_url) => new_url,
+                    Err(_) => return Err(Security),
+                };
+                if !is_same_origin(&new_url, &document_url) {
+                    return Err(Security);
+                }
+                new_url
+            }
+            None => document_url,
+        };
+
+        // Step 4.
+        let page = window.r().page();
+        let ConstellationChan(ref chan) = page.constellation_chan;
+        let msg = if replace {
+            ConstellationMsg::ReplaceHistoryState(page.id, new_url.clone(), Some(serialized.clone()))
+        } else {
+            ConstellationMsg::PushHistoryState(page.id, new_url.clone(), Some(serialized.clone()))
+        };
+        chan.send(msg).unwrap();
+        if !replace {
+            page.push_session_history_entry();
+        }
+
+        // Step 5-6.
+        document.r().set_url(new_url);
+        self.state.set(StructuredCloneData::read_vec(serialized.as_slice(),
+                                                     GlobalRef::Window(window.r())));
+        *self.serialized_state.borrow_mut() = Some(serialized);
+        Ok(())
+    }
+}
+
+impl<'a> HistoryMethods for JSRef<'a, History> {
+    // http://www.whatwg.org/html/#dom-history-length
+    fn Length(self) -> u32 {
+        let window = self.window.root();
+        let page = window.r().page();
+        page.session_history_length.get()
+    }
+
+    // http://www.whatwg.org/html/#dom-history-state
+    fn State(self, _cx: *mut JSContext) -> JSVal {
+        self.state.get()
+    }
+
+    // http://www.whatwg.org/html/#dom-history-go
+    fn Go(self, delta: i32) {
+        let window = self.window.root();
+        let page = window.r().page();
+        if delta == 0 {
+            let url = window.r().Document().root().r().url();
+            window.r().script_chan().send(ScriptMsg::TriggerLoad(page.id, LoadData::new(url))).unwrap();
+            return;
+        }
+        let ConstellationChan(ref chan) = page.constellation_chan;
+        chan.send(ConstellationMsg::TraverseHistory(page.id, delta)).unwrap();
+    }
+
+    // http://www.whatwg.org/html/#dom-history-back
+    fn Back(self) {
+        self.Go(-1);
+    }
+
+    // http://www.whatwg.org/html/#dom-history-forward
+    fn Forward(self) {
+        self.Go(1);
+    }
+
+    // http://www.whatwg.org/html/#dom-history-pushstate
+    fn PushState(self, cx: *mut JSContext, data: JSVal, _title: DOMString,
+                 url: Option<DOMString>) -> ErrorResult {
+        self.add_state(cx, data, url, false)
+    }
+
+    // http://www.whatwg.org/html/#dom-history-replacestate
+    fn ReplaceState(self, cx: *mut JSContext, data: JSVal, _title: DOMString,
+                    url: Option<DOMString>) -> ErrorResult {
+        self.add_state(cx, data, url, true)
+    }
+}
+
+/// Fires a `hashchange` event at `window`.
+/// http://www.whatwg.org/html/#scroll-to-fragid step 4
+pub fn fire_hashchange(window: JSRef<Window>, old_url: &Url, new_url: &Url) {
+    let event = HashChangeEvent::new(GlobalRef::Window(window),
+                                     "hashchange".to_owned(), false, false,
+                                     old_url.serialize(), new_url.serialize()).root();
+    let event: JSRef<Event> = EventCast::from_ref(event.r());
+    let target: JSRef<EventTarget> = EventTargetCast::from_ref(window);
+    event.fire(target);
+}
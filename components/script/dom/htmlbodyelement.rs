@@ -56,6 +56,36 @@ impl HTMLBodyElement {
 }
 
 impl<'a> HTMLBodyElementMethods for JSRef<'a, HTMLBodyElement> {
+    fn GetOnhashchange(self) -> Option<EventHandlerNonNull> {
+        let win = window_from_node(self).root();
+        win.r().GetOnhashchange()
+    }
+
+    fn SetOnhashchange(self, listener: Option<EventHandlerNonNull>) {
+        let win = window_from_node(self).root();
+        win.r().SetOnhashchange(listener)
+    }
+
+    fn GetOnpopstate(self) -> Option<EventHandlerNonNull> {
+        let win = window_from_node(self).root();
+        win.r().GetOnpopstate()
+    }
+
+    fn SetOnpopstate(self, listener: Option<EventHandlerNonNull>) {
+        let win = window_from_node(self).root();
+        win.r().SetOnpopstate(listener)
+    }
+
+    fn GetOnstorage(self) -> Option<EventHandlerNonNull> {
+        let win = window_from_node(self).root();
+        win.r().GetOnstorage()
+    }
+
+    fn SetOnstorage(self, listener: Option<EventHandlerNonNull>) {
+        let win = window_from_node(self).root();
+        win.r().SetOnstorage(listener)
+    }
+
     fn GetOnunload(self) -> Option<EventHandlerNonNull> {
         let win = window_from_node(self).root();
         win.r().GetOnunload()
@@ -3,14 +3,22 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use canvas::canvas_paint_task::CanvasMsg;
+use canvas::image_encoder::{ImageFormat, encode_image};
 use dom::attr::Attr;
 use dom::attr::AttrHelpers;
+use dom::bindings::callback::ExceptionHandling::Report;
+use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::HTMLCanvasElementBinding;
-use dom::bindings::codegen::Bindings::HTMLCanvasElementBinding::HTMLCanvasElementMethods;
+use dom::bindings::codegen::Bindings::HTMLCanvasElementBinding::{BlobCallback, HTMLCanvasElementMethods};
 use dom::bindings::codegen::InheritTypes::HTMLCanvasElementDerived;
 use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast};
+use dom::bindings::conversions::FromJSValConvertible;
+use dom::bindings::error::Error::Security;
+use dom::bindings::error::{ErrorResult, Fallible};
 use dom::bindings::global::GlobalRef;
-use dom::bindings::js::{MutNullableJS, JSRef, LayoutJS, Temporary};
+use dom::bindings::js::{MutNullableJS, JSRef, LayoutJS, RootedReference, Temporary};
+use dom::bindings::refcounted::Trusted;
+use dom::blob::Blob;
 use dom::canvasrenderingcontext2d::{CanvasRenderingContext2D, LayoutCanvasRenderingContext2DHelpers};
 use dom::document::Document;
 use dom::element::{Element, AttributeHandlers};
@@ -19,11 +27,17 @@ use dom::element::ElementTypeId;
 use dom::htmlelement::{HTMLElement, HTMLElementTypeId};
 use dom::node::{Node, NodeTypeId, window_from_node};
 use dom::virtualmethods::VirtualMethods;
+use script_task::{Runnable, ScriptChan, ScriptMsg};
 
 use util::str::{DOMString, parse_unsigned_integer};
+use util::task::spawn_named;
 
 use geom::size::Size2D;
+use js::jsapi::JSContext;
+use js::jsval::JSVal;
+use rustc_serialize::base64::{ToBase64, STANDARD};
 
+use std::borrow::ToOwned;
 use std::cell::Cell;
 use std::default::Default;
 use std::sync::mpsc::Sender;
@@ -37,6 +51,12 @@ pub struct HTMLCanvasElement {
     context: MutNullableJS<CanvasRenderingContext2D>,
     width: Cell<u32>,
     height: Cell<u32>,
+    /// Whether nothing from another origin has been drawn into the canvas.
+    /// http://www.whatwg.org/html/#concept-canvas-origin-clean
+    origin_clean: Cell<bool>,
+    /// The callbacks of `toBlob()` calls whose image is still being encoded.
+    blob_callbacks: DOMRefCell<Vec<(u32, BlobCallback)>>,
+    next_blob_callback: Cell<u32>,
 }
 
 impl HTMLCanvasElementDerived for EventTarget {
@@ -52,6 +72,9 @@ impl HTMLCanvasElement {
             context: Default::default(),
             width: Cell::new(DEFAULT_WIDTH),
             height: Cell::new(DEFAULT_HEIGHT),
+            origin_clean: Cell::new(true),
+            blob_callbacks: DOMRefCell::new(vec!()),
+            next_blob_callback: Cell::new(0),
         }
     }
 
@@ -85,12 +108,94 @@ impl LayoutHTMLCanvasElementHelpers for LayoutJS<HTMLCanvasElement> {
 
 pub trait HTMLCanvasElementHelpers {
     fn get_size(&self) -> Size2D<i32>;
+    fn fetch_all_data(&self) -> Option<Vec<u8>>;
+    fn is_origin_clean(&self) -> bool;
+    fn taint(&self);
 }
 
 impl<'a> HTMLCanvasElementHelpers for JSRef<'a, HTMLCanvasElement> {
     fn get_size(&self) -> Size2D<i32> {
         Size2D(self.Width() as i32, self.Height() as i32)
     }
+
+    /// Returns the B8G8R8A8 pixels of the canvas, or `None` if it has no context yet.
+    fn fetch_all_data(&self) -> Option<Vec<u8>> {
+        self.context.get().map(|context| context.root().r().get_pixel_contents())
+    }
+
+    fn is_origin_clean(&self) -> bool {
+        self.origin_clean.get()
+    }
+
+    /// Marks the canvas as containing data from another origin, which can then no
+    /// longer be read back by script.
+    fn taint(&self) {
+        self.origin_clean.set(false)
+    }
+}
+
+trait PrivateHTMLCanvasElementHelpers {
+    fn fetch_bitmap(self) -> Option<Vec<u8>>;
+    fn run_blob_callback(self, ident: u32, blob: Option<(Vec<u8>, ImageFormat)>);
+}
+
+impl<'a> PrivateHTMLCanvasElementHelpers for JSRef<'a, HTMLCanvasElement> {
+    /// Returns the pixels of the canvas, which are transparent black if it has no
+    /// context, or `None` if it has no pixels at all.
+    fn fetch_bitmap(self) -> Option<Vec<u8>> {
+        let size = self.get_size();
+        if size.width == 0 || size.height == 0 {
+            return None
+        }
+        Some(self.fetch_all_data().unwrap_or_else(|| {
+            range(0, (size.width * size.height * 4) as usize).map(|_| 0u8).collect()
+        }))
+    }
+
+    // http://www.whatwg.org/html/#dom-canvas-toblob step 4
+    fn run_blob_callback(self, ident: u32, blob: Option<(Vec<u8>, ImageFormat)>) {
+        let callback = {
+            let mut callbacks = self.blob_callbacks.borrow_mut();
+            let index = callbacks.iter().position(|&(callback_ident, _)| callback_ident == ident);
+            callbacks.remove(index.unwrap()).1
+        };
+        let window = window_from_node(self).root();
+        let blob = blob.map(|(bytes, format)| {
+            Blob::new(GlobalRef::Window(window.r()), Some(bytes), format.mime_type()).root()
+        });
+        let _ = callback.Call_(window.r(), blob.r(), Report);
+    }
+}
+
+/// Returns the format requested by the `type` and `quality` arguments of `toDataURL()`
+/// and `toBlob()`.
+fn requested_format(cx: *mut JSContext, type_: Option<DOMString>, quality: JSVal) -> ImageFormat {
+    // A number is stored either as a double or as an int32, and jsvals have no is_int32
+    // yet, so numbers are told apart from the other types of values instead.
+    let is_number = !(quality.is_undefined() || quality.is_null() || quality.is_boolean() ||
+                      quality.is_string() || quality.is_object());
+    let quality = if is_number {
+        FromJSValConvertible::from_jsval(cx, quality, ()).ok()
+    } else {
+        None
+    };
+    let type_ = type_.unwrap_or("image/png".to_owned());
+    ImageFormat::from_mime_type(type_.as_slice(), quality)
+}
+
+/// Delivers the image encoded for a `toBlob()` call to its callback.
+struct BlobCallbackRunnable {
+    canvas: Trusted<HTMLCanvasElement>,
+    ident: u32,
+    blob: Option<(Vec<u8>, ImageFormat)>,
+}
+
+impl Runnable for BlobCallbackRunnable {
+    fn handler(self: Box<BlobCallbackRunnable>) {
+        let this = *self;
+        let canvas = this.canvas.to_temporary().root();
+        canvas.r().run_blob_callback(this.ident, this.blob);
+    }
 }
 
 impl<'a> HTMLCanvasElementMethods for JSRef<'a, HTMLCanvasElement> {
@@ -123,6 +228,63 @@ impl<'a> HTMLCanvasElementMethods for JSRef<'a, HTMLCanvasElement> {
             CanvasRenderingContext2D::new(GlobalRef::Window(window.r()), self, Size2D(w, h))
         }))
      }
+
+    // http://www.whatwg.org/html/#dom-canvas-todataurl
+    fn ToDataURL(self, cx: *mut JSContext, type_: Option<DOMString>,
+                 quality: JSVal) -> Fallible<DOMString> {
+        // Step 1.
+        if !self.origin_clean.get() {
+            return Err(Security)
+        }
+
+        // Step 2.
+        let pixels = match self.fetch_bitmap() {
+            Some(pixels) => pixels,
+            None => return Ok("data:,".to_owned()),
+        };
+
+        // Step 3.
+        let format = requested_format(cx, type_, quality);
+        Ok(match encode_image(pixels.as_slice(), self.get_size(), format) {
+            Some(bytes) => format!("data:{};base64,{}", format.mime_type(),
+                                   bytes.as_slice().to_base64(STANDARD)),
+            None => "data:,".to_owned(),
+        })
+    }
+
+    // http://www.whatwg.org/html/#dom-canvas-toblob
+    fn ToBlob(self, cx: *mut JSContext, callback: BlobCallback, type_: Option<DOMString>,
+              quality: JSVal) -> ErrorResult {
+        // Step 1.
+        if !self.origin_clean.get() {
+            return Err(Security)
+        }
+
+        // Step 2-3.
+        let pixels = self.fetch_bitmap();
+        let format = requested_format(cx, type_, quality);
+        let size = self.get_size();
+
+        let ident = self.next_blob_callback.get();
+        self.next_blob_callback.set(ident + 1);
+        self.blob_callbacks.borrow_mut().push((ident, callback));
+
+        // Step 4: the callback runs from a task queued once the image is encoded.
+        let window = window_from_node(self).root();
+        let script_chan = window.r().script_chan();
+        let canvas = Trusted::new(window.r().get_cx(), self, script_chan.clone());
+        spawn_named("CanvasImageEncoder".to_owned(), move || {
+            let blob = pixels.and_then(|pixels| encode_image(pixels.as_slice(), size, format))
+                             .map(|bytes| (bytes, format));
+            let runnable = box BlobCallbackRunnable {
+                canvas: canvas,
+                ident: ident,
+                blob: blob,
+            };
+            script_chan.send(ScriptMsg::RunnableMsg(runnable)).unwrap();
+        });
+        Ok(())
+    }
 }
 
 impl<'a> VirtualMethods for JSRef<'a, HTMLCanvasElement> {
@@ -12,19 +12,20 @@ use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLFrameSetElementDerived};
 use dom::bindings::codegen::InheritTypes::{EventTargetCast, HTMLInputElementCast};
 use dom::bindings::codegen::InheritTypes::{HTMLElementDerived, HTMLBodyElementDerived};
-use dom::bindings::js::{JSRef, Temporary, MutNullableJS};
+use dom::bindings::js::{JSRef, Temporary, MutNullableJS, OptionalRootable, RootedReference};
 use dom::bindings::error::ErrorResult;
 use dom::bindings::error::Error::Syntax;
 use dom::bindings::utils::Reflectable;
 use dom::cssstyledeclaration::{CSSStyleDeclaration, CSSModificationAccess};
-use dom::document::Document;
+use dom::document::{Document, DocumentHelpers};
 use dom::domstringmap::DOMStringMap;
 use dom::element::{Element, ElementTypeId, ActivationElementHelpers, AttributeHandlers};
+use dom::element::ElementHelpers;
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
 use dom::htmlinputelement::HTMLInputElement;
 use dom::htmlmediaelement::HTMLMediaElementTypeId;
 use dom::htmltablecellelement::HTMLTableCellElementTypeId;
-use dom::node::{Node, NodeTypeId, window_from_node};
+use dom::node::{Node, NodeTypeId, document_from_node, window_from_node};
 use dom::virtualmethods::VirtualMethods;
 
 use util::str::DOMString;
@@ -81,7 +82,8 @@ impl<'a> HTMLElementMethods for JSRef<'a, HTMLElement> {
     fn Style(self) -> Temporary<CSSStyleDeclaration> {
         self.style_decl.or_init(|| {
             let global = window_from_node(self).root();
-            CSSStyleDeclaration::new(global.r(), self, CSSModificationAccess::ReadWrite)
+            let element: JSRef<Element> = ElementCast::from_ref(self);
+            CSSStyleDeclaration::new(global.r(), element, None, CSSModificationAccess::ReadWrite)
         })
     }
 
@@ -133,6 +135,44 @@ impl<'a> HTMLElementMethods for JSRef<'a, HTMLElement> {
         // https://www.w3.org/Bugs/Public/show_bug.cgi?id=27430 ?
         element.as_maybe_activatable().map(|a| a.synthetic_click_activation(false, false, false, false));
     }
+
+    // https://html.spec.whatwg.org/multipage/#dom-tabindex
+    fn TabIndex(self) -> i32 {
+        let element: JSRef<Element> = ElementCast::from_ref(self);
+        element.tab_index().unwrap_or_else(|| {
+            if element.is_focusable_by_default() { 0 } else { -1 }
+        })
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-tabindex
+    fn SetTabIndex(self, index: i32) {
+        let element: JSRef<Element> = ElementCast::from_ref(self);
+        element.set_string_attribute(&atom!("tabindex"), index.to_string())
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-focus
+    fn Focus(self) {
+        let element: JSRef<Element> = ElementCast::from_ref(self);
+        if !element.is_focusable_area() {
+            return;
+        }
+        let document = document_from_node(self).root();
+        document.r().begin_focus_transaction();
+        document.r().request_focus(element);
+        document.r().commit_focus_transaction();
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-blur
+    fn Blur(self) {
+        let element: JSRef<Element> = ElementCast::from_ref(self);
+        let document = document_from_node(self).root();
+        let focused = document.r().get_focused_element().root();
+        if focused.r() != Some(element) {
+            return;
+        }
+        document.r().begin_focus_transaction();
+        document.r().commit_focus_transaction();
+    }
 }
 
 // https://html.spec.whatwg.org/#attr-data-*
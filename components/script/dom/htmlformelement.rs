@@ -32,6 +32,7 @@ use std::ascii::OwnedAsciiExt;
 use url::UrlParser;
 use url::form_urlencoded::serialize;
 use string_cache::Atom;
+use uuid::Uuid;
 
 use std::borrow::ToOwned;
 use std::cell::Cell;
@@ -190,24 +191,35 @@ impl<'a> HTMLFormElementHelpers for JSRef<'a, HTMLFormElement> {
 
         let mut load_data = LoadData::new(action_components);
 
-        let parsed_data = match enctype {
+        let (parsed_data, content_type) = match enctype {
             FormEncType::UrlEncoded => {
-                let mime: mime::Mime = "application/x-www-form-urlencoded".parse().unwrap();
-                load_data.headers.set(ContentType(mime));
-                serialize(form_data.iter().map(|d| (d.name.as_slice(), d.value.as_slice())))
+                let data = serialize(form_data.iter().map(|d| (d.name.as_slice(), d.value.as_string())));
+                (data.into_bytes(), "application/x-www-form-urlencoded".to_owned())
+            }
+            FormEncType::FormDataEncoded => {
+                let boundary = generate_boundary();
+                (encode_multipart_form_data(form_data.as_slice(), boundary.as_slice()),
+                 format!("multipart/form-data; boundary={}", boundary))
+            }
+            FormEncType::TextPlainEncoded => {
+                (encode_text_plain(form_data.as_slice()).into_bytes(), "text/plain".to_owned())
             }
-            _ => "".to_owned() // TODO: Add serializers for the other encoding types
         };
+        let mime: mime::Mime = content_type.parse().unwrap();
+        load_data.headers.set(ContentType(mime));
 
         // Step 18
         match (scheme.as_slice(), method) {
             (_, FormMethod::FormDialog) => return, // Unimplemented
             ("http", FormMethod::FormGet) | ("https", FormMethod::FormGet) => {
-                load_data.url.query = Some(parsed_data);
+                // https://html.spec.whatwg.org/multipage/forms.html#submit-mutate-action
+                // always uses the urlencoded serialization.
+                load_data.url.query = Some(
+                    serialize(form_data.iter().map(|d| (d.name.as_slice(), d.value.as_string()))));
             },
             ("http", FormMethod::FormPost) | ("https", FormMethod::FormPost) => {
                 load_data.method = Method::Post;
-                load_data.data = Some(parsed_data.into_bytes());
+                load_data.data = Some(parsed_data);
             },
             // https://html.spec.whatwg.org/multipage/forms.html#submit-get-action
             ("ftp", _) | ("javascript", _) | ("data", FormMethod::FormGet) => (),
@@ -297,16 +309,26 @@ impl<'a> HTMLFormElementHelpers for JSRef<'a, HTMLFormElement> {
                             Some(FormDatum {
                                 ty: ty,
                                 name: name,
-                                value: value
+                                value: FormDatumValue::String(value)
                             })
                         },
                         // Discard buttons which are not the submitter
                         "submit" | "button" | "reset" if !is_submitter => None,
-                        "file" => None, // Unimplemented
+                        // There is no way to select files yet, so this is always
+                        // the entry for an input with no selected files.
+                        "file" => Some(FormDatum {
+                            ty: ty,
+                            name: name,
+                            value: FormDatumValue::File {
+                                name: "".to_owned(),
+                                type_: "application/octet-stream".to_owned(),
+                                bytes: vec!()
+                            }
+                        }),
                         _ => Some(FormDatum {
                             ty: ty,
                             name: name,
-                            value: input.Value()
+                            value: FormDatumValue::String(input.Value())
                         })
                     }
                 }
@@ -337,7 +359,9 @@ impl<'a> HTMLFormElementHelpers for JSRef<'a, HTMLFormElement> {
                 "file" | "textarea" => (),
                 _ => {
                     datum.name = clean_crlf(datum.name.as_slice());
-                    datum.value = clean_crlf(datum.value.as_slice());
+                    if let FormDatumValue::String(ref mut value) = datum.value {
+                        *value = clean_crlf(value.as_slice());
+                    }
                 }
             }
         };
@@ -399,11 +423,84 @@ impl<'a> HTMLFormElementHelpers for JSRef<'a, HTMLFormElement> {
     }
 }
 
-// TODO: add file support
 pub struct FormDatum {
     pub ty: DOMString,
     pub name: DOMString,
-    pub value: DOMString
+    pub value: FormDatumValue
+}
+
+/// The value of an entry in a form data set.
+#[derive(Clone)]
+pub enum FormDatumValue {
+    String(DOMString),
+    /// A file's name, type and contents.
+    File {
+        name: DOMString,
+        type_: DOMString,
+        bytes: Vec<u8>
+    }
+}
+
+impl FormDatumValue {
+    /// The value used by the encodings that have no room for file contents,
+    /// which is the file name for files.
+    pub fn as_string<'a>(&'a self) -> &'a str {
+        match *self {
+            FormDatumValue::String(ref value) => value.as_slice(),
+            FormDatumValue::File { ref name, .. } => name.as_slice()
+        }
+    }
+}
+
+/// Returns a new boundary for a `multipart/form-data` payload.
+pub fn generate_boundary() -> String {
+    format!("----ServoFormBoundary{}", Uuid::new_v4().to_simple_string())
+}
+
+// https://html.spec.whatwg.org/multipage/forms.html#multipart/form-data-encoding-algorithm
+pub fn encode_multipart_form_data(form_data: &[FormDatum], boundary: &str) -> Vec<u8> {
+    fn escape_name(name: &str) -> String {
+        name.replace("\"", "%22").replace("\r", "%0D").replace("\n", "%0A")
+    }
+
+    let mut result = vec!();
+    for datum in form_data.iter() {
+        let disposition = format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+                                  boundary, escape_name(datum.name.as_slice()));
+        result.push_all(disposition.as_bytes());
+        match datum.value {
+            FormDatumValue::String(ref value) => {
+                result.push_all(b"\r\n\r\n");
+                result.push_all(value.as_bytes());
+            }
+            FormDatumValue::File { ref name, ref type_, ref bytes } => {
+                let type_ = if type_.is_empty() {
+                    "application/octet-stream"
+                } else {
+                    type_.as_slice()
+                };
+                let headers = format!("; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                                      escape_name(name.as_slice()), type_);
+                result.push_all(headers.as_bytes());
+                result.push_all(bytes.as_slice());
+            }
+        }
+        result.push_all(b"\r\n");
+    }
+    result.push_all(format!("--{}--\r\n", boundary).as_bytes());
+    result
+}
+
+// https://html.spec.whatwg.org/multipage/forms.html#text/plain-encoding-algorithm
+pub fn encode_text_plain(form_data: &[FormDatum]) -> String {
+    let mut result = "".to_owned();
+    for datum in form_data.iter() {
+        result.push_str(datum.name.as_slice());
+        result.push('=');
+        result.push_str(datum.value.as_string());
+        result.push_str("\r\n");
+    }
+    result
 }
 
 #[derive(Copy)]
@@ -552,3 +649,71 @@ pub trait FormControl<'a> : Copy + Sized {
 
     fn to_element(self) -> JSRef<'a, Element>;
 }
+
+#[cfg(test)]
+fn test_form_data() -> Vec<FormDatum> {
+    vec!(
+        FormDatum {
+            ty: "text".to_owned(),
+            name: "quote\"d".to_owned(),
+            value: FormDatumValue::String("line\r\nbreak".to_owned()),
+        },
+        FormDatum {
+            ty: "file".to_owned(),
+            name: "upload".to_owned(),
+            value: FormDatumValue::File {
+                name: "new\nline.txt".to_owned(),
+                type_: "".to_owned(),
+                bytes: vec!(0, 1, 2),
+            },
+        },
+        FormDatum {
+            ty: "file".to_owned(),
+            name: "typed".to_owned(),
+            value: FormDatumValue::File {
+                name: "a.txt".to_owned(),
+                type_: "text/plain".to_owned(),
+                bytes: b"text".to_vec(),
+            },
+        },
+    )
+}
+
+#[test]
+fn test_encode_multipart_form_data() {
+    let encoded = encode_multipart_form_data(test_form_data().as_slice(), "BOUNDARY");
+    let expected: &[u8] =
+        b"--BOUNDARY\r\n\
+          Content-Disposition: form-data; name=\"quote%22d\"\r\n\
+          \r\n\
+          line\r\nbreak\r\n\
+          --BOUNDARY\r\n\
+          Content-Disposition: form-data; name=\"upload\"; filename=\"new%0Aline.txt\"\r\n\
+          Content-Type: application/octet-stream\r\n\
+          \r\n\
+          \x00\x01\x02\r\n\
+          --BOUNDARY\r\n\
+          Content-Disposition: form-data; name=\"typed\"; filename=\"a.txt\"\r\n\
+          Content-Type: text/plain\r\n\
+          \r\n\
+          text\r\n\
+          --BOUNDARY--\r\n";
+    assert_eq!(encoded.as_slice(), expected);
+
+    let empty: &[u8] = b"--BOUNDARY--\r\n";
+    assert_eq!(encode_multipart_form_data(&[], "BOUNDARY").as_slice(), empty);
+}
+
+#[test]
+fn test_generate_boundary() {
+    let boundary = generate_boundary();
+    assert!(boundary.as_slice().starts_with("----ServoFormBoundary"));
+    assert!(boundary != generate_boundary());
+}
+
+#[test]
+fn test_encode_text_plain() {
+    assert_eq!(encode_text_plain(test_form_data().as_slice()).as_slice(),
+               "quote\"d=line\r\nbreak\r\nupload=new\nline.txt\r\ntyped=a.txt\r\n");
+    assert_eq!(encode_text_plain(&[]).as_slice(), "");
+}
@@ -26,6 +26,8 @@ use page::{IterablePage, Page};
 use msg::constellation_msg::{PipelineId, SubpageId, ConstellationChan};
 use msg::constellation_msg::IFrameSandboxState::{IFrameSandboxed, IFrameUnsandboxed};
 use msg::constellation_msg::Msg as ConstellationMsg;
+use net::mixed_content;
+use net::resource_task::LoadContext;
 use util::str::DOMString;
 use string_cache::Atom;
 
@@ -92,9 +94,18 @@ impl<'a> HTMLIFrameElementHelpers for JSRef<'a, HTMLIFrameElement> {
     }
 
     fn process_the_iframe_attributes(self) {
+        let window = window_from_node(self).root();
+        let window = window.r();
+        let page = window.page();
+
+        // A frame whose document would be mixed content is left blank.
         let url = match self.get_url() {
-            Some(url) => url.clone(),
-            None => Url::parse("about:blank").unwrap(),
+            Some(ref url) if mixed_content::allow_subresource_load(&page.get_url(), url,
+                                                                  LoadContext::Browsing,
+                                                                  page.console_chan.as_ref()) => {
+                url.clone()
+            }
+            _ => Url::parse("about:blank").unwrap(),
         };
 
         let sandboxed = if self.is_sandboxed() {
@@ -103,9 +114,6 @@ impl<'a> HTMLIFrameElementHelpers for JSRef<'a, HTMLIFrameElement> {
             IFrameUnsandboxed
         };
 
-        let window = window_from_node(self).root();
-        let window = window.r();
-        let page = window.page();
         let (new_subpage_id, old_subpage_id) = self.generate_new_subpage_id(page);
 
         self.containing_page_pipeline_id.set(Some(page.id));
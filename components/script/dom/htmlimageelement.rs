@@ -18,6 +18,8 @@ use dom::htmlelement::{HTMLElement, HTMLElementTypeId};
 use dom::node::{Node, NodeTypeId, NodeHelpers, NodeDamage, window_from_node};
 use dom::virtualmethods::VirtualMethods;
 use net::image_cache_task;
+use net::mixed_content;
+use net::resource_task::LoadContext;
 use util::geometry::to_px;
 use util::str::DOMString;
 use string_cache::Atom;
@@ -61,6 +63,12 @@ impl<'a> PrivateHTMLImageElementHelpers for JSRef<'a, HTMLImageElement> {
                 let img_url = img_url.unwrap();
                 *self.image.borrow_mut() = Some(img_url.clone());
 
+                // Images are optionally blockable, so mixed content is only reported.
+                let page = window.page();
+                mixed_content::allow_subresource_load(&page.get_url(), &img_url,
+                                                      LoadContext::Image,
+                                                      page.console_chan.as_ref());
+
                 // inform the image cache to load this, but don't store a
                 // handle.
                 image_cache.send(image_cache_task::Msg::Prefetch(img_url));
@@ -94,6 +102,17 @@ impl LayoutHTMLImageElementHelpers for LayoutJS<HTMLImageElement> {
     }
 }
 
+pub trait HTMLImageElementHelpers {
+    fn get_url(&self) -> Option<Url>;
+}
+
+impl<'a> HTMLImageElementHelpers for JSRef<'a, HTMLImageElement> {
+    /// The resolved URL of the image, if the `src` attribute is set.
+    fn get_url(&self) -> Option<Url> {
+        self.image.borrow().clone()
+    }
+}
+
 impl<'a> HTMLImageElementMethods for JSRef<'a, HTMLImageElement> {
     make_getter!(Alt);
 
@@ -18,6 +18,8 @@ use dom::htmlelement::{HTMLElement, HTMLElementTypeId};
 use dom::node::{Node, NodeHelpers, NodeTypeId, window_from_node};
 use dom::virtualmethods::VirtualMethods;
 use layout_interface::{LayoutChan, Msg};
+use net::mixed_content;
+use net::resource_task::LoadContext;
 use util::str::{DOMString, HTML_SPACE_CHARACTERS};
 
 use std::ascii::AsciiExt;
@@ -130,10 +132,17 @@ impl<'a> PrivateHTMLLinkElementHelpers for JSRef<'a, HTMLLinkElement> {
     fn handle_stylesheet_url(self, href: &str) {
         let window = window_from_node(self).root();
         let window = window.r();
-        match UrlParser::new().base_url(&window.page().get_url()).parse(href) {
+        let page = window.page();
+        let document_url = page.get_url();
+        match UrlParser::new().base_url(&document_url).parse(href) {
             Ok(url) => {
-                let LayoutChan(ref layout_chan) = window.page().layout_chan;
-                layout_chan.send(Msg::LoadStylesheet(url)).unwrap();
+                if !mixed_content::allow_subresource_load(&document_url, &url, LoadContext::Style,
+                                                          page.console_chan.as_ref()) {
+                    return;
+                }
+                let LayoutChan(ref layout_chan) = page.layout_chan;
+                let console = page.console_chan.clone();
+                layout_chan.send(Msg::LoadStylesheet(url, document_url.clone(), console)).unwrap();
             }
             Err(e) => debug!("Parsing url {} failed: {}", href, e)
         }
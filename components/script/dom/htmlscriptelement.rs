@@ -30,7 +30,7 @@ use script_task::{ScriptMsg, Runnable};
 
 use encoding::all::UTF_8;
 use encoding::types::{Encoding, DecoderTrap};
-use net::resource_task::{load_whole_resource, Metadata};
+use net::resource_task::{load_whole_subresource, LoadContext, Metadata};
 use util::str::{DOMString, HTML_SPACE_CHARACTERS, StaticStringVec};
 use std::borrow::ToOwned;
 use std::cell::Cell;
@@ -243,7 +243,10 @@ impl<'a> HTMLScriptElementHelpers for JSRef<'a, HTMLScriptElement> {
                         // state of the element's `crossorigin` content attribute, the origin being
                         // the origin of the script element's node document, and the default origin
                         // behaviour set to taint.
-                        ScriptOrigin::External(load_whole_resource(&page.resource_task, url))
+                        ScriptOrigin::External(load_whole_subresource(&page.resource_task, url,
+                                                                      LoadContext::Script,
+                                                                      page.get_url(),
+                                                                      page.console_chan.clone()))
                     }
                 }
             },
@@ -55,9 +55,10 @@ impl<'a> StyleElementHelpers for JSRef<'a, HTMLStyleElement> {
         let url = win.page().get_url();
 
         let data = node.GetTextContent().expect("Element.textContent must be a string");
-        let sheet = Stylesheet::from_str(data.as_slice(), url, Origin::Author);
+        let sheet = Stylesheet::from_str(data.as_slice(), url.clone(), Origin::Author);
         let LayoutChan(ref layout_chan) = win.page().layout_chan;
-        layout_chan.send(Msg::AddStylesheet(sheet)).unwrap();
+        let console = win.page().console_chan.clone();
+        layout_chan.send(Msg::AddStylesheet(sheet, url, console)).unwrap();
     }
 }
 
@@ -7,12 +7,14 @@ use dom::bindings::codegen::Bindings::LocationBinding::LocationMethods;
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JSRef, Temporary};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::document::DocumentHelpers;
 use dom::urlhelper::UrlHelper;
 use dom::window::Window;
 use dom::window::WindowHelpers;
 use page::Page;
 
 use util::str::DOMString;
+use url::Url;
 
 use std::rc::Rc;
 
@@ -35,6 +37,13 @@ impl Location {
                            GlobalRef::Window(window),
                            LocationBinding::Wrap)
     }
+
+    /// The URL of the active document, which differs from the URL it was loaded from after
+    /// `pushState` or a fragment navigation.
+    fn get_url(&self) -> Url {
+        let document = self.page.frame().as_ref().unwrap().document.root();
+        document.r().url()
+    }
 }
 
 impl<'a> LocationMethods for JSRef<'a, Location> {
@@ -44,7 +53,7 @@ impl<'a> LocationMethods for JSRef<'a, Location> {
     }
 
     fn Href(self) -> DOMString {
-        UrlHelper::Href(&self.page.get_url())
+        UrlHelper::Href(&self.get_url())
     }
 
     fn Stringify(self) -> DOMString {
@@ -52,11 +61,11 @@ impl<'a> LocationMethods for JSRef<'a, Location> {
     }
 
     fn Search(self) -> DOMString {
-        UrlHelper::Search(&self.page.get_url())
+        UrlHelper::Search(&self.get_url())
     }
 
     fn Hash(self) -> DOMString {
-        UrlHelper::Hash(&self.page.get_url())
+        UrlHelper::Hash(&self.get_url())
     }
 }
 
@@ -0,0 +1,55 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::MessageChannelBinding;
+use dom::bindings::codegen::Bindings::MessageChannelBinding::MessageChannelMethods;
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::messageport::{MessagePort, TransferredPort};
+
+// http://www.whatwg.org/html/#messagechannel
+#[dom_struct]
+pub struct MessageChannel {
+    reflector_: Reflector,
+    port1: JS<MessagePort>,
+    port2: JS<MessagePort>,
+}
+
+impl MessageChannel {
+    fn new_inherited(port1: Temporary<MessagePort>, port2: Temporary<MessagePort>)
+                     -> MessageChannel {
+        MessageChannel {
+            reflector_: Reflector::new(),
+            port1: JS::from_rooted(port1),
+            port2: JS::from_rooted(port2),
+        }
+    }
+
+    pub fn new(global: GlobalRef) -> Temporary<MessageChannel> {
+        let (port1, port2) = TransferredPort::new_pair();
+        let port1 = MessagePort::new(global, port1);
+        let port2 = MessagePort::new(global, port2);
+        reflect_dom_object(box MessageChannel::new_inherited(port1, port2),
+                           global, MessageChannelBinding::Wrap)
+    }
+
+    // http://www.whatwg.org/html/#dom-messagechannel
+    pub fn Constructor(global: GlobalRef) -> Fallible<Temporary<MessageChannel>> {
+        Ok(MessageChannel::new(global))
+    }
+}
+
+impl<'a> MessageChannelMethods for JSRef<'a, MessageChannel> {
+    // http://www.whatwg.org/html/#dom-messagechannel-port1
+    fn Port1(self) -> Temporary<MessagePort> {
+        Temporary::new(self.port1)
+    }
+
+    // http://www.whatwg.org/html/#dom-messagechannel-port2
+    fn Port2(self) -> Temporary<MessagePort> {
+        Temporary::new(self.port2)
+    }
+}
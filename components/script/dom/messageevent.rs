@@ -6,18 +6,21 @@ use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
 use dom::bindings::codegen::Bindings::MessageEventBinding;
 use dom::bindings::codegen::Bindings::MessageEventBinding::MessageEventMethods;
 use dom::bindings::codegen::InheritTypes::{EventCast, MessageEventDerived};
+use dom::bindings::conversions::ToJSValConvertible;
 use dom::bindings::error::Fallible;
 use dom::bindings::global::GlobalRef;
-use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::js::{JS, JSRef, Temporary};
 use dom::bindings::utils::reflect_dom_object;
 use dom::event::{Event, EventHelpers, EventTypeId};
 use dom::eventtarget::EventTarget;
+use dom::messageport::MessagePort;
 
 use util::str::DOMString;
 
-use js::jsapi::JSContext;
-use js::jsval::{JSVal, UndefinedValue};
+use js::jsapi::{JSContext, JS_NewArrayObject};
+use js::jsval::{JSVal, NullValue, ObjectValue, UndefinedValue};
 
+use libc::c_int;
 use std::borrow::ToOwned;
 
 #[dom_struct]
@@ -26,6 +29,8 @@ pub struct MessageEvent {
     data: JSVal,
     origin: DOMString,
     lastEventId: DOMString,
+    /// The ports transferred along with the message.
+    ports: Vec<JS<MessagePort>>,
 }
 
 impl MessageEventDerived for Event {
@@ -35,13 +40,14 @@ impl MessageEventDerived for Event {
 }
 
 impl MessageEvent {
-    fn new_inherited(data: JSVal, origin: DOMString, lastEventId: DOMString)
-                         -> MessageEvent {
+    fn new_inherited(data: JSVal, origin: DOMString, lastEventId: DOMString,
+                     ports: Vec<JS<MessagePort>>) -> MessageEvent {
         MessageEvent {
             event: Event::new_inherited(EventTypeId::MessageEvent),
             data: data,
             origin: origin,
             lastEventId: lastEventId,
+            ports: ports,
         }
     }
 
@@ -50,7 +56,7 @@ impl MessageEvent {
     }
 
     pub fn new_initialized(global: GlobalRef, data: JSVal, origin: DOMString, lastEventId: DOMString) -> Temporary<MessageEvent> {
-        reflect_dom_object(box MessageEvent::new_inherited(data, origin, lastEventId),
+        reflect_dom_object(box MessageEvent::new_inherited(data, origin, lastEventId, vec!()),
         global,
         MessageEventBinding::Wrap)
     }
@@ -76,13 +82,19 @@ impl MessageEvent {
 }
 
 impl MessageEvent {
-    pub fn dispatch_jsval(target: JSRef<EventTarget>,
-                          scope: GlobalRef,
-                          message: JSVal) {
-        let messageevent = MessageEvent::new(
-            scope, "message".to_owned(), false, false, message,
-            "".to_owned(), "".to_owned()).root();
+    /// Fires an event of type `type_` at `target`, for a message sent through a
+    /// `MessagePort` or a `postMessage` call that transferred `ports`.
+    pub fn dispatch_with_ports(target: JSRef<EventTarget>,
+                               scope: GlobalRef,
+                               type_: DOMString,
+                               message: JSVal,
+                               ports: Vec<Temporary<MessagePort>>) {
+        let ports = ports.into_iter().map(|port| JS::from_rooted(port)).collect();
+        let messageevent = reflect_dom_object(
+            box MessageEvent::new_inherited(message, "".to_owned(), "".to_owned(), ports),
+            scope, MessageEventBinding::Wrap).root();
         let event: JSRef<Event> = EventCast::from_ref(messageevent.r());
+        event.InitEvent(type_, false, false);
         event.fire(target);
     }
 }
@@ -99,5 +111,20 @@ impl<'a> MessageEventMethods for JSRef<'a, MessageEvent> {
     fn LastEventId(self) -> DOMString {
         self.lastEventId.clone()
     }
+
+    // http://www.whatwg.org/html/#dom-messageevent-ports
+    #[allow(unsafe_blocks)]
+    fn Ports(self, cx: *mut JSContext) -> JSVal {
+        let mut values: Vec<JSVal> = self.ports.iter().map(|port| {
+            port.root().r().to_jsval(cx)
+        }).collect();
+        let array = unsafe {
+            JS_NewArrayObject(cx, values.len() as c_int, values.as_mut_ptr())
+        };
+        if array.is_null() {
+            return NullValue();
+        }
+        unsafe { ObjectValue(&*array) }
+    }
 }
 
@@ -0,0 +1,301 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use dom::bindings::codegen::Bindings::MessagePortBinding;
+use dom::bindings::codegen::Bindings::MessagePortBinding::MessagePortMethods;
+use dom::bindings::codegen::InheritTypes::EventTargetCast;
+use dom::bindings::conversions::unwrap_jsmanaged;
+use dom::bindings::error::{ErrorResult, Fallible};
+use dom::bindings::error::Error::{DataClone, JSFailed, TypeError};
+use dom::bindings::global::{GlobalRef, GlobalField};
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::refcounted::Trusted;
+use dom::bindings::structuredclone::StructuredCloneData;
+use dom::bindings::utils::reflect_dom_object;
+use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
+use dom::messageevent::MessageEvent;
+use script_task::{ScriptChan, ScriptMsg, Runnable};
+
+use js::jsapi::{JSContext, JS_GetArrayLength, JS_GetElement, JS_IsArrayObject};
+use js::jsval::{JSVal, UndefinedValue};
+
+use std::borrow::ToOwned;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+/// The port message queue of a port, shared between the port and the port it is
+/// entangled with, which may live in another event loop.
+struct PortQueue {
+    /// The messages posted while the queue was not enabled.
+    pending: Vec<(Vec<u64>, Vec<TransferredPort>)>,
+    /// The port object that messages are delivered to and the event loop it lives in,
+    /// while the queue is enabled. This keeps the port alive, so it is only set while
+    /// the entangled port can still post messages.
+    owner: Option<(Trusted<MessagePort>, Box<ScriptChan+Send>)>,
+    /// Whether the port was closed, after which the messages sent to it are dropped.
+    closed: bool,
+    /// Whether the entangled port was closed or collected.
+    disentangled: bool,
+}
+
+impl PortQueue {
+    fn new() -> Arc<Mutex<PortQueue>> {
+        Arc::new(Mutex::new(PortQueue {
+            pending: vec!(),
+            owner: None,
+            closed: false,
+            disentangled: false,
+        }))
+    }
+
+    /// Sends a message to the event loop of the port if the queue is enabled, and
+    /// queues it otherwise. If the port was closed, the ports transferred with the
+    /// message are returned, for the caller to close them once the lock is released.
+    fn post(&mut self, data: Vec<u64>, ports: Vec<TransferredPort>)
+            -> Option<Vec<TransferredPort>> {
+        if self.closed {
+            return Some(ports);
+        }
+        match self.owner {
+            Some((ref port, ref script_chan)) => deliver(port, &**script_chan, data, ports),
+            None => self.pending.push((data, ports)),
+        }
+        None
+    }
+
+    /// Enables the queue: the pending messages and the ones posted later are sent to
+    /// `port` in the event loop of `script_chan`.
+    fn enable(&mut self, port: Trusted<MessagePort>, script_chan: Box<ScriptChan+Send>) {
+        for (data, ports) in self.pending.drain() {
+            deliver(&port, &*script_chan, data, ports);
+        }
+        if !self.disentangled {
+            self.owner = Some((port, script_chan));
+        }
+    }
+}
+
+/// Queues a task in the event loop of `port` that dispatches a message to it.
+fn deliver(port: &Trusted<MessagePort>, script_chan: &ScriptChan,
+           data: Vec<u64>, ports: Vec<TransferredPort>) {
+    let handler = box PortMessageHandler {
+        port: port.clone(),
+        data: data,
+        ports: ports,
+    };
+    let _ = script_chan.send(ScriptMsg::RunnableMsg(handler));
+}
+
+/// A port that is not owned by a port object, because it is sent to another event loop
+/// along with a message or as the port of a shared worker connection. Port objects keep
+/// their port in one as well; dropping it closes the port.
+pub struct TransferredPort {
+    /// The queue of the messages sent to this port.
+    queue: Arc<Mutex<PortQueue>>,
+    /// The queue of the port this port is entangled with.
+    entangled: Arc<Mutex<PortQueue>>,
+}
+
+no_jsmanaged_fields!(TransferredPort);
+
+impl TransferredPort {
+    /// Creates two entangled ports that are not owned by any event loop yet.
+    pub fn new_pair() -> (TransferredPort, TransferredPort) {
+        let first = PortQueue::new();
+        let second = PortQueue::new();
+        (TransferredPort { queue: first.clone(), entangled: second.clone() },
+         TransferredPort { queue: second, entangled: first })
+    }
+}
+
+impl Drop for TransferredPort {
+    fn drop(&mut self) {
+        // The locks are taken one after the other, so that ports closing at the same
+        // time on both sides can't deadlock. The dropped messages may transfer ports,
+        // which lock their own queues when they are dropped in turn.
+        let pending = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.closed = true;
+            queue.owner = None;
+            mem::replace(&mut queue.pending, vec!())
+        };
+        drop(pending);
+
+        let owner = {
+            let mut entangled = self.entangled.lock().unwrap();
+            entangled.disentangled = true;
+            entangled.owner.take()
+        };
+        drop(owner);
+    }
+}
+
+// http://www.whatwg.org/html/#messageport
+#[dom_struct]
+pub struct MessagePort {
+    eventtarget: EventTarget,
+    global: GlobalField,
+    /// The port, or `None` once it has been closed or transferred to another event loop.
+    port: DOMRefCell<Option<TransferredPort>>,
+}
+
+impl MessagePort {
+    fn new_inherited(global: GlobalRef, port: TransferredPort) -> MessagePort {
+        MessagePort {
+            eventtarget: EventTarget::new_inherited(EventTargetTypeId::MessagePort),
+            global: GlobalField::from_rooted(&global),
+            port: DOMRefCell::new(Some(port)),
+        }
+    }
+
+    /// Creates the port object for `port` in `global`. The messages sent to the port
+    /// are delivered to it once its port message queue is enabled.
+    pub fn new(global: GlobalRef, port: TransferredPort) -> Temporary<MessagePort> {
+        reflect_dom_object(box MessagePort::new_inherited(global, port),
+                           global,
+                           MessagePortBinding::Wrap)
+    }
+
+    /// Returns the ports of the transfer argument of a `postMessage` call, which is
+    /// either undefined or an array of distinct ports that can still be transferred.
+    #[allow(unsafe_blocks)]
+    pub fn transfer_list(cx: *mut JSContext, transfer: JSVal)
+                         -> Fallible<Vec<Temporary<MessagePort>>> {
+        if transfer.is_undefined() {
+            return Ok(vec!());
+        }
+        if !transfer.is_object() || unsafe { JS_IsArrayObject(cx, transfer.to_object()) } == 0 {
+            return Err(TypeError("The transfer list is not a sequence.".to_owned()));
+        }
+
+        let array = transfer.to_object();
+        let mut length = 0;
+        if unsafe { JS_GetArrayLength(cx, array, &mut length) } == 0 {
+            return Err(JSFailed);
+        }
+
+        let mut ports: Vec<Temporary<MessagePort>> = vec!();
+        for index in range(0, length) {
+            let mut value = UndefinedValue();
+            if unsafe { JS_GetElement(cx, array, index, &mut value) } == 0 {
+                return Err(JSFailed);
+            }
+            let port = if value.is_object() {
+                unwrap_jsmanaged::<MessagePort>(value.to_object()).ok()
+            } else {
+                None
+            };
+            let port = match port {
+                Some(port) => Temporary::from_unrooted(port),
+                None => return Err(DataClone),
+            };
+            if ports.contains(&port) || port.root().r().port.borrow().is_none() {
+                return Err(DataClone);
+            }
+            ports.push(port);
+        }
+        Ok(ports)
+    }
+}
+
+pub trait MessagePortHelpers {
+    fn transfer(self) -> TransferredPort;
+}
+
+impl<'a> MessagePortHelpers for JSRef<'a, MessagePort> {
+    /// Detaches this port from its event loop so that it can be sent to another one.
+    /// The messages sent to the port in the meantime stay queued.
+    fn transfer(self) -> TransferredPort {
+        let port = self.port.borrow_mut().take().unwrap();
+        let owner = port.queue.lock().unwrap().owner.take();
+        drop(owner);
+        port
+    }
+}
+
+trait PrivateMessagePortHelpers {
+    fn dispatch_message(self, data: Vec<u64>, ports: Vec<TransferredPort>);
+}
+
+impl<'a> PrivateMessagePortHelpers for JSRef<'a, MessagePort> {
+    fn dispatch_message(self, data: Vec<u64>, ports: Vec<TransferredPort>) {
+        // The port may have been closed or transferred after the message was queued.
+        if self.port.borrow().is_none() {
+            return;
+        }
+
+        let global = self.global.root();
+        let message = StructuredCloneData::read_vec(data.as_slice(), global.r());
+        let ports = ports.into_iter().map(|port| MessagePort::new(global.r(), port)).collect();
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        MessageEvent::dispatch_with_ports(target, global.r(), "message".to_owned(),
+                                          message, ports);
+    }
+}
+
+impl<'a> MessagePortMethods for JSRef<'a, MessagePort> {
+    // http://www.whatwg.org/html/#dom-messageport-postmessage
+    fn PostMessage(self, cx: *mut JSContext, message: JSVal, transfer: JSVal) -> ErrorResult {
+        // Step 1-4.
+        let ports = try!(MessagePort::transfer_list(cx, transfer));
+        if ports.contains(&Temporary::from_rooted(self)) {
+            return Err(DataClone);
+        }
+
+        // Step 5-6.
+        let data = try!(StructuredCloneData::write(cx, message)).to_vec();
+        let ports = ports.into_iter().map(|port| port.root().r().transfer()).collect();
+
+        // Step 7-8: the message is dropped if this port is not entangled anymore.
+        if let Some(ref port) = *self.port.borrow() {
+            let dropped = port.entangled.lock().unwrap().post(data, ports);
+            drop(dropped);
+        }
+        Ok(())
+    }
+
+    // http://www.whatwg.org/html/#dom-messageport-start
+    fn Start(self) {
+        if let Some(ref port) = *self.port.borrow() {
+            let global = self.global.root();
+            let owner = Trusted::new(global.r().get_cx(), self, global.r().script_chan());
+            port.queue.lock().unwrap().enable(owner, global.r().script_chan());
+        }
+    }
+
+    // http://www.whatwg.org/html/#dom-messageport-close
+    fn Close(self) {
+        let port = self.port.borrow_mut().take();
+        drop(port);
+    }
+
+    fn GetOnmessage(self) -> Option<EventHandlerNonNull> {
+        let eventtarget: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        eventtarget.get_event_handler_common("message")
+    }
+
+    // http://www.whatwg.org/html/#handler-messageport-onmessage
+    fn SetOnmessage(self, listener: Option<EventHandlerNonNull>) {
+        let eventtarget: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        eventtarget.set_event_handler_common("message", listener);
+        self.Start();
+    }
+}
+
+/// Dispatches a message queued for a port.
+pub struct PortMessageHandler {
+    port: Trusted<MessagePort>,
+    data: Vec<u64>,
+    ports: Vec<TransferredPort>,
+}
+
+impl Runnable for PortMessageHandler {
+    fn handler(self: Box<PortMessageHandler>) {
+        let this = *self;
+        let port = this.port.to_temporary().root();
+        port.r().dispatch_message(this.data, this.ports);
+    }
+}
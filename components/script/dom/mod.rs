@@ -199,6 +199,7 @@ pub mod canvasgradient;
 pub mod canvaspattern;
 pub mod canvasrenderingcontext2d;
 pub mod characterdata;
+pub mod closeevent;
 pub mod cssstyledeclaration;
 pub mod domrect;
 pub mod domrectlist;
@@ -221,7 +222,10 @@ pub mod event;
 pub mod eventdispatcher;
 pub mod eventtarget;
 pub mod file;
+pub mod focusevent;
 pub mod formdata;
+pub mod hashchangeevent;
+pub mod history;
 pub mod htmlanchorelement;
 pub mod htmlappletelement;
 pub mod htmlareaelement;
@@ -295,8 +299,12 @@ pub mod htmlunknownelement;
 pub mod imagedata;
 pub mod keyboardevent;
 pub mod location;
+pub mod messagechannel;
 pub mod messageevent;
+pub mod messageport;
 pub mod mouseevent;
+pub mod mutationobserver;
+pub mod mutationrecord;
 pub mod namednodemap;
 pub mod navigator;
 pub mod navigatorinfo;
@@ -306,12 +314,20 @@ pub mod nodelist;
 pub mod processinginstruction;
 pub mod performance;
 pub mod performancetiming;
+pub mod popstateevent;
 pub mod progressevent;
 pub mod range;
 pub mod screen;
 pub mod servohtmlparser;
+pub mod sharedworker;
+pub mod sharedworkerglobalscope;
 pub mod storage;
+pub mod storageevent;
 pub mod text;
+pub mod textmetrics;
+pub mod touch;
+pub mod touchevent;
+pub mod touchlist;
 pub mod treewalker;
 pub mod uievent;
 pub mod urlhelper;
@@ -319,6 +335,7 @@ pub mod urlsearchparams;
 pub mod validitystate;
 pub mod virtualmethods;
 pub mod websocket;
+pub mod wheelevent;
 pub mod window;
 pub mod worker;
 pub mod workerglobalscope;
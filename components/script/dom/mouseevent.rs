@@ -35,14 +35,17 @@ pub struct MouseEvent {
 
 impl MouseEventDerived for Event {
     fn is_mouseevent(&self) -> bool {
-        *self.type_id() == EventTypeId::MouseEvent
+        match *self.type_id() {
+            EventTypeId::MouseEvent | EventTypeId::WheelEvent => true,
+            _ => false
+        }
     }
 }
 
 impl MouseEvent {
-    fn new_inherited() -> MouseEvent {
+    pub fn new_inherited(type_id: EventTypeId) -> MouseEvent {
         MouseEvent {
-            uievent: UIEvent::new_inherited(EventTypeId::MouseEvent),
+            uievent: UIEvent::new_inherited(type_id),
             screen_x: Cell::new(0),
             screen_y: Cell::new(0),
             client_x: Cell::new(0),
@@ -57,7 +60,7 @@ impl MouseEvent {
     }
 
     pub fn new_uninitialized(window: JSRef<Window>) -> Temporary<MouseEvent> {
-        reflect_dom_object(box MouseEvent::new_inherited(),
+        reflect_dom_object(box MouseEvent::new_inherited(EventTypeId::MouseEvent),
                            GlobalRef::Window(window),
                            MouseEventBinding::Wrap)
     }
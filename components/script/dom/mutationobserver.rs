@@ -0,0 +1,375 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::callback::ExceptionHandling::Report;
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::MutationObserverBinding;
+use dom::bindings::codegen::Bindings::MutationObserverBinding::MutationCallback;
+use dom::bindings::codegen::Bindings::MutationObserverBinding::MutationObserverInit;
+use dom::bindings::codegen::Bindings::MutationObserverBinding::MutationObserverMethods;
+use dom::bindings::conversions::{FromJSValConvertible, StringificationBehavior};
+use dom::bindings::conversions::ToJSValConvertible;
+use dom::bindings::error::{ErrorResult, Fallible};
+use dom::bindings::error::Error::{JSFailed, TypeError};
+use dom::bindings::global::{GlobalRef, GlobalField};
+use dom::bindings::js::{JS, JSRef, Temporary, TemporaryPushable};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::mutationrecord::MutationRecord;
+use dom::node::{Node, NodeHelpers};
+use dom::window::WindowHelpers;
+use util::str::DOMString;
+
+use js::jsapi::{JSContext, JS_GetArrayLength, JS_GetElement, JS_IsArrayObject, JS_NewArrayObject};
+use js::jsval::{JSVal, ObjectValue, NullValue, UndefinedValue};
+use libc::c_int;
+use string_cache::{Atom, Namespace};
+
+use std::borrow::ToOwned;
+use std::mem;
+
+/// The options of a registered observer.
+/// http://dom.spec.whatwg.org/#dictdef-mutationobserverinit
+#[derive(Clone)]
+#[jstraceable]
+pub struct ObserverOptions {
+    child_list: bool,
+    attributes: bool,
+    character_data: bool,
+    subtree: bool,
+    attribute_old_value: bool,
+    character_data_old_value: bool,
+    /// The local names of the attributes to observe, if not all of them.
+    attribute_filter: Option<Vec<DOMString>>,
+}
+
+/// An entry in a node's list of registered observers.
+/// http://dom.spec.whatwg.org/#registered-observer
+#[jstraceable]
+#[must_root]
+pub struct RegisteredObserver {
+    observer: JS<MutationObserver>,
+    options: ObserverOptions,
+    /// Whether this is a transient registered observer, added to a node
+    /// removed from a subtree that was being observed.
+    transient: bool,
+}
+
+/// A mutation for which records should be queued.
+pub enum Mutation<'a> {
+    /// An attribute's local name and namespace, and its old value.
+    Attribute(&'a Atom, &'a Namespace, Option<DOMString>),
+    /// The old data of a character data node.
+    CharacterData(DOMString),
+    /// The added and removed nodes, and the previous and next siblings.
+    ChildList(Vec<JSRef<'a, Node>>, Vec<JSRef<'a, Node>>,
+              Option<JSRef<'a, Node>>, Option<JSRef<'a, Node>>),
+}
+
+// http://dom.spec.whatwg.org/#interface-mutationobserver
+#[dom_struct]
+pub struct MutationObserver {
+    reflector_: Reflector,
+    global: GlobalField,
+    callback: MutationCallback,
+    record_queue: DOMRefCell<Vec<JS<MutationRecord>>>,
+    /// The nodes this observer has registered observers on.
+    nodes: DOMRefCell<Vec<JS<Node>>>,
+}
+
+impl MutationObserver {
+    fn new_inherited(global: GlobalRef, callback: MutationCallback) -> MutationObserver {
+        MutationObserver {
+            reflector_: Reflector::new(),
+            global: GlobalField::from_rooted(&global),
+            callback: callback,
+            record_queue: DOMRefCell::new(vec!()),
+            nodes: DOMRefCell::new(vec!()),
+        }
+    }
+
+    pub fn new(global: GlobalRef, callback: MutationCallback) -> Temporary<MutationObserver> {
+        reflect_dom_object(box MutationObserver::new_inherited(global, callback),
+                           global,
+                           MutationObserverBinding::Wrap)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationobserver-mutationobserver
+    pub fn Constructor(global: GlobalRef, callback: MutationCallback)
+                       -> Fallible<Temporary<MutationObserver>> {
+        Ok(MutationObserver::new(global, callback))
+    }
+}
+
+pub trait MutationObserverHelpers {
+    fn notify(self) -> bool;
+    fn is_observing(self) -> bool;
+}
+
+impl<'a> MutationObserverHelpers for JSRef<'a, MutationObserver> {
+    /// Delivers the queued records of this observer, and returns whether there were any.
+    // http://dom.spec.whatwg.org/#notify-mutation-observers
+    fn notify(self) -> bool {
+        // Step 3.1-3.2.
+        let records = mem::replace(&mut *self.record_queue.borrow_mut(), vec!());
+
+        // Step 3.3.
+        self.remove_transient_registered_observers();
+
+        // Step 3.4.
+        if records.is_empty() {
+            return false;
+        }
+        let global = self.global.root();
+        let mutations = records_to_jsval(global.r().get_cx(), records);
+        let _ = self.callback.Call_(self, mutations, self, Report);
+        true
+    }
+
+    /// Whether this observer can still receive records: it is registered on some node, or
+    /// has records waiting to be delivered.
+    fn is_observing(self) -> bool {
+        !self.nodes.borrow().is_empty() || !self.record_queue.borrow().is_empty()
+    }
+}
+
+trait PrivateMutationObserverHelpers {
+    fn remove_transient_registered_observers(self);
+}
+
+impl<'a> PrivateMutationObserverHelpers for JSRef<'a, MutationObserver> {
+    fn remove_transient_registered_observers(self) {
+        self.nodes.borrow_mut().retain(|node| {
+            let node = node.root();
+            let mut registered_observers = node.r().registered_observers().borrow_mut();
+            registered_observers.retain(|registered| {
+                !(registered.transient && registered.observer == JS::from_rooted(self))
+            });
+            registered_observers.iter().any(|registered| {
+                registered.observer == JS::from_rooted(self)
+            })
+        });
+    }
+}
+
+impl<'a> MutationObserverMethods for JSRef<'a, MutationObserver> {
+    // http://dom.spec.whatwg.org/#dom-mutationobserver-observe
+    fn Observe(self, target: JSRef<Node>, options: &MutationObserverInit) -> ErrorResult {
+        let global = self.global.root();
+        let attribute_filter = try!(attribute_filter(global.r().get_cx(), options.attributeFilter));
+
+        // Step 1.
+        let attributes = options.attributes.unwrap_or(options.attributeOldValue.is_some() ||
+                                                      attribute_filter.is_some());
+
+        // Step 2.
+        let character_data =
+            options.characterData.unwrap_or(options.characterDataOldValue.is_some());
+
+        // Step 3.
+        if !options.childList && !attributes && !character_data {
+            return Err(TypeError("One of childList, attributes or characterData must be true"
+                                 .to_owned()));
+        }
+
+        // Step 4.
+        let attribute_old_value = options.attributeOldValue.unwrap_or(false);
+        if attribute_old_value && !attributes {
+            return Err(TypeError("attributeOldValue requires attributes".to_owned()));
+        }
+
+        // Step 5.
+        if attribute_filter.is_some() && !attributes {
+            return Err(TypeError("attributeFilter requires attributes".to_owned()));
+        }
+
+        // Step 6.
+        let character_data_old_value = options.characterDataOldValue.unwrap_or(false);
+        if character_data_old_value && !character_data {
+            return Err(TypeError("characterDataOldValue requires characterData".to_owned()));
+        }
+
+        let options = ObserverOptions {
+            child_list: options.childList,
+            attributes: attributes,
+            character_data: character_data,
+            subtree: options.subtree,
+            attribute_old_value: attribute_old_value,
+            character_data_old_value: character_data_old_value,
+            attribute_filter: attribute_filter,
+        };
+
+        // Step 7.
+        let mut registered_observers = target.registered_observers().borrow_mut();
+        for registered in registered_observers.iter_mut() {
+            if !registered.transient && registered.observer == JS::from_rooted(self) {
+                registered.options = options;
+                return Ok(());
+            }
+        }
+        global.r().as_window().register_mutation_observer(self);
+
+        // Step 8.
+        registered_observers.push(RegisteredObserver {
+            observer: JS::from_rooted(self),
+            options: options,
+            transient: false,
+        });
+        self.nodes.borrow_mut().push(JS::from_rooted(target));
+        Ok(())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationobserver-disconnect
+    fn Disconnect(self) {
+        for node in self.nodes.borrow_mut().drain() {
+            let node = node.root();
+            node.r().registered_observers().borrow_mut().retain(|registered| {
+                registered.observer != JS::from_rooted(self)
+            });
+        }
+        self.record_queue.borrow_mut().clear();
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationobserver-takerecords
+    fn TakeRecords(self, cx: *mut JSContext) -> JSVal {
+        let records = mem::replace(&mut *self.record_queue.borrow_mut(), vec!());
+        records_to_jsval(cx, records)
+    }
+}
+
+/// The local names in the attributeFilter member of a MutationObserverInit dictionary, which is
+/// either undefined or an array of strings.
+#[allow(unsafe_blocks)]
+fn attribute_filter(cx: *mut JSContext, filter: JSVal) -> Fallible<Option<Vec<DOMString>>> {
+    if filter.is_undefined() {
+        return Ok(None);
+    }
+    if !filter.is_object() || unsafe { JS_IsArrayObject(cx, filter.to_object()) } == 0 {
+        return Err(TypeError("attributeFilter is not a sequence.".to_owned()));
+    }
+
+    let array = filter.to_object();
+    let mut length = 0;
+    if unsafe { JS_GetArrayLength(cx, array, &mut length) } == 0 {
+        return Err(JSFailed);
+    }
+
+    let mut names = vec!();
+    for index in range(0, length) {
+        let mut value = UndefinedValue();
+        if unsafe { JS_GetElement(cx, array, index, &mut value) } == 0 {
+            return Err(JSFailed);
+        }
+        match FromJSValConvertible::from_jsval(cx, value, StringificationBehavior::Default) {
+            Ok(name) => names.push(name),
+            Err(()) => return Err(JSFailed),
+        }
+    }
+    Ok(Some(names))
+}
+
+/// An array of the given records.
+#[allow(unsafe_blocks)]
+fn records_to_jsval(cx: *mut JSContext, records: Vec<JS<MutationRecord>>) -> JSVal {
+    let mut values: Vec<JSVal> = records.iter().map(|record| {
+        record.root().r().to_jsval(cx)
+    }).collect();
+    let array = unsafe {
+        JS_NewArrayObject(cx, values.len() as c_int, values.as_mut_ptr())
+    };
+    if array.is_null() {
+        return NullValue();
+    }
+    unsafe { ObjectValue(&*array) }
+}
+
+/// Adds a transient registered observer to `node`, which is being removed
+/// from `parent`, for every observer of a subtree containing `parent`.
+/// http://dom.spec.whatwg.org/#concept-node-remove step 6
+pub fn add_transient_registered_observers(node: JSRef<Node>, parent: JSRef<Node>) {
+    for ancestor in parent.inclusive_ancestors() {
+        let ancestor_observers = ancestor.registered_observers().borrow();
+        for registered in ancestor_observers.iter().filter(|registered| registered.options.subtree) {
+            node.registered_observers().borrow_mut().push(RegisteredObserver {
+                observer: registered.observer,
+                options: registered.options.clone(),
+                transient: true,
+            });
+            let observer = registered.observer.root();
+            observer.r().nodes.borrow_mut().push(JS::from_rooted(node));
+        }
+    }
+}
+
+// http://dom.spec.whatwg.org/#queue-a-mutation-record
+#[allow(unrooted_must_root)]
+pub fn queue_mutation_record(target: JSRef<Node>, mutation: Mutation) {
+    // Step 1-3.
+    let mut interested_observers: Vec<(JS<MutationObserver>, Option<DOMString>)> = vec!();
+    for node in target.inclusive_ancestors() {
+        for registered in node.registered_observers().borrow().iter() {
+            let options = &registered.options;
+            // Step 3.1.
+            if node != target && !options.subtree {
+                continue;
+            }
+            if let Mutation::Attribute(local_name, namespace, _) = mutation {
+                if let Some(ref filter) = options.attribute_filter {
+                    if *namespace != ns!("") ||
+                       !filter.iter().any(|name| name.as_slice() == local_name.as_slice()) {
+                        continue;
+                    }
+                }
+            }
+            let (interested, old_value) = match mutation {
+                Mutation::Attribute(_, _, ref old_value) =>
+                    (options.attributes, if options.attribute_old_value { old_value.clone() } else { None }),
+                Mutation::CharacterData(ref old_value) =>
+                    (options.character_data,
+                     if options.character_data_old_value { Some(old_value.clone()) } else { None }),
+                Mutation::ChildList(..) => (options.child_list, None),
+            };
+            if !interested {
+                continue;
+            }
+
+            // Step 3.2-3.3.
+            match interested_observers.iter().position(|&(observer, _)| observer == registered.observer) {
+                Some(index) => {
+                    if old_value.is_some() {
+                        interested_observers[index].1 = old_value;
+                    }
+                }
+                None => interested_observers.push((registered.observer, old_value)),
+            }
+        }
+    }
+
+    // Step 4.
+    for (observer, old_value) in interested_observers.into_iter() {
+        let record = match mutation {
+            Mutation::Attribute(local_name, namespace, _) => {
+                let &Namespace(ref namespace) = namespace;
+                let namespace = match namespace.as_slice() {
+                    "" => None,
+                    url => Some(url.to_owned()),
+                };
+                MutationRecord::new("attributes", target, vec!(), vec!(), None, None,
+                                    Some(local_name.as_slice().to_owned()), namespace,
+                                    old_value)
+            }
+            Mutation::CharacterData(_) => {
+                MutationRecord::new("characterData", target, vec!(), vec!(), None, None,
+                                    None, None, old_value)
+            }
+            Mutation::ChildList(ref added, ref removed, previous_sibling, next_sibling) => {
+                MutationRecord::new("childList", target, added.clone(), removed.clone(),
+                                    previous_sibling, next_sibling, None, None, None)
+            }
+        };
+        let observer = observer.root();
+        observer.r().record_queue.borrow_mut().push_unrooted(&record);
+    }
+
+    // Step 5: the records are delivered at the next microtask checkpoint.
+}
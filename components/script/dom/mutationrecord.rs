@@ -0,0 +1,122 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::MutationRecordBinding;
+use dom::bindings::codegen::Bindings::MutationRecordBinding::MutationRecordMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::node::{Node, window_from_node};
+use dom::nodelist::NodeList;
+use util::str::DOMString;
+
+use std::borrow::ToOwned;
+
+// http://dom.spec.whatwg.org/#interface-mutationrecord
+#[dom_struct]
+pub struct MutationRecord {
+    reflector_: Reflector,
+    type_: DOMString,
+    target: JS<Node>,
+    added_nodes: JS<NodeList>,
+    removed_nodes: JS<NodeList>,
+    previous_sibling: Option<JS<Node>>,
+    next_sibling: Option<JS<Node>>,
+    attribute_name: Option<DOMString>,
+    attribute_namespace: Option<DOMString>,
+    old_value: Option<DOMString>,
+}
+
+impl MutationRecord {
+    fn new_inherited(type_: &str,
+                     target: JSRef<Node>,
+                     added_nodes: JSRef<NodeList>,
+                     removed_nodes: JSRef<NodeList>,
+                     previous_sibling: Option<JSRef<Node>>,
+                     next_sibling: Option<JSRef<Node>>,
+                     attribute_name: Option<DOMString>,
+                     attribute_namespace: Option<DOMString>,
+                     old_value: Option<DOMString>) -> MutationRecord {
+        MutationRecord {
+            reflector_: Reflector::new(),
+            type_: type_.to_owned(),
+            target: JS::from_rooted(target),
+            added_nodes: JS::from_rooted(added_nodes),
+            removed_nodes: JS::from_rooted(removed_nodes),
+            previous_sibling: previous_sibling.map(JS::from_rooted),
+            next_sibling: next_sibling.map(JS::from_rooted),
+            attribute_name: attribute_name,
+            attribute_namespace: attribute_namespace,
+            old_value: old_value,
+        }
+    }
+
+    pub fn new(type_: &str,
+               target: JSRef<Node>,
+               added_nodes: Vec<JSRef<Node>>,
+               removed_nodes: Vec<JSRef<Node>>,
+               previous_sibling: Option<JSRef<Node>>,
+               next_sibling: Option<JSRef<Node>>,
+               attribute_name: Option<DOMString>,
+               attribute_namespace: Option<DOMString>,
+               old_value: Option<DOMString>) -> Temporary<MutationRecord> {
+        let window = window_from_node(target).root();
+        let added_nodes = NodeList::new_simple_list(window.r(), added_nodes).root();
+        let removed_nodes = NodeList::new_simple_list(window.r(), removed_nodes).root();
+        reflect_dom_object(box MutationRecord::new_inherited(type_, target,
+                                                             added_nodes.r(), removed_nodes.r(),
+                                                             previous_sibling, next_sibling,
+                                                             attribute_name, attribute_namespace,
+                                                             old_value),
+                           GlobalRef::Window(window.r()),
+                           MutationRecordBinding::Wrap)
+    }
+}
+
+impl<'a> MutationRecordMethods for JSRef<'a, MutationRecord> {
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-type
+    fn Type(self) -> DOMString {
+        self.type_.clone()
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-target
+    fn Target(self) -> Temporary<Node> {
+        Temporary::new(self.target)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-addednodes
+    fn AddedNodes(self) -> Temporary<NodeList> {
+        Temporary::new(self.added_nodes)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-removednodes
+    fn RemovedNodes(self) -> Temporary<NodeList> {
+        Temporary::new(self.removed_nodes)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-previoussibling
+    fn GetPreviousSibling(self) -> Option<Temporary<Node>> {
+        self.previous_sibling.map(Temporary::new)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-nextsibling
+    fn GetNextSibling(self) -> Option<Temporary<Node>> {
+        self.next_sibling.map(Temporary::new)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-attributename
+    fn GetAttributeName(self) -> Option<DOMString> {
+        self.attribute_name.clone()
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-attributenamespace
+    fn GetAttributeNamespace(self) -> Option<DOMString> {
+        self.attribute_namespace.clone()
+    }
+
+    // http://dom.spec.whatwg.org/#dom-mutationrecord-oldvalue
+    fn GetOldValue(self) -> Option<DOMString> {
+        self.old_value.clone()
+    }
+}
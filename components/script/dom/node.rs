@@ -20,7 +20,7 @@ use dom::bindings::codegen::InheritTypes::{ProcessingInstructionCast, EventTarge
 use dom::bindings::codegen::InheritTypes::{HTMLLegendElementDerived, HTMLFieldSetElementDerived};
 use dom::bindings::codegen::InheritTypes::HTMLOptGroupElementDerived;
 use dom::bindings::conversions;
-use dom::bindings::error::Fallible;
+use dom::bindings::error::{ErrorResult, Fallible};
 use dom::bindings::error::Error::{NotFound, HierarchyRequest, Syntax};
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JS, JSRef, LayoutJS, RootedReference, Temporary, Root, Unrooted};
@@ -37,8 +37,11 @@ use dom::element::{AttributeHandlers, Element, ElementCreator, ElementTypeId};
 use dom::element::ElementHelpers;
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
 use dom::htmlelement::HTMLElementTypeId;
+use dom::mutationobserver::{Mutation, RegisteredObserver, queue_mutation_record};
+use dom::mutationobserver::add_transient_registered_observers;
 use dom::nodelist::NodeList;
 use dom::processinginstruction::ProcessingInstruction;
+use dom::range::{Range, RangeHelpers, WeakRangeRef, any_live_ranges, ranges_in};
 use dom::text::Text;
 use dom::virtualmethods::{VirtualMethods, vtable_for};
 use dom::window::Window;
@@ -112,6 +115,12 @@ pub struct Node {
     layout_data: LayoutDataRef,
 
     unique_id: DOMRefCell<String>,
+
+    /// The registered observers of this node.
+    registered_observers: DOMRefCell<Vec<RegisteredObserver>>,
+
+    /// The live ranges with a boundary point in this node.
+    ranges: DOMRefCell<Vec<WeakRangeRef>>,
 }
 
 impl NodeDerived for EventTarget {
@@ -186,7 +195,7 @@ impl Drop for Node {
 /// suppress observers flag
 /// http://dom.spec.whatwg.org/#concept-node-insert
 /// http://dom.spec.whatwg.org/#concept-node-remove
-#[derive(Copy)]
+#[derive(Copy, PartialEq)]
 enum SuppressObserver {
     Suppressed,
     Unsuppressed
@@ -416,6 +425,21 @@ pub trait NodeHelpers<'a> {
 
     fn type_id(self) -> NodeTypeId;
 
+    /// The number of preceding siblings of this node.
+    /// http://dom.spec.whatwg.org/#concept-tree-index
+    fn index(self) -> u32;
+    /// http://dom.spec.whatwg.org/#concept-node-length
+    fn len(self) -> u32;
+
+    /// http://dom.spec.whatwg.org/#registered-observer
+    fn registered_observers(self) -> &'a DOMRefCell<Vec<RegisteredObserver>>;
+
+    /// Makes the range follow the mutations of this node.
+    fn add_range(self, range: &WeakRangeRef);
+    fn remove_range(self, range: JSRef<Range>);
+    /// The live ranges with a boundary point in this node.
+    fn ranges(self) -> Vec<Temporary<Range>>;
+
     fn parent_node(self) -> Option<Temporary<Node>>;
     fn first_child(self) -> Option<Temporary<Node>>;
     fn last_child(self) -> Option<Temporary<Node>>;
@@ -530,6 +554,52 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
         self.type_id
     }
 
+    fn index(self) -> u32 {
+        let mut index = 0;
+        let mut current = self.prev_sibling();
+        while let Some(sibling) = current {
+            index += 1;
+            current = sibling.root().r().prev_sibling();
+        }
+        index
+    }
+
+    fn len(self) -> u32 {
+        match self.type_id {
+            NodeTypeId::DocumentType => 0,
+            NodeTypeId::Text |
+            NodeTypeId::Comment |
+            NodeTypeId::ProcessingInstruction => {
+                let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(self).unwrap();
+                characterdata.Length()
+            }
+            _ => self.children().count() as u32
+        }
+    }
+
+    fn registered_observers(self) -> &'a DOMRefCell<Vec<RegisteredObserver>> {
+        &self.extended_deref().registered_observers
+    }
+
+    fn add_range(self, range: &WeakRangeRef) {
+        let mut ranges = self.ranges.borrow_mut();
+        ranges.retain(|range| range.is_alive());
+        let new_range = range.get().unwrap().root();
+        if !ranges.iter().any(|existing| existing.refers_to(new_range.r())) {
+            ranges.push(range.clone());
+        }
+    }
+
+    fn remove_range(self, range: JSRef<Range>) {
+        self.ranges.borrow_mut().retain(|weak_ref| weak_ref.is_alive() && !weak_ref.refers_to(range));
+    }
+
+    fn ranges(self) -> Vec<Temporary<Range>> {
+        let mut ranges = self.ranges.borrow_mut();
+        ranges.retain(|range| range.is_alive());
+        ranges.iter().filter_map(|range| range.get()).collect()
+    }
+
     fn parent_node(self) -> Option<Temporary<Node>> {
         self.parent_node.get()
     }
@@ -609,7 +679,13 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
     }
 
     fn set_disabled_state(self, state: bool) {
-        self.set_flag(IN_DISABLED_STATE, state)
+        self.set_flag(IN_DISABLED_STATE, state);
+        if state {
+            if let Some(element) = ElementCast::to_ref(self) {
+                let document = self.owner_doc().root();
+                document.r().unfocus_if_focused(element);
+            }
+        }
     }
 
     fn get_enabled_state(self) -> bool {
@@ -843,7 +919,7 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
 
     fn remove_self(self) {
         match self.parent_node().root() {
-            Some(parent) => parent.r().remove_child(self),
+            Some(parent) => Node::remove(self, parent.r(), SuppressObserver::Unsuppressed),
             None => ()
         }
     }
@@ -1220,6 +1296,10 @@ impl Node {
             layout_data: LayoutDataRef::new(),
 
             unique_id: DOMRefCell::new(String::new()),
+
+            registered_observers: DOMRefCell::new(vec!()),
+
+            ranges: DOMRefCell::new(vec!()),
         }
     }
 
@@ -1260,9 +1340,10 @@ impl Node {
         // If node is an element, it is _affected by a base URL change_.
     }
 
-    // http://dom.spec.whatwg.org/#concept-node-pre-insert
-    fn pre_insert(node: JSRef<Node>, parent: JSRef<Node>, child: Option<JSRef<Node>>)
-                  -> Fallible<Temporary<Node>> {
+    // http://dom.spec.whatwg.org/#concept-node-ensure-pre-insertion-validity
+    pub fn ensure_pre_insertion_validity(node: JSRef<Node>,
+                                         parent: JSRef<Node>,
+                                         child: Option<JSRef<Node>>) -> ErrorResult {
         // Step 1.
         match parent.type_id() {
             NodeTypeId::Document |
@@ -1376,6 +1457,15 @@ impl Node {
             _ => (),
         }
 
+        Ok(())
+    }
+
+    // http://dom.spec.whatwg.org/#concept-node-pre-insert
+    fn pre_insert(node: JSRef<Node>, parent: JSRef<Node>, child: Option<JSRef<Node>>)
+                  -> Fallible<Temporary<Node>> {
+        // Step 1-6.
+        try!(Node::ensure_pre_insertion_validity(node, parent, child));
+
         // Step 7-8.
         let reference_child = match child {
             Some(child) if child.clone() == node => node.next_sibling().map(|node| node.root().get_unsound_ref_forever()),
@@ -1420,20 +1510,50 @@ impl Node {
         }
 
         // XXX assert owner_doc
-        // Step 1-3: ranges.
+        // Step 1-3.
+        match child {
+            Some(child) if any_live_ranges() => {
+                let count = match node.type_id() {
+                    NodeTypeId::DocumentFragment => node.children().count() as u32,
+                    _ => 1,
+                };
+                let index = child.index();
+                for range in parent.ranges().into_iter() {
+                    range.root().r().update_for_insertion(parent, index, count);
+                }
+            }
+            _ => (),
+        }
+
+        let previous_sibling = match child {
+            Some(child) => child.prev_sibling(),
+            None => parent.last_child(),
+        }.root();
 
         match node.type_id() {
             NodeTypeId::DocumentFragment => {
                 // Step 4.
-                // Step 5: DocumentFragment, mutation records.
-                // Step 6: DocumentFragment.
                 let mut kids = Vec::new();
                 for kid in node.children() {
                     kids.push(kid.clone());
-                    Node::remove(kid, node, SuppressObserver::Suppressed);
                 }
 
-                // Step 7: mutation records.
+                // Step 5.
+                if !kids.is_empty() {
+                    queue_mutation_record(node, Mutation::ChildList(vec!(), kids.clone(), None, None));
+                }
+
+                // Step 6.
+                for kid in kids.iter() {
+                    Node::remove(*kid, node, SuppressObserver::Suppressed);
+                }
+
+                // Step 7.
+                if suppress_observers == SuppressObserver::Unsuppressed && !kids.is_empty() {
+                    queue_mutation_record(parent, Mutation::ChildList(kids.clone(), vec!(),
+                                                                      previous_sibling.r(), child));
+                }
+
                 // Step 8.
                 for kid in kids.iter() {
                     do_insert((*kid).clone(), parent, child);
@@ -1445,9 +1565,13 @@ impl Node {
             }
             _ => {
                 // Step 4.
-                // Step 5: DocumentFragment, mutation records.
-                // Step 6: DocumentFragment.
-                // Step 7: mutation records.
+                // Step 5-6: DocumentFragment.
+                // Step 7.
+                if suppress_observers == SuppressObserver::Unsuppressed {
+                    queue_mutation_record(parent, Mutation::ChildList(vec!(node), vec!(),
+                                                                      previous_sibling.r(), child));
+                }
+
                 // Step 8.
                 do_insert(node, parent, child);
                 // Step 9.
@@ -1457,7 +1581,7 @@ impl Node {
     }
 
     // http://dom.spec.whatwg.org/#concept-node-replace-all
-    fn replace_all(node: Option<JSRef<Node>>, parent: JSRef<Node>) {
+    pub fn replace_all(node: Option<JSRef<Node>>, parent: JSRef<Node>) {
         // Step 1.
         match node {
             Some(node) => {
@@ -1490,7 +1614,12 @@ impl Node {
             None => (),
         }
 
-        // Step 6: mutation records.
+        // Step 6.
+        if !added_nodes.is_empty() || !removed_nodes.is_empty() {
+            queue_mutation_record(parent, Mutation::ChildList(added_nodes.clone(),
+                                                              removed_nodes.clone(),
+                                                              None, None));
+        }
 
         // Step 7.
         let parent_in_doc = parent.is_in_doc();
@@ -1522,8 +1651,27 @@ impl Node {
     fn remove(node: JSRef<Node>, parent: JSRef<Node>, suppress_observers: SuppressObserver) {
         assert!(node.parent_node().map_or(false, |node_parent| node_parent == Temporary::from_rooted(parent)));
 
-        // Step 1-5: ranges.
-        // Step 6-7: mutation observers.
+        // Step 1-5.
+        if any_live_ranges() {
+            let index = node.index();
+            let nodes = node.traverse_preorder().chain(Some(parent).into_iter());
+            for range in ranges_in(nodes).into_iter() {
+                range.root().r().update_for_removal(node, parent, index);
+            }
+        }
+
+        // Step 6.
+        add_transient_registered_observers(node, parent);
+
+        // Step 7.
+        if suppress_observers == SuppressObserver::Unsuppressed {
+            let previous_sibling = node.prev_sibling().root();
+            let next_sibling = node.next_sibling().root();
+            queue_mutation_record(parent, Mutation::ChildList(vec!(), vec!(node),
+                                                              previous_sibling.r(),
+                                                              next_sibling.r()));
+        }
+
         // Step 8.
         parent.remove_child(node);
 
@@ -1851,7 +1999,8 @@ impl<'a> NodeMethods for JSRef<'a, Node> {
             NodeTypeId::Text |
             NodeTypeId::ProcessingInstruction => {
                 let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(self).unwrap();
-                characterdata.set_data(value);
+                let length = characterdata.Length();
+                characterdata.ReplaceData(0, length, value).unwrap();
 
                 // Notify the document that the content of this node is different
                 let document = self.owner_doc().root();
@@ -1978,6 +2127,15 @@ impl<'a> NodeMethods for JSRef<'a, Node> {
         let document = document_from_node(self).root();
         Node::adopt(node, document.r());
 
+        let previous_sibling = child.prev_sibling().root();
+
+        // Step 12.
+        let nodes: Vec<JSRef<Node>> = if node.type_id() == NodeTypeId::DocumentFragment {
+            node.children().collect()
+        } else {
+            vec!(node)
+        };
+
         {
             // Step 10.
             Node::remove(child, self, SuppressObserver::Suppressed);
@@ -1986,14 +2144,13 @@ impl<'a> NodeMethods for JSRef<'a, Node> {
             Node::insert(node, self, reference_child, SuppressObserver::Suppressed);
         }
 
-        // Step 12-14.
-        // Step 13: mutation records.
+        // Step 13.
+        queue_mutation_record(self, Mutation::ChildList(nodes.clone(), vec!(child),
+                                                        previous_sibling.r(), reference_child));
+
+        // Step 14.
         child.node_removed(self.is_in_doc());
-        if node.type_id() == NodeTypeId::DocumentFragment {
-            for child_node in node.children() {
-                child_node.node_inserted();
-            }
-        } else {
+        for node in nodes.into_iter() {
             node.node_inserted();
         }
 
@@ -2016,13 +2173,13 @@ impl<'a> NodeMethods for JSRef<'a, Node> {
                 Some(text) => {
                     let characterdata: JSRef<CharacterData> = CharacterDataCast::from_ref(text);
                     if characterdata.Length() == 0 {
-                        self.remove_child(child);
+                        Node::remove(child, self, SuppressObserver::Unsuppressed);
                     } else {
                         match prev_text {
                             Some(text_node) => {
                                 let prev_characterdata: JSRef<CharacterData> = CharacterDataCast::from_ref(text_node);
                                 let _ = prev_characterdata.AppendData(characterdata.Data());
-                                self.remove_child(child);
+                                Node::remove(child, self, SuppressObserver::Unsuppressed);
                             },
                             None => prev_text = Some(text)
                         }
@@ -2,44 +2,1063 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use dom::bindings::codegen::Bindings::CharacterDataBinding::CharacterDataMethods;
+use dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
+use dom::bindings::codegen::Bindings::NodeBinding::NodeConstants;
+use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use dom::bindings::codegen::Bindings::RangeBinding;
+use dom::bindings::codegen::Bindings::RangeBinding::RangeConstants;
 use dom::bindings::codegen::Bindings::RangeBinding::RangeMethods;
+use dom::bindings::codegen::Bindings::TextBinding::TextMethods;
 use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
-use dom::bindings::error::Fallible;
+use dom::bindings::codegen::InheritTypes::{CharacterDataCast, ElementCast, NodeCast, TextCast};
+use dom::bindings::error::{ErrorResult, Fallible};
+use dom::bindings::error::Error::{HierarchyRequest, IndexSize, InvalidNodeType};
+use dom::bindings::error::Error::{InvalidState, NotSupported, WrongDocument};
 use dom::bindings::global::GlobalRef;
-use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::js::{JS, JSRef, MutHeap, OptionalRootable, Temporary};
+use dom::bindings::trace::JSTraceable;
 use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::characterdata::CharacterData;
 use dom::document::{Document, DocumentHelpers};
+use dom::documentfragment::DocumentFragment;
+use dom::element::ElementHelpers;
+use dom::node::{Node, NodeHelpers, NodeTypeId, document_from_node};
+use dom::text::Text;
 
+use util::str::DOMString;
+
+use js::jsapi::JSTracer;
+
+use std::borrow::ToOwned;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+// http://dom.spec.whatwg.org/#interface-range
 #[dom_struct]
 pub struct Range {
-    reflector_: Reflector
+    reflector_: Reflector,
+    start_container: MutHeap<JS<Node>>,
+    start_offset: Cell<u32>,
+    end_container: MutHeap<JS<Node>>,
+    end_offset: Cell<u32>,
+    /// The reference to this range that the containers of its boundary points hold.
+    weak_ref: WeakRangeRef,
+}
+
+/// The number of live ranges in this script task, so that mutations of the tree can skip
+/// looking for the ranges to update when there are none.
+thread_local!(static LIVE_RANGE_COUNT: Cell<usize> = Cell::new(0));
+
+/// Whether any range is alive in this script task.
+pub fn any_live_ranges() -> bool {
+    LIVE_RANGE_COUNT.with(|count| count.get() > 0)
+}
+
+#[unsafe_destructor]
+impl Drop for Range {
+    fn drop(&mut self) {
+        self.weak_ref.0.set(None);
+        LIVE_RANGE_COUNT.with(|count| count.set(count.get() - 1));
+    }
+}
+
+/// A reference to a range that doesn't keep it alive, so that nodes can keep track of the
+/// ranges with a boundary point in them. It is cleared when the range is finalized.
+#[derive(Clone)]
+#[allow(unrooted_must_root)]
+pub struct WeakRangeRef(Rc<Cell<Option<JS<Range>>>>);
+
+impl WeakRangeRef {
+    fn new() -> WeakRangeRef {
+        WeakRangeRef(Rc::new(Cell::new(None)))
+    }
+
+    /// The range, unless it has been finalized.
+    pub fn get(&self) -> Option<Temporary<Range>> {
+        self.0.get().map(Temporary::new)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.0.get().is_some()
+    }
+
+    pub fn refers_to(&self, range: JSRef<Range>) -> bool {
+        self.0.get() == Some(JS::from_rooted(range))
+    }
+}
+
+impl JSTraceable for WeakRangeRef {
+    fn trace(&self, _: *mut JSTracer) {
+        // The reference is weak, so it isn't traced.
+    }
 }
 
 impl Range {
-    fn new_inherited() -> Range {
+    fn new_inherited(start_container: JSRef<Node>, start_offset: u32,
+                     end_container: JSRef<Node>, end_offset: u32) -> Range {
         Range {
-            reflector_: Reflector::new()
+            reflector_: Reflector::new(),
+            start_container: MutHeap::new(JS::from_rooted(start_container)),
+            start_offset: Cell::new(start_offset),
+            end_container: MutHeap::new(JS::from_rooted(end_container)),
+            end_offset: Cell::new(end_offset),
+            weak_ref: WeakRangeRef::new(),
         }
     }
 
-    pub fn new(document: JSRef<Document>) -> Temporary<Range> {
+    /// Create a range collapsed at the start of `document`.
+    pub fn new_with_doc(document: JSRef<Document>) -> Temporary<Range> {
+        let root: JSRef<Node> = NodeCast::from_ref(document);
+        Range::new(document, root, 0, root, 0)
+    }
+
+    pub fn new(document: JSRef<Document>,
+               start_container: JSRef<Node>, start_offset: u32,
+               end_container: JSRef<Node>, end_offset: u32) -> Temporary<Range> {
         let window = document.window().root();
-        reflect_dom_object(box Range::new_inherited(),
-                           GlobalRef::Window(window.r()),
-                           RangeBinding::Wrap)
+        let range = reflect_dom_object(box Range::new_inherited(start_container, start_offset,
+                                                                 end_container, end_offset),
+                                       GlobalRef::Window(window.r()),
+                                       RangeBinding::Wrap).root();
+        range.r().weak_ref.0.set(Some(JS::from_rooted(range.r())));
+        LIVE_RANGE_COUNT.with(|count| count.set(count.get() + 1));
+        start_container.add_range(&range.r().weak_ref);
+        end_container.add_range(&range.r().weak_ref);
+        Temporary::from_rooted(range.r())
     }
 
+    // http://dom.spec.whatwg.org/#dom-range
     pub fn Constructor(global: GlobalRef) -> Fallible<Temporary<Range>> {
         let document = global.as_window().Document().root();
-        Ok(Range::new(document.r()))
+        Ok(Range::new_with_doc(document.r()))
+    }
+}
+
+/// The live ranges with a boundary point in any of `nodes`, each once.
+pub fn ranges_in<'a, I>(nodes: I) -> Vec<Temporary<Range>> where I: Iterator<Item=JSRef<'a, Node>> {
+    let mut ranges = vec!();
+    for node in nodes {
+        for range in node.ranges().into_iter() {
+            if !ranges.contains(&range) {
+                ranges.push(range);
+            }
+        }
+    }
+    ranges
+}
+
+/// The updates that keep live ranges in sync with mutations of the tree.
+pub trait RangeHelpers {
+    /// http://dom.spec.whatwg.org/#concept-node-insert, steps 1-3.
+    fn update_for_insertion(self, parent: JSRef<Node>, index: u32, count: u32);
+    /// http://dom.spec.whatwg.org/#concept-node-remove, steps 1-5.
+    fn update_for_removal(self, node: JSRef<Node>, parent: JSRef<Node>, index: u32);
+    /// http://dom.spec.whatwg.org/#concept-cd-replace, steps 7-10.
+    fn update_for_replace_data(self, node: JSRef<Node>, offset: u32, count: u32, length: u32);
+    /// http://dom.spec.whatwg.org/#concept-text-split, steps 7.2-7.5.
+    fn update_for_split_text(self, node: JSRef<Node>, new_node: JSRef<Node>, offset: u32,
+                             parent: JSRef<Node>, index: u32);
+}
+
+impl<'a> RangeHelpers for JSRef<'a, Range> {
+    fn update_for_insertion(self, parent: JSRef<Node>, index: u32, count: u32) {
+        if self.start_container.get() == JS::from_rooted(parent) && self.start_offset.get() > index {
+            self.start_offset.set(self.start_offset.get() + count);
+        }
+        if self.end_container.get() == JS::from_rooted(parent) && self.end_offset.get() > index {
+            self.end_offset.set(self.end_offset.get() + count);
+        }
+    }
+
+    fn update_for_removal(self, node: JSRef<Node>, parent: JSRef<Node>, index: u32) {
+        let start = self.start_container.get().root();
+        if node.is_inclusive_ancestor_of(start.r()) {
+            self.set_start_point(parent, index);
+        }
+        let end = self.end_container.get().root();
+        if node.is_inclusive_ancestor_of(end.r()) {
+            self.set_end_point(parent, index);
+        }
+
+        if self.start_container.get() == JS::from_rooted(parent) && self.start_offset.get() > index {
+            self.start_offset.set(self.start_offset.get() - 1);
+        }
+        if self.end_container.get() == JS::from_rooted(parent) && self.end_offset.get() > index {
+            self.end_offset.set(self.end_offset.get() - 1);
+        }
+    }
+
+    fn update_for_replace_data(self, node: JSRef<Node>, offset: u32, count: u32, length: u32) {
+        fn replaced_offset(current: u32, offset: u32, count: u32, length: u32) -> u32 {
+            if current > offset && current <= offset + count {
+                offset
+            } else if current > offset + count {
+                current + length - count
+            } else {
+                current
+            }
+        }
+
+        if self.start_container.get() == JS::from_rooted(node) {
+            self.start_offset.set(replaced_offset(self.start_offset.get(), offset, count, length));
+        }
+        if self.end_container.get() == JS::from_rooted(node) {
+            self.end_offset.set(replaced_offset(self.end_offset.get(), offset, count, length));
+        }
+    }
+
+    fn update_for_split_text(self, node: JSRef<Node>, new_node: JSRef<Node>, offset: u32,
+                             parent: JSRef<Node>, index: u32) {
+        if self.start_container.get() == JS::from_rooted(node) && self.start_offset.get() > offset {
+            self.set_start_point(new_node, self.start_offset.get() - offset);
+        }
+        if self.end_container.get() == JS::from_rooted(node) && self.end_offset.get() > offset {
+            self.set_end_point(new_node, self.end_offset.get() - offset);
+        }
+
+        if self.start_container.get() == JS::from_rooted(parent) && self.start_offset.get() == index + 1 {
+            self.start_offset.set(index + 2);
+        }
+        if self.end_container.get() == JS::from_rooted(parent) && self.end_offset.get() == index + 1 {
+            self.end_offset.set(index + 2);
+        }
+    }
+}
+
+trait PrivateRangeHelpers {
+    fn root_node(self) -> Temporary<Node>;
+    fn set_start(self, node: JSRef<Node>, offset: u32) -> ErrorResult;
+    fn set_end(self, node: JSRef<Node>, offset: u32) -> ErrorResult;
+    fn set_start_and_end(self, node: JSRef<Node>, offset: u32);
+    /// Moves the start of the range, without the checks of `set_start`.
+    fn set_start_point(self, node: JSRef<Node>, offset: u32);
+    /// Moves the end of the range, without the checks of `set_end`.
+    fn set_end_point(self, node: JSRef<Node>, offset: u32);
+    fn forget_container(self, node: JSRef<Node>);
+    fn contains(self, node: JSRef<Node>) -> bool;
+    fn partially_contains(self, node: JSRef<Node>) -> bool;
+    fn process_contents(self, action: ContentsAction) -> Fallible<Temporary<DocumentFragment>>;
+}
+
+/// Whether `process_contents` moves the contents of the range out of the
+/// tree, or copies them.
+#[derive(Copy, PartialEq)]
+enum ContentsAction {
+    Extract,
+    Clone,
+}
+
+/// http://dom.spec.whatwg.org/#concept-tree-root
+fn root_of<'a>(node: JSRef<'a, Node>) -> JSRef<'a, Node> {
+    node.inclusive_ancestors().last().unwrap()
+}
+
+fn is_character_data(node: JSRef<Node>) -> bool {
+    match node.type_id() {
+        NodeTypeId::Text | NodeTypeId::Comment | NodeTypeId::ProcessingInstruction => true,
+        _ => false
+    }
+}
+
+/// The position of the boundary point (`node_a`, `offset_a`) relative to
+/// (`node_b`, `offset_b`); both nodes must share the same root.
+/// http://dom.spec.whatwg.org/#concept-range-bp-position
+fn bp_position(node_a: JSRef<Node>, offset_a: u32,
+               node_b: JSRef<Node>, offset_b: u32) -> Ordering {
+    // Step 1.
+    if node_a == node_b {
+        return offset_a.cmp(&offset_b);
+    }
+
+    let position = node_b.CompareDocumentPosition(node_a);
+
+    // Step 2.
+    if position & NodeConstants::DOCUMENT_POSITION_FOLLOWING != 0 {
+        return match bp_position(node_b, offset_b, node_a, offset_a) {
+            Ordering::Less => Ordering::Greater,
+            Ordering::Equal => Ordering::Equal,
+            Ordering::Greater => Ordering::Less,
+        };
+    }
+
+    // Step 3.
+    if position & NodeConstants::DOCUMENT_POSITION_CONTAINS != 0 {
+        let child = node_b.inclusive_ancestors().find(|child| node_a.is_parent_of(*child)).unwrap();
+        if child.index() < offset_a {
+            return Ordering::Greater;
+        }
+    }
+
+    // Step 4.
+    Ordering::Less
+}
+
+impl<'a> PrivateRangeHelpers for JSRef<'a, Range> {
+    // http://dom.spec.whatwg.org/#concept-range-root
+    fn root_node(self) -> Temporary<Node> {
+        let start = self.start_container.get().root();
+        Temporary::from_rooted(root_of(start.r()))
+    }
+
+    // http://dom.spec.whatwg.org/#concept-range-bp-set
+    fn set_start(self, node: JSRef<Node>, offset: u32) -> ErrorResult {
+        // Step 1.
+        if node.type_id() == NodeTypeId::DocumentType {
+            return Err(InvalidNodeType);
+        }
+
+        // Step 2.
+        if offset > node.len() {
+            return Err(IndexSize);
+        }
+
+        // Step 3-4.
+        let root = self.root_node().root();
+        let end = self.end_container.get().root();
+        if root_of(node) != root.r() ||
+           bp_position(node, offset, end.r(), self.end_offset.get()) == Ordering::Greater {
+            self.set_end_point(node, offset);
+        }
+        self.set_start_point(node, offset);
+        Ok(())
+    }
+
+    // http://dom.spec.whatwg.org/#concept-range-bp-set
+    fn set_end(self, node: JSRef<Node>, offset: u32) -> ErrorResult {
+        // Step 1.
+        if node.type_id() == NodeTypeId::DocumentType {
+            return Err(InvalidNodeType);
+        }
+
+        // Step 2.
+        if offset > node.len() {
+            return Err(IndexSize);
+        }
+
+        // Step 3-4.
+        let root = self.root_node().root();
+        let start = self.start_container.get().root();
+        if root_of(node) != root.r() ||
+           bp_position(node, offset, start.r(), self.start_offset.get()) == Ordering::Less {
+            self.set_start_point(node, offset);
+        }
+        self.set_end_point(node, offset);
+        Ok(())
+    }
+
+    fn set_start_and_end(self, node: JSRef<Node>, offset: u32) {
+        self.set_start_point(node, offset);
+        self.set_end_point(node, offset);
+    }
+
+    fn set_start_point(self, node: JSRef<Node>, offset: u32) {
+        let old_container = self.start_container.get().root();
+        if old_container.r() != node {
+            self.start_container.set(JS::from_rooted(node));
+            node.add_range(&self.weak_ref);
+            self.forget_container(old_container.r());
+        }
+        self.start_offset.set(offset);
+    }
+
+    fn set_end_point(self, node: JSRef<Node>, offset: u32) {
+        let old_container = self.end_container.get().root();
+        if old_container.r() != node {
+            self.end_container.set(JS::from_rooted(node));
+            node.add_range(&self.weak_ref);
+            self.forget_container(old_container.r());
+        }
+        self.end_offset.set(offset);
+    }
+
+    /// Stops `node` from keeping track of this range, unless a boundary point is still in it.
+    fn forget_container(self, node: JSRef<Node>) {
+        if self.start_container.get() != JS::from_rooted(node) &&
+           self.end_container.get() != JS::from_rooted(node) {
+            node.remove_range(self);
+        }
+    }
+
+    // http://dom.spec.whatwg.org/#contained
+    fn contains(self, node: JSRef<Node>) -> bool {
+        let root = self.root_node().root();
+        if root_of(node) != root.r() {
+            return false;
+        }
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        bp_position(node, 0, start.r(), self.start_offset.get()) == Ordering::Greater &&
+        bp_position(node, node.len(), end.r(), self.end_offset.get()) == Ordering::Less
+    }
+
+    // http://dom.spec.whatwg.org/#partially-contained
+    fn partially_contains(self, node: JSRef<Node>) -> bool {
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        node.is_inclusive_ancestor_of(start.r()) != node.is_inclusive_ancestor_of(end.r())
+    }
+
+    // http://dom.spec.whatwg.org/#concept-range-extract
+    // http://dom.spec.whatwg.org/#concept-range-clone
+    fn process_contents(self, action: ContentsAction) -> Fallible<Temporary<DocumentFragment>> {
+        let start_node = self.start_container.get().root();
+        let start_node = start_node.r();
+        let start_offset = self.start_offset.get();
+        let end_node = self.end_container.get().root();
+        let end_node = end_node.r();
+        let end_offset = self.end_offset.get();
+
+        // Step 1.
+        let document = document_from_node(start_node).root();
+        let fragment = DocumentFragment::new(document.r()).root();
+        let fragment_node: JSRef<Node> = NodeCast::from_ref(fragment.r());
+
+        // Step 2.
+        if self.Collapsed() {
+            return Ok(Temporary::from_rooted(fragment.r()));
+        }
+
+        // Step 3.
+        if start_node == end_node && is_character_data(start_node) {
+            let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(start_node).unwrap();
+            let clone = start_node.CloneNode(false).root();
+            let clone_data: JSRef<CharacterData> = CharacterDataCast::to_ref(clone.r()).unwrap();
+            let data = try!(characterdata.SubstringData(start_offset, end_offset - start_offset));
+            try!(clone_data.SetData(data));
+            try!(fragment_node.AppendChild(clone.r()));
+            if action == ContentsAction::Extract {
+                try!(characterdata.ReplaceData(start_offset, end_offset - start_offset,
+                                               "".to_owned()));
+            }
+            return Ok(Temporary::from_rooted(fragment.r()));
+        }
+
+        // Step 4.
+        let common_ancestor = start_node.inclusive_ancestors()
+                                        .find(|ancestor| ancestor.is_inclusive_ancestor_of(end_node))
+                                        .unwrap();
+
+        // Step 5.
+        let first_partially_contained_child = if start_node.is_inclusive_ancestor_of(end_node) {
+            None
+        } else {
+            common_ancestor.children().find(|child| self.partially_contains(*child))
+        };
+
+        // Step 6.
+        let last_partially_contained_child = if end_node.is_inclusive_ancestor_of(start_node) {
+            None
+        } else {
+            common_ancestor.rev_children()
+                           .map(|child| child.root().get_unsound_ref_forever())
+                           .find(|child| self.partially_contains(*child))
+        };
+
+        // Step 7.
+        let contained_children: Vec<JSRef<Node>> =
+            common_ancestor.children().filter(|child| self.contains(*child)).collect();
+
+        // Step 8.
+        if contained_children.iter().any(|child| child.is_doctype()) {
+            return Err(HierarchyRequest);
+        }
+
+        // Step 9-10.
+        let (new_node, new_offset) = if start_node.is_inclusive_ancestor_of(end_node) {
+            (start_node, start_offset)
+        } else {
+            let reference_node = start_node.inclusive_ancestors()
+                                           .take_while(|ancestor| !ancestor.is_inclusive_ancestor_of(end_node))
+                                           .last()
+                                           .unwrap();
+            let parent = reference_node.parent_node().root().unwrap().get_unsound_ref_forever();
+            (parent, reference_node.index() + 1)
+        };
+
+        match first_partially_contained_child {
+            // Step 11.
+            Some(child) if is_character_data(child) => {
+                let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(start_node).unwrap();
+                let length = start_node.len();
+                let clone = start_node.CloneNode(false).root();
+                let clone_data: JSRef<CharacterData> = CharacterDataCast::to_ref(clone.r()).unwrap();
+                let data = try!(characterdata.SubstringData(start_offset, length - start_offset));
+                try!(clone_data.SetData(data));
+                try!(fragment_node.AppendChild(clone.r()));
+                if action == ContentsAction::Extract {
+                    try!(characterdata.ReplaceData(start_offset, length - start_offset,
+                                                   "".to_owned()));
+                }
+            }
+            // Step 12.
+            Some(child) => {
+                let clone = child.CloneNode(false).root();
+                try!(fragment_node.AppendChild(clone.r()));
+                let subrange = Range::new(document.r(), start_node, start_offset,
+                                          child, child.len()).root();
+                let subfragment = try!(subrange.r().process_contents(action)).root();
+                let subfragment: JSRef<Node> = NodeCast::from_ref(subfragment.r());
+                try!(clone.r().AppendChild(subfragment));
+            }
+            None => (),
+        }
+
+        // Step 13.
+        for child in contained_children.into_iter() {
+            match action {
+                ContentsAction::Extract => {
+                    try!(fragment_node.AppendChild(child));
+                }
+                ContentsAction::Clone => {
+                    let clone = child.CloneNode(true).root();
+                    try!(fragment_node.AppendChild(clone.r()));
+                }
+            }
+        }
+
+        match last_partially_contained_child {
+            // Step 14.
+            Some(child) if is_character_data(child) => {
+                let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(end_node).unwrap();
+                let clone = end_node.CloneNode(false).root();
+                let clone_data: JSRef<CharacterData> = CharacterDataCast::to_ref(clone.r()).unwrap();
+                let data = try!(characterdata.SubstringData(0, end_offset));
+                try!(clone_data.SetData(data));
+                try!(fragment_node.AppendChild(clone.r()));
+                if action == ContentsAction::Extract {
+                    try!(characterdata.ReplaceData(0, end_offset, "".to_owned()));
+                }
+            }
+            // Step 15.
+            Some(child) => {
+                let clone = child.CloneNode(false).root();
+                try!(fragment_node.AppendChild(clone.r()));
+                let subrange = Range::new(document.r(), child, 0, end_node, end_offset).root();
+                let subfragment = try!(subrange.r().process_contents(action)).root();
+                let subfragment: JSRef<Node> = NodeCast::from_ref(subfragment.r());
+                try!(clone.r().AppendChild(subfragment));
+            }
+            None => (),
+        }
+
+        // Step 16.
+        if action == ContentsAction::Extract {
+            self.set_start_and_end(new_node, new_offset);
+        }
+
+        // Step 17.
+        Ok(Temporary::from_rooted(fragment.r()))
     }
 }
 
 impl<'a> RangeMethods for JSRef<'a, Range> {
-    /// http://dom.spec.whatwg.org/#dom-range-detach
+    // http://dom.spec.whatwg.org/#dom-range-startcontainer
+    fn StartContainer(self) -> Temporary<Node> {
+        Temporary::new(self.start_container.get())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-startoffset
+    fn StartOffset(self) -> u32 {
+        self.start_offset.get()
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-endcontainer
+    fn EndContainer(self) -> Temporary<Node> {
+        Temporary::new(self.end_container.get())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-endoffset
+    fn EndOffset(self) -> u32 {
+        self.end_offset.get()
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-collapsed
+    fn Collapsed(self) -> bool {
+        self.start_container.get() == self.end_container.get() &&
+        self.start_offset.get() == self.end_offset.get()
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-commonancestorcontainer
+    fn CommonAncestorContainer(self) -> Temporary<Node> {
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        let common_ancestor = start.r().inclusive_ancestors()
+                                       .find(|ancestor| ancestor.is_inclusive_ancestor_of(end.r()))
+                                       .unwrap();
+        Temporary::from_rooted(common_ancestor)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-setstart
+    fn SetStart(self, node: JSRef<Node>, offset: u32) -> ErrorResult {
+        self.set_start(node, offset)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-setend
+    fn SetEnd(self, node: JSRef<Node>, offset: u32) -> ErrorResult {
+        self.set_end(node, offset)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-setstartbefore
+    fn SetStartBefore(self, node: JSRef<Node>) -> ErrorResult {
+        let parent = try!(node.parent_node().root().ok_or(InvalidNodeType));
+        self.set_start(parent.r(), node.index())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-setstartafter
+    fn SetStartAfter(self, node: JSRef<Node>) -> ErrorResult {
+        let parent = try!(node.parent_node().root().ok_or(InvalidNodeType));
+        self.set_start(parent.r(), node.index() + 1)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-setendbefore
+    fn SetEndBefore(self, node: JSRef<Node>) -> ErrorResult {
+        let parent = try!(node.parent_node().root().ok_or(InvalidNodeType));
+        self.set_end(parent.r(), node.index())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-setendafter
+    fn SetEndAfter(self, node: JSRef<Node>) -> ErrorResult {
+        let parent = try!(node.parent_node().root().ok_or(InvalidNodeType));
+        self.set_end(parent.r(), node.index() + 1)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-collapse
+    fn Collapse(self, to_start: bool) {
+        if to_start {
+            let start = self.start_container.get().root();
+            self.set_end_point(start.r(), self.start_offset.get());
+        } else {
+            let end = self.end_container.get().root();
+            self.set_start_point(end.r(), self.end_offset.get());
+        }
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-selectnode
+    fn SelectNode(self, node: JSRef<Node>) -> ErrorResult {
+        // Step 1-2.
+        let parent = try!(node.parent_node().root().ok_or(InvalidNodeType));
+
+        // Step 3.
+        let index = node.index();
+
+        // Step 4-5.
+        try!(self.set_start(parent.r(), index));
+        self.set_end(parent.r(), index + 1)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-selectnodecontents
+    fn SelectNodeContents(self, node: JSRef<Node>) -> ErrorResult {
+        // Step 1.
+        if node.is_doctype() {
+            return Err(InvalidNodeType);
+        }
+
+        // Step 2-4.
+        try!(self.set_start(node, 0));
+        self.set_end(node, node.len())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-compareboundarypoints
+    fn CompareBoundaryPoints(self, how: u16, source_range: JSRef<Range>) -> Fallible<i16> {
+        // Step 1.
+        let (this_point, other_point) = match how {
+            RangeConstants::START_TO_START => (true, true),
+            RangeConstants::START_TO_END => (false, true),
+            RangeConstants::END_TO_END => (false, false),
+            RangeConstants::END_TO_START => (true, false),
+            _ => return Err(NotSupported),
+        };
+
+        // Step 2.
+        let root = self.root_node().root();
+        let source_root = source_range.root_node().root();
+        if root.r() != source_root.r() {
+            return Err(WrongDocument);
+        }
+
+        // Step 3-10.
+        let (this_node, this_offset) = if this_point {
+            (self.start_container.get().root(), self.start_offset.get())
+        } else {
+            (self.end_container.get().root(), self.end_offset.get())
+        };
+        let (other_node, other_offset) = if other_point {
+            (source_range.start_container.get().root(), source_range.start_offset.get())
+        } else {
+            (source_range.end_container.get().root(), source_range.end_offset.get())
+        };
+        Ok(match bp_position(this_node.r(), this_offset, other_node.r(), other_offset) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        })
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-deletecontents
+    fn DeleteContents(self) -> ErrorResult {
+        // Step 1.
+        if self.Collapsed() {
+            return Ok(());
+        }
+
+        // Step 2.
+        let start_node = self.start_container.get().root();
+        let start_node = start_node.r();
+        let start_offset = self.start_offset.get();
+        let end_node = self.end_container.get().root();
+        let end_node = end_node.r();
+        let end_offset = self.end_offset.get();
+
+        // Step 3.
+        if start_node == end_node && is_character_data(start_node) {
+            let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(start_node).unwrap();
+            return characterdata.ReplaceData(start_offset, end_offset - start_offset,
+                                             "".to_owned());
+        }
+
+        // Step 4.
+        let common_ancestor = start_node.inclusive_ancestors()
+                                        .find(|ancestor| ancestor.is_inclusive_ancestor_of(end_node))
+                                        .unwrap();
+        let nodes_to_remove: Vec<JSRef<Node>> = common_ancestor.traverse_preorder().filter(|node| {
+            self.contains(*node) && !node.parent_node().root().map_or(false, |parent| {
+                self.contains(parent.r())
+            })
+        }).collect();
+
+        // Step 5-6.
+        let (new_node, new_offset) = if start_node.is_inclusive_ancestor_of(end_node) {
+            (start_node, start_offset)
+        } else {
+            let reference_node = start_node.inclusive_ancestors()
+                                           .take_while(|ancestor| !ancestor.is_inclusive_ancestor_of(end_node))
+                                           .last()
+                                           .unwrap();
+            let parent = reference_node.parent_node().root().unwrap().get_unsound_ref_forever();
+            (parent, reference_node.index() + 1)
+        };
+
+        // Step 7.
+        if is_character_data(start_node) {
+            let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(start_node).unwrap();
+            let length = start_node.len();
+            try!(characterdata.ReplaceData(start_offset, length - start_offset, "".to_owned()));
+        }
+
+        // Step 8.
+        for node in nodes_to_remove.into_iter() {
+            node.remove_self();
+        }
+
+        // Step 9.
+        if is_character_data(end_node) {
+            let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(end_node).unwrap();
+            try!(characterdata.ReplaceData(0, end_offset, "".to_owned()));
+        }
+
+        // Step 10.
+        self.set_start_and_end(new_node, new_offset);
+        Ok(())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-extractcontents
+    fn ExtractContents(self) -> Fallible<Temporary<DocumentFragment>> {
+        self.process_contents(ContentsAction::Extract)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-clonecontents
+    fn CloneContents(self) -> Fallible<Temporary<DocumentFragment>> {
+        self.process_contents(ContentsAction::Clone)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-insertnode
+    // http://dom.spec.whatwg.org/#concept-range-insert
+    fn InsertNode(self, node: JSRef<Node>) -> ErrorResult {
+        let start_node = self.start_container.get().root();
+        let start_node = start_node.r();
+        let start_offset = self.start_offset.get();
+
+        // Step 1.
+        match start_node.type_id() {
+            NodeTypeId::ProcessingInstruction | NodeTypeId::Comment => return Err(HierarchyRequest),
+            NodeTypeId::Text if start_node.parent_node().is_none() => return Err(HierarchyRequest),
+            _ if start_node == node => return Err(HierarchyRequest),
+            _ => (),
+        }
+
+        // Step 2-3.
+        let reference_node = if start_node.is_text() {
+            Some(start_node)
+        } else {
+            start_node.children().nth(start_offset as usize)
+        };
+
+        // Step 4.
+        let parent = match reference_node {
+            None => start_node,
+            Some(reference_node) => {
+                reference_node.parent_node().root().unwrap().get_unsound_ref_forever()
+            }
+        };
+
+        // Step 5.
+        try!(Node::ensure_pre_insertion_validity(node, parent, reference_node));
+
+        // Step 6.
+        let reference_node = match TextCast::to_ref(start_node) {
+            Some(text) => {
+                let text: JSRef<Text> = text;
+                let split: Temporary<Node> = NodeCast::from_temporary(try!(text.SplitText(start_offset)));
+                Some(split.root().get_unsound_ref_forever())
+            }
+            None => reference_node,
+        };
+
+        // Step 7.
+        let reference_node = if reference_node == Some(node) {
+            node.next_sibling().root().map(|sibling| sibling.get_unsound_ref_forever())
+        } else {
+            reference_node
+        };
+
+        // Step 8.
+        match node.parent_node().root() {
+            Some(old_parent) => {
+                try!(old_parent.r().RemoveChild(node));
+            }
+            None => (),
+        }
+
+        // Step 9.
+        let new_offset = match reference_node {
+            None => parent.len(),
+            Some(reference_node) => reference_node.index(),
+        };
+
+        // Step 10.
+        let new_offset = new_offset + match node.type_id() {
+            NodeTypeId::DocumentFragment => node.len(),
+            _ => 1,
+        };
+
+        // Step 11.
+        try!(parent.InsertBefore(node, reference_node));
+
+        // Step 12.
+        if self.Collapsed() {
+            self.set_end_point(parent, new_offset);
+        }
+        Ok(())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-surroundcontents
+    fn SurroundContents(self, new_parent: JSRef<Node>) -> ErrorResult {
+        // Step 1.
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        let common_ancestor = start.r().inclusive_ancestors()
+                                       .find(|ancestor| ancestor.is_inclusive_ancestor_of(end.r()))
+                                       .unwrap();
+        if common_ancestor.traverse_preorder().any(|node| {
+            !node.is_text() && self.partially_contains(node)
+        }) {
+            return Err(InvalidState);
+        }
+
+        // Step 2.
+        match new_parent.type_id() {
+            NodeTypeId::Document | NodeTypeId::DocumentType | NodeTypeId::DocumentFragment => {
+                return Err(InvalidNodeType);
+            }
+            _ => (),
+        }
+
+        // Step 3.
+        let fragment = try!(self.ExtractContents()).root();
+        let fragment: JSRef<Node> = NodeCast::from_ref(fragment.r());
+
+        // Step 4.
+        let children: Vec<JSRef<Node>> = new_parent.children().collect();
+        for child in children.into_iter() {
+            try!(new_parent.RemoveChild(child));
+        }
+
+        // Step 5.
+        try!(self.InsertNode(new_parent));
+
+        // Step 6.
+        try!(new_parent.AppendChild(fragment));
+
+        // Step 7.
+        self.SelectNode(new_parent)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-clonerange
+    fn CloneRange(self) -> Temporary<Range> {
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        let document = document_from_node(start.r()).root();
+        Range::new(document.r(), start.r(), self.start_offset.get(),
+                   end.r(), self.end_offset.get())
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-detach
     fn Detach(self) {
         // This method intentionally left blank.
     }
-}
 
+    // http://dom.spec.whatwg.org/#dom-range-ispointinrange
+    fn IsPointInRange(self, node: JSRef<Node>, offset: u32) -> Fallible<bool> {
+        // Step 1.
+        let root = self.root_node().root();
+        if root_of(node) != root.r() {
+            return Ok(false);
+        }
+
+        // Step 2.
+        if node.is_doctype() {
+            return Err(InvalidNodeType);
+        }
+
+        // Step 3.
+        if offset > node.len() {
+            return Err(IndexSize);
+        }
+
+        // Step 4-5.
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        Ok(bp_position(node, offset, start.r(), self.start_offset.get()) != Ordering::Less &&
+           bp_position(node, offset, end.r(), self.end_offset.get()) != Ordering::Greater)
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-comparepoint
+    fn ComparePoint(self, node: JSRef<Node>, offset: u32) -> Fallible<i16> {
+        // Step 1.
+        let root = self.root_node().root();
+        if root_of(node) != root.r() {
+            return Err(WrongDocument);
+        }
+
+        // Step 2.
+        if node.is_doctype() {
+            return Err(InvalidNodeType);
+        }
+
+        // Step 3.
+        if offset > node.len() {
+            return Err(IndexSize);
+        }
+
+        // Step 4-6.
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        if bp_position(node, offset, start.r(), self.start_offset.get()) == Ordering::Less {
+            Ok(-1)
+        } else if bp_position(node, offset, end.r(), self.end_offset.get()) == Ordering::Greater {
+            Ok(1)
+        } else {
+            Ok(0)
+        }
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-intersectsnode
+    fn IntersectsNode(self, node: JSRef<Node>) -> bool {
+        // Step 1.
+        let root = self.root_node().root();
+        if root_of(node) != root.r() {
+            return false;
+        }
+
+        // Step 2-3.
+        let parent = match node.parent_node().root() {
+            Some(parent) => parent,
+            None => return true,
+        };
+
+        // Step 4.
+        let offset = node.index();
+
+        // Step 5-6.
+        let start = self.start_container.get().root();
+        let end = self.end_container.get().root();
+        bp_position(parent.r(), offset, end.r(), self.end_offset.get()) == Ordering::Less &&
+        bp_position(parent.r(), offset + 1, start.r(), self.start_offset.get()) == Ordering::Greater
+    }
+
+    // http://dom.spec.whatwg.org/#dom-range-stringifier
+    fn Stringify(self) -> DOMString {
+        let start_node = self.start_container.get().root();
+        let start_node = start_node.r();
+        let start_offset = self.start_offset.get();
+        let end_node = self.end_container.get().root();
+        let end_node = end_node.r();
+        let end_offset = self.end_offset.get();
+
+        // Step 1.
+        let mut s = String::new();
+
+        // Step 2.
+        if start_node == end_node && start_node.is_text() {
+            let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(start_node).unwrap();
+            return characterdata.SubstringData(start_offset, end_offset - start_offset).unwrap();
+        }
+
+        // Step 3.
+        if start_node.is_text() {
+            let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(start_node).unwrap();
+            let length = start_node.len();
+            s.push_str(characterdata.SubstringData(start_offset, length - start_offset)
+                                    .unwrap().as_slice());
+        }
+
+        // Step 4.
+        let common_ancestor = start_node.inclusive_ancestors()
+                                        .find(|ancestor| ancestor.is_inclusive_ancestor_of(end_node))
+                                        .unwrap();
+        for node in common_ancestor.traverse_preorder() {
+            if node.is_text() && self.contains(node) {
+                let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(node).unwrap();
+                s.push_str(characterdata.Data().as_slice());
+            }
+        }
+
+        // Step 5.
+        if end_node.is_text() {
+            let characterdata: JSRef<CharacterData> = CharacterDataCast::to_ref(end_node).unwrap();
+            s.push_str(characterdata.SubstringData(0, end_offset).unwrap().as_slice());
+        }
+
+        // Step 6.
+        s
+    }
+
+    // http://domparsing.spec.whatwg.org/#dom-range-createcontextualfragment
+    fn CreateContextualFragment(self, fragment: DOMString) -> Fallible<Temporary<DocumentFragment>> {
+        // Step 1.
+        let start = self.start_container.get().root();
+        let node = start.r();
+        let element = match node.type_id() {
+            NodeTypeId::Document | NodeTypeId::DocumentFragment => None,
+            NodeTypeId::Element(_) => Some(node),
+            _ => node.parent_node().root().map(|parent| parent.get_unsound_ref_forever()),
+        };
+
+        // Step 2.
+        let element = match element.and_then(ElementCast::to_ref) {
+            Some(element) if !(element.html_element_in_html_document() &&
+                               element.local_name().as_slice() == "html") => {
+                Temporary::from_rooted(element)
+            }
+            _ => {
+                let document = document_from_node(node).root();
+                document.r().CreateElement("body".to_owned()).unwrap()
+            }
+        }.root();
+
+        // Step 3-5.
+        element.r().parse_fragment(fragment)
+    }
+}
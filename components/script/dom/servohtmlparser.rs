@@ -21,6 +21,7 @@ use std::default::Default;
 use url::Url;
 use js::jsapi::JSTracer;
 use html5ever::tokenizer;
+use html5ever::tokenizer::TokenizerOpts;
 use html5ever::tree_builder;
 use html5ever::tree_builder::{TreeBuilder, TreeBuilderOpts};
 
@@ -29,10 +30,21 @@ use html5ever::tree_builder::{TreeBuilder, TreeBuilderOpts};
 pub struct Sink {
     pub base_url: Option<Url>,
     pub document: JS<Document>,
+    /// Whether scripts inserted by the parser may run; false when parsing
+    /// fragments.
+    pub scripting_enabled: bool,
 }
 
 pub type Tokenizer = tokenizer::Tokenizer<TreeBuilder<JS<Node>, Sink>>;
 
+/// The context element of the fragment parsing algorithm, and the form
+/// element pointer it starts with.
+/// http://html.spec.whatwg.org/multipage/syntax.html#parsing-html-fragments
+pub struct FragmentContext<'a> {
+    pub context_elem: JSRef<'a, Node>,
+    pub form_elem: Option<JSRef<'a, Node>>,
+}
+
 // NB: JSTraceable is *not* auto-derived.
 // You must edit the impl below if you add fields!
 #[must_root]
@@ -54,10 +66,10 @@ impl Parser for ServoHTMLParser{
 impl ServoHTMLParser {
     #[allow(unrooted_must_root)]
     pub fn new(base_url: Option<Url>, document: JSRef<Document>) -> Temporary<ServoHTMLParser> {
-        let window = document.window().root();
         let sink = Sink {
             base_url: base_url,
             document: JS::from_rooted(document),
+            scripting_enabled: true,
         };
 
         let tb = TreeBuilder::new(sink, TreeBuilderOpts {
@@ -67,9 +79,45 @@ impl ServoHTMLParser {
 
         let tok = tokenizer::Tokenizer::new(tb, Default::default());
 
+        ServoHTMLParser::new_with_tokenizer(document, tok)
+    }
+
+    /// Create a parser for the fragment parsing algorithm, whose script
+    /// elements are never executed.
+    #[allow(unrooted_must_root)]
+    pub fn new_for_fragment(base_url: Option<Url>, document: JSRef<Document>,
+                            fragment_context: FragmentContext) -> Temporary<ServoHTMLParser> {
+        let sink = Sink {
+            base_url: base_url,
+            document: JS::from_rooted(document),
+            scripting_enabled: false,
+        };
+
+        let tb = TreeBuilder::new_for_fragment(sink,
+                                               JS::from_rooted(fragment_context.context_elem),
+                                               fragment_context.form_elem.map(JS::from_rooted),
+                                               TreeBuilderOpts {
+                                                   ignore_missing_rules: true,
+                                                   scripting_enabled: false,
+                                                   .. Default::default()
+                                               });
+
+        let tok_opts = TokenizerOpts {
+            initial_state: Some(tb.tokenizer_state_for_context_elem()),
+            .. Default::default()
+        };
+        let tok = tokenizer::Tokenizer::new(tb, tok_opts);
+
+        ServoHTMLParser::new_with_tokenizer(document, tok)
+    }
+
+    #[allow(unrooted_must_root)]
+    fn new_with_tokenizer(document: JSRef<Document>, tokenizer: Tokenizer)
+                          -> Temporary<ServoHTMLParser> {
+        let window = document.window().root();
         let parser = ServoHTMLParser {
             reflector_: Reflector::new(),
-            tokenizer: DOMRefCell::new(tok),
+            tokenizer: DOMRefCell::new(tokenizer),
         };
 
         reflect_dom_object(box parser, GlobalRef::Window(window.r()),
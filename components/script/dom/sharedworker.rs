@@ -0,0 +1,123 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::SharedWorkerBinding;
+use dom::bindings::codegen::Bindings::SharedWorkerBinding::SharedWorkerMethods;
+use dom::bindings::codegen::InheritTypes::EventTargetCast;
+use dom::bindings::error::Fallible;
+use dom::bindings::error::Error::{Security, Syntax};
+use dom::bindings::global::{GlobalRef, GlobalField};
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::refcounted::Trusted;
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
+use dom::eventtarget::{EventTarget, EventTargetTypeId};
+use dom::messageport::{MessagePort, TransferredPort};
+use dom::sharedworkerglobalscope::{SharedWorkerConnection, SharedWorkerGlobalScope};
+use script_task::Runnable;
+
+use net::fetch::request::is_same_origin;
+use util::str::DOMString;
+
+use std::borrow::ToOwned;
+use url::UrlParser;
+
+// http://www.whatwg.org/html/#sharedworker
+#[dom_struct]
+pub struct SharedWorker {
+    eventtarget: EventTarget,
+    global: GlobalField,
+    /// The outside port of the connection to the worker.
+    port: JS<MessagePort>,
+    /// The connection that keeps the worker running while this object is alive.
+    connection: DOMRefCell<Option<SharedWorkerConnection>>,
+}
+
+impl SharedWorker {
+    fn new_inherited(global: GlobalRef, port: Temporary<MessagePort>) -> SharedWorker {
+        SharedWorker {
+            eventtarget: EventTarget::new_inherited(EventTargetTypeId::SharedWorker),
+            global: GlobalField::from_rooted(&global),
+            port: JS::from_rooted(port),
+            connection: DOMRefCell::new(None),
+        }
+    }
+
+    pub fn new(global: GlobalRef, port: Temporary<MessagePort>) -> Temporary<SharedWorker> {
+        reflect_dom_object(box SharedWorker::new_inherited(global, port),
+                           global,
+                           SharedWorkerBinding::Wrap)
+    }
+
+    // http://www.whatwg.org/html/#dom-sharedworker
+    pub fn Constructor(global: GlobalRef, script_url: DOMString,
+                       name: DOMString) -> Fallible<Temporary<SharedWorker>> {
+        // Step 2-4.
+        let global_url = global.get_url();
+        let worker_url = match UrlParser::new().base_url(&global_url)
+                .parse(script_url.as_slice()) {
+            Ok(url) => url,
+            Err(_) => return Err(Syntax),
+        };
+
+        // Step 5.
+        if !is_same_origin(&worker_url, &global_url) {
+            return Err(Security);
+        }
+
+        // Step 6-7.
+        let (outside_port, inside_port) = TransferredPort::new_pair();
+        let port = MessagePort::new(global, outside_port);
+        let worker = SharedWorker::new(global, port).root();
+
+        // Step 8.
+        let worker_ref = Trusted::new(global.get_cx(), worker.r(), global.script_chan());
+        let connection = SharedWorkerGlobalScope::connect(
+            worker_url, name, global.resource_task(), inside_port, worker_ref,
+            global.script_chan());
+        *worker.r().connection.borrow_mut() = Some(connection);
+
+        Ok(Temporary::from_rooted(worker.r()))
+    }
+
+    /// Fires an error event at the worker, whose script failed to load.
+    fn dispatch_simple_error(address: Trusted<SharedWorker>) {
+        let worker = address.to_temporary().root();
+        let global = worker.r().global.root();
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(worker.r());
+
+        let event = Event::new(global.r(),
+                               "error".to_owned(),
+                               EventBubbles::DoesNotBubble,
+                               EventCancelable::NotCancelable).root();
+        event.r().fire(target);
+    }
+}
+
+impl<'a> SharedWorkerMethods for JSRef<'a, SharedWorker> {
+    // http://www.whatwg.org/html/#dom-sharedworker-port
+    fn Port(self) -> Temporary<MessagePort> {
+        Temporary::new(self.port)
+    }
+}
+
+pub struct SharedWorkerErrorHandler {
+    addr: Trusted<SharedWorker>,
+}
+
+impl SharedWorkerErrorHandler {
+    pub fn new(addr: Trusted<SharedWorker>) -> SharedWorkerErrorHandler {
+        SharedWorkerErrorHandler {
+            addr: addr
+        }
+    }
+}
+
+impl Runnable for SharedWorkerErrorHandler {
+    fn handler(self: Box<SharedWorkerErrorHandler>) {
+        let this = *self;
+        SharedWorker::dispatch_simple_error(this.addr);
+    }
+}
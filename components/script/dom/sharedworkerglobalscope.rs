@@ -0,0 +1,356 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use dom::bindings::codegen::Bindings::SharedWorkerGlobalScopeBinding;
+use dom::bindings::codegen::Bindings::SharedWorkerGlobalScopeBinding::SharedWorkerGlobalScopeMethods;
+use dom::bindings::codegen::InheritTypes::SharedWorkerGlobalScopeDerived;
+use dom::bindings::codegen::InheritTypes::{EventTargetCast, WorkerGlobalScopeCast};
+use dom::bindings::conversions::ToJSValConvertible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, Temporary, RootCollection};
+use dom::bindings::refcounted::{LiveDOMReferences, Trusted};
+use dom::bindings::utils::Reflectable;
+use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
+use dom::messageevent::MessageEvent;
+use dom::messageport::{MessagePort, TransferredPort};
+use dom::sharedworker::{SharedWorker, SharedWorkerErrorHandler};
+use dom::worker::Worker;
+use dom::workerglobalscope::{WorkerGlobalScope, WorkerGlobalScopeHelpers, WorkerRuntime};
+use dom::workerglobalscope::WorkerGlobalScopeTypeId;
+use script_task::{ScriptTask, ScriptChan, ScriptMsg, NonWorkerScriptChan, TimerSource};
+use script_task::StackRootTLS;
+
+use net::resource_task::{ResourceTask, load_whole_resource};
+use util::str::DOMString;
+use util::task::spawn_named;
+use util::task_state;
+use util::task_state::{SCRIPT, IN_WORKER};
+
+use js::rust::Cx;
+
+use std::borrow::ToOwned;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver, SendError};
+use url::Url;
+
+/// The `SharedWorker` objects to fire an error event at if the script of a worker
+/// fails to load, with the event loops they live in.
+type LoadErrorTargets = Vec<(Trusted<SharedWorker>, Box<ScriptChan+Send>)>;
+
+/// A running shared worker.
+struct SharedWorkerEntry {
+    /// The event loop of the worker.
+    sender: Sender<ScriptMsg>,
+    /// The closing flag of the worker, which also identifies it.
+    closing: Arc<AtomicBool>,
+    /// The runtime of the worker, interrupted when it is shut down.
+    runtime: WorkerRuntime,
+    /// The number of `SharedWorker` objects connected to the worker.
+    connections: usize,
+    /// The objects to report a load error to, or `None` once the script was loaded.
+    load_error_targets: Option<LoadErrorTargets>,
+}
+
+impl SharedWorkerEntry {
+    fn is(&self, closing: &Arc<AtomicBool>) -> bool {
+        &*self.closing as *const AtomicBool == &**closing as *const AtomicBool
+    }
+
+    /// Returns the entry of the worker with the given closing flag, unless the worker was
+    /// unregistered already.
+    fn get<'a>(workers: &'a mut HashMap<(Url, DOMString), SharedWorkerEntry>,
+               key: &(Url, DOMString), closing: &Arc<AtomicBool>)
+               -> Option<&'a mut SharedWorkerEntry> {
+        match workers.get_mut(key) {
+            Some(entry) => if entry.is(closing) { Some(entry) } else { None },
+            None => None,
+        }
+    }
+
+    /// Stops the script the worker is running and its event loop.
+    fn shut_down(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+        self.runtime.interrupt();
+        let _ = self.sender.send(ScriptMsg::TerminateWorker);
+    }
+}
+
+// The shared workers of this process, keyed by script URL and name. Documents of
+// different pipelines connect to the same worker through it.
+lazy_static! {
+    static ref SHARED_WORKERS: Mutex<HashMap<(Url, DOMString), SharedWorkerEntry>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The connection of a `SharedWorker` object to its worker, which keeps the worker
+/// running. The worker is shut down when its last connection is dropped.
+pub struct SharedWorkerConnection {
+    key: (Url, DOMString),
+    closing: Arc<AtomicBool>,
+}
+
+no_jsmanaged_fields!(SharedWorkerConnection);
+
+impl Drop for SharedWorkerConnection {
+    fn drop(&mut self) {
+        let mut workers = SHARED_WORKERS.lock().unwrap();
+        let last = match SharedWorkerEntry::get(&mut *workers, &self.key, &self.closing) {
+            Some(entry) => {
+                entry.connections -= 1;
+                entry.connections == 0
+            }
+            // The worker has closed itself.
+            None => return,
+        };
+        if last {
+            workers.remove(&self.key).unwrap().shut_down();
+        }
+    }
+}
+
+#[dom_struct]
+pub struct SharedWorkerGlobalScope {
+    workerglobalscope: WorkerGlobalScope,
+    name: DOMString,
+    receiver: Receiver<ScriptMsg>,
+    own_sender: Sender<ScriptMsg>,
+}
+
+impl SharedWorkerGlobalScope {
+    fn new_inherited(worker_url: Url,
+                     name: DOMString,
+                     cx: Rc<Cx>,
+                     resource_task: ResourceTask,
+                     own_sender: Sender<ScriptMsg>,
+                     receiver: Receiver<ScriptMsg>,
+                     closing: Arc<AtomicBool>)
+                     -> SharedWorkerGlobalScope {
+        SharedWorkerGlobalScope {
+            workerglobalscope: WorkerGlobalScope::new_inherited(
+                WorkerGlobalScopeTypeId::SharedGlobalScope, worker_url, cx, resource_task,
+                closing),
+            name: name,
+            receiver: receiver,
+            own_sender: own_sender,
+        }
+    }
+
+    pub fn new(worker_url: Url,
+               name: DOMString,
+               cx: Rc<Cx>,
+               resource_task: ResourceTask,
+               own_sender: Sender<ScriptMsg>,
+               receiver: Receiver<ScriptMsg>,
+               closing: Arc<AtomicBool>)
+               -> Temporary<SharedWorkerGlobalScope> {
+        let scope = box SharedWorkerGlobalScope::new_inherited(
+            worker_url, name, cx.clone(), resource_task, own_sender, receiver, closing);
+        SharedWorkerGlobalScopeBinding::Wrap(cx.ptr, scope)
+    }
+}
+
+impl SharedWorkerGlobalScope {
+    /// Connects `port` to the shared worker with the given script URL and name, starting
+    /// the worker if it is not running yet. `worker` is the object the connection is
+    /// made for, which keeps the worker running as long as it holds the returned
+    /// connection.
+    /// http://www.whatwg.org/html/#dom-sharedworker step 8
+    pub fn connect(worker_url: Url, name: DOMString, resource_task: ResourceTask,
+                   port: TransferredPort, worker: Trusted<SharedWorker>,
+                   script_chan: Box<ScriptChan+Send>) -> SharedWorkerConnection {
+        let mut workers = SHARED_WORKERS.lock().unwrap();
+        let key = (worker_url.clone(), name.clone());
+        let port = match workers.get_mut(&key) {
+            // A worker that closed itself is replaced by a new one below.
+            Some(entry) => if entry.closing.load(Ordering::SeqCst) {
+                port
+            } else {
+                match entry.sender.send(ScriptMsg::ConnectSharedWorker(port)) {
+                    Ok(()) => {
+                        entry.connections += 1;
+                        if let Some(ref mut targets) = entry.load_error_targets {
+                            targets.push((worker, script_chan));
+                        }
+                        return SharedWorkerConnection {
+                            key: key.clone(),
+                            closing: entry.closing.clone(),
+                        };
+                    }
+                    // The worker has exited but was not unregistered yet.
+                    Err(SendError(ScriptMsg::ConnectSharedWorker(port))) => port,
+                    Err(_) => unreachable!(),
+                }
+            },
+            None => port,
+        };
+
+        let (sender, receiver) = channel();
+        sender.send(ScriptMsg::ConnectSharedWorker(port)).unwrap();
+        let closing = Arc::new(AtomicBool::new(false));
+        let runtime = WorkerRuntime::new();
+        workers.insert(key.clone(), SharedWorkerEntry {
+            sender: sender.clone(),
+            closing: closing.clone(),
+            runtime: runtime.clone(),
+            connections: 1,
+            load_error_targets: Some(vec!((worker, script_chan))),
+        });
+        SharedWorkerGlobalScope::run_worker_scope(worker_url, name, resource_task,
+                                                  sender, receiver, closing.clone(), runtime);
+        SharedWorkerConnection {
+            key: key,
+            closing: closing,
+        }
+    }
+
+    fn run_worker_scope(worker_url: Url,
+                        name: DOMString,
+                        resource_task: ResourceTask,
+                        own_sender: Sender<ScriptMsg>,
+                        receiver: Receiver<ScriptMsg>,
+                        closing: Arc<AtomicBool>,
+                        runtime: WorkerRuntime) {
+        spawn_named(format!("SharedWorker for {}", worker_url.serialize()), move || {
+            task_state::initialize(SCRIPT | IN_WORKER);
+
+            let roots = RootCollection::new();
+            let _stack_roots_tls = StackRootTLS::new(&roots);
+
+            let key = (worker_url.clone(), name.clone());
+            let loaded = load_whole_resource(&resource_task, worker_url.clone());
+            let targets = {
+                let mut workers = SHARED_WORKERS.lock().unwrap();
+                let targets = match SharedWorkerEntry::get(&mut *workers, &key, &closing) {
+                    Some(entry) => entry.load_error_targets.take(),
+                    None => None,
+                };
+                targets
+            };
+            if loaded.is_err() {
+                // Documents that connect from now on start a new worker.
+                let mut workers = SHARED_WORKERS.lock().unwrap();
+                if SharedWorkerEntry::get(&mut *workers, &key, &closing).is_some() {
+                    workers.remove(&key);
+                }
+            }
+
+            // http://www.whatwg.org/html/#run-a-worker step 4
+            let (url, source) = match loaded {
+                Err(_) => {
+                    error!("error loading the script of shared worker {}",
+                           worker_url.serialize());
+                    for (worker, script_chan) in targets.unwrap_or(vec!()).into_iter() {
+                        let handler = box SharedWorkerErrorHandler::new(worker);
+                        let _ = script_chan.send(ScriptMsg::RunnableMsg(handler));
+                    }
+                    return;
+                }
+                Ok((metadata, bytes)) => {
+                    let source = String::from_utf8_lossy(bytes.as_slice()).into_owned();
+                    (metadata.final_url, source)
+                }
+            };
+            drop(targets);
+
+            let (js_runtime, js_context) = ScriptTask::new_rt_and_cx();
+            let global = SharedWorkerGlobalScope::new(
+                worker_url, name, js_context.clone(), resource_task,
+                own_sender, receiver, closing.clone()).root();
+            let _attached = runtime.attach(js_runtime.ptr, js_context.ptr, closing.clone());
+
+            // The worker may have been shut down before its runtime could be interrupted.
+            // Uncaught exceptions are reported by the error reporter of the context.
+            let scope: JSRef<WorkerGlobalScope> = WorkerGlobalScopeCast::from_ref(global.r());
+            if !scope.is_closing() {
+                let _ = js_context.evaluate_script(
+                    global.r().reflector().get_jsobject(), source, url.serialize(), 1);
+            }
+
+            while !scope.is_closing() {
+                match global.r().receiver.recv() {
+                    Ok(msg) => {
+                        // Tasks that were queued before the worker was closed are discarded.
+                        if scope.is_closing() {
+                            break;
+                        }
+                        global.r().handle_event(msg);
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            // Documents that connect from now on start a new worker, unless that already
+            // happened because the worker was shut down.
+            let mut workers = SHARED_WORKERS.lock().unwrap();
+            if SharedWorkerEntry::get(&mut *workers, &key, &closing).is_some() {
+                workers.remove(&key);
+            }
+        });
+    }
+}
+
+pub trait SharedWorkerGlobalScopeHelpers {
+    fn script_chan(self) -> Box<ScriptChan+Send>;
+}
+
+impl<'a> SharedWorkerGlobalScopeHelpers for JSRef<'a, SharedWorkerGlobalScope> {
+    fn script_chan(self) -> Box<ScriptChan+Send> {
+        box NonWorkerScriptChan(self.own_sender.clone())
+    }
+}
+
+trait PrivateSharedWorkerGlobalScopeHelpers {
+    fn handle_event(self, msg: ScriptMsg);
+}
+
+impl<'a> PrivateSharedWorkerGlobalScopeHelpers for JSRef<'a, SharedWorkerGlobalScope> {
+    fn handle_event(self, msg: ScriptMsg) {
+        let scope: JSRef<WorkerGlobalScope> = WorkerGlobalScopeCast::from_ref(self);
+        match msg {
+            ScriptMsg::ConnectSharedWorker(port) => {
+                // http://www.whatwg.org/html/#dom-sharedworker step 8.7
+                let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+                let port = MessagePort::new(GlobalRef::Worker(scope), port);
+                let data = "".to_owned().to_jsval(scope.get_cx());
+                MessageEvent::dispatch_with_ports(target, GlobalRef::Worker(scope),
+                                                  "connect".to_owned(), data, vec!(port));
+            },
+            ScriptMsg::RunnableMsg(runnable) => {
+                runnable.handler()
+            },
+            ScriptMsg::RefcountCleanup(addr) => {
+                LiveDOMReferences::cleanup(scope.get_cx(), addr);
+            }
+            ScriptMsg::WorkerDispatchErrorEvent(addr, msg, file_name, line_num, col_num) => {
+                Worker::handle_error_message(addr, msg, file_name, line_num, col_num);
+            },
+            ScriptMsg::FireTimer(TimerSource::FromWorker, timer_id) => {
+                scope.handle_fire_timer(timer_id);
+            }
+            _ => panic!("Unexpected message"),
+        }
+    }
+}
+
+impl<'a> SharedWorkerGlobalScopeMethods for JSRef<'a, SharedWorkerGlobalScope> {
+    // http://www.whatwg.org/html/#dom-sharedworkerglobalscope-name
+    fn Name(self) -> DOMString {
+        self.name.clone()
+    }
+
+    event_handler!(connect, GetOnconnect, SetOnconnect);
+}
+
+impl SharedWorkerGlobalScopeDerived for EventTarget {
+    fn is_sharedworkerglobalscope(&self) -> bool {
+        match *self.type_id() {
+            EventTargetTypeId::WorkerGlobalScope(WorkerGlobalScopeTypeId::SharedGlobalScope) => true,
+            _ => false
+        }
+    }
+}
@@ -7,7 +7,10 @@ use dom::bindings::codegen::Bindings::StorageBinding::StorageMethods;
 use dom::bindings::global::{GlobalRef, GlobalField};
 use dom::bindings::js::{JSRef, Temporary};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
-use dom::bindings::error::Fallible;
+use dom::bindings::error::{Error, ErrorResult};
+use msg::constellation_msg::ConstellationChan;
+use msg::constellation_msg::Msg as ConstellationMsg;
+use msg::constellation_msg::StorageType;
 use util::str::DOMString;
 use net::storage_task::StorageTask;
 use net::storage_task::StorageTaskMsg;
@@ -18,22 +21,20 @@ use url::Url;
 pub struct Storage {
     reflector_: Reflector,
     global: GlobalField,
+    storage_type: StorageType,
 }
 
 impl Storage {
-    fn new_inherited(global: &GlobalRef) -> Storage {
+    fn new_inherited(global: &GlobalRef, storage_type: StorageType) -> Storage {
         Storage {
             reflector_: Reflector::new(),
             global: GlobalField::from_rooted(global),
+            storage_type: storage_type,
         }
     }
 
-    pub fn new(global: &GlobalRef) -> Temporary<Storage> {
-        reflect_dom_object(box Storage::new_inherited(global), *global, StorageBinding::Wrap)
-    }
-
-    pub fn Constructor(global: &GlobalRef) -> Fallible<Temporary<Storage>> {
-        Ok(Storage::new(global))
+    pub fn new(global: &GlobalRef, storage_type: StorageType) -> Temporary<Storage> {
+        reflect_dom_object(box Storage::new_inherited(global, storage_type), *global, StorageBinding::Wrap)
     }
 
     fn get_url(&self) -> Url {
@@ -48,27 +49,39 @@ impl Storage {
         global_ref.as_window().storage_task()
     }
 
+    /// https://html.spec.whatwg.org/multipage/webstorage.html#send-a-storage-notification
+    /// The other pipelines fire the `storage` event; this document does not.
+    fn broadcast_change_notification(&self, key: Option<DOMString>, old_value: Option<DOMString>,
+                                     new_value: Option<DOMString>) {
+        let global_root = self.global.root();
+        let global_ref = global_root.r();
+        let page = global_ref.as_window().page();
+        let ConstellationChan(ref chan) = page.constellation_chan;
+        chan.send(ConstellationMsg::BroadcastStorageEvent(page.id, self.storage_type,
+                                                          self.get_url(), key, old_value,
+                                                          new_value)).unwrap();
+    }
 }
 
 impl<'a> StorageMethods for JSRef<'a, Storage> {
     fn Length(self) -> u32 {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::Length(sender, self.get_url())).unwrap();
+        self.get_storage_task().send(StorageTaskMsg::Length(sender, self.get_url(), self.storage_type)).unwrap();
         receiver.recv().unwrap()
     }
 
     fn Key(self, index: u32) -> Option<DOMString> {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::Key(sender, self.get_url(), index)).unwrap();
+        self.get_storage_task().send(StorageTaskMsg::Key(sender, self.get_url(), self.storage_type, index)).unwrap();
         receiver.recv().unwrap()
     }
 
     fn GetItem(self, name: DOMString) -> Option<DOMString> {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::GetItem(sender, self.get_url(), name)).unwrap();
+        self.get_storage_task().send(StorageTaskMsg::GetItem(sender, self.get_url(), self.storage_type, name)).unwrap();
         receiver.recv().unwrap()
     }
 
@@ -78,29 +91,36 @@ impl<'a> StorageMethods for JSRef<'a, Storage> {
         item
     }
 
-    fn SetItem(self, name: DOMString, value: DOMString) {
+    fn SetItem(self, name: DOMString, value: DOMString) -> ErrorResult {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::SetItem(sender, self.get_url(), name, value)).unwrap();
-        if receiver.recv().unwrap() {
-            //TODO send notification
+        self.get_storage_task().send(StorageTaskMsg::SetItem(sender, self.get_url(), self.storage_type,
+                                                             name.clone(), value.clone())).unwrap();
+        match receiver.recv().unwrap() {
+            Err(()) => Err(Error::QuotaExceeded),
+            Ok((true, old_value)) => {
+                self.broadcast_change_notification(Some(name), old_value, Some(value));
+                Ok(())
+            }
+            Ok((false, _)) => Ok(()),
         }
     }
 
-    fn NamedSetter(self, name: DOMString, value: DOMString) {
-        self.SetItem(name, value);
+    fn NamedSetter(self, name: DOMString, value: DOMString) -> ErrorResult {
+        self.SetItem(name, value)
     }
 
-    fn NamedCreator(self, name: DOMString, value: DOMString) {
-        self.SetItem(name, value);
+    fn NamedCreator(self, name: DOMString, value: DOMString) -> ErrorResult {
+        self.SetItem(name, value)
     }
 
     fn RemoveItem(self, name: DOMString) {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::RemoveItem(sender, self.get_url(), name)).unwrap();
-        if receiver.recv().unwrap() {
-            //TODO send notification
+        self.get_storage_task().send(StorageTaskMsg::RemoveItem(sender, self.get_url(), self.storage_type,
+                                                                name.clone())).unwrap();
+        if let Some(old_value) = receiver.recv().unwrap() {
+            self.broadcast_change_notification(Some(name), Some(old_value), None);
         }
     }
 
@@ -111,10 +131,9 @@ impl<'a> StorageMethods for JSRef<'a, Storage> {
     fn Clear(self) {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::Clear(sender, self.get_url())).unwrap();
+        self.get_storage_task().send(StorageTaskMsg::Clear(sender, self.get_url(), self.storage_type)).unwrap();
         if receiver.recv().unwrap() {
-            //TODO send notification
+            self.broadcast_change_notification(None, None, None);
         }
     }
 }
-
@@ -0,0 +1,94 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use dom::bindings::codegen::Bindings::StorageEventBinding;
+use dom::bindings::codegen::Bindings::StorageEventBinding::StorageEventMethods;
+use dom::bindings::codegen::InheritTypes::{EventCast, StorageEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, MutNullableJS, RootedReference, Temporary};
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventTypeId};
+use dom::storage::Storage;
+use util::str::DOMString;
+
+use std::default::Default;
+
+#[dom_struct]
+pub struct StorageEvent {
+    event: Event,
+    key: Option<DOMString>,
+    old_value: Option<DOMString>,
+    new_value: Option<DOMString>,
+    url: DOMString,
+    storage_area: MutNullableJS<Storage>,
+}
+
+impl StorageEventDerived for Event {
+    fn is_storageevent(&self) -> bool {
+        *self.type_id() == EventTypeId::StorageEvent
+    }
+}
+
+impl StorageEvent {
+    fn new_inherited(key: Option<DOMString>, old_value: Option<DOMString>,
+                     new_value: Option<DOMString>, url: DOMString) -> StorageEvent {
+        StorageEvent {
+            event: Event::new_inherited(EventTypeId::StorageEvent),
+            key: key,
+            old_value: old_value,
+            new_value: new_value,
+            url: url,
+            storage_area: Default::default(),
+        }
+    }
+
+    pub fn new(global: GlobalRef, type_: DOMString,
+               can_bubble: bool, cancelable: bool,
+               key: Option<DOMString>, old_value: Option<DOMString>,
+               new_value: Option<DOMString>, url: DOMString,
+               storage_area: Option<JSRef<Storage>>) -> Temporary<StorageEvent> {
+        let ev = reflect_dom_object(box StorageEvent::new_inherited(key, old_value, new_value, url),
+                                    global,
+                                    StorageEventBinding::Wrap).root();
+        ev.r().storage_area.assign(storage_area);
+        let event: JSRef<Event> = EventCast::from_ref(ev.r());
+        event.InitEvent(type_, can_bubble, cancelable);
+        Temporary::from_rooted(ev.r())
+    }
+
+    pub fn Constructor(global: GlobalRef,
+                       type_: DOMString,
+                       init: &StorageEventBinding::StorageEventInit)
+                       -> Fallible<Temporary<StorageEvent>> {
+        let ev = StorageEvent::new(global, type_, init.parent.bubbles, init.parent.cancelable,
+                                   init.key.clone(), init.oldValue.clone(),
+                                   init.newValue.clone(), init.url.clone(),
+                                   init.storageArea.r());
+        Ok(ev)
+    }
+}
+
+impl<'a> StorageEventMethods for JSRef<'a, StorageEvent> {
+    fn GetKey(self) -> Option<DOMString> {
+        self.key.clone()
+    }
+
+    fn GetOldValue(self) -> Option<DOMString> {
+        self.old_value.clone()
+    }
+
+    fn GetNewValue(self) -> Option<DOMString> {
+        self.new_value.clone()
+    }
+
+    fn Url(self) -> DOMString {
+        self.url.clone()
+    }
+
+    fn GetStorageArea(self) -> Option<Temporary<Storage>> {
+        self.storage_area.get()
+    }
+}
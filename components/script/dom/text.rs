@@ -2,18 +2,25 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use dom::bindings::codegen::Bindings::CharacterDataBinding::CharacterDataMethods;
+use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use dom::bindings::codegen::Bindings::TextBinding;
+use dom::bindings::codegen::Bindings::TextBinding::TextMethods;
 use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
-use dom::bindings::codegen::InheritTypes::TextDerived;
+use dom::bindings::codegen::InheritTypes::{CharacterDataCast, NodeCast, TextDerived};
 use dom::bindings::error::Fallible;
+use dom::bindings::error::Error::IndexSize;
 use dom::bindings::global::GlobalRef;
-use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::js::{JSRef, OptionalRootable, RootedReference, Temporary};
 use dom::characterdata::CharacterData;
 use dom::document::Document;
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
-use dom::node::{Node, NodeTypeId};
+use dom::node::{Node, NodeHelpers, NodeTypeId};
+use dom::range::{RangeHelpers, any_live_ranges, ranges_in};
 use util::str::DOMString;
 
+use std::borrow::ToOwned;
+
 /// An HTML text node.
 #[dom_struct]
 pub struct Text {
@@ -49,3 +56,56 @@ impl Text {
     }
 }
 
+
+impl<'a> TextMethods for JSRef<'a, Text> {
+    // http://dom.spec.whatwg.org/#dom-text-splittext
+    // http://dom.spec.whatwg.org/#concept-text-split
+    fn SplitText(self, offset: u32) -> Fallible<Temporary<Text>> {
+        let characterdata: JSRef<CharacterData> = CharacterDataCast::from_ref(self);
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+
+        // Step 1.
+        let length = characterdata.Length();
+
+        // Step 2.
+        if offset > length {
+            return Err(IndexSize);
+        }
+
+        // Step 3.
+        let count = length - offset;
+
+        // Step 4.
+        let new_data = try!(characterdata.SubstringData(offset, count));
+
+        // Step 5.
+        let document = node.owner_doc().root();
+        let new_text = Text::new(new_data, document.r()).root();
+        let new_node: JSRef<Node> = NodeCast::from_ref(new_text.r());
+
+        // Step 6.
+        match node.parent_node().root() {
+            Some(parent) => {
+                // Step 7.1.
+                let next_sibling = node.next_sibling().root();
+                try!(parent.r().InsertBefore(new_node, next_sibling.r()));
+
+                // Step 7.2-7.5.
+                if any_live_ranges() {
+                    let index = node.index();
+                    for range in ranges_in(vec!(node, parent.r()).into_iter()).into_iter() {
+                        range.root().r().update_for_split_text(node, new_node, offset, parent.r(),
+                                                               index);
+                    }
+                }
+            }
+            None => (),
+        }
+
+        // Step 8.
+        try!(characterdata.ReplaceData(offset, count, "".to_owned()));
+
+        // Step 9.
+        Ok(Temporary::from_rooted(new_text.r()))
+    }
+}
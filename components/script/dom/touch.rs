@@ -0,0 +1,96 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::TouchBinding;
+use dom::bindings::codegen::Bindings::TouchBinding::TouchMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::eventtarget::EventTarget;
+use dom::window::Window;
+
+#[dom_struct]
+pub struct Touch {
+    reflector_: Reflector,
+    identifier: i32,
+    target: JS<EventTarget>,
+    screen_x: i32,
+    screen_y: i32,
+    client_x: i32,
+    client_y: i32,
+    page_x: i32,
+    page_y: i32,
+}
+
+impl Touch {
+    fn new_inherited(identifier: i32, target: JSRef<EventTarget>,
+                     screen_x: i32, screen_y: i32,
+                     client_x: i32, client_y: i32,
+                     page_x: i32, page_y: i32) -> Touch {
+        Touch {
+            reflector_: Reflector::new(),
+            identifier: identifier,
+            target: JS::from_rooted(target),
+            screen_x: screen_x,
+            screen_y: screen_y,
+            client_x: client_x,
+            client_y: client_y,
+            page_x: page_x,
+            page_y: page_y,
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, identifier: i32, target: JSRef<EventTarget>,
+               screen_x: i32, screen_y: i32,
+               client_x: i32, client_y: i32,
+               page_x: i32, page_y: i32) -> Temporary<Touch> {
+        reflect_dom_object(box Touch::new_inherited(identifier, target,
+                                                    screen_x, screen_y,
+                                                    client_x, client_y,
+                                                    page_x, page_y),
+                           GlobalRef::Window(window), TouchBinding::Wrap)
+    }
+}
+
+impl<'a> TouchMethods for JSRef<'a, Touch> {
+    // http://www.w3.org/TR/touch-events/#widl-Touch-identifier
+    fn Identifier(self) -> i32 {
+        self.identifier
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Touch-target
+    fn Target(self) -> Temporary<EventTarget> {
+        Temporary::new(self.target)
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Touch-screenX
+    fn ScreenX(self) -> i32 {
+        self.screen_x
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Touch-screenY
+    fn ScreenY(self) -> i32 {
+        self.screen_y
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Touch-clientX
+    fn ClientX(self) -> i32 {
+        self.client_x
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Touch-clientY
+    fn ClientY(self) -> i32 {
+        self.client_y
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Touch-pageX
+    fn PageX(self) -> i32 {
+        self.page_x
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-Touch-pageY
+    fn PageY(self) -> i32 {
+        self.page_y
+    }
+}
@@ -0,0 +1,150 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::TouchEventBinding;
+use dom::bindings::codegen::Bindings::TouchEventBinding::TouchEventMethods;
+use dom::bindings::codegen::Bindings::UIEventBinding::UIEventMethods;
+use dom::bindings::codegen::InheritTypes::{EventCast, UIEventCast, TouchEventDerived};
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, JSRef, MutHeap, Temporary};
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventTypeId};
+use dom::touchlist::TouchList;
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use util::str::DOMString;
+use std::cell::Cell;
+
+#[dom_struct]
+pub struct TouchEvent {
+    uievent: UIEvent,
+    touches: MutHeap<JS<TouchList>>,
+    target_touches: MutHeap<JS<TouchList>>,
+    changed_touches: MutHeap<JS<TouchList>>,
+    alt_key: Cell<bool>,
+    meta_key: Cell<bool>,
+    ctrl_key: Cell<bool>,
+    shift_key: Cell<bool>,
+}
+
+impl TouchEventDerived for Event {
+    fn is_touchevent(&self) -> bool {
+        *self.type_id() == EventTypeId::TouchEvent
+    }
+}
+
+impl TouchEvent {
+    fn new_inherited(touches: JSRef<TouchList>,
+                     target_touches: JSRef<TouchList>,
+                     changed_touches: JSRef<TouchList>) -> TouchEvent {
+        TouchEvent {
+            uievent: UIEvent::new_inherited(EventTypeId::TouchEvent),
+            touches: MutHeap::new(JS::from_rooted(touches)),
+            target_touches: MutHeap::new(JS::from_rooted(target_touches)),
+            changed_touches: MutHeap::new(JS::from_rooted(changed_touches)),
+            alt_key: Cell::new(false),
+            meta_key: Cell::new(false),
+            ctrl_key: Cell::new(false),
+            shift_key: Cell::new(false),
+        }
+    }
+
+    pub fn new_uninitialized(window: JSRef<Window>) -> Temporary<TouchEvent> {
+        let touches = TouchList::new(window, &[]).root();
+        let target_touches = TouchList::new(window, &[]).root();
+        let changed_touches = TouchList::new(window, &[]).root();
+        reflect_dom_object(box TouchEvent::new_inherited(touches.r(), target_touches.r(),
+                                                         changed_touches.r()),
+                           GlobalRef::Window(window),
+                           TouchEventBinding::Wrap)
+    }
+
+    pub fn new(window: JSRef<Window>,
+               type_: DOMString,
+               can_bubble: bool,
+               cancelable: bool,
+               view: Option<JSRef<Window>>,
+               detail: i32,
+               ctrl_key: bool,
+               alt_key: bool,
+               shift_key: bool,
+               meta_key: bool,
+               touches: JSRef<TouchList>,
+               target_touches: JSRef<TouchList>,
+               changed_touches: JSRef<TouchList>) -> Temporary<TouchEvent> {
+        let ev = TouchEvent::new_uninitialized(window).root();
+        ev.r().InitTouchEvent(type_, can_bubble, cancelable, view, detail,
+                              ctrl_key, alt_key, shift_key, meta_key,
+                              touches, target_touches, changed_touches);
+        Temporary::from_rooted(ev.r())
+    }
+}
+
+impl<'a> TouchEventMethods for JSRef<'a, TouchEvent> {
+    // http://www.w3.org/TR/touch-events/#widl-TouchEvent-touches
+    fn Touches(self) -> Temporary<TouchList> {
+        Temporary::new(self.touches.get())
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-TouchEvent-targetTouches
+    fn TargetTouches(self) -> Temporary<TouchList> {
+        Temporary::new(self.target_touches.get())
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-TouchEvent-changedTouches
+    fn ChangedTouches(self) -> Temporary<TouchList> {
+        Temporary::new(self.changed_touches.get())
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-TouchEvent-altKey
+    fn AltKey(self) -> bool {
+        self.alt_key.get()
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-TouchEvent-metaKey
+    fn MetaKey(self) -> bool {
+        self.meta_key.get()
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-TouchEvent-ctrlKey
+    fn CtrlKey(self) -> bool {
+        self.ctrl_key.get()
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-TouchEvent-shiftKey
+    fn ShiftKey(self) -> bool {
+        self.shift_key.get()
+    }
+
+    // Non-standard, but used by content to create synthetic touch events; see
+    // https://developer.mozilla.org/en-US/docs/Web/API/TouchEvent/initTouchEvent
+    fn InitTouchEvent(self,
+                      type_arg: DOMString,
+                      can_bubble_arg: bool,
+                      cancelable_arg: bool,
+                      view_arg: Option<JSRef<Window>>,
+                      detail_arg: i32,
+                      ctrl_key_arg: bool,
+                      alt_key_arg: bool,
+                      shift_key_arg: bool,
+                      meta_key_arg: bool,
+                      touches_arg: JSRef<TouchList>,
+                      target_touches_arg: JSRef<TouchList>,
+                      changed_touches_arg: JSRef<TouchList>) {
+        let event: JSRef<Event> = EventCast::from_ref(self);
+        if event.dispatching() {
+            return;
+        }
+
+        let uievent: JSRef<UIEvent> = UIEventCast::from_ref(self);
+        uievent.InitUIEvent(type_arg, can_bubble_arg, cancelable_arg, view_arg, detail_arg);
+        self.ctrl_key.set(ctrl_key_arg);
+        self.alt_key.set(alt_key_arg);
+        self.shift_key.set(shift_key_arg);
+        self.meta_key.set(meta_key_arg);
+        self.touches.set(JS::from_rooted(touches_arg));
+        self.target_touches.set(JS::from_rooted(target_touches_arg));
+        self.changed_touches.set(JS::from_rooted(changed_touches_arg));
+    }
+}
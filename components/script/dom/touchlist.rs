@@ -0,0 +1,49 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::TouchListBinding;
+use dom::bindings::codegen::Bindings::TouchListBinding::TouchListMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::touch::Touch;
+use dom::window::Window;
+
+#[dom_struct]
+pub struct TouchList {
+    reflector_: Reflector,
+    touches: Vec<JS<Touch>>,
+}
+
+impl TouchList {
+    fn new_inherited(touches: &[JSRef<Touch>]) -> TouchList {
+        TouchList {
+            reflector_: Reflector::new(),
+            touches: touches.iter().map(|touch| JS::from_rooted(*touch)).collect(),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, touches: &[JSRef<Touch>]) -> Temporary<TouchList> {
+        reflect_dom_object(box TouchList::new_inherited(touches),
+                           GlobalRef::Window(window), TouchListBinding::Wrap)
+    }
+}
+
+impl<'a> TouchListMethods for JSRef<'a, TouchList> {
+    // http://www.w3.org/TR/touch-events/#widl-TouchList-length
+    fn Length(self) -> u32 {
+        self.touches.len() as u32
+    }
+
+    // http://www.w3.org/TR/touch-events/#widl-TouchList-item-getter-Touch-unsigned-long-index
+    fn Item(self, index: u32) -> Option<Temporary<Touch>> {
+        self.touches.get(index as usize).map(|touch| Temporary::new(touch.clone()))
+    }
+
+    fn IndexedGetter(self, index: u32, found: &mut bool) -> Option<Temporary<Touch>> {
+        let touch = self.Item(index);
+        *found = touch.is_some();
+        touch
+    }
+}
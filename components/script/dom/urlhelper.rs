@@ -30,6 +30,15 @@ impl UrlHelper {
         }
     }
 
+    /// https://html.spec.whatwg.org/multipage/browsers.html#ascii-serialisation-of-an-origin
+    pub fn Origin(url: &Url) -> DOMString {
+        let host = url.serialize_host().unwrap_or("".to_owned());
+        match url.port() {
+            Some(port) => format!("{}://{}:{}", url.scheme, host, port),
+            None => format!("{}://{}", url.scheme, host)
+        }
+    }
+
     /// https://html.spec.whatwg.org/multipage/browsers.html#same-origin
     pub fn SameOrigin(urlA: &Url, urlB: &Url) -> bool {
         if urlA.host() != urlB.host() {
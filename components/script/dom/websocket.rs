@@ -2,42 +2,413 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
 use dom::bindings::codegen::Bindings::WebSocketBinding;
-use dom::bindings::codegen::Bindings::WebSocketBinding::WebSocketMethods;
-use dom::bindings::error::Fallible;
-use dom::bindings::global::GlobalRef;
-use dom::bindings::js::{Temporary, JSRef};
+use dom::bindings::codegen::Bindings::WebSocketBinding::{BinaryType, WebSocketMethods};
+use dom::bindings::codegen::InheritTypes::{EventCast, EventTargetCast};
+use dom::bindings::conversions::{FromJSValConvertible, ToJSValConvertible, StringificationBehavior};
+use dom::bindings::conversions::unwrap_jsmanaged;
+use dom::bindings::error::{ErrorResult, Fallible};
+use dom::bindings::error::Error::{InvalidAccess, InvalidState, JSFailed, Security, Syntax};
+use dom::bindings::global::{GlobalField, GlobalRef};
+use dom::bindings::js::{JSRef, Root, Temporary, Unrooted};
+use dom::bindings::refcounted::Trusted;
 use dom::bindings::utils::reflect_dom_object;
-use dom::eventtarget::{EventTarget, EventTargetTypeId};
+use dom::blob::{Blob, BlobHelpers};
+use dom::closeevent::CloseEvent;
+use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
+use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
+use dom::messageevent::MessageEvent;
+use dom::urlhelper::UrlHelper;
+use script_task::{ScriptChan, ScriptMsg, Runnable};
+
+use net::resource_task::ControlMsg;
+use net::websocket_loader::{WebSocketConnectData, WebSocketDomAction, WebSocketNetworkEvent};
 use util::str::DOMString;
+use util::task::spawn_named;
+
+use js::jsapi::{JSContext, JSObject};
+use js::jsfriendapi::bindgen::{JS_NewArrayBuffer, JS_IsArrayBufferObject};
+use js::jsfriendapi::bindgen::{JS_GetArrayBufferData, JS_GetArrayBufferByteLength};
+use js::jsfriendapi::bindgen::{JS_IsArrayBufferViewObject, JS_GetArrayBufferViewData};
+use js::jsfriendapi::bindgen::JS_GetArrayBufferViewByteLength;
+use js::jsval::{JSVal, ObjectValue};
+use libc::uint8_t;
+use url::Url;
+
+use std::borrow::ToOwned;
+use std::cell::Cell;
+use std::cmp::min;
+use std::ptr;
+use std::slice;
+use std::sync::mpsc::{channel, Sender};
+
+/// The longest reason `close()` accepts, in bytes, so that a close frame fits in 125 bytes.
+static MAX_CLOSE_REASON_LENGTH: uint = 123;
+
+#[derive(PartialEq, Copy)]
+#[jstraceable]
+enum WebSocketRequestState {
+    Connecting = 0,
+    Open = 1,
+    Closing = 2,
+    Closed = 3,
+}
 
 #[dom_struct]
 pub struct WebSocket {
     eventtarget: EventTarget,
-    url: DOMString
+    url: Url,
+    global: GlobalField,
+    ready_state: Cell<WebSocketRequestState>,
+    buffered_amount: Cell<u32>,
+    protocol: DOMRefCell<DOMString>,
+    binary_type: Cell<BinaryType>,
+    /// Takes the messages for the connection. Dropping it closes the connection.
+    sender: DOMRefCell<Option<Sender<WebSocketDomAction>>>,
+}
+
+/// Whether `protocol` is a valid subprotocol name, a token as described in RFC 2616.
+fn is_valid_protocol(protocol: &str) -> bool {
+    !protocol.is_empty() && protocol.chars().all(|c| {
+        c > ' ' && c < '\x7F' && !"()<>@,;:\\\"/[]?={}".contains_char(c)
+    })
 }
 
 impl WebSocket {
-    pub fn new_inherited(url: DOMString) -> WebSocket {
+    fn new_inherited(global: GlobalRef, url: Url) -> WebSocket {
         WebSocket {
             eventtarget: EventTarget::new_inherited(EventTargetTypeId::WebSocket),
-            url: url
+            url: url,
+            global: GlobalField::from_rooted(&global),
+            ready_state: Cell::new(WebSocketRequestState::Connecting),
+            buffered_amount: Cell::new(0),
+            protocol: DOMRefCell::new("".to_owned()),
+            binary_type: Cell::new(BinaryType::Blob),
+            sender: DOMRefCell::new(None),
         }
     }
 
-    pub fn new(global: GlobalRef, url: DOMString) -> Temporary<WebSocket> {
-        reflect_dom_object(box WebSocket::new_inherited(url),
+    fn new(global: GlobalRef, url: Url) -> Temporary<WebSocket> {
+        reflect_dom_object(box WebSocket::new_inherited(global, url),
                            global,
                            WebSocketBinding::Wrap)
     }
 
-    pub fn Constructor(global: GlobalRef, url: DOMString) -> Fallible<Temporary<WebSocket>> {
-        Ok(WebSocket::new(global, url))
+    /// http://www.whatwg.org/html/#dom-websocket
+    pub fn Constructor(global: GlobalRef, url: DOMString,
+                       protocols: Option<DOMString>) -> Fallible<Temporary<WebSocket>> {
+        let url = match Url::parse(url.as_slice()) {
+            Ok(url) => url,
+            Err(_) => return Err(Syntax),
+        };
+        match url.scheme.as_slice() {
+            "ws" | "wss" => {}
+            _ => return Err(Syntax),
+        }
+        if url.fragment.is_some() {
+            return Err(Syntax);
+        }
+        let document_url = global.get_url();
+        if url.scheme.as_slice() == "ws" && document_url.scheme.as_slice() == "https" {
+            return Err(Security);
+        }
+        let protocols = match protocols {
+            Some(protocol) => {
+                if !is_valid_protocol(protocol.as_slice()) {
+                    return Err(Syntax);
+                }
+                vec!(protocol)
+            }
+            None => vec!(),
+        };
+
+        let ws = WebSocket::new(global, url.clone()).root();
+
+        let (action_sender, action_receiver) = channel();
+        let (event_sender, event_receiver) = channel();
+        let connect_data = WebSocketConnectData {
+            url: url,
+            origin: UrlHelper::Origin(&document_url),
+            protocols: protocols,
+        };
+        global.resource_task().send(ControlMsg::WebSocketConnect(connect_data, event_sender,
+                                                                 action_receiver)).unwrap();
+        *ws.r().sender.borrow_mut() = Some(action_sender);
+
+        // Keep the object alive for as long as the connection can still report events.
+        let script_chan = global.script_chan();
+        let addr = Trusted::new(global.get_cx(), ws.r(), script_chan.clone());
+        spawn_named("WebSocket event forwarder".to_owned(), move || {
+            for event in event_receiver.iter() {
+                let last = match event {
+                    WebSocketNetworkEvent::Fail | WebSocketNetworkEvent::Close(..) => true,
+                    _ => false,
+                };
+                let handler = box WebSocketEventHandler::new(addr.clone(), event);
+                if script_chan.send(ScriptMsg::RunnableMsg(handler)).is_err() || last {
+                    return;
+                }
+            }
+        });
+
+        Ok(Temporary::from_rooted(ws.r()))
+    }
+
+    pub fn handle_network_event(addr: Trusted<WebSocket>, event: WebSocketNetworkEvent) {
+        let ws = addr.to_temporary().root();
+        ws.r().process_network_event(event);
+    }
+}
+
+struct WebSocketEventHandler {
+    addr: Trusted<WebSocket>,
+    event: WebSocketNetworkEvent,
+}
+
+impl WebSocketEventHandler {
+    fn new(addr: Trusted<WebSocket>, event: WebSocketNetworkEvent) -> WebSocketEventHandler {
+        WebSocketEventHandler { addr: addr, event: event }
+    }
+}
+
+impl Runnable for WebSocketEventHandler {
+    fn handler(self: Box<WebSocketEventHandler>) {
+        let this = *self;
+        WebSocket::handle_network_event(this.addr, this.event);
+    }
+}
+
+trait PrivateWebSocketHelpers {
+    fn process_network_event(self, event: WebSocketNetworkEvent);
+    fn dispatch_simple_event(self, type_: &str);
+    fn dispatch_message(self, data: JSVal);
+    fn dispatch_close(self, code: u16, reason: DOMString, was_clean: bool);
+    fn binary_message(self, cx: *mut JSContext, data: Vec<u8>) -> JSVal;
+}
+
+impl<'a> PrivateWebSocketHelpers for JSRef<'a, WebSocket> {
+    fn process_network_event(self, event: WebSocketNetworkEvent) {
+        if self.ready_state.get() == WebSocketRequestState::Closed {
+            return;
+        }
+        match event {
+            WebSocketNetworkEvent::ConnectionEstablished(protocol) => {
+                if self.ready_state.get() == WebSocketRequestState::Closing {
+                    // close() was called before the connection was established, which fails it.
+                    self.dispatch_simple_event("error");
+                    self.dispatch_close(1006, "".to_owned(), false);
+                    return;
+                }
+                self.ready_state.set(WebSocketRequestState::Open);
+                *self.protocol.borrow_mut() = protocol.unwrap_or("".to_owned());
+                self.dispatch_simple_event("open");
+            }
+            WebSocketNetworkEvent::TextMessage(text) => {
+                if self.ready_state.get() != WebSocketRequestState::Open {
+                    return;
+                }
+                let cx = self.global.root().r().get_cx();
+                self.dispatch_message(text.to_jsval(cx));
+            }
+            WebSocketNetworkEvent::BinaryMessage(data) => {
+                if self.ready_state.get() != WebSocketRequestState::Open {
+                    return;
+                }
+                let cx = self.global.root().r().get_cx();
+                let message = self.binary_message(cx, data);
+                self.dispatch_message(message);
+            }
+            WebSocketNetworkEvent::DataSent(length) => {
+                let buffered_amount = self.buffered_amount.get();
+                self.buffered_amount.set(buffered_amount - min(length, buffered_amount as u64) as u32);
+            }
+            WebSocketNetworkEvent::Fail => {
+                self.dispatch_simple_event("error");
+                self.dispatch_close(1006, "".to_owned(), false);
+            }
+            WebSocketNetworkEvent::Close(code, reason, was_clean) => {
+                self.dispatch_close(code, reason, was_clean);
+            }
+        }
+    }
+
+    fn dispatch_simple_event(self, type_: &str) {
+        let global = self.global.root();
+        let event = Event::new(global.r(),
+                               type_.to_owned(),
+                               EventBubbles::DoesNotBubble,
+                               EventCancelable::NotCancelable).root();
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        event.r().fire(target);
+    }
+
+    fn dispatch_message(self, data: JSVal) {
+        let global = self.global.root();
+        let event = MessageEvent::new(global.r(), "message".to_owned(), false, false, data,
+                                      UrlHelper::Origin(&self.url), "".to_owned()).root();
+        let event: JSRef<Event> = EventCast::from_ref(event.r());
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        event.fire(target);
+    }
+
+    /// Closes the object, with the status code and reason of the connection.
+    fn dispatch_close(self, code: u16, reason: DOMString, was_clean: bool) {
+        self.ready_state.set(WebSocketRequestState::Closed);
+        *self.sender.borrow_mut() = None;
+        let global = self.global.root();
+        let event = CloseEvent::new(global.r(), "close".to_owned(), false, false,
+                                    was_clean, code, reason).root();
+        let event: JSRef<Event> = EventCast::from_ref(event.r());
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        event.fire(target);
+    }
+
+    /// A binary message as a `Blob` or an `ArrayBuffer`, depending on `binaryType`.
+    #[allow(unsafe_blocks)]
+    fn binary_message(self, cx: *mut JSContext, data: Vec<u8>) -> JSVal {
+        match self.binary_type.get() {
+            BinaryType::Blob => {
+                let global = self.global.root();
+                Blob::new(global.r(), Some(data), "").root().to_jsval(cx)
+            }
+            BinaryType::Arraybuffer => {
+                unsafe {
+                    let buffer: *mut JSObject = JS_NewArrayBuffer(cx, data.len() as u32);
+                    let buffer_data: *mut uint8_t = JS_GetArrayBufferData(buffer, cx);
+                    ptr::copy_nonoverlapping_memory(buffer_data, data.as_ptr(), data.len());
+                    ObjectValue(&*buffer)
+                }
+            }
+        }
+    }
+}
+
+/// The bytes of an `ArrayBuffer` or an `ArrayBufferView`, or `None` for any other object.
+#[allow(unsafe_blocks)]
+fn array_buffer_bytes(cx: *mut JSContext, obj: *mut JSObject) -> Option<Vec<u8>> {
+    unsafe {
+        if JS_IsArrayBufferObject(obj, cx) != 0 {
+            let data = JS_GetArrayBufferData(obj, cx) as *const uint8_t;
+            let length = JS_GetArrayBufferByteLength(obj, cx) as uint;
+            Some(slice::from_raw_parts(data, length).to_vec())
+        } else if JS_IsArrayBufferViewObject(obj, cx) != 0 {
+            let data = JS_GetArrayBufferViewData(obj, cx) as *const uint8_t;
+            let length = JS_GetArrayBufferViewByteLength(obj, cx) as uint;
+            Some(slice::from_raw_parts(data, length).to_vec())
+        } else {
+            None
+        }
     }
 }
 
 impl<'a> WebSocketMethods for JSRef<'a, WebSocket> {
+    event_handler!(open, GetOnopen, SetOnopen);
+    event_handler!(error, GetOnerror, SetOnerror);
+    event_handler!(close, GetOnclose, SetOnclose);
+    event_handler!(message, GetOnmessage, SetOnmessage);
+
     fn Url(self) -> DOMString {
-        self.url.clone()
+        self.url.serialize()
+    }
+
+    fn ReadyState(self) -> u16 {
+        self.ready_state.get() as u16
+    }
+
+    fn BufferedAmount(self) -> u32 {
+        self.buffered_amount.get()
+    }
+
+    fn Extensions(self) -> DOMString {
+        // No extension is ever negotiated.
+        "".to_owned()
+    }
+
+    fn Protocol(self) -> DOMString {
+        self.protocol.borrow().clone()
+    }
+
+    fn BinaryType(self) -> BinaryType {
+        self.binary_type.get()
+    }
+
+    fn SetBinaryType(self, binary_type: BinaryType) {
+        self.binary_type.set(binary_type)
+    }
+
+    /// http://www.whatwg.org/html/#dom-websocket-close
+    fn Close(self, code: Option<u16>, reason: Option<DOMString>) -> ErrorResult {
+        if let Some(code) = code {
+            if code != 1000 && (code < 3000 || code > 4999) {
+                return Err(InvalidAccess);
+            }
+        }
+        let reason = reason.unwrap_or("".to_owned());
+        if reason.len() > MAX_CLOSE_REASON_LENGTH {
+            return Err(Syntax);
+        }
+        match self.ready_state.get() {
+            WebSocketRequestState::Closing | WebSocketRequestState::Closed => return Ok(()),
+            WebSocketRequestState::Connecting => {
+                // Dropping the sender closes the connection as soon as it is established.
+                *self.sender.borrow_mut() = None;
+            }
+            WebSocketRequestState::Open => {
+                if let Some(ref sender) = *self.sender.borrow() {
+                    let _ = sender.send(WebSocketDomAction::Close(code, reason));
+                }
+            }
+        }
+        self.ready_state.set(WebSocketRequestState::Closing);
+        Ok(())
+    }
+
+    /// http://www.whatwg.org/html/#dom-websocket-send
+    fn Send(self, cx: *mut JSContext, data: JSVal) -> ErrorResult {
+        if self.ready_state.get() == WebSocketRequestState::Connecting {
+            return Err(InvalidState);
+        }
+
+        let blob: Option<Root<Blob>> = if data.is_object() {
+            unwrap_jsmanaged(data.to_object()).ok().map(|blob: Unrooted<Blob>| blob.root())
+        } else {
+            None
+        };
+        let action = match blob {
+            Some(blob) => WebSocketDomAction::SendBinary(blob.r().get_bytes()),
+            None => {
+                let bytes = if data.is_object() {
+                    array_buffer_bytes(cx, data.to_object())
+                } else {
+                    None
+                };
+                match bytes {
+                    Some(bytes) => WebSocketDomAction::SendBinary(bytes),
+                    None => {
+                        let text: DOMString = try!(FromJSValConvertible::from_jsval(
+                            cx, data, StringificationBehavior::Default).map_err(|_| JSFailed));
+                        WebSocketDomAction::SendText(text)
+                    }
+                }
+            }
+        };
+
+        // The data stays buffered until the connection reports it sent; once the connection is
+        // closing, it is discarded and stays counted.
+        let length = match action {
+            WebSocketDomAction::SendText(ref text) => text.len(),
+            WebSocketDomAction::SendBinary(ref bytes) => bytes.len(),
+            WebSocketDomAction::Close(..) => 0,
+        };
+        self.buffered_amount.set(self.buffered_amount.get() + length as u32);
+        if self.ready_state.get() != WebSocketRequestState::Open {
+            return Ok(());
+        }
+        if let Some(ref sender) = *self.sender.borrow() {
+            let _ = sender.send(action);
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,119 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::MouseEventBinding::MouseEventMethods;
+use dom::bindings::codegen::Bindings::WheelEventBinding;
+use dom::bindings::codegen::Bindings::WheelEventBinding::WheelEventMethods;
+use dom::bindings::codegen::InheritTypes::{MouseEventCast, WheelEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, RootedReference, Temporary};
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventTypeId};
+use dom::eventtarget::EventTarget;
+use dom::mouseevent::MouseEvent;
+use dom::window::Window;
+use util::str::DOMString;
+use std::cell::Cell;
+
+#[dom_struct]
+pub struct WheelEvent {
+    mouseevent: MouseEvent,
+    delta_x: Cell<f64>,
+    delta_y: Cell<f64>,
+    delta_z: Cell<f64>,
+    delta_mode: Cell<u32>,
+}
+
+impl WheelEventDerived for Event {
+    fn is_wheelevent(&self) -> bool {
+        *self.type_id() == EventTypeId::WheelEvent
+    }
+}
+
+impl WheelEvent {
+    fn new_inherited() -> WheelEvent {
+        WheelEvent {
+            mouseevent: MouseEvent::new_inherited(EventTypeId::WheelEvent),
+            delta_x: Cell::new(0.0),
+            delta_y: Cell::new(0.0),
+            delta_z: Cell::new(0.0),
+            delta_mode: Cell::new(0),
+        }
+    }
+
+    pub fn new_uninitialized(window: JSRef<Window>) -> Temporary<WheelEvent> {
+        reflect_dom_object(box WheelEvent::new_inherited(),
+                           GlobalRef::Window(window),
+                           WheelEventBinding::Wrap)
+    }
+
+    pub fn new(window: JSRef<Window>,
+               type_: DOMString,
+               canBubble: bool,
+               cancelable: bool,
+               view: Option<JSRef<Window>>,
+               detail: i32,
+               screenX: i32,
+               screenY: i32,
+               clientX: i32,
+               clientY: i32,
+               ctrlKey: bool,
+               altKey: bool,
+               shiftKey: bool,
+               metaKey: bool,
+               button: i16,
+               relatedTarget: Option<JSRef<EventTarget>>,
+               deltaX: f64,
+               deltaY: f64,
+               deltaZ: f64,
+               deltaMode: u32) -> Temporary<WheelEvent> {
+        let ev = WheelEvent::new_uninitialized(window).root();
+        let mouseevent: JSRef<MouseEvent> = MouseEventCast::from_ref(ev.r());
+        mouseevent.InitMouseEvent(type_, canBubble, cancelable, view, detail,
+                                  screenX, screenY, clientX, clientY,
+                                  ctrlKey, altKey, shiftKey, metaKey,
+                                  button, relatedTarget);
+        ev.r().delta_x.set(deltaX);
+        ev.r().delta_y.set(deltaY);
+        ev.r().delta_z.set(deltaZ);
+        ev.r().delta_mode.set(deltaMode);
+        Temporary::from_rooted(ev.r())
+    }
+
+    pub fn Constructor(global: GlobalRef,
+                       type_: DOMString,
+                       init: &WheelEventBinding::WheelEventInit) -> Fallible<Temporary<WheelEvent>> {
+        let event = WheelEvent::new(global.as_window(), type_,
+                                    init.parent.parent.parent.parent.bubbles,
+                                    init.parent.parent.parent.parent.cancelable,
+                                    init.parent.parent.parent.view.r(),
+                                    init.parent.parent.parent.detail,
+                                    init.parent.screenX, init.parent.screenY,
+                                    init.parent.clientX, init.parent.clientY,
+                                    init.parent.parent.ctrlKey, init.parent.parent.altKey,
+                                    init.parent.parent.shiftKey, init.parent.parent.metaKey,
+                                    init.parent.button, init.parent.relatedTarget.r(),
+                                    init.deltaX, init.deltaY, init.deltaZ, init.deltaMode);
+        Ok(event)
+    }
+}
+
+impl<'a> WheelEventMethods for JSRef<'a, WheelEvent> {
+    fn DeltaX(self) -> f64 {
+        self.delta_x.get()
+    }
+
+    fn DeltaY(self) -> f64 {
+        self.delta_y.get()
+    }
+
+    fn DeltaZ(self) -> f64 {
+        self.delta_z.get()
+    }
+
+    fn DeltaMode(self) -> u32 {
+        self.delta_mode.get()
+    }
+}
@@ -6,37 +6,43 @@ use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::EventHandlerBinding::{OnErrorEventHandlerNonNull, EventHandlerNonNull};
 use dom::bindings::codegen::Bindings::FunctionBinding::Function;
 use dom::bindings::codegen::Bindings::WindowBinding;
-use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use dom::bindings::codegen::Bindings::WindowBinding::{FrameRequestCallback, WindowMethods};
 use dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
-use dom::bindings::codegen::InheritTypes::EventTargetCast;
+use dom::bindings::codegen::InheritTypes::{EventTargetCast, NodeCast};
 use dom::bindings::global::global_object_for_js_object;
 use dom::bindings::error::{report_pending_exception, Fallible};
 use dom::bindings::error::Error::InvalidCharacter;
 use dom::bindings::global::GlobalRef;
-use dom::bindings::js::{MutNullableJS, JSRef, Temporary};
+use dom::bindings::js::{JS, MutNullableJS, JSRef, OptionalRootable, Temporary};
+use dom::bindings::refcounted::Trusted;
 use dom::bindings::utils::Reflectable;
 use dom::browsercontext::BrowserContext;
 use dom::console::Console;
-use dom::document::Document;
+use dom::cssstyledeclaration::{CSSStyleDeclaration, CSSModificationAccess};
+use dom::document::{Document, DocumentHelpers};
 use dom::element::Element;
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
+use dom::history::History;
 use dom::location::Location;
+use dom::mutationobserver::{MutationObserver, MutationObserverHelpers};
 use dom::navigator::Navigator;
-use dom::node::window_from_node;
+use dom::node::{Node, NodeHelpers, window_from_node};
 use dom::performance::Performance;
 use dom::screen::Screen;
 use dom::storage::Storage;
 use layout_interface::{ReflowGoal, ReflowQueryType};
 use page::Page;
 use script_task::{TimerSource, ScriptChan};
-use script_task::ScriptMsg;
+use script_task::{ScriptMsg, Runnable};
 use script_traits::ScriptControlChan;
 use timers::{IsInterval, TimerId, TimerManager, TimerCallback};
 
-use msg::compositor_msg::ScriptListener;
-use msg::constellation_msg::LoadData;
+use msg::compositor_msg::{LayerId, ScriptListener};
+use msg::constellation_msg::{LoadData, StorageType};
 use net::image_cache_task::ImageCacheTask;
 use net::storage_task::StorageTask;
+use selectors::parser::PseudoElement;
+use util::geometry::to_frac_px;
 use util::str::{DOMString,HTML_SPACE_CHARACTERS};
 
 use js::jsapi::JS_EvaluateUCScript;
@@ -44,11 +50,13 @@ use js::jsapi::JSContext;
 use js::jsapi::{JS_GC, JS_GetRuntime};
 use js::jsval::{JSVal, UndefinedValue};
 use js::rust::with_compartment;
+use geom::point::Point2D;
 use url::{Url, UrlParser};
 
 use libc;
 use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
-use std::cell::{Ref, RefMut};
+use std::ascii::AsciiExt;
+use std::cell::{Cell, Ref, RefMut};
 use std::default::Default;
 use std::ffi::CString;
 use std::rc::Rc;
@@ -61,6 +69,7 @@ pub struct Window {
     control_chan: ScriptControlChan,
     console: MutNullableJS<Console>,
     navigator: MutNullableJS<Navigator>,
+    history: MutNullableJS<History>,
     image_cache_task: ImageCacheTask,
     compositor: DOMRefCell<Box<ScriptListener+'static>>,
     browser_context: DOMRefCell<Option<BrowserContext>>,
@@ -70,7 +79,13 @@ pub struct Window {
     navigation_start_precise: f64,
     screen: MutNullableJS<Screen>,
     session_storage: MutNullableJS<Storage>,
+    local_storage: MutNullableJS<Storage>,
     timers: TimerManager,
+    /// The MutationObserver objects of this window that are observing nodes.
+    /// http://dom.spec.whatwg.org/#mutation-observers
+    mutation_observers: DOMRefCell<Vec<JS<MutationObserver>>>,
+    /// The scroll position of the viewport, in CSS pixels.
+    scroll_offset: Cell<Point2D<f64>>,
 }
 
 impl Window {
@@ -209,8 +224,17 @@ impl<'a> WindowMethods for JSRef<'a, Window> {
         self.Document().root().r().Location()
     }
 
+    fn History(self) -> Temporary<History> {
+        self.history.or_init(|| History::new(self))
+    }
+
     fn SessionStorage(self) -> Temporary<Storage> {
-        self.session_storage.or_init(|| Storage::new(&GlobalRef::Window(self)))
+        let storage_type = StorageType::Session(self.page().top_level_browsing_context_id);
+        self.session_storage.or_init(|| Storage::new(&GlobalRef::Window(self), storage_type))
+    }
+
+    fn LocalStorage(self) -> Temporary<Storage> {
+        self.local_storage.or_init(|| Storage::new(&GlobalRef::Window(self), StorageType::Local))
     }
 
     fn Console(self) -> Temporary<Console> {
@@ -301,7 +325,34 @@ impl<'a> WindowMethods for JSRef<'a, Window> {
         })
     }
 
+    fn RequestAnimationFrame(self, callback: FrameRequestCallback) -> i32 {
+        self.Document().root().r().request_animation_frame(callback)
+    }
+
+    fn CancelAnimationFrame(self, ident: i32) {
+        self.Document().root().r().cancel_animation_frame(ident);
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-window-getcomputedstyle
+    fn GetComputedStyle(self, element: JSRef<Element>,
+                        pseudo: Option<DOMString>) -> Temporary<CSSStyleDeclaration> {
+        // Steps 1-4.
+        let pseudo = match pseudo.map(|pseudo| pseudo.as_slice().to_ascii_lowercase()) {
+            Some(ref pseudo) if pseudo.as_slice() == ":before" || pseudo.as_slice() == "::before" =>
+                Some(PseudoElement::Before),
+            Some(ref pseudo) if pseudo.as_slice() == ":after" || pseudo.as_slice() == "::after" =>
+                Some(PseudoElement::After),
+            _ => None,
+        };
+
+        // Step 5.
+        CSSStyleDeclaration::new(self, element, pseudo, CSSModificationAccess::Readonly)
+    }
+
     global_event_handlers!();
+    event_handler!(hashchange, GetOnhashchange, SetOnhashchange);
+    event_handler!(popstate, GetOnpopstate, SetOnpopstate);
+    event_handler!(storage, GetOnstorage, SetOnstorage);
     event_handler!(unload, GetOnunload, SetOnunload);
     error_event_handler!(error, GetOnerror, SetOnerror);
 
@@ -309,6 +360,42 @@ impl<'a> WindowMethods for JSRef<'a, Window> {
         self.screen.or_init(|| Screen::new(self))
     }
 
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrollx
+    fn ScrollX(self) -> f64 {
+        self.scroll_offset.get().x
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-pagexoffset
+    fn PageXOffset(self) -> f64 {
+        self.ScrollX()
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrolly
+    fn ScrollY(self) -> f64 {
+        self.scroll_offset.get().y
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-pageyoffset
+    fn PageYOffset(self) -> f64 {
+        self.ScrollY()
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scroll
+    fn Scroll(self, x: f64, y: f64) {
+        self.ScrollTo(x, y)
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrollto
+    fn ScrollTo(self, x: f64, y: f64) {
+        self.scroll(x, y)
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrollby
+    fn ScrollBy(self, x: f64, y: f64) {
+        let offset = self.scroll_offset.get();
+        self.scroll(offset.x + x, offset.y + y)
+    }
+
     fn Debug(self, message: DOMString) {
         debug!("{}", message);
     }
@@ -337,6 +424,10 @@ pub trait WindowHelpers {
     fn IndexedGetter(self, _index: u32, _found: &mut bool) -> Option<Temporary<Window>>;
     fn thaw(self);
     fn freeze(self);
+    fn register_mutation_observer(self, observer: JSRef<MutationObserver>);
+    fn mutation_observers(self) -> Vec<Temporary<MutationObserver>>;
+    fn set_scroll_offset(self, offset: Point2D<f64>) -> bool;
+    fn scroll(self, x: f64, y: f64);
 }
 
 pub trait ScriptHelpers {
@@ -383,18 +474,21 @@ impl<'a> WindowHelpers for JSRef<'a, Window> {
 
     /// Commence a new URL load which will either replace this window or scroll to a fragment.
     fn load_url(self, href: DOMString) {
-        let base_url = self.page().get_url();
+        let base_url = self.Document().root().r().url();
         debug!("current page url is {}", base_url);
         let url = UrlParser::new().base_url(&base_url).parse(href.as_slice());
         // FIXME: handle URL parse errors more gracefully.
         let url = url.unwrap();
-        match url.fragment {
-            Some(fragment) => {
-                self.script_chan.send(ScriptMsg::TriggerFragment(self.page.id, fragment)).unwrap();
-            },
-            None => {
-                self.script_chan.send(ScriptMsg::TriggerLoad(self.page.id, LoadData::new(url))).unwrap();
-            }
+
+        // http://www.whatwg.org/html/#navigating-across-documents step 13
+        let mut url_without_fragment = url.clone();
+        url_without_fragment.fragment = None;
+        let mut base_url_without_fragment = base_url;
+        base_url_without_fragment.fragment = None;
+        if url.fragment.is_some() && url_without_fragment == base_url_without_fragment {
+            self.script_chan.send(ScriptMsg::TriggerFragment(self.page.id, url)).unwrap();
+        } else {
+            self.script_chan.send(ScriptMsg::TriggerLoad(self.page.id, LoadData::new(url))).unwrap();
         }
     }
 
@@ -416,6 +510,74 @@ impl<'a> WindowHelpers for JSRef<'a, Window> {
         self.timers.suspend();
     }
 
+    fn register_mutation_observer(self, observer: JSRef<MutationObserver>) {
+        let mut observers = self.mutation_observers.borrow_mut();
+        if !observers.contains(&JS::from_rooted(observer)) {
+            observers.push(JS::from_rooted(observer));
+        }
+    }
+
+    fn mutation_observers(self) -> Vec<Temporary<MutationObserver>> {
+        let mut observers = self.mutation_observers.borrow_mut();
+        observers.retain(|observer| observer.root().r().is_observing());
+        observers.iter().map(|observer| Temporary::new(observer.clone())).collect()
+    }
+
+    /// Records the scroll position of the viewport, and returns whether it changed.
+    fn set_scroll_offset(self, offset: Point2D<f64>) -> bool {
+        if self.scroll_offset.get() == offset {
+            return false;
+        }
+        self.scroll_offset.set(offset);
+        true
+    }
+
+    /// Scrolls the viewport to the given position, clamped to the scrolling area of the
+    /// document.
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scroll
+    fn scroll(self, x: f64, y: f64) {
+        // Step 1-2: non-finite values are treated as zero.
+        let x = if x.is_finite() { x } else { 0.0 };
+        let y = if y.is_finite() { y } else { 0.0 };
+
+        // Step 3-9.
+        let document = self.Document().root();
+        let viewport = self.page().window_size.get().initial_viewport;
+        let (max_x, max_y) = match document.r().GetDocumentElement().root() {
+            Some(root) => {
+                let root: JSRef<Node> = NodeCast::from_ref(root.r());
+                let rect = root.get_bounding_content_box();
+                (to_frac_px(rect.max_x()) - viewport.width.get() as f64,
+                 to_frac_px(rect.max_y()) - viewport.height.get() as f64)
+            }
+            None => (0.0, 0.0),
+        };
+        let offset = Point2D(x.min(max_x).max(0.0), y.min(max_y).max(0.0));
+        if !self.set_scroll_offset(offset) {
+            return;
+        }
+
+        // Step 10: perform the scroll, and let the document know asynchronously.
+        let point = Point2D(offset.x as f32, offset.y as f32);
+        self.compositor().scroll_fragment_point(self.page().id, LayerId::null(), point);
+        let runnable = box ScrollEventRunnable {
+            document: Trusted::new(self.get_cx(), document.r(), self.script_chan()),
+        };
+        self.script_chan.send(ScriptMsg::RunnableMsg(runnable)).unwrap();
+    }
+
+}
+
+/// Fires the `scroll` event of a scroll that script performed.
+struct ScrollEventRunnable {
+    document: Trusted<Document>,
+}
+
+impl Runnable for ScrollEventRunnable {
+    fn handler(self: Box<ScrollEventRunnable>) {
+        let document = self.document.to_temporary().root();
+        document.r().fire_scroll_event();
+    }
 }
 
 impl Window {
@@ -434,6 +596,7 @@ impl Window {
             compositor: DOMRefCell::new(compositor),
             page: page,
             navigator: Default::default(),
+            history: Default::default(),
             image_cache_task: image_cache_task,
             browser_context: DOMRefCell::new(None),
             performance: Default::default(),
@@ -441,7 +604,10 @@ impl Window {
             navigation_start_precise: time::precise_time_ns() as f64,
             screen: Default::default(),
             session_storage: Default::default(),
+            local_storage: Default::default(),
             timers: TimerManager::new(),
+            mutation_observers: DOMRefCell::new(vec!()),
+            scroll_offset: Cell::new(Point2D(0.0, 0.0)),
         };
 
         WindowBinding::Wrap(cx, win)
@@ -19,6 +19,8 @@ use dom::errorevent::ErrorEvent;
 use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
 use dom::messageevent::MessageEvent;
+use dom::messageport::{MessagePort, MessagePortHelpers, TransferredPort};
+use dom::workerglobalscope::WorkerRuntime;
 use script_task::{ScriptChan, ScriptMsg, Runnable};
 
 use util::str::DOMString;
@@ -28,6 +30,8 @@ use js::jsval::{JSVal, UndefinedValue};
 use url::UrlParser;
 
 use std::borrow::ToOwned;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Sender};
 
 pub type TrustedWorkerAddress = Trusted<Worker>;
@@ -39,19 +43,28 @@ pub struct Worker {
     /// Sender to the Receiver associated with the DedicatedWorkerGlobalScope
     /// this Worker created.
     sender: Sender<(TrustedWorkerAddress, ScriptMsg)>,
+    /// The closing flag of the DedicatedWorkerGlobalScope, set when the worker is
+    /// terminated.
+    closing: Arc<AtomicBool>,
+    /// The runtime of the worker, interrupted when the worker is terminated.
+    runtime: WorkerRuntime,
 }
 
 impl Worker {
-    fn new_inherited(global: GlobalRef, sender: Sender<(TrustedWorkerAddress, ScriptMsg)>) -> Worker {
+    fn new_inherited(global: GlobalRef, sender: Sender<(TrustedWorkerAddress, ScriptMsg)>,
+                     closing: Arc<AtomicBool>, runtime: WorkerRuntime) -> Worker {
         Worker {
             eventtarget: EventTarget::new_inherited(EventTargetTypeId::Worker),
             global: GlobalField::from_rooted(&global),
             sender: sender,
+            closing: closing,
+            runtime: runtime,
         }
     }
 
-    pub fn new(global: GlobalRef, sender: Sender<(TrustedWorkerAddress, ScriptMsg)>) -> Temporary<Worker> {
-        reflect_dom_object(box Worker::new_inherited(global, sender),
+    pub fn new(global: GlobalRef, sender: Sender<(TrustedWorkerAddress, ScriptMsg)>,
+               closing: Arc<AtomicBool>, runtime: WorkerRuntime) -> Temporary<Worker> {
+        reflect_dom_object(box Worker::new_inherited(global, sender, closing, runtime),
                            global,
                            WorkerBinding::Wrap)
     }
@@ -68,25 +81,35 @@ impl Worker {
         let resource_task = global.resource_task();
 
         let (sender, receiver) = channel();
-        let worker = Worker::new(global, sender.clone()).root();
+        let closing = Arc::new(AtomicBool::new(false));
+        let runtime = WorkerRuntime::new();
+        let worker = Worker::new(global, sender.clone(), closing.clone(),
+                                 runtime.clone()).root();
         let worker_ref = Trusted::new(global.get_cx(), worker.r(), global.script_chan());
 
         DedicatedWorkerGlobalScope::run_worker_scope(
             worker_url, worker_ref, resource_task, global.script_chan(),
-            sender, receiver);
+            sender, receiver, closing, runtime);
 
         Ok(Temporary::from_rooted(worker.r()))
     }
 
     pub fn handle_message(address: TrustedWorkerAddress,
-                          data: StructuredCloneData) {
+                          data: StructuredCloneData,
+                          ports: Vec<TransferredPort>) {
         let worker = address.to_temporary().root();
+        // Messages that were still queued when the worker was terminated are dropped.
+        if worker.r().closing.load(Ordering::SeqCst) {
+            return;
+        }
 
         let global = worker.r().global.root();
         let target: JSRef<EventTarget> = EventTargetCast::from_ref(worker.r());
 
         let message = data.read(global.r());
-        MessageEvent::dispatch_jsval(target, global.r(), message);
+        let ports = ports.into_iter().map(|port| MessagePort::new(global.r(), port)).collect();
+        MessageEvent::dispatch_with_ports(target, global.r(), "message".to_owned(),
+                                          message, ports);
     }
 
     pub fn dispatch_simple_error(address: TrustedWorkerAddress) {
@@ -116,10 +139,27 @@ impl Worker {
 }
 
 impl<'a> WorkerMethods for JSRef<'a, Worker> {
-    fn PostMessage(self, cx: *mut JSContext, message: JSVal) -> ErrorResult {
+    // http://www.whatwg.org/html/#dom-worker-terminate
+    fn Terminate(self) {
+        if self.closing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // A running script stops once it is interrupted, and the worker then discards
+        // its queued tasks; an idle worker is woken up to see the closing flag.
+        self.runtime.interrupt();
+        let global = self.global.root();
+        let address = Trusted::new(global.r().get_cx(), self, global.r().script_chan());
+        let _ = self.sender.send((address, ScriptMsg::TerminateWorker));
+    }
+
+    // http://www.whatwg.org/html/#dom-worker-postmessage
+    fn PostMessage(self, cx: *mut JSContext, message: JSVal, transfer: JSVal) -> ErrorResult {
+        let ports = try!(MessagePort::transfer_list(cx, transfer));
         let data = try!(StructuredCloneData::write(cx, message));
+        let ports = ports.into_iter().map(|port| port.root().r().transfer()).collect();
         let address = Trusted::new(cx, self, self.global.root().r().script_chan().clone());
-        self.sender.send((address, ScriptMsg::DOMMessage(data))).unwrap();
+        // The worker may have closed itself, in which case the message is dropped.
+        let _ = self.sender.send((address, ScriptMsg::DOMMessage(data, ports)));
         Ok(())
     }
 
@@ -129,13 +169,16 @@ impl<'a> WorkerMethods for JSRef<'a, Worker> {
 pub struct WorkerMessageHandler {
     addr: TrustedWorkerAddress,
     data: StructuredCloneData,
+    ports: Vec<TransferredPort>,
 }
 
 impl WorkerMessageHandler {
-    pub fn new(addr: TrustedWorkerAddress, data: StructuredCloneData) -> WorkerMessageHandler {
+    pub fn new(addr: TrustedWorkerAddress, data: StructuredCloneData,
+               ports: Vec<TransferredPort>) -> WorkerMessageHandler {
         WorkerMessageHandler {
             addr: addr,
             data: data,
+            ports: ports,
         }
     }
 }
@@ -143,7 +186,7 @@ impl WorkerMessageHandler {
 impl Runnable for WorkerMessageHandler {
     fn handler(self: Box<WorkerMessageHandler>) {
         let this = *self;
-        Worker::handle_message(this.addr, this.data);
+        Worker::handle_message(this.addr, this.data, this.ports);
     }
 }
 
@@ -4,7 +4,7 @@
 
 use dom::bindings::codegen::Bindings::FunctionBinding::Function;
 use dom::bindings::codegen::Bindings::WorkerGlobalScopeBinding::WorkerGlobalScopeMethods;
-use dom::bindings::codegen::InheritTypes::DedicatedWorkerGlobalScopeCast;
+use dom::bindings::codegen::InheritTypes::{DedicatedWorkerGlobalScopeCast, SharedWorkerGlobalScopeCast};
 use dom::bindings::error::{ErrorResult, Fallible};
 use dom::bindings::error::Error::{Syntax, Network, JSFailed};
 use dom::bindings::global::GlobalRef;
@@ -13,27 +13,107 @@ use dom::bindings::utils::Reflectable;
 use dom::console::Console;
 use dom::dedicatedworkerglobalscope::{DedicatedWorkerGlobalScope, DedicatedWorkerGlobalScopeHelpers};
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
+use dom::sharedworkerglobalscope::{SharedWorkerGlobalScope, SharedWorkerGlobalScopeHelpers};
 use dom::workerlocation::WorkerLocation;
 use dom::workernavigator::WorkerNavigator;
 use dom::window::{base64_atob, base64_btoa};
 use script_task::{ScriptChan, TimerSource};
 use timers::{IsInterval, TimerId, TimerManager, TimerCallback};
 
-use net::resource_task::{ResourceTask, load_whole_resource};
+use net::resource_task::{LoadContext, ResourceTask, load_whole_subresource};
 use util::str::DOMString;
 
-use js::jsapi::JSContext;
+use js::jsapi::{JSBool, JSContext, JSRuntime};
+use js::jsapi::{JS_SetOperationCallback, JS_TriggerOperationCallback};
 use js::jsval::JSVal;
 use js::rust::Cx;
 
+use std::cell::RefCell;
 use std::default::Default;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use url::{Url, UrlParser};
 
+// The closing flag of the worker running on this thread, checked when its script is
+// interrupted.
+thread_local!(static CLOSING: RefCell<Option<Arc<AtomicBool>>> = RefCell::new(None));
+
+unsafe extern "C" fn interrupt_callback(_cx: *mut JSContext) -> JSBool {
+    // Returning false stops the running script, without an exception.
+    CLOSING.with(|closing| {
+        match *closing.borrow() {
+            Some(ref closing) if closing.load(Ordering::SeqCst) => 0,
+            _ => 1,
+        }
+    })
+}
+
+/// A pointer to the runtime of a worker, sent to the thread that can terminate it.
+struct SharedRt(*mut JSRuntime);
+unsafe impl Send for SharedRt {}
+
+/// The runtime of a worker, through which the scripts of the worker are interrupted
+/// when it is terminated from another thread.
+#[derive(Clone)]
+pub struct WorkerRuntime {
+    /// The runtime, or `None` when the worker has not created it yet or destroyed it.
+    rt: Arc<Mutex<Option<SharedRt>>>,
+}
+
+no_jsmanaged_fields!(WorkerRuntime);
+
+impl WorkerRuntime {
+    pub fn new() -> WorkerRuntime {
+        WorkerRuntime {
+            rt: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Makes the scripts of `cx` interruptible, until the returned guard is dropped,
+    /// which must happen before `rt` is destroyed. An interrupted script stops if
+    /// `closing` is set. Must be called on the worker thread.
+    #[allow(unsafe_blocks)]
+    pub fn attach(&self, rt: *mut JSRuntime, cx: *mut JSContext,
+                  closing: Arc<AtomicBool>) -> AttachedWorkerRuntime {
+        CLOSING.with(|flag| *flag.borrow_mut() = Some(closing));
+        unsafe {
+            JS_SetOperationCallback(cx, Some(interrupt_callback as
+                                             unsafe extern "C" fn(*mut JSContext) -> JSBool));
+        }
+        *self.rt.lock().unwrap() = Some(SharedRt(rt));
+        AttachedWorkerRuntime {
+            runtime: self.clone(),
+        }
+    }
+
+    /// Interrupts the script that the worker is running, if any.
+    #[allow(unsafe_blocks)]
+    pub fn interrupt(&self) {
+        if let Some(SharedRt(rt)) = *self.rt.lock().unwrap() {
+            unsafe {
+                JS_TriggerOperationCallback(rt);
+            }
+        }
+    }
+}
+
+/// Detaches a worker runtime when dropped.
+pub struct AttachedWorkerRuntime {
+    runtime: WorkerRuntime,
+}
+
+impl Drop for AttachedWorkerRuntime {
+    fn drop(&mut self) {
+        *self.runtime.rt.lock().unwrap() = None;
+    }
+}
+
 #[derive(Copy, PartialEq)]
 #[jstraceable]
 pub enum WorkerGlobalScopeTypeId {
     DedicatedGlobalScope,
+    SharedGlobalScope,
 }
 
 #[dom_struct]
@@ -46,13 +126,18 @@ pub struct WorkerGlobalScope {
     navigator: MutNullableJS<WorkerNavigator>,
     console: MutNullableJS<Console>,
     timers: TimerManager,
+    /// The closing flag, which is shared with the `Worker` object that can terminate
+    /// this worker.
+    /// http://www.whatwg.org/html/#dom-workerglobalscope-closing
+    closing: Arc<AtomicBool>,
 }
 
 impl WorkerGlobalScope {
     pub fn new_inherited(type_id: WorkerGlobalScopeTypeId,
                          worker_url: Url,
                          cx: Rc<Cx>,
-                         resource_task: ResourceTask) -> WorkerGlobalScope {
+                         resource_task: ResourceTask,
+                         closing: Arc<AtomicBool>) -> WorkerGlobalScope {
         WorkerGlobalScope {
             eventtarget: EventTarget::new_inherited(EventTargetTypeId::WorkerGlobalScope(type_id)),
             worker_url: worker_url,
@@ -62,6 +147,7 @@ impl WorkerGlobalScope {
             navigator: Default::default(),
             console: Default::default(),
             timers: TimerManager::new(),
+            closing: closing,
         }
     }
 
@@ -94,6 +180,11 @@ impl<'a> WorkerGlobalScopeMethods for JSRef<'a, WorkerGlobalScope> {
         })
     }
 
+    // http://www.whatwg.org/html/#dom-workerglobalscope-close
+    fn Close(self) {
+        self.closing.store(true, Ordering::SeqCst);
+    }
+
     fn ImportScripts(self, url_strings: Vec<DOMString>) -> ErrorResult {
         let mut urls = Vec::with_capacity(url_strings.len());
         for url in url_strings.into_iter() {
@@ -106,7 +197,9 @@ impl<'a> WorkerGlobalScopeMethods for JSRef<'a, WorkerGlobalScope> {
         }
 
         for url in urls.into_iter() {
-            let (url, source) = match load_whole_resource(&self.resource_task, url) {
+            let (url, source) = match load_whole_subresource(&self.resource_task, url,
+                                                             LoadContext::Script,
+                                                             self.worker_url.clone(), None) {
                 Err(_) => return Err(Network),
                 Ok((metadata, bytes)) => {
                     (metadata.final_url, String::from_utf8(bytes).unwrap())
@@ -191,16 +284,19 @@ pub trait WorkerGlobalScopeHelpers {
     fn handle_fire_timer(self, timer_id: TimerId);
     fn script_chan(self) -> Box<ScriptChan+Send>;
     fn get_cx(self) -> *mut JSContext;
+    fn is_closing(self) -> bool;
 }
 
 impl<'a> WorkerGlobalScopeHelpers for JSRef<'a, WorkerGlobalScope> {
     fn script_chan(self) -> Box<ScriptChan+Send> {
         let dedicated: Option<JSRef<DedicatedWorkerGlobalScope>> =
             DedicatedWorkerGlobalScopeCast::to_ref(self);
-        match dedicated {
-            Some(dedicated) => dedicated.script_chan(),
-            None => panic!("need to implement a sender for SharedWorker"),
+        if let Some(dedicated) = dedicated {
+            return dedicated.script_chan();
         }
+        let shared: Option<JSRef<SharedWorkerGlobalScope>> =
+            SharedWorkerGlobalScopeCast::to_ref(self);
+        shared.unwrap().script_chan()
     }
 
     fn handle_fire_timer(self, timer_id: TimerId) {
@@ -210,5 +306,11 @@ impl<'a> WorkerGlobalScopeHelpers for JSRef<'a, WorkerGlobalScope> {
     fn get_cx(self) -> *mut JSContext {
         self.js_context.ptr
     }
+
+    /// Whether the worker was closed or terminated, in which case its event loop
+    /// discards the remaining tasks.
+    fn is_closing(self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
 }
 
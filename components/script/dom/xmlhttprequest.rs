@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::DocumentBinding::DocumentReadyState;
 use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
 use dom::bindings::codegen::Bindings::XMLHttpRequestBinding;
 use dom::bindings::codegen::Bindings::XMLHttpRequestBinding::XMLHttpRequestMethods;
@@ -18,9 +19,11 @@ use dom::bindings::js::{MutNullableJS, JS, JSRef, Temporary, OptionalRootedRoota
 use dom::bindings::refcounted::Trusted;
 use dom::bindings::str::ByteString;
 use dom::bindings::utils::{Reflectable, reflect_dom_object};
-use dom::document::Document;
+use dom::document::{Document, DocumentHelpers, DocumentSource, IsHTMLDocument};
 use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
+use dom::formdata::FormDataHelpers;
+use dom::htmlformelement::{encode_multipart_form_data, generate_boundary};
 use dom::progressevent::ProgressEvent;
 use dom::urlsearchparams::URLSearchParamsHelpers;
 use dom::xmlhttprequesteventtarget::XMLHttpRequestEventTarget;
@@ -42,15 +45,15 @@ use js::jsapi::{JS_ParseJSON, JSContext};
 use js::jsapi::JS_ClearPendingException;
 use js::jsval::{JSVal, NullValue, UndefinedValue};
 
-use net::resource_task::{ResourceTask, ResourceCORSData, LoadData, LoadResponse};
-use net::resource_task::ControlMsg::Load;
+use net::fetch::request::{Context, CredentialsMode, Referer, Request, RequestMode};
 use net::resource_task::ProgressMsg::{Payload, Done};
-use cors::{allow_cross_origin_request, CORSRequest, RequestMode};
+use parse::html::{HTMLInput, parse_html};
+use parse::xml::{XMLInput, XMLScripting, decode_xml, is_xml_mime_type, parse_xml};
 use util::str::DOMString;
 use util::task::spawn_named;
 
 use std::ascii::AsciiExt;
-use std::borrow::ToOwned;
+use std::borrow::{IntoCow, ToOwned};
 use std::cell::Cell;
 use std::sync::mpsc::{Sender, Receiver, channel};
 use std::default::Default;
@@ -60,9 +63,9 @@ use std::time::duration::Duration;
 use time;
 use url::{Url, UrlParser};
 
-use dom::bindings::codegen::UnionTypes::StringOrURLSearchParams;
-use dom::bindings::codegen::UnionTypes::StringOrURLSearchParams::{eString, eURLSearchParams};
-pub type SendParam = StringOrURLSearchParams;
+use dom::bindings::codegen::UnionTypes::FormDataOrStringOrURLSearchParams;
+use dom::bindings::codegen::UnionTypes::FormDataOrStringOrURLSearchParams::{eFormData, eString, eURLSearchParams};
+pub type SendParam = FormDataOrStringOrURLSearchParams;
 
 #[derive(PartialEq, Copy)]
 #[jstraceable]
@@ -209,10 +212,8 @@ impl XMLHttpRequest {
     }
 
     #[allow(unsafe_blocks)]
-    fn fetch(fetch_type: &SyncOrAsync, resource_task: ResourceTask,
-             mut load_data: LoadData, terminate_receiver: Receiver<TerminateReason>,
-             cors_request: Result<Option<CORSRequest>,()>, gen_id: GenerationId,
-             start_port: Receiver<LoadResponse>) -> ErrorResult {
+    fn fetch(fetch_type: &SyncOrAsync, request: Request,
+             terminate_receiver: Receiver<TerminateReason>, gen_id: GenerationId) -> ErrorResult {
 
         fn notify_partial_progress(fetch_type: &SyncOrAsync, msg: XHRProgress) {
             match *fetch_type {
@@ -245,64 +246,39 @@ impl XMLHttpRequest {
             );
         );
 
-
-        match cors_request {
-            Err(_) => {
-                // Happens in case of cross-origin non-http URIs
-                notify_error_and_return!(Network);
-            }
-
-            Ok(Some(ref req)) => {
-                let (chan, cors_port) = channel();
-                let req2 = req.clone();
-                // TODO: this exists only to make preflight check non-blocking
-                // perhaps should be handled by the resource_loader?
-                spawn_named("XHR:Cors".to_owned(), move || {
-                    let response = req2.http_fetch();
-                    chan.send(response).unwrap();
-                });
-
-                select! (
-                    response = cors_port.recv() => {
-                        let response = response.unwrap();
-                        if response.network_error {
-                            notify_error_and_return!(Network);
-                        } else {
-                            load_data.cors = Some(ResourceCORSData {
-                                preflight: req.preflight_flag,
-                                origin: req.origin.clone()
-                            });
-                        }
-                    },
-                    reason = terminate_receiver.recv() => terminate!(reason.unwrap())
-                );
-            }
-            _ => {}
-        }
-
         // Step 10, 13
-        resource_task.send(Load(load_data)).unwrap();
-
+        // Fetching blocks, so it happens on its own task while this one watches for the
+        // request being aborted or timing out.
+        let (response_chan, response_port) = channel();
+        let (progress_chan, progress_port) = channel();
+        spawn_named("XHR:Fetch".to_owned(), move || {
+            let mut request = request;
+            let mut response = request.fetch_response_head(false);
+            if response_chan.send(response.clone()).is_err() || response.is_network_error() {
+                return;
+            }
+            request.receive_body(&mut response, |chunk| {
+                progress_chan.send(Payload(chunk.to_vec())).is_ok()
+            });
+            let result = match response.termination_reason {
+                Some(_) => Err("the response body was not received".to_owned()),
+                None => Ok(())
+            };
+            let _ = progress_chan.send(Done(result));
+        });
 
-        let progress_port;
         select! (
-            response = start_port.recv() => {
+            response = response_port.recv() => {
                 let response = response.unwrap();
-                match cors_request {
-                    Ok(Some(ref req)) => {
-                        match response.metadata.headers {
-                            Some(ref h) if allow_cross_origin_request(req, h) => {},
-                            _ => notify_error_and_return!(Network)
-                        }
-                    },
-
-                    _ => {}
-                };
+                if response.is_network_error() {
+                    notify_error_and_return!(Network);
+                }
                 // XXXManishearth Clear cache entries in case of a network error
+                let status = response.status.map(|status| {
+                    RawStatus(status as u16, response.status_message.clone().into_cow())
+                });
                 notify_partial_progress(fetch_type, XHRProgress::HeadersReceived(gen_id,
-                    response.metadata.headers.clone(), response.metadata.status.clone()));
-
-                progress_port = response.progress_port;
+                    Some(response.headers.clone()), status));
             },
             reason = terminate_receiver.recv() => terminate!(reason.unwrap())
         );
@@ -392,6 +368,7 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
                 // Step 12
                 *self.request_url.borrow_mut() = Some(parsed_url);
                 *self.request_headers.borrow_mut() = Headers::new();
+                self.response_xml.clear();
                 self.send_flag.set(false);
                 *self.status_text.borrow_mut() = ByteString::new(vec!());
                 self.status.set(0);
@@ -516,7 +493,13 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
             Method::Get | Method::Head => None, // Step 3
             _ => data
         };
-        let extracted = data.as_ref().map(|d| d.extract());
+        let (extracted, content_type) = match data {
+            Some(ref data) => {
+                let (body, content_type) = data.extract();
+                (Some(body), Some(content_type))
+            }
+            None => (None, None)
+        };
         self.request_body_len.set(extracted.as_ref().map(|e| e.len()).unwrap_or(0));
 
         // Step 6
@@ -555,79 +538,49 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
         }
 
         let global = self.global.root();
-        let resource_task = global.r().resource_task();
-        let (start_chan, start_port) = channel();
-        let mut load_data = LoadData::new(self.request_url.borrow().clone().unwrap(), start_chan);
-        load_data.data = extracted;
-
-        #[inline]
-        fn join_raw(a: &str, b: &str) -> Vec<u8> {
-            let len = a.len() + b.len();
-            let mut vec = Vec::with_capacity(len);
-            vec.push_all(a.as_bytes());
-            vec.push_all(b.as_bytes());
-            vec
+        let url = self.request_url.borrow().clone().unwrap();
+        let mut request = Request::new(url, Context::XMLHttpRequest, global.r().resource_task());
+        request.method = (*self.request_method.borrow()).clone();
+        request.headers = (*self.request_headers.borrow()).clone();
+        if let Some(content_type) = content_type {
+            request.headers.set_raw("content-type".to_owned(), vec![content_type.into_bytes()]);
         }
-
-        // XHR spec differs from http, and says UTF-8 should be in capitals,
-        // instead of "utf-8", which is what Hyper defaults to.
-        let params = ";charset=UTF-8";
-        let n = "content-type";
-        match data {
-            Some(eString(_)) =>
-                load_data.headers.set_raw(n.to_owned(), vec![join_raw("text/plain", params)]),
-            Some(eURLSearchParams(_)) =>
-                load_data.headers.set_raw(
-                    n.to_owned(), vec![join_raw("application/x-www-form-urlencoded", params)]),
-            None => ()
-        }
-
-        load_data.preserved_headers = (*self.request_headers.borrow()).clone();
-
-        if !load_data.preserved_headers.has::<Accept>() {
+        if !request.headers.has::<Accept>() {
             let mime = Mime(mime::TopLevel::Star, mime::SubLevel::Star, vec![]);
-            load_data.preserved_headers.set(Accept(vec![QualityItem::new(mime, 1.0)]));
+            request.headers.set(Accept(vec![QualityItem::new(mime, 1.0)]));
         }
+        request.body = extracted;
+        request.unsafe_request = true;
+        let mut referer_url = global.r().get_url();
+        referer_url.fragment = None;
+        request.origin = Some(referer_url.clone());
+        request.client_url = Some(referer_url.clone());
+        request.referer = Referer::RefererUrl(referer_url);
+        request.console = match global.r() {
+            GlobalRef::Window(window) => window.page().console_chan.clone(),
+            GlobalRef::Worker(_) => None,
+        };
+        request.mode = if self.upload_events.get() {
+            RequestMode::ForcedPreflightMode
+        } else {
+            RequestMode::CORSMode
+        };
+        request.credentials_mode = if self.with_credentials.get() {
+            CredentialsMode::Include
+        } else {
+            CredentialsMode::CredentialsSameOrigin
+        };
+        request.use_url_credentials = true;
 
-        load_data.method = (*self.request_method.borrow()).clone();
         let (terminate_sender, terminate_receiver) = channel();
         *self.terminate_sender.borrow_mut() = Some(terminate_sender);
 
-        // CORS stuff
-        let referer_url = self.global.root().r().get_url();
-        let mode = if self.upload_events.get() {
-            RequestMode::ForcedPreflight
-        } else {
-            RequestMode::CORS
-        };
-        let mut combined_headers = load_data.headers.clone();
-        combined_headers.extend(load_data.preserved_headers.iter());
-        let cors_request = CORSRequest::maybe_new(referer_url.clone(), load_data.url.clone(), mode,
-                                                  load_data.method.clone(), combined_headers);
-        match cors_request {
-            Ok(None) => {
-                let mut buf = String::new();
-                buf.push_str(referer_url.scheme.as_slice());
-                buf.push_str("://".as_slice());
-                referer_url.serialize_host().map(|ref h| buf.push_str(h.as_slice()));
-                referer_url.port().as_ref().map(|&p| {
-                    buf.push_str(":".as_slice());
-                    buf.push_str(format!("{}", p).as_slice());
-                });
-                referer_url.serialize_path().map(|ref h| buf.push_str(h.as_slice()));
-                self.request_headers.borrow_mut().set_raw("Referer".to_owned(), vec![buf.into_bytes()]);
-            },
-            Ok(Some(ref req)) => self.insert_trusted_header("origin".to_owned(),
-                                                            format!("{}", req.origin)),
-            _ => {}
-        }
-
-        debug!("request_headers = {:?}", *self.request_headers.borrow());
+        debug!("request_headers = {:?}", request.headers);
 
         let gen_id = self.generation_id.get();
         if self.sync.get() {
-            return XMLHttpRequest::fetch(&mut SyncOrAsync::Sync(self), resource_task, load_data,
-                                         terminate_receiver, cors_request, gen_id, start_port);
+            return XMLHttpRequest::fetch(&mut SyncOrAsync::Sync(self), request,
+                                         terminate_receiver, gen_id);
         } else {
             self.fetch_time.set(time::now().to_timespec().sec);
             let script_chan = global.r().script_chan();
@@ -638,12 +591,9 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
                                     script_chan.clone());
             spawn_named("XHRTask".to_owned(), move || {
                 let _ = XMLHttpRequest::fetch(&mut SyncOrAsync::Async(addr, script_chan),
-                                              resource_task,
-                                              load_data,
+                                              request,
                                               terminate_receiver,
-                                              cors_request,
-                                              gen_id,
-                                              start_port);
+                                              gen_id);
             });
             let timeout = self.timeout.get();
             if timeout > 0 {
@@ -729,6 +679,12 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
                 }
                 vp
             }
+            XMLHttpRequestResponseType::Document => {
+                match self.document_response() {
+                    Some(document) => document.root().to_jsval(cx),
+                    None => NullValue(),
+                }
+            }
             _ => {
                 // XXXManishearth handle other response types
                 self.response.borrow().to_jsval(cx)
@@ -746,8 +702,16 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
             _ => Err(InvalidState)
         }
     }
-    fn GetResponseXML(self) -> Option<Temporary<Document>> {
-        self.response_xml.get()
+    // http://xhr.spec.whatwg.org/#the-responsexml-attribute
+    fn GetResponseXML(self) -> Fallible<Option<Temporary<Document>>> {
+        match self.response_type.get() {
+            _empty | XMLHttpRequestResponseType::Document => {}
+            _ => return Err(InvalidState),
+        }
+        if self.ready_state.get() != XMLHttpRequestState::XHRDone {
+            return Ok(None);
+        }
+        Ok(self.document_response())
     }
 }
 
@@ -767,7 +731,6 @@ trait PrivateXMLHttpRequestHelpers {
     fn change_ready_state(self, XMLHttpRequestState);
     fn process_partial_response(self, progress: XHRProgress);
     fn terminate_ongoing_fetch(self);
-    fn insert_trusted_header(self, name: String, value: String);
     fn dispatch_progress_event(self, upload: bool, type_: DOMString, loaded: u64, total: Option<u64>);
     fn dispatch_upload_progress_event(self, type_: DOMString, partial_load: Option<u64>);
     fn dispatch_response_progress_event(self, type_: DOMString);
@@ -775,6 +738,7 @@ trait PrivateXMLHttpRequestHelpers {
     fn set_timeout(self, timeout:u32);
     fn cancel_timeout(self);
     fn filter_response_headers(self) -> Headers;
+    fn document_response(self) -> Option<Temporary<Document>>;
 }
 
 impl<'a> PrivateXMLHttpRequestHelpers for JSRef<'a, XMLHttpRequest> {
@@ -908,12 +872,6 @@ impl<'a> PrivateXMLHttpRequestHelpers for JSRef<'a, XMLHttpRequest> {
         self.terminate_sender.borrow().as_ref().map(|s| s.send(TerminateReason::AbortedOrReopened));
     }
 
-    fn insert_trusted_header(self, name: String, value: String) {
-        // Insert a header without checking spec-compliance
-        // Use for hardcoded headers
-        self.request_headers.borrow_mut().set_raw(name, vec![value.into_bytes()]);
-    }
-
     fn dispatch_progress_event(self, upload: bool, type_: DOMString, loaded: u64, total: Option<u64>) {
         let global = self.global.root();
         let upload_target = self.upload.root();
@@ -1015,18 +973,74 @@ impl<'a> PrivateXMLHttpRequestHelpers for JSRef<'a, XMLHttpRequest> {
         // XXXManishearth additional CORS filtering goes here
         headers
     }
+
+    // http://xhr.spec.whatwg.org/#document-response
+    fn document_response(self) -> Option<Temporary<Document>> {
+        if let Some(document) = self.response_xml.get() {
+            return Some(document);
+        }
+        let window = match self.global.root() {
+            GlobalRoot::Window(window) => window,
+            GlobalRoot::Worker(_) => return None,
+        };
+        // A response without a Content-Type is treated as text/xml.
+        let (top_level, sub_level, charset) = match self.response_headers.borrow().get() {
+            Some(&ContentType(mime::Mime(ref top_level, ref sub_level, ref params))) => {
+                let charset = params.iter().find(|&&(ref name, _)| *name == mime::Attr::Charset)
+                                    .map(|&(_, ref value)| value.to_string());
+                (top_level.to_string(), sub_level.to_string(), charset)
+            }
+            None => ("text".to_owned(), "xml".to_owned(), None),
+        };
+        let content_type = format!("{}/{}", top_level, sub_level);
+        let url = self.request_url.borrow().clone().unwrap();
+
+        let is_html = content_type.as_slice().eq_ignore_ascii_case("text/html");
+        let document = if is_html && self.response_type.get() == XMLHttpRequestResponseType::Document {
+            let document = Document::new(window.r(), Some(url.clone()),
+                                         IsHTMLDocument::HTMLDocument,
+                                         Some(content_type),
+                                         DocumentSource::FromParser).root();
+            parse_html(document.r(), HTMLInput::InputString(self.text_response()), &url);
+            document
+        } else if is_xml_mime_type(top_level.as_slice(), sub_level.as_slice()) {
+            let document = Document::new(window.r(), Some(url.clone()),
+                                         IsHTMLDocument::NonHTMLDocument,
+                                         Some(content_type),
+                                         DocumentSource::FromParser).root();
+            let text = decode_xml(self.response.borrow().as_slice(), charset);
+            parse_xml(document.r(), XMLInput::InputString(text), &url, XMLScripting::Disabled);
+            document
+        } else {
+            return None;
+        };
+        document.r().set_ready_state(DocumentReadyState::Complete);
+        self.response_xml.assign(Some(document.r()));
+        Some(Temporary::from_rooted(document.r()))
+    }
 }
 
 trait Extractable {
-    fn extract(&self) -> Vec<u8>;
+    /// Returns the body and its content type.
+    fn extract(&self) -> (Vec<u8>, String);
 }
 impl Extractable for SendParam {
-    fn extract(&self) -> Vec<u8> {
+    fn extract(&self) -> (Vec<u8>, String) {
         // http://fetch.spec.whatwg.org/#concept-fetchbodyinit-extract
+        // XHR spec differs from http, and says UTF-8 should be in capitals,
+        // instead of "utf-8", which is what Hyper defaults to.
         let encoding = UTF_8 as EncodingRef;
         match *self {
-            eString(ref s) => encoding.encode(s.as_slice(), EncoderTrap::Replace).unwrap(),
-            eURLSearchParams(ref usp) => usp.root().r().serialize(None) // Default encoding is UTF8
+            eString(ref s) => (encoding.encode(s.as_slice(), EncoderTrap::Replace).unwrap(),
+                               "text/plain;charset=UTF-8".to_owned()),
+            eURLSearchParams(ref usp) => (usp.root().r().serialize(None), // Default encoding is UTF8
+                                          "application/x-www-form-urlencoded;charset=UTF-8".to_owned()),
+            eFormData(ref form_data) => {
+                let boundary = generate_boundary();
+                let form_data = form_data.root().r().form_data_set();
+                (encode_multipart_form_data(form_data.as_slice(), boundary.as_slice()),
+                 format!("multipart/form-data;boundary={}", boundary))
+            }
         }
     }
 }
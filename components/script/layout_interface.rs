@@ -10,12 +10,17 @@ use dom::node::LayoutDataRef;
 
 use geom::point::Point2D;
 use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::font_cache_task::FontCacheTask;
 use script_traits::{ScriptControlChan, OpaqueScriptLayoutChannel, UntrustedNodeAddress};
 use msg::constellation_msg::{PipelineExitType, WindowSizeData};
 use util::geometry::Au;
 use std::any::Any;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::boxed::BoxAny;
+use std::sync::Arc;
+use selectors::parser::PseudoElement;
+use style::properties::ComputedValues;
 use style::stylesheets::Stylesheet;
 use url::Url;
 
@@ -23,11 +28,13 @@ pub use dom::node::TrustedNodeAddress;
 
 /// Asynchronous messages that script can send to layout.
 pub enum Msg {
-    /// Adds the given stylesheet to the document.
-    AddStylesheet(Stylesheet),
+    /// Adds the given stylesheet to the document at the given URL, reporting blocked loads
+    /// to the document's console.
+    AddStylesheet(Stylesheet, Url, Option<Sender<String>>),
 
-    /// Adds the given stylesheet to the document.
-    LoadStylesheet(Url),
+    /// Loads the stylesheet at the first URL and adds it to the document at the second one,
+    /// reporting blocked loads to the document's console.
+    LoadStylesheet(Url, Url, Option<Sender<String>>),
 
     /// Puts a document into quirks mode, causing the quirks mode stylesheet to be loaded.
     SetQuirksMode,
@@ -38,6 +45,9 @@ pub enum Msg {
     /// Get an RPC interface.
     GetRPC(Sender<Box<LayoutRPC + Send>>),
 
+    /// Get the font cache task, for text drawn outside of layout such as canvas text.
+    GetFontCacheTask(Sender<FontCacheTask>),
+
     /// Destroys layout data associated with a DOM node.
     ///
     /// TODO(pcwalton): Maybe think about batching to avoid message traffic.
@@ -66,6 +76,9 @@ pub trait LayoutRPC {
     fn content_box(&self) -> ContentBoxResponse;
     /// Requests the dimensions of all the content boxes, as in the `getClientRects()` call.
     fn content_boxes(&self) -> ContentBoxesResponse;
+    /// Requests the computed style of a node or one of its pseudo-elements, together with the
+    /// used size of its content box, as in the `getComputedStyle()` call.
+    fn computed_style(&self) -> ComputedStyleResponse;
     /// Requests the node containing the point of interest
     fn hit_test(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<HitTestResponse, ()>;
     fn mouse_over(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<MouseOverResponse, ()>;
@@ -73,6 +86,9 @@ pub trait LayoutRPC {
 
 pub struct ContentBoxResponse(pub Rect<Au>);
 pub struct ContentBoxesResponse(pub Vec<Rect<Au>>);
+/// The computed style, if the node has been styled, and the used content box size, if the node
+/// or pseudo-element generated any boxes.
+pub struct ComputedStyleResponse(pub Option<Arc<ComputedValues>>, pub Option<Size2D<Au>>);
 pub struct HitTestResponse(pub UntrustedNodeAddress);
 pub struct MouseOverResponse(pub Vec<UntrustedNodeAddress>);
 
@@ -90,6 +106,7 @@ pub enum ReflowQueryType {
     NoQuery,
     ContentBoxQuery(TrustedNodeAddress),
     ContentBoxesQuery(TrustedNodeAddress),
+    ComputedStyleQuery(TrustedNodeAddress, Option<PseudoElement>),
 }
 
 /// Information needed for a reflow.
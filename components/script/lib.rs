@@ -30,13 +30,16 @@ extern crate devtools_traits;
 extern crate cssparser;
 extern crate collections;
 extern crate geom;
+extern crate gfx;
 extern crate html5ever;
 extern crate encoding;
 extern crate hyper;
 extern crate js;
+#[macro_use] extern crate lazy_static;
 extern crate libc;
 extern crate msg;
 extern crate net;
+extern crate png;
 extern crate "rustc-serialize" as rustc_serialize;
 extern crate time;
 extern crate canvas;
@@ -53,8 +56,6 @@ extern crate string_cache;
 #[no_link] #[macro_use] #[plugin]
 extern crate string_cache_macros;
 
-pub mod cors;
-
 #[macro_use]
 pub mod dom;
 
@@ -11,9 +11,9 @@ use dom::document::{Document, DocumentHelpers};
 use dom::element::Element;
 use dom::node::{Node, NodeHelpers};
 use dom::window::Window;
-use devtools_traits::DevtoolsControlChan;
+use devtools_traits::{ConsoleMessage, DevtoolsControlChan, DevtoolsControlMsg};
 use layout_interface::{
-    ContentBoxResponse, ContentBoxesResponse,
+    ComputedStyleResponse, ContentBoxResponse, ContentBoxesResponse,
     HitTestResponse, LayoutChan, LayoutRPC, MouseOverResponse, Msg, Reflow,
     ReflowGoal, ReflowQueryType,
     TrustedNodeAddress
@@ -21,18 +21,22 @@ use layout_interface::{
 use script_traits::{UntrustedNodeAddress, ScriptControlChan};
 
 use geom::{Point2D, Rect, Size2D};
+use gfx::font_cache_task::FontCacheTask;
 use js::rust::Cx;
 use msg::compositor_msg::ScriptListener;
 use msg::constellation_msg::{ConstellationChan, WindowSizeData};
-use msg::constellation_msg::{PipelineId, SubpageId};
+use msg::constellation_msg::{PipelineId, SubpageId, TopLevelBrowsingContextId};
 use net::resource_task::ResourceTask;
 use net::storage_task::StorageTask;
+use selectors::parser::PseudoElement;
 use util::geometry::{Au, MAX_RECT};
 use util::geometry;
 use util::str::DOMString;
 use util::smallvec::SmallVec;
+use util::task::spawn_named;
+use std::borrow::ToOwned;
 use std::cell::{Cell, Ref, RefMut};
-use std::sync::mpsc::{channel, Receiver};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::mpsc::TryRecvError::{Empty, Disconnected};
 use std::mem::replace;
 use std::num::Float;
@@ -48,6 +52,9 @@ pub struct Page {
     /// Subpage id associated with this page, if any.
     pub subpage_id: Option<SubpageId>,
 
+    /// The top-level browsing context this page belongs to.
+    pub top_level_browsing_context_id: TopLevelBrowsingContextId,
+
     /// Unique id for last reflow request; used for confirming completion reply.
     pub last_reflow_id: Cell<uint>,
 
@@ -60,6 +67,9 @@ pub struct Page {
     /// A handle to perform RPC calls into the layout, quickly.
     layout_rpc: Box<LayoutRPC+'static>,
 
+    /// The font cache task of the layout task, for drawing text outside of layout.
+    pub font_cache_task: FontCacheTask,
+
     /// The port that we will use to join layout. If this is `None`, then layout is not running.
     pub layout_join_port: DOMRefCell<Option<Receiver<()>>>,
 
@@ -104,6 +114,16 @@ pub struct Page {
 
     /// For providing instructions to an optional devtools server.
     pub devtools_chan: Option<DevtoolsControlChan>,
+
+    /// For other tasks, such as the resource task, to log messages to the devtools console.
+    pub console_chan: Option<Sender<String>>,
+
+    /// The index of the current entry of the session history, as last reported by the
+    /// constellation or changed by this page.
+    pub session_history_index: Cell<u32>,
+
+    /// The number of entries in the session history, kept like `session_history_index`.
+    pub session_history_length: Cell<u32>,
 }
 
 pub struct PageIterator {
@@ -134,6 +154,7 @@ impl IterablePage for Rc<Page> {
 
 impl Page {
     pub fn new(id: PipelineId, subpage_id: Option<SubpageId>,
+               top_level_browsing_context_id: TopLevelBrowsingContextId,
                layout_chan: LayoutChan,
                window_size: WindowSizeData,
                resource_task: ResourceTask,
@@ -151,12 +172,35 @@ impl Page {
             lchan.send(Msg::GetRPC(rpc_send)).unwrap();
             rpc_recv.recv().unwrap()
         };
+        let font_cache_task = {
+            let (font_cache_send, font_cache_recv) = channel();
+            let LayoutChan(ref lchan) = layout_chan;
+            lchan.send(Msg::GetFontCacheTask(font_cache_send)).unwrap();
+            font_cache_recv.recv().unwrap()
+        };
+        // Forward the messages of other tasks to the devtools console of this page.
+        let console_chan = devtools_chan.as_ref().map(|devtools_chan| {
+            let (console_chan, console_port) = channel();
+            let devtools_chan = devtools_chan.clone();
+            spawn_named("Console forwarder".to_owned(), move || {
+                for message in console_port.iter() {
+                    let msg = DevtoolsControlMsg::SendConsoleMessage(id,
+                                                                     ConsoleMessage::LogMessage(message));
+                    if devtools_chan.send(msg).is_err() {
+                        break;
+                    }
+                }
+            });
+            console_chan
+        });
         Page {
             id: id,
             subpage_id: subpage_id,
+            top_level_browsing_context_id: top_level_browsing_context_id,
             frame: DOMRefCell::new(None),
             layout_chan: layout_chan,
             layout_rpc: layout_rpc,
+            font_cache_task: font_cache_task,
             layout_join_port: DOMRefCell::new(None),
             window_size: Cell::new(window_size),
             js_info: DOMRefCell::new(Some(js_info)),
@@ -172,9 +216,20 @@ impl Page {
             page_clip_rect: Cell::new(MAX_RECT),
             devtools_wants_updates: Cell::new(false),
             devtools_chan: devtools_chan,
+            console_chan: console_chan,
+            session_history_index: Cell::new(0),
+            session_history_length: Cell::new(1),
         }
     }
 
+    /// Accounts for an entry this page added to the session history after the current one,
+    /// which drops the entries after it.
+    pub fn push_session_history_entry(&self) {
+        let index = self.session_history_index.get() + 1;
+        self.session_history_index.set(index);
+        self.session_history_length.set(index + 1);
+    }
+
     pub fn flush_layout(&self, goal: ReflowGoal, query: ReflowQueryType) {
         let frame = self.frame();
         let window = frame.as_ref().unwrap().window.root();
@@ -199,6 +254,14 @@ impl Page {
         rects
     }
 
+    pub fn computed_style_query(&self, computed_style_request: TrustedNodeAddress,
+                                pseudo_element: Option<PseudoElement>) -> ComputedStyleResponse {
+        self.flush_layout(ReflowGoal::ForScriptQuery,
+                          ReflowQueryType::ComputedStyleQuery(computed_style_request, pseudo_element));
+        self.join_layout(); //FIXME: is this necessary, or is layout_rpc's mutex good enough?
+        self.layout_rpc.computed_style()
+    }
+
     // must handle root case separately
     pub fn remove(&self, id: PipelineId) -> Option<Rc<Page>> {
         let remove_idx = {
@@ -6,17 +6,19 @@
 
 use dom::attr::AttrHelpers;
 use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
-use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast, HTMLScriptElementCast};
+use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast, HTMLFormElementCast};
+use dom::bindings::codegen::InheritTypes::HTMLScriptElementCast;
 use dom::bindings::js::{JS, JSRef, Temporary, OptionalRootable, Root};
 use dom::comment::Comment;
-use dom::document::{Document, DocumentHelpers};
+use dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
+use dom::document::{Document, DocumentHelpers, DocumentSource, IsHTMLDocument};
 use dom::documenttype::DocumentType;
 use dom::element::{Element, AttributeHandlers, ElementHelpers, ElementCreator};
 use dom::htmlscriptelement::HTMLScriptElement;
 use dom::htmlscriptelement::HTMLScriptElementHelpers;
-use dom::node::{Node, NodeHelpers};
+use dom::node::{Node, NodeHelpers, document_from_node};
 use dom::servohtmlparser;
-use dom::servohtmlparser::ServoHTMLParser;
+use dom::servohtmlparser::{FragmentContext, ServoHTMLParser};
 use dom::text::Text;
 use parse::Parser;
 
@@ -24,6 +26,7 @@ use encoding::all::UTF_8;
 use encoding::types::{Encoding, DecoderTrap};
 
 use net::resource_task::{ProgressMsg, LoadResponse};
+use util::str::DOMString;
 use util::task_state;
 use util::task_state::IN_HTML_PARSER;
 use std::ascii::AsciiExt;
@@ -161,7 +164,11 @@ impl<'a> TreeSink for servohtmlparser::Sink {
     fn complete_script(&mut self, node: JS<Node>) {
         let node: Root<Node> = node.root();
         let script: Option<JSRef<HTMLScriptElement>> = HTMLScriptElementCast::to_ref(node.r());
-        script.map(|script| script.prepare());
+        if self.scripting_enabled {
+            script.map(|script| script.prepare());
+        } else {
+            script.map(|script| script.mark_already_started());
+        }
     }
 
     fn reparent_children(&mut self, _node: JS<Node>, _new_parent: JS<Node>) {
@@ -217,3 +224,48 @@ pub fn parse_html(document: JSRef<Document>,
 
     debug!("finished parsing");
 }
+
+/// Parse `input` as an HTML fragment in the context of `context`, returning
+/// the resulting nodes, which belong to a new inert document.
+/// http://html.spec.whatwg.org/multipage/syntax.html#parsing-html-fragments
+pub fn parse_html_fragment(context: JSRef<Element>, input: DOMString) -> Vec<Temporary<Node>> {
+    let context_node: JSRef<Node> = NodeCast::from_ref(context);
+    let context_document = document_from_node(context_node).root();
+    let window = context_document.r().window().root();
+    let url = context_document.r().url();
+
+    // Step 1.
+    let document = Document::new(window.r(), Some(url.clone()),
+                                 IsHTMLDocument::HTMLDocument, None,
+                                 DocumentSource::FromParser).root();
+
+    // Step 2.
+    document.r().set_quirks_mode(context_document.r().quirks_mode());
+
+    // Step 11.
+    let form = context_node.inclusive_ancestors()
+                           .find(|element| HTMLFormElementCast::to_ref(*element).is_some());
+    let fragment_context = FragmentContext {
+        context_elem: context_node,
+        form_elem: form,
+    };
+
+    // Step 3-10, 12-13.
+    let parser = ServoHTMLParser::new_for_fragment(Some(url), document.r(), fragment_context).root();
+    let parser: JSRef<ServoHTMLParser> = parser.r();
+
+    let nested_parse = task_state::get().contains(task_state::IN_HTML_PARSER);
+    if !nested_parse {
+        task_state::enter(IN_HTML_PARSER);
+    }
+    parser.parse_chunk(input);
+    parser.finish();
+    if !nested_parse {
+        task_state::exit(IN_HTML_PARSER);
+    }
+
+    // Step 14.
+    let root_element = document.r().GetDocumentElement().expect("no document element").root();
+    let root_node: JSRef<Node> = NodeCast::from_ref(root_element.r());
+    root_node.children().map(|child| Temporary::from_rooted(child)).collect()
+}
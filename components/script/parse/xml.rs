@@ -0,0 +1,744 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A namespace-aware, non-validating XML parser, following http://www.w3.org/TR/xml/ and
+//! http://www.w3.org/TR/xml-names/
+//!
+//! The whole input is parsed before any node is created, so that a document that isn't
+//! well-formed can be given a `parsererror` element instead of a partial tree.
+
+#![allow(unrooted_must_root)]
+
+use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
+use dom::bindings::codegen::InheritTypes::{NodeCast, HTMLScriptElementCast};
+use dom::bindings::js::{JS, JSRef, Root, Temporary};
+use dom::bindings::utils::{xml_name_type, XMLName};
+use dom::comment::Comment;
+use dom::document::Document;
+use dom::documenttype::DocumentType;
+use dom::element::{Element, AttributeHandlers, ElementCreator};
+use dom::htmlscriptelement::{HTMLScriptElement, HTMLScriptElementHelpers};
+use dom::node::Node;
+use dom::processinginstruction::ProcessingInstruction;
+use dom::text::Text;
+
+use encoding::all::{UTF_8, UTF_16BE, UTF_16LE};
+use encoding::label::encoding_from_whatwg_label;
+use encoding::types::{DecoderTrap, EncodingRef};
+
+use net::resource_task::{LoadResponse, ProgressMsg};
+use string_cache::{Atom, Namespace, QualName};
+use url::Url;
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::char;
+
+/// The namespace of the element that replaces the contents of a document that isn't
+/// well-formed, as used by Gecko.
+static PARSERERROR_NAMESPACE: &'static str = "http://www.mozilla.org/newlayout/xml/parsererror.xml";
+
+pub enum XMLInput {
+    InputString(String),
+    InputUrl(LoadResponse),
+}
+
+/// Whether the scripts of a parsed document run. They only do in a browsing context; documents
+/// from `DOMParser` and `XMLHttpRequest` are parsed with scripting disabled.
+#[derive(PartialEq, Copy)]
+pub enum XMLScripting {
+    Enabled,
+    Disabled,
+}
+
+struct XMLElement {
+    name: QualName,
+    prefix: Option<String>,
+    attrs: Vec<(QualName, Option<String>, String)>,
+}
+
+/// The parsed document is a flat list of nodes in document order, so that neither parsing it
+/// nor building the tree from it recurses for each level of nesting.
+enum XMLNode {
+    /// The start of an element. Its children follow, up to the matching `EndElement`.
+    StartElement(XMLElement),
+    EndElement,
+    Text(String),
+    Comment(String),
+    ProcessingInstruction(String, String),
+    /// The name, public id and system id of a document type declaration.
+    Doctype(String, String, String),
+}
+
+type ParseResult<T> = Result<T, String>;
+
+/// What ended a run of element content.
+#[derive(PartialEq, Copy)]
+enum ContentEnd {
+    /// The `<` of a start tag.
+    StartTag,
+    /// The `</` of an end tag.
+    EndTag,
+}
+
+/// http://www.w3.org/TR/xml/#NT-Char
+fn is_xml_char(c: char) -> bool {
+    match c {
+        '\t' | '\n' | '\r' |
+        '\u{20}'...'\u{D7FF}' |
+        '\u{E000}'...'\u{FFFD}' |
+        '\u{10000}'...'\u{10FFFF}' => true,
+        _ => false,
+    }
+}
+
+fn is_xml_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r'
+}
+
+/// Whether `c` can't be part of a name, which is checked more strictly by `xml_name_type`.
+fn ends_name(c: char) -> bool {
+    is_xml_whitespace(c) || "/>=<?&;'\"[]!%()|,".contains_char(c)
+}
+
+/// Splits a qualified name into its prefix and local name.
+fn split_qname(name: &str) -> (Option<&str>, &str) {
+    match name.find(':') {
+        Some(i) => (Some(&name[..i]), &name[i + 1..]),
+        None => (None, name),
+    }
+}
+
+struct XMLParser {
+    input: Vec<char>,
+    pos: uint,
+    /// The namespace declarations in scope, innermost last, as prefix and namespace pairs. The
+    /// default namespace has an empty prefix, and is undeclared by an empty namespace.
+    namespaces: Vec<(String, String)>,
+}
+
+impl XMLParser {
+    fn new(input: &str) -> XMLParser {
+        // http://www.w3.org/TR/xml/#sec-line-ends
+        let input = input.replace("\r\n", "\n").replace("\r", "\n");
+        XMLParser {
+            input: input.as_slice().chars().collect(),
+            pos: 0,
+            namespaces: vec!(("xml".to_owned(), ns!(XML).0.as_slice().to_owned()),
+                             ("xmlns".to_owned(), ns!(XMLNS).0.as_slice().to_owned())),
+        }
+    }
+
+    /// An error message, with the position the parser stopped at.
+    fn error(&self, message: &str) -> String {
+        let consumed = &self.input[..self.pos];
+        let line = consumed.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = self.pos - consumed.iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1) + 1;
+        format!("{} (line {}, column {})", message, line, column)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.pos).map(|&c| c)
+    }
+
+    fn next_char(&mut self) -> ParseResult<char> {
+        match self.peek() {
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+            None => Err(self.error("unexpected end of document")),
+        }
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        let mut pos = self.pos;
+        for c in s.chars() {
+            if self.input.get(pos) != Some(&c) {
+                return false;
+            }
+            pos += 1;
+        }
+        true
+    }
+
+    fn consume(&mut self, s: &str) -> bool {
+        if self.starts_with(s) {
+            self.pos += s.chars().count();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> ParseResult<()> {
+        if self.consume(s) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected \"{}\"", s).as_slice()))
+        }
+    }
+
+    /// Skips whitespace, and returns whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().map_or(false, is_xml_whitespace) {
+            self.pos += 1;
+        }
+        self.pos != start
+    }
+
+    fn expect_whitespace(&mut self) -> ParseResult<()> {
+        if self.skip_whitespace() {
+            Ok(())
+        } else {
+            Err(self.error("expected whitespace"))
+        }
+    }
+
+    /// Consumes everything up to `end`, and `end` itself.
+    fn consume_until(&mut self, end: &str) -> ParseResult<String> {
+        let mut text = String::new();
+        while !self.consume(end) {
+            text.push(try!(self.next_char()));
+        }
+        Ok(text)
+    }
+
+    fn parse_name(&mut self) -> ParseResult<String> {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if ends_name(c) {
+                break;
+            }
+            name.push(c);
+            self.pos += 1;
+        }
+        if name.is_empty() || xml_name_type(name.as_slice()) == XMLName::InvalidXMLName {
+            return Err(self.error("invalid name"));
+        }
+        Ok(name)
+    }
+
+    /// A name that is valid with namespaces, which is one with at most one colon, not at either end.
+    fn parse_qname(&mut self) -> ParseResult<String> {
+        let name = try!(self.parse_name());
+        if xml_name_type(name.as_slice()) != XMLName::QName ||
+                split_qname(name.as_slice()).1.is_empty() {
+            return Err(self.error(format!("\"{}\" isn't a valid qualified name", name).as_slice()));
+        }
+        Ok(name)
+    }
+
+    fn parse_quoted(&mut self) -> ParseResult<String> {
+        let quote = try!(self.next_char());
+        if quote != '"' && quote != '\'' {
+            return Err(self.error("expected a quoted string"));
+        }
+        let mut value = String::new();
+        loop {
+            match try!(self.next_char()) {
+                c if c == quote => return Ok(value),
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// http://www.w3.org/TR/xml/#NT-Reference, after the `&`.
+    fn parse_reference(&mut self) -> ParseResult<String> {
+        if self.consume("#") {
+            let radix = if self.consume("x") { 16 } else { 10 };
+            let mut value = None;
+            while let Some(digit) = self.peek().and_then(|c| c.to_digit(radix)) {
+                self.pos += 1;
+                value = Some(value.unwrap_or(0u32).saturating_mul(radix as u32)
+                                  .saturating_add(digit as u32));
+            }
+            try!(self.expect(";"));
+            return match value.and_then(char::from_u32) {
+                Some(c) if is_xml_char(c) => Ok(c.to_string()),
+                _ => Err(self.error("invalid character reference")),
+            };
+        }
+        let name = try!(self.parse_name());
+        try!(self.expect(";"));
+        match name.as_slice() {
+            "amp" => Ok("&".to_owned()),
+            "lt" => Ok("<".to_owned()),
+            "gt" => Ok(">".to_owned()),
+            "quot" => Ok("\"".to_owned()),
+            "apos" => Ok("'".to_owned()),
+            _ => Err(self.error(format!("undefined entity \"{}\"", name).as_slice())),
+        }
+    }
+
+    /// http://www.w3.org/TR/xml/#NT-AttValue, with the normalization of
+    /// http://www.w3.org/TR/xml/#AVNormalize for attributes declared as CDATA.
+    fn parse_attribute_value(&mut self) -> ParseResult<String> {
+        let quote = try!(self.next_char());
+        if quote != '"' && quote != '\'' {
+            return Err(self.error("expected a quoted attribute value"));
+        }
+        let mut value = String::new();
+        loop {
+            match try!(self.next_char()) {
+                c if c == quote => return Ok(value),
+                '<' => return Err(self.error("\"<\" in an attribute value")),
+                '&' => value.push_str(try!(self.parse_reference()).as_slice()),
+                c if is_xml_whitespace(c) => value.push(' '),
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// After the `<!--`.
+    fn parse_comment(&mut self) -> ParseResult<XMLNode> {
+        let text = try!(self.consume_until("--"));
+        if !self.consume(">") {
+            return Err(self.error("\"--\" in a comment"));
+        }
+        Ok(XMLNode::Comment(text))
+    }
+
+    /// After the `<?`.
+    fn parse_processing_instruction(&mut self) -> ParseResult<XMLNode> {
+        let target = try!(self.parse_name());
+        if target.as_slice().eq_ignore_ascii_case("xml") {
+            return Err(self.error("the XML declaration is only allowed at the start of the document"));
+        }
+        if target.as_slice().contains_char(':') {
+            return Err(self.error("processing instruction targets can't contain a colon"));
+        }
+        if self.consume("?>") {
+            return Ok(XMLNode::ProcessingInstruction(target, String::new()));
+        }
+        try!(self.expect_whitespace());
+        let data = try!(self.consume_until("?>"));
+        Ok(XMLNode::ProcessingInstruction(target, data))
+    }
+
+    /// After the `<!DOCTYPE`. The internal subset is skipped, so the entities it declares are
+    /// undefined.
+    fn parse_doctype(&mut self) -> ParseResult<XMLNode> {
+        try!(self.expect_whitespace());
+        let name = try!(self.parse_qname());
+        self.skip_whitespace();
+        let (public_id, system_id) = if self.consume("PUBLIC") {
+            try!(self.expect_whitespace());
+            let public_id = try!(self.parse_quoted());
+            try!(self.expect_whitespace());
+            (public_id, try!(self.parse_quoted()))
+        } else if self.consume("SYSTEM") {
+            try!(self.expect_whitespace());
+            (String::new(), try!(self.parse_quoted()))
+        } else {
+            (String::new(), String::new())
+        };
+        self.skip_whitespace();
+        if self.consume("[") {
+            loop {
+                if self.consume("]") {
+                    break;
+                } else if self.consume("<!--") {
+                    try!(self.parse_comment());
+                } else if self.peek() == Some('"') || self.peek() == Some('\'') {
+                    try!(self.parse_quoted());
+                } else {
+                    try!(self.next_char());
+                }
+            }
+            self.skip_whitespace();
+        }
+        try!(self.expect(">"));
+        Ok(XMLNode::Doctype(name, public_id, system_id))
+    }
+
+    /// http://www.w3.org/TR/xml/#NT-XMLDecl, after the `<?xml`.
+    fn parse_xml_declaration(&mut self) -> ParseResult<()> {
+        try!(self.expect_whitespace());
+        if !self.consume("version") {
+            return Err(self.error("the XML declaration must have a version"));
+        }
+        let declaration = try!(self.consume_until("?>"));
+        if declaration.as_slice().contains_char('<') {
+            return Err(self.error("unterminated XML declaration"));
+        }
+        Ok(())
+    }
+
+    fn lookup_namespace(&self, prefix: &str) -> Option<&str> {
+        self.namespaces.iter().rev()
+            .find(|&&(ref declared, _)| declared.as_slice() == prefix)
+            .map(|&(_, ref namespace)| namespace.as_slice())
+    }
+
+    /// After the `<`. Returns the qualified name of the element, the element and whether it is
+    /// an empty-element tag. The namespace declarations of the element are left in scope.
+    fn parse_start_tag(&mut self) -> ParseResult<(String, XMLElement, bool)> {
+        let name = try!(self.parse_qname());
+        let mut raw_attrs: Vec<(String, String)> = vec!();
+        let empty;
+        loop {
+            let had_whitespace = self.skip_whitespace();
+            if self.consume("/>") {
+                empty = true;
+                break;
+            }
+            if self.consume(">") {
+                empty = false;
+                break;
+            }
+            if !had_whitespace {
+                return Err(self.error("expected whitespace between attributes"));
+            }
+            let attr_name = try!(self.parse_qname());
+            self.skip_whitespace();
+            try!(self.expect("="));
+            self.skip_whitespace();
+            let value = try!(self.parse_attribute_value());
+            if raw_attrs.iter().any(|&(ref existing, _)| *existing == attr_name) {
+                return Err(self.error(format!("duplicate attribute \"{}\"", attr_name).as_slice()));
+            }
+            raw_attrs.push((attr_name, value));
+        }
+
+        // http://www.w3.org/TR/xml-names/#ns-decl
+        for &(ref attr_name, ref value) in raw_attrs.iter() {
+            let prefix = match split_qname(attr_name.as_slice()) {
+                (None, "xmlns") => "",
+                (Some("xmlns"), prefix) => {
+                    if value.is_empty() {
+                        return Err(self.error("a prefix can't be bound to the empty namespace"));
+                    }
+                    prefix
+                }
+                _ => continue,
+            };
+            let is_xml = value.as_slice() == ns!(XML).0.as_slice();
+            if prefix == "xmlns" || value.as_slice() == ns!(XMLNS).0.as_slice() ||
+                    (prefix == "xml") != is_xml {
+                return Err(self.error("reserved prefix or namespace"));
+            }
+            self.namespaces.push((prefix.to_owned(), value.clone()));
+        }
+
+        let element_name = {
+            let (prefix, local) = split_qname(name.as_slice());
+            let namespace = match self.lookup_namespace(prefix.unwrap_or("")) {
+                Some(namespace) => namespace.to_owned(),
+                None if prefix.is_none() => String::new(),
+                None => return Err(self.error(format!("undeclared prefix in \"{}\"", name).as_slice())),
+            };
+            (prefix.map(|prefix| prefix.to_owned()), local.to_owned(), namespace)
+        };
+
+        let mut attrs = vec!();
+        for (attr_name, value) in raw_attrs.into_iter() {
+            let (prefix, local, namespace) = {
+                let (prefix, local) = split_qname(attr_name.as_slice());
+                let namespace = match prefix {
+                    // Unprefixed attributes are in no namespace, except for `xmlns` itself.
+                    None if local == "xmlns" => ns!(XMLNS).0.as_slice().to_owned(),
+                    None => String::new(),
+                    Some(prefix) => match self.lookup_namespace(prefix) {
+                        Some(namespace) => namespace.to_owned(),
+                        None => return Err(self.error(format!("undeclared prefix in \"{}\"",
+                                                              attr_name).as_slice())),
+                    },
+                };
+                (prefix.map(|prefix| prefix.to_owned()), local.to_owned(), namespace)
+            };
+            if attrs.iter().any(|&(ref existing, _, _): &(QualName, Option<String>, String)| {
+                existing.ns.0.as_slice() == namespace.as_slice() &&
+                    existing.local.as_slice() == local.as_slice()
+            }) {
+                return Err(self.error(format!("duplicate attribute \"{}\"", attr_name).as_slice()));
+            }
+            let qname = QualName::new(Namespace(Atom::from_slice(namespace.as_slice())),
+                                      Atom::from_slice(local.as_slice()));
+            attrs.push((qname, prefix, value));
+        }
+
+        let (prefix, local, namespace) = element_name;
+        let element = XMLElement {
+            name: QualName::new(Namespace(Atom::from_slice(namespace.as_slice())),
+                                Atom::from_slice(local.as_slice())),
+            prefix: prefix,
+            attrs: attrs,
+        };
+        Ok((name, element, empty))
+    }
+
+    /// After the `<`. The element and its descendants are added to `nodes`.
+    fn parse_element(&mut self, nodes: &mut Vec<XMLNode>) -> ParseResult<()> {
+        // The qualified names of the open elements, innermost last, with where their namespace
+        // declarations start in `self.namespaces`.
+        let mut open_elements: Vec<(String, uint)> = vec!();
+        loop {
+            let scope_start = self.namespaces.len();
+            let (name, element, empty) = try!(self.parse_start_tag());
+            nodes.push(XMLNode::StartElement(element));
+            if empty {
+                self.namespaces.truncate(scope_start);
+                nodes.push(XMLNode::EndElement);
+            } else {
+                open_elements.push((name, scope_start));
+            }
+
+            // Close elements until the content of the innermost one has another start tag.
+            loop {
+                let (name, scope_start) = match open_elements.last() {
+                    Some(&(ref name, scope_start)) => (name.clone(), scope_start),
+                    None => return Ok(()),
+                };
+                if try!(self.parse_content(nodes)) == ContentEnd::StartTag {
+                    break;
+                }
+                if !self.consume(name.as_slice()) || self.peek().map_or(false, |c| !ends_name(c)) {
+                    return Err(self.error(format!("expected \"</{}>\"", name).as_slice()));
+                }
+                self.skip_whitespace();
+                try!(self.expect(">"));
+                self.namespaces.truncate(scope_start);
+                nodes.push(XMLNode::EndElement);
+                open_elements.pop();
+            }
+        }
+    }
+
+    /// http://www.w3.org/TR/xml/#NT-content, up to and including the `<` of a start tag or the
+    /// `</` of an end tag.
+    fn parse_content(&mut self, nodes: &mut Vec<XMLNode>) -> ParseResult<ContentEnd> {
+        let mut text = String::new();
+        loop {
+            if self.peek().is_none() {
+                return Err(self.error("unclosed element"));
+            }
+            if self.starts_with("<") || self.starts_with("&") {
+                if self.consume("&") {
+                    text.push_str(try!(self.parse_reference()).as_slice());
+                    continue;
+                }
+                if self.consume("<![CDATA[") {
+                    text.push_str(try!(self.consume_until("]]>")).as_slice());
+                    continue;
+                }
+                if !text.is_empty() {
+                    nodes.push(XMLNode::Text(text));
+                    text = String::new();
+                }
+                if self.consume("</") {
+                    return Ok(ContentEnd::EndTag);
+                } else if self.consume("<!--") {
+                    nodes.push(try!(self.parse_comment()));
+                } else if self.consume("<?") {
+                    nodes.push(try!(self.parse_processing_instruction()));
+                } else if self.starts_with("<!") {
+                    return Err(self.error("unexpected markup declaration"));
+                } else {
+                    self.pos += 1;
+                    return Ok(ContentEnd::StartTag);
+                }
+            } else {
+                if self.starts_with("]]>") {
+                    return Err(self.error("\"]]>\" in text"));
+                }
+                text.push(try!(self.next_char()));
+            }
+        }
+    }
+
+    /// http://www.w3.org/TR/xml/#NT-document
+    fn parse_document(&mut self) -> ParseResult<Vec<XMLNode>> {
+        if let Some(i) = self.input.iter().position(|&c| !is_xml_char(c)) {
+            self.pos = i;
+            return Err(self.error("invalid character"));
+        }
+        self.consume("\u{FEFF}");
+        if self.starts_with("<?xml") &&
+                self.input.get(self.pos + 5).map_or(false, |&c| is_xml_whitespace(c)) {
+            self.pos += 5;
+            try!(self.parse_xml_declaration());
+        }
+
+        let mut nodes = vec!();
+        let mut seen_doctype = false;
+        let mut seen_root = false;
+        loop {
+            self.skip_whitespace();
+            if self.peek().is_none() {
+                break;
+            }
+            if self.consume("<!--") {
+                nodes.push(try!(self.parse_comment()));
+            } else if self.consume("<?") {
+                nodes.push(try!(self.parse_processing_instruction()));
+            } else if self.consume("<!DOCTYPE") {
+                if seen_doctype || seen_root {
+                    return Err(self.error("unexpected document type declaration"));
+                }
+                seen_doctype = true;
+                nodes.push(try!(self.parse_doctype()));
+            } else if self.starts_with("<") && !self.starts_with("<!") && !seen_root {
+                self.pos += 1;
+                seen_root = true;
+                try!(self.parse_element(&mut nodes));
+            } else if seen_root {
+                return Err(self.error("junk after the document element"));
+            } else {
+                return Err(self.error("expected the document element"));
+            }
+        }
+        if !seen_root {
+            return Err(self.error("no document element"));
+        }
+        Ok(nodes)
+    }
+}
+
+/// Decodes an XML document, with the character encoding from its byte order mark, from the
+/// transport, from its XML declaration, or UTF-8.
+pub fn decode_xml(bytes: &[u8], charset: Option<String>) -> String {
+    let (encoding, bytes) = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        (UTF_8 as EncodingRef, &bytes[3..])
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        (UTF_16BE as EncodingRef, &bytes[2..])
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        (UTF_16LE as EncodingRef, &bytes[2..])
+    } else {
+        let label = charset.or_else(|| declared_encoding(bytes));
+        let encoding = label.and_then(|label| encoding_from_whatwg_label(label.as_slice()));
+        (encoding.unwrap_or(UTF_8 as EncodingRef), bytes)
+    };
+    encoding.decode(bytes, DecoderTrap::Replace).unwrap()
+}
+
+/// The encoding named by the XML declaration at the start of `bytes`, if any.
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    if !bytes.starts_with(b"<?xml") {
+        return None;
+    }
+    let end = match bytes.iter().position(|&b| b == b'>') {
+        Some(end) => end,
+        None => return None,
+    };
+    let declaration = String::from_utf8_lossy(&bytes[..end]).into_owned();
+    let start = match declaration.as_slice().find_str("encoding") {
+        Some(start) => start + "encoding".len(),
+        None => return None,
+    };
+    let rest = declaration.as_slice()[start..].trim_left();
+    if !rest.starts_with("=") {
+        return None;
+    }
+    let rest = rest[1..].trim_left();
+    let quote = match rest.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => quote,
+        _ => return None,
+    };
+    rest[1..].find(quote).map(|len| rest[1..len + 1].to_owned())
+}
+
+fn append(parent: JSRef<Node>, child: JSRef<Node>) {
+    assert!(parent.AppendChild(child).is_ok());
+}
+
+/// Creates the nodes of a parsed document as children of `document`.
+fn build_nodes(document: JSRef<Document>, nodes: Vec<XMLNode>, scripting: XMLScripting) {
+    // The document and the elements whose end hasn't been reached yet, innermost last. They are
+    // all in the document, which keeps them alive.
+    let mut open_nodes: Vec<JS<Node>> = vec!(JS::from_rooted(NodeCast::from_ref(document)));
+    for node in nodes.into_iter() {
+        let parent = open_nodes.last().unwrap().root();
+        let child: Temporary<Node> = match node {
+            XMLNode::StartElement(element) => {
+                let XMLElement { name, prefix, attrs } = element;
+                let elem = Element::create(name, prefix, document,
+                                           ElementCreator::ParserCreated).root();
+                for (qname, prefix, value) in attrs.into_iter() {
+                    elem.r().set_attribute_from_parser(qname, value, prefix);
+                }
+                let node: JSRef<Node> = NodeCast::from_ref(elem.r());
+                if scripting == XMLScripting::Disabled {
+                    let script: Option<JSRef<HTMLScriptElement>> = HTMLScriptElementCast::to_ref(node);
+                    script.map(|script| script.mark_already_started());
+                }
+                append(parent.r(), node);
+                open_nodes.push(JS::from_rooted(node));
+                continue;
+            }
+            XMLNode::EndElement => {
+                let script: Option<JSRef<HTMLScriptElement>> = HTMLScriptElementCast::to_ref(parent.r());
+                script.map(|script| script.prepare());
+                open_nodes.pop();
+                continue;
+            }
+            XMLNode::Text(text) => NodeCast::from_temporary(Text::new(text, document)),
+            XMLNode::Comment(text) => NodeCast::from_temporary(Comment::new(text, document)),
+            XMLNode::ProcessingInstruction(target, data) => {
+                NodeCast::from_temporary(ProcessingInstruction::new(target, data, document))
+            }
+            XMLNode::Doctype(name, public_id, system_id) => {
+                NodeCast::from_temporary(DocumentType::new(name, Some(public_id), Some(system_id),
+                                                           document))
+            }
+        };
+        let child: Root<Node> = child.root();
+        append(parent.r(), child.r());
+    }
+}
+
+/// Gives `document` a `parsererror` element describing `message`.
+fn build_parser_error(document: JSRef<Document>, message: String, url: &Url) {
+    let name = QualName::new(Namespace(Atom::from_slice(PARSERERROR_NAMESPACE)),
+                             Atom::from_slice("parsererror"));
+    let root = Element::create(name, None, document, ElementCreator::ParserCreated).root();
+    let root: JSRef<Node> = NodeCast::from_ref(root.r());
+    append(NodeCast::from_ref(document), root);
+    let text = format!("XML Parsing Error: {}\nLocation: {}", message, url.serialize());
+    let text: Root<Node> = NodeCast::from_temporary(Text::new(text, document)).root();
+    append(root, text.r());
+}
+
+pub fn parse_xml(document: JSRef<Document>,
+                 input: XMLInput,
+                 url: &Url,
+                 scripting: XMLScripting) {
+    let input = match input {
+        XMLInput::InputString(s) => s,
+        XMLInput::InputUrl(load_response) => {
+            let mut bytes = vec!();
+            for msg in load_response.progress_port.iter() {
+                match msg {
+                    ProgressMsg::Payload(data) => bytes.push_all(data.as_slice()),
+                    ProgressMsg::Done(Err(err)) => {
+                        panic!("Failed to load page URL {}, error: {}", url.serialize(), err);
+                    }
+                    ProgressMsg::Done(Ok(())) => break,
+                }
+            }
+            decode_xml(bytes.as_slice(), load_response.metadata.charset.clone())
+        }
+    };
+
+    match XMLParser::new(input.as_slice()).parse_document() {
+        Ok(nodes) => build_nodes(document, nodes, scripting),
+        Err(message) => {
+            debug!("XML parse error: {}", message);
+            build_parser_error(document, message, url);
+        }
+    }
+
+    debug!("finished parsing");
+}
+
+/// Whether a document of this MIME type is parsed as XML.
+pub fn is_xml_mime_type(top_level: &str, sub_level: &str) -> bool {
+    let top_level = top_level.to_ascii_lowercase();
+    let sub_level = sub_level.to_ascii_lowercase();
+    match (top_level.as_slice(), sub_level.as_slice()) {
+        ("text", "xml") | ("application", "xml") => true,
+        (_, sub_level) => sub_level.ends_with("+xml"),
+    }
+}
@@ -9,9 +9,11 @@
 
 use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::DocumentBinding::{DocumentMethods, DocumentReadyState};
+use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::codegen::InheritTypes::{ElementCast, EventTargetCast, HTMLIFrameElementCast, NodeCast, EventCast};
 use dom::bindings::conversions::FromJSValConvertible;
 use dom::bindings::conversions::StringificationBehavior;
+use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JS, JSRef, Temporary, OptionalRootable, RootedReference};
 use dom::bindings::js::{RootCollection, RootCollectionPtr};
 use dom::bindings::refcounted::{LiveDOMReferences, Trusted, TrustedReference};
@@ -21,12 +23,17 @@ use dom::bindings::utils::{wrap_for_same_compartment, pre_wrap};
 use dom::document::{Document, IsHTMLDocument, DocumentHelpers, DocumentProgressHandler, DocumentProgressTask, DocumentSource};
 use dom::element::{Element, AttributeHandlers};
 use dom::event::{Event, EventHelpers};
+use dom::storageevent::StorageEvent;
 use dom::uievent::UIEvent;
 use dom::eventtarget::EventTarget;
+use dom::history::{HistoryHelpers, fire_hashchange};
+use dom::messageport::TransferredPort;
+use dom::mutationobserver::MutationObserverHelpers;
 use dom::node::{self, Node, NodeHelpers, NodeDamage};
 use dom::window::{Window, WindowHelpers, ScriptHelpers};
 use dom::worker::{Worker, TrustedWorkerAddress};
 use parse::html::{HTMLInput, parse_html};
+use parse::xml::{XMLInput, XMLScripting, is_xml_mime_type, parse_xml};
 use layout_interface::{ScriptLayoutChan, LayoutChan, ReflowGoal, ReflowQueryType};
 use layout_interface;
 use page::{Page, IterablePage, Frame};
@@ -39,17 +46,19 @@ use script_traits::CompositorEvent;
 use script_traits::CompositorEvent::{ResizeEvent, ReflowEvent, ClickEvent};
 use script_traits::CompositorEvent::{MouseDownEvent, MouseUpEvent};
 use script_traits::CompositorEvent::{MouseMoveEvent, KeyEvent};
+use script_traits::CompositorEvent::{WheelEvent, ScrollEvent, TouchEvent};
 use script_traits::{NewLayoutInfo, OpaqueScriptLayoutChannel};
 use script_traits::{ConstellationControlMsg, ScriptControlChan};
 use script_traits::ScriptTaskFactory;
 use msg::compositor_msg::ReadyState::{FinishedLoading, Loading, PerformingLayout};
 use msg::compositor_msg::{LayerId, ScriptListener};
 use msg::constellation_msg::{ConstellationChan};
-use msg::constellation_msg::{LoadData, PipelineId, SubpageId};
+use msg::constellation_msg::{HistoryState, LoadData, PipelineId, SubpageId};
 use msg::constellation_msg::{Failure, Msg, WindowSizeData, PipelineExitType};
+use msg::constellation_msg::{StorageType, TopLevelBrowsingContextId};
 use msg::constellation_msg::Msg as ConstellationMsg;
 use net::image_cache_task::ImageCacheTask;
-use net::resource_task::{ResourceTask, ControlMsg};
+use net::resource_task::{ResourceTask, ControlMsg, LoadContext};
 use net::resource_task::LoadData as NetLoadData;
 use net::storage_task::StorageTask;
 use string_cache::Atom;
@@ -98,7 +107,7 @@ pub trait Runnable {
 pub enum ScriptMsg {
     /// Acts on a fragment URL load on the specified pipeline (only dispatched
     /// to ScriptTask).
-    TriggerFragment(PipelineId, String),
+    TriggerFragment(PipelineId, Url),
     /// Begins a content-initiated load on the specified pipeline (only
     /// dispatched to ScriptTask).
     TriggerLoad(PipelineId, LoadData),
@@ -109,9 +118,15 @@ pub enum ScriptMsg {
     /// Notifies the script that a window associated with a particular pipeline
     /// should be closed (only dispatched to ScriptTask).
     ExitWindow(PipelineId),
-    /// Message sent through Worker.postMessage (only dispatched to
-    /// DedicatedWorkerGlobalScope).
-    DOMMessage(StructuredCloneData),
+    /// Message sent through Worker.postMessage, with the ports it transfers (only
+    /// dispatched to DedicatedWorkerGlobalScope).
+    DOMMessage(StructuredCloneData, Vec<TransferredPort>),
+    /// Wakes up the event loop of a worker that was terminated or shut down (only
+    /// dispatched to worker global scopes).
+    TerminateWorker,
+    /// A new connection to a shared worker, through the given port (only dispatched to
+    /// SharedWorkerGlobalScope).
+    ConnectSharedWorker(TransferredPort),
     /// Sends a message to the Worker object (dispatched to all tasks) regarding error.
     WorkerDispatchErrorEvent(TrustedWorkerAddress, DOMString, DOMString, u32, u32),
     /// Generic message that encapsulates event handling.
@@ -211,7 +226,10 @@ pub struct ScriptTask {
     /// The JSContext.
     js_context: DOMRefCell<Option<Rc<Cx>>>,
 
-    mouse_over_targets: DOMRefCell<Vec<JS<Node>>>
+    mouse_over_targets: DOMRefCell<Vec<JS<Node>>>,
+
+    /// http://www.whatwg.org/html/#performing-a-microtask-checkpoint
+    performing_a_microtask_checkpoint: Cell<bool>,
 }
 
 /// In the event of task failure, all data on the stack runs its destructor. However, there
@@ -264,6 +282,7 @@ impl ScriptTaskFactory for ScriptTask {
 
     fn create<C>(_phantom: Option<&mut ScriptTask>,
                  id: PipelineId,
+                 top_level_browsing_context_id: TopLevelBrowsingContextId,
                  compositor: C,
                  layout_chan: &OpaqueScriptLayoutChannel,
                  control_chan: ScriptControlChan,
@@ -281,6 +300,7 @@ impl ScriptTaskFactory for ScriptTask {
         let layout_chan = LayoutChan(layout_chan.sender());
         spawn_named_with_send_on_failure("ScriptTask", task_state::SCRIPT, move || {
             let script_task = ScriptTask::new(id,
+                                              top_level_browsing_context_id,
                                               box compositor as Box<ScriptListener>,
                                               layout_chan,
                                               script_port,
@@ -313,6 +333,7 @@ unsafe extern "C" fn debug_gc_callback(_rt: *mut JSRuntime, status: JSGCStatus)
 impl ScriptTask {
     /// Creates a new script task.
     pub fn new(id: PipelineId,
+               top_level_browsing_context_id: TopLevelBrowsingContextId,
                compositor: Box<ScriptListener+'static>,
                layout_chan: LayoutChan,
                port: Receiver<ScriptMsg>,
@@ -348,7 +369,8 @@ impl ScriptTask {
                                       Some(pre_wrap));
         }
 
-        let page = Page::new(id, None, layout_chan, window_size,
+        let page = Page::new(id, None, top_level_browsing_context_id,
+                             layout_chan, window_size,
                              resource_task.clone(),
                              storage_task,
                              constellation_chan.clone(),
@@ -374,7 +396,8 @@ impl ScriptTask {
 
             js_runtime: js_runtime,
             js_context: DOMRefCell::new(Some(js_context)),
-            mouse_over_targets: DOMRefCell::new(vec!())
+            mouse_over_targets: DOMRefCell::new(vec!()),
+            performing_a_microtask_checkpoint: Cell::new(false),
         }
     }
 
@@ -546,11 +569,49 @@ impl ScriptTask {
                 MixedMessage::FromScript(inner_msg) => self.handle_msg_from_script(inner_msg),
                 MixedMessage::FromDevtools(inner_msg) => self.handle_msg_from_devtools(inner_msg),
             }
+
+            self.perform_a_microtask_checkpoint();
         }
 
         true
     }
 
+    /// Delivers the pending mutation records of every window in this task.
+    /// http://www.whatwg.org/html/#perform-a-microtask-checkpoint
+    fn perform_a_microtask_checkpoint(&self) {
+        // A callback that spins a nested event loop must not deliver the records again.
+        if self.performing_a_microtask_checkpoint.get() {
+            return;
+        }
+        self.performing_a_microtask_checkpoint.set(true);
+
+        // The observers' callbacks can queue more records, so they are delivered until
+        // none are left.
+        // http://dom.spec.whatwg.org/#notify-mutation-observers
+        loop {
+            let windows: Vec<Temporary<Window>> = {
+                let page = self.page.borrow();
+                page.iter().filter_map(|page| {
+                    let frame = page.frame();
+                    frame.as_ref().map(|frame| Temporary::new(frame.window.clone()))
+                }).collect()
+            };
+
+            let mut delivered = false;
+            for window in windows.into_iter() {
+                let window = window.root();
+                for observer in window.r().mutation_observers().into_iter() {
+                    delivered |= observer.root().r().notify();
+                }
+            }
+            if !delivered {
+                break;
+            }
+        }
+
+        self.performing_a_microtask_checkpoint.set(false);
+    }
+
     fn handle_msg_from_constellation(&self, msg: ConstellationControlMsg) {
         match msg {
             ConstellationControlMsg::AttachLayout(_) =>
@@ -574,7 +635,17 @@ impl ScriptTask {
             ConstellationControlMsg::Freeze(pipeline_id) =>
                 self.handle_freeze_msg(pipeline_id),
             ConstellationControlMsg::Thaw(pipeline_id) =>
-                self.handle_thaw_msg(pipeline_id)
+                self.handle_thaw_msg(pipeline_id),
+            ConstellationControlMsg::DispatchStorageEvent(pipeline_id, storage_type, url, key,
+                                                          old_value, new_value) =>
+                self.handle_storage_event(pipeline_id, storage_type, url, key, old_value,
+                                          new_value),
+            ConstellationControlMsg::HistoryTraversal(pipeline_id, url, state) =>
+                self.handle_history_traversal_msg(pipeline_id, url, state),
+            ConstellationControlMsg::SessionHistoryChanged(pipeline_id, index, length) =>
+                self.handle_session_history_changed_msg(pipeline_id, index, length),
+            ConstellationControlMsg::TickAllAnimations(pipeline_id) =>
+                self.handle_tick_all_animations(pipeline_id),
         }
     }
 
@@ -582,15 +653,16 @@ impl ScriptTask {
         match msg {
             ScriptMsg::TriggerLoad(id, load_data) =>
                 self.trigger_load(id, load_data),
-            ScriptMsg::TriggerFragment(id, fragment) =>
-                self.trigger_fragment(id, fragment),
+            ScriptMsg::TriggerFragment(id, url) =>
+                self.trigger_fragment(id, url),
             ScriptMsg::FireTimer(TimerSource::FromWindow(id), timer_id) =>
                 self.handle_fire_timer_msg(id, timer_id),
             ScriptMsg::FireTimer(TimerSource::FromWorker, _) =>
                 panic!("Worker timeouts must not be sent to script task"),
             ScriptMsg::ExitWindow(id) =>
                 self.handle_exit_window_msg(id),
-            ScriptMsg::DOMMessage(..) =>
+            ScriptMsg::DOMMessage(..) | ScriptMsg::TerminateWorker |
+            ScriptMsg::ConnectSharedWorker(..) =>
                 panic!("unexpected message"),
             ScriptMsg::WorkerDispatchErrorEvent(addr, msg, file_name,line_num, col_num) =>
                 Worker::handle_error_message(addr, msg, file_name, line_num, col_num),
@@ -635,6 +707,7 @@ impl ScriptTask {
         let new_page = {
             let window_size = parent_page.window_size.get();
             Page::new(new_pipeline_id, Some(subpage_id),
+                      parent_page.top_level_browsing_context_id,
                       LayoutChan(layout_chan.downcast_ref::<Sender<layout_interface::Msg>>().unwrap().clone()),
                       window_size,
                       parent_page.resource_task.clone(),
@@ -656,6 +729,21 @@ impl ScriptTask {
         window.r().handle_fire_timer(timer_id);
     }
 
+    /// Runs the animation frame callbacks of the given pipeline, then reflows it.
+    fn handle_tick_all_animations(&self, id: PipelineId) {
+        let page = self.page.borrow();
+        // The pipeline may have exited since the constellation sent this tick.
+        let page = match page.find(id) {
+            Some(page) => page,
+            None => return,
+        };
+        let frame = page.frame();
+        let document = frame.as_ref().unwrap().document.root();
+        document.r().run_the_animation_frame_callbacks();
+        let window = frame.as_ref().unwrap().window.root();
+        window.r().flush_layout(ReflowGoal::ForDisplay, ReflowQueryType::NoQuery);
+    }
+
     /// Handles freeze message
     fn handle_freeze_msg(&self, id: PipelineId) {
         let page = self.page.borrow_mut();
@@ -664,6 +752,8 @@ impl ScriptTask {
         let frame = page.frame();
         let window = frame.as_ref().unwrap().window.root();
         window.r().freeze();
+        let document = frame.as_ref().unwrap().document.root();
+        document.r().suspend_animation_frames();
     }
 
     /// Handles thaw message
@@ -674,6 +764,8 @@ impl ScriptTask {
         let frame = page.frame();
         let window = frame.as_ref().unwrap().window.root();
         window.r().thaw();
+        let document = frame.as_ref().unwrap().document.root();
+        document.r().resume_animation_frames();
     }
 
     /// Handles a notification that reflow completed.
@@ -821,8 +913,12 @@ impl ScriptTask {
                 headers: Headers::new(),
                 preserved_headers: load_data.headers,
                 data: load_data.data,
-                cors: None,
+                follow_redirects: true,
+                credentials: true,
                 consumer: input_chan,
+                context: LoadContext::Browsing,
+                document_url: None,
+                console: None,
             })).unwrap();
 
             let load_response = input_port.recv().unwrap();
@@ -841,6 +937,20 @@ impl ScriptTask {
             (None, doc_url, None)
         };
 
+        // Documents served with an XML MIME type, such as XHTML and SVG, are parsed as XML.
+        let xml_content_type = match parser_input {
+            Some(HTMLInput::InputUrl(ref load_response)) => {
+                match load_response.metadata.content_type {
+                    Some((ref top_level, ref sub_level))
+                            if is_xml_mime_type(top_level.as_slice(), sub_level.as_slice()) => {
+                        Some(format!("{}/{}", top_level, sub_level))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
         // Store the final URL before we start parsing, so that DOM routines
         // (e.g. HTMLImageElement::update_image) can resolve relative URLs
         // correctly.
@@ -858,8 +968,13 @@ impl ScriptTask {
                                  self.compositor.borrow_mut().dup(),
                                  self.image_cache_task.clone()).root();
 
+        let is_html_document = if xml_content_type.is_some() {
+            IsHTMLDocument::NonHTMLDocument
+        } else {
+            IsHTMLDocument::HTMLDocument
+        };
         let document = Document::new(window.r(), Some(final_url.clone()),
-                                     IsHTMLDocument::HTMLDocument, None,
+                                     is_html_document, xml_content_type.clone(),
                                      DocumentSource::FromParser).root();
         if let Some(tm) = last_modified {
             document.r().set_last_modified(dom_last_modified(&tm));
@@ -884,7 +999,13 @@ impl ScriptTask {
             parser_input = Some(HTMLInput::InputString(strval.unwrap_or("".to_owned())));
         };
 
-        parse_html(document.r(), parser_input.unwrap(), &final_url);
+        match parser_input.unwrap() {
+            HTMLInput::InputUrl(load_response) if xml_content_type.is_some() => {
+                parse_xml(document.r(), XMLInput::InputUrl(load_response), &final_url,
+                          XMLScripting::Enabled);
+            }
+            input => parse_html(document.r(), input, &final_url),
+        }
 
         document.r().set_ready_state(DocumentReadyState::Interactive);
         self.compositor.borrow_mut().set_ready_state(pipeline_id, PerformingLayout);
@@ -1008,6 +1129,39 @@ impl ScriptTask {
                 }
             }
 
+            WheelEvent(delta, point) => {
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                let frame = page.frame();
+                let document = frame.as_ref().unwrap().document.root();
+                document.r().handle_wheel_event(self.js_runtime.ptr, delta, point);
+            }
+
+            ScrollEvent(position) => {
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                // The compositor reports the scroll position of every layer it has, which can
+                // belong to a page that is not loaded yet.
+                if let Some(ref frame) = *page.frame() {
+                    let document = frame.document.root();
+                    document.r().handle_scroll_event(position);
+                }
+            }
+
+            TouchEvent(event_type, identifier, point, window_point) => {
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                // The compositor runs its gestures once it hears back, even for a page that has
+                // not loaded yet.
+                let prevented = match *page.frame() {
+                    Some(ref frame) => {
+                        let document = frame.document.root();
+                        document.r().handle_touch_event(self.js_runtime.ptr, event_type,
+                                                        identifier, point)
+                    }
+                    None => false,
+                };
+                self.compositor.borrow_mut().send_touch_event(event_type, identifier,
+                                                              window_point, prevented);
+            }
+
             KeyEvent(key, state, modifiers) => {
                 let page = get_page(&*self.page.borrow(), pipeline_id);
                 let frame = page.frame();
@@ -1027,9 +1181,41 @@ impl ScriptTask {
 
     /// The entry point for content to notify that a fragment url has been requested
     /// for the given pipeline.
-    fn trigger_fragment(&self, pipeline_id: PipelineId, fragment: String) {
-        let page = get_page(&*self.page.borrow(), pipeline_id);
-        match page.find_fragment_node(fragment).root() {
+    /// http://www.whatwg.org/html/#scroll-to-fragid
+    fn trigger_fragment(&self, pipeline_id: PipelineId, url: Url) {
+        let window = {
+            let page = get_page(&*self.page.borrow(), pipeline_id);
+            let frame = page.frame();
+            Temporary::new(frame.as_ref().unwrap().window.clone())
+        }.root();
+        let document = window.r().Document().root();
+        let old_url = document.r().url();
+
+        // Step 1-2.
+        if old_url != url {
+            document.r().set_url(url.clone());
+            window.r().History().root().r().clear_state();
+            let ConstellationChan(ref chan) = self.constellation_chan;
+            chan.send(ConstellationMsg::PushHistoryState(pipeline_id, url.clone(), None)).unwrap();
+            window.r().page().push_session_history_entry();
+        }
+
+        // Step 3.
+        self.scroll_to_fragment(pipeline_id, &url);
+
+        // Step 4.
+        if old_url.fragment != url.fragment {
+            fire_hashchange(window.r(), &old_url, &url);
+        }
+    }
+
+    /// Scrolls to the element indicated by the fragment of `url`, if any.
+    fn scroll_to_fragment(&self, pipeline_id: PipelineId, url: &Url) {
+        let node = {
+            let page = get_page(&*self.page.borrow(), pipeline_id);
+            url.fragment.clone().and_then(|fragment| page.find_fragment_node(fragment))
+        };
+        match node.root() {
             Some(node) => {
                 self.scroll_fragment_point(pipeline_id, node.r());
             }
@@ -1037,6 +1223,28 @@ impl ScriptTask {
         }
     }
 
+    /// Handles a traversal of the session history to an entry of the given pipeline. A `None`
+    /// URL stands for the URL the document was loaded from.
+    fn handle_history_traversal_msg(&self, pipeline_id: PipelineId, url: Option<Url>,
+                                    state: Option<HistoryState>) {
+        let (window, url) = {
+            let page = get_page(&*self.page.borrow(), pipeline_id);
+            let url = url.unwrap_or_else(|| page.get_url());
+            let frame = page.frame();
+            (Temporary::new(frame.as_ref().unwrap().window.clone()), url)
+        };
+        let window = window.root();
+        window.r().History().root().r().traverse(url.clone(), state);
+        self.scroll_to_fragment(pipeline_id, &url);
+    }
+
+    fn handle_session_history_changed_msg(&self, pipeline_id: PipelineId, index: u32,
+                                          length: u32) {
+        let page = get_page(&*self.page.borrow(), pipeline_id);
+        page.session_history_index.set(index);
+        page.session_history_length.set(length);
+    }
+
 
     fn handle_resize_event(&self, pipeline_id: PipelineId, new_size: WindowSizeData) {
         let window = {
@@ -1079,6 +1287,44 @@ impl ScriptTask {
         }
     }
 
+    /// https://html.spec.whatwg.org/multipage/webstorage.html#send-a-storage-notification
+    fn handle_storage_event(&self, pipeline_id: PipelineId, storage_type: StorageType, url: Url,
+                            key: Option<DOMString>, old_value: Option<DOMString>,
+                            new_value: Option<DOMString>) {
+        let window = {
+            let page = self.page.borrow();
+            let page = match page.find(pipeline_id) {
+                Some(page) => page,
+                None => return,
+            };
+            let frame = page.frame();
+            frame.as_ref().map(|frame| Temporary::new(frame.window.clone()))
+        };
+
+        let window = match window.root() {
+            Some(window) => window,
+            None => return,
+        };
+        let document_url = window.r().get_url();
+        if document_url.scheme != url.scheme || document_url.domain() != url.domain() ||
+           document_url.port() != url.port() {
+            return;
+        }
+
+        let storage = match storage_type {
+            StorageType::Session(_) => window.r().SessionStorage(),
+            StorageType::Local => window.r().LocalStorage(),
+        }.root();
+        let storage_event = StorageEvent::new(GlobalRef::Window(window.r()),
+                                              "storage".to_owned(), false, false,
+                                              key, old_value, new_value, url.serialize(),
+                                              Some(storage.r())).root();
+        let event: JSRef<Event> = EventCast::from_ref(storage_event.r());
+
+        let wintarget: JSRef<EventTarget> = EventTargetCast::from_ref(window.r());
+        event.fire(wintarget);
+    }
+
     fn handle_reflow_event(&self, pipeline_id: PipelineId) {
         debug!("script got reflow event");
         let page = get_page(&*self.page.borrow(), pipeline_id);
@@ -24,17 +24,20 @@ use devtools_traits::DevtoolsControlChan;
 use libc::c_void;
 use msg::constellation_msg::{ConstellationChan, PipelineId, Failure, WindowSizeData};
 use msg::constellation_msg::{LoadData, SubpageId, Key, KeyState, KeyModifiers};
-use msg::constellation_msg::PipelineExitType;
+use msg::constellation_msg::{HistoryState, PipelineExitType, TouchEventType, TouchId};
+use msg::constellation_msg::{StorageType, TopLevelBrowsingContextId};
 use msg::compositor_msg::ScriptListener;
 use net::image_cache_task::ImageCacheTask;
 use net::resource_task::ResourceTask;
 use net::storage_task::StorageTask;
 use util::smallvec::SmallVec1;
+use util::str::DOMString;
 use std::any::Any;
 use std::sync::mpsc::{Sender, Receiver};
 
 use geom::point::Point2D;
 use geom::rect::Rect;
+use url::Url;
 
 /// The address of a node. Layout sends these back. They must be validated via
 /// `from_untrusted_node_address` before they can be used, because we do not trust layout.
@@ -73,7 +76,21 @@ pub enum ConstellationControlMsg {
     /// Notifies script task to suspend all its timers
     Freeze(PipelineId),
     /// Notifies script task to resume all its timers
-    Thaw(PipelineId)
+    Thaw(PipelineId),
+    /// Notifies script that another document changed a storage area: the storage type, the
+    /// URL of that document, and the key, old value and new value of the change.
+    DispatchStorageEvent(PipelineId, StorageType, Url, Option<DOMString>, Option<DOMString>,
+                         Option<DOMString>),
+    /// Notifies script that the session history was traversed to an entry of the given
+    /// pipeline's document: the URL of the entry, or `None` if it is the URL the document was
+    /// loaded from, and its serialized state.
+    HistoryTraversal(PipelineId, Option<Url>, Option<HistoryState>),
+    /// Notifies script of the index of the current entry of the session history and of the
+    /// number of entries, after they changed.
+    SessionHistoryChanged(PipelineId, u32, u32),
+    /// Notifies script that a new frame is about to be painted, so that it runs its animation
+    /// frame callbacks.
+    TickAllAnimations(PipelineId),
 }
 
 unsafe impl Send for ConstellationControlMsg {
@@ -87,6 +104,15 @@ pub enum CompositorEvent {
     MouseDownEvent(uint, Point2D<f32>),
     MouseUpEvent(uint, Point2D<f32>),
     MouseMoveEvent(Point2D<f32>),
+    /// A mouse wheel rotation, with the scroll delta (positive when scrolling down or
+    /// right) and the position of the cursor.
+    WheelEvent(Point2D<f32>, Point2D<f32>),
+    /// The root layer of the pipeline was scrolled to the given position.
+    ScrollEvent(Point2D<f32>),
+    /// A touch point was pressed, moved, released or cancelled at the given position in the
+    /// layer. The position in the window is sent back to the compositor once the event has
+    /// been dispatched.
+    TouchEvent(TouchEventType, TouchId, Point2D<f32>, Point2D<f32>),
     KeyEvent(Key, KeyState, KeyModifiers),
 }
 
@@ -101,6 +127,7 @@ pub struct ScriptControlChan(pub Sender<ConstellationControlMsg>);
 pub trait ScriptTaskFactory {
     fn create<C>(_phantom: Option<&mut Self>,
                  id: PipelineId,
+                 top_level_browsing_context_id: TopLevelBrowsingContextId,
                  compositor: C,
                  layout_chan: &OpaqueScriptLayoutChannel,
                  control_chan: ScriptControlChan,
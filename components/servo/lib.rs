@@ -36,7 +36,7 @@ use script::dom::bindings::codegen::RegisterBindings;
 #[cfg(not(test))]
 use net::image_cache_task::ImageCacheTask;
 #[cfg(not(test))]
-use net::resource_task::new_resource_task;
+use net::resource_task::{new_resource_task, ProtocolRegistry};
 #[cfg(not(test))]
 use net::storage_task::{StorageTaskFactory, StorageTask};
 #[cfg(not(test))]
@@ -62,8 +62,10 @@ pub struct Browser<Window> {
 }
 
 impl<Window> Browser<Window> where Window: WindowMethods + 'static {
+    /// Starts Servo. URLs with a scheme registered in `protocols` are loaded by the
+    /// embedder's handler instead of the built-in loaders.
     #[cfg(not(test))]
-    pub fn new(window: Option<Rc<Window>>) -> Browser<Window> {
+    pub fn new(window: Option<Rc<Window>>, protocols: ProtocolRegistry) -> Browser<Window> {
         use std::env;
 
         ::util::opts::set_experimental_enabled(opts::get().enable_experimental);
@@ -89,7 +91,7 @@ impl<Window> Browser<Window> where Window: WindowMethods + 'static {
             .spawn(move || {
             let opts = &opts_clone;
             // Create a Servo instance.
-            let resource_task = new_resource_task(opts.user_agent.clone());
+            let resource_task = new_resource_task(opts.user_agent.clone(), protocols);
             // If we are emitting an output file, then we need to block on
             // image load or we risk emitting an output file missing the
             // image.
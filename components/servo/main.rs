@@ -29,7 +29,7 @@ use libc::c_int;
 use util::opts;
 
 #[cfg(not(test))]
-use net::resource_task;
+use net::resource_task::{self, ProtocolRegistry};
 
 #[cfg(not(test))]
 use servo::Browser;
@@ -122,7 +122,7 @@ fn main() {
         };
 
         let mut browser = BrowserWrapper {
-            browser: Browser::new(window.clone()),
+            browser: Browser::new(window.clone(), ProtocolRegistry::new()),
         };
 
         match window {
@@ -1182,7 +1182,9 @@ pub mod longhands {
             })
         }
         pub mod computed_value {
+            use cssparser::ToCss;
             use std::fmt;
+            use text_writer::{self, TextWriter};
             #[derive(PartialEq, Eq, Copy, Clone)]
             pub enum T {
                 % for weight in range(100, 901, 100):
@@ -1198,6 +1200,15 @@ pub mod longhands {
                     }
                 }
             }
+            impl ToCss for T {
+                fn to_css<W>(&self, dest: &mut W) -> text_writer::Result where W: TextWriter {
+                    match self {
+                        % for weight in range(100, 901, 100):
+                            &T::Weight${weight} => dest.write_str("${weight}"),
+                        % endfor
+                    }
+                }
+            }
             impl T {
                 pub fn is_bold(self) -> bool {
                     match self {
@@ -673,8 +673,10 @@ pub mod computed {
     use super::specified::{AngleOrCorner};
     use super::{specified, CSSFloat};
     pub use cssparser::Color as CSSColor;
+    use cssparser::ToCss;
     use properties::longhands;
     use std::fmt;
+    use text_writer::{self, TextWriter};
     use url::Url;
     use util::geometry::Au;
 
@@ -752,6 +754,15 @@ pub mod computed {
         }
     }
 
+    impl ToCss for LengthOrPercentage {
+        fn to_css<W>(&self, dest: &mut W) -> text_writer::Result where W: TextWriter {
+            match self {
+                &LengthOrPercentage::Length(length) => length.to_css(dest),
+                &LengthOrPercentage::Percentage(percentage) => write!(dest, "{}%", percentage * 100.),
+            }
+        }
+    }
+
     impl ToComputedValue for specified::LengthOrPercentage {
         type ComputedValue = LengthOrPercentage;
 
@@ -783,6 +794,17 @@ pub mod computed {
         }
     }
 
+    impl ToCss for LengthOrPercentageOrAuto {
+        fn to_css<W>(&self, dest: &mut W) -> text_writer::Result where W: TextWriter {
+            match self {
+                &LengthOrPercentageOrAuto::Length(length) => length.to_css(dest),
+                &LengthOrPercentageOrAuto::Percentage(percentage) =>
+                    write!(dest, "{}%", percentage * 100.),
+                &LengthOrPercentageOrAuto::Auto => dest.write_str("auto"),
+            }
+        }
+    }
+
     impl ToComputedValue for specified::LengthOrPercentageOrAuto {
         type ComputedValue = LengthOrPercentageOrAuto;
 
@@ -818,6 +840,17 @@ pub mod computed {
         }
     }
 
+    impl ToCss for LengthOrPercentageOrNone {
+        fn to_css<W>(&self, dest: &mut W) -> text_writer::Result where W: TextWriter {
+            match self {
+                &LengthOrPercentageOrNone::Length(length) => length.to_css(dest),
+                &LengthOrPercentageOrNone::Percentage(percentage) =>
+                    write!(dest, "{}%", percentage * 100.),
+                &LengthOrPercentageOrNone::None => dest.write_str("none"),
+            }
+        }
+    }
+
     impl ToComputedValue for specified::LengthOrPercentageOrNone {
         type ComputedValue = LengthOrPercentageOrNone;
 
@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use cssparser::ToCss;
 use geom::length::Length;
 use geom::point::Point2D;
 use geom::rect::Rect;
@@ -15,6 +16,7 @@ use std::fmt;
 use std::ops::{Add, Sub, Neg, Mul, Div, Rem};
 
 use rustc_serialize::{Encoder, Encodable};
+use text_writer::{self, TextWriter};
 
 // Units for use with geom::length and geom::scale_factor.
 
@@ -125,6 +127,12 @@ impl fmt::Debug for Au {
         write!(f, "{}px", to_frac_px(*self))
     }}
 
+impl ToCss for Au {
+    fn to_css<W>(&self, dest: &mut W) -> text_writer::Result where W: TextWriter {
+        write!(dest, "{}px", to_frac_px(*self))
+    }
+}
+
 impl Add for Au {
     type Output = Au;
 
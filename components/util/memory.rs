@@ -120,40 +120,48 @@ impl MemoryProfiler {
     fn handle_print_msg(&self) {
         println!("{:12}: {}", "_size (MiB)_", "_category_");
 
-        // Virtual and physical memory usage, as reported by the OS.
-        MemoryProfiler::print_measurement("vsize", get_vsize());
-        MemoryProfiler::print_measurement("resident", get_resident());
-
-        for seg in get_resident_segments().iter() {
-            MemoryProfiler::print_measurement(seg.0.as_slice(), Some(seg.1));
+        for &(ref path, nbytes) in get_memory_report().iter() {
+            MemoryProfiler::print_measurement(path.as_slice(), nbytes);
         }
 
-        // Total number of bytes allocated by the application on the system
-        // heap.
-        MemoryProfiler::print_measurement("system-heap-allocated",
-                                          get_system_heap_allocated());
+        println!("");
+    }
+}
 
-        // The descriptions of the following jemalloc measurements are taken
-        // directly from the jemalloc documentation.
+/// Takes every memory measurement, as a list of categories and their size in bytes. A size
+/// is `None` if it can't be measured on this platform.
+pub fn get_memory_report() -> Vec<(String, Option<u64>)> {
+    let mut report = vec!();
 
-        // "Total number of bytes allocated by the application."
-        MemoryProfiler::print_measurement("jemalloc-heap-allocated",
-                                          get_jemalloc_stat("stats.allocated"));
+    // Virtual and physical memory usage, as reported by the OS.
+    report.push(("vsize".to_owned(), get_vsize()));
+    report.push(("resident".to_owned(), get_resident()));
 
-        // "Total number of bytes in active pages allocated by the application.
-        // This is a multiple of the page size, and greater than or equal to
-        // |stats.allocated|."
-        MemoryProfiler::print_measurement("jemalloc-heap-active",
-                                          get_jemalloc_stat("stats.active"));
+    for seg in get_resident_segments().into_iter() {
+        report.push((seg.0, Some(seg.1)));
+    }
 
-        // "Total number of bytes in chunks mapped on behalf of the application.
-        // This is a multiple of the chunk size, and is at least as large as
-        // |stats.active|. This does not include inactive chunks."
-        MemoryProfiler::print_measurement("jemalloc-heap-mapped",
-                                          get_jemalloc_stat("stats.mapped"));
+    // Total number of bytes allocated by the application on the system
+    // heap.
+    report.push(("system-heap-allocated".to_owned(), get_system_heap_allocated()));
 
-        println!("");
-    }
+    // The descriptions of the following jemalloc measurements are taken
+    // directly from the jemalloc documentation.
+
+    // "Total number of bytes allocated by the application."
+    report.push(("jemalloc-heap-allocated".to_owned(), get_jemalloc_stat("stats.allocated")));
+
+    // "Total number of bytes in active pages allocated by the application.
+    // This is a multiple of the page size, and greater than or equal to
+    // |stats.allocated|."
+    report.push(("jemalloc-heap-active".to_owned(), get_jemalloc_stat("stats.active")));
+
+    // "Total number of bytes in chunks mapped on behalf of the application.
+    // This is a multiple of the chunk size, and is at least as large as
+    // |stats.active|. This does not include inactive chunks."
+    report.push(("jemalloc-heap-mapped".to_owned(), get_jemalloc_stat("stats.mapped")));
+
+    report
 }
 
 #[cfg(target_os="linux")]
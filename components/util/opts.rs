@@ -11,6 +11,7 @@ use geom::scale_factor::ScaleFactor;
 use geom::size::TypedSize2D;
 use layers::geometry::DevicePixel;
 use getopts;
+use std::borrow::ToOwned;
 use std::collections::HashSet;
 use std::cmp;
 use std::env;
@@ -118,6 +119,80 @@ pub struct Opts {
 
     /// A specific path to find required resources (such as user-agent.css).
     pub resources_path: Option<String>,
+
+    /// A directory in which to persist the HTTP cache across runs (`--http-cache-dir`). If
+    /// unset, the cache is only kept in memory.
+    pub http_cache_path: Option<String>,
+
+    /// A file in which to keep persistent cookies across runs (`--cookie-jar`). If unset,
+    /// cookies are only kept in memory.
+    pub cookie_jar_path: Option<String>,
+
+    /// A directory in which to persist localStorage across runs (`--local-storage-dir`). If
+    /// unset, localStorage is only kept in memory.
+    pub local_storage_path: Option<String>,
+
+    /// The maximum number of simultaneous connections to a single host
+    /// (`--max-connections-per-host`). Further requests to that host wait for a connection.
+    pub max_connections_per_host: usize,
+
+    /// The proxy to send HTTP and HTTPS requests through (`--proxy`), as an `http://` or
+    /// `socks5://` URL. If unset, the `http_proxy` and `https_proxy` environment variables apply.
+    pub proxy: Option<String>,
+
+    /// A comma-separated list of hosts to reach without the proxy (`--no-proxy`). If unset, the
+    /// `no_proxy` environment variable applies.
+    pub no_proxy: Option<String>,
+}
+
+impl Opts {
+    /// The name and current value of each option, as shown on `about:config`.
+    pub fn config_entries(&self) -> Vec<(&'static str, String)> {
+        fn optional<T: ToString>(value: &Option<T>) -> String {
+            match *value {
+                Some(ref value) => value.to_string(),
+                None => "(unset)".to_owned(),
+            }
+        }
+
+        vec!(
+            ("urls", self.urls.connect(" ")),
+            ("paint_threads", self.paint_threads.to_string()),
+            ("gpu_painting", self.gpu_painting.to_string()),
+            ("tile_size", self.tile_size.to_string()),
+            ("device_pixels_per_px", optional(&self.device_pixels_per_px.as_ref().map(|scale| scale.get()))),
+            ("time_profiler_period", optional(&self.time_profiler_period)),
+            ("memory_profiler_period", optional(&self.memory_profiler_period)),
+            ("enable_experimental", self.enable_experimental.to_string()),
+            ("layout_threads", self.layout_threads.to_string()),
+            ("nonincremental_layout", self.nonincremental_layout.to_string()),
+            ("output_file", optional(&self.output_file)),
+            ("headless", self.headless.to_string()),
+            ("hard_fail", self.hard_fail.to_string()),
+            ("bubble_inline_sizes_separately", self.bubble_inline_sizes_separately.to_string()),
+            ("show_debug_borders", self.show_debug_borders.to_string()),
+            ("show_debug_fragment_borders", self.show_debug_fragment_borders.to_string()),
+            ("show_debug_parallel_paint", self.show_debug_parallel_paint.to_string()),
+            ("show_debug_parallel_layout", self.show_debug_parallel_layout.to_string()),
+            ("enable_text_antialiasing", self.enable_text_antialiasing.to_string()),
+            ("trace_layout", self.trace_layout.to_string()),
+            ("profile_tasks", self.profile_tasks.to_string()),
+            ("devtools_port", optional(&self.devtools_port)),
+            ("initial_window_size", format!("{}x{}", self.initial_window_size.width.get(),
+                                            self.initial_window_size.height.get())),
+            ("user_agent", optional(&self.user_agent)),
+            ("dump_flow_tree", self.dump_flow_tree.to_string()),
+            ("dump_display_list", self.dump_display_list.to_string()),
+            ("validate_display_list_geometry", self.validate_display_list_geometry.to_string()),
+            ("resources_path", optional(&self.resources_path)),
+            ("http_cache_path", optional(&self.http_cache_path)),
+            ("cookie_jar_path", optional(&self.cookie_jar_path)),
+            ("local_storage_path", optional(&self.local_storage_path)),
+            ("max_connections_per_host", self.max_connections_per_host.to_string()),
+            ("proxy", optional(&self.proxy)),
+            ("no_proxy", optional(&self.no_proxy)),
+        )
+    }
 }
 
 fn print_usage(app: &str, opts: &[getopts::OptGroup]) {
@@ -191,6 +266,12 @@ pub fn default_opts() -> Opts {
         validate_display_list_geometry: false,
         profile_tasks: false,
         resources_path: None,
+        http_cache_path: None,
+        cookie_jar_path: None,
+        local_storage_path: None,
+        max_connections_per_host: 6,
+        proxy: None,
+        no_proxy: None,
     }
 }
 
@@ -220,6 +301,12 @@ pub fn from_cmdline_args(args: &[String]) -> bool {
         getopts::optflag("h", "help", "Print this message"),
         getopts::optopt("r", "render-api", "Set the rendering API to use", "gl|mesa"),
         getopts::optopt("", "resources-path", "Path to find static resources", "/home/servo/resources"),
+        getopts::optopt("", "http-cache-dir", "Directory in which to persist the HTTP cache", "/home/servo/.cache/servo"),
+        getopts::optopt("", "cookie-jar", "File in which to persist cookies", "/home/servo/cookies.json"),
+        getopts::optopt("", "local-storage-dir", "Directory in which to persist localStorage", "/home/servo/.local/share/servo/storage"),
+        getopts::optopt("", "max-connections-per-host", "Maximum number of simultaneous connections to a single host", "6"),
+        getopts::optopt("", "proxy", "Proxy for HTTP and HTTPS requests", "socks5://localhost:1080"),
+        getopts::optopt("", "no-proxy", "Comma-separated list of hosts to reach without the proxy", "localhost,.example.com"),
     );
 
     let opt_match = match getopts::getopts(args, opts.as_slice()) {
@@ -299,6 +386,11 @@ pub fn from_cmdline_args(args: &[String]) -> bool {
         port.parse().unwrap()
     });
 
+    let max_connections_per_host = match opt_match.opt_str("max-connections-per-host") {
+        Some(max_str) => cmp::max(max_str.parse().unwrap(), 1),
+        None => 6,
+    };
+
     let initial_window_size = match opt_match.opt_str("resolution") {
         Some(res_string) => {
             let res: Vec<u32> = res_string.split('x').map(|r| r.parse().unwrap()).collect();
@@ -338,6 +430,12 @@ pub fn from_cmdline_args(args: &[String]) -> bool {
         dump_display_list: debug_options.contains(&"dump-display-list"),
         validate_display_list_geometry: debug_options.contains(&"validate-display-list-geometry"),
         resources_path: opt_match.opt_str("resources-path"),
+        http_cache_path: opt_match.opt_str("http-cache-dir"),
+        cookie_jar_path: opt_match.opt_str("cookie-jar"),
+        local_storage_path: opt_match.opt_str("local-storage-dir"),
+        max_connections_per_host: max_connections_per_host,
+        proxy: opt_match.opt_str("proxy"),
+        no_proxy: opt_match.opt_str("no-proxy"),
     };
 
     set_opts(opts);
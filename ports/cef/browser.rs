@@ -15,6 +15,7 @@ use window;
 use compositing::windowing::{WindowNavigateMsg, WindowEvent};
 use glutin_app;
 use libc::c_int;
+use net::resource_task::ProtocolRegistry;
 use util::opts;
 use std::borrow::ToOwned;
 use std::cell::{Cell, RefCell, BorrowState};
@@ -98,7 +99,7 @@ impl ServoCefBrowser {
 
         let servo_browser = if window_info.windowless_rendering_enabled == 0 {
             let glutin_window = glutin_app::create_window();
-            let servo_browser = Browser::new(Some(glutin_window.clone()));
+            let servo_browser = Browser::new(Some(glutin_window.clone()), ProtocolRegistry::new());
             ServoBrowser::OnScreen(servo_browser)
         } else {
             ServoBrowser::Invalid
@@ -131,7 +132,7 @@ impl ServoCefBrowserExtensions for CefBrowser {
     fn init(&self, window_info: &cef_window_info_t) {
         if window_info.windowless_rendering_enabled != 0 {
             let window = window::Window::new();
-            let servo_browser = Browser::new(Some(window.clone()));
+            let servo_browser = Browser::new(Some(window.clone()), ProtocolRegistry::new());
             window.set_browser(self.clone());
             *self.downcast().servo_browser.borrow_mut() = ServoBrowser::OffScreen(servo_browser);
         }
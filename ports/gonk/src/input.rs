@@ -8,7 +8,6 @@ use std::mem::transmute;
 use std::mem::zeroed;
 use std::os::errno;
 use std::os::unix::AsRawFd;
-use std::num::Float;
 use std::old_io::File;
 use std::thread::Thread;
 use std::sync::mpsc::Sender;
@@ -20,7 +19,7 @@ use libc::c_long;
 use libc::time_t;
 
 use compositing::windowing::WindowEvent;
-use compositing::windowing::MouseWindowEvent;
+use msg::constellation_msg::{TouchEventType, TouchId};
 
 
 extern {
@@ -75,15 +74,20 @@ const ABS_MT_POSITION_Y: u16 = 0x36;
 const ABS_MT_TRACKING_ID: u16 = 0x39;
 
 struct InputSlot {
+    /// The tracking id the device reports for this slot, or -1 if nothing touches it.
     tracking_id: i32,
+    /// The tracking id of the touch point that was last reported to the compositor, or -1.
+    reported_id: i32,
     x: i32,
     y: i32,
+    /// Whether the position changed since the last report.
+    moved: bool,
 }
 
-fn dist(x1: i32, x2: i32, y1: i32, y2: i32) -> f32 {
-    let deltaX = (x2 - x1) as f32;
-    let deltaY = (y2 - y1) as f32;
-    (deltaX * deltaX + deltaY * deltaY).sqrt()
+fn send_touch_event(sender: &Sender<WindowEvent>, event_type: TouchEventType, slot: &InputSlot,
+                    id: i32) {
+    let point = TypedPoint2D(slot.x as f32, slot.y as f32);
+    sender.send(WindowEvent::Touch(event_type, TouchId(id), point)).ok().unwrap();
 }
 
 fn read_input_device(device_path: &Path,
@@ -123,18 +127,10 @@ fn read_input_device(device_path: &Path,
     let mut slots: [InputSlot; 10] = unsafe { zeroed() };
     for slot in slots.iter_mut() {
         slot.tracking_id = -1;
+        slot.reported_id = -1;
     }
 
-    let mut last_x = 0;
-    let mut last_y = 0;
-    let mut first_x = 0;
-    let mut first_y = 0;
-
-    let mut last_dist: f32 = 0f32;
-    let mut touch_count: i32 = 0;
     let mut current_slot: uint = 0;
-    // XXX: Need to use the real dimensions of the screen
-    let screen_dist = dist(0, 480, 854, 0);
     loop {
         let read = match device.read(buf.as_mut_slice()) {
             Ok(count) => {
@@ -150,50 +146,28 @@ fn read_input_device(device_path: &Path,
 
         let count = read / size_of::<linux_input_event>();
         let events: *mut linux_input_event = unsafe { transmute(buf.as_mut_ptr()) };
-        let mut tracking_updated = false;
         for idx in range(0, count as int) {
             let event: &linux_input_event = unsafe { transmute(events.offset(idx)) };
             match (event.evt_type, event.code) {
                 (EV_SYN, EV_REPORT) => {
-                    let slotA = &slots[0];
-                    if tracking_updated {
-                        tracking_updated = false;
-                        if slotA.tracking_id == -1 {
-                            println!("Touch up");
-                            let delta_x = slotA.x - first_x;
-                            let delta_y = slotA.y - first_y;
-                            let dist = delta_x * delta_x + delta_y * delta_y;
-                            if dist < 16 {
-                                let click_pt = TypedPoint2D(slotA.x as f32, slotA.y as f32);
-                                println!("Dispatching click!");
-                                sender.send(WindowEvent::MouseWindowEventClass(MouseWindowEvent::MouseDown(0, click_pt))).ok().unwrap();
-                                sender.send(WindowEvent::MouseWindowEventClass(MouseWindowEvent::MouseUp(0, click_pt))).ok().unwrap();
-                                sender.send(WindowEvent::MouseWindowEventClass(MouseWindowEvent::Click(0, click_pt))).ok().unwrap();
-                            }
-                        } else {
-                            println!("Touch down");
-                            last_x = slotA.x;
-                            last_y = slotA.y;
-                            first_x = slotA.x;
-                            first_y = slotA.y;
-                            if touch_count >= 2 {
-                                let slotB = &slots[1];
-                                last_dist = dist(slotA.x, slotB.x, slotA.y, slotB.y);
-                            }
+                    // Report the touch points that changed since the last report, one event per
+                    // point, and leave pinch zoom and scroll gestures to the compositor.
+                    for slot in slots.iter_mut() {
+                        if slot.reported_id != -1 && slot.reported_id != slot.tracking_id {
+                            send_touch_event(sender, TouchEventType::Up, slot, slot.reported_id);
+                            slot.reported_id = -1;
                         }
-                    } else {
-                        println!("Touch move x: {}, y: {}", slotA.x, slotA.y);
-                        sender.send(WindowEvent::Scroll(TypedPoint2D((slotA.x - last_x) as f32, (slotA.y - last_y) as f32),
-                                                        TypedPoint2D(slotA.x, slotA.y))).ok().unwrap();
-                        last_x = slotA.x;
-                        last_y = slotA.y;
-                        if touch_count >= 2 {
-                            let slotB = &slots[1];
-                            let cur_dist = dist(slotA.x, slotB.x, slotA.y, slotB.y);
-                            println!("Zooming {} {} {} {}", cur_dist, last_dist, screen_dist, ((screen_dist + (cur_dist - last_dist))/screen_dist));
-                            sender.send(WindowEvent::Zoom((screen_dist + (cur_dist - last_dist))/screen_dist)).ok().unwrap();
-                            last_dist = cur_dist;
+                        if slot.tracking_id != -1 {
+                            if slot.reported_id == -1 {
+                                send_touch_event(sender, TouchEventType::Down, slot,
+                                                 slot.tracking_id);
+                                slot.reported_id = slot.tracking_id;
+                            } else if slot.moved {
+                                send_touch_event(sender, TouchEventType::Move, slot,
+                                                 slot.tracking_id);
+                            }
                         }
+                        slot.moved = false;
                     }
                 },
                 (EV_SYN, _) => println!("Unknown SYN code {}", event.code),
@@ -211,21 +185,13 @@ fn read_input_device(device_path: &Path,
                 (EV_ABS, ABS_MT_ORIENTATION) => (),
                 (EV_ABS, ABS_MT_POSITION_X) => {
                     slots[current_slot].x = event.value - x_info.minimum;
+                    slots[current_slot].moved = true;
                 },
                 (EV_ABS, ABS_MT_POSITION_Y) => {
                     slots[current_slot].y = event.value - y_info.minimum;
+                    slots[current_slot].moved = true;
                 },
                 (EV_ABS, ABS_MT_TRACKING_ID) => {
-                    let current_id = slots[current_slot].tracking_id;
-                    if current_id != event.value &&
-                       (current_id == -1 || event.value == -1) {
-                        tracking_updated = true;
-                        if event.value == -1 {
-                            touch_count -= 1;
-                        } else {
-                            touch_count += 1;
-                        }
-                    }
                     slots[current_slot].tracking_id = event.value;
                 },
                 (EV_ABS, _) => println!("Unknown ABS code {}", event.code),
@@ -42,7 +42,7 @@ use net::image_cache_task::ImageCacheTask;
 #[cfg(not(test))]
 use net::storage_task::StorageTaskFactory;
 #[cfg(not(test))]
-use net::resource_task::new_resource_task;
+use net::resource_task::{new_resource_task, ProtocolRegistry};
 #[cfg(not(test))]
 use gfx::font_cache_task::FontCacheTask;
 #[cfg(not(test))]
@@ -68,8 +68,10 @@ pub struct Browser<Window> {
 }
 
 impl<Window> Browser<Window> where Window: WindowMethods + 'static {
+    /// Starts Servo. URLs with a scheme registered in `protocols` are loaded by the
+    /// embedder's handler instead of the built-in loaders.
     #[cfg(not(test))]
-    pub fn new(window: Option<Rc<Window>>) -> Browser<Window> {
+    pub fn new(window: Option<Rc<Window>>, protocols: ProtocolRegistry) -> Browser<Window> {
         ::util::opts::set_experimental_enabled(opts::get().enable_experimental);
         let opts = opts::get();
         RegisterBindings::RegisterProxyHandlers();
@@ -93,7 +95,7 @@ impl<Window> Browser<Window> where Window: WindowMethods + 'static {
             .spawn(move || {
             let opts = &opts_clone;
             // Create a Servo instance.
-            let resource_task = new_resource_task(opts.user_agent.clone());
+            let resource_task = new_resource_task(opts.user_agent.clone(), protocols);
             // If we are emitting an output file, then we need to block on
             // image load or we risk emitting an output file missing the
             // image.
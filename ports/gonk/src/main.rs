@@ -19,11 +19,13 @@ extern crate compositing;
 extern crate geom;
 extern crate libc;
 extern crate msg;
+extern crate net;
 extern crate gleam;
 extern crate layers;
 extern crate egl;
 
 use util::opts;
+use net::resource_task::ProtocolRegistry;
 use servo::Browser;
 use compositing::windowing::WindowEvent;
 
@@ -46,7 +48,7 @@ fn main() {
         };
 
         let mut browser = BrowserWrapper {
-            browser: Browser::new(window.clone()),
+            browser: Browser::new(window.clone(), ProtocolRegistry::new()),
         };
 
         match window {
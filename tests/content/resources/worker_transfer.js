@@ -0,0 +1,3 @@
+onmessage = function(e) {
+  e.ports[0].postMessage("from worker: " + e.data);
+};